@@ -16,7 +16,7 @@ async fn main() -> Result<()> {
     setup_test_files()?;
     
     // 2. 配置SSH连接
-    let mut manager = AnsibleManager::new();
+    let manager = AnsibleManager::new();
     
     // 添加目标主机（请修改为您的实际SSH服务器信息）
     // manager.add_host(
@@ -43,7 +43,7 @@ async fn main() -> Result<()> {
         "179.10.18.10",
     ];
 
-    let _ = hosts.iter().for_each(|&host| {
+    hosts.iter().for_each(|&host| {
         manager.add_host(format!("test-server-{}", host),
             AnsibleManager::host_builder()
             .hostname(host)
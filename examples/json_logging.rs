@@ -0,0 +1,47 @@
+use rs_ansible::{AnsibleManager, HostConfig, Playbook, Task, TaskExecutor};
+
+/// 按主机输出结构化 JSON 日志
+///
+/// 每台主机的操作位于 `host` span（字段 `host`、`attempt`）中，Playbook 运行时外层还有
+/// `playbook` 与 `task` span。使用 JSON 格式输出后，可以按字段过滤单台主机的全部日志：
+///
+/// ```bash
+/// cargo run --example json_logging 2> run.log
+/// jq 'select(.spans | any(.host == "web-1"))' run.log
+/// ```
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .with_current_span(true) // 当前 span 的字段（host / task）
+        .with_span_list(true)    // 从 playbook 到 host 的完整 span 链
+        .flatten_event(true)     // 事件字段（如 exit_code、elapsed_ms）提升到顶层
+        .with_writer(std::io::stderr)
+        .init();
+
+    let manager = AnsibleManager::new();
+    for (name, ip) in [("web-1", "192.168.1.101"), ("web-2", "192.168.1.102")] {
+        manager.add_host(
+            name.to_string(),
+            HostConfig {
+                hostname: ip.to_string(),
+                username: "admin".to_string(),
+                private_key_path: Some("~/.ssh/id_ed25519".to_string()),
+                ..Default::default()
+            },
+        );
+    }
+
+    let playbook = Playbook::new("json logging demo")
+        .add_task(Task::ping("check connectivity"))
+        .add_task(Task::command("uptime", "uptime"));
+
+    // 注意: 需要可连接的真实主机；连接失败的日志同样带有 host 字段
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await?;
+    println!("overall success: {}", result.overall_success);
+    Ok(())
+}
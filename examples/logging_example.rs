@@ -1,6 +1,5 @@
 use rs_ansible::{AnsibleManager, UserOptions, UserState, TemplateOptions, HostConfig};
 use std::collections::HashMap;
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -19,7 +18,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("=== RS-Ansible 日志示例 ===\n");
 
-    let mut manager = AnsibleManager::new();
+    let manager = AnsibleManager::new();
     
     // 添加主机
     let host_config = HostConfig {
@@ -29,6 +28,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         password: Some("password".to_string()),
         private_key_path: None,
         passphrase: None,
+        ..Default::default()
     };
     manager.add_host("web-server".to_string(), host_config);
 
@@ -76,6 +76,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         group: Some("root".to_string()),
         backup: true,
         validate: None,
+
+        keep_temp_on_failure: false,
+        reload_command: None,
+        rollback_on_failure: false,
+        validate_render_first: true,
+        lookup_dirs: Vec::new(),
+        allow_pipe_lookup: false,
     };
 
     // 注意: 实际使用时需要连接到真实主机
@@ -0,0 +1,47 @@
+use metrics_exporter_prometheus::PrometheusBuilder;
+use rs_ansible::metrics::{set_label_mode, LabelMode};
+use rs_ansible::{AnsibleManager, HostConfig, Playbook, Task, TaskExecutor};
+
+/// 将执行指标导出为 Prometheus 文本格式
+///
+/// 需要启用 `metrics` feature：
+///
+/// ```bash
+/// cargo run --example prometheus_metrics --features metrics
+/// ```
+///
+/// 长期运行的服务通常会通过 HTTP 暴露 `handle.render()` 的结果供 Prometheus 抓取。
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+
+    let handle = PrometheusBuilder::new().install_recorder()?;
+
+    // 主机较多时限制标签基数：超过 50 台后的主机归入 "other"
+    set_label_mode(LabelMode::Capped(50));
+
+    let manager = AnsibleManager::new();
+    for (name, ip) in [("web-1", "192.168.1.101"), ("web-2", "192.168.1.102")] {
+        manager.add_host(
+            name.to_string(),
+            HostConfig {
+                hostname: ip.to_string(),
+                username: "admin".to_string(),
+                private_key_path: Some("~/.ssh/id_ed25519".to_string()),
+                ..Default::default()
+            },
+        );
+    }
+
+    let playbook = Playbook::new("metrics demo")
+        .add_task(Task::ping("check connectivity"))
+        .add_task(Task::command("uptime", "uptime"));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await?;
+    println!("Playbook success: {}\n", result.overall_success);
+
+    // 输出示例：
+    // rs_ansible_connections_opened_total{host="192.168.1.101"} 2
+    // rs_ansible_task_duration_seconds{task="uptime",quantile="0.5"} 0.21
+    println!("{}", handle.render());
+    Ok(())
+}
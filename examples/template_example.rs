@@ -1,10 +1,10 @@
-use rs_ansible::{AnsibleManager, TemplateOptions};
+use rs_ansible::{AnsibleManager, HostOutcome, TemplateOptions};
 use std::collections::HashMap;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 初始化管理器
-    let mut manager = AnsibleManager::new();
+    let manager = AnsibleManager::new();
     
     // 添加主机
     let hosts = [
@@ -20,7 +20,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "179.10.18.10",
     ];
 
-    let _ = hosts.iter().for_each(|&host| {
+    hosts.iter().for_each(|&host| {
         manager.add_host(format!("test-server-{}", host),
             AnsibleManager::host_builder()
             .hostname(host)
@@ -41,6 +41,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[allow(dead_code)]
 async fn deploy_nginx_config(manager: &AnsibleManager) -> Result<(), Box<dyn std::error::Error>> {
     println!("部署 Nginx 配置...");
     
@@ -61,7 +62,13 @@ async fn deploy_nginx_config(manager: &AnsibleManager) -> Result<(), Box<dyn std
         owner: Some("root".to_string()),
         group: Some("root".to_string()),
         backup: true,
-        validate: Some("nginx -t -c %s".to_string()),
+        validate: Some("nginx -t -c {{ path }}".to_string()),
+        keep_temp_on_failure: false,
+        reload_command: Some("systemctl reload nginx".to_string()), // 仅在文件变化时执行
+        rollback_on_failure: true, // reload 失败时恢复原配置
+        validate_render_first: true,
+        lookup_dirs: Vec::new(),
+        allow_pipe_lookup: false,
     };
     
 
@@ -74,7 +81,7 @@ async fn deploy_nginx_config(manager: &AnsibleManager) -> Result<(), Box<dyn std
     // 检查结果
     if let Some(result) = batch_result.results.get("test-server-1") {
         match result {
-            Ok(template_result) => {
+            HostOutcome::Ok { value: template_result, .. } => {
                 println!("✓ Nginx 配置部署成功");
                 if template_result.changed {
                     println!("  配置已更新");
@@ -85,9 +92,10 @@ async fn deploy_nginx_config(manager: &AnsibleManager) -> Result<(), Box<dyn std
                     println!("  配置未变更");
                 }
             }
-            Err(e) => {
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => {
                 println!("✗ 部署失败: {}", e);
             }
+            HostOutcome::Skipped(reason) => println!("- 已跳过: {:?}", reason),
         }
     }
     
@@ -124,6 +132,13 @@ async fn deploy_app_config(manager: &AnsibleManager) -> Result<(), Box<dyn std::
         group: Some("root".to_string()),
         backup: true,
         validate: None, // 可以添加配置验证命令
+
+        keep_temp_on_failure: false,
+        reload_command: None,
+        rollback_on_failure: false,
+        validate_render_first: true,
+        lookup_dirs: Vec::new(),
+        allow_pipe_lookup: false,
     };
 
     let hosts = [
@@ -149,7 +164,7 @@ async fn deploy_app_config(manager: &AnsibleManager) -> Result<(), Box<dyn std::
     // 检查结果
     if let Some(result) = batch_result.results.get("test-server-179.10.18.1") {
         match result {
-            Ok(template_result) => {
+            HostOutcome::Ok { value: template_result, .. } => {
                 println!("✓ 应用配置部署成功");
                 if template_result.changed {
                     println!("  配置已更新");
@@ -157,9 +172,10 @@ async fn deploy_app_config(manager: &AnsibleManager) -> Result<(), Box<dyn std::
                     println!("  配置未变更");
                 }
             }
-            Err(e) => {
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => {
                 println!("✗ 部署失败: {}", e);
             }
+            HostOutcome::Skipped(reason) => println!("- 已跳过: {:?}", reason),
         }
     }
     
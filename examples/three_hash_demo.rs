@@ -1,4 +1,4 @@
-use rs_ansible::{AnsibleManager, FileCopyOptions, Result};
+use rs_ansible::{AnsibleManager, FileCopyOptions, HostOutcome, Result};
 use std::fs;
 use std::io::Write;
 
@@ -23,7 +23,7 @@ async fn main() -> Result<()> {
     setup_test_files()?;
 
     // 配置SSH连接
-    let mut manager = AnsibleManager::new();
+    let manager = AnsibleManager::new();
     
     // 添加测试主机（请修改为您的实际SSH服务器信息）
     println!("📋 配置SSH连接...");
@@ -52,7 +52,7 @@ async fn main() -> Result<()> {
     let ping_result = manager.ping_all().await;
     println!("✓ 连接成功率: {:.0}%\n", ping_result.success_rate() * 100.0);
 
-    if ping_result.failed.len() > 0 {
+    if !ping_result.failed.is_empty() {
         println!("⚠️  部分主机连接失败: {:?}", ping_result.failed);
         println!("继续使用成功的主机进行演示...\n");
     }
@@ -87,12 +87,13 @@ async fn main() -> Result<()> {
     println!("\n结果：");
     for (host, res) in &result1.results {
         match res {
-            Ok(file_result) => {
+            HostOutcome::Ok { value: file_result, .. } => {
                 println!("  ✅ {} - 传输成功", host);
                 println!("     传输字节: {}", file_result.bytes_transferred);
                 println!("     消息: {}", file_result.message);
             }
-            Err(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Skipped(reason) => println!("  ⏭️  {} - 跳过: {:?}", host, reason),
         }
     }
 
@@ -111,7 +112,7 @@ async fn main() -> Result<()> {
     println!("\n结果：");
     for (host, res) in &result2.results {
         match res {
-            Ok(file_result) => {
+            HostOutcome::Ok { value: file_result, .. } => {
                 if file_result.bytes_transferred == 0 {
                     println!("  ✅ {} - 跳过传输（文件未改变）", host);
                 } else {
@@ -119,7 +120,8 @@ async fn main() -> Result<()> {
                 }
                 println!("     消息: {}", file_result.message);
             }
-            Err(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Skipped(reason) => println!("  ⏭️  {} - 跳过: {:?}", host, reason),
         }
     }
 
@@ -147,7 +149,7 @@ async fn main() -> Result<()> {
     println!("\n结果：");
     for (host, res) in &result3.results {
         match res {
-            Ok(file_result) => {
+            HostOutcome::Ok { value: file_result, .. } => {
                 if file_result.bytes_transferred > 0 {
                     println!("  ✅ {} - 检测到变化，重新传输", host);
                     println!("     传输字节: {}", file_result.bytes_transferred);
@@ -156,7 +158,8 @@ async fn main() -> Result<()> {
                 }
                 println!("     消息: {}", file_result.message);
             }
-            Err(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Skipped(reason) => println!("  ⏭️  {} - 跳过: {:?}", host, reason),
         }
     }
 
@@ -182,12 +185,13 @@ async fn main() -> Result<()> {
     println!("\n结果：");
     for (host, res) in &result4.results {
         match res {
-            Ok(file_result) => {
+            HostOutcome::Ok { value: file_result, .. } => {
                 println!("  ✅ {} - 传输成功（未检查幂等性）", host);
                 println!("     传输字节: {}", file_result.bytes_transferred);
                 println!("     消息: {}", file_result.message);
             }
-            Err(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => println!("  ❌ {} - 失败: {}", host, e),
+            HostOutcome::Skipped(reason) => println!("  ⏭️  {} - 跳过: {:?}", host, reason),
         }
     }
 
@@ -52,7 +52,7 @@ fn demo_log_levels() {
 async fn demo_ssh_operations() -> Result<()> {
     info!("📋 步骤2: 演示SSH操作中的日志");
     
-    let mut manager = AnsibleManager::new();
+    let manager = AnsibleManager::new();
     
     debug!("创建测试主机配置...");
     
@@ -0,0 +1,168 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 单次读取的最大块大小，保证限速平滑
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 基于令牌桶的带宽限制器（线程安全，可在多个并发传输间共享）
+///
+/// 令牌以 `bytes_per_sec` 的速率补充，桶容量为一秒的配额；
+/// 令牌不足时允许透支，由调用方按透支量休眠，从而使长期速率收敛到设定值。
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    bytes_per_sec: u64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl BandwidthLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            // 初始为空桶，避免开始时的突发流量
+            state: Mutex::new(BucketState {
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// 合适的单次传输块大小（约 1/10 秒的配额）
+    fn chunk_size(&self) -> usize {
+        ((self.bytes_per_sec / 10) as usize).clamp(1024, MAX_CHUNK_SIZE)
+    }
+
+    /// 消耗令牌，返回需要等待的时间
+    fn reserve(&self, bytes: usize) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let mut state = self.state.lock().expect("bandwidth limiter poisoned");
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * rate).min(rate);
+        state.last_refill = now;
+        state.tokens -= bytes as f64;
+
+        if state.tokens < 0.0 {
+            Duration::from_secs_f64(-state.tokens / rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// 阻塞直到 `bytes` 字节的配额可用
+    pub fn consume(&self, bytes: usize) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// [`Self::consume`] 的异步版本，等待期间不占用线程
+    pub async fn consume_async(&self, bytes: usize) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// 同时受多个限制器约束时的单次传输块大小（取最小值）
+pub(crate) fn chunk_size(limiters: &[Arc<BandwidthLimiter>]) -> usize {
+    limiters
+        .iter()
+        .map(|l| l.chunk_size())
+        .min()
+        .unwrap_or(MAX_CHUNK_SIZE)
+}
+
+/// 按一个或多个带宽限制器节流的 Reader（用于上传时的分块读取）
+pub struct ThrottledReader<R> {
+    inner: R,
+    limiters: Vec<Arc<BandwidthLimiter>>,
+    chunk_size: usize,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    pub fn new(inner: R, limiters: Vec<Arc<BandwidthLimiter>>) -> Self {
+        let chunk_size = chunk_size(&limiters);
+        Self {
+            inner,
+            limiters,
+            chunk_size,
+        }
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.chunk_size);
+        let n = self.inner.read(&mut buf[..len])?;
+        if n > 0 {
+            for limiter in &self.limiters {
+                limiter.consume(n);
+            }
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttled_copy_rate_within_tolerance() {
+        let rate = 200 * 1024;
+        let data = vec![7u8; 100 * 1024];
+        let limiter = Arc::new(BandwidthLimiter::new(rate));
+
+        let mut reader = ThrottledReader::new(std::io::Cursor::new(data.clone()), vec![limiter]);
+        let mut writer = Vec::new();
+        let started = Instant::now();
+        let copied = std::io::copy(&mut reader, &mut writer).unwrap();
+        let elapsed = started.elapsed().as_secs_f64();
+
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(writer, data);
+        // 期望约 0.5 秒
+        let effective = copied as f64 / elapsed;
+        assert!(
+            effective <= rate as f64 * 1.2 && effective >= rate as f64 * 0.6,
+            "effective rate {:.0} B/s outside tolerance (elapsed {:.3}s)",
+            effective,
+            elapsed
+        );
+    }
+
+    #[test]
+    fn test_shared_limiter_bounds_aggregate_rate() {
+        let rate = 200 * 1024;
+        let shared = Arc::new(BandwidthLimiter::new(rate));
+        let started = Instant::now();
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let mut reader = ThrottledReader::new(std::io::Cursor::new(vec![0u8; 50 * 1024]), vec![shared]);
+                    std::io::copy(&mut reader, &mut std::io::sink()).unwrap()
+                })
+            })
+            .collect();
+        let total: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        // 两个传输共享同一限额，总耗时约 0.5 秒
+        let effective = total as f64 / started.elapsed().as_secs_f64();
+        assert!(effective <= rate as f64 * 1.2, "aggregate rate {:.0} B/s exceeds limit", effective);
+    }
+}
@@ -0,0 +1,568 @@
+use crate::error::AnsibleError;
+use chrono::Utc;
+use crate::executor::{Playbook, PlaybookResult, Task, TaskResult};
+use crate::manager::{BatchResult, HostOutcome, SkipReason};
+use crate::types::{
+    BlockInFileResult, CommandResult, EnsureDirsResult, FileSetResult, FileTransferResult, HealthProbeResult,
+    KernelModuleResult, LogRotateResult, RepoResult, SshKeypairResult, SystemInfo, TemplateResult, UserResult,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// 主机在某个任务上的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostStatus {
+    Ok,
+    Failed,
+    Unreachable, // 连接阶段失败（无法连接、认证失败、连接超时）
+    Skipped,
+}
+
+/// 单台主机在某个任务上的结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskOutcome {
+    pub status: HostStatus,
+    pub error: Option<String>,       // 失败原因
+    pub exit_code: Option<i32>,      // 命令类任务的退出码
+    pub changed: Option<bool>,       // 任务是否修改了主机状态（结果中无此信息时为 None）
+    pub stderr: Option<String>,      // 命令类任务的标准错误输出（非空时）
+    pub duration: Option<Duration>,  // 该主机上的执行耗时
+    pub skip_reason: Option<SkipReason>, // 跳过的原因（仅 Skipped）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,        // 差异模式下文件类任务的统一 diff
+}
+
+impl TaskOutcome {
+    pub fn ok() -> Self {
+        Self::with_status(HostStatus::Ok)
+    }
+
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Self::with_status(HostStatus::Failed)
+        }
+    }
+
+    pub fn unreachable(error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Self::with_status(HostStatus::Unreachable)
+        }
+    }
+
+    pub fn skipped(reason: SkipReason) -> Self {
+        Self {
+            skip_reason: Some(reason),
+            ..Self::with_status(HostStatus::Skipped)
+        }
+    }
+
+    fn with_status(status: HostStatus) -> Self {
+        Self {
+            status,
+            error: None,
+            exit_code: None,
+            changed: None,
+            stderr: None,
+            duration: None,
+            skip_reason: None,
+            diff: None,
+        }
+    }
+}
+
+/// 执行生命周期回调（用于对接 Slack、部署看板等）
+///
+/// 所有方法默认为空实现，按需覆盖即可。回调中的错误与 panic 会被捕获并记录日志，不会中断执行。
+pub trait ExecutionCallback: Send + Sync {
+    fn on_playbook_start(&self, _playbook: &Playbook) -> Result<(), AnsibleError> {
+        Ok(())
+    }
+
+    fn on_task_start(&self, _task: &Task) -> Result<(), AnsibleError> {
+        Ok(())
+    }
+
+    fn on_host_result(&self, _host: &str, _task: &Task, _outcome: &TaskOutcome) -> Result<(), AnsibleError> {
+        Ok(())
+    }
+
+    /// 某台主机连接失败、即将重试时调用（`attempt` 为即将进行的第几次尝试）
+    fn on_host_retry(&self, _host: &str, _task: &Task, _attempt: u32, _error: &str) -> Result<(), AnsibleError> {
+        Ok(())
+    }
+
+    /// 任务整体执行出错（而非单台主机失败）时调用
+    fn on_task_error(&self, _task: &Task, _error: &AnsibleError) -> Result<(), AnsibleError> {
+        Ok(())
+    }
+
+    fn on_playbook_end(&self, _result: &PlaybookResult) -> Result<(), AnsibleError> {
+        Ok(())
+    }
+}
+
+/// 依次调用所有回调，捕获其中的错误与 panic
+pub(crate) fn dispatch<F>(callbacks: &[Arc<dyn ExecutionCallback>], event: &str, f: F)
+where
+    F: Fn(&dyn ExecutionCallback) -> Result<(), AnsibleError>,
+{
+    for callback in callbacks {
+        match catch_unwind(AssertUnwindSafe(|| f(callback.as_ref()))) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!(event, "Execution callback failed: {}", e),
+            Err(_) => error!(event, "Execution callback panicked"),
+        }
+    }
+}
+
+/// 从单台主机的结果值中提取回调关心的细节（退出码、stderr、diff），默认都没有
+pub(crate) trait OutcomeDetails {
+    fn exit_code(&self) -> Option<i32> {
+        None
+    }
+
+    fn stderr(&self) -> Option<&str> {
+        None
+    }
+
+    fn diff(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl OutcomeDetails for CommandResult {
+    fn exit_code(&self) -> Option<i32> {
+        Some(self.exit_code)
+    }
+
+    fn stderr(&self) -> Option<&str> {
+        Some(&self.stderr)
+    }
+}
+
+impl OutcomeDetails for FileTransferResult {
+    fn diff(&self) -> Option<&str> {
+        self.diff.as_deref()
+    }
+}
+
+impl OutcomeDetails for TemplateResult {
+    fn diff(&self) -> Option<&str> {
+        self.diff.as_deref()
+    }
+}
+
+impl OutcomeDetails for BlockInFileResult {
+    fn diff(&self) -> Option<&str> {
+        self.diff.as_deref()
+    }
+}
+
+impl OutcomeDetails for Vec<CommandResult> {}
+impl OutcomeDetails for SystemInfo {}
+impl OutcomeDetails for bool {}
+impl OutcomeDetails for UserResult {}
+impl OutcomeDetails for LogRotateResult {}
+impl OutcomeDetails for EnsureDirsResult {}
+impl OutcomeDetails for SshKeypairResult {}
+impl OutcomeDetails for RepoResult {}
+impl OutcomeDetails for KernelModuleResult {}
+impl OutcomeDetails for FileSetResult {}
+impl OutcomeDetails for Vec<HealthProbeResult> {}
+
+/// 将任务结果拆分为每台主机的结果（按 成功、失败/不可达、跳过 的顺序）
+///
+/// 退出码、stderr 与 diff 直接取自各主机的结果值（见 [`OutcomeDetails`]）；
+/// changed 只对会修改主机状态的任务给出（见 [`TaskResult::reports_changes`]）。
+pub(crate) fn host_outcomes(result: &TaskResult) -> Vec<(String, TaskOutcome)> {
+    let reports_changes = result.reports_changes();
+    match result {
+        TaskResult::Command(r) => batch_outcomes(r, reports_changes),
+        TaskResult::Commands(r) => batch_outcomes(r, reports_changes),
+        TaskResult::CopyFile(r) => batch_outcomes(r, reports_changes),
+        TaskResult::Fetch(r) => batch_outcomes(r, reports_changes),
+        TaskResult::SystemInfo(r) => batch_outcomes(r, reports_changes),
+        TaskResult::Ping(r) => batch_outcomes(r, reports_changes),
+        TaskResult::User(r) => batch_outcomes(r, reports_changes),
+        TaskResult::Template(r) => batch_outcomes(r, reports_changes),
+        TaskResult::DnsConfig(r) => batch_outcomes(r, reports_changes),
+        TaskResult::LogRotate(r) => batch_outcomes(r, reports_changes),
+        TaskResult::Permissions(r) => batch_outcomes(r, reports_changes),
+        TaskResult::EnsureDirs(r) => batch_outcomes(r, reports_changes),
+        TaskResult::SshKeypair(r) => batch_outcomes(r, reports_changes),
+        TaskResult::Repo(r) => batch_outcomes(r, reports_changes),
+        TaskResult::KernelModule(r) => batch_outcomes(r, reports_changes),
+        TaskResult::FileSet(r) => batch_outcomes(r, reports_changes),
+        TaskResult::BlockInFile(r) => batch_outcomes(r, reports_changes),
+        TaskResult::HealthCheck(r) => batch_outcomes(r, reports_changes),
+        TaskResult::Cgroup(r) => batch_outcomes(r, reports_changes),
+    }
+}
+
+fn batch_outcomes<T: OutcomeDetails>(batch: &BatchResult<T>, reports_changes: bool) -> Vec<(String, TaskOutcome)> {
+    let with_duration = |host: &String, mut outcome: TaskOutcome| {
+        outcome.duration = batch.durations.get(host).copied();
+        (host.clone(), outcome)
+    };
+
+    let mut outcomes: Vec<(String, TaskOutcome)> = batch
+        .successful
+        .iter()
+        .map(|host| {
+            let mut outcome = TaskOutcome::ok();
+            if let Some(HostOutcome::Ok { value, changed }) = batch.results.get(host) {
+                outcome.exit_code = value.exit_code();
+                outcome.stderr = value.stderr().filter(|e| !e.trim().is_empty()).map(str::to_string);
+                outcome.diff = value.diff().filter(|d| !d.is_empty()).map(str::to_string);
+                outcome.changed = Some(*changed).filter(|_| reports_changes);
+            }
+            with_duration(host, outcome)
+        })
+        .collect();
+    outcomes.extend(batch.failed.iter().filter_map(|host| {
+        let outcome = match batch.results.get(host)? {
+            HostOutcome::Unreachable(e) => TaskOutcome::unreachable(e.to_string()),
+            HostOutcome::Failed(e) => TaskOutcome::failed(e.to_string()),
+            _ => return None,
+        };
+        Some(with_duration(host, outcome))
+    }));
+    outcomes.extend(batch.skipped.iter().map(|host| {
+        let reason = match batch.results.get(host) {
+            Some(HostOutcome::Skipped(reason)) => *reason,
+            _ => SkipReason::Condition,
+        };
+        (host.clone(), TaskOutcome::skipped(reason))
+    }));
+    outcomes
+}
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Default, Clone, Copy)]
+struct HostStats {
+    ok: usize,
+    failed: usize,
+    unreachable: usize,
+    skipped: usize,
+}
+
+/// 控制台输出：每个任务的主机结果，以及结束时的 PLAY RECAP 汇总表
+pub struct ConsoleReporter {
+    writer: Mutex<Box<dyn Write + Send>>,
+    color: bool,
+    stats: Mutex<BTreeMap<String, HostStats>>,
+}
+
+impl ConsoleReporter {
+    /// 输出到标准输出（带颜色）
+    pub fn new() -> Self {
+        Self::to_writer(Box::new(std::io::stdout()), true)
+    }
+
+    /// 输出到任意 writer
+    pub fn to_writer(writer: Box<dyn Write + Send>, color: bool) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            color,
+            stats: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn write_line(&self, line: &str) -> Result<(), AnsibleError> {
+        let mut writer = self.writer.lock().expect("console writer poisoned");
+        writeln!(writer, "{}", line).map_err(|e| AnsibleError::IoError(e.to_string()))
+    }
+}
+
+impl Default for ConsoleReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionCallback for ConsoleReporter {
+    fn on_playbook_start(&self, playbook: &Playbook) -> Result<(), AnsibleError> {
+        self.stats.lock().expect("console stats poisoned").clear();
+        self.write_line(&format!("PLAY [{}] {}", playbook.name, "*".repeat(40)))
+    }
+
+    fn on_task_start(&self, task: &Task) -> Result<(), AnsibleError> {
+        self.write_line(&format!("\nTASK [{}] {}", task.name, "*".repeat(40)))
+    }
+
+    fn on_host_result(&self, host: &str, _task: &Task, outcome: &TaskOutcome) -> Result<(), AnsibleError> {
+        {
+            let mut stats = self.stats.lock().expect("console stats poisoned");
+            let entry = stats.entry(host.to_string()).or_default();
+            match outcome.status {
+                HostStatus::Ok => entry.ok += 1,
+                HostStatus::Failed => entry.failed += 1,
+                HostStatus::Unreachable => entry.unreachable += 1,
+                // 因之前失败而未执行不算作跳过，失败已计入 failed
+                HostStatus::Skipped if outcome.skip_reason == Some(SkipReason::PreviousFailure) => {}
+                HostStatus::Skipped => entry.skipped += 1,
+            }
+        }
+
+        let line = match outcome.status {
+            HostStatus::Ok if outcome.changed == Some(true) => self.paint(YELLOW, &format!("changed: [{}]", host)),
+            HostStatus::Ok => self.paint(GREEN, &format!("ok: [{}]", host)),
+            HostStatus::Failed => self.paint(
+                RED,
+                &format!("failed: [{}] => {}", host, outcome.error.as_deref().unwrap_or_default()),
+            ),
+            HostStatus::Unreachable => self.paint(
+                RED,
+                &format!("unreachable: [{}] => {}", host, outcome.error.as_deref().unwrap_or_default()),
+            ),
+            HostStatus::Skipped => match outcome.skip_reason {
+                Some(reason) => self.paint(CYAN, &format!("skipping: [{}] ({})", host, reason)),
+                None => self.paint(CYAN, &format!("skipping: [{}]", host)),
+            },
+        };
+        self.write_line(&line)?;
+        for diff_line in outcome.diff.iter().flat_map(|diff| diff.lines()) {
+            let diff_line = match diff_line.as_bytes().first() {
+                Some(b'+') if !diff_line.starts_with("+++") => self.paint(GREEN, diff_line),
+                Some(b'-') if !diff_line.starts_with("---") => self.paint(RED, diff_line),
+                _ if diff_line.starts_with("@@") => self.paint(CYAN, diff_line),
+                _ => diff_line.to_string(),
+            };
+            self.write_line(&diff_line)?;
+        }
+        Ok(())
+    }
+
+    fn on_task_error(&self, task: &Task, error: &AnsibleError) -> Result<(), AnsibleError> {
+        self.write_line(&self.paint(RED, &format!("error: task '{}' aborted => {}", task.name, error)))
+    }
+
+    fn on_playbook_end(&self, result: &PlaybookResult) -> Result<(), AnsibleError> {
+        self.write_line(&format!("\nPLAY RECAP {}", "*".repeat(40)))?;
+        if let Some(ref limit) = result.limit {
+            self.write_line(&format!("(limited to: {})", limit))?;
+        }
+        if !result.resume_skipped_tasks.is_empty() {
+            self.write_line(&format!(
+                "(resumed, not run: {})",
+                result.resume_skipped_tasks.join(", ")
+            ))?;
+        }
+        if !result.step_skipped_tasks.is_empty() {
+            self.write_line(&format!("(skipped in step mode: {})", result.step_skipped_tasks.join(", ")))?;
+        }
+        if let Some(ref task) = result.aborted_at {
+            self.write_line(&format!("(aborted before task '{}')", task))?;
+        }
+        let stats = self.stats.lock().expect("console stats poisoned").clone();
+        for (host, stats) in stats {
+            let color = if stats.failed > 0 || stats.unreachable > 0 { RED } else { GREEN };
+            self.write_line(&format!(
+                "{:<30} : ok={:<4} failed={:<4} unreachable={:<4} skipped={}",
+                self.paint(color, &host),
+                stats.ok,
+                stats.failed,
+                stats.unreachable,
+                stats.skipped
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// 以 JSON Lines 格式将事件追加写入文件（每行一个事件）
+#[deprecated(note = "use RunLogger, which writes a documented schema and flushes after every event")]
+pub struct JsonLinesWriter {
+    file: Mutex<std::fs::File>,
+}
+
+#[allow(deprecated)]
+impl JsonLinesWriter {
+    pub fn create<P: AsRef<std::path::Path>>(path: P) -> Result<Self, AnsibleError> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to open event log: {}", e)))?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    fn write_event(&self, event: &str, mut fields: serde_json::Value) -> Result<(), AnsibleError> {
+        fields["event"] = serde_json::Value::from(event);
+        fields["timestamp"] = serde_json::Value::from(Utc::now().to_rfc3339());
+        let line = serde_json::to_string(&fields)
+            .map_err(|e| AnsibleError::IoError(format!("Failed to serialize event: {}", e)))?;
+        let mut file = self.file.lock().expect("event log poisoned");
+        writeln!(file, "{}", line).map_err(|e| AnsibleError::IoError(e.to_string()))
+    }
+}
+
+#[allow(deprecated)]
+impl ExecutionCallback for JsonLinesWriter {
+    fn on_playbook_start(&self, playbook: &Playbook) -> Result<(), AnsibleError> {
+        self.write_event(
+            "playbook_start",
+            serde_json::json!({ "playbook": playbook.name, "tasks": playbook.tasks.len() }),
+        )
+    }
+
+    fn on_task_start(&self, task: &Task) -> Result<(), AnsibleError> {
+        self.write_event("task_start", serde_json::json!({ "task": task.name }))
+    }
+
+    fn on_host_result(&self, host: &str, task: &Task, outcome: &TaskOutcome) -> Result<(), AnsibleError> {
+        self.write_event(
+            "host_result",
+            serde_json::json!({ "host": host, "task": task.name, "outcome": outcome }),
+        )
+    }
+
+    fn on_task_error(&self, task: &Task, error: &AnsibleError) -> Result<(), AnsibleError> {
+        self.write_event("task_error", serde_json::json!({ "task": task.name, "error": error.to_string() }))
+    }
+
+    fn on_playbook_end(&self, result: &PlaybookResult) -> Result<(), AnsibleError> {
+        let mut failed_hosts: Vec<&String> = result.failed_hosts.iter().collect();
+        failed_hosts.sort();
+        self.write_event(
+            "playbook_end",
+            serde_json::json!({
+                "playbook": result.playbook_name,
+                "overall_success": result.overall_success,
+                "failed_hosts": failed_hosts,
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::TaskExecutor;
+    use crate::manager::AnsibleManager;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl ExecutionCallback for Recorder {
+        fn on_playbook_start(&self, playbook: &Playbook) -> Result<(), AnsibleError> {
+            self.events.lock().unwrap().push(format!("start:{}", playbook.name));
+            Ok(())
+        }
+
+        fn on_task_start(&self, task: &Task) -> Result<(), AnsibleError> {
+            self.events.lock().unwrap().push(format!("task:{}", task.name));
+            Ok(())
+        }
+
+        fn on_host_result(&self, host: &str, task: &Task, outcome: &TaskOutcome) -> Result<(), AnsibleError> {
+            let status = if outcome.status == HostStatus::Ok { "ok" } else { "failed" };
+            self.events.lock().unwrap().push(format!("{}:{}:{}", task.name, host, status));
+            Ok(())
+        }
+
+        fn on_playbook_end(&self, result: &PlaybookResult) -> Result<(), AnsibleError> {
+            self.events.lock().unwrap().push(format!("end:{}", result.overall_success));
+            Ok(())
+        }
+    }
+
+    struct Faulty;
+
+    impl ExecutionCallback for Faulty {
+        fn on_task_start(&self, _task: &Task) -> Result<(), AnsibleError> {
+            panic!("callback bug");
+        }
+
+        fn on_host_result(&self, _host: &str, _task: &Task, _outcome: &TaskOutcome) -> Result<(), AnsibleError> {
+            Err(AnsibleError::IoError("dashboard unreachable".to_string()))
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_callbacks_called_in_order_and_isolated() {
+        let manager = AnsibleManager::new();
+        let recorder = Arc::new(Recorder::default());
+        let output = SharedBuf::default();
+        let log_path = std::env::temp_dir().join(format!("rs_ansible_events_{}.jsonl", rand::random::<u32>()));
+
+        let executor = TaskExecutor::new(&manager)
+            .with_callback(Arc::new(Faulty))
+            .with_callback(recorder.clone())
+            .with_callback(Arc::new(ConsoleReporter::to_writer(Box::new(output.clone()), false)))
+            .with_callback(Arc::new(JsonLinesWriter::create(&log_path).unwrap()));
+
+        let playbook = Playbook::new("callbacks")
+            .add_task(Task::local_command("hello", "echo hi"))
+            .add_task(Task::local_command("bye", "echo bye"));
+        let result = executor.execute_playbook(&playbook).await.unwrap();
+        assert!(result.overall_success);
+
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec![
+                "start:callbacks",
+                "task:hello",
+                "hello:localhost:ok",
+                "task:bye",
+                "bye:localhost:ok",
+                "end:true",
+            ]
+        );
+
+        let console = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(console.contains("TASK [hello]"));
+        assert!(console.contains("ok: [localhost]"));
+        assert!(console.contains("PLAY RECAP"));
+        assert!(console.contains("ok=2"));
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&log_path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        std::fs::remove_file(&log_path).unwrap();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0]["event"], "playbook_start");
+        assert_eq!(lines[2]["outcome"]["status"], "ok");
+        assert_eq!(lines[5]["event"], "playbook_end");
+        assert_eq!(lines[5]["overall_success"], true);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::error::AnsibleError;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::info;
+
+/// 单次批量操作的执行选项
+#[derive(Debug, Clone, Default)]
+pub struct OperationOptions {
+    /// 覆盖管理器的 `max_concurrent_connections`
+    pub max_concurrency: Option<usize>,
+    /// 自适应并发控制（遇到限流/瞬时错误时自动降低并发）
+    pub adaptive: Option<AdaptiveConcurrency>,
+    /// 有效并发数变化事件的接收端
+    pub events: Option<UnboundedSender<ConcurrencyEvent>>,
+    /// 失败主机数达到该值后不再启动新的主机（已在执行的主机继续完成），未启动的主机记为跳过
+    pub max_failures: Option<usize>,
+    /// 取消后不再启动新的主机（已在执行的主机继续完成），未启动的主机记为跳过
+    pub cancellation: Option<CancellationToken>,
+    /// 在 `BatchResult::metrics` 中附带每台主机的连接耗时、通道数、命令数与传输字节数
+    pub host_metrics: bool,
+    /// 连接失败后即将重试的事件的接收端
+    pub retry_events: Option<UnboundedSender<RetryEvent>>,
+}
+
+impl OperationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = Some(max);
+        self
+    }
+
+    pub fn adaptive(mut self, adaptive: AdaptiveConcurrency) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    pub fn events(mut self, sender: UnboundedSender<ConcurrencyEvent>) -> Self {
+        self.events = Some(sender);
+        self
+    }
+
+    pub fn max_failures(mut self, max: usize) -> Self {
+        self.max_failures = Some(max);
+        self
+    }
+
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn host_metrics(mut self, enabled: bool) -> Self {
+        self.host_metrics = enabled;
+        self
+    }
+
+    pub fn retry_events(mut self, sender: UnboundedSender<RetryEvent>) -> Self {
+        self.retry_events = Some(sender);
+        self
+    }
+}
+
+/// 取消令牌：克隆共享同一状态，任一克隆调用 `cancel` 后所有克隆都处于已取消状态
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationState>,
+}
+
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 取消（可重复调用）
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 等待取消；已取消时立即返回
+    pub async fn cancelled(&self) {
+        loop {
+            // 先注册等待再检查状态，避免错过检查与等待之间发出的通知
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// 自适应并发参数
+///
+/// 在 `window` 时间窗口内出现 `error_threshold` 次瞬时错误时并发减半（不低于 `min_concurrency`），
+/// 之后每连续成功 `ramp_up_after` 次并发加一，直到恢复到上限。
+#[derive(Debug, Clone)]
+pub struct AdaptiveConcurrency {
+    pub error_threshold: usize,
+    pub window: Duration,
+    pub min_concurrency: usize,
+    pub ramp_up_after: usize,
+}
+
+impl Default for AdaptiveConcurrency {
+    fn default() -> Self {
+        Self {
+            error_threshold: 3,
+            window: Duration::from_secs(10),
+            min_concurrency: 1,
+            ramp_up_after: 5,
+        }
+    }
+}
+
+/// 有效并发数变化原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConcurrencyChange {
+    Initial,
+    BackedOff,
+    RampedUp,
+}
+
+/// 有效并发数变化事件
+#[derive(Debug, Clone, Serialize)]
+pub struct ConcurrencyEvent {
+    pub effective_concurrency: usize,
+    pub change: ConcurrencyChange,
+}
+
+/// 主机连接失败、即将重试的事件
+#[derive(Debug, Clone, Serialize)]
+pub struct RetryEvent {
+    pub host: String,
+    pub attempt: u32,  // 即将进行的第几次尝试
+    pub error: String, // 上一次失败的原因
+}
+
+struct LimiterState {
+    current: usize,
+    debt: usize,              // 尚未从信号量中回收的许可数
+    recent_errors: VecDeque<Instant>,
+    success_streak: usize,
+}
+
+/// 基于信号量的并发限制器，支持运行时调整有效并发数
+pub(crate) struct ConcurrencyLimiter {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+    adaptive: Option<AdaptiveConcurrency>,
+    events: Option<UnboundedSender<ConcurrencyEvent>>,
+    state: Mutex<LimiterState>,
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(max: usize, options: &OperationOptions) -> Self {
+        let max = max.max(1);
+        let limiter = Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+            adaptive: options.adaptive.clone(),
+            events: options.events.clone(),
+            state: Mutex::new(LimiterState {
+                current: max,
+                debt: 0,
+                recent_errors: VecDeque::new(),
+                success_streak: 0,
+            }),
+        };
+        limiter.emit(max, ConcurrencyChange::Initial);
+        limiter
+    }
+
+    /// 当前有效并发数
+    pub(crate) fn effective_concurrency(&self) -> usize {
+        self.state.lock().expect("limiter state poisoned").current
+    }
+
+    pub(crate) async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Semaphore closed")
+    }
+
+    /// 归还许可并根据操作结果调整并发
+    pub(crate) fn release<T>(&self, permit: OwnedSemaphorePermit, result: &Result<T, AnsibleError>) {
+        let mut state = self.state.lock().expect("limiter state poisoned");
+
+        // 并发已被降低时，归还的许可直接回收
+        if state.debt > 0 {
+            state.debt -= 1;
+            permit.forget();
+        } else {
+            drop(permit);
+        }
+
+        let Some(ref adaptive) = self.adaptive else {
+            return;
+        };
+
+        match result {
+            Err(e) if e.is_transient() => {
+                let now = Instant::now();
+                state.success_streak = 0;
+                state.recent_errors.push_back(now);
+                while let Some(&first) = state.recent_errors.front() {
+                    if now.duration_since(first) > adaptive.window {
+                        state.recent_errors.pop_front();
+                    } else {
+                        break;
+                    }
+                }
+
+                if state.recent_errors.len() >= adaptive.error_threshold {
+                    state.recent_errors.clear();
+                    let target = (state.current / 2).max(adaptive.min_concurrency.max(1));
+                    if target < state.current {
+                        let reduce = state.current - target;
+                        let forgotten = self.semaphore.forget_permits(reduce);
+                        state.debt += reduce - forgotten;
+                        state.current = target;
+                        info!("Transient errors exceeded threshold, backing off concurrency to {}", target);
+                        self.emit(target, ConcurrencyChange::BackedOff);
+                    }
+                }
+            }
+            Err(_) => {}
+            Ok(_) => {
+                state.success_streak += 1;
+                if state.current < self.max && state.success_streak >= adaptive.ramp_up_after {
+                    state.success_streak = 0;
+                    if state.debt > 0 {
+                        state.debt -= 1;
+                    } else {
+                        self.semaphore.add_permits(1);
+                    }
+                    state.current += 1;
+                    info!("Ramping concurrency back up to {}", state.current);
+                    self.emit(state.current, ConcurrencyChange::RampedUp);
+                }
+            }
+        }
+    }
+
+    fn emit(&self, effective_concurrency: usize, change: ConcurrencyChange) {
+        if let Some(ref sender) = self.events {
+            let _ = sender.send(ConcurrencyEvent {
+                effective_concurrency,
+                change,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transient() -> Result<(), AnsibleError> {
+        Err(AnsibleError::SshConnectionError("Connection reset by peer".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_backoff_and_ramp_up() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let options = OperationOptions::new()
+            .adaptive(AdaptiveConcurrency {
+                error_threshold: 2,
+                window: Duration::from_secs(60),
+                min_concurrency: 1,
+                ramp_up_after: 2,
+            })
+            .events(tx);
+        let limiter = ConcurrencyLimiter::new(8, &options);
+        assert_eq!(rx.recv().await.unwrap().change, ConcurrencyChange::Initial);
+
+        // 两次瞬时错误后并发减半
+        for _ in 0..2 {
+            let permit = limiter.acquire().await;
+            limiter.release(permit, &transient());
+        }
+        assert_eq!(limiter.effective_concurrency(), 4);
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.change, ConcurrencyChange::BackedOff);
+        assert_eq!(event.effective_concurrency, 4);
+        assert_eq!(limiter.semaphore.available_permits(), 4);
+
+        // 非瞬时错误不触发降级
+        let permit = limiter.acquire().await;
+        limiter.release(permit, &Err::<(), _>(AnsibleError::CommandError("exit 1".to_string())));
+        assert_eq!(limiter.effective_concurrency(), 4);
+
+        // 连续成功后逐步恢复
+        for _ in 0..2 {
+            let permit = limiter.acquire().await;
+            limiter.release(permit, &Ok(()));
+        }
+        assert_eq!(limiter.effective_concurrency(), 5);
+        assert_eq!(rx.recv().await.unwrap().change, ConcurrencyChange::RampedUp);
+        assert_eq!(limiter.semaphore.available_permits(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_backoff_while_permits_held() {
+        let options = OperationOptions::new().adaptive(AdaptiveConcurrency {
+            error_threshold: 1,
+            ..Default::default()
+        });
+        let limiter = ConcurrencyLimiter::new(2, &options);
+
+        let held = limiter.acquire().await;
+        let failing = limiter.acquire().await;
+        limiter.release(failing, &transient());
+        assert_eq!(limiter.effective_concurrency(), 1);
+
+        // 降级后可用许可数与新的有效并发一致
+        limiter.release(held, &Ok(()));
+        assert_eq!(limiter.semaphore.available_permits(), 1);
+    }
+}
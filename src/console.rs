@@ -0,0 +1,400 @@
+use crate::config::resolve_host_pattern;
+use crate::error::AnsibleError;
+use crate::manager::{AnsibleManager, BatchResult};
+use crate::ssh::SshClient;
+use crate::types::{CommandResult, FactSubset, SystemInfo};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+type Connector = Arc<dyn Fn(&str) -> Result<SshClient, AnsibleError> + Send + Sync>;
+
+/// 连接池中的单个连接，首次使用时建立，操作出错后丢弃以便下次重连
+type PooledClient = Arc<Mutex<Option<SshClient>>>;
+
+const HELP: &str = "\
+:limit <pattern>  切换目标主机（all、组名、主机名或带 * 的通配，逗号分隔）
+:serial <N>       每批最多 N 台主机，0 表示不分批
+:facts            显示目标主机的系统信息
+:hosts            列出当前目标主机
+:help             显示帮助
+:quit             关闭连接并退出
+其他输入作为命令在目标主机上并发执行";
+
+/// 交互式临时命令会话
+///
+/// 会话期间保持到各主机的连接，每输入一行即在目标主机上并发执行，
+/// 并按完成顺序输出各主机的结果。
+pub struct InteractiveSession {
+    connector: Connector,
+    hosts: Vec<String>,                   // 管理器中未禁用的主机
+    groups: HashMap<String, Vec<String>>, // 可用于 `:limit` 的主机组
+    targets: Vec<String>,
+    serial: Option<usize>,
+    max_concurrency: usize,
+    pool: HashMap<String, PooledClient>,
+}
+
+impl InteractiveSession {
+    /// 创建会话，`target_pattern` 为初始目标主机（语法同 `:limit`）
+    pub fn new(manager: AnsibleManager, target_pattern: &str) -> Result<Self, AnsibleError> {
+        // 已禁用的主机不参与会话
+        let disabled = manager.list_disabled();
+        let mut hosts: Vec<String> = manager.list_hosts().into_iter().filter(|h| !disabled.contains(h)).collect();
+        hosts.sort();
+        let max_concurrency = manager.get_max_concurrent_connections();
+        let manager = Arc::new(manager);
+        let connector: Connector = Arc::new(move |host: &str| manager.connect_host(host));
+        Self::with_connector(connector, hosts, max_concurrency, target_pattern)
+    }
+
+    pub(crate) fn with_connector(
+        connector: Connector,
+        hosts: Vec<String>,
+        max_concurrency: usize,
+        target_pattern: &str,
+    ) -> Result<Self, AnsibleError> {
+        let mut session = Self {
+            connector,
+            hosts,
+            groups: HashMap::new(),
+            targets: Vec::new(),
+            serial: None,
+            max_concurrency: max_concurrency.max(1),
+            pool: HashMap::new(),
+        };
+        session.targets = session.resolve(target_pattern)?;
+        Ok(session)
+    }
+
+    /// 设置可在目标模式中使用的主机组（例如 inventory 中的 groups）
+    pub fn with_groups(mut self, groups: HashMap<String, Vec<String>>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// 当前目标主机
+    pub fn targets(&self) -> &[String] {
+        &self.targets
+    }
+
+    /// 按模式切换目标主机
+    pub fn set_limit(&mut self, pattern: &str) -> Result<(), AnsibleError> {
+        self.targets = self.resolve(pattern)?;
+        Ok(())
+    }
+
+    /// 设置每批执行的主机数（`None` 表示所有目标主机同时执行）
+    pub fn set_serial(&mut self, serial: Option<usize>) {
+        self.serial = serial.filter(|n| *n > 0);
+    }
+
+    /// 已建立的连接数
+    pub fn open_connections(&self) -> usize {
+        self.pool
+            .values()
+            .filter(|client| client.lock().is_ok_and(|c| c.is_some()))
+            .count()
+    }
+
+    fn resolve(&self, pattern: &str) -> Result<Vec<String>, AnsibleError> {
+        let resolved = resolve_host_pattern(pattern, &self.hosts, &self.groups)?;
+        if resolved.is_empty() {
+            return Err(AnsibleError::ValidationError("Empty host pattern".to_string()));
+        }
+        Ok(resolved)
+    }
+
+    /// 在目标主机上执行命令，每台主机完成时立即回调
+    pub async fn run_command<F>(&mut self, command: &str, on_result: F) -> BatchResult<CommandResult>
+    where
+        F: FnMut(&str, &Result<CommandResult, AnsibleError>),
+    {
+        let command = command.to_string();
+        self.run_on_targets(move |client| client.execute_command(&command), on_result)
+            .await
+    }
+
+    /// 采集目标主机的系统信息（仅 OS 类别），每台主机完成时立即回调
+    pub async fn gather_facts<F>(&mut self, on_result: F) -> BatchResult<SystemInfo>
+    where
+        F: FnMut(&str, &Result<SystemInfo, AnsibleError>),
+    {
+        self.run_on_targets(|client| client.get_system_info_subset(FactSubset::OS), on_result)
+            .await
+    }
+
+    async fn run_on_targets<T, W, F>(&mut self, work: W, mut on_result: F) -> BatchResult<T>
+    where
+        T: Send + 'static,
+        W: Fn(&SshClient) -> Result<T, AnsibleError> + Send + Sync + 'static,
+        F: FnMut(&str, &Result<T, AnsibleError>),
+    {
+        let work = Arc::new(work);
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrency));
+        let batch_size = self.serial.unwrap_or(self.targets.len()).max(1);
+        let mut result = BatchResult::new();
+
+        for batch in self.targets.chunks(batch_size) {
+            let mut running = JoinSet::new();
+            for host in batch {
+                let pooled = self.pool.entry(host.clone()).or_default().clone();
+                let connector = self.connector.clone();
+                let semaphore = semaphore.clone();
+                let work = work.clone();
+                let host = host.clone();
+                running.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    let name = host.clone();
+                    let outcome = tokio::task::spawn_blocking(move || {
+                        let mut slot = pooled.lock().expect("connection pool poisoned");
+                        if slot.is_none() {
+                            *slot = Some(connector(&name)?);
+                        }
+                        let outcome = work(slot.as_ref().expect("connection just established"));
+                        if outcome.is_err() {
+                            // 连接可能已失效，下次使用时重新建立
+                            *slot = None;
+                        }
+                        outcome
+                    })
+                    .await
+                    .unwrap_or_else(|e| {
+                        Err(AnsibleError::CommandExecutionError(format!(
+                            "Blocking task for host {} failed: {}",
+                            host, e
+                        )))
+                    });
+                    (host, outcome)
+                });
+            }
+
+            while let Some(joined) = running.join_next().await {
+                if let Ok((host, outcome)) = joined {
+                    on_result(&host, &outcome);
+                    result.add_result(host, outcome);
+                }
+            }
+        }
+        result
+    }
+
+    /// 处理一行输入，返回 false 表示用户要求退出
+    pub async fn handle_line(&mut self, line: &str, output: &mut dyn Write) -> Result<bool, AnsibleError> {
+        let line = line.trim();
+        let (directive, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+
+        match directive {
+            "" => {}
+            ":quit" | ":exit" => return Ok(false),
+            ":help" => writeln!(output, "{}", HELP)?,
+            ":hosts" => writeln!(output, "{}", self.targets.join(", "))?,
+            ":limit" => {
+                self.set_limit(argument)?;
+                writeln!(output, "Targeting {} host(s): {}", self.targets.len(), self.targets.join(", "))?;
+            }
+            ":serial" => {
+                let serial = argument.parse::<usize>().map_err(|_| {
+                    AnsibleError::ValidationError(format!("Invalid serial value '{}'", argument))
+                })?;
+                self.set_serial(Some(serial));
+                match self.serial {
+                    Some(n) => writeln!(output, "Running on {} host(s) at a time", n)?,
+                    None => writeln!(output, "Running on all targets at once")?,
+                }
+            }
+            ":facts" => {
+                let result = self
+                    .gather_facts(|host, outcome| {
+                        let _ = match outcome {
+                            Ok(info) => writeln!(
+                                output,
+                                "{} | {} | {} {} {} | {}",
+                                host, info.hostname, info.distribution, info.kernel_version, info.architecture, info.uptime
+                            ),
+                            Err(e) => writeln!(output, "{} | UNREACHABLE! => {}", host, e),
+                        };
+                    })
+                    .await;
+                writeln!(output, "ok={} failed={}", result.successful.len(), result.failed.len())?;
+            }
+            _ if directive.starts_with(':') => {
+                return Err(AnsibleError::ValidationError(format!(
+                    "Unknown command '{}', type :help for help",
+                    directive
+                )));
+            }
+            _ => {
+                let result = self
+                    .run_command(line, |host, outcome| {
+                        let _ = write_command_result(output, host, outcome);
+                    })
+                    .await;
+                let failed = result
+                    .results
+                    .values()
+                    .filter(|r| !matches!(r.value(), Some(output) if output.exit_code == 0))
+                    .count();
+                writeln!(output, "ok={} failed={}", result.results.len() - failed, failed)?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// 运行读取-执行循环，直到输入结束或 `:quit`，退出时关闭所有连接
+    pub async fn run<R: BufRead>(&mut self, mut input: R, output: &mut dyn Write) -> Result<(), AnsibleError> {
+        loop {
+            write!(output, "rs-ansible [{} hosts]> ", self.targets.len())?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                break;
+            }
+            match self.handle_line(&line, output).await {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => writeln!(output, "ERROR: {}", e)?,
+            }
+        }
+        self.close().await;
+        Ok(())
+    }
+
+    /// 关闭会话中的所有连接
+    pub async fn close(&mut self) {
+        let clients: Vec<PooledClient> = self.pool.drain().map(|(_, client)| client).collect();
+        let closed = tokio::task::spawn_blocking(move || {
+            let mut closed = 0;
+            for client in clients {
+                if let Some(client) = client.lock().ok().and_then(|mut slot| slot.take()) {
+                    client.disconnect();
+                    closed += 1;
+                }
+            }
+            closed
+        })
+        .await;
+        match closed {
+            Ok(closed) => info!("Interactive session closed {} connection(s)", closed),
+            Err(e) => warn!("Failed to close interactive session connections: {}", e),
+        }
+    }
+}
+
+fn write_command_result(
+    output: &mut dyn Write,
+    host: &str,
+    outcome: &Result<CommandResult, AnsibleError>,
+) -> std::io::Result<()> {
+    match outcome {
+        Ok(result) => {
+            let status = if result.exit_code == 0 { "CHANGED" } else { "FAILED" };
+            writeln!(output, "{} | {} | rc={} >>", host, status, result.exit_code)?;
+            write!(output, "{}", result.stdout)?;
+            if !result.stderr.is_empty() {
+                write!(output, "{}", result.stderr)?;
+            }
+            Ok(())
+        }
+        Err(e) => writeln!(output, "{} | UNREACHABLE! => {}", host, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::matches_glob;
+    use crate::ssh::Transport;
+    use crate::types::{CommandOptions, HostConfig};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 回显命令的传输层，统计断开次数
+    struct EchoTransport {
+        disconnects: Arc<AtomicUsize>,
+    }
+
+    impl Transport for EchoTransport {
+        fn connect(
+            _config: &HostConfig,
+            _secret: Option<&crate::credentials::SecretString>,
+        ) -> Result<Self, AnsibleError> {
+            Ok(Self { disconnects: Arc::default() })
+        }
+
+        fn exec(&self, command: &str, _options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+            let exit_code = if command == "false" { 1 } else { 0 };
+            Ok(CommandResult { stdout: format!("{}\n", command), stderr: String::new(), exit_code, raw_stdout: None })
+        }
+
+        fn upload(&self, _: &mut dyn std::io::Read, _: u64, _: &str, _: i32) -> Result<u64, AnsibleError> {
+            Ok(0)
+        }
+
+        fn download(&self, _: &str, _: &mut dyn Write) -> Result<u64, AnsibleError> {
+            Ok(0)
+        }
+
+        fn disconnect(&self) {
+            self.disconnects.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_reuses_connections() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let disconnects = Arc::new(AtomicUsize::new(0));
+        let connector: Connector = {
+            let connects = connects.clone();
+            let disconnects = disconnects.clone();
+            Arc::new(move |host: &str| {
+                connects.fetch_add(1, Ordering::SeqCst);
+                let config = HostConfig { hostname: host.to_string(), ..Default::default() };
+                Ok(SshClient::with_transport(config, Box::new(EchoTransport { disconnects: disconnects.clone() })))
+            })
+        };
+        let hosts = vec!["db1".to_string(), "web1".to_string(), "web2".to_string()];
+        let mut session = InteractiveSession::with_connector(connector, hosts, 4, "web*")
+            .unwrap()
+            .with_groups(HashMap::from([("db".to_string(), vec!["db1".to_string()])]));
+        assert_eq!(session.targets(), ["web1", "web2"]);
+
+        let input = "uptime\n:serial 1\nfalse\n:limit db,web2\n:bogus\nhostname\n:quit\nnot run\n";
+        let mut output = Vec::new();
+        session.run(input.as_bytes(), &mut output).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("web1 | CHANGED | rc=0 >>\nuptime\n"));
+        assert!(output.contains("web2 | FAILED | rc=1 >>"));
+        assert!(output.contains("ok=0 failed=2"));
+        assert!(output.contains("Targeting 2 host(s): db1, web2"));
+        assert!(output.contains("ERROR: Validation error: Unknown command ':bogus'"));
+        assert!(output.contains("db1 | CHANGED | rc=0 >>\nhostname\n"));
+        assert!(!output.contains("not run"));
+
+        // 三条命令只为每台主机建立一次连接，退出时全部断开
+        assert_eq!(connects.load(Ordering::SeqCst), 3);
+        assert_eq!(disconnects.load(Ordering::SeqCst), 3);
+        assert_eq!(session.open_connections(), 0);
+    }
+
+    #[test]
+    fn test_pattern_resolution() {
+        assert!(matches_glob("web*", "web01"));
+        assert!(matches_glob("*-db-*", "eu-db-1"));
+        assert!(!matches_glob("web*", "db1"));
+        assert!(matches_glob("db1", "db1"));
+
+        let connector: Connector = Arc::new(|_: &str| Err(AnsibleError::SshConnectionError("offline".to_string())));
+        let hosts = vec!["db1".to_string(), "web1".to_string()];
+        let mut session = InteractiveSession::with_connector(connector, hosts, 1, "all").unwrap();
+        assert_eq!(session.targets().len(), 2);
+        assert!(session.set_limit("cache*").is_err());
+        assert_eq!(session.targets().len(), 2);
+    }
+}
@@ -0,0 +1,451 @@
+use crate::types::SystemInfo;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+/// 两次采集之间通常都会变化的字段，做漂移检测时一般需要忽略
+pub const VOLATILE_FACTS: &[&str] = &["uptime", "memory_free"];
+
+/// 统一 diff 输出的大小上限，超出部分在行边界处截断
+pub const MAX_DIFF_BYTES: usize = 64 * 1024;
+/// 超过该大小的文件不计算差异，只报告文件过大
+pub const MAX_DIFF_SOURCE_BYTES: u64 = 1024 * 1024;
+/// 每个差异块前后保留的上下文行数
+const DIFF_CONTEXT_LINES: usize = 3;
+/// 去掉公共前后缀后 LCS 表的最大单元数，超出时把中间部分整体视为删除加新增
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// 新增或删除的 fact
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactEntry {
+    pub path: String, // 例如 `packages.nginx`、`network_interfaces[eth0]`
+    pub value: Value,
+}
+
+/// 取值发生变化的 fact
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactChange {
+    pub path: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// 两份 SystemInfo 快照之间的差异（各列表按路径排序）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemInfoDiff {
+    pub added: Vec<FactEntry>,
+    pub removed: Vec<FactEntry>,
+    pub changed: Vec<FactChange>,
+}
+
+impl SystemInfoDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for SystemInfoDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.added {
+            writeln!(f, "+ {}: {}", entry.path, entry.value)?;
+        }
+        for entry in &self.removed {
+            writeln!(f, "- {}: {}", entry.path, entry.value)?;
+        }
+        for change in &self.changed {
+            writeln!(f, "~ {}: {} -> {}", change.path, change.old, change.new)?;
+        }
+        Ok(())
+    }
+}
+
+impl SystemInfo {
+    /// 比较两份快照，返回从 `self`（基线）到 `other`（当前）的变化
+    ///
+    /// 比较基于序列化后的结构，不依赖具体字段：映射按键比较，标量列表按集合比较，
+    /// 对象列表优先按 `name`/`mount` 字段配对（缺少键或键重复时按下标）。
+    pub fn diff(&self, other: &SystemInfo) -> SystemInfoDiff {
+        self.diff_ignoring(other, &[])
+    }
+
+    /// 比较两份快照，忽略指定路径（及其子路径）上的变化，例如 [`VOLATILE_FACTS`]
+    pub fn diff_ignoring(&self, other: &SystemInfo, ignored: &[&str]) -> SystemInfoDiff {
+        let old = serde_json::to_value(self).unwrap_or(Value::Null);
+        let new = serde_json::to_value(other).unwrap_or(Value::Null);
+
+        let mut diff = SystemInfoDiff::default();
+        diff_value("", &old, &new, &mut diff);
+
+        let keep = |path: &str| !ignored.iter().any(|prefix| is_under(path, prefix));
+        diff.added.retain(|e| keep(&e.path));
+        diff.removed.retain(|e| keep(&e.path));
+        diff.changed.retain(|c| keep(&c.path));
+        diff.added.sort_by(|a, b| a.path.cmp(&b.path));
+        diff.removed.sort_by(|a, b| a.path.cmp(&b.path));
+        diff.changed.sort_by(|a, b| a.path.cmp(&b.path));
+        diff
+    }
+}
+
+/// `path` 是否等于 `prefix` 或位于其下
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+}
+
+fn child_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() { key.to_string() } else { format!("{}.{}", parent, key) }
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, diff: &mut SystemInfoDiff) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let child = child_path(path, key);
+                match new_map.get(key) {
+                    Some(new_value) => diff_value(&child, old_value, new_value, diff),
+                    None => diff.removed.push(FactEntry { path: child, value: old_value.clone() }),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    diff.added.push(FactEntry { path: child_path(path, key), value: new_value.clone() });
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => diff_array(path, old_items, new_items, diff),
+        _ if old != new => diff.changed.push(FactChange {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// 列表元素的配对键：对象取 `name` 字段（磁盘取 `mount`）
+fn element_key(value: &Value) -> Option<String> {
+    value
+        .get("name")
+        .or_else(|| value.get("mount"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+/// 每个元素都有配对键且同一侧内不重复时才按键配对，否则按下标配对
+fn unique_keys(items: &[Value]) -> Option<Vec<String>> {
+    let keys: Vec<String> = items.iter().map(element_key).collect::<Option<_>>()?;
+    let distinct: HashSet<&String> = keys.iter().collect();
+    (distinct.len() == keys.len()).then_some(keys)
+}
+
+fn diff_array(path: &str, old: &[Value], new: &[Value], diff: &mut SystemInfoDiff) {
+    let keyed = unique_keys(old).zip(unique_keys(new));
+    let scalar = old.iter().chain(new).all(|v| !v.is_object() && !v.is_array());
+
+    if let Some((old_keys, new_keys)) = keyed {
+        for (key, old_item) in old_keys.iter().zip(old) {
+            let item_path = format!("{}[{}]", path, key);
+            match new_keys.iter().position(|k| k == key) {
+                Some(index) => diff_value(&item_path, old_item, &new[index], diff),
+                None => diff.removed.push(FactEntry { path: item_path, value: old_item.clone() }),
+            }
+        }
+        for (key, new_item) in new_keys.iter().zip(new) {
+            if !old_keys.contains(key) {
+                diff.added.push(FactEntry { path: format!("{}[{}]", path, key), value: new_item.clone() });
+            }
+        }
+    } else if scalar {
+        // 标量列表（例如监听端口）按集合比较，忽略顺序
+        for item in old.iter().filter(|v| !new.contains(v)) {
+            diff.removed.push(FactEntry { path: path.to_string(), value: item.clone() });
+        }
+        for item in new.iter().filter(|v| !old.contains(v)) {
+            diff.added.push(FactEntry { path: path.to_string(), value: item.clone() });
+        }
+    } else {
+        for index in 0..old.len().max(new.len()) {
+            let item_path = format!("{}[{}]", path, index);
+            match (old.get(index), new.get(index)) {
+                (Some(o), Some(n)) => diff_value(&item_path, o, n, diff),
+                (Some(o), None) => diff.removed.push(FactEntry { path: item_path, value: o.clone() }),
+                (None, Some(n)) => diff.added.push(FactEntry { path: item_path, value: n.clone() }),
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+/// 生成 `before` 到 `after` 的统一 diff（`--- before: 路径`、`+++ after: 路径` 与 `@@` 差异块）
+///
+/// 内容相同时返回空字符串；任意一侧包含 NUL 字节或不是合法 UTF-8 时只报告二进制文件不同；
+/// 输出超过 [`MAX_DIFF_BYTES`] 时截断并注明原始大小。
+pub fn unified_diff(path: &str, before: &[u8], after: &[u8]) -> String {
+    if before == after {
+        return String::new();
+    }
+    let (Some(old), Some(new)) = (as_text(before), as_text(after)) else {
+        return format!("Binary files before: {0} and after: {0} differ\n", path);
+    };
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = line_edits(&old_lines, &new_lines);
+
+    // 每个编辑之前已经过的旧/新行数，用于差异块头
+    let mut positions = Vec::with_capacity(edits.len());
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for (edit, _) in &edits {
+        positions.push((old_pos, new_pos));
+        match edit {
+            Edit::Keep => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Delete => old_pos += 1,
+            Edit::Insert => new_pos += 1,
+        }
+    }
+
+    let changes: Vec<usize> = (0..edits.len()).filter(|&i| edits[i].0 != Edit::Keep).collect();
+    let mut output = format!("--- before: {0}\n+++ after: {0}\n", path);
+    let mut first = 0;
+    while first < changes.len() {
+        // 间隔不超过两倍上下文的变化合并为一个差异块
+        let mut last = first;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * DIFF_CONTEXT_LINES + 1 {
+            last += 1;
+        }
+        let start = changes[first].saturating_sub(DIFF_CONTEXT_LINES);
+        let end = (changes[last] + DIFF_CONTEXT_LINES + 1).min(edits.len());
+        let hunk = &edits[start..end];
+        let old_count = hunk.iter().filter(|(edit, _)| *edit != Edit::Insert).count();
+        let new_count = hunk.iter().filter(|(edit, _)| *edit != Edit::Delete).count();
+        let (old_start, new_start) = positions[start];
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        ));
+        for (edit, line) in hunk {
+            output.push(match edit {
+                Edit::Keep => ' ',
+                Edit::Delete => '-',
+                Edit::Insert => '+',
+            });
+            output.push_str(line);
+            if !line.ends_with('\n') {
+                output.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        first = last + 1;
+    }
+    truncate_diff(output)
+}
+
+/// 文本内容；包含 NUL 字节或不是合法 UTF-8 时视为二进制
+fn as_text(content: &[u8]) -> Option<&str> {
+    if content.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// 逐行编辑序列：先去掉公共前后缀，再对中间部分求最长公共子序列
+fn line_edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Edit, &'a str)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut edits: Vec<(Edit, &str)> = old[..prefix].iter().map(|line| (Edit::Keep, *line)).collect();
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_LCS_CELLS {
+        edits.extend(old_mid.iter().map(|line| (Edit::Delete, *line)));
+        edits.extend(new_mid.iter().map(|line| (Edit::Insert, *line)));
+    } else {
+        // lcs[i][j]：old_mid[i..] 与 new_mid[j..] 的最长公共子序列长度
+        let width = new_mid.len() + 1;
+        let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_mid.len() || j < new_mid.len() {
+            if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+                edits.push((Edit::Keep, old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if j == new_mid.len() || (i < old_mid.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+                edits.push((Edit::Delete, old_mid[i]));
+                i += 1;
+            } else {
+                edits.push((Edit::Insert, new_mid[j]));
+                j += 1;
+            }
+        }
+    }
+    edits.extend(old[old.len() - suffix..].iter().map(|line| (Edit::Keep, *line)));
+    edits
+}
+
+/// 差异块头中的 `起始行,行数`；行数为 0 时起始行为该位置之前的一行
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+fn truncate_diff(mut output: String) -> String {
+    if output.len() <= MAX_DIFF_BYTES {
+        return output;
+    }
+    let total = output.len();
+    let cut = output.as_bytes()[..MAX_DIFF_BYTES]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    output.truncate(cut);
+    output.push_str(&format!("... diff truncated ({} of {} bytes shown)\n", cut, total));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NetworkInterface;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn snapshot(kernel: &str, root_usage: &str, packages: &[(&str, &str)], interfaces: &[(&str, &str)]) -> SystemInfo {
+        SystemInfo {
+            hostname: "web1".to_string(),
+            os: "Linux".to_string(),
+            kernel_version: kernel.to_string(),
+            uptime: "up 3 days".to_string(),
+            disk_usage: HashMap::from([("/".to_string(), root_usage.to_string())]),
+            packages: packages.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+            network_interfaces: interfaces
+                .iter()
+                .map(|(name, ip)| NetworkInterface {
+                    name: name.to_string(),
+                    ip_address: ip.to_string(),
+                    mac_address: String::new(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_system_info_diff() {
+        let baseline = snapshot("6.1.0", "40%", &[("nginx", "1.22"), ("curl", "7.88")], &[("eth0", "10.0.0.1")]);
+        let mut current = snapshot(
+            "6.1.5",
+            "71%",
+            &[("nginx", "1.24"), ("redis", "7.0")],
+            &[("eth0", "10.0.0.2"), ("eth1", "192.168.0.1")],
+        );
+        current.uptime = "up 10 days".to_string();
+
+        assert!(baseline.diff(&baseline).is_empty());
+
+        let diff = baseline.diff(&current);
+        let added: Vec<&str> = diff.added.iter().map(|e| e.path.as_str()).collect();
+        let removed: Vec<&str> = diff.removed.iter().map(|e| e.path.as_str()).collect();
+        let changed: Vec<&str> = diff.changed.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(added, vec!["network_interfaces[eth1]", "packages.redis"]);
+        assert_eq!(removed, vec!["packages.curl"]);
+        assert_eq!(
+            changed,
+            vec![
+                "disk_usage./",
+                "kernel_version",
+                "network_interfaces[eth0].ip_address",
+                "packages.nginx",
+                "uptime",
+            ]
+        );
+        assert_eq!(diff.changed[1].old, json!("6.1.0"));
+        assert_eq!(diff.changed[1].new, json!("6.1.5"));
+
+        let stable = baseline.diff_ignoring(&current, VOLATILE_FACTS);
+        assert!(stable.changed.iter().all(|c| c.path != "uptime"));
+        assert!(stable.to_string().contains("~ kernel_version: \"6.1.0\" -> \"6.1.5\""));
+    }
+
+    #[test]
+    fn test_scalar_lists_compare_as_sets() {
+        let mut diff = SystemInfoDiff::default();
+        diff_value("ports", &json!([22, 80]), &json!([443, 22]), &mut diff);
+        assert_eq!(diff.added, vec![FactEntry { path: "ports".to_string(), value: json!(443) }]);
+        assert_eq!(diff.removed, vec![FactEntry { path: "ports".to_string(), value: json!(80) }]);
+        assert!(is_under("packages.nginx", "packages"));
+        assert!(!is_under("packages_extra", "packages"));
+    }
+
+    #[test]
+    fn test_duplicate_names_pair_by_index() {
+        // 同名的两块网卡（例如别名）无法按名称配对，退回按下标比较
+        let old = json!([{"name": "eth0", "ip": "10.0.0.1"}, {"name": "eth0", "ip": "10.0.0.2"}]);
+        let new = json!([{"name": "eth0", "ip": "10.0.0.1"}, {"name": "eth0", "ip": "10.0.0.3"}]);
+        let mut diff = SystemInfoDiff::default();
+        diff_value("interfaces", &old, &new, &mut diff);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].path, "interfaces[1].ip");
+        assert_eq!(diff.changed[0].new, json!("10.0.0.3"));
+
+        // 缺少名称的元素同样按下标配对
+        let mut diff = SystemInfoDiff::default();
+        diff_value("items", &json!([{"name": "a"}]), &json!([{"name": "a"}, {"id": 2}]), &mut diff);
+        assert_eq!(diff.added, vec![FactEntry { path: "items[1]".to_string(), value: json!({"id": 2}) }]);
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        let after = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn";
+        assert_eq!(unified_diff("/etc/app.conf", before.as_bytes(), before.as_bytes()), "");
+        assert_eq!(
+            unified_diff("/etc/app.conf", before.as_bytes(), after.as_bytes()),
+            "--- before: /etc/app.conf\n+++ after: /etc/app.conf\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -11,3 +11,4 @@\n k\n l\n m\n+n\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified_diff("/etc/motd", b"", b"hello\n"),
+            "--- before: /etc/motd\n+++ after: /etc/motd\n@@ -0,0 +1 @@\n+hello\n"
+        );
+        assert_eq!(
+            unified_diff("/usr/bin/app", b"\x7fELF\0\x01", b"\x7fELF\0\x02"),
+            "Binary files before: /usr/bin/app and after: /usr/bin/app differ\n"
+        );
+
+        let large: String = (0..20_000).map(|i| format!("line {}\n", i)).collect();
+        let diff = unified_diff("/var/lib/data", b"", large.as_bytes());
+        assert!(diff.len() < MAX_DIFF_BYTES + 100);
+        assert!(diff.ends_with(" bytes shown)\n"));
+        assert!(diff.lines().rev().nth(1).unwrap().starts_with("+line "));
+    }
+}
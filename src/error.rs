@@ -1,7 +1,7 @@
 use thiserror::Error;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Error, Debug, Serialize)]
+#[derive(Error, Debug, Serialize, Deserialize)]
 pub enum AnsibleError {
     #[error("SSH connection failed: {0}")]
     SshConnectionError(String),
@@ -32,6 +32,32 @@ pub enum AnsibleError {
     
     #[error("SSH error: {0}")]
     Ssh2Error(String),
+
+    #[error("Operation cancelled: {0}")]
+    Cancelled(String),
+}
+
+impl AnsibleError {
+    /// 是否为瞬时错误（连接被拒绝/重置、握手失败、超时等，通常由限流或网络抖动引起）
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AnsibleError::SshConnectionError(_) => true,
+            AnsibleError::Ssh2Error(msg) => {
+                let msg = msg.to_lowercase();
+                msg.contains("timed out") || msg.contains("connection reset") || msg.contains("banner")
+            }
+            _ => false,
+        }
+    }
+
+    /// 是否为连接阶段的错误（主机不可达、认证失败、连接超时），区别于主机上的任务失败
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            AnsibleError::SshConnectionError(_) | AnsibleError::AuthenticationError(_) => true,
+            AnsibleError::Ssh2Error(_) => self.is_transient(),
+            _ => false,
+        }
+    }
 }
 
 impl From<std::io::Error> for AnsibleError {
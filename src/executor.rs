@@ -1,16 +1,38 @@
+use crate::callback::{self, ExecutionCallback, TaskOutcome};
+use crate::concurrency::RetryEvent;
+use crate::config::{resolve_host_pattern, with_disabled_group};
 use crate::error::AnsibleError;
-use crate::types::{CommandResult, FileTransferResult, SystemInfo, FileCopyOptions, UserOptions, UserResult, TemplateOptions, TemplateResult};
-use crate::manager::{AnsibleManager, BatchResult};
-use crate::utils::{generate_local_temp_path, generate_remote_temp_path};
+use crate::types::{
+    CommandResult, FactSubset, FileTransferResult, SystemInfo, FileCopyOptions, UserOptions, UserResult, TemplateOptions,
+    TemplateResult, LogRotateResult, DnsConfig, PermissionsOptions, DirSpec, EnsureDirsResult, SshKeyType, SshKeypairResult, RepoConfig, RepoResult,
+    UserState, BlockInFileOptions, BlockInFileResult, HealthProbe, HealthProbeResult, HostRequirement, CgroupConfig,
+    CommandBatchOptions, KernelModuleConfig, KernelModuleResult, PlannedFile, FileSetResult,
+};
+use crate::manager::{AnsibleManager, BatchResult, HostOutcome, SkipReason};
+use crate::metrics;
+use crate::run_handle::{update_status, HostRunStatus, RunState, RunStatus, SharedRunStatus};
+use crate::report::{self, JunitGrouping, TaskTiming, TimingReport, DEFAULT_SLOWEST_COUNT};
+use crate::state_file::RunStateFile;
+use crate::utils::{generate_remote_temp_path, run_local_command, shell_quote, LocalTempFile};
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
-use std::collections::HashSet;
+use tracing::{info, info_span, instrument, warn, Instrument};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::Utc;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "task_type")]
 pub enum TaskType {
     #[serde(rename = "command")]
     Command { cmd: String },
+    #[serde(rename = "commands")]
+    Commands {
+        commands: Vec<String>,
+        #[serde(flatten)]
+        options: CommandBatchOptions,
+    },
     #[serde(rename = "copy")]
     CopyFile { 
         src: String, 
@@ -18,12 +40,27 @@ pub enum TaskType {
         #[serde(skip_serializing_if = "Option::is_none")]
         options: Option<FileCopyOptions>,
     },
+    #[serde(rename = "fetch")]
+    Fetch {
+        src: String,  // 远程文件路径
+        dest: String, // 本地目录，文件保存为 `<dest>/<主机名>/<文件名>`
+    },
     #[serde(rename = "system_info")]
     GetSystemInfo,
     #[serde(rename = "ping")]
     Ping,
     #[serde(rename = "shell")]
-    Shell { script: String },
+    Shell {
+        script: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        creates: Option<String>, // 该路径已存在时跳过
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        removes: Option<String>, // 该路径不存在时跳过
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chdir: Option<String>,   // 执行脚本前切换到的目录
+        #[serde(default)]
+        keep_temp_on_failure: bool, // 脚本执行失败时保留远程脚本文件（用于调试）
+    },
     #[serde(rename = "user")]
     User { 
         #[serde(flatten)]
@@ -34,6 +71,75 @@ pub enum TaskType {
         #[serde(flatten)]
         options: TemplateOptions 
     },
+    #[serde(rename = "logrotate")]
+    LogRotate {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        config_file: Option<String>,
+        #[serde(default)]
+        force: bool,
+    },
+    #[serde(rename = "dns_config")]
+    DnsConfig { config: DnsConfig },
+    #[serde(rename = "local_command")]
+    LocalCommand { cmd: String }, // 在控制机本地执行，只执行一次
+    #[serde(rename = "permissions")]
+    Permissions {
+        #[serde(flatten)]
+        options: PermissionsOptions,
+    },
+    #[serde(rename = "ensure_dirs")]
+    EnsureDirs { dirs: Vec<DirSpec> },
+    #[serde(rename = "ssh_keypair")]
+    SshKeypair {
+        path: String,
+        #[serde(rename = "type", default)]
+        type_: SshKeyType,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bits: Option<u32>,        // 默认按密钥类型选择
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        comment: Option<String>,
+    },
+    #[serde(rename = "blockinfile")]
+    BlockInFile {
+        #[serde(flatten)]
+        options: BlockInFileOptions,
+    },
+    #[serde(rename = "repo")]
+    Repo {
+        config: RepoConfig,
+        #[serde(default)]
+        state: UserState,
+    },
+    #[serde(rename = "kernel_module")]
+    KernelModule {
+        config: KernelModuleConfig,
+        #[serde(default)]
+        state: UserState,
+    },
+    #[serde(rename = "file_set")]
+    FileSet {
+        files: Vec<PlannedFile>,
+    },
+    #[serde(rename = "health_check")]
+    HealthCheck {
+        probes: Vec<HealthProbe>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        requirements: Vec<HostRequirement>, // 主机条件（例如命令的最低版本），不满足时主机失败
+    },
+    #[serde(rename = "cgroup")]
+    Cgroup { config: CgroupConfig },
+}
+
+impl TaskType {
+    /// 差异模式下报告 diff 的任务所修改的远程文件；其他任务返回 None
+    fn diff_path(&self) -> Option<&str> {
+        match self {
+            TaskType::Template { options } => Some(&options.dest),
+            TaskType::CopyFile { dest, .. } => Some(dest),
+            TaskType::BlockInFile { options } => Some(&options.path),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,55 +151,260 @@ pub struct Task {
     pub hosts: Option<Vec<String>>, // 如果为None，则在所有主机上执行
     #[serde(default)]
     pub ignore_errors: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignore_unreachable: bool,        // 主机不可达时不移出后续任务（ignore_errors 不包含不可达）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#become: Option<bool>,          // 覆盖主机默认的 become 设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub become_user: Option<String>,     // 覆盖主机默认的提权用户
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_user: Option<String>,     // 覆盖本任务的 SSH 登录用户
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,               // 覆盖本任务的 SSH 端口
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub register: Option<String>,        // 将任务结果保存为变量，供后续任务使用
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,               // 任务标签，用于按标签选择要执行的任务
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_threshold: Option<f32>,  // 覆盖 playbook 的成功阈值（0.0-1.0）
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Playbook {
     pub name: String,
+    #[serde(default)]
+    pub gather_facts: bool, // 是否在执行前统一收集一次 facts
+    #[serde(default)]
+    pub fact_subset: FactSubset, // 收集 facts 的类别
+    #[serde(default = "default_success_threshold")]
+    pub success_threshold: f32,  // 任务视为成功所需的成功主机比例，默认 1.0 即所有主机都必须成功
     pub tasks: Vec<Task>,
 }
 
+fn default_success_threshold() -> f32 {
+    1.0
+}
+
 #[derive(Debug, Serialize)]
 pub enum TaskResult {
     Command(BatchResult<CommandResult>),
+    Commands(BatchResult<Vec<CommandResult>>),
     CopyFile(BatchResult<FileTransferResult>),
+    Fetch(BatchResult<FileTransferResult>),
     SystemInfo(BatchResult<SystemInfo>),
     Ping(BatchResult<bool>),
     User(BatchResult<UserResult>),
     Template(BatchResult<TemplateResult>),
+    DnsConfig(BatchResult<bool>),
+    LogRotate(BatchResult<LogRotateResult>),
+    Permissions(BatchResult<bool>),
+    EnsureDirs(BatchResult<EnsureDirsResult>),
+    SshKeypair(BatchResult<SshKeypairResult>),
+    Repo(BatchResult<RepoResult>),
+    KernelModule(BatchResult<KernelModuleResult>),
+    FileSet(BatchResult<FileSetResult>),
+    BlockInFile(BatchResult<BlockInFileResult>),
+    HealthCheck(BatchResult<Vec<HealthProbeResult>>),
+    Cgroup(BatchResult<bool>),
 }
 
 impl TaskResult {
+    /// 构造某类任务的结果，其中所有主机都以 `reason` 被跳过（变体与该任务正常执行时一致）
+    pub fn skipped_for(task_type: &TaskType, hosts: Vec<String>, reason: SkipReason) -> Self {
+        fn skipped<T>(hosts: Vec<String>, reason: SkipReason) -> BatchResult<T> {
+            let mut batch_result = BatchResult::new();
+            for host in hosts {
+                batch_result.add_skipped(host, reason);
+            }
+            batch_result
+        }
+
+        match task_type {
+            TaskType::Command { .. } | TaskType::Shell { .. } | TaskType::LocalCommand { .. } => {
+                TaskResult::Command(skipped(hosts, reason))
+            }
+            TaskType::Commands { .. } => TaskResult::Commands(skipped(hosts, reason)),
+            TaskType::CopyFile { .. } => TaskResult::CopyFile(skipped(hosts, reason)),
+            TaskType::Fetch { .. } => TaskResult::Fetch(skipped(hosts, reason)),
+            TaskType::GetSystemInfo => TaskResult::SystemInfo(skipped(hosts, reason)),
+            TaskType::Ping => TaskResult::Ping(skipped(hosts, reason)),
+            TaskType::User { .. } => TaskResult::User(skipped(hosts, reason)),
+            TaskType::Template { .. } => TaskResult::Template(skipped(hosts, reason)),
+            TaskType::LogRotate { .. } => TaskResult::LogRotate(skipped(hosts, reason)),
+            TaskType::DnsConfig { .. } => TaskResult::DnsConfig(skipped(hosts, reason)),
+            TaskType::Permissions { .. } => TaskResult::Permissions(skipped(hosts, reason)),
+            TaskType::EnsureDirs { .. } => TaskResult::EnsureDirs(skipped(hosts, reason)),
+            TaskType::SshKeypair { .. } => TaskResult::SshKeypair(skipped(hosts, reason)),
+            TaskType::BlockInFile { .. } => TaskResult::BlockInFile(skipped(hosts, reason)),
+            TaskType::Repo { .. } => TaskResult::Repo(skipped(hosts, reason)),
+            TaskType::KernelModule { .. } => TaskResult::KernelModule(skipped(hosts, reason)),
+            TaskType::FileSet { .. } => TaskResult::FileSet(skipped(hosts, reason)),
+            TaskType::HealthCheck { .. } => TaskResult::HealthCheck(skipped(hosts, reason)),
+            TaskType::Cgroup { .. } => TaskResult::Cgroup(skipped(hosts, reason)),
+        }
+    }
+
     pub fn success_rate(&self) -> f32 {
         match self {
             TaskResult::Command(r) => r.success_rate(),
+            TaskResult::Commands(r) => r.success_rate(),
             TaskResult::CopyFile(r) => r.success_rate(),
+            TaskResult::Fetch(r) => r.success_rate(),
             TaskResult::SystemInfo(r) => r.success_rate(),
             TaskResult::Ping(r) => r.success_rate(),
             TaskResult::User(r) => r.success_rate(),
             TaskResult::Template(r) => r.success_rate(),
+            TaskResult::DnsConfig(r) => r.success_rate(),
+            TaskResult::LogRotate(r) => r.success_rate(),
+            TaskResult::Permissions(r) => r.success_rate(),
+            TaskResult::EnsureDirs(r) => r.success_rate(),
+            TaskResult::SshKeypair(r) => r.success_rate(),
+            TaskResult::Repo(r) => r.success_rate(),
+            TaskResult::KernelModule(r) => r.success_rate(),
+            TaskResult::FileSet(r) => r.success_rate(),
+            TaskResult::BlockInFile(r) => r.success_rate(),
+            TaskResult::HealthCheck(r) => r.success_rate(),
+            TaskResult::Cgroup(r) => r.success_rate(),
         }
     }
 
+    /// 结果中的 `changed` 是否有意义（会修改主机状态的任务）
+    pub fn reports_changes(&self) -> bool {
+        matches!(
+            self,
+            TaskResult::User(_)
+                | TaskResult::Template(_)
+                | TaskResult::DnsConfig(_)
+                | TaskResult::Permissions(_)
+                | TaskResult::EnsureDirs(_)
+                | TaskResult::SshKeypair(_)
+                | TaskResult::Repo(_)
+                | TaskResult::KernelModule(_)
+                | TaskResult::FileSet(_)
+                | TaskResult::BlockInFile(_)
+                | TaskResult::Cgroup(_)
+        )
+    }
+
     pub fn successful_hosts(&self) -> &Vec<String> {
         match self {
             TaskResult::Command(r) => &r.successful,
+            TaskResult::Commands(r) => &r.successful,
             TaskResult::CopyFile(r) => &r.successful,
+            TaskResult::Fetch(r) => &r.successful,
             TaskResult::SystemInfo(r) => &r.successful,
             TaskResult::Ping(r) => &r.successful,
             TaskResult::User(r) => &r.successful,
             TaskResult::Template(r) => &r.successful,
+            TaskResult::DnsConfig(r) => &r.successful,
+            TaskResult::LogRotate(r) => &r.successful,
+            TaskResult::Permissions(r) => &r.successful,
+            TaskResult::EnsureDirs(r) => &r.successful,
+            TaskResult::SshKeypair(r) => &r.successful,
+            TaskResult::Repo(r) => &r.successful,
+            TaskResult::KernelModule(r) => &r.successful,
+            TaskResult::FileSet(r) => &r.successful,
+            TaskResult::BlockInFile(r) => &r.successful,
+            TaskResult::HealthCheck(r) => &r.successful,
+            TaskResult::Cgroup(r) => &r.successful,
         }
     }
 
     pub fn failed_hosts(&self) -> &Vec<String> {
         match self {
             TaskResult::Command(r) => &r.failed,
+            TaskResult::Commands(r) => &r.failed,
             TaskResult::CopyFile(r) => &r.failed,
+            TaskResult::Fetch(r) => &r.failed,
             TaskResult::SystemInfo(r) => &r.failed,
             TaskResult::Ping(r) => &r.failed,
             TaskResult::User(r) => &r.failed,
             TaskResult::Template(r) => &r.failed,
+            TaskResult::DnsConfig(r) => &r.failed,
+            TaskResult::LogRotate(r) => &r.failed,
+            TaskResult::Permissions(r) => &r.failed,
+            TaskResult::EnsureDirs(r) => &r.failed,
+            TaskResult::SshKeypair(r) => &r.failed,
+            TaskResult::Repo(r) => &r.failed,
+            TaskResult::KernelModule(r) => &r.failed,
+            TaskResult::FileSet(r) => &r.failed,
+            TaskResult::BlockInFile(r) => &r.failed,
+            TaskResult::HealthCheck(r) => &r.failed,
+            TaskResult::Cgroup(r) => &r.failed,
+        }
+    }
+
+    /// 因连接失败而失败的主机（`failed_hosts` 的子集）
+    pub fn unreachable_hosts(&self) -> &Vec<String> {
+        match self {
+            TaskResult::Command(r) => &r.unreachable,
+            TaskResult::Commands(r) => &r.unreachable,
+            TaskResult::CopyFile(r) => &r.unreachable,
+            TaskResult::Fetch(r) => &r.unreachable,
+            TaskResult::SystemInfo(r) => &r.unreachable,
+            TaskResult::Ping(r) => &r.unreachable,
+            TaskResult::User(r) => &r.unreachable,
+            TaskResult::Template(r) => &r.unreachable,
+            TaskResult::DnsConfig(r) => &r.unreachable,
+            TaskResult::LogRotate(r) => &r.unreachable,
+            TaskResult::Permissions(r) => &r.unreachable,
+            TaskResult::EnsureDirs(r) => &r.unreachable,
+            TaskResult::SshKeypair(r) => &r.unreachable,
+            TaskResult::Repo(r) => &r.unreachable,
+            TaskResult::KernelModule(r) => &r.unreachable,
+            TaskResult::FileSet(r) => &r.unreachable,
+            TaskResult::BlockInFile(r) => &r.unreachable,
+            TaskResult::HealthCheck(r) => &r.unreachable,
+            TaskResult::Cgroup(r) => &r.unreachable,
+        }
+    }
+
+    pub fn skipped_hosts(&self) -> &Vec<String> {
+        match self {
+            TaskResult::Command(r) => &r.skipped,
+            TaskResult::Commands(r) => &r.skipped,
+            TaskResult::CopyFile(r) => &r.skipped,
+            TaskResult::Fetch(r) => &r.skipped,
+            TaskResult::SystemInfo(r) => &r.skipped,
+            TaskResult::Ping(r) => &r.skipped,
+            TaskResult::User(r) => &r.skipped,
+            TaskResult::Template(r) => &r.skipped,
+            TaskResult::DnsConfig(r) => &r.skipped,
+            TaskResult::LogRotate(r) => &r.skipped,
+            TaskResult::Permissions(r) => &r.skipped,
+            TaskResult::EnsureDirs(r) => &r.skipped,
+            TaskResult::SshKeypair(r) => &r.skipped,
+            TaskResult::Repo(r) => &r.skipped,
+            TaskResult::KernelModule(r) => &r.skipped,
+            TaskResult::FileSet(r) => &r.skipped,
+            TaskResult::BlockInFile(r) => &r.skipped,
+            TaskResult::HealthCheck(r) => &r.skipped,
+            TaskResult::Cgroup(r) => &r.skipped,
+        }
+    }
+
+    /// 每台主机的执行耗时
+    pub fn durations(&self) -> &HashMap<String, Duration> {
+        match self {
+            TaskResult::Command(r) => &r.durations,
+            TaskResult::Commands(r) => &r.durations,
+            TaskResult::CopyFile(r) => &r.durations,
+            TaskResult::Fetch(r) => &r.durations,
+            TaskResult::SystemInfo(r) => &r.durations,
+            TaskResult::Ping(r) => &r.durations,
+            TaskResult::User(r) => &r.durations,
+            TaskResult::Template(r) => &r.durations,
+            TaskResult::DnsConfig(r) => &r.durations,
+            TaskResult::LogRotate(r) => &r.durations,
+            TaskResult::Permissions(r) => &r.durations,
+            TaskResult::EnsureDirs(r) => &r.durations,
+            TaskResult::SshKeypair(r) => &r.durations,
+            TaskResult::Repo(r) => &r.durations,
+            TaskResult::KernelModule(r) => &r.durations,
+            TaskResult::FileSet(r) => &r.durations,
+            TaskResult::BlockInFile(r) => &r.durations,
+            TaskResult::HealthCheck(r) => &r.durations,
+            TaskResult::Cgroup(r) => &r.durations,
         }
     }
 
@@ -103,11 +414,24 @@ impl TaskResult {
         
         match self {
             TaskResult::Command(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::Commands(r) => Self::collect_failures(r, &mut failures),
             TaskResult::CopyFile(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::Fetch(r) => Self::collect_failures(r, &mut failures),
             TaskResult::SystemInfo(r) => Self::collect_failures(r, &mut failures),
             TaskResult::Ping(r) => Self::collect_failures(r, &mut failures),
             TaskResult::User(r) => Self::collect_failures(r, &mut failures),
             TaskResult::Template(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::DnsConfig(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::LogRotate(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::Permissions(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::EnsureDirs(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::SshKeypair(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::Repo(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::KernelModule(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::FileSet(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::BlockInFile(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::HealthCheck(r) => Self::collect_failures(r, &mut failures),
+            TaskResult::Cgroup(r) => Self::collect_failures(r, &mut failures),
         }
         
         failures
@@ -115,41 +439,336 @@ impl TaskResult {
 
     fn collect_failures<T>(result: &BatchResult<T>, failures: &mut Vec<(String, String)>) {
         for host in &result.failed {
-            if let Some(Err(e)) = result.results.get(host) {
+            if let Some(e) = result.error(host) {
                 failures.push((host.clone(), e.to_string()));
             }
         }
     }
 }
 
-#[derive(Debug)]
+fn record_skip(skips: &mut HashMap<String, Vec<(String, SkipReason)>>, host: String, task: &Task, reason: SkipReason) {
+    skips.entry(host).or_default().push((task.name.clone(), reason));
+}
+
+/// 本地任务在结果中使用的主机名
+pub const LOCALHOST: &str = "localhost";
+
+/// Playbook 运行期间在任务之间共享的上下文
+#[derive(Debug, Default, Clone)]
+pub struct ExecutionContext {
+    pub facts: HashMap<String, SystemInfo>,        // 按主机的 facts
+    pub vars: HashMap<String, serde_json::Value>,  // register 保存的变量
+}
+
+#[derive(Debug, Serialize)]
 pub struct PlaybookResult {
     pub playbook_name: String,
     pub task_results: Vec<(String, TaskResult)>,
     pub overall_success: bool,
     pub failed_hosts: HashSet<String>,  // 记录所有失败的主机
-    pub skipped_hosts: HashSet<String>, // 记录被跳过的主机
+    pub skipped_hosts: HashSet<String>, // 至少有一个任务因失败以外的原因（条件、limit 等）被跳过的主机
+    pub unreachable_hosts: HashSet<String>, // 最近一次连接失败的主机（之后恢复连接的主机会移出）
+    pub task_timings: Vec<TaskTiming>,  // 每个已执行任务的起止时间与各主机耗时
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<String>,          // 运行时生效的主机限制（`--limit` 语法）
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub(crate) skips: HashMap<String, Vec<(String, SkipReason)>>, // 主机 -> (任务名, 跳过原因)，按执行顺序
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resume_skipped_tasks: Vec<String>, // 从中间任务恢复执行时未执行的前置任务（按顺序）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub step_skipped_tasks: Vec<String>,   // 逐步执行时被确认回调跳过的任务（按顺序）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aborted_at: Option<String>,        // 逐步执行时在该任务前被中止，之后的任务均未执行
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) diffs: Vec<HostDiff>,       // 差异模式下各任务对文件的修改，按执行顺序
+}
+
+/// 差异模式下某个任务在某台主机上对远程文件的修改
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HostDiff {
+    pub host: String,
+    pub task: String,
+    pub path: String, // 远程文件路径
+    pub diff: String, // 统一 diff；二进制文件或文件过大时为说明文字
+}
+
+impl PlaybookResult {
+    /// 差异模式（`RunOptions::diff`）下所有文件修改的统一 diff，按任务执行顺序、同一任务内按主机名排列
+    pub fn collect_diffs(&self) -> Vec<HostDiff> {
+        self.diffs.clone()
+    }
+
+    /// 每台主机被跳过的任务及原因（按执行顺序），包括因之前失败而未执行的任务
+    pub fn skips(&self) -> &HashMap<String, Vec<(String, SkipReason)>> {
+        &self.skips
+    }
+
+    /// 失败主机组成的 limit 模式（按主机名排序），用于只重跑失败的主机；没有失败主机时返回 None
+    pub fn failed_hosts_pattern(&self) -> Option<String> {
+        if self.failed_hosts.is_empty() {
+            return None;
+        }
+        let mut hosts: Vec<&String> = self.failed_hosts.iter().collect();
+        hosts.sort();
+        Some(hosts.into_iter().cloned().collect::<Vec<_>>().join(","))
+    }
+
+    /// 写入 Ansible 风格的 `.retry` 文件：每行一个失败主机（按主机名排序）
+    pub fn write_retry_file(&self, path: impl AsRef<std::path::Path>) -> Result<(), AnsibleError> {
+        let mut content = self.failed_hosts_pattern().unwrap_or_default().replace(',', "\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        std::fs::write(path.as_ref(), content).map_err(|e| {
+            AnsibleError::FileOperationError(format!(
+                "Failed to write retry file {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })
+    }
+
+    /// 生成耗时报告（类似 Ansible 的 profile_tasks），包含最慢的 10 个主机/任务组合
+    pub fn timing_report(&self) -> TimingReport {
+        self.timing_report_with_slowest(DEFAULT_SLOWEST_COUNT)
+    }
+
+    /// 生成耗时报告，列出最慢的 `slowest` 个主机/任务组合
+    pub fn timing_report_with_slowest(&self, slowest: usize) -> TimingReport {
+        TimingReport::from_timings(&self.task_timings, slowest)
+    }
+
+    /// 生成 JUnit XML 报告（每个任务一个 testsuite），供 Jenkins / GitLab 等 CI 展示
+    pub fn to_junit_xml(&self) -> String {
+        self.to_junit_xml_grouped(JunitGrouping::ByTask)
+    }
+
+    /// 按指定方式分组生成 JUnit XML 报告
+    pub fn to_junit_xml_grouped(&self, grouping: JunitGrouping) -> String {
+        report::junit_xml(self, grouping)
+    }
+
+    /// 将 JUnit XML 报告写入文件
+    pub fn save_junit(&self, path: impl AsRef<std::path::Path>, grouping: JunitGrouping) -> Result<(), AnsibleError> {
+        std::fs::write(path.as_ref(), self.to_junit_xml_grouped(grouping)).map_err(|e| {
+            AnsibleError::FileOperationError(format!(
+                "Failed to write JUnit report {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })
+    }
+}
+
+/// 逐步执行时确认回调对每个任务的决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepAction {
+    Run,   // 执行该任务
+    Skip,  // 跳过该任务，继续下一个
+    Abort, // 停止执行，不再运行该任务及之后的任务
+}
+
+/// playbook 运行选项
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub limit: Option<String>,        // 只在匹配的主机上执行（语法同 inventory 主机模式）
+    pub exclude: Option<Vec<String>>, // 排除的主机（主机名、组名或通配符）
+    pub diff: bool,                   // 差异模式：template、copy、blockinfile 任务的主机结果附带统一 diff
+}
+
+impl RunOptions {
+    /// 以 `--limit` 语法描述生效的限制，例如 `web*,!web3`；未限制时返回 None
+    pub fn describe(&self) -> Option<String> {
+        let mut parts: Vec<String> = self.limit.iter().cloned().collect();
+        parts.extend(self.exclude.iter().flatten().map(|host| format!("!{}", host)));
+        (!parts.is_empty()).then(|| parts.join(","))
+    }
 }
 
 pub struct TaskExecutor<'a> {
     manager: &'a AnsibleManager,
+    callbacks: Vec<Arc<dyn ExecutionCallback>>, // 生命周期回调，按注册顺序调用
+    run_options: RunOptions,
+    groups: HashMap<String, Vec<String>>,       // 解析 limit/exclude 时可用的主机组
+    status: Option<SharedRunStatus>,            // 后台运行时对外公开的运行状态
+    state_file: Option<PathBuf>,                // 记录每个任务结果的状态文件
+    resume: bool,                               // 是否跳过状态文件中已成功且未修改的任务
 }
 
 impl<'a> TaskExecutor<'a> {
     pub fn new(manager: &'a AnsibleManager) -> Self {
-        Self { manager }
+        Self {
+            manager,
+            callbacks: Vec::new(),
+            run_options: RunOptions::default(),
+            groups: HashMap::new(),
+            status: None,
+            state_file: None,
+            resume: false,
+        }
+    }
+
+    /// 在任务边界把运行状态写入共享状态（供 `RunHandle::status` 读取）
+    pub(crate) fn with_status(mut self, status: SharedRunStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    fn update_status(&self, update: impl FnOnce(&mut RunStatus)) {
+        if let Some(ref status) = self.status {
+            update_status(status, update);
+        }
+    }
+
+    /// 设置运行选项（limit/exclude 在任务的目标主机确定后生效）
+    pub fn with_run_options(mut self, options: RunOptions) -> Self {
+        self.run_options = options;
+        self
+    }
+
+    /// 设置可在 limit/exclude 中使用的主机组（例如 inventory 中的 groups）
+    pub fn with_groups(mut self, groups: HashMap<String, Vec<String>>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// 任务的目标主机：`Task.hosts`（未指定时为全部主机）再应用 limit/exclude
+    pub fn target_hosts(&self, task: &Task) -> Result<Vec<String>, AnsibleError> {
+        let hosts = match task.hosts {
+            Some(ref specific_hosts) => specific_hosts.clone(),
+            None => self.manager.list_hosts(),
+        };
+        self.apply_limit(hosts)
+    }
+
+    /// 任务原本的目标主机中被 limit/exclude 排除的主机
+    fn limited_out_hosts(&self, task: &Task) -> Result<Vec<String>, AnsibleError> {
+        let targeted = self.target_hosts(task)?;
+        let candidates = match task.hosts {
+            Some(ref specific_hosts) => specific_hosts.clone(),
+            None => self.manager.list_hosts(),
+        };
+        Ok(candidates.into_iter().filter(|h| !targeted.contains(h)).collect())
+    }
+
+    fn apply_limit(&self, mut hosts: Vec<String>) -> Result<Vec<String>, AnsibleError> {
+        if self.run_options.limit.is_none() && self.run_options.exclude.is_none() {
+            return Ok(hosts);
+        }
+        let known = self.manager.list_hosts();
+        let groups = with_disabled_group(&self.groups, self.manager.list_disabled());
+        if let Some(ref limit) = self.run_options.limit {
+            let allowed = resolve_host_pattern(limit, known.iter(), &groups)?;
+            hosts.retain(|h| allowed.contains(h));
+        }
+        for pattern in self.run_options.exclude.iter().flatten() {
+            // 排除项不匹配任何主机时忽略
+            if let Ok(excluded) = resolve_host_pattern(pattern, known.iter(), &groups) {
+                hosts.retain(|h| !excluded.contains(h));
+            }
+        }
+        Ok(hosts)
+    }
+
+    /// 在每个任务结束后把各主机的结果写入状态文件（版本化的 JSON，见 [`RunStateFile`]）
+    ///
+    /// `resume` 为 true 时，文件中记录的、在某台主机上成功且报告未修改（changed 为 false）的任务在该主机上跳过，
+    /// 并以 `SkipReason::Cached` 报告；命令等不报告 changed 的任务总是执行。修改 playbook 中的任务
+    /// 或模板、复制任务的本地源文件会使该任务及之后任务的记录自动失效。
+    /// 为 false 时忽略已有记录并重新开始记录。被跳过任务的 `register` 变量不存在。
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>, resume: bool) -> Self {
+        self.state_file = Some(path.into());
+        self.resume = resume;
+        self
+    }
+
+    /// 注册执行生命周期回调（可注册多个，按注册顺序调用）
+    pub fn with_callback(mut self, callback: Arc<dyn ExecutionCallback>) -> Self {
+        self.callbacks.push(callback);
+        self
+    }
+
+    /// 注册执行生命周期回调（可变引用）
+    pub fn add_callback(&mut self, callback: Arc<dyn ExecutionCallback>) {
+        self.callbacks.push(callback);
     }
 
     /// 执行单个任务，排除已失败的主机
     pub async fn execute_task(&self, task: &Task, failed_hosts: &HashSet<String>) -> Result<TaskResult, AnsibleError> {
+        self.execute_task_with_context(task, failed_hosts, &ExecutionContext::default()).await
+    }
+
+    /// 执行单个任务，并使用运行上下文中的 facts 与已注册变量（用于模板渲染）
+    ///
+    /// 注册了回调时，主机连接失败后的每次重试都会在发生时通知 `on_host_retry`。
+    pub async fn execute_task_with_context(
+        &self,
+        task: &Task,
+        failed_hosts: &HashSet<String>,
+        context: &ExecutionContext,
+    ) -> Result<TaskResult, AnsibleError> {
+        if self.callbacks.is_empty() {
+            return self.run_task(self.manager, task, failed_hosts, context).await;
+        }
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let manager = self.manager.with_retry_events(sender);
+        let notify = |event: RetryEvent| {
+            callback::dispatch(&self.callbacks, "host_retry", |cb| {
+                cb.on_host_retry(&event.host, task, event.attempt, &event.error)
+            });
+        };
+        let run = self.run_task(&manager, task, failed_hosts, context);
+        tokio::pin!(run);
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result,
+                Some(event) = receiver.recv() => notify(event),
+            }
+        };
+        // 任务结束前已发送但尚未处理的事件
+        while let Ok(event) = receiver.try_recv() {
+            notify(event);
+        }
+        result
+    }
+
+    /// 在 `base` 管理器（或其任务级覆盖视图）上执行任务
+    async fn run_task(
+        &self,
+        base: &AnsibleManager,
+        task: &Task,
+        failed_hosts: &HashSet<String>,
+        context: &ExecutionContext,
+    ) -> Result<TaskResult, AnsibleError> {
         info!("Executing task: {}", task.name);
 
-        let all_hosts = if let Some(ref specific_hosts) = task.hosts {
-            specific_hosts.clone()
+        // 本地任务不按主机展开，在控制机上执行一次
+        if let TaskType::LocalCommand { cmd } = &task.task_type {
+            let cmd = cmd.clone();
+            let started = Instant::now();
+            let local_result = tokio::task::spawn_blocking(move || run_local_command(&cmd))
+                .await
+                .map_err(|e| AnsibleError::CommandExecutionError(format!("Local command task failed: {}", e)))?;
+            let mut batch_result = BatchResult::new();
+            batch_result.record_duration(LOCALHOST, started.elapsed());
+            batch_result.add_result(LOCALHOST.to_string(), local_result);
+            return Ok(TaskResult::Command(batch_result));
+        }
+
+        // 任务级 become/用户/端口覆盖只作用于本任务：使用临时的管理器视图，不修改原有主机配置
+        let scoped_manager;
+        let manager = if task.has_become_override() || task.has_connection_override() {
+            scoped_manager = base
+                .with_become_override(task.r#become, task.become_user.clone())
+                .with_connection_override(task.remote_user.clone(), task.port);
+            &scoped_manager
         } else {
-            self.manager.list_hosts().into_iter().cloned().collect()
+            base
         };
 
+        let all_hosts = self.target_hosts(task)?;
+
         // 过滤掉已失败的主机
         let active_hosts: Vec<String> = all_hosts
             .iter()
@@ -175,74 +794,180 @@ impl<'a> TaskExecutor<'a> {
 
         if active_hosts.is_empty() {
             warn!("No active hosts available for task '{}'", task.name);
-            // 返回一个空的结果，表示所有主机都被跳过
-            let mut batch_result = BatchResult::new();
-            for host in skipped_hosts {
-                batch_result.add_result(
-                    host,
-                    Err(AnsibleError::SshConnectionError("Host skipped due to previous failure".to_string()))
-                );
-            }
-            return Ok(TaskResult::Ping(batch_result));
+            // 返回一个所有主机都被跳过的结果（保持该任务自身的结果类型）
+            return Ok(TaskResult::skipped_for(&task.task_type, skipped_hosts, SkipReason::PreviousFailure));
         }
 
         let result = match &task.task_type {
             TaskType::Command { cmd } => {
-                let batch_result = self.manager.execute_command_on_hosts(cmd, &active_hosts).await;
+                let batch_result = manager.execute_command_on_hosts(cmd, &active_hosts).await;
                 TaskResult::Command(batch_result)
             }
+            TaskType::Commands { commands, options } => {
+                let batch_result = manager
+                    .execute_commands_on_hosts_with_options(commands, &active_hosts, options)
+                    .await;
+                TaskResult::Commands(batch_result)
+            }
             TaskType::CopyFile { src, dest, options } => {
-                let batch_result = if let Some(opts) = options {
-                    self.manager.copy_file_to_hosts_with_options(src, dest, &active_hosts, opts).await
+                let batch_result = if self.run_options.diff {
+                    let opts = FileCopyOptions {
+                        diff: true,
+                        ..options.clone().unwrap_or_default()
+                    };
+                    manager.copy_file_to_hosts_with_options(src, dest, &active_hosts, &opts).await
+                } else if let Some(opts) = options {
+                    manager.copy_file_to_hosts_with_options(src, dest, &active_hosts, opts).await
                 } else {
-                    self.manager.copy_file_to_hosts(src, dest, &active_hosts).await
+                    manager.copy_file_to_hosts(src, dest, &active_hosts).await
                 };
                 TaskResult::CopyFile(batch_result)
             }
+            TaskType::Fetch { src, dest } => {
+                TaskResult::Fetch(manager.fetch_file_from_hosts_verified(src, dest, &active_hosts).await)
+            }
             TaskType::GetSystemInfo => {
-                let batch_result = self.manager.get_system_info_from_hosts(&active_hosts).await;
+                let batch_result = manager.get_system_info_from_hosts(&active_hosts).await;
                 TaskResult::SystemInfo(batch_result)
             }
             TaskType::Ping => {
-                let batch_result = self.manager.ping_hosts(&active_hosts).await;
+                let batch_result = manager.ping_hosts(&active_hosts).await;
                 TaskResult::Ping(batch_result)
             }
             TaskType::User { options } => {
-                let batch_result = self.manager.manage_user_on_hosts(options, &active_hosts).await;
+                let batch_result = manager.manage_user_on_hosts(options, &active_hosts).await;
                 TaskResult::User(batch_result)
             }
             TaskType::Template { options } => {
-                let batch_result = self.manager.deploy_template_to_hosts(options, &active_hosts).await;
-                TaskResult::Template(batch_result)
+                // 注入已注册的变量（不覆盖任务中显式指定的变量）
+                let mut options = options.clone();
+                for (key, value) in &context.vars {
+                    options.variables.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+                let batch_result = if context.facts.is_empty() {
+                    manager.deploy_template_to_hosts(&options, &active_hosts).await
+                } else {
+                    manager.deploy_template_to_hosts_with_facts(&options, &active_hosts, &context.facts).await
+                };
+                TaskResult::Template(fail_unsuccessful_templates(batch_result))
             }
-            TaskType::Shell { script } => {
+            TaskType::LogRotate { config_file, force } => {
+                let batch_result = manager
+                    .rotate_logs_on_hosts(config_file.as_deref(), *force, &active_hosts)
+                    .await;
+                TaskResult::LogRotate(batch_result)
+            }
+            TaskType::LocalCommand { .. } => unreachable!("local commands are handled before host fan-out"),
+            TaskType::DnsConfig { config } => {
+                let batch_result = manager.set_dns_config_on_hosts(config, &active_hosts).await;
+                TaskResult::DnsConfig(batch_result)
+            }
+            TaskType::Permissions { options } => {
+                let batch_result = manager.set_permissions_on_hosts(options, &active_hosts).await;
+                TaskResult::Permissions(batch_result)
+            }
+            TaskType::EnsureDirs { dirs } => {
+                TaskResult::EnsureDirs(manager.ensure_dirs_on_hosts(dirs, &active_hosts).await)
+            }
+            TaskType::SshKeypair { path, type_, bits, comment } => {
+                let batch_result = manager
+                    .generate_ssh_keypair_on_hosts(
+                        path,
+                        *type_,
+                        bits.unwrap_or_else(|| type_.default_bits()),
+                        comment.as_deref().unwrap_or(""),
+                        &active_hosts,
+                    )
+                    .await;
+                TaskResult::SshKeypair(batch_result)
+            }
+            TaskType::Repo { config, state } => {
+                let batch_result = manager.manage_repo_on_hosts(config, state.clone(), &active_hosts).await;
+                TaskResult::Repo(batch_result)
+            }
+            TaskType::KernelModule { config, state } => {
+                let batch_result = manager
+                    .manage_kernel_module_on_hosts(config, state.clone(), &active_hosts)
+                    .await;
+                TaskResult::KernelModule(batch_result)
+            }
+            TaskType::FileSet { files } => {
+                let batch_result = manager.deploy_fileset_on_hosts(files, &active_hosts).await;
+                TaskResult::FileSet(batch_result)
+            }
+            TaskType::BlockInFile { options } => {
+                let batch_result = manager.block_in_file_on_hosts(options, &active_hosts).await;
+                TaskResult::BlockInFile(batch_result)
+            }
+            TaskType::HealthCheck { probes, requirements } => {
+                let probe_result = manager.run_health_checks_on_hosts(probes, requirements, &active_hosts).await;
+                TaskResult::HealthCheck(fail_unhealthy_hosts(probe_result))
+            }
+            TaskType::Cgroup { config } => {
+                let batch_result = manager.create_cgroup_on_hosts(config, &active_hosts).await;
+                TaskResult::Cgroup(batch_result)
+            }
+            TaskType::Shell { script, creates, removes, chdir, keep_temp_on_failure } => {
+                let mut batch_result = BatchResult::new();
+
+                // creates/removes 守卫：先在各主机上检查标记文件，决定哪些主机需要执行
+                let run_hosts = match shell_guard_command(creates.as_deref(), removes.as_deref()) {
+                    Some(guard_cmd) => {
+                        let guard_result = manager.execute_command_on_hosts(&guard_cmd, &active_hosts).await;
+                        batch_result.add_durations_from(&guard_result);
+                        batch_result.add_metrics_from(&guard_result);
+                        let mut run_hosts = Vec::new();
+                        for host in &active_hosts {
+                            match guard_result.results.get(host) {
+                                Some(HostOutcome::Ok { value: r, .. }) if r.stdout.trim() == "skip" => {
+                                    info!("Skipping task '{}' on host '{}': creates/removes condition met", task.name, host);
+                                    batch_result.add_skipped(host.clone(), SkipReason::Condition);
+                                }
+                                Some(HostOutcome::Ok { .. }) => run_hosts.push(host.clone()),
+                                Some(HostOutcome::Failed(e) | HostOutcome::Unreachable(e)) => batch_result.add_result(
+                                    host.clone(),
+                                    Err(AnsibleError::CommandExecutionError(format!(
+                                        "Failed to evaluate creates/removes condition: {}",
+                                        e
+                                    ))),
+                                ),
+                                Some(HostOutcome::Skipped(reason)) => batch_result.add_skipped(host.clone(), *reason),
+                                None => {}
+                            }
+                        }
+                        run_hosts
+                    }
+                    None => active_hosts.clone(),
+                };
+
+                if run_hosts.is_empty() {
+                    return Ok(TaskResult::Command(batch_result));
+                }
+
                 // 创建临时脚本文件并执行（使用统一的工具函数生成唯一路径）
                 let script_path = generate_remote_temp_path("/tmp/rs_ansible_script.sh");
-                let temp_file = generate_local_temp_path("rs_ansible_local_script");
                 
                 // 确保脚本使用 Unix 换行符 (\n)，避免在 Windows 上生成 \r\n 导致执行失败
                 let script_unix = script.replace('\r', "");
                 
-                // 写入本地临时文件
-                std::fs::write(&temp_file, script_unix)
-                    .map_err(|e| AnsibleError::FileOperationError(format!("Failed to create script file: {}", e)))?;
+                // 本地脚本只写一次并在所有主机间复用，任务结束（包括出错返回）时自动删除
+                let local_script = LocalTempFile::create("rs_ansible_local_script", script_unix.as_bytes())?;
+                let copy_options = FileCopyOptions {
+                    precomputed_hash: Some(local_script.hash().to_string()),
+                    keep_temp_on_failure: *keep_temp_on_failure,
+                    ..Default::default()
+                };
 
-                // 复制脚本到远程主机
-                let copy_result = self.manager.copy_file_to_hosts(&temp_file, &script_path, &active_hosts).await;
-                
-                // 如果复制成功，执行脚本
-                if copy_result.success_rate() > 0.0 {
-                    let exec_cmd = format!("chmod +x {} && {}", script_path, script_path);
-                    let batch_result = self.manager.execute_command_on_hosts(&exec_cmd, &active_hosts).await;
-                    
-                    // 清理远程脚本文件
-                    let cleanup_cmd = format!("rm -f {}", script_path);
-                    let _ = self.manager.execute_command_on_hosts(&cleanup_cmd, &active_hosts).await;
-                    
-                    TaskResult::Command(batch_result)
-                } else {
-                    return Err(AnsibleError::FileOperationError(format!("Failed to copy script to remote hosts: Reason: {:?}", copy_result.results)));
+                // 每台主机在同一连接上上传并执行脚本；复制失败的主机记为失败且不会执行
+                let script_result = manager
+                    .run_script_on_hosts(local_script.path(), &script_path, &copy_options, chdir.as_deref(), &run_hosts)
+                    .await;
+                batch_result.add_durations_from(&script_result);
+                batch_result.add_metrics_from(&script_result);
+                for (host, host_result) in script_result.results {
+                    batch_result.add_outcome(host, host_result);
                 }
+                TaskResult::Command(batch_result)
             }
         };
 
@@ -250,39 +975,424 @@ impl<'a> TaskExecutor<'a> {
     }
 
     /// 执行整个Playbook，支持主机级别的失败追踪
+    ///
+    /// 整个运行位于 `playbook` span 中，每个任务位于带 `task` 字段的子 span 中。
     pub async fn execute_playbook(&self, playbook: &Playbook) -> Result<PlaybookResult, AnsibleError> {
+        self.execute_playbook_from_index(playbook, 0).await
+    }
+
+    /// 从第一个名为 `task_name` 的任务开始执行 Playbook，之前的任务不执行（同 `ansible-playbook --start-at-task`）
+    ///
+    /// facts 照常收集；被跳过任务的 `register` 变量不存在，依赖它们的条件按未定义变量处理。
+    pub async fn execute_playbook_from_task(
+        &self,
+        playbook: &Playbook,
+        task_name: &str,
+    ) -> Result<PlaybookResult, AnsibleError> {
+        let index = playbook.tasks.iter().position(|task| task.name == task_name).ok_or_else(|| {
+            AnsibleError::ValidationError(format!(
+                "Task '{}' not found in playbook '{}'",
+                task_name, playbook.name
+            ))
+        })?;
+        self.execute_playbook_from_index(playbook, index).await
+    }
+
+    /// 从下标为 `index` 的任务（从 0 开始）开始执行 Playbook，之前的任务记录在
+    /// [`PlaybookResult::resume_skipped_tasks`] 中
+    pub async fn execute_playbook_from_index(
+        &self,
+        playbook: &Playbook,
+        index: usize,
+    ) -> Result<PlaybookResult, AnsibleError> {
+        self.execute_playbook_with(playbook, index, |_| StepAction::Run).await
+    }
+
+    /// 逐步执行 Playbook（同 `ansible-playbook --step`）：每个任务执行前调用 `confirm` 决定执行、跳过或中止
+    ///
+    /// 目标主机全部被 limit 排除的任务不会触发确认。跳过的任务记录在 [`PlaybookResult::step_skipped_tasks`] 中；
+    /// 中止时返回已执行任务的结果，`aborted_at` 为中止时的任务，`overall_success` 为 false。
+    pub async fn execute_playbook_step<F>(&self, playbook: &Playbook, confirm: F) -> Result<PlaybookResult, AnsibleError>
+    where
+        F: FnMut(&Task) -> StepAction,
+    {
+        self.execute_playbook_with(playbook, 0, confirm).await
+    }
+
+    #[instrument(name = "playbook", skip_all, fields(playbook = %playbook.name))]
+    async fn execute_playbook_with<F>(
+        &self,
+        playbook: &Playbook,
+        start_at: usize,
+        confirm: F,
+    ) -> Result<PlaybookResult, AnsibleError>
+    where
+        F: FnMut(&Task) -> StepAction,
+    {
+        let result = self.run_playbook(playbook, start_at, confirm).await;
+        self.update_status(|status| {
+            status.current_task = None;
+            status.state = match result {
+                Ok(ref r) if r.overall_success => RunState::Completed,
+                Err(AnsibleError::Cancelled(_)) => RunState::Cancelled,
+                _ => RunState::Failed,
+            };
+        });
+        result
+    }
+
+    async fn run_playbook<F>(
+        &self,
+        playbook: &Playbook,
+        start_at: usize,
+        mut confirm: F,
+    ) -> Result<PlaybookResult, AnsibleError>
+    where
+        F: FnMut(&Task) -> StepAction,
+    {
+        if start_at > 0 && start_at >= playbook.tasks.len() {
+            return Err(AnsibleError::ValidationError(format!(
+                "Task index {} out of range: playbook '{}' has {} task(s)",
+                start_at,
+                playbook.name,
+                playbook.tasks.len()
+            )));
+        }
         info!("Starting playbook execution: {}", playbook.name);
+        let cancellation = self.manager.cancellation();
+        let cancelled = |task: &Task| {
+            warn!("Playbook '{}' cancelled before task '{}' completed", playbook.name, task.name);
+            AnsibleError::Cancelled(format!("playbook '{}' stopped at task '{}'", playbook.name, task.name))
+        };
 
         let mut task_results = Vec::new();
         let mut overall_success = true;
         let mut failed_hosts: HashSet<String> = HashSet::new();
+        let mut unreachable_hosts: HashSet<String> = HashSet::new();
+        let mut context = ExecutionContext::default();
+        let mut task_timings = Vec::new();
+        let mut skips: HashMap<String, Vec<(String, SkipReason)>> = HashMap::new();
+        let mut diffs = Vec::new();
+        callback::dispatch(&self.callbacks, "playbook_start", |cb| cb.on_playbook_start(playbook));
+
+        let thresholds = std::iter::once((playbook.name.as_str(), playbook.success_threshold))
+            .chain(playbook.tasks.iter().filter_map(|t| t.success_threshold.map(|v| (t.name.as_str(), v))));
+        for (name, threshold) in thresholds {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(AnsibleError::ValidationError(format!(
+                    "Invalid success_threshold {} for '{}': must be between 0.0 and 1.0",
+                    threshold, name
+                )));
+            }
+        }
+
+        // 先解析一次 limit，模式错误时在执行任何任务之前返回
+        let limit = self.run_options.describe();
+        let limited_hosts = self.apply_limit(self.manager.list_hosts())?;
+        if let Some(ref limit) = limit {
+            info!("Limiting run to {} host(s) ({})", limited_hosts.len(), limit);
+        }
+
+        // 在执行任务前统一收集一次 facts，整个运行期间复用
+        if playbook.gather_facts {
+            let all_hosts = limited_hosts;
+            info!("Gathering facts from {} host(s)", all_hosts.len());
+            let gathered = self
+                .manager
+                .get_system_info_subset_from_hosts(playbook.fact_subset, &all_hosts)
+                .await;
+            for (host, result) in gathered.results {
+                match result {
+                    HostOutcome::Ok { value: info, .. } => {
+                        context.facts.insert(host, info);
+                    }
+                    HostOutcome::Skipped(_) => {}
+                    HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => {
+                        warn!("Failed to gather facts from host '{}': {}, host will be skipped", host, e);
+                        if e.is_unreachable() {
+                            unreachable_hosts.insert(host.clone());
+                        }
+                        failed_hosts.insert(host);
+                    }
+                }
+            }
+        }
+
+        let mut state = match self.state_file {
+            Some(ref path) => {
+                let previous = if self.resume { RunStateFile::load(path)? } else { None };
+                let state = RunStateFile::for_playbook(playbook, previous);
+                state.save(path)?;
+                Some(state)
+            }
+            None => None,
+        };
+
+        let (resumed, tasks) = playbook.tasks.split_at(start_at);
+        let resume_skipped_tasks: Vec<String> = resumed.iter().map(|task| task.name.clone()).collect();
+        let mut step_skipped_tasks = Vec::new();
+        let mut aborted_at = None;
+        if let Some(first) = tasks.first().filter(|_| start_at > 0) {
+            info!("Resuming playbook at task '{}', skipping {} task(s)", first.name, start_at);
+            self.update_status(|status| status.tasks_completed = start_at);
+        }
+
+        for (index, task) in tasks.iter().enumerate().map(|(offset, task)| (start_at + offset, task)) {
+            let is_local = matches!(task.task_type, TaskType::LocalCommand { .. });
+            if limit.is_some() && !is_local {
+                for host in self.limited_out_hosts(task)? {
+                    record_skip(&mut skips, host, task, SkipReason::Limit);
+                }
+            }
+            // 目标主机全部被 limit/exclude 排除的任务不执行
+            if limit.is_some() && self.target_hosts(task)?.is_empty() {
+                info!("Skipping task '{}': no target host within the limit", task.name);
+                continue;
+            }
+            if cancellation.is_some_and(|token| token.is_cancelled()) {
+                return Err(cancelled(task));
+            }
+
+            // 状态文件中已成功且未修改的主机不再执行本任务（本次运行中已失败的主机照常按失败跳过）
+            let cached: Vec<String> = match state {
+                Some(ref state) if !is_local => {
+                    let recorded = state.cached_hosts(index);
+                    self.target_hosts(task)?
+                        .into_iter()
+                        .filter(|h| recorded.contains(h) && !failed_hosts.contains(h))
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+            let report_cached = |skips: &mut HashMap<String, Vec<(String, SkipReason)>>| {
+                let outcome = TaskOutcome::skipped(SkipReason::Cached);
+                for host in &cached {
+                    callback::dispatch(&self.callbacks, "host_result", |cb| cb.on_host_result(host, task, &outcome));
+                    record_skip(skips, host.clone(), task, SkipReason::Cached);
+                }
+            };
+            let remaining: Vec<String> = if cached.is_empty() {
+                Vec::new()
+            } else {
+                self.target_hosts(task)?.into_iter().filter(|h| !cached.contains(h)).collect()
+            };
+            if !cached.is_empty() && remaining.is_empty() {
+                info!("Skipping task '{}': cached result for all {} host(s)", task.name, cached.len());
+                callback::dispatch(&self.callbacks, "task_start", |cb| cb.on_task_start(task));
+                report_cached(&mut skips);
+                self.update_status(|status| status.tasks_completed += 1);
+                continue;
+            }
+
+            match confirm(task) {
+                StepAction::Run => {}
+                StepAction::Skip => {
+                    info!("Task '{}' skipped by step confirmation", task.name);
+                    step_skipped_tasks.push(task.name.clone());
+                    self.update_status(|status| status.tasks_completed += 1);
+                    continue;
+                }
+                StepAction::Abort => {
+                    warn!("Playbook '{}' aborted before task '{}'", playbook.name, task.name);
+                    aborted_at = Some(task.name.clone());
+                    overall_success = false;
+                    break;
+                }
+            }
+            callback::dispatch(&self.callbacks, "task_start", |cb| cb.on_task_start(task));
+            let uncached_task;
+            let run_task = if cached.is_empty() {
+                task
+            } else {
+                info!("Task '{}': using cached result for {} host(s)", task.name, cached.len());
+                report_cached(&mut skips);
+                uncached_task = task.clone().on_hosts(remaining);
+                &uncached_task
+            };
+            if self.status.is_some() {
+                let running: Vec<String> = match task.task_type {
+                    TaskType::LocalCommand { .. } => Vec::new(),
+                    _ => self.target_hosts(run_task)?.into_iter().filter(|h| !failed_hosts.contains(h)).collect(),
+                };
+                self.update_status(|status| {
+                    status.current_task = Some(task.name.clone());
+                    for host in running {
+                        status.hosts.insert(host, HostRunStatus::Running { task: task.name.clone() });
+                    }
+                });
+            }
+            let previously_failed: Vec<String> = if is_local {
+                Vec::new()
+            } else {
+                self.target_hosts(task)?.into_iter().filter(|h| failed_hosts.contains(h)).collect()
+            };
+            let started_at = Utc::now();
+            let started = Instant::now();
+            let task_future = self
+                .execute_task_with_context(run_task, &failed_hosts, &context)
+                .instrument(info_span!("task", task = %task.name));
+            // 取消时不再等待本任务：已在执行的阻塞调用在后台完成，结果被丢弃
+            let outcome = match cancellation {
+                Some(token) => tokio::select! {
+                    outcome = task_future => outcome,
+                    _ = token.cancelled() => return Err(cancelled(task)),
+                },
+                None => task_future.await,
+            };
+            metrics::record_task(
+                &task.name,
+                started.elapsed(),
+                &outcome.as_ref().map(|r| r.failed_hosts().clone()).unwrap_or_default(),
+            );
+            task_timings.push(TaskTiming {
+                task_name: task.name.clone(),
+                started_at,
+                finished_at: Utc::now(),
+                host_durations: outcome.as_ref().map(|r| r.durations().clone()).unwrap_or_default(),
+            });
 
-        for task in &playbook.tasks {
-            match self.execute_task(task, &failed_hosts).await {
+            match &outcome {
                 Ok(result) => {
-                    let success = result.success_rate() > 0.0;
-                    let task_failed_hosts = result.failed_hosts();
-                    let task_successful_hosts = result.successful_hosts();
-                    
-                    // 记录本次任务失败的主机（不包括ignore_errors的任务）
-                    if !task.ignore_errors {
-                        for host in task_failed_hosts {
-                            if !failed_hosts.contains(host) {
-                                info!("Host '{}' failed on task '{}', will be skipped in subsequent tasks", 
-                                      host, task.name);
-                                failed_hosts.insert(host.clone());
+                    let mut host_outcomes = callback::host_outcomes(result);
+                    // 非差异模式下不报告 diff（template、blockinfile 总会计算 diff）
+                    let diff_path = if self.run_options.diff { task.task_type.diff_path() } else { None };
+                    let mut task_diffs = Vec::new();
+                    for (host, host_outcome) in host_outcomes.iter_mut() {
+                        match (diff_path, host_outcome.diff.as_ref()) {
+                            (Some(path), Some(diff)) => task_diffs.push(HostDiff {
+                                host: host.clone(),
+                                task: task.name.clone(),
+                                path: path.to_string(),
+                                diff: diff.clone(),
+                            }),
+                            (None, _) => host_outcome.diff = None,
+                            _ => {}
+                        }
+                    }
+                    task_diffs.sort_by(|a, b| a.host.cmp(&b.host));
+                    diffs.extend(task_diffs);
+                    for (host, host_outcome) in &host_outcomes {
+                        callback::dispatch(&self.callbacks, "host_result", |cb| {
+                            cb.on_host_result(host, task, host_outcome)
+                        });
+                    }
+                    // 之前失败的主机只在所有主机都被跳过时出现在任务结果中
+                    let reported: HashSet<&String> = host_outcomes.iter().map(|(host, _)| host).collect();
+                    for host in previously_failed.iter().filter(|h| !reported.contains(h)) {
+                        record_skip(&mut skips, host.clone(), task, SkipReason::PreviousFailure);
+                    }
+                    for (host, host_outcome) in &host_outcomes {
+                        if let Some(reason) = host_outcome.skip_reason {
+                            record_skip(&mut skips, host.clone(), task, reason);
+                        }
+                    }
+                    if let (Some(state), Some(path)) = (state.as_mut(), self.state_file.as_ref())
+                        && !is_local
+                    {
+                        state.record(index, &host_outcomes);
+                        if let Err(e) = state.save(path) {
+                            warn!("Failed to update state file after task '{}': {}", task.name, e);
+                        }
+                    }
+                    self.update_status(|status| {
+                        for (host, host_outcome) in host_outcomes {
+                            // 本地任务的结果记在 localhost 上，不属于 inventory 主机
+                            if let Some(entry) = status.hosts.get_mut(&host) {
+                                *entry = HostRunStatus::Finished { task: task.name.clone(), status: host_outcome.status };
                             }
                         }
-                    } else if !task_failed_hosts.is_empty() {
+                    });
+                }
+                Err(e) => callback::dispatch(&self.callbacks, "task_error", |cb| cb.on_task_error(task, e)),
+            }
+            self.update_status(|status| status.tasks_completed += 1);
+
+            match outcome {
+                Ok(result) => {
+                    // 显式的 system_info 任务会刷新对应主机的 facts
+                    if let TaskResult::SystemInfo(ref batch) = result {
+                        for (host, info) in &batch.results {
+                            if let Some(info) = info.value() {
+                                context.facts.insert(host.clone(), info.clone());
+                            }
+                        }
+                    }
+
+                    if let Some(ref var_name) = task.register {
+                        let value = Self::register_value(task, &result);
+                        info!("Registered result of task '{}' as '{}'", task.name, var_name);
+                        context.vars.insert(var_name.clone(), value);
+                    }
+
+                    let task_failed_hosts = result.failed_hosts();
+                    let task_successful_hosts = result.successful_hosts();
+
+                    if !result.skipped_hosts().is_empty() {
                         info!(
-                            "Task '{}' failed on {} host(s) but errors are ignored: {}",
+                            "Task '{}' skipped on {} host(s) by condition: {}",
                             task.name,
-                            task_failed_hosts.len(),
-                            task_failed_hosts.join(", ")
+                            result.skipped_hosts().len(),
+                            result.skipped_hosts().join(", ")
                         );
                     }
                     
-                    if !success && !task.ignore_errors {
+                    // 不可达的主机由 ignore_unreachable 决定是否忽略，其余失败由 ignore_errors 决定
+                    let task_unreachable_hosts = result.unreachable_hosts();
+                    let is_ignored = |host: &String| {
+                        if task_unreachable_hosts.contains(host) {
+                            task.ignore_unreachable
+                        } else {
+                            task.ignore_errors
+                        }
+                    };
+                    // 已忽略的失败不计入成功率；没有计入的主机（例如全部被跳过）时任务视为成功
+                    let counted_failures = task_failed_hosts.iter().filter(|h| !is_ignored(h)).count();
+                    let threshold = task.success_threshold.unwrap_or(playbook.success_threshold);
+                    let success = counted_failures == 0
+                        || task_successful_hosts.len() as f32
+                            / (task_successful_hosts.len() + counted_failures) as f32
+                            >= threshold;
+                    let all_failed = counted_failures > 0 && task_successful_hosts.is_empty();
+
+                    // 重新连上的主机不再视为不可达
+                    for host in task_successful_hosts {
+                        if unreachable_hosts.remove(host) {
+                            info!("Host '{}' is reachable again", host);
+                        }
+                    }
+
+                    // 记录本次任务失败的主机（已忽略的失败除外）
+                    for host in task_failed_hosts {
+                        // 之前已失败的主机在本任务中只是被跳过
+                        if failed_hosts.contains(host) {
+                            continue;
+                        }
+                        let unreachable = task_unreachable_hosts.contains(host);
+                        if unreachable {
+                            unreachable_hosts.insert(host.clone());
+                        }
+                        if is_ignored(host) {
+                            info!(
+                                "Host '{}' {} on task '{}' but this is ignored",
+                                host,
+                                if unreachable { "was unreachable" } else { "failed" },
+                                task.name
+                            );
+                        } else {
+                            info!("Host '{}' failed on task '{}', will be skipped in subsequent tasks",
+                                  host, task.name);
+                            failed_hosts.insert(host.clone());
+                        }
+                    }
+
+                    if !success {
+                        warn!(
+                            "Task '{}' succeeded on {}/{} host(s), below the success threshold of {:.0}%",
+                            task.name,
+                            task_successful_hosts.len(),
+                            task_successful_hosts.len() + counted_failures,
+                            threshold * 100.0
+                        );
                         overall_success = false;
                     }
                     
@@ -299,7 +1409,7 @@ impl<'a> TaskExecutor<'a> {
                     task_results.push((task.name.clone(), result));
                     
                     // 如果所有主机都失败了且不忽略错误，停止执行
-                    if !success && !task.ignore_errors {
+                    if all_failed {
                         info!("All hosts failed on task '{}', stopping playbook execution", task.name);
                         break;
                     }
@@ -314,16 +1424,73 @@ impl<'a> TaskExecutor<'a> {
             }
         }
 
-        // 统计最终被跳过的主机
-        let skipped_hosts = failed_hosts.clone();
+        // 只因之前失败而未执行的主机已记录在 failed_hosts 中，不计入 skipped_hosts
+        let skipped_hosts = skips
+            .iter()
+            .filter(|(_, host_skips)| host_skips.iter().any(|(_, reason)| *reason != SkipReason::PreviousFailure))
+            .map(|(host, _)| host.clone())
+            .collect();
 
-        Ok(PlaybookResult {
+        let result = PlaybookResult {
             playbook_name: playbook.name.clone(),
             task_results,
             overall_success,
             failed_hosts,
             skipped_hosts,
-        })
+            unreachable_hosts,
+            task_timings,
+            limit,
+            skips,
+            resume_skipped_tasks,
+            step_skipped_tasks,
+            aborted_at,
+            diffs,
+        };
+        callback::dispatch(&self.callbacks, "playbook_end", |cb| cb.on_playbook_end(&result));
+        Ok(result)
+    }
+
+    /// 将任务结果转换为可注册的变量值
+    ///
+    /// 本地任务注册其 `CommandResult`；远程任务注册 `主机名 -> 结果` 的映射，
+    /// 失败的主机记录为 `{"failed": true, "msg": ...}`（不可达时另有 `"unreachable": true`），
+    /// 跳过的主机记录为 `{"skipped": true, "reason": ...}`。
+    fn register_value(task: &Task, result: &TaskResult) -> serde_json::Value {
+        let mut per_host = serde_json::Map::new();
+        if let Ok(serde_json::Value::Object(variant)) = serde_json::to_value(result)
+            && let Some(serde_json::Value::Object(batch)) = variant.into_values().next()
+            && let Some(serde_json::Value::Object(results)) = batch.get("results")
+        {
+            for (host, host_result) in results {
+                let Some((status, detail)) = host_result.as_object().and_then(|r| r.iter().next()) else {
+                    continue;
+                };
+                let value = match status.as_str() {
+                    "ok" => detail.get("value").cloned().unwrap_or_default(),
+                    "skipped" => serde_json::json!({ "skipped": true, "reason": detail }),
+                    _ => {
+                        // 错误序列化为 {"<错误类型>": "<信息>"}
+                        let msg = detail
+                            .as_object()
+                            .and_then(|e| e.values().next())
+                            .cloned()
+                            .unwrap_or_default();
+                        let mut value = serde_json::json!({ "failed": true, "msg": msg });
+                        if status == "unreachable" {
+                            value["unreachable"] = serde_json::Value::Bool(true);
+                        }
+                        value
+                    }
+                };
+                per_host.insert(host.clone(), value);
+            }
+        }
+
+        if matches!(task.task_type, TaskType::LocalCommand { .. }) {
+            per_host.remove(LOCALHOST).unwrap_or_default()
+        } else {
+            serde_json::Value::Object(per_host)
+        }
     }
 
     /// 从YAML文件加载并执行Playbook
@@ -331,92 +1498,215 @@ impl<'a> TaskExecutor<'a> {
         let content = std::fs::read_to_string(&path)
             .map_err(|e| AnsibleError::FileOperationError(format!("Failed to read playbook file: {}", e)))?;
         
-        let playbook: Playbook = serde_yaml::from_str(&content)
-            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to parse playbook YAML: {}", e)))?;
+        // 同时支持完整 Playbook 与仅包含任务列表的 YAML
+        let default_name = path
+            .as_ref()
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "playbook".to_string());
+        let playbook = Playbook::from_yaml_str(&content, &default_name)?;
 
         self.execute_playbook(&playbook).await
     }
 }
 
+/// 根据 creates/removes 生成守卫检查命令，需要跳过时输出 `skip`
+pub(crate) fn shell_guard_command(creates: Option<&str>, removes: Option<&str>) -> Option<String> {
+    let mut conditions = Vec::new();
+    if let Some(path) = creates {
+        conditions.push(format!("[ -e {} ]", shell_quote(path)));
+    }
+    if let Some(path) = removes {
+        conditions.push(format!("[ ! -e {} ]", shell_quote(path)));
+    }
+    if conditions.is_empty() {
+        return None;
+    }
+    Some(format!("if {}; then echo skip; fi", conditions.join(" || ")))
+}
+
+/// 有探针未通过的主机在 playbook 中视为失败，错误信息列出未通过的探针
+fn fail_unhealthy_hosts(probe_result: BatchResult<Vec<HealthProbeResult>>) -> BatchResult<Vec<HealthProbeResult>> {
+    let mut batch_result = BatchResult::new();
+    batch_result.add_durations_from(&probe_result);
+    batch_result.add_metrics_from(&probe_result);
+    for (host, outcome) in probe_result.results {
+        let outcome = match outcome {
+            HostOutcome::Ok { value: probes, .. } => {
+                let failed: Vec<&str> = probes.iter().filter(|p| !p.passed).map(|p| p.name.as_str()).collect();
+                if failed.is_empty() {
+                    HostOutcome::ok(probes)
+                } else {
+                    HostOutcome::Failed(AnsibleError::CommandError(format!(
+                        "Health probes failed: {}",
+                        failed.join(", ")
+                    )))
+                }
+            }
+            other => other,
+        };
+        batch_result.add_outcome(host, outcome);
+    }
+    batch_result
+}
+
+/// reload 失败（无论是否已回滚）的主机在 playbook 中记为失败，critical 主机的错误以 "CRITICAL:" 开头
+fn fail_unsuccessful_templates(deploy_result: BatchResult<TemplateResult>) -> BatchResult<TemplateResult> {
+    let mut batch_result = BatchResult::new();
+    batch_result.add_durations_from(&deploy_result);
+    batch_result.add_metrics_from(&deploy_result);
+    for (host, outcome) in deploy_result.results {
+        let outcome = match outcome {
+            HostOutcome::Ok { value, .. } if !value.success => {
+                HostOutcome::Failed(AnsibleError::CommandError(value.message))
+            }
+            other => other,
+        };
+        batch_result.add_outcome(host, outcome);
+    }
+    batch_result
+}
+
 impl Task {
-    pub fn command(name: &str, cmd: &str) -> Self {
+    fn new(name: &str, task_type: TaskType) -> Self {
         Self {
             name: name.to_string(),
-            task_type: TaskType::Command { cmd: cmd.to_string() },
+            task_type,
             hosts: None,
             ignore_errors: false,
+            ignore_unreachable: false,
+            r#become: None,
+            become_user: None,
+            remote_user: None,
+            port: None,
+            register: None,
+            tags: Vec::new(),
+            success_threshold: None,
         }
     }
 
+    pub fn command(name: &str, cmd: &str) -> Self {
+        Self::new(name, TaskType::Command { cmd: cmd.to_string() })
+    }
+
+    /// 在同一连接上依次执行多条命令，保留每条命令的退出码和输出
+    pub fn commands(name: &str, commands: &[&str]) -> Self {
+        Self::new(name, TaskType::Commands {
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            options: CommandBatchOptions::default(),
+        })
+    }
+
     pub fn copy_file(name: &str, src: &str, dest: &str) -> Self {
-        Self {
-            name: name.to_string(),
-            task_type: TaskType::CopyFile { 
-                src: src.to_string(), 
-                dest: dest.to_string(),
-                options: None,
-            },
-            hosts: None,
-            ignore_errors: false,
-        }
+        Self::new(name, TaskType::CopyFile {
+            src: src.to_string(),
+            dest: dest.to_string(),
+            options: None,
+        })
     }
 
     pub fn copy_file_with_options(name: &str, src: &str, dest: &str, options: FileCopyOptions) -> Self {
-        Self {
-            name: name.to_string(),
-            task_type: TaskType::CopyFile { 
-                src: src.to_string(), 
-                dest: dest.to_string(),
-                options: Some(options),
-            },
-            hosts: None,
-            ignore_errors: false,
-        }
+        Self::new(name, TaskType::CopyFile {
+            src: src.to_string(),
+            dest: dest.to_string(),
+            options: Some(options),
+        })
+    }
+
+    /// 从每台目标主机下载 `src` 到本地 `<dest>/<主机名>/` 并校验 SHA256
+    pub fn fetch(name: &str, src: &str, dest: &str) -> Self {
+        Self::new(name, TaskType::Fetch {
+            src: src.to_string(),
+            dest: dest.to_string(),
+        })
     }
 
     pub fn ping(name: &str) -> Self {
-        Self {
-            name: name.to_string(),
-            task_type: TaskType::Ping,
-            hosts: None,
-            ignore_errors: false,
-        }
+        Self::new(name, TaskType::Ping)
     }
 
     pub fn system_info(name: &str) -> Self {
-        Self {
-            name: name.to_string(),
-            task_type: TaskType::GetSystemInfo,
-            hosts: None,
-            ignore_errors: false,
-        }
+        Self::new(name, TaskType::GetSystemInfo)
     }
 
     pub fn shell_script(name: &str, script: &str) -> Self {
-        Self {
-            name: name.to_string(),
-            task_type: TaskType::Shell { script: script.to_string() },
-            hosts: None,
-            ignore_errors: false,
-        }
+        Self::new(
+            name,
+            TaskType::Shell {
+                script: script.to_string(),
+                creates: None,
+                removes: None,
+                chdir: None,
+                keep_temp_on_failure: false,
+            },
+        )
     }
 
     pub fn user(name: &str, options: UserOptions) -> Self {
-        Self {
-            name: name.to_string(),
-            task_type: TaskType::User { options },
-            hosts: None,
-            ignore_errors: false,
-        }
+        Self::new(name, TaskType::User { options })
     }
 
     pub fn template(name: &str, options: TemplateOptions) -> Self {
-        Self {
-            name: name.to_string(),
-            task_type: TaskType::Template { options },
-            hosts: None,
-            ignore_errors: false,
-        }
+        Self::new(name, TaskType::Template { options })
+    }
+
+    pub fn log_rotate(name: &str, config_file: Option<&str>, force: bool) -> Self {
+        Self::new(name, TaskType::LogRotate {
+            config_file: config_file.map(|s| s.to_string()),
+            force,
+        })
+    }
+
+    pub fn dns_config(name: &str, config: DnsConfig) -> Self {
+        Self::new(name, TaskType::DnsConfig { config })
+    }
+
+    pub fn local_command(name: &str, cmd: &str) -> Self {
+        Self::new(name, TaskType::LocalCommand { cmd: cmd.to_string() })
+    }
+
+    pub fn permissions(name: &str, options: PermissionsOptions) -> Self {
+        Self::new(name, TaskType::Permissions { options })
+    }
+
+    pub fn ensure_dirs(name: &str, dirs: Vec<DirSpec>) -> Self {
+        Self::new(name, TaskType::EnsureDirs { dirs })
+    }
+
+    pub fn ssh_keypair(name: &str, path: &str, key_type: SshKeyType) -> Self {
+        Self::new(
+            name,
+            TaskType::SshKeypair {
+                path: path.to_string(),
+                type_: key_type,
+                bits: None,
+                comment: None,
+            },
+        )
+    }
+
+    pub fn repo(name: &str, config: RepoConfig) -> Self {
+        Self::new(name, TaskType::Repo { config, state: UserState::Present })
+    }
+
+    pub fn kernel_module(name: &str, config: KernelModuleConfig, state: UserState) -> Self {
+        Self::new(name, TaskType::KernelModule { config, state })
+    }
+
+    pub fn file_set(name: &str, files: Vec<PlannedFile>) -> Self {
+        Self::new(name, TaskType::FileSet { files })
+    }
+
+    pub fn block_in_file(name: &str, options: BlockInFileOptions) -> Self {
+        Self::new(name, TaskType::BlockInFile { options })
+    }
+
+    pub fn health_check(name: &str, probes: Vec<HealthProbe>) -> Self {
+        Self::new(name, TaskType::HealthCheck { probes, requirements: Vec::new() })
+    }
+
+    pub fn cgroup(name: &str, config: CgroupConfig) -> Self {
+        Self::new(name, TaskType::Cgroup { config })
     }
 
     pub fn on_hosts(mut self, hosts: Vec<String>) -> Self {
@@ -428,21 +1718,177 @@ impl Task {
         self.ignore_errors = true;
         self
     }
+
+    /// 主机不可达时继续在后续任务中尝试该主机
+    pub fn ignore_unreachable(mut self) -> Self {
+        self.ignore_unreachable = true;
+        self
+    }
+
+    /// 设置本任务视为成功所需的成功主机比例，覆盖 playbook 的设置
+    pub fn success_threshold(mut self, threshold: f32) -> Self {
+        self.success_threshold = Some(threshold);
+        self
+    }
+
+    /// 要求所有目标主机都成功（等价于 `success_threshold(1.0)`）
+    pub fn require_all(self) -> Self {
+        self.success_threshold(1.0)
+    }
+
+    /// 仅对当前任务覆盖主机的 become 设置
+    pub fn r#become(mut self, enabled: bool) -> Self {
+        self.r#become = Some(enabled);
+        self
+    }
+
+    /// 仅对当前任务覆盖提权目标用户（未显式设置 become 时隐含启用）
+    pub fn become_user(mut self, user: Option<String>) -> Self {
+        self.become_user = user;
+        self
+    }
+
+    /// 仅对当前任务使用指定用户登录（例如只有这一步需要以 root 连接）
+    pub fn as_user(mut self, user: &str) -> Self {
+        self.remote_user = Some(user.to_string());
+        self
+    }
+
+    /// 仅对当前任务连接指定端口（例如 2222 上的救援 sshd）
+    pub fn on_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// 将任务结果注册为变量
+    pub fn register(mut self, var_name: &str) -> Self {
+        self.register = Some(var_name.to_string());
+        self
+    }
+
+    /// 设置 shell 任务的 creates 守卫：路径已存在时跳过（仅对 shell 任务生效）
+    pub fn creates(mut self, path: &str) -> Self {
+        if let TaskType::Shell { ref mut creates, .. } = self.task_type {
+            *creates = Some(path.to_string());
+        }
+        self
+    }
+
+    /// 设置 shell 任务的 removes 守卫：路径不存在时跳过（仅对 shell 任务生效）
+    pub fn removes(mut self, path: &str) -> Self {
+        if let TaskType::Shell { ref mut removes, .. } = self.task_type {
+            *removes = Some(path.to_string());
+        }
+        self
+    }
+
+    /// 设置 shell 任务执行脚本时的工作目录（仅对 shell 任务生效）
+    pub fn chdir(mut self, dir: &str) -> Self {
+        if let TaskType::Shell { ref mut chdir, .. } = self.task_type {
+            *chdir = Some(dir.to_string());
+        }
+        self
+    }
+
+    /// 脚本执行失败时保留远程脚本文件，用于调试（仅对 shell 任务生效）
+    pub fn keep_temp_on_failure(mut self, keep: bool) -> Self {
+        if let TaskType::Shell { ref mut keep_temp_on_failure, .. } = self.task_type {
+            *keep_temp_on_failure = keep;
+        }
+        self
+    }
+
+    /// 某条命令失败后不再执行后续命令（仅对 commands 任务生效）
+    pub fn stop_on_failure(mut self) -> Self {
+        if let TaskType::Commands { ref mut options, .. } = self.task_type {
+            options.stop_on_failure = true;
+        }
+        self
+    }
+
+    /// 将多条命令合并为一次执行（仅对 commands 任务生效）
+    pub fn fast(mut self) -> Self {
+        if let TaskType::Commands { ref mut options, .. } = self.task_type {
+            options.fast = true;
+        }
+        self
+    }
+
+    /// 要求主机上的命令不低于指定版本，例如 `require_command_version("docker", "20.10")`（仅对 health_check 任务生效）
+    pub fn require_command_version(mut self, command: &str, min_version: &str) -> Self {
+        if let TaskType::HealthCheck { ref mut requirements, .. } = self.task_type {
+            requirements.push(HostRequirement::CommandVersion {
+                command: command.to_string(),
+                min_version: min_version.to_string(),
+            });
+        }
+        self
+    }
+
+    /// 设置任务标签
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    /// 任务是否带有任一指定标签（带 `always` 标签的任务总是匹配）
+    pub fn matches_tags(&self, tags: &[String]) -> bool {
+        self.tags.iter().any(|t| t == "always" || tags.contains(t))
+    }
+
+    /// 任务是否覆盖了主机的 become 设置
+    pub fn has_become_override(&self) -> bool {
+        self.r#become.is_some() || self.become_user.is_some()
+    }
+
+    /// 任务是否覆盖了主机的登录用户或端口
+    pub fn has_connection_override(&self) -> bool {
+        self.remote_user.is_some() || self.port.is_some()
+    }
 }
 
 impl Playbook {
     pub fn new(name: &str) -> Self {
         Self {
             name: name.to_string(),
+            gather_facts: false,
+            fact_subset: FactSubset::default(),
+            success_threshold: default_success_threshold(),
             tasks: Vec::new(),
         }
     }
 
+    pub fn gather_facts(mut self, enabled: bool) -> Self {
+        self.gather_facts = enabled;
+        self
+    }
+
+    /// 设置任务视为成功所需的成功主机比例（默认 1.0；任务可单独覆盖）
+    pub fn success_threshold(mut self, threshold: f32) -> Self {
+        self.success_threshold = threshold;
+        self
+    }
+
+    /// 只收集指定类别的 facts（同时启用 gather_facts）
+    pub fn gather_fact_subset(mut self, subset: FactSubset) -> Self {
+        self.gather_facts = true;
+        self.fact_subset = subset;
+        self
+    }
+
     pub fn add_task(mut self, task: Task) -> Self {
         self.tasks.push(task);
         self
     }
 
+    /// 只保留带有任一指定标签的任务（`tags` 为空时保留全部）
+    pub fn filter_tags(mut self, tags: &[String]) -> Self {
+        if !tags.is_empty() {
+            self.tasks.retain(|task| task.matches_tags(tags));
+        }
+        self
+    }
+
     pub fn save_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), AnsibleError> {
         let yaml_content = serde_yaml::to_string(self)
             .map_err(|e| AnsibleError::FileOperationError(format!("Failed to serialize playbook: {}", e)))?;
@@ -450,4 +1896,47 @@ impl Playbook {
         std::fs::write(path, yaml_content)
             .map_err(|e| AnsibleError::FileOperationError(format!("Failed to write playbook file: {}", e)))
     }
+
+    /// 仅将任务列表保存为独立的 YAML 文件（可被 `import_tasks_from_file` 导入）
+    pub fn save_tasks_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), AnsibleError> {
+        let yaml_content = serde_yaml::to_string(&self.tasks)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to serialize tasks: {}", e)))?;
+
+        std::fs::write(path, yaml_content)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to write tasks file: {}", e)))
+    }
+
+    /// 从仅包含任务列表的 YAML 文件导入任务，追加到当前任务之后
+    pub fn import_tasks_from_file<P: AsRef<std::path::Path>>(self, path: P) -> Result<Self, AnsibleError> {
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to read tasks file: {}", e)))?;
+
+        self.import_tasks_from_str(&content)
+    }
+
+    /// 从任务列表 YAML 字符串导入任务
+    pub fn import_tasks_from_str(mut self, yaml: &str) -> Result<Self, AnsibleError> {
+        let tasks: Vec<Task> = serde_yaml::from_str(yaml)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to parse tasks YAML: {}", e)))?;
+
+        self.tasks.extend(tasks);
+        Ok(self)
+    }
+
+    /// 解析完整 Playbook 或仅包含任务列表的 YAML
+    ///
+    /// 根节点为序列时视为任务列表，使用 `default_name` 作为 Playbook 名称。
+    pub fn from_yaml_str(yaml: &str, default_name: &str) -> Result<Self, AnsibleError> {
+        let value: serde_yaml::Value = serde_yaml::from_str(yaml)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to parse playbook YAML: {}", e)))?;
+
+        match value {
+            serde_yaml::Value::Sequence(_) => Playbook::new(default_name).import_tasks_from_str(yaml),
+            serde_yaml::Value::Mapping(ref map) if map.contains_key("name") => serde_yaml::from_value(value)
+                .map_err(|e| AnsibleError::FileOperationError(format!("Failed to parse playbook YAML: {}", e))),
+            _ => Err(AnsibleError::FileOperationError(
+                "Playbook YAML must be a mapping with a `name` key or a sequence of tasks".to_string(),
+            )),
+        }
+    }
 }
\ No newline at end of file
@@ -5,20 +5,66 @@ pub mod manager;
 pub mod config;
 pub mod executor;
 pub mod utils;
+pub mod credentials;
+pub mod concurrency;
+pub mod report;
+pub mod bandwidth;
+pub mod callback;
+pub mod run_log;
+pub mod state_file;
+pub mod run_handle;
+pub mod metrics;
+pub mod diff;
+pub mod console;
+pub mod testing;
+#[cfg(feature = "http")]
+pub mod webhook;
 
 #[cfg(test)]
 mod tests;
 
 pub use error::AnsibleError;
 pub use types::{
-    HostConfig, SystemInfo, CommandResult, FileTransferResult, NetworkInterface, FileCopyOptions,
+    HostConfig, TransportKind, SystemInfo, CommandResult, FileTransferResult, NetworkInterface, FileCopyOptions,
     UserOptions, UserResult, UserInfo, UserState,
-    TemplateOptions, TemplateResult,
+    TemplateOptions, TemplateResult, ContainerRuntimeInfo, LogRotateResult, LogRotateStatus,
+    DnsConfig, PermissionsOptions, CommandOptions, OutputEncoding, TemplateCacheStats, CronEntry, SystemdTimer, ScheduledTasks,
+    SshKeyType, HostKeyType, SshKeypairResult, SshKeyEntry, DirSpec, EnsureDirsResult, DiskBenchmark, IpVersion, IptablesChain, DmesgEntry, FactSubset,
+    TransferProgress, HugepagesInfo, DirectoryManifest, ManifestEntry, ManifestAction, RaidArray,
+    SshConfigAudit, SshFinding, DiskUsage, RepoConfig, RepoResult, RepoType,
+    LvmInfo, VolumeGroup, LogicalVolume, BlockInFileOptions, BlockInFileResult, DockerConnection, ContainerEngine,
+    HealthProbe, HealthProbeResult, PendingUpdate, DeployRunResult, CgroupConfig, CgroupResource, TcpConnection,
+    CommandBatchOptions, KernelModuleConfig, KernelModuleResult, TrustStoreCert, HostMetrics,
+    HostConfigOverrides, HostRequirement, PlannedFile, FileSetAction, FileSetFileResult, FileSetResult,
+    AclEntryType, AclEntry, FileAcl, PamLine, PamConfig, DuplicateHostPolicy, DuplicateHostKey, DuplicateHost, SysctlDiff,
+};
+pub use ssh::{SshClient, Transport, Ssh2Transport, LocalTransport, DockerTransport, TemplateCache, parse_version};
+pub use ssh::{AsyncSshClient, AsyncTransport};
+#[cfg(feature = "async-ssh")]
+pub use ssh::RusshTransport;
+pub use manager::{
+    AnsibleManager, BatchResult, HostConfigBuilder, BatchOperationStats, DrainConfig, DrainResult,
+    DeploymentStage, DeploymentResult, CanaryConfig, CanaryResult, TransportFactory, AsyncTransportFactory, DeployRunBatchResult, HostOutcome, SkipReason,
 };
-pub use ssh::SshClient;
-pub use manager::{AnsibleManager, BatchResult, HostConfigBuilder, BatchOperationStats};
 pub use config::InventoryConfig;
-pub use executor::{TaskExecutor, Task, Playbook, TaskType, TaskResult, PlaybookResult};
+pub use report::{FleetReport, DiskUsageAlert, TaskTiming, TimingReport, TaskTimingSummary, HostTaskTiming, JunitGrouping};
+pub use bandwidth::BandwidthLimiter;
+pub use concurrency::{OperationOptions, AdaptiveConcurrency, ConcurrencyEvent, ConcurrencyChange, CancellationToken, RetryEvent};
+pub use credentials::{
+    CredentialProvider, CredentialKind, SecretString, EnvCredentialProvider, StaticCredentialProvider,
+    PassphraseProvider, key_requires_passphrase, key_data_requires_passphrase,
+};
+pub use callback::{ExecutionCallback, TaskOutcome, HostStatus, ConsoleReporter};
+#[allow(deprecated)]
+pub use callback::JsonLinesWriter;
+pub use run_log::RunLogger;
+pub use state_file::{RunStateFile, STATE_FILE_VERSION};
+pub use run_handle::{RunHandle, RunStatus, RunState, HostRunStatus};
+pub use diff::{SystemInfoDiff, FactEntry, FactChange, VOLATILE_FACTS, unified_diff, MAX_DIFF_BYTES, MAX_DIFF_SOURCE_BYTES};
+pub use console::InteractiveSession;
+#[cfg(feature = "http")]
+pub use webhook::{WebhookCallback, NotifyOn};
+pub use executor::{TaskExecutor, Task, Playbook, TaskType, TaskResult, PlaybookResult, ExecutionContext, RunOptions, StepAction, HostDiff};
 
 // 便捷的重新导出
 pub type Result<T> = std::result::Result<T, AnsibleError>;
\ No newline at end of file
@@ -1,184 +1,436 @@
-use rs_ansible::{AnsibleManager, InventoryConfig, TaskExecutor, Task, Playbook, Result};
+use clap::{Args, Parser, Subcommand};
+use rs_ansible::{
+    AnsibleError, AnsibleManager, BatchResult, ConsoleReporter, HostOutcome, InteractiveSession, InventoryConfig, Playbook, Result,
+    RunOptions, StepAction, Task, TaskExecutor,
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+/// 基于 SSH 的批量运维命令行工具
+#[derive(Parser)]
+#[command(name = "rs-ansible", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// 连接相关的公共参数
+#[derive(Args)]
+struct ConnectionArgs {
+    /// inventory 文件（`.json` 为 JSON，其余按 YAML 解析）
+    #[arg(short, long)]
+    inventory: PathBuf,
+
+    /// 最大并发连接数
+    #[arg(short, long, default_value_t = 15)]
+    forks: usize,
+
+    /// 提示输入 SSH 登录密码（不回显）
+    #[arg(short = 'k', long)]
+    ask_pass: bool,
+
+    /// 提示输入 sudo 密码（不回显）
+    #[arg(short = 'K', long)]
+    ask_become_pass: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 测试主机连通性
+    Ping {
+        #[command(flatten)]
+        connection: ConnectionArgs,
+
+        /// 主机模式：all、组名或主机名，多个以逗号分隔
+        #[arg(default_value = "all")]
+        pattern: String,
+    },
+
+    /// 执行 playbook
+    Run {
+        /// playbook 文件（完整 playbook 或仅包含任务列表）
+        playbook: PathBuf,
+
+        #[command(flatten)]
+        connection: ConnectionArgs,
+
+        /// 只在匹配的主机上执行；`@<文件>` 从文件读取主机（每行一个，例如 --retry-file 的输出）
+        #[arg(short, long)]
+        limit: Option<String>,
+
+        /// 排除的主机（主机名、组名或通配符，逗号分隔）
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// 有主机失败时将失败主机写入该文件（每行一个），可用 `--limit @<文件>` 只重跑失败的主机
+        #[arg(long)]
+        retry_file: Option<PathBuf>,
+
+        /// 只执行带有这些标签的任务（逗号分隔）
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Vec<String>,
+
+        /// 只列出将要执行的任务与主机，不连接主机
+        #[arg(short = 'C', long)]
+        check: bool,
+
+        /// 显示 template、copy、blockinfile 任务对远程文件的修改（统一 diff）
+        #[arg(short = 'D', long)]
+        diff: bool,
+
+        /// 从该名称的任务开始执行，跳过之前的任务
+        #[arg(long)]
+        start_at_task: Option<String>,
+
+        /// 逐步执行：每个任务执行前确认（y 执行 / n 跳过 / a 中止）
+        #[arg(long, conflicts_with_all = ["start_at_task", "check"])]
+        step: bool,
+
+        /// 将每个任务在各主机上的结果记录到该文件；再次运行时跳过已成功且未修改的任务
+        #[arg(long)]
+        state_file: Option<PathBuf>,
+
+        /// 忽略状态文件中已有的记录，重新执行所有任务（记录照常更新）
+        #[arg(long, requires = "state_file")]
+        no_resume: bool,
+    },
+
+    /// 在一组主机上执行临时命令
+    Cmd {
+        #[command(flatten)]
+        connection: ConnectionArgs,
+
+        /// 要执行的命令
+        command: String,
+
+        /// 主机模式：all、组名或主机名，多个以逗号分隔
+        #[arg(short, long, default_value = "all")]
+        group: String,
+    },
+
+    /// 交互式会话：逐行输入命令并在目标主机上执行，会话期间保持连接
+    Console {
+        #[command(flatten)]
+        connection: ConnectionArgs,
+
+        /// 初始目标主机模式，可在会话中用 `:limit` 切换
+        #[arg(default_value = "all")]
+        pattern: String,
+    },
+
+    /// inventory 工具
+    Inventory {
+        #[command(subcommand)]
+        action: InventoryCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum InventoryCommand {
+    /// 检查 inventory 中的问题
+    Validate { file: PathBuf },
+
+    /// 在 YAML 与 JSON 之间转换（按扩展名判断格式）
+    Convert { input: PathBuf, output: PathBuf },
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // 初始化tracing日志
+async fn main() -> ExitCode {
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"))
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn")),
         )
         .with_target(false)
-        .with_thread_ids(false)
-        .with_file(true)
-        .with_line_number(true)
+        .with_writer(std::io::stderr)
         .init();
-    
-    println!("=== Rust Ansible Library Advanced Demo ===\n");
-    
-    // 演示基本功能
-    demo_basic_functionality().await?;
-    
-    // 演示配置文件功能
-    demo_config_file_functionality().await?;
-    
-    // 演示任务执行器功能
-    demo_task_executor_functionality().await?;
-    
-    Ok(())
-}
-
-async fn demo_basic_functionality() -> Result<()> {
-    println!("📋 1. 基本功能演示");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    
-    // 创建Ansible管理器
-    let mut manager = AnsibleManager::new();
-    
-    // 添加主机配置示例
-    let host1 = AnsibleManager::host_builder()
-        .hostname("192.168.1.100")
-        .port(22)
-        .username("ubuntu")
-        .password("demo_password")  // 仅为演示
-        .build();
-    
-    let host2 = AnsibleManager::host_builder()
-        .hostname("192.168.1.101")
-        .port(22)
-        .username("ubuntu")
-        .private_key_path("/home/user/.ssh/id_rsa")
-        .build();
-    
-    manager.add_host("web-server".to_string(), host1);
-    manager.add_host("db-server".to_string(), host2);
-    
-    println!("✅ 已配置主机: {:?}", manager.list_hosts());
-    println!();
-    
-    Ok(())
-}
-
-async fn demo_config_file_functionality() -> Result<()> {
-    println!("📂 2. 配置文件功能演示");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    
-    // 创建示例配置
-    let mut inventory = InventoryConfig::new();
-    
-    // 添加主机配置
-    let web_server = AnsibleManager::host_builder()
-        .hostname("web1.example.com")
-        .username("deploy")
-        .private_key_path("/home/user/.ssh/deploy_key")
-        .build();
-    
-    let db_server = AnsibleManager::host_builder()
-        .hostname("db1.example.com")
-        .username("deploy")
-        .private_key_path("/home/user/.ssh/deploy_key")
-        .build();
-    
-    inventory.hosts.insert("web1".to_string(), web_server);
-    inventory.hosts.insert("db1".to_string(), db_server);
-    
-    // 添加主机组
-    inventory.add_host_to_group("web1".to_string(), "webservers".to_string());
-    inventory.add_host_to_group("db1".to_string(), "databases".to_string());
-    
-    // 保存配置到YAML文件
-    match inventory.save_to_yaml("inventory.yml") {
-        Ok(_) => println!("✅ 配置已保存到 inventory.yml"),
-        Err(e) => println!("❌ 保存配置失败: {}", e),
+
+    let cli = Cli::parse();
+    let outcome = match cli.command {
+        Command::Ping { connection, pattern } => ping(&connection, &pattern).await,
+        Command::Run {
+            playbook,
+            connection,
+            limit,
+            exclude,
+            retry_file,
+            tags,
+            check,
+            diff,
+            start_at_task,
+            step,
+            state_file,
+            no_resume,
+        } => {
+            let options = RunOptions {
+                limit,
+                exclude: (!exclude.is_empty()).then_some(exclude),
+                diff,
+            };
+            let start = match (step, start_at_task) {
+                (true, _) => Start::Step,
+                (false, Some(task)) => Start::AtTask(task),
+                (false, None) => Start::Beginning,
+            };
+            let files = RunFiles {
+                retry_file,
+                state_file,
+                resume: !no_resume,
+            };
+            run(&playbook, &connection, options, &files, &tags, check, start).await
+        }
+        Command::Cmd {
+            connection,
+            command,
+            group,
+        } => cmd(&connection, &command, &group).await,
+        Command::Console { connection, pattern } => console(&connection, &pattern).await,
+        Command::Inventory { action } => inventory(action),
+    };
+
+    match outcome {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            ExitCode::from(2)
+        }
     }
-    
-    // 保存配置到JSON文件
-    match inventory.save_to_json("inventory.json") {
-        Ok(_) => println!("✅ 配置已保存到 inventory.json"),
-        Err(e) => println!("❌ 保存配置失败: {}", e),
+}
+
+/// 读取 inventory，按模式选出主机，并应用命令行提供的密码
+fn build_manager(connection: &ConnectionArgs, pattern: &str) -> Result<(AnsibleManager, Vec<String>)> {
+    let inventory = InventoryConfig::load(&connection.inventory)?;
+    let hosts = inventory.resolve_pattern(pattern)?;
+
+    let password = connection.ask_pass.then(|| prompt_secret("SSH password: ")).transpose()?;
+    let become_password = connection
+        .ask_become_pass
+        .then(|| prompt_secret("BECOME password: "))
+        .transpose()?;
+
+    let manager = AnsibleManager::new().with_max_concurrent_connections(connection.forks);
+    for name in &hosts {
+        let mut config = inventory.hosts[name].clone();
+        if let Some(ref password) = password {
+            config.password = Some(password.clone());
+        }
+        if let Some(ref become_password) = become_password {
+            config.become_password = Some(become_password.clone());
+        }
+        manager.add_host(name.clone(), config);
     }
-    
-    println!("📊 配置统计:");
-    println!("   - 主机数量: {}", inventory.hosts.len());
-    println!("   - 组数量: {}", inventory.groups.len());
-    for group in inventory.get_groups() {
-        let hosts = inventory.get_hosts_in_group(group);
-        println!("   - 组 '{}': {:?}", group, hosts);
+    Ok((manager, hosts))
+}
+
+fn prompt_secret(prompt: &str) -> Result<String> {
+    rpassword::prompt_password(prompt)
+        .map_err(|e| AnsibleError::AuthenticationError(format!("Failed to read password: {}", e)))
+}
+
+async fn ping(connection: &ConnectionArgs, pattern: &str) -> Result<bool> {
+    let (manager, hosts) = build_manager(connection, pattern)?;
+    let result = manager.ping_hosts(&hosts).await;
+
+    for host in &hosts {
+        match result.results.get(host) {
+            Some(HostOutcome::Ok { value: true, .. }) => println!("{} | SUCCESS => pong", host),
+            Some(HostOutcome::Ok { value: false, .. }) => println!("{} | FAILED => unexpected ping response", host),
+            Some(HostOutcome::Unreachable(e)) => println!("{} | UNREACHABLE! => {}", host, e),
+            Some(HostOutcome::Failed(e)) => println!("{} | FAILED => {}", host, e),
+            Some(HostOutcome::Skipped(_)) | None => {}
+        }
+    }
+    let failed: Vec<&String> = hosts
+        .iter()
+        .filter(|h| result.value(h) != Some(&true))
+        .collect();
+    print_recap(&hosts, &failed, &result.unreachable);
+    Ok(failed.is_empty())
+}
+
+async fn cmd(connection: &ConnectionArgs, command: &str, pattern: &str) -> Result<bool> {
+    let (manager, hosts) = build_manager(connection, pattern)?;
+    let result = manager.execute_command_on_hosts(command, &hosts).await;
+
+    for host in &hosts {
+        match result.results.get(host) {
+            Some(HostOutcome::Ok { value: output, .. }) => {
+                let status = if output.exit_code == 0 { "CHANGED" } else { "FAILED" };
+                println!("{} | {} | rc={} >>", host, status, output.exit_code);
+                print!("{}", output.stdout);
+                if !output.stderr.is_empty() {
+                    eprint!("{}", output.stderr);
+                }
+            }
+            Some(HostOutcome::Unreachable(e)) => println!("{} | UNREACHABLE! => {}", host, e),
+            Some(HostOutcome::Failed(e)) => println!("{} | FAILED => {}", host, e),
+            Some(HostOutcome::Skipped(_)) | None => {}
+        }
+    }
+    let failed = failed_commands(&hosts, &result);
+    print_recap(&hosts, &failed, &result.unreachable);
+    Ok(failed.is_empty())
+}
+
+/// 连接失败或退出码非零的主机
+fn failed_commands<'a>(hosts: &'a [String], result: &BatchResult<rs_ansible::CommandResult>) -> Vec<&'a String> {
+    hosts
+        .iter()
+        .filter(|h| !matches!(result.value(h), Some(output) if output.exit_code == 0))
+        .collect()
+}
+
+/// `@<文件>` 形式的 limit 从文件读取主机，每行一个
+fn read_limit(limit: String) -> Result<String> {
+    let Some(file) = limit.strip_prefix('@') else {
+        return Ok(limit);
+    };
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| AnsibleError::FileOperationError(format!("Failed to read limit file {}: {}", file, e)))?;
+    let hosts: Vec<&str> = content.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    Ok(hosts.join(","))
+}
+
+/// playbook 从哪里开始执行
+enum Start {
+    Beginning,
+    AtTask(String), // --start-at-task
+    Step,           // --step，逐个任务确认
+}
+
+/// 运行时读写的文件
+struct RunFiles {
+    retry_file: Option<PathBuf>, // --retry-file
+    state_file: Option<PathBuf>, // --state-file
+    resume: bool,                // 未指定 --no-resume
+}
+
+/// 在终端询问是否执行任务；无法读取输入时中止
+fn confirm_step(task: &Task) -> StepAction {
+    use std::io::Write;
+    loop {
+        print!("Perform task '{}'? (N)o/(y)es/(a)bort: ", task.name);
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            return StepAction::Abort;
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return StepAction::Run,
+            "" | "n" | "no" => return StepAction::Skip,
+            "a" | "abort" => return StepAction::Abort,
+            _ => continue,
+        }
+    }
+}
+
+async fn run(
+    path: &Path,
+    connection: &ConnectionArgs,
+    mut options: RunOptions,
+    files: &RunFiles,
+    tags: &[String],
+    check: bool,
+    start: Start,
+) -> Result<bool> {
+    options.limit = options.limit.map(read_limit).transpose()?;
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| AnsibleError::FileOperationError(format!("Failed to read {}: {}", path.display(), e)))?;
+    let default_name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let playbook = Playbook::from_yaml_str(&content, &default_name)?.filter_tags(tags);
+
+    let (manager, _) = build_manager(connection, "all")?;
+    let groups = InventoryConfig::load(&connection.inventory)?.groups;
+    let mut executor = TaskExecutor::new(&manager)
+        .with_run_options(options.clone())
+        .with_groups(groups);
+
+    if check {
+        println!("PLAY [{}] (check mode, no hosts contacted)", playbook.name);
+        if let Some(limit) = options.describe() {
+            println!("  limit: {}", limit);
+        }
+        let start_at = match start {
+            Start::AtTask(ref name) => playbook.tasks.iter().position(|task| task.name == *name).ok_or_else(|| {
+                AnsibleError::ValidationError(format!("Task '{}' not found in playbook '{}'", name, playbook.name))
+            })?,
+            _ => 0,
+        };
+        for task in &playbook.tasks[start_at..] {
+            let mut targets = executor.target_hosts(task)?;
+            targets.sort();
+            if !targets.is_empty() {
+                println!("  TASK [{}] => {}", task.name, targets.join(", "));
+            }
+        }
+        return Ok(true);
     }
-    
-    println!();
-    Ok(())
-}
-
-async fn demo_task_executor_functionality() -> Result<()> {
-    println!("🚀 3. 任务执行器功能演示");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    
-    // 创建管理器并添加一些演示主机
-    let mut manager = AnsibleManager::new();
-    let demo_host = AnsibleManager::host_builder()
-        .hostname("demo.example.com")
-        .username("demo")
-        .password("demo")
-        .build();
-    manager.add_host("demo-host".to_string(), demo_host);
-    
-    // 创建任务执行器
-    let _executor = TaskExecutor::new(&manager);
-    
-    // 创建一个示例Playbook
-    let playbook = Playbook::new("系统维护任务")
-        .add_task(Task::ping("连接测试").ignore_errors())
-        .add_task(Task::command("获取系统信息", "uname -a"))
-        .add_task(Task::command("检查磁盘使用", "df -h"))
-        .add_task(Task::shell_script("系统更新检查", r#"
-#!/bin/bash
-echo "开始系统检查..."
-echo "当前时间: $(date)"
-echo "系统负载: $(uptime)"
-echo "内存使用:"
-free -h
-echo "检查完成!"
-"#))
-        .add_task(Task::system_info("收集详细系统信息"));
-    
-    // 保存Playbook到文件
-    match playbook.save_to_file("maintenance_playbook.yml") {
-        Ok(_) => println!("✅ Playbook已保存到 maintenance_playbook.yml"),
-        Err(e) => println!("❌ 保存Playbook失败: {}", e),
+
+    if let Some(ref state_file) = files.state_file {
+        executor = executor.with_state_file(state_file, files.resume);
     }
-    
-    // 展示Playbook内容
-    println!("📋 Playbook '{}' 包含 {} 个任务:", playbook.name, playbook.tasks.len());
-    for (i, task) in playbook.tasks.iter().enumerate() {
-        println!("   {}. {}", i + 1, task.name);
+    let executor = executor.with_callback(Arc::new(ConsoleReporter::new()));
+    let result = match start {
+        Start::Beginning => executor.execute_playbook(&playbook).await?,
+        Start::AtTask(name) => executor.execute_playbook_from_task(&playbook, &name).await?,
+        Start::Step => executor.execute_playbook_step(&playbook, confirm_step).await?,
+    };
+    if let Some(ref retry_file) = files.retry_file
+        && !result.failed_hosts.is_empty()
+    {
+        result.write_retry_file(retry_file)?;
+        println!("Failed hosts written to {}", retry_file.display());
     }
-    
-    println!("\n💡 注意: 由于演示环境限制，实际的SSH连接可能会失败。");
-    println!("   在真实环境中，请:");
-    println!("   - 配置正确的主机地址和认证信息");
-    println!("   - 确保目标主机可达且SSH服务正常");
-    println!("   - 使用SSH密钥认证替代密码认证");
-    
-    // 创建单独的任务演示
-    println!("\n🔧 任务构建器演示:");
-    let sample_tasks = vec![
-        Task::command("检查服务状态", "systemctl status nginx"),
-        Task::copy_file("部署配置文件", "/local/config.conf", "/remote/config.conf")
-            .on_hosts(vec!["web1".to_string(), "web2".to_string()]),
-        Task::shell_script("备份脚本", "tar -czf /backup/$(date +%Y%m%d).tar.gz /var/www/")
-            .ignore_errors(),
-    ];
-    
-    for task in &sample_tasks {
-        println!("   - 任务: {} (忽略错误: {})", task.name, task.ignore_errors);
-        if let Some(ref hosts) = task.hosts {
-            println!("     目标主机: {:?}", hosts);
-        } else {
-            println!("     目标主机: 所有主机");
+    Ok(result.overall_success)
+}
+
+async fn console(connection: &ConnectionArgs, pattern: &str) -> Result<bool> {
+    let (manager, _) = build_manager(connection, "all")?;
+    let groups = InventoryConfig::load(&connection.inventory)?.groups;
+    let mut session = InteractiveSession::new(manager, pattern)?.with_groups(groups);
+    println!("Type :help for commands, :quit to exit");
+    session.run(std::io::stdin().lock(), &mut std::io::stdout()).await?;
+    Ok(true)
+}
+
+fn inventory(action: InventoryCommand) -> Result<bool> {
+    match action {
+        InventoryCommand::Validate { file } => {
+            let inventory = InventoryConfig::load(&file)?;
+            let problems = inventory.validate();
+            for problem in &problems {
+                println!("- {}", problem);
+            }
+            println!(
+                "{}: {} host(s), {} group(s), {} problem(s)",
+                file.display(),
+                inventory.hosts.len(),
+                inventory.groups.len(),
+                problems.len()
+            );
+            Ok(problems.is_empty())
+        }
+        InventoryCommand::Convert { input, output } => {
+            InventoryConfig::load(&input)?.save(&output)?;
+            println!("Converted {} -> {}", input.display(), output.display());
+            Ok(true)
         }
     }
-    
-    println!();
-    Ok(())
+}
+
+fn print_recap(hosts: &[String], failed: &[&String], unreachable: &[String]) {
+    println!("\nRECAP {}", "*".repeat(60));
+    for host in hosts {
+        let unreachable = unreachable.contains(host);
+        let failed = !unreachable && failed.contains(&host);
+        println!(
+            "{:<30} : ok={} failed={} unreachable={}",
+            host,
+            if failed || unreachable { 0 } else { 1 },
+            failed as u8,
+            unreachable as u8
+        );
+    }
 }
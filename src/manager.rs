@@ -1,23 +1,177 @@
+use crate::bandwidth::BandwidthLimiter;
+use crate::config::{duplicate_host_key, InventoryConfig};
+use crate::concurrency::{CancellationToken, ConcurrencyLimiter, OperationOptions, RetryEvent};
+use crate::credentials::{CredentialProvider, PassphraseProvider};
 use crate::error::AnsibleError;
-use crate::ssh::SshClient;
-use crate::types::{CommandResult, FileCopyOptions, FileTransferResult, HostConfig, SystemInfo};
-use serde::Serialize;
+use crate::executor::{Playbook, PlaybookResult, Task, TaskExecutor};
+use crate::metrics;
+use crate::report::FleetReport;
+use crate::run_handle::{RunHandle, RunStatus};
+use crate::ssh::{AsyncSshClient, AsyncTransport, RemoteTempFile, SshClient, TemplateCache, Transport};
+use crate::utils::shell_quote;
+use crate::types::{
+    BlockInFileOptions, BlockInFileResult, CommandResult, ContainerRuntimeInfo, DiskBenchmark, DmesgEntry, DnsConfig, FactSubset, FileCopyOptions, FileTransferResult, HostConfig, HostConfigOverrides, IpVersion, IptablesChain, LogRotateResult,
+    LogRotateStatus, LvmInfo, DirSpec, EnsureDirsResult, PermissionsOptions, RepoConfig, RepoResult, ScheduledTasks, SshKeyEntry, SshKeyType, HostKeyType, SshKeypairResult, SystemInfo, SystemdTimer,
+    TransferProgress, HugepagesInfo, DirectoryManifest, RaidArray, SshConfigAudit, UserState, HealthProbe, HealthProbeResult, HostRequirement, PendingUpdate,
+    DeployRunResult, CgroupConfig, HostMetrics, TcpConnection, CommandBatchOptions, KernelModuleConfig, KernelModuleResult,
+    TrustStoreCert, PlannedFile, FileSetResult, AclEntry, FileAcl, PamConfig, DuplicateHost, DuplicateHostKey, DuplicateHostPolicy, SysctlDiff,
+};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Semaphore;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::task;
-use tracing::info;
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+
+/// 金丝雀发布观察期内两次健康探测之间的间隔
+const CANARY_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Default)]
 pub struct AnsibleManager {
-    hosts: HashMap<String, HostConfig>,
+    hosts: RwLock<HashMap<String, HostConfig>>, // 可在操作执行期间增删；批量操作在开始时取快照
     max_concurrent_connections: usize,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
+    operation_options: OperationOptions,
+    bandwidth_limiter: Option<Arc<BandwidthLimiter>>, // 所有并发传输共享的总带宽限制
+    transport_factory: Option<TransportFactory>,      // 自定义连接方式（未设置时按主机配置建立 SSH 连接）
+    async_transport_factory: Option<AsyncTransportFactory>, // 异步传输主机的自定义连接方式
+    duplicate_policy: DuplicateHostPolicy,            // 添加指向同一台机器的主机时的处理方式
+    duplicate_key: DuplicateHostKey,                  // 判断重复主机的依据
+    aliases: RwLock<HashMap<String, String>>,         // 别名 -> 规范主机名
+    duplicates: RwLock<Vec<DuplicateHost>>,           // 添加主机时检测到的重复
+    resolved_addresses: Arc<Mutex<HashMap<String, String>>>, // 按 ResolvedAddress 判断时的解析缓存
+}
+
+/// 按主机名与配置创建传输层的工厂，用于替换默认的 SSH 连接（例如测试中的 mock）
+pub type TransportFactory =
+    Arc<dyn Fn(&str, &HostConfig) -> Result<Box<dyn Transport>, AnsibleError> + Send + Sync>;
+
+/// 为异步传输（`transport: russh`）的主机创建连接的工厂，见 [`AnsibleManager::with_async_transport_factory`]
+pub type AsyncTransportFactory = Arc<
+    dyn Fn(&str, &HostConfig) -> Result<Box<dyn AsyncTransport>, AnsibleError> + Send + Sync,
+>;
+
+/// 主机未执行操作的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    PreviousFailure, // 主机在之前的任务中已失败
+    Condition,       // creates/removes 等条件不满足
+    FailureLimit,    // 失败主机数达到上限，未启动
+    Cancelled,       // 操作已取消，未启动
+    Limit,           // 被运行时的 limit/exclude 排除
+    Disabled,        // 主机已被禁用（维护中）
+    Cached,          // 状态文件中记录该任务已在该主机上成功且未修改（见 `TaskExecutor::with_state_file`）
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            SkipReason::PreviousFailure => "previous_failure",
+            SkipReason::Condition => "condition",
+            SkipReason::FailureLimit => "failure_limit",
+            SkipReason::Cancelled => "cancelled",
+            SkipReason::Limit => "limit",
+            SkipReason::Disabled => "disabled",
+            SkipReason::Cached => "cached",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// 单台主机的操作结果
+///
+/// 连接阶段的错误（见 [`AnsibleError::is_unreachable`]）记为 `Unreachable`，其余错误记为 `Failed`。
+/// `changed` 只在会修改主机状态的操作中有意义（用户、模板、DNS 等），其余操作始终为 false。
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostOutcome<T> {
+    Ok { value: T, changed: bool },
+    Failed(AnsibleError),
+    Unreachable(AnsibleError),
+    Skipped(SkipReason),
+}
+
+impl<T> HostOutcome<T> {
+    pub fn ok(value: T) -> Self {
+        HostOutcome::Ok { value, changed: false }
+    }
+
+    /// 按错误类型区分失败与不可达
+    pub fn from_error(error: AnsibleError) -> Self {
+        if error.is_unreachable() {
+            HostOutcome::Unreachable(error)
+        } else {
+            HostOutcome::Failed(error)
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(self, HostOutcome::Ok { .. })
+    }
+
+    /// 失败或不可达
+    pub fn is_failed(&self) -> bool {
+        matches!(self, HostOutcome::Failed(_) | HostOutcome::Unreachable(_))
+    }
+
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, HostOutcome::Unreachable(_))
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, HostOutcome::Skipped(_))
+    }
+
+    pub fn changed(&self) -> bool {
+        matches!(self, HostOutcome::Ok { changed: true, .. })
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            HostOutcome::Ok { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&AnsibleError> {
+        match self {
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// 转换回 `Result`，跳过的主机返回 None
+    pub fn into_result(self) -> Option<Result<T, AnsibleError>> {
+        match self {
+            HostOutcome::Ok { value, .. } => Some(Ok(value)),
+            HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => Some(Err(e)),
+            HostOutcome::Skipped(_) => None,
+        }
+    }
+}
+
+impl<T> From<Result<T, AnsibleError>> for HostOutcome<T> {
+    fn from(result: Result<T, AnsibleError>) -> Self {
+        match result {
+            Ok(value) => HostOutcome::ok(value),
+            Err(e) => HostOutcome::from_error(e),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct BatchResult<T> {
-    pub results: HashMap<String, Result<T, AnsibleError>>,
+    pub results: HashMap<String, HostOutcome<T>>, // 每台主机的结果（包括跳过的主机）
     pub successful: Vec<String>,
     pub failed: Vec<String>,
+    pub unreachable: Vec<String>, // 因连接失败而失败的主机（failed 的子集）
+    pub skipped: Vec<String>, // 未执行的主机：条件不满足、达到失败上限或已取消
+    pub durations: HashMap<String, Duration>, // 每台主机的执行耗时（不含排队等待）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metrics: HashMap<String, HostMetrics>, // 每台主机的连接与通道统计（`OperationOptions::host_metrics` 开启时收集）
 }
 
 impl<T> BatchResult<T> {
@@ -26,30 +180,109 @@ impl<T> BatchResult<T> {
             results: HashMap::new(),
             successful: Vec::new(),
             failed: Vec::new(),
+            unreachable: Vec::new(),
+            skipped: Vec::new(),
+            durations: HashMap::new(),
+            metrics: HashMap::new(),
+        }
+    }
+
+    /// 累加某台主机的执行耗时（一个任务包含多个步骤时逐步累加）
+    pub fn record_duration(&mut self, host: &str, duration: Duration) {
+        *self.durations.entry(host.to_string()).or_default() += duration;
+    }
+
+    /// 合并另一个批量结果中各主机的耗时
+    pub fn add_durations_from<U>(&mut self, other: &BatchResult<U>) {
+        for (host, duration) in &other.durations {
+            self.record_duration(host, *duration);
+        }
+    }
+
+    /// 累加某台主机的连接与通道统计
+    pub fn record_metrics(&mut self, host: &str, metrics: &HostMetrics) {
+        self.metrics.entry(host.to_string()).or_default().merge(metrics);
+    }
+
+    /// 合并另一个批量结果中各主机的统计
+    pub fn add_metrics_from<U>(&mut self, other: &BatchResult<U>) {
+        for (host, metrics) in &other.metrics {
+            self.record_metrics(host, metrics);
         }
     }
 
+    /// 记录被跳过的主机
+    pub fn add_skipped(&mut self, host: String, reason: SkipReason) {
+        self.add_outcome(host, HostOutcome::Skipped(reason));
+    }
+
     pub fn add_result(&mut self, host: String, result: Result<T, AnsibleError>) {
-        match result {
-            Ok(_) => self.successful.push(host.clone()),
-            Err(_) => self.failed.push(host.clone()),
+        self.add_outcome(host, result.into());
+    }
+
+    pub fn add_outcome(&mut self, host: String, outcome: HostOutcome<T>) {
+        match outcome {
+            HostOutcome::Ok { .. } => self.successful.push(host.clone()),
+            HostOutcome::Failed(_) => self.failed.push(host.clone()),
+            HostOutcome::Unreachable(_) => {
+                self.unreachable.push(host.clone());
+                self.failed.push(host.clone());
+            }
+            HostOutcome::Skipped(_) => self.skipped.push(host.clone()),
+        }
+        self.results.insert(host, outcome);
+    }
+
+    /// 成功的值
+    pub fn value(&self, host: &str) -> Option<&T> {
+        self.results.get(host).and_then(HostOutcome::value)
+    }
+
+    /// 失败或不可达主机的错误
+    pub fn error(&self, host: &str) -> Option<&AnsibleError> {
+        self.results.get(host).and_then(HostOutcome::error)
+    }
+
+    /// 按结果值设置成功主机的 `changed`
+    pub fn with_changed(mut self, changed: impl Fn(&T) -> bool) -> Self {
+        for outcome in self.results.values_mut() {
+            if let HostOutcome::Ok { value, changed: flag } = outcome {
+                *flag = changed(value);
+            }
         }
-        self.results.insert(host, result);
+        self
+    }
+
+    /// 修改了主机状态的主机
+    pub fn changed_hosts(&self) -> Vec<&String> {
+        self.successful.iter().filter(|h| self.results[*h].changed()).collect()
     }
 
+    /// 成功主机占已执行主机（不含跳过的主机）的比例；没有执行任何主机时为 1.0（没有失败）
     pub fn success_rate(&self) -> f32 {
-        if self.results.is_empty() {
-            return 0.0;
+        let executed = self.successful.len() + self.failed.len();
+        if executed == 0 {
+            return 1.0;
         }
-        self.successful.len() as f32 / self.results.len() as f32
+        self.successful.len() as f32 / executed as f32
     }
 }
 
 impl AnsibleManager {
     pub fn new() -> Self {
         Self {
-            hosts: HashMap::new(),
+            hosts: RwLock::new(HashMap::new()),
             max_concurrent_connections: 15, // 默认最大10个并发连接
+            credential_provider: None,
+            operation_options: OperationOptions::default(),
+            bandwidth_limiter: None,
+            transport_factory: None,
+            async_transport_factory: None,
+            duplicate_policy: DuplicateHostPolicy::default(),
+            duplicate_key: DuplicateHostKey::default(),
+            aliases: RwLock::new(HashMap::new()),
+            duplicates: RwLock::new(Vec::new()),
+            resolved_addresses: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -69,37 +302,423 @@ impl AnsibleManager {
         self.max_concurrent_connections
     }
 
-    pub fn add_host(&mut self, name: String, config: HostConfig) {
-        self.hosts.insert(name, config);
+    /// 设置凭据提供者（用于补全配置中缺失的密码/口令）
+    pub fn with_credential_provider(mut self, provider: Arc<dyn CredentialProvider>) -> Self {
+        self.credential_provider = Some(provider);
+        self
+    }
+
+    /// 设置凭据提供者（可变引用）
+    pub fn set_credential_provider(&mut self, provider: Arc<dyn CredentialProvider>) {
+        self.credential_provider = Some(provider);
+    }
+
+    /// 获取当前的凭据提供者
+    pub fn get_credential_provider(&self) -> Option<Arc<dyn CredentialProvider>> {
+        self.credential_provider.clone()
+    }
+
+    /// 通过回调按主机解析私钥口令（仅在私钥加密时调用，结果按主机缓存）
+    ///
+    /// 回调只用于私钥口令；其他凭据以及回调返回 `None` 的口令仍交给已设置的凭据提供者。
+    pub fn with_passphrase_provider<F>(mut self, provider: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        let mut passphrase = PassphraseProvider::new(provider);
+        if let Some(existing) = self.credential_provider.take() {
+            passphrase = passphrase.with_fallback(existing);
+        }
+        self.credential_provider = Some(Arc::new(passphrase));
+        self
+    }
+
+    /// 设置所有并发上传共享的总带宽上限（字节/秒）
+    pub fn with_aggregate_bandwidth_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.set_aggregate_bandwidth_limit(Some(bytes_per_sec));
+        self
+    }
+
+    /// 设置或取消总带宽上限（可变引用）
+    pub fn set_aggregate_bandwidth_limit(&mut self, bytes_per_sec: Option<u64>) {
+        self.bandwidth_limiter = bytes_per_sec.map(|rate| Arc::new(BandwidthLimiter::new(rate)));
+    }
+
+    /// 通过工厂创建所有主机的连接，替代默认的 SSH 连接
+    ///
+    /// 工厂建立的连接不经过凭据解析与重试，例如 `testing::MockTransport::factory()`。
+    pub fn with_transport_factory(mut self, factory: TransportFactory) -> Self {
+        self.transport_factory = Some(factory);
+        self
+    }
+
+    /// 通过工厂创建异步传输主机的连接，替代 russh 连接（例如 `testing::MockTransport::async_factory()`）
+    ///
+    /// 只用于 `transport: russh` 的主机，与 [`Self::with_transport_factory`] 一样不经过凭据解析与重试。
+    pub fn with_async_transport_factory(mut self, factory: AsyncTransportFactory) -> Self {
+        self.async_transport_factory = Some(factory);
+        self
+    }
+
+    /// 设置添加主机时对重复主机（不同名称指向同一台机器）的处理方式，默认按地址判断并只记录警告
+    ///
+    /// 只影响之后添加的主机；加载 inventory 时可先调用 `InventoryConfig::deduplicate`。
+    pub fn with_duplicate_policy(mut self, policy: DuplicateHostPolicy, key: DuplicateHostKey) -> Self {
+        self.duplicate_policy = policy;
+        self.duplicate_key = key;
+        self
+    }
+
+    /// 使用 inventory 中的全部主机创建管理器，inventory 中记录的别名一并保留
+    pub fn from_inventory(inventory: &InventoryConfig) -> Self {
+        let manager = Self::new();
+        for (name, config) in &inventory.hosts {
+            manager.add_host(name.clone(), config.clone());
+        }
+        manager
+            .aliases
+            .write()
+            .expect("alias map poisoned")
+            .extend(inventory.aliases.clone());
+        manager
+    }
+
+    /// 添加或替换主机，可在其他操作执行期间调用
+    ///
+    /// 已开始的批量操作使用开始时的主机快照，新主机只参与之后启动的操作。
+    /// 按重复主机策略被拒绝时记录错误且不添加，需要处理错误时使用 `try_add_host`。
+    pub fn add_host(&self, name: String, config: HostConfig) {
+        if let Err(e) = self.try_add_host(name, config) {
+            error!("{}", e);
+        }
+    }
+
+    /// 添加或替换主机，并按重复主机策略检查是否与已有主机指向同一台机器
+    ///
+    /// `Error` 策略下返回错误；`Warn` 记录警告后添加；`Alias` 不添加，而是将 `name` 记为已有主机的别名
+    /// （有多个同键主机时取名称排序在前者）。
+    /// 检测到的重复可通过 `duplicate_hosts` 获取。
+    pub fn try_add_host(&self, name: String, config: HostConfig) -> Result<(), AnsibleError> {
+        // 键的计算可能需要 DNS 解析，在获取主机表与别名表的写锁之前完成，避免阻塞正在取主机快照的操作
+        let key = self.duplicate_key_of(&config);
+        let existing_keys: Vec<(String, String)> = match key {
+            Some(_) => {
+                let others: Vec<(String, HostConfig)> = self
+                    .hosts()
+                    .iter()
+                    .filter(|(other, _)| **other != name)
+                    .map(|(other, other_config)| (other.clone(), other_config.clone()))
+                    .collect();
+                others
+                    .into_iter()
+                    .filter_map(|(other, other_config)| Some((other, self.duplicate_key_of(&other_config)?)))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        let mut hosts = self.hosts_mut();
+        let mut aliases = self.aliases.write().expect("alias map poisoned");
+        // 计算键之后被移除的主机不参与比较
+        let canonical = key.and_then(|key| {
+            existing_keys
+                .iter()
+                .filter(|(other, other_key)| *other_key == key && hosts.contains_key(other))
+                .map(|(other, _)| other)
+                .min()
+                .map(|canonical| DuplicateHost {
+                    name: name.clone(),
+                    canonical: canonical.clone(),
+                    key,
+                })
+        });
+
+        let Some(duplicate) = canonical else {
+            aliases.remove(&name);
+            hosts.insert(name, config);
+            return Ok(());
+        };
+        match self.duplicate_policy {
+            DuplicateHostPolicy::Error => {
+                return Err(AnsibleError::ValidationError(format!(
+                    "Host {} is the same machine as {} ({})",
+                    duplicate.name, duplicate.canonical, duplicate.key
+                )));
+            }
+            DuplicateHostPolicy::Warn => {
+                warn!("Host '{}' is the same machine as '{}' ({})", duplicate.name, duplicate.canonical, duplicate.key);
+                aliases.remove(&name);
+                hosts.insert(name, config);
+            }
+            DuplicateHostPolicy::Alias => {
+                info!("Host '{}' is an alias of '{}' ({})", duplicate.name, duplicate.canonical, duplicate.key);
+                hosts.remove(&name);
+                aliases.insert(name, duplicate.canonical.clone());
+            }
+        }
+        let mut duplicates = self.duplicates.write().expect("duplicate list poisoned");
+        duplicates.retain(|d| d.name != duplicate.name);
+        duplicates.push(duplicate);
+        Ok(())
+    }
+
+    /// 按管理器的重复主机键计算主机的键；需要解析时不持有缓存锁，解析结果写回缓存
+    fn duplicate_key_of(&self, config: &HostConfig) -> Option<String> {
+        let hostname = config.hostname.trim().to_ascii_lowercase();
+        let mut resolved: HashMap<String, String> = self
+            .resolved_addresses
+            .lock()
+            .expect("address cache poisoned")
+            .get(&hostname)
+            .map(|address| (hostname.clone(), address.clone()))
+            .into_iter()
+            .collect();
+        let key = duplicate_host_key(config, self.duplicate_key, &mut resolved);
+        self.resolved_addresses.lock().expect("address cache poisoned").extend(resolved);
+        key
+    }
+
+    /// 别名对应的规范主机名；不是别名时原样返回
+    pub fn resolve_alias(&self, name: &str) -> String {
+        self.aliases
+            .read()
+            .expect("alias map poisoned")
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// 添加主机时检测到的重复主机
+    pub fn duplicate_hosts(&self) -> Vec<DuplicateHost> {
+        self.duplicates.read().expect("duplicate list poisoned").clone()
+    }
+
+    /// 移除主机并返回其配置；已开始的批量操作仍会在该主机上完成
+    ///
+    /// 指向该主机的别名一并移除。
+    pub fn remove_host(&self, name: &str) -> Option<HostConfig> {
+        let removed = self.hosts_mut().remove(name);
+        if removed.is_some() {
+            self.aliases.write().expect("alias map poisoned").retain(|_, canonical| canonical != name);
+        }
+        removed
+    }
+
+    /// 主机配置的副本
+    pub fn get_host(&self, name: &str) -> Option<HostConfig> {
+        self.hosts().get(name).cloned()
+    }
+
+    fn hosts(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, HostConfig>> {
+        self.hosts.read().expect("host map poisoned")
+    }
+
+    fn hosts_mut(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, HostConfig>> {
+        self.hosts.write().expect("host map poisoned")
+    }
+
+    /// 将别名替换为规范主机名并去重，保持原有顺序
+    fn resolve_host_names(&self, host_names: &[String]) -> Vec<String> {
+        let aliases = self.aliases.read().expect("alias map poisoned");
+        if aliases.is_empty() {
+            return host_names.to_vec();
+        }
+        let mut seen = std::collections::HashSet::new();
+        host_names
+            .iter()
+            .map(|name| aliases.get(name).unwrap_or(name))
+            .filter(|name| seen.insert(*name))
+            .cloned()
+            .collect()
+    }
+
+    /// 当前所有主机名
+    fn host_names(&self) -> Vec<String> {
+        self.hosts().keys().cloned().collect()
     }
 
-    pub fn remove_host(&mut self, name: &str) -> Option<HostConfig> {
-        self.hosts.remove(name)
+    /// 以已有主机为模板添加新主机：复制 `source_host` 的配置并应用 `overrides` 中非 None 的字段
+    ///
+    /// 源主机不存在或 `new_name` 已被使用时返回错误，不会覆盖已有主机。
+    pub fn clone_host_config(
+        &self,
+        source_host: &str,
+        new_name: &str,
+        overrides: HostConfigOverrides,
+    ) -> Result<(), AnsibleError> {
+        self.add_hosts_from_template(source_host, vec![(new_name.to_string(), overrides)])
+            .map(|_| ())
+    }
+
+    /// 以 `template_name` 为模板批量添加主机，返回添加的主机数
+    ///
+    /// 先检查所有名称，任一名称已存在或重复时不添加任何主机。
+    pub fn add_hosts_from_template(
+        &self,
+        template_name: &str,
+        new_hosts: Vec<(String, HostConfigOverrides)>,
+    ) -> Result<usize, AnsibleError> {
+        // 检查与插入在同一把写锁内完成，避免并发添加同名主机
+        let mut hosts = self.hosts_mut();
+        let template = hosts.get(template_name).ok_or_else(|| {
+            AnsibleError::ValidationError(format!("Template host {} not found", template_name))
+        })?;
+
+        let mut names = std::collections::HashSet::new();
+        for (name, _) in &new_hosts {
+            if hosts.contains_key(name) || !names.insert(name.as_str()) {
+                return Err(AnsibleError::ValidationError(format!("Host {} already exists", name)));
+            }
+        }
+
+        let configs: Vec<(String, HostConfig)> = new_hosts
+            .into_iter()
+            .map(|(name, overrides)| (name, template.with_overrides(&overrides)))
+            .collect();
+        let added = configs.len();
+        hosts.extend(configs);
+        Ok(added)
+    }
+
+    /// 当前所有主机名（副本，不随之后的增删变化），包括已禁用的主机
+    pub fn list_hosts(&self) -> Vec<String> {
+        self.host_names()
+    }
+
+    /// 禁用主机（例如维护窗口期间）：之后启动的操作都跳过该主机并记录为 `SkipReason::Disabled`，配置保留
+    pub fn disable_host(&self, name: &str) -> Result<(), AnsibleError> {
+        self.set_host_enabled(name, false)
+    }
+
+    /// 重新启用被禁用的主机
+    pub fn enable_host(&self, name: &str) -> Result<(), AnsibleError> {
+        self.set_host_enabled(name, true)
+    }
+
+    fn set_host_enabled(&self, name: &str, enabled: bool) -> Result<(), AnsibleError> {
+        let mut hosts = self.hosts_mut();
+        let config = hosts
+            .get_mut(name)
+            .ok_or_else(|| AnsibleError::ValidationError(format!("Host {} not found", name)))?;
+        if config.enabled != enabled {
+            info!("Host '{}' {}", name, if enabled { "enabled" } else { "disabled" });
+            config.enabled = enabled;
+        }
+        Ok(())
+    }
+
+    /// 已禁用的主机名（按名称排序）
+    pub fn list_disabled(&self) -> Vec<String> {
+        let mut disabled: Vec<String> = self
+            .hosts()
+            .iter()
+            .filter(|(_, config)| !config.enabled)
+            .map(|(name, _)| name.clone())
+            .collect();
+        disabled.sort();
+        disabled
+    }
+
+    /// 创建一个临时的管理器视图，其中所有主机的 become 设置被覆盖
+    ///
+    /// 用于任务级别的提权覆盖，原管理器中的主机配置保持不变。
+    pub fn with_become_override(&self, r#become: Option<bool>, become_user: Option<String>) -> AnsibleManager {
+        let mut scoped = self.scoped_clone();
+        for config in scoped.hosts.get_mut().expect("host map poisoned").values_mut() {
+            if let Some(ref user) = become_user {
+                config.become_user = Some(user.clone());
+            }
+            // 仅指定 become_user 时隐含启用 become
+            config.r#become = r#become.unwrap_or(config.r#become || become_user.is_some());
+        }
+        scoped
+    }
+
+    /// 创建一个临时的管理器视图，其中所有主机的登录用户和/或端口被覆盖
+    ///
+    /// 用于任务级别的连接覆盖，原管理器中的主机配置保持不变。认证方式（密码、私钥）沿用主机配置。
+    pub fn with_connection_override(&self, username: Option<String>, port: Option<u16>) -> AnsibleManager {
+        let mut scoped = self.scoped_clone();
+        for config in scoped.hosts.get_mut().expect("host map poisoned").values_mut() {
+            if let Some(ref user) = username {
+                config.username = user.clone();
+            }
+            if let Some(port) = port {
+                config.port = port;
+            }
+        }
+        scoped
+    }
+
+    /// 创建一个临时的管理器视图，其批量操作使用指定的执行选项（并发上限、自适应并发等）
+    ///
+    /// 例如大文件分发时使用较低的并发：`manager.override_operation_options(opts).copy_file_to_hosts(..)`
+    pub fn override_operation_options(&self, options: OperationOptions) -> AnsibleManager {
+        let mut scoped = self.scoped_clone();
+        scoped.operation_options = options;
+        scoped
+    }
+
+    /// 设置默认的批量操作执行选项
+    pub fn set_operation_options(&mut self, options: OperationOptions) {
+        self.operation_options = options;
     }
 
-    pub fn get_host(&self, name: &str) -> Option<&HostConfig> {
-        self.hosts.get(name)
+    /// 批量操作使用的取消令牌（由 `spawn_playbook` 设置）
+    pub(crate) fn cancellation(&self) -> Option<&CancellationToken> {
+        self.operation_options.cancellation.as_ref()
     }
 
-    pub fn list_hosts(&self) -> Vec<&String> {
-        self.hosts.keys().collect()
+    /// 创建一个临时的管理器视图，其连接重试事件发送到 `sender`（其他执行选项不变）
+    pub(crate) fn with_retry_events(&self, sender: UnboundedSender<RetryEvent>) -> AnsibleManager {
+        self.override_operation_options(self.operation_options.clone().retry_events(sender))
+    }
+
+    /// 复制主机配置与设置，用于构建临时视图
+    fn scoped_clone(&self) -> AnsibleManager {
+        AnsibleManager {
+            hosts: RwLock::new(self.hosts().clone()),
+            max_concurrent_connections: self.max_concurrent_connections,
+            credential_provider: self.credential_provider.clone(),
+            operation_options: self.operation_options.clone(),
+            bandwidth_limiter: self.bandwidth_limiter.clone(),
+            transport_factory: self.transport_factory.clone(),
+            async_transport_factory: self.async_transport_factory.clone(),
+            duplicate_policy: self.duplicate_policy,
+            duplicate_key: self.duplicate_key,
+            aliases: RwLock::new(self.aliases.read().expect("alias map poisoned").clone()),
+            duplicates: RwLock::new(self.duplicate_hosts()),
+            resolved_addresses: self.resolved_addresses.clone(),
+        }
     }
 
     /// 对所有主机执行ping操作
     pub async fn ping_all(&self) -> BatchResult<bool> {
-        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
+        let host_names = self.host_names();
         self.ping_hosts(&host_names).await
     }
 
     /// 对指定主机列表执行ping操作（带并发控制）
     pub async fn ping_hosts(&self, host_names: &[String]) -> BatchResult<bool> {
-        self.execute_concurrent_operation(host_names, |client| async move { client.ping() })
+        self.execute_transport_operation(
+            host_names,
+            |_host, client| async move { client.ping() },
+            Some(|_host, client: AsyncSshClient| async move { client.ping().await }),
+        )
+        .await
+    }
+
+    /// 提权预检：在执行需要 root 的 playbook 之前确认各主机的 sudo 可用
+    ///
+    /// 不修改主机上的任何内容，返回 false 的主机在 become 任务上会失败。
+    pub async fn check_privilege_escalation(&self, host_names: &[String]) -> BatchResult<bool> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.check_privilege_escalation() })
             .await
     }
 
     /// 对所有主机执行命令
     pub async fn execute_command_all(&self, command: &str) -> BatchResult<CommandResult> {
-        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
+        let host_names = self.host_names();
         self.execute_command_on_hosts(command, &host_names).await
     }
 
@@ -110,9 +729,78 @@ impl AnsibleManager {
         host_names: &[String],
     ) -> BatchResult<CommandResult> {
         let command = command.to_string();
+        let async_command = command.clone();
+        self.execute_transport_operation(
+            host_names,
+            move |_host, client| {
+                let cmd = command.clone();
+                async move { client.execute_command(&cmd) }
+            },
+            Some(move |_host, client: AsyncSshClient| {
+                let cmd = async_command.clone();
+                async move { client.execute_command(&cmd).await }
+            }),
+        )
+        .await
+    }
+
+    /// 对指定主机列表执行命令，失败主机数达到 `max_failures` 后不再启动新的主机（带并发控制）
+    ///
+    /// 非零退出码视为失败。已在执行的主机会继续完成，未启动的主机记录在 `skipped` 中，
+    /// 与 `successful`、`failed` 区分。
+    pub async fn execute_command_on_hosts_with_fail_limit(
+        &self,
+        command: &str,
+        host_names: &[String],
+        max_failures: usize,
+    ) -> BatchResult<CommandResult> {
+        let command = command.to_string();
+        let async_command = command.clone();
+        self.override_operation_options(self.operation_options.clone().max_failures(max_failures))
+            .execute_transport_operation(
+                host_names,
+                move |_host, client| {
+                    let cmd = command.clone();
+                    async move { nonzero_exit_as_error(client.execute_command(&cmd)?) }
+                },
+                Some(move |_host, client: AsyncSshClient| {
+                    let cmd = async_command.clone();
+                    async move { nonzero_exit_as_error(client.execute_command(&cmd).await?) }
+                }),
+            )
+            .await
+    }
+
+    /// 在指定主机列表上依次执行多条命令，每台主机复用一个会话（带并发控制）
+    ///
+    /// `fast` 为 true 时将命令合并为一次执行，减少高延迟链路上的往返次数。
+    pub async fn execute_commands_on_hosts(
+        &self,
+        commands: &[String],
+        host_names: &[String],
+        fast: bool,
+    ) -> BatchResult<Vec<CommandResult>> {
+        let options = CommandBatchOptions { fast, ..Default::default() };
+        self.execute_commands_on_hosts_with_options(commands, host_names, &options).await
+    }
+
+    /// 在指定主机列表上依次执行多条命令，可在某条命令失败后停止（带并发控制）
+    ///
+    /// 非零退出码不会使主机失败，每条命令的退出码和输出都保留在结果中。
+    pub async fn execute_commands_on_hosts_with_options(
+        &self,
+        commands: &[String],
+        host_names: &[String],
+        options: &CommandBatchOptions,
+    ) -> BatchResult<Vec<CommandResult>> {
+        let commands = commands.to_vec();
+        let options = *options;
         self.execute_concurrent_operation(host_names, move |client| {
-            let cmd = command.clone();
-            async move { client.execute_command(&cmd) }
+            let commands = commands.clone();
+            async move {
+                let refs: Vec<&str> = commands.iter().map(|c| c.as_str()).collect();
+                client.execute_commands_with_options(&refs, &options)
+            }
         })
         .await
     }
@@ -123,7 +811,7 @@ impl AnsibleManager {
         local_path: &str,
         remote_path: &str,
     ) -> BatchResult<FileTransferResult> {
-        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
+        let host_names = self.host_names();
         self.copy_file_to_hosts(local_path, remote_path, &host_names)
             .await
     }
@@ -135,7 +823,7 @@ impl AnsibleManager {
         remote_path: &str,
         options: &FileCopyOptions,
     ) -> BatchResult<FileTransferResult> {
-        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
+        let host_names = self.host_names();
         self.copy_file_to_hosts_with_options(local_path, remote_path, &host_names, options)
             .await
     }
@@ -166,90 +854,1346 @@ impl AnsibleManager {
     ) -> BatchResult<FileTransferResult> {
         let local_path = local_path.to_string();
         let remote_path = remote_path.to_string();
-        
-        // 优化：在此处预先计算本地文件 Hash，避免每个并发任务都重复计算
-        let mut options = options.clone();
-        if options.precomputed_hash.is_none() {
-             // 尝试计算 hash (SHA256)
-             // 如果计算成功，注入到 options 中
-             // 如果失败（例如文件不存在），则忽略，留给底层的 SshClient 再次尝试并汇报具体的错误
-             if let Ok(hash) = crate::utils::calculate_file_hash(&local_path, "sha256") {
-                 info!("Pre-calculated local file hash for batch transfer: {}", hash);
-                 options.precomputed_hash = Some(hash);
-             }
-        }
+        let options = with_precomputed_hash(&local_path, options);
+        let paths = (local_path.clone(), remote_path.clone(), options.clone());
 
-        self.execute_concurrent_operation(host_names, move |client| {
-            let local = local_path.clone();
-            let remote = remote_path.clone();
-            let opts = options.clone();
-            async move { client.copy_file_to_remote_with_options(&local, &remote, &opts) }
-        })
+        self.execute_transport_operation(
+            host_names,
+            move |_host, client| {
+                let local = local_path.clone();
+                let remote = remote_path.clone();
+                let opts = options.clone();
+                async move { client.copy_file_to_remote_with_options(&local, &remote, &opts) }
+            },
+            Some(move |_host, client: AsyncSshClient| {
+                let (local, remote, opts) = paths.clone();
+                async move { client.copy_file_to_remote_with_options(&local, &remote, &opts).await }
+            }),
+        )
         .await
     }
 
-    /// 获取所有主机的系统信息
-    pub async fn get_system_info_all(&self) -> BatchResult<SystemInfo> {
-        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
-        self.get_system_info_from_hosts(&host_names).await
+    /// 复制文件后立即在同一连接上执行命令（带并发控制）
+    ///
+    /// 每台主机只建立一次连接：先传输并校验文件，成功后再执行 `run_cmd`。
+    /// 传输失败的主机不会执行命令，记录在 `transfer_failed` 中。
+    pub async fn deploy_and_run(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+        options: &FileCopyOptions,
+        run_cmd: &str,
+        host_names: &[String],
+    ) -> DeployRunBatchResult {
+        let local_path = local_path.to_string();
+        let remote_path = remote_path.to_string();
+        let run_cmd = run_cmd.to_string();
+        let options = with_precomputed_hash(&local_path, options);
+        let transfer_failed = Arc::new(Mutex::new(Vec::new()));
+
+        let failed = transfer_failed.clone();
+        let batch = self
+            .execute_concurrent_operation_with_host(host_names, move |host, client| {
+                let local = local_path.clone();
+                let remote = remote_path.clone();
+                let opts = options.clone();
+                let run_cmd = run_cmd.clone();
+                let failed = failed.clone();
+                async move {
+                    let transfer = client
+                        .copy_file_to_remote_with_options(&local, &remote, &opts)
+                        .and_then(|transfer| {
+                            if transfer.success {
+                                Ok(transfer)
+                            } else {
+                                Err(AnsibleError::FileOperationError(transfer.message))
+                            }
+                        });
+                    let transfer = match transfer {
+                        Ok(transfer) => transfer,
+                        Err(e) => {
+                            warn!("Transfer to host '{}' failed, command not run: {}", host, e);
+                            failed.lock().unwrap_or_else(|e| e.into_inner()).push(host);
+                            return Err(e);
+                        }
+                    };
+                    let command = client.execute_command(&run_cmd)?;
+                    Ok(DeployRunResult { transfer, command })
+                }
+            })
+            .await;
+
+        let mut transfer_failed = std::mem::take(&mut *transfer_failed.lock().unwrap_or_else(|e| e.into_inner()));
+        transfer_failed.sort();
+        DeployRunBatchResult { batch, transfer_failed }
     }
 
-    /// 获取指定主机列表的系统信息（带并发控制）
-    pub async fn get_system_info_from_hosts(
+    /// 上传脚本并在同一连接上执行（带并发控制）
+    ///
+    /// 远程脚本由 [`RemoteTempFile`] 守卫：脚本以退出码 0 执行完毕后立即删除；传输失败、
+    /// 执行出错或非零退出时，除非设置了 `options.keep_temp_on_failure`，否则同样会被删除。
+    /// russh 主机按相同规则在执行结束后显式删除。设置 `chdir` 时先切换到该目录再执行。
+    pub async fn run_script_on_hosts(
         &self,
+        local_path: &str,
+        remote_path: &str,
+        options: &FileCopyOptions,
+        chdir: Option<&str>,
         host_names: &[String],
-    ) -> BatchResult<SystemInfo> {
-        self.execute_concurrent_operation(
+    ) -> BatchResult<CommandResult> {
+        let local_path = local_path.to_string();
+        let remote_path = remote_path.to_string();
+        let options = with_precomputed_hash(&local_path, options);
+        let quoted_script = shell_quote(&remote_path);
+        let run_cmd = match chdir {
+            Some(dir) => format!("chmod +x {} && cd {} && {}", quoted_script, shell_quote(dir), quoted_script),
+            None => format!("chmod +x {} && {}", quoted_script, quoted_script),
+        };
+
+        let script = (local_path.clone(), remote_path.clone(), options.clone(), run_cmd.clone());
+
+        self.execute_transport_operation(
             host_names,
-            |client| async move { client.get_system_info() },
+            move |_host, client| {
+                let local = local_path.clone();
+                let remote = remote_path.clone();
+                let opts = options.clone();
+                let run_cmd = run_cmd.clone();
+                async move {
+                    let script = RemoteTempFile::new(&client, remote.clone()).keep_on_failure(opts.keep_temp_on_failure);
+                    client
+                        .copy_file_to_remote_with_options(&local, &remote, &opts)
+                        .and_then(successful_transfer)
+                        .map_err(script_copy_error)?;
+
+                    let result = client.execute_command(&run_cmd)?;
+                    if result.exit_code == 0 {
+                        script.discard();
+                    }
+                    Ok(result)
+                }
+            },
+            Some(move |_host, client: AsyncSshClient| {
+                let (local, remote, opts, run_cmd) = script.clone();
+                async move {
+                    // 异步客户端没有 Drop 守卫，按 RemoteTempFile 的规则显式清理
+                    let copied = client
+                        .copy_file_to_remote_with_options(&local, &remote, &opts)
+                        .await
+                        .and_then(successful_transfer)
+                        .map_err(script_copy_error);
+                    let result = match copied {
+                        Ok(_) => client.execute_command(&run_cmd).await,
+                        Err(e) => Err(e),
+                    };
+                    let succeeded = result.as_ref().is_ok_and(|result| result.exit_code == 0);
+                    client.discard_temp(&remote, opts.keep_temp_on_failure && !succeeded).await;
+                    result
+                }
+            }),
         )
         .await
     }
 
-    /// 在所有主机上管理用户
-    pub async fn manage_user_all(
+    /// 向指定主机列表复制文件并回调传输进度（回调参数包含主机名，带并发控制）
+    pub async fn copy_file_to_hosts_with_progress<F>(
         &self,
-        options: &crate::types::UserOptions,
-    ) -> BatchResult<crate::types::UserResult> {
-        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
-        self.manage_user_on_hosts(options, &host_names).await
+        local_path: &str,
+        remote_path: &str,
+        host_names: &[String],
+        on_progress: F,
+    ) -> BatchResult<FileTransferResult>
+    where
+        F: Fn(&str, TransferProgress) + Send + Sync + 'static,
+    {
+        let local_path = local_path.to_string();
+        let remote_path = remote_path.to_string();
+        let options = with_precomputed_hash(&local_path, &FileCopyOptions::default());
+        let on_progress = Arc::new(on_progress);
+
+        self.execute_concurrent_operation_with_host(host_names, move |host, client| {
+            let local = local_path.clone();
+            let remote = remote_path.clone();
+            let opts = options.clone();
+            let on_progress = on_progress.clone();
+            async move {
+                client.copy_file_to_remote_with_progress(&local, &remote, &opts, |progress| {
+                    on_progress(&host, progress)
+                })
+            }
+        })
+        .await
     }
 
-    /// 在指定主机列表上管理用户（带并发控制）
-    pub async fn manage_user_on_hosts(
+    /// 向指定主机列表复制目录树，返回每台主机的文件清单（带选项和并发控制）
+    pub async fn copy_dir_to_hosts_with_manifest(
         &self,
-        options: &crate::types::UserOptions,
+        local_dir: &str,
+        remote_dir: &str,
         host_names: &[String],
-    ) -> BatchResult<crate::types::UserResult> {
+        options: &FileCopyOptions,
+    ) -> BatchResult<DirectoryManifest> {
+        let local_dir = local_dir.to_string();
+        let remote_dir = remote_dir.to_string();
         let options = options.clone();
+
         self.execute_concurrent_operation(host_names, move |client| {
+            let local = local_dir.clone();
+            let remote = remote_dir.clone();
             let opts = options.clone();
-            async move { client.manage_user(&opts) }
+            async move { client.copy_dir_with_manifest(&local, &remote, &opts) }
         })
         .await
     }
 
-    /// 向所有主机部署模板
-    pub async fn deploy_template_to_all(
+    /// 从所有主机下载文件并校验完整性（SHA256），保存为 `<local_dir>/<主机名>/<文件名>`
+    ///
+    /// 例如收集所有主机上的 `/var/log/app.log` 用于排查问题，见 [`Self::fetch_file_from_hosts_verified`]。
+    pub async fn fetch_file_all(&self, remote_path: &str, local_dir: &str) -> BatchResult<FileTransferResult> {
+        let host_names = self.host_names();
+        self.fetch_file_from_hosts_verified(remote_path, local_dir, &host_names).await
+    }
+
+    /// 从指定主机列表下载文件并校验完整性（SHA256），保存为 `<local_dir>/<主机名>/<文件名>`（带并发控制）
+    ///
+    /// 文件不存在或校验失败的主机记为失败，不影响其他主机；失败时不保留部分下载的文件与空的主机目录。
+    pub async fn fetch_file_from_hosts_verified(
         &self,
-        options: &crate::types::TemplateOptions,
+        remote_path: &str,
+        local_dir: &str,
+        host_names: &[String],
+    ) -> BatchResult<FileTransferResult> {
+        let remote_path = remote_path.to_string();
+        let local_dir = std::path::PathBuf::from(local_dir);
+        let async_remote_path = remote_path.clone();
+        let async_local_dir = local_dir.clone();
+        self.execute_transport_operation(
+            host_names,
+            move |host_name, client| {
+                let remote = remote_path.clone();
+                let host_dir = local_dir.join(&host_name);
+                async move {
+                    let local = fetch_destination(&remote, &host_dir)?;
+                    std::fs::create_dir_all(&host_dir).map_err(|e| {
+                        AnsibleError::FileOperationError(format!("Failed to create {}: {}", host_dir.display(), e))
+                    })?;
+                    client
+                        .copy_file_from_remote_verified(&remote, &local.to_string_lossy(), "sha256")
+                        .inspect_err(|_| {
+                            // 目录中还有其他文件时 remove_dir 失败，保持原样
+                            let _ = std::fs::remove_dir(&host_dir);
+                        })
+                }
+            },
+            Some(move |host_name: String, client: AsyncSshClient| {
+                let remote = async_remote_path.clone();
+                let host_dir = async_local_dir.join(&host_name);
+                async move {
+                    let local = fetch_destination(&remote, &host_dir)?;
+                    tokio::fs::create_dir_all(&host_dir).await.map_err(|e| {
+                        AnsibleError::FileOperationError(format!("Failed to create {}: {}", host_dir.display(), e))
+                    })?;
+                    let fetched = client.copy_file_from_remote_verified(&remote, &local.to_string_lossy(), "sha256").await;
+                    if fetched.is_err() {
+                        let _ = tokio::fs::remove_dir(&host_dir).await;
+                    }
+                    fetched
+                }
+            }),
+        )
+        .await
+    }
+
+    /// 在指定主机列表上幂等地设置文件权限与所有者，结果表示是否发生变更（带并发控制）
+    pub async fn set_permissions_on_hosts(
+        &self,
+        options: &PermissionsOptions,
+        host_names: &[String],
+    ) -> BatchResult<bool> {
+        let options = options.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let opts = options.clone();
+            async move { client.set_file_attributes(&opts) }
+        })
+        .await
+        .with_changed(|changed| *changed)
+    }
+
+    /// 在所有主机上确保目录存在且属性正确，有目录被创建或修改的主机计为 changed
+    pub async fn ensure_dirs_all(&self, dirs: &[DirSpec]) -> BatchResult<EnsureDirsResult> {
+        let host_names = self.host_names();
+        self.ensure_dirs_on_hosts(dirs, &host_names).await
+    }
+
+    /// 在指定主机列表上确保目录存在且属性正确（带并发控制）
+    pub async fn ensure_dirs_on_hosts(&self, dirs: &[DirSpec], host_names: &[String]) -> BatchResult<EnsureDirsResult> {
+        let dirs = dirs.to_vec();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let dirs = dirs.clone();
+            async move { client.ensure_dirs(&dirs) }
+        })
+        .await
+        .with_changed(|r| r.changed)
+    }
+
+    /// 获取所有主机的系统信息
+    pub async fn get_system_info_all(&self) -> BatchResult<SystemInfo> {
+        let host_names = self.host_names();
+        self.get_system_info_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表的系统信息（带并发控制）
+    pub async fn get_system_info_from_hosts(
+        &self,
+        host_names: &[String],
+    ) -> BatchResult<SystemInfo> {
+        self.execute_concurrent_operation(
+            host_names,
+            |client| async move { client.get_system_info() },
+        )
+        .await
+    }
+
+    /// 获取指定主机列表指定类别的系统信息（带并发控制）
+    pub async fn get_system_info_subset_from_hosts(
+        &self,
+        subset: FactSubset,
+        host_names: &[String],
+    ) -> BatchResult<SystemInfo> {
+        self.execute_concurrent_operation(host_names, move |client| async move {
+            client.get_system_info_subset(subset)
+        })
+        .await
+    }
+
+    /// 收集所有主机的系统信息并汇总为机群报告
+    pub async fn get_fleet_report_all(&self, disk_usage_threshold: f32) -> FleetReport {
+        let host_names = self.host_names();
+        self.get_fleet_report_from_hosts(&host_names, disk_usage_threshold).await
+    }
+
+    /// 收集指定主机的系统信息并汇总为机群报告
+    pub async fn get_fleet_report_from_hosts(
+        &self,
+        host_names: &[String],
+        disk_usage_threshold: f32,
+    ) -> FleetReport {
+        let batch = self.get_system_info_from_hosts(host_names).await;
+        FleetReport::from_batch(&batch, disk_usage_threshold)
+    }
+
+    /// 获取所有主机的容器运行时信息
+    pub async fn get_container_runtime_info_all(&self) -> BatchResult<Option<ContainerRuntimeInfo>> {
+        let host_names = self.host_names();
+        self.get_container_runtime_info_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表的容器运行时信息（带并发控制）
+    pub async fn get_container_runtime_info_from_hosts(
+        &self,
+        host_names: &[String],
+    ) -> BatchResult<Option<ContainerRuntimeInfo>> {
+        self.execute_concurrent_operation(
+            host_names,
+            |client| async move { client.get_container_runtime_info() },
+        )
+        .await
+    }
+
+    /// Kubernetes 预检：找出容器运行时 cgroup 驱动与预期不一致的主机
+    ///
+    /// 只返回检测到运行时且驱动不匹配的主机；探测失败、未安装运行时或无法确定驱动的主机会记录警告。
+    pub async fn find_hosts_with_cgroup_mismatch(
+        &self,
+        expected_driver: &str,
+        host_names: &[String],
+    ) -> Vec<String> {
+        let batch_result = self.get_container_runtime_info_from_hosts(host_names).await;
+
+        let mut mismatched = Vec::new();
+        for (host, result) in &batch_result.results {
+            match result {
+                HostOutcome::Ok { value: Some(info), .. } if info.cgroup_driver == "unknown" => {
+                    warn!("Could not determine the cgroup driver of {} on host '{}'", info.runtime, host)
+                }
+                HostOutcome::Ok { value: Some(info), .. } if info.cgroup_driver != expected_driver => {
+                    info!(
+                        "Host '{}' uses cgroup driver '{}' (expected '{}')",
+                        host, info.cgroup_driver, expected_driver
+                    );
+                    mismatched.push(host.clone());
+                }
+                HostOutcome::Ok { value: Some(_), .. } | HostOutcome::Skipped(_) => {}
+                HostOutcome::Ok { value: None, .. } => warn!("No container runtime detected on host '{}'", host),
+                HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => {
+                    warn!("Failed to probe container runtime on host '{}': {}", host, e)
+                }
+            }
+        }
+        mismatched.sort();
+        mismatched
+    }
+
+    /// 在所有主机上触发日志轮转
+    pub async fn rotate_logs_all(&self, config_file: Option<&str>, force: bool) -> BatchResult<LogRotateResult> {
+        let host_names = self.host_names();
+        self.rotate_logs_on_hosts(config_file, force, &host_names).await
+    }
+
+    /// 在指定主机列表上触发日志轮转（带并发控制）
+    pub async fn rotate_logs_on_hosts(
+        &self,
+        config_file: Option<&str>,
+        force: bool,
+        host_names: &[String],
+    ) -> BatchResult<LogRotateResult> {
+        let config_file = config_file.map(|s| s.to_string());
+        self.execute_concurrent_operation(host_names, move |client| {
+            let config_file = config_file.clone();
+            async move { client.run_logrotate(config_file.as_deref(), force) }
+        })
+        .await
+    }
+
+    /// 获取指定主机列表的 logrotate 状态（带并发控制）
+    pub async fn get_logrotate_status_from_hosts(
+        &self,
+        host_names: &[String],
+    ) -> BatchResult<Vec<LogRotateStatus>> {
+        self.execute_concurrent_operation(
+            host_names,
+            |client| async move { client.get_logrotate_status() },
+        )
+        .await
+    }
+
+    /// 在指定主机列表上生成 SSH 密钥对（已存在则跳过，带并发控制）
+    pub async fn generate_ssh_keypair_on_hosts(
+        &self,
+        key_path: &str,
+        key_type: SshKeyType,
+        bits: u32,
+        comment: &str,
+        host_names: &[String],
+    ) -> BatchResult<SshKeypairResult> {
+        let key_path = key_path.to_string();
+        let comment = comment.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let key_path = key_path.clone();
+            let comment = comment.clone();
+            async move { client.generate_ssh_keypair(&key_path, key_type, bits, &comment, None) }
+        })
+        .await
+        .with_changed(|r| r.changed)
+    }
+
+    /// 获取所有主机上指定用户的 authorized_keys
+    pub async fn get_authorized_keys_all(&self, user: &str) -> BatchResult<Vec<SshKeyEntry>> {
+        let host_names = self.host_names();
+        self.get_authorized_keys_from_hosts(user, &host_names).await
+    }
+
+    /// 获取指定主机列表上指定用户的 authorized_keys（带并发控制）
+    pub async fn get_authorized_keys_from_hosts(&self, user: &str, host_names: &[String]) -> BatchResult<Vec<SshKeyEntry>> {
+        let user = user.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let user = user.clone();
+            async move { client.get_authorized_keys(&user) }
+        })
+        .await
+    }
+
+    /// 列出所有主机上指定用户 `~/.ssh/` 中的私钥文件
+    pub async fn get_user_private_keys_all(&self, user: &str) -> BatchResult<Vec<String>> {
+        let host_names = self.host_names();
+        self.get_user_private_keys_from_hosts(user, &host_names).await
+    }
+
+    /// 列出指定主机列表上指定用户 `~/.ssh/` 中的私钥文件（带并发控制）
+    pub async fn get_user_private_keys_from_hosts(&self, user: &str, host_names: &[String]) -> BatchResult<Vec<String>> {
+        let user = user.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let user = user.clone();
+            async move { client.get_user_private_keys(&user) }
+        })
+        .await
+    }
+
+    /// 在所有主机上找出需要轮换密钥的用户
+    pub async fn find_users_with_old_keys_all(&self, users: &[String], max_age_days: u32) -> BatchResult<Vec<String>> {
+        let host_names = self.host_names();
+        self.find_users_with_old_keys_on_hosts(users, max_age_days, &host_names).await
+    }
+
+    /// 在指定主机列表上找出需要轮换密钥的用户（带并发控制）
+    pub async fn find_users_with_old_keys_on_hosts(
+        &self,
+        users: &[String],
+        max_age_days: u32,
+        host_names: &[String],
+    ) -> BatchResult<Vec<String>> {
+        let users = users.to_vec();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let users = users.clone();
+            async move { client.find_users_with_old_keys(&users, max_age_days) }
+        })
+        .await
+    }
+
+    /// 在所有主机的文件中插入、更新或删除受管理的文本块
+    pub async fn block_in_file_all(&self, options: &BlockInFileOptions) -> BatchResult<BlockInFileResult> {
+        let host_names = self.host_names();
+        self.block_in_file_on_hosts(options, &host_names).await
+    }
+
+    /// 在指定主机列表的文件中插入、更新或删除受管理的文本块（带并发控制）
+    pub async fn block_in_file_on_hosts(
+        &self,
+        options: &BlockInFileOptions,
+        host_names: &[String],
+    ) -> BatchResult<BlockInFileResult> {
+        let options = options.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let options = options.clone();
+            async move { client.block_in_file(&options) }
+        })
+        .await
+        .with_changed(|r| r.changed)
+    }
+
+    /// 在所有主机上创建 cgroup 并应用资源限制
+    pub async fn create_cgroup_all(&self, config: &CgroupConfig) -> BatchResult<bool> {
+        let host_names = self.host_names();
+        self.create_cgroup_on_hosts(config, &host_names).await
+    }
+
+    /// 在指定主机列表上创建 cgroup 并应用资源限制（带并发控制）
+    pub async fn create_cgroup_on_hosts(&self, config: &CgroupConfig, host_names: &[String]) -> BatchResult<bool> {
+        let config = config.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let config = config.clone();
+            async move { client.create_cgroup(&config) }
+        })
+        .await
+        .with_changed(|changed| *changed)
+    }
+
+    /// 在所有主机上执行健康探针
+    pub async fn run_health_probes_all(&self, probes: &[HealthProbe]) -> BatchResult<Vec<HealthProbeResult>> {
+        let host_names = self.host_names();
+        self.run_health_probes_on_hosts(probes, &host_names).await
+    }
+
+    /// 在指定主机列表上执行健康探针（带并发控制）
+    pub async fn run_health_probes_on_hosts(
+        &self,
+        probes: &[HealthProbe],
+        host_names: &[String],
+    ) -> BatchResult<Vec<HealthProbeResult>> {
+        self.run_health_checks_on_hosts(probes, &[], host_names).await
+    }
+
+    /// 在指定主机列表上执行健康探针并检查主机条件（例如命令的最低版本）
+    pub async fn run_health_checks_on_hosts(
+        &self,
+        probes: &[HealthProbe],
+        requirements: &[HostRequirement],
+        host_names: &[String],
+    ) -> BatchResult<Vec<HealthProbeResult>> {
+        let probes = probes.to_vec();
+        let requirements = requirements.to_vec();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let probes = probes.clone();
+            let requirements = requirements.clone();
+            async move { client.run_health_checks(&probes, &requirements) }
+        })
+        .await
+    }
+
+    /// 获取所有主机待更新的软件包
+    pub async fn get_pending_updates_all(&self) -> BatchResult<Vec<PendingUpdate>> {
+        let host_names = self.host_names();
+        self.get_pending_updates_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表待更新的软件包（带并发控制）
+    pub async fn get_pending_updates_from_hosts(&self, host_names: &[String]) -> BatchResult<Vec<PendingUpdate>> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.get_pending_updates() })
+            .await
+    }
+
+    /// 找出有待安装安全更新的主机（按主机名排序）
+    ///
+    /// 查询失败的主机不计入结果，会记录警告。
+    pub async fn find_hosts_needing_security_patches(&self, host_names: &[String]) -> Vec<String> {
+        let batch_result = self.get_pending_updates_from_hosts(host_names).await;
+
+        let mut hosts = Vec::new();
+        for (host, result) in &batch_result.results {
+            match result {
+                HostOutcome::Ok { value: updates, .. } => {
+                    let security = updates.iter().filter(|u| u.security).count();
+                    if security > 0 {
+                        info!("Host '{}' has {} pending security update(s)", host, security);
+                        hosts.push(host.clone());
+                    }
+                }
+                HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => {
+                    warn!("Failed to query pending updates on host '{}': {}", host, e)
+                }
+                HostOutcome::Skipped(_) => {}
+            }
+        }
+        hosts.sort();
+        hosts
+    }
+
+    /// 获取所有主机的 SSH 主机密钥类型与 SHA256 指纹（不做校验，用于资产盘点与 known_hosts 核对）
+    pub async fn get_server_key_fingerprints_all(&self) -> BatchResult<(HostKeyType, String)> {
+        let host_names = self.host_names();
+        self.get_server_key_fingerprints_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机的 SSH 主机密钥类型与 SHA256 指纹（带并发控制）
+    pub async fn get_server_key_fingerprints_from_hosts(&self, host_names: &[String]) -> BatchResult<(HostKeyType, String)> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.server_key_fingerprint() })
+            .await
+    }
+
+    /// 获取所有主机相对控制端的时钟偏差（毫秒，正值表示远程时钟超前）
+    pub async fn get_time_offsets_all(&self) -> BatchResult<f64> {
+        let host_names = self.host_names();
+        self.get_time_offsets_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机相对控制端的时钟偏差（带并发控制）
+    pub async fn get_time_offsets_from_hosts(&self, host_names: &[String]) -> BatchResult<f64> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.get_time_offset_ms() })
+            .await
+    }
+
+    /// 找出时钟偏差绝对值超过 `max_offset_ms` 的主机及其偏差（按主机名排序）
+    ///
+    /// 查询失败的主机不计入结果，会记录警告。
+    pub async fn find_hosts_with_clock_drift_ms(&self, host_names: &[String], max_offset_ms: f64) -> Vec<(String, f64)> {
+        let batch_result = self.get_time_offsets_from_hosts(host_names).await;
+
+        let mut drifted = Vec::new();
+        for (host, result) in &batch_result.results {
+            match result {
+                HostOutcome::Ok { value: offset, .. } if offset.abs() > max_offset_ms => {
+                    info!("Host '{}' clock is off by {:.1} ms (limit {} ms)", host, offset, max_offset_ms);
+                    drifted.push((host.clone(), *offset));
+                }
+                HostOutcome::Ok { .. } | HostOutcome::Skipped(_) => {}
+                HostOutcome::Failed(e) | HostOutcome::Unreachable(e) => {
+                    warn!("Failed to read clock on host '{}': {}", host, e)
+                }
+            }
+        }
+        drifted.sort_by(|a, b| a.0.cmp(&b.0));
+        drifted
+    }
+
+    /// 获取所有主机的 LVM 卷组与逻辑卷
+    pub async fn get_lvm_info_all(&self) -> BatchResult<LvmInfo> {
+        let host_names = self.host_names();
+        self.get_lvm_info_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表的 LVM 卷组与逻辑卷（带并发控制）
+    pub async fn get_lvm_info_from_hosts(&self, host_names: &[String]) -> BatchResult<LvmInfo> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.get_lvm_info() })
+            .await
+    }
+
+    /// 获取所有主机上的 TCP 连接（可按状态过滤）
+    pub async fn get_tcp_connections_all(&self, state_filter: Option<&str>) -> BatchResult<Vec<TcpConnection>> {
+        let host_names = self.host_names();
+        self.get_tcp_connections_from_hosts(state_filter, &host_names).await
+    }
+
+    /// 获取指定主机上的 TCP 连接（带并发控制）
+    pub async fn get_tcp_connections_from_hosts(
+        &self,
+        state_filter: Option<&str>,
+        host_names: &[String],
+    ) -> BatchResult<Vec<TcpConnection>> {
+        let state_filter = state_filter.map(str::to_string);
+        self.execute_concurrent_operation(host_names, move |client| {
+            let state_filter = state_filter.clone();
+            async move { client.get_tcp_connections(state_filter.as_deref()) }
+        })
+        .await
+    }
+
+    /// 统计所有主机上指定端口的已建立连接数
+    pub async fn count_connections_to_port_all(&self, port: u16) -> BatchResult<u32> {
+        let host_names = self.host_names();
+        self.count_connections_to_port_from_hosts(port, &host_names).await
+    }
+
+    /// 统计指定主机上指定端口的已建立连接数（带并发控制）
+    pub async fn count_connections_to_port_from_hosts(&self, port: u16, host_names: &[String]) -> BatchResult<u32> {
+        self.execute_concurrent_operation(host_names, move |client| async move {
+            client.count_connections_to_port(port)
+        })
+        .await
+    }
+
+    /// 获取所有主机信任库中的 CA 证书
+    pub async fn get_trusted_certificates_all(&self) -> BatchResult<Vec<TrustStoreCert>> {
+        let host_names = self.host_names();
+        self.get_trusted_certificates_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表信任库中的 CA 证书（带并发控制）
+    pub async fn get_trusted_certificates_from_hosts(&self, host_names: &[String]) -> BatchResult<Vec<TrustStoreCert>> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.get_trusted_certificates() })
+            .await
+    }
+
+    /// 读取所有主机上指定路径的 POSIX ACL
+    pub async fn get_file_acl_all(&self, path: &str) -> BatchResult<FileAcl> {
+        let host_names = self.host_names();
+        self.get_file_acl_from_hosts(path, &host_names).await
+    }
+
+    /// 读取指定主机列表上指定路径的 POSIX ACL（带并发控制）
+    pub async fn get_file_acl_from_hosts(&self, path: &str, host_names: &[String]) -> BatchResult<FileAcl> {
+        let path = path.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let path = path.clone();
+            async move { client.get_file_acl(&path) }
+        })
+        .await
+    }
+
+    /// 在所有主机上设置一条 ACL 条目，结果表示是否发生变更
+    pub async fn set_file_acl_all(&self, path: &str, acl: &AclEntry, recursive: bool) -> BatchResult<bool> {
+        let host_names = self.host_names();
+        self.set_file_acl_on_hosts(path, acl, recursive, &host_names).await
+    }
+
+    /// 在指定主机列表上设置一条 ACL 条目，结果表示是否发生变更（带并发控制）
+    pub async fn set_file_acl_on_hosts(
+        &self,
+        path: &str,
+        acl: &AclEntry,
+        recursive: bool,
+        host_names: &[String],
+    ) -> BatchResult<bool> {
+        let path = path.to_string();
+        let acl = acl.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let path = path.clone();
+            let acl = acl.clone();
+            async move { client.set_file_acl(&path, &acl, recursive) }
+        })
+        .await
+        .with_changed(|changed| *changed)
+    }
+
+    /// 读取所有主机上指定服务的 PAM 配置
+    pub async fn get_pam_config_all(&self, service: &str) -> BatchResult<PamConfig> {
+        let host_names = self.host_names();
+        self.get_pam_config_from_hosts(service, &host_names).await
+    }
+
+    /// 读取指定主机列表上指定服务的 PAM 配置（带并发控制）
+    pub async fn get_pam_config_from_hosts(&self, service: &str, host_names: &[String]) -> BatchResult<PamConfig> {
+        let service = service.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let service = service.clone();
+            async move { client.get_pam_config(&service) }
+        })
+        .await
+    }
+
+    /// 检查所有主机上指定服务的 PAM 配置是否使用某个模块
+    pub async fn check_pam_module_all(&self, service: &str, module: &str) -> BatchResult<bool> {
+        let host_names = self.host_names();
+        self.check_pam_module_on_hosts(service, module, &host_names).await
+    }
+
+    /// 检查指定主机列表上指定服务的 PAM 配置是否使用某个模块（带并发控制）
+    pub async fn check_pam_module_on_hosts(&self, service: &str, module: &str, host_names: &[String]) -> BatchResult<bool> {
+        let service = service.to_string();
+        let module = module.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let service = service.clone();
+            let module = module.clone();
+            async move { client.check_pam_module(&service, &module) }
+        })
+        .await
+    }
+
+    /// 在所有主机上设置密码最小长度与复杂度，结果表示是否发生变更
+    pub async fn set_pam_password_policy_all(&self, min_length: u8, complexity: u8) -> BatchResult<bool> {
+        let host_names = self.host_names();
+        self.set_pam_password_policy_on_hosts(min_length, complexity, &host_names).await
+    }
+
+    /// 在指定主机列表上设置密码最小长度与复杂度，结果表示是否发生变更（带并发控制）
+    pub async fn set_pam_password_policy_on_hosts(
+        &self,
+        min_length: u8,
+        complexity: u8,
+        host_names: &[String],
+    ) -> BatchResult<bool> {
+        self.execute_concurrent_operation(host_names, move |client| async move {
+            client.set_pam_password_policy(min_length, complexity)
+        })
+        .await
+        .with_changed(|changed| *changed)
+    }
+
+    /// 检查所有主机是否信任指定 SHA256 指纹的证书
+    pub async fn is_certificate_trusted_all(&self, fingerprint: &str) -> BatchResult<bool> {
+        let host_names = self.host_names();
+        self.is_certificate_trusted_on_hosts(fingerprint, &host_names).await
+    }
+
+    /// 检查指定主机列表是否信任指定 SHA256 指纹的证书（带并发控制）
+    pub async fn is_certificate_trusted_on_hosts(&self, fingerprint: &str, host_names: &[String]) -> BatchResult<bool> {
+        let fingerprint = fingerprint.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let fingerprint = fingerprint.clone();
+            async move { client.is_certificate_trusted(&fingerprint) }
+        })
+        .await
+    }
+
+    /// 在所有主机上配置软件包仓库
+    pub async fn manage_repo_all(&self, config: &RepoConfig, state: UserState) -> BatchResult<RepoResult> {
+        let host_names = self.host_names();
+        self.manage_repo_on_hosts(config, state, &host_names).await
+    }
+
+    /// 在指定主机列表上配置软件包仓库（带并发控制）
+    pub async fn manage_repo_on_hosts(
+        &self,
+        config: &RepoConfig,
+        state: UserState,
+        host_names: &[String],
+    ) -> BatchResult<RepoResult> {
+        let config = config.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let config = config.clone();
+            let state = state.clone();
+            async move { client.manage_repo(&config, state) }
+        })
+        .await
+        .with_changed(|r| r.changed)
+    }
+
+    /// 在所有主机上加载或卸载内核模块
+    pub async fn manage_kernel_module_all(
+        &self,
+        config: &KernelModuleConfig,
+        state: UserState,
+    ) -> BatchResult<KernelModuleResult> {
+        let host_names = self.host_names();
+        self.manage_kernel_module_on_hosts(config, state, &host_names).await
+    }
+
+    /// 在指定主机列表上加载或卸载内核模块（带并发控制）
+    pub async fn manage_kernel_module_on_hosts(
+        &self,
+        config: &KernelModuleConfig,
+        state: UserState,
+        host_names: &[String],
+    ) -> BatchResult<KernelModuleResult> {
+        let config = config.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let config = config.clone();
+            let state = state.clone();
+            async move { client.manage_kernel_module(&config, state) }
+        })
+        .await
+        .with_changed(|r| r.changed)
+    }
+
+    /// 在所有主机上以事务方式部署一组文件
+    pub async fn deploy_fileset_all(&self, files: &[PlannedFile]) -> BatchResult<FileSetResult> {
+        let host_names = self.host_names();
+        self.deploy_fileset_on_hosts(files, &host_names).await
+    }
+
+    /// 在指定主机列表上以事务方式部署一组文件（带并发控制）
+    ///
+    /// 事务失败（包括已回滚）的主机记为失败，错误信息中包含每个文件的状态。
+    pub async fn deploy_fileset_on_hosts(
+        &self,
+        files: &[PlannedFile],
+        host_names: &[String],
+    ) -> BatchResult<FileSetResult> {
+        let files = files.to_vec();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let files = files.clone();
+            async move {
+                let result = client.deploy_fileset(&files)?;
+                if !result.success {
+                    return Err(AnsibleError::FileOperationError(format!(
+                        "{} ({})",
+                        result.error.as_deref().unwrap_or("File set deployment failed"),
+                        result.summary()
+                    )));
+                }
+                Ok(result)
+            }
+        })
+        .await
+        .with_changed(|r| r.changed)
+    }
+
+    /// 获取所有主机指定表的防火墙规则
+    pub async fn get_iptables_rules_all(&self, table: &str, ip_version: IpVersion) -> BatchResult<Vec<IptablesChain>> {
+        let host_names = self.host_names();
+        self.get_iptables_rules_from_hosts(table, ip_version, &host_names).await
+    }
+
+    /// 获取指定主机列表指定表的防火墙规则（带并发控制）
+    pub async fn get_iptables_rules_from_hosts(
+        &self,
+        table: &str,
+        ip_version: IpVersion,
+        host_names: &[String],
+    ) -> BatchResult<Vec<IptablesChain>> {
+        let table = table.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let table = table.clone();
+            async move { client.get_iptables_rules(&table, ip_version) }
+        })
+        .await
+    }
+
+    /// 在指定主机列表上将防火墙规则保存到文件（带并发控制）
+    pub async fn save_iptables_rules_on_hosts(
+        &self,
+        path: &str,
+        ip_version: IpVersion,
+        host_names: &[String],
+    ) -> BatchResult<()> {
+        let path = path.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let path = path.clone();
+            async move { client.save_iptables_rules(&path, ip_version) }
+        })
+        .await
+    }
+
+    /// 获取所有主机的内核日志
+    pub async fn get_dmesg_all(&self, since_boot_secs: Option<u64>) -> BatchResult<Vec<DmesgEntry>> {
+        let host_names = self.host_names();
+        self.get_dmesg_from_hosts(since_boot_secs, &host_names).await
+    }
+
+    /// 获取指定主机列表的内核日志（带并发控制）
+    pub async fn get_dmesg_from_hosts(
+        &self,
+        since_boot_secs: Option<u64>,
+        host_names: &[String],
+    ) -> BatchResult<Vec<DmesgEntry>> {
+        self.execute_concurrent_operation(host_names, move |client| async move { client.get_dmesg(since_boot_secs) })
+            .await
+    }
+
+    /// 在指定主机列表上按模式与严重级别搜索内核日志（带并发控制）
+    pub async fn search_dmesg_on_hosts(
+        &self,
+        pattern: &str,
+        severity_level: Option<u8>,
+        host_names: &[String],
+    ) -> BatchResult<Vec<DmesgEntry>> {
+        let pattern = pattern.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let pattern = pattern.clone();
+            async move { client.search_dmesg(&pattern, severity_level) }
+        })
+        .await
+    }
+
+    /// 获取所有主机的 hugepages 使用情况
+    pub async fn get_hugepages_info_all(&self) -> BatchResult<Vec<HugepagesInfo>> {
+        let host_names = self.host_names();
+        self.get_hugepages_info_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表的 hugepages 使用情况（带并发控制）
+    pub async fn get_hugepages_info_from_hosts(&self, host_names: &[String]) -> BatchResult<Vec<HugepagesInfo>> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.get_hugepages_info() })
+            .await
+    }
+
+    /// 在指定主机列表上设置 hugepages 数量，结果表示是否有变更（带并发控制）
+    pub async fn set_hugepages_count_on_hosts(
+        &self,
+        count: u64,
+        size_kb: u32,
+        persist: bool,
+        host_names: &[String],
+    ) -> BatchResult<bool> {
+        self.execute_concurrent_operation(host_names, move |client| async move {
+            client.set_hugepages_count(count, size_kb, persist)
+        })
+        .await
+    }
+
+    /// 在指定主机列表上将内核参数改为基线值，结果为修改前与基线的比较（带并发控制）
+    ///
+    /// 只修改运行时的值；有参数被修改的主机计为 changed。
+    pub async fn apply_sysctl_baseline_to_hosts(
+        &self,
+        baseline: &HashMap<String, String>,
+        host_names: &[String],
+    ) -> BatchResult<SysctlDiff> {
+        let baseline = baseline.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let baseline = baseline.clone();
+            async move { client.apply_sysctl_baseline(&baseline) }
+        })
+        .await
+        .with_changed(|diff| !diff.wrong_value.is_empty())
+    }
+
+    /// 获取所有主机的软件 RAID 阵列状态
+    pub async fn get_raid_arrays_all(&self) -> BatchResult<Vec<RaidArray>> {
+        let host_names = self.host_names();
+        self.get_raid_arrays_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表的软件 RAID 阵列状态（带并发控制）
+    pub async fn get_raid_arrays_from_hosts(&self, host_names: &[String]) -> BatchResult<Vec<RaidArray>> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.get_raid_arrays() })
+            .await
+    }
+
+    /// 获取所有主机上处于降级状态的 RAID 阵列
+    pub async fn find_degraded_raid_arrays_all(&self) -> BatchResult<Vec<RaidArray>> {
+        let host_names = self.host_names();
+        self.find_degraded_raid_arrays_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表上处于降级状态的 RAID 阵列（带并发控制）
+    pub async fn find_degraded_raid_arrays_from_hosts(&self, host_names: &[String]) -> BatchResult<Vec<RaidArray>> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.find_degraded_raid_arrays() })
+            .await
+    }
+
+    /// 检查所有主机的 sshd_config 安全基线
+    pub async fn audit_ssh_config_all(&self) -> BatchResult<SshConfigAudit> {
+        let host_names = self.host_names();
+        self.audit_ssh_config_from_hosts(&host_names).await
+    }
+
+    /// 检查指定主机列表的 sshd_config 安全基线（带并发控制）
+    pub async fn audit_ssh_config_from_hosts(&self, host_names: &[String]) -> BatchResult<SshConfigAudit> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.audit_ssh_config() })
+            .await
+    }
+
+    /// 获取所有主机的定时任务（cron 与 systemd 定时器）
+    pub async fn get_scheduled_tasks_all(&self) -> BatchResult<ScheduledTasks> {
+        let host_names = self.host_names();
+        self.get_scheduled_tasks_from_hosts(&host_names).await
+    }
+
+    /// 获取指定主机列表的定时任务（带并发控制）
+    pub async fn get_scheduled_tasks_from_hosts(&self, host_names: &[String]) -> BatchResult<ScheduledTasks> {
+        self.execute_concurrent_operation(
+            host_names,
+            |client| async move { client.get_all_scheduled_tasks() },
+        )
+        .await
+    }
+
+    /// 获取指定主机列表的 systemd 定时器（带并发控制）
+    pub async fn get_systemd_timers_from_hosts(&self, host_names: &[String]) -> BatchResult<Vec<SystemdTimer>> {
+        self.execute_concurrent_operation(
+            host_names,
+            |client| async move { client.get_systemd_timers() },
+        )
+        .await
+    }
+
+    /// 对所有主机执行磁盘 I/O 基准测试
+    pub async fn benchmark_disk_io_all(&self, test_path: &str, size_mb: u64) -> BatchResult<DiskBenchmark> {
+        let host_names = self.host_names();
+        self.benchmark_disk_io_on_hosts(test_path, size_mb, &host_names).await
+    }
+
+    /// 对指定主机列表执行磁盘 I/O 基准测试（带并发控制）
+    pub async fn benchmark_disk_io_on_hosts(
+        &self,
+        test_path: &str,
+        size_mb: u64,
+        host_names: &[String],
+    ) -> BatchResult<DiskBenchmark> {
+        let test_path = test_path.to_string();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let test_path = test_path.clone();
+            async move { client.benchmark_disk_io(&test_path, size_mb) }
+        })
+        .await
+    }
+
+    /// 获取指定主机列表的 DNS 配置（带并发控制）
+    pub async fn get_dns_config_from_hosts(&self, host_names: &[String]) -> BatchResult<DnsConfig> {
+        self.execute_concurrent_operation(host_names, |client| async move { client.get_dns_config() })
+            .await
+    }
+
+    /// 在指定主机列表上设置 DNS 配置，结果表示是否发生变更（带并发控制）
+    pub async fn set_dns_config_on_hosts(&self, config: &DnsConfig, host_names: &[String]) -> BatchResult<bool> {
+        let config = config.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let config = config.clone();
+            async move { client.set_dns_config(&config) }
+        })
+        .await
+        .with_changed(|changed| *changed)
+    }
+
+    /// 在所有主机上管理用户
+    pub async fn manage_user_all(
+        &self,
+        options: &crate::types::UserOptions,
+    ) -> BatchResult<crate::types::UserResult> {
+        let host_names = self.host_names();
+        self.manage_user_on_hosts(options, &host_names).await
+    }
+
+    /// 在指定主机列表上管理用户（带并发控制）
+    pub async fn manage_user_on_hosts(
+        &self,
+        options: &crate::types::UserOptions,
+        host_names: &[String],
+    ) -> BatchResult<crate::types::UserResult> {
+        let options = options.clone();
+        self.execute_concurrent_operation(host_names, move |client| {
+            let opts = options.clone();
+            async move { client.manage_user(&opts) }
+        })
+        .await
+        .with_changed(|r| r.changed)
+    }
+
+    /// 向所有主机部署模板
+    pub async fn deploy_template_to_all(
+        &self,
+        options: &crate::types::TemplateOptions,
+    ) -> BatchResult<crate::types::TemplateResult> {
+        let host_names = self.host_names();
+        self.deploy_template_to_hosts(options, &host_names).await
+    }
+
+    /// 向指定主机列表部署模板（带并发控制）
+    ///
+    /// `reload_command` 失败的主机仍返回结构化的结果：`success` 为 false，`rolled_back` / `critical`
+    /// 表示是否已恢复原文件、恢复是否也失败。这些主机不计入 `failed`，调用方需检查 `success`。
+    pub async fn deploy_template_to_hosts(
+        &self,
+        options: &crate::types::TemplateOptions,
+        host_names: &[String],
+    ) -> BatchResult<crate::types::TemplateResult> {
+        self.deploy_template_batch(options, host_names, None).await
+    }
+
+    /// 向指定主机列表部署模板，并为每台主机注入已收集的 facts（`ansible_facts` 变量）
+    pub async fn deploy_template_to_hosts_with_facts(
+        &self,
+        options: &crate::types::TemplateOptions,
+        host_names: &[String],
+        facts: &HashMap<String, SystemInfo>,
+    ) -> BatchResult<crate::types::TemplateResult> {
+        self.deploy_template_batch(options, host_names, Some(facts)).await
+    }
+
+    /// 先收集 facts 再部署模板，无需手动传入变量
+    ///
+    /// 每台主机的 facts 以 `ansible_facts` 注入，所有收集成功的主机的 facts 可通过 `hostvars` 访问
+    /// （例如根据所有主机的 IP 生成 `/etc/hosts`）。收集 facts 失败的主机不部署，保留收集时的错误。
+    pub async fn gather_facts_and_deploy_template(
+        &self,
+        options: &crate::types::TemplateOptions,
+        subset: FactSubset,
+        host_names: &[String],
+    ) -> BatchResult<crate::types::TemplateResult> {
+        let mut gathered = self.get_system_info_subset_from_hosts(subset, host_names).await;
+        let mut facts = HashMap::new();
+        let mut ready = Vec::new();
+        let mut not_ready = Vec::new();
+        for host in host_names {
+            match gathered.results.remove(host) {
+                Some(HostOutcome::Ok { value, .. }) => {
+                    facts.insert(host.clone(), value);
+                    ready.push(host.clone());
+                }
+                Some(HostOutcome::Failed(e)) => not_ready.push((host.clone(), HostOutcome::Failed(e))),
+                Some(HostOutcome::Unreachable(e)) => not_ready.push((host.clone(), HostOutcome::Unreachable(e))),
+                Some(HostOutcome::Skipped(reason)) => not_ready.push((host.clone(), HostOutcome::Skipped(reason))),
+                None => {}
+            }
+        }
+
+        let mut result = self.deploy_template_to_hosts_with_facts(options, &ready, &facts).await;
+        result.add_durations_from(&gathered);
+        result.add_metrics_from(&gathered);
+        for (host, outcome) in not_ready {
+            result.add_outcome(host, outcome);
+        }
+        result
+    }
+
+    /// 在本地为每台主机渲染模板（不连接任何主机），返回渲染失败的主机及其错误（按主机名排序）
+    ///
+    /// 渲染上下文与部署时一致：主机变量来自管理器中的主机配置，`facts` 中有该主机时注入 `ansible_facts`。
+    pub fn check_template_render(
+        &self,
+        options: &crate::types::TemplateOptions,
+        host_names: &[String],
+        facts: Option<&HashMap<String, SystemInfo>>,
+    ) -> Vec<(String, AnsibleError)> {
+        let options = self.with_hostvars(options, facts);
+        self.check_template_render_with_cache(&options, host_names, facts, &TemplateCache::new())
+    }
+
+    /// 注入 `hostvars` 变量（已显式指定时不覆盖）：所有主机的名称 -> 主机变量、连接地址与 facts
+    ///
+    /// 用于根据整个清单渲染配置，例如 `{% for name, host in hostvars %}{{ host.ansible_host }} {{ name }}{% endfor %}`。
+    fn with_hostvars(
+        &self,
+        options: &crate::types::TemplateOptions,
+        facts: Option<&HashMap<String, SystemInfo>>,
+    ) -> crate::types::TemplateOptions {
+        let mut options = options.clone();
+        if options.variables.contains_key("hostvars") {
+            return options;
+        }
+        let hostvars: serde_json::Map<String, serde_json::Value> = self
+            .hosts()
+            .iter()
+            .map(|(name, config)| {
+                let mut vars: serde_json::Map<String, serde_json::Value> =
+                    config.vars.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                vars.insert("inventory_hostname".to_string(), name.clone().into());
+                vars.insert("ansible_host".to_string(), config.hostname.clone().into());
+                if let Some(host_facts) = facts.and_then(|facts| facts.get(name))
+                    && let Ok(value) = serde_json::to_value(host_facts)
+                {
+                    vars.insert("ansible_facts".to_string(), value);
+                }
+                (name.clone(), vars.into())
+            })
+            .collect();
+        options.variables.insert("hostvars".to_string(), hostvars.into());
+        options
+    }
+
+    fn check_template_render_with_cache(
+        &self,
+        options: &crate::types::TemplateOptions,
+        host_names: &[String],
+        facts: Option<&HashMap<String, SystemInfo>>,
+        cache: &TemplateCache,
+    ) -> Vec<(String, AnsibleError)> {
+        let mut failures: Vec<(String, AnsibleError)> = host_names
+            .iter()
+            .filter_map(|host_name| {
+                // 未知主机留给部署阶段报告
+                let config = self.get_host(host_name)?;
+                let opts = template_options_for_host(options, host_name, facts);
+                cache.render_for_host(&opts, &config).err().map(|e| (host_name.clone(), e))
+            })
+            .collect();
+        failures.sort_by(|a, b| a.0.cmp(&b.0));
+        failures
+    }
+
+    async fn deploy_template_batch(
+        &self,
+        options: &crate::types::TemplateOptions,
+        host_names: &[String],
+        facts: Option<&HashMap<String, SystemInfo>>,
     ) -> BatchResult<crate::types::TemplateResult> {
-        let host_names: Vec<String> = self.hosts.keys().cloned().collect();
-        self.deploy_template_to_hosts(options, &host_names).await
+        // 整个批次共享模板缓存：模板只解析一次，相同渲染结果只生成一个本地文件，批次结束后统一清理
+        let cache = Arc::new(TemplateCache::new());
+        let options = self.with_hostvars(options, facts);
+
+        if options.validate_render_first {
+            let failures = self.check_template_render_with_cache(&options, host_names, facts, &cache);
+            if !failures.is_empty() {
+                return Self::abort_template_batch(host_names, failures);
+            }
+        }
+
+        let dest = options.dest.clone();
+        let facts = Arc::new(facts.cloned().unwrap_or_default());
+        let op_cache = cache.clone();
+        let result = self
+            .execute_concurrent_operation_with_host(host_names, move |host_name, client| {
+                let opts = template_options_for_host(&options, &host_name, Some(&facts));
+                let cache = op_cache.clone();
+                async move { client.deploy_template_with_cache(&opts, &cache) }
+            })
+            .await;
+        Self::log_template_cache_stats(&cache);
+
+        let mut critical: Vec<&String> = result
+            .results
+            .iter()
+            .filter(|(_, outcome)| outcome.value().is_some_and(|r| r.critical))
+            .map(|(host, _)| host)
+            .collect();
+        if !critical.is_empty() {
+            critical.sort();
+            error!(
+                "CRITICAL: {} host(s) are in an inconsistent state after a failed reload and rollback of {}: {}",
+                critical.len(),
+                dest,
+                critical.iter().map(|h| h.as_str()).collect::<Vec<_>>().join(", ")
+            );
+        }
+        result.with_changed(|r| r.changed)
     }
 
-    /// 向指定主机列表部署模板（带并发控制）
-    pub async fn deploy_template_to_hosts(
-        &self,
-        options: &crate::types::TemplateOptions,
+    /// 预渲染失败时不部署到任何主机：失败的主机报告各自的渲染错误，其余主机报告部署被中止
+    fn abort_template_batch(
         host_names: &[String],
+        failures: Vec<(String, AnsibleError)>,
     ) -> BatchResult<crate::types::TemplateResult> {
-        let options = options.clone();
-        self.execute_concurrent_operation(host_names, move |client| {
-            let opts = options.clone();
-            async move { client.deploy_template(&opts) }
-        })
-        .await
+        error!("Template rendering failed on {} host(s), nothing was deployed", failures.len());
+        let failed_hosts: Vec<String> = failures.iter().map(|(host, _)| host.clone()).collect();
+        let mut result = BatchResult::new();
+        for (host, e) in failures {
+            error!("  {}: {}", host, e);
+            result.add_result(host, Err(e));
+        }
+        for host in host_names.iter().filter(|host| !failed_hosts.contains(host)) {
+            result.add_result(
+                host.clone(),
+                Err(AnsibleError::TemplateError(format!(
+                    "Template deployment aborted: rendering failed on {}",
+                    failed_hosts.join(", ")
+                ))),
+            );
+        }
+        result
+    }
+
+    fn log_template_cache_stats(cache: &TemplateCache) {
+        let stats = cache.stats();
+        info!(
+            "Template cache: {} parse(s) ({} reused), {} render(s) ({} reused), parse {:.2}ms, render {:.2}ms, saved ~{:.2}ms",
+            stats.parses,
+            stats.parse_cache_hits,
+            stats.renders,
+            stats.render_cache_hits,
+            stats.parse_time_ms,
+            stats.render_time_ms,
+            stats.estimated_saved_ms
+        );
     }
 
     /// 通用的并发操作执行器
@@ -262,45 +2206,261 @@ impl AnsibleManager {
         T: Send + 'static,
         F: Fn(SshClient) -> Fut + Send + Sync + Clone + 'static,
         Fut: std::future::Future<Output = Result<T, AnsibleError>> + Send + 'static,
+    {
+        self.execute_concurrent_operation_with_host(host_names, move |_host, client| operation(client))
+            .await
+    }
+
+    /// 按管理器的凭据与带宽设置连接单台主机（阻塞调用），连接由调用方持有
+    pub(crate) fn connect_host(&self, host_name: &str) -> Result<SshClient, AnsibleError> {
+        let config = self
+            .get_host(host_name)
+            .ok_or_else(|| AnsibleError::SshConnectionError(format!("Host {} not found", host_name)))?;
+        if !config.enabled {
+            return Err(AnsibleError::ValidationError(format!("Host {} is disabled", host_name)));
+        }
+        connect_client(
+            host_name,
+            config,
+            self.transport_factory.as_ref(),
+            self.credential_provider.clone(),
+            self.bandwidth_limiter.clone(),
+            self.operation_options.retry_events.as_ref(),
+        )
+    }
+
+    /// 通用的并发操作执行器（操作闭包额外接收主机名，用于按主机定制参数）
+    ///
+    /// 操作使用同步的 `SshClient`，异步传输（`transport: russh`）的主机不支持这类操作，记为失败；
+    /// 同时支持两种传输的批量操作见 [`Self::execute_transport_operation`]。
+    pub async fn execute_concurrent_operation_with_host<T, F, Fut>(
+        &self,
+        host_names: &[String],
+        operation: F,
+    ) -> BatchResult<T>
+    where
+        T: Send + 'static,
+        F: Fn(String, SshClient) -> Fut + Send + Sync + Clone + 'static,
+        Fut: std::future::Future<Output = Result<T, AnsibleError>> + Send + 'static,
+    {
+        self.execute_transport_operation(host_names, operation, None::<SyncOnly<T>>)
+            .await
+    }
+
+    /// 按主机的传输方式执行批量操作
+    ///
+    /// 同步传输的主机在阻塞线程中连接并执行 `operation`；异步传输（`transport: russh`）的主机
+    /// 由 `AsyncSshClient` 执行 `async_operation`，连接与操作都在主机的 tokio 任务中直接 await，
+    /// 不占用阻塞线程。`async_operation` 为 `None` 时异步传输的主机同样交给 `operation`，
+    /// 连接时返回不支持的错误（设置了 `transport_factory` 时由工厂决定）。
+    pub async fn execute_transport_operation<T, F, Fut, A, AFut>(
+        &self,
+        host_names: &[String],
+        operation: F,
+        async_operation: Option<A>,
+    ) -> BatchResult<T>
+    where
+        T: Send + 'static,
+        F: Fn(String, SshClient) -> Fut + Send + Sync + Clone + 'static,
+        Fut: std::future::Future<Output = Result<T, AnsibleError>> + Send + 'static,
+        A: Fn(String, AsyncSshClient) -> AFut + Send + Sync + Clone + 'static,
+        AFut: std::future::Future<Output = Result<T, AnsibleError>> + Send + 'static,
+    {
+        let provider = self.credential_provider.clone();
+        let bandwidth_limiter = self.bandwidth_limiter.clone();
+        let factory = self.transport_factory.clone();
+        let async_factory = self.async_transport_factory.clone();
+        let retry_events = self.operation_options.retry_events.clone();
+        let runtime = tokio::runtime::Handle::current();
+        // 开启时收集每台主机的统计；连接失败的主机没有统计
+        let collected: Option<Arc<Mutex<HashMap<String, HostMetrics>>>> =
+            self.operation_options.host_metrics.then(Arc::default);
+        let sink = collected.clone();
+
+        let mut result = self
+            .execute_host_operation(host_names, move |host_name, config| {
+                let provider = provider.clone();
+                let bandwidth_limiter = bandwidth_limiter.clone();
+                let factory = factory.clone();
+                let async_factory = async_factory.clone();
+                let retry_events = retry_events.clone();
+                let runtime = runtime.clone();
+                let sink = sink.clone();
+                let operation = operation.clone();
+                let async_operation = async_operation.clone().filter(|_| config.transport.is_async());
+                async move {
+                    let Some(async_operation) = async_operation else {
+                        let name = host_name.clone();
+                        return run_blocking(&name, move || {
+                            let client = connect_client(
+                                &host_name,
+                                config,
+                                factory.as_ref(),
+                                provider,
+                                bandwidth_limiter,
+                                retry_events.as_ref(),
+                            )?;
+                            let counters = client.metrics_counters();
+                            // 操作本身是同步的 ssh2 调用，这里在阻塞线程中驱动其 Future
+                            let op_result = runtime.block_on(operation(host_name.clone(), client));
+                            if let Some(ref sink) = sink {
+                                sink.lock().expect("host metrics poisoned").insert(host_name, counters.snapshot());
+                            }
+                            op_result
+                        })
+                        .await;
+                    };
+
+                    let client = connect_async_client(
+                        &host_name,
+                        config,
+                        async_factory.as_ref(),
+                        provider,
+                        bandwidth_limiter,
+                        retry_events.as_ref(),
+                    )
+                    .await?;
+                    let counters = client.metrics_counters();
+                    let op_result = async_operation(host_name.clone(), client).await;
+                    if let Some(ref sink) = sink {
+                        sink.lock().expect("host metrics poisoned").insert(host_name, counters.snapshot());
+                    }
+                    op_result
+                }
+            })
+            .await;
+        if let Some(collected) = collected {
+            result.metrics = std::mem::take(&mut *collected.lock().expect("host metrics poisoned"));
+        }
+        result
+    }
+
+    /// 在 tokio 阻塞线程池中并发执行同步操作（ssh2 的连接、执行、传输都是阻塞调用）
+    ///
+    /// 异步工作线程不会被网络 IO 占用；调度规则见 [`Self::execute_host_operation`]。
+    #[cfg(test)]
+    pub(crate) async fn execute_blocking_operation<T, W>(
+        &self,
+        host_names: &[String],
+        work: W,
+    ) -> BatchResult<T>
+    where
+        T: Send + 'static,
+        W: Fn(String, HostConfig) -> Result<T, AnsibleError> + Send + Sync + Clone + 'static,
+    {
+        self.execute_host_operation(host_names, move |host_name, config| {
+            let work = work.clone();
+            async move {
+                let name = host_name.clone();
+                run_blocking(&name, move || work(host_name, config)).await
+            }
+        })
+        .await
+    }
+
+    /// 为每台主机启动一个 tokio 任务并 await `work` 返回的 Future
+    ///
+    /// 并发数由执行选项中的 `max_concurrency`（默认为 `max_concurrent_connections`）控制，
+    /// 启用自适应模式时会根据瞬时错误动态调整。`work` 在主机的 span 中运行，不得阻塞线程，
+    /// 同步操作需经 [`run_blocking`] 交给阻塞线程池。
+    async fn execute_host_operation<T, W, Fut>(
+        &self,
+        host_names: &[String],
+        work: W,
+    ) -> BatchResult<T>
+    where
+        T: Send + 'static,
+        W: Fn(String, HostConfig) -> Fut + Send + Sync + Clone + 'static,
+        Fut: std::future::Future<Output = Result<T, AnsibleError>> + Send + 'static,
     {
         let mut result = BatchResult::new();
+        // 别名按规范主机执行，同一台机器只执行一次
+        let host_names = &self.resolve_host_names(host_names);
 
-        // 创建信号量来控制并发数
-        let semaphore = Arc::new(Semaphore::new(self.max_concurrent_connections));
+        // 并发限制器（支持按操作覆盖上限与自适应调整）
+        let max_concurrency = self
+            .operation_options
+            .max_concurrency
+            .unwrap_or(self.max_concurrent_connections);
+        let limiter = Arc::new(ConcurrencyLimiter::new(max_concurrency, &self.operation_options));
+        let max_failures = self.operation_options.max_failures;
+        let cancellation = self.operation_options.cancellation.clone();
+        let failures = Arc::new(AtomicUsize::new(0));
         let mut handles = Vec::new();
 
         info!(
             "Starting concurrent operation on {} hosts with max {} concurrent connections",
             host_names.len(),
-            self.max_concurrent_connections
+            max_concurrency
         );
 
+        // 在启动前取主机配置快照，执行期间增删主机不影响本次操作
+        let snapshot: HashMap<String, HostConfig> = {
+            let hosts = self.hosts();
+            host_names.iter().filter_map(|name| Some((name.clone(), hosts.get(name)?.clone()))).collect()
+        };
         for host_name in host_names {
-            if let Some(config) = self.hosts.get(host_name) {
+            if snapshot.get(host_name).is_some_and(|config| !config.enabled) {
+                debug!(host = %host_name, "Host is disabled, skipping");
+                result.add_skipped(host_name.clone(), SkipReason::Disabled);
+                continue;
+            }
+            if let Some(config) = snapshot.get(host_name) {
                 let config = config.clone();
                 let host_name = host_name.clone();
-                let semaphore = semaphore.clone();
-                let operation = operation.clone();
+                let limiter = limiter.clone();
+                let failures = failures.clone();
+                let cancellation = cancellation.clone();
+                let work = work.clone();
+
+                // 每台主机的操作都在独立的 span 中执行，继承调用方的 playbook/task 字段
+                let host_span = info_span!(
+                    parent: Span::current(),
+                    "host",
+                    host = %host_name,
+                    attempt = tracing::field::Empty
+                );
 
-                let handle = task::spawn(async move {
-                    // 测试日志：确认日志是否能正确输出
-                    tracing::info!("Task started for host: {}", host_name);
+                let handle = task::spawn(
+                    async move {
+                        let limit_reached = || {
+                            max_failures.is_some_and(|max| failures.load(Ordering::SeqCst) >= max)
+                                || cancellation.as_ref().is_some_and(|token| token.is_cancelled())
+                        };
+                        if limit_reached() {
+                            return (host_name, None, Duration::ZERO);
+                        }
+
+                        debug!("Waiting for concurrency permit");
 
-                    // 获取信号量许可（限制并发数）
-                    let _permit = semaphore.acquire().await.expect("Semaphore closed");
+                        // 获取信号量许可（限制并发数）
+                        let permit = limiter.acquire().await;
 
-                    tracing::info!("Semaphore acquired for host: {}", host_name);
+                        debug!("Concurrency permit acquired");
+
+                        // 排队期间其他主机可能已经达到失败上限，或操作已被取消
+                        if limit_reached() {
+                            drop(permit);
+                            return (host_name, None, Duration::ZERO);
+                        }
 
-                    let client_result = SshClient::new(config);
-                    match client_result {
-                        Ok(client) => {
-                            tracing::info!("SSH client created for host: {}", host_name);
-                            let op_result = operation(client).await;
-                            (host_name, op_result)
+                        let started = Instant::now();
+                        let op_result = work(host_name.clone(), config).await;
+                        let elapsed = started.elapsed();
+                        info!(
+                            success = op_result.is_ok(),
+                            elapsed_ms = elapsed.as_millis() as u64,
+                            "Host operation finished"
+                        );
+                        metrics::record_host_operation(&host_name, elapsed, op_result.is_ok());
+                        if op_result.is_err() {
+                            failures.fetch_add(1, Ordering::SeqCst);
                         }
-                        Err(e) => (host_name, Err(e)),
+                        limiter.release(permit, &op_result);
+                        (host_name, Some(op_result), elapsed)
                     }
-                });
+                    .instrument(host_span),
+                );
                 handles.push(handle);
             } else {
                 result.add_result(
@@ -315,18 +2475,342 @@ impl AnsibleManager {
 
         // 等待所有任务完成
         for handle in handles {
-            if let Ok((host_name, op_result)) = handle.await {
-                result.add_result(host_name, op_result);
+            match handle.await {
+                Ok((host_name, Some(op_result), elapsed)) => {
+                    result.record_duration(&host_name, elapsed);
+                    result.add_result(host_name, op_result);
+                }
+                Ok((host_name, None, _)) => {
+                    let cancelled = cancellation.as_ref().is_some_and(|token| token.is_cancelled());
+                    let reason = if cancelled { SkipReason::Cancelled } else { SkipReason::FailureLimit };
+                    result.add_skipped(host_name, reason)
+                }
+                Err(_) => {}
             }
         }
 
+        if !result.skipped.is_empty() {
+            let reason = if result.results.values().any(|r| matches!(r, HostOutcome::Skipped(SkipReason::Cancelled))) {
+                "Operation cancelled"
+            } else {
+                "Failure limit reached"
+            };
+            warn!(
+                "{}, {} host(s) not started: {}",
+                reason,
+                result.skipped.len(),
+                result.skipped.join(", ")
+            );
+        }
+
         info!(
-            "Concurrent operation completed. Success rate: {:.2}%",
-            result.success_rate() * 100.0
+            "Concurrent operation completed. Success rate: {:.2}%, final concurrency: {}",
+            result.success_rate() * 100.0,
+            limiter.effective_concurrency()
         );
         result
     }
 
+    /// 在后台运行 playbook，返回可查询状态与取消运行的句柄
+    ///
+    /// 需要在 tokio 运行时中调用。运行使用管理器当前配置的副本，之后对管理器的修改不影响本次运行。
+    pub fn spawn_playbook(&self, playbook: Playbook) -> RunHandle {
+        self.spawn_playbook_with(playbook, |executor| executor)
+    }
+
+    /// 在后台运行 playbook，`configure` 可为执行器注册回调、设置 limit 等
+    pub fn spawn_playbook_with<F>(&self, playbook: Playbook, configure: F) -> RunHandle
+    where
+        F: for<'a> FnOnce(TaskExecutor<'a>) -> TaskExecutor<'a> + Send + 'static,
+    {
+        let cancellation = CancellationToken::new();
+        let mut manager = self.scoped_clone();
+        manager.operation_options = manager.operation_options.cancellation(cancellation.clone());
+
+        let status = Arc::new(RwLock::new(RunStatus::new(
+            &playbook.name,
+            playbook.tasks.len(),
+            &self.host_names(),
+        )));
+        let run_status = status.clone();
+        let join = task::spawn(async move {
+            let executor = configure(TaskExecutor::new(&manager).with_status(run_status));
+            executor.execute_playbook(&playbook).await
+        });
+        RunHandle::new(status, cancellation, join)
+    }
+
+    /// 按依赖关系分阶段部署
+    ///
+    /// 根据 `depends_on` 构建依赖图并检查循环依赖，然后按层执行：同一深度的阶段并发执行，
+    /// 每个阶段的 playbook 只在该阶段的主机上运行。某个阶段失败后，直接或间接依赖它的阶段不会启动，
+    /// 其余阶段照常执行。阶段名重复、依赖不存在或存在循环时返回 `ValidationError`。
+    pub async fn parallel_deploy(&self, stages: Vec<DeploymentStage>) -> Result<DeploymentResult, AnsibleError> {
+        let levels = deployment_levels(&stages)?;
+        let started = Instant::now();
+        let mut stages: HashMap<String, DeploymentStage> =
+            stages.into_iter().map(|stage| (stage.name.clone(), stage)).collect();
+        let mut result = DeploymentResult {
+            stage_results: HashMap::new(),
+            skipped_stages: Vec::new(),
+            total_duration: Duration::ZERO,
+            success: true,
+        };
+
+        for (depth, level) in levels.into_iter().enumerate() {
+            let mut running = task::JoinSet::new();
+            for name in level {
+                let Some(stage) = stages.remove(&name) else {
+                    continue;
+                };
+                let blocked_by = stage.depends_on.iter().find(|dep| {
+                    result.skipped_stages.contains(dep)
+                        || result.stage_results.get(*dep).is_some_and(|r| !r.overall_success)
+                });
+                if let Some(dep) = blocked_by {
+                    warn!("Skipping stage '{}' because dependency '{}' did not succeed", stage.name, dep);
+                    result.skipped_stages.push(stage.name);
+                    continue;
+                }
+
+                info!("Starting deployment stage '{}' (level {}) on {} host(s)", stage.name, depth, stage.hosts.len());
+                let manager = self.scoped_clone();
+                let playbook = stage.playbook_for_hosts();
+                running.spawn(async move {
+                    let outcome = TaskExecutor::new(&manager).execute_playbook(&playbook).await;
+                    (stage.name, outcome)
+                });
+            }
+
+            let mut first_error = None;
+            while let Some(joined) = running.join_next().await {
+                match joined {
+                    Ok((name, Ok(stage_result))) => {
+                        info!("Deployment stage '{}' finished (success: {})", name, stage_result.overall_success);
+                        result.stage_results.insert(name, stage_result);
+                    }
+                    Ok((name, Err(e))) => {
+                        warn!("Deployment stage '{}' aborted: {}", name, e);
+                        first_error.get_or_insert(e);
+                    }
+                    Err(e) => {
+                        first_error.get_or_insert(AnsibleError::CommandExecutionError(format!(
+                            "Deployment stage task failed: {}",
+                            e
+                        )));
+                    }
+                }
+            }
+            // 同层其他阶段已经执行完毕，再返回错误
+            if let Some(e) = first_error {
+                return Err(e);
+            }
+        }
+
+        result.skipped_stages.sort();
+        result.total_duration = started.elapsed();
+        result.success =
+            result.skipped_stages.is_empty() && result.stage_results.values().all(|r| r.overall_success);
+        Ok(result)
+    }
+
+    /// 金丝雀发布：先在一小部分主机上执行 `canary_playbook` 并观察，健康后再在其余主机上执行 `full_playbook`
+    ///
+    /// 金丝雀主机为按主机名排序后的前 `canary_percent`% 台。金丝雀 playbook 成功后，在 `monitor_duration`
+    /// 内每隔 `CANARY_PROBE_INTERVAL` 执行一次健康探针，任意一次未通过的主机视为不健康。金丝雀 playbook 失败，
+    /// 或健康主机比例低于 `success_threshold` 时中止，不执行全量发布（`full_rollout_executed` 为 false）。
+    pub async fn execute_canary_deployment(
+        &self,
+        canary_playbook: &Playbook,
+        full_playbook: &Playbook,
+        config: &CanaryConfig,
+    ) -> Result<CanaryResult, AnsibleError> {
+        if !(config.canary_percent > 0.0 && config.canary_percent <= 100.0) {
+            return Err(AnsibleError::ValidationError(format!(
+                "Canary percent must be in (0, 100], got {}",
+                config.canary_percent
+            )));
+        }
+        if !(0.0..=1.0).contains(&config.success_threshold) {
+            return Err(AnsibleError::ValidationError(format!(
+                "Canary success threshold must be between 0.0 and 1.0, got {}",
+                config.success_threshold
+            )));
+        }
+        let mut host_names = self.host_names();
+        if host_names.is_empty() {
+            return Err(AnsibleError::ValidationError("No hosts available for canary deployment".to_string()));
+        }
+        host_names.sort();
+        let canary_count =
+            ((host_names.len() as f32 * config.canary_percent / 100.0).ceil() as usize).clamp(1, host_names.len());
+        let (canary_hosts, remaining_hosts) = host_names.split_at(canary_count);
+        info!(
+            "Starting canary deployment on {} of {} host(s): {}",
+            canary_hosts.len(),
+            host_names.len(),
+            canary_hosts.join(", ")
+        );
+
+        let canary_result = TaskExecutor::new(self)
+            .execute_playbook(&playbook_for_hosts(canary_playbook, canary_hosts))
+            .await?;
+        let mut result = CanaryResult {
+            canary_hosts: canary_hosts.to_vec(),
+            canary_result,
+            canary_success_rate: 0.0,
+            full_rollout_executed: false,
+            full_rollout_result: None,
+        };
+        if !result.canary_result.overall_success {
+            warn!("Canary playbook '{}' failed, aborting rollout", canary_playbook.name);
+            return Ok(result);
+        }
+
+        let unhealthy = self.monitor_canary_hosts(canary_hosts, config).await;
+        result.canary_success_rate = (canary_hosts.len() - unhealthy.len()) as f32 / canary_hosts.len() as f32;
+        if result.canary_success_rate < config.success_threshold {
+            warn!(
+                "Canary health check '{}' failed on {} host(s) ({}), aborting rollout",
+                config.canary_health_check.name,
+                unhealthy.len(),
+                unhealthy.join(", ")
+            );
+            return Ok(result);
+        }
+        if remaining_hosts.is_empty() {
+            info!("Canary healthy, no hosts left for full rollout");
+            return Ok(result);
+        }
+
+        info!(
+            "Canary healthy ({:.0}%), rolling out to {} remaining host(s)",
+            result.canary_success_rate * 100.0,
+            remaining_hosts.len()
+        );
+        let full_result = TaskExecutor::new(self)
+            .execute_playbook(&playbook_for_hosts(full_playbook, remaining_hosts))
+            .await?;
+        result.full_rollout_executed = true;
+        result.full_rollout_result = Some(full_result);
+        Ok(result)
+    }
+
+    /// 在观察期内反复探测金丝雀主机，返回至少一次未通过（包括无法执行探针）的主机；不健康的主机不再探测
+    async fn monitor_canary_hosts(&self, canary_hosts: &[String], config: &CanaryConfig) -> Vec<String> {
+        let probes = [config.canary_health_check.clone()];
+        let deadline = Instant::now() + config.monitor_duration;
+        let mut unhealthy: Vec<String> = Vec::new();
+        loop {
+            let healthy: Vec<String> =
+                canary_hosts.iter().filter(|host| !unhealthy.contains(host)).cloned().collect();
+            let batch = self.run_health_probes_on_hosts(&probes, &healthy).await;
+            for host in healthy {
+                let passed = batch
+                    .results
+                    .get(&host)
+                    .and_then(HostOutcome::value)
+                    .is_some_and(|results| results.iter().all(|r| r.passed));
+                if !passed {
+                    debug!("Canary host '{}' failed health check '{}'", host, config.canary_health_check.name);
+                    unhealthy.push(host);
+                }
+            }
+            let now = Instant::now();
+            if unhealthy.len() == canary_hosts.len() || now >= deadline {
+                return unhealthy;
+            }
+            tokio::time::sleep(CANARY_PROBE_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    /// 将主机优雅地摘除出服务（drain），成功后从主机列表中移除
+    ///
+    /// 依次执行 pre_drain_command、drain_command、verify_command，任一步失败即停止；
+    /// 全部成功后移除主机，并在剩余主机上执行 post_remove_tasks。
+    pub async fn drain_host(
+        &self,
+        host_name: &str,
+        config: &DrainConfig,
+    ) -> Result<DrainResult, AnsibleError> {
+        if !self.hosts().contains_key(host_name) {
+            return Err(AnsibleError::SshConnectionError(format!(
+                "Host {} not found",
+                host_name
+            )));
+        }
+
+        info!("Draining host: {}", host_name);
+
+        let steps = config.clone();
+        let mut batch_result = self
+            .execute_concurrent_operation(&[host_name.to_string()], move |client| {
+                let steps = steps.clone();
+                async move { Self::run_drain_steps(&client, &steps) }
+            })
+            .await;
+
+        let mut result = match batch_result.results.remove(host_name).and_then(HostOutcome::into_result) {
+            Some(result) => result?,
+            None => {
+                return Err(AnsibleError::CommandExecutionError(format!(
+                    "No drain result returned for host {}",
+                    host_name
+                )));
+            }
+        };
+
+        if !self.apply_drain_result(host_name, &result) {
+            info!("Drain of host '{}' did not complete, host kept in inventory", host_name);
+            return Ok(result);
+        }
+
+        if !config.post_remove_tasks.is_empty() {
+            let mut playbook = Playbook::new(&format!("post-drain: {}", host_name));
+            playbook.tasks = config.post_remove_tasks.clone();
+            let executor = TaskExecutor::new(self);
+            result.post_tasks_result = Some(executor.execute_playbook(&playbook).await?);
+        }
+
+        Ok(result)
+    }
+
+    /// 在单个连接上顺序执行 drain 步骤
+    fn run_drain_steps(client: &SshClient, config: &DrainConfig) -> Result<DrainResult, AnsibleError> {
+        let mut result = DrainResult::default();
+
+        if let Some(ref cmd) = config.pre_drain_command {
+            result.pre_drain_ok = client.execute_command(cmd)?.exit_code == 0;
+            if !result.pre_drain_ok {
+                return Ok(result);
+            }
+        } else {
+            result.pre_drain_ok = true;
+        }
+
+        result.drained = client.execute_command(&config.drain_command)?.exit_code == 0;
+        if !result.drained {
+            return Ok(result);
+        }
+
+        result.verified = match config.verify_command {
+            Some(ref cmd) => client.execute_command(cmd)?.exit_code == 0,
+            None => true,
+        };
+
+        Ok(result)
+    }
+
+    /// 根据 drain 结果决定是否移除主机，返回主机是否已被移除
+    pub(crate) fn apply_drain_result(&self, host_name: &str, result: &DrainResult) -> bool {
+        if result.is_success() {
+            info!("Host '{}' drained successfully, removing from inventory", host_name);
+            self.remove_host(host_name).is_some()
+        } else {
+            false
+        }
+    }
+
     /// 批量操作统计信息
     pub async fn get_batch_operation_stats(&self, host_names: &[String]) -> BatchOperationStats {
         BatchOperationStats {
@@ -349,6 +2833,160 @@ impl AnsibleManager {
     }
 }
 
+/// 建立连接（设置了传输工厂时使用工厂，否则建立 SSH 连接）并应用共享的带宽限制
+///
+/// SSH 连接失败后的每次重试都会发送到 `retry_events`（如果设置）。
+fn connect_client(
+    host_name: &str,
+    config: HostConfig,
+    factory: Option<&TransportFactory>,
+    provider: Option<Arc<dyn CredentialProvider>>,
+    bandwidth_limiter: Option<Arc<BandwidthLimiter>>,
+    retry_events: Option<&UnboundedSender<RetryEvent>>,
+) -> Result<SshClient, AnsibleError> {
+    let mut client = match factory {
+        Some(factory) => {
+            let started = Instant::now();
+            let transport = factory(host_name, &config)?;
+            let client = SshClient::with_transport(config, transport);
+            client.record_connect_time(started.elapsed());
+            client
+        }
+        None => SshClient::connect_with_retry_hook(config, provider, &|attempt, error| {
+            if let Some(sender) = retry_events {
+                let _ = sender.send(RetryEvent {
+                    host: host_name.to_string(),
+                    attempt,
+                    error: error.to_string(),
+                });
+            }
+        })?,
+    };
+    client.set_bandwidth_limiter(bandwidth_limiter);
+    debug!("SSH client created");
+    Ok(client)
+}
+
+/// 建立异步连接（设置了异步传输工厂时使用工厂，否则建立 russh 连接）并应用共享的带宽限制
+///
+/// 连接失败后的每次重试都会发送到 `retry_events`（如果设置）。
+async fn connect_async_client(
+    host_name: &str,
+    config: HostConfig,
+    factory: Option<&AsyncTransportFactory>,
+    provider: Option<Arc<dyn CredentialProvider>>,
+    bandwidth_limiter: Option<Arc<BandwidthLimiter>>,
+    retry_events: Option<&UnboundedSender<RetryEvent>>,
+) -> Result<AsyncSshClient, AnsibleError> {
+    let mut client = match factory {
+        Some(factory) => {
+            let started = Instant::now();
+            let transport = factory(host_name, &config)?;
+            let client = AsyncSshClient::with_transport(config, transport);
+            client.metrics_counters().record_connect_time(started.elapsed());
+            client
+        }
+        None => {
+            AsyncSshClient::connect_with_retry_hook(config, provider, &|attempt, error| {
+                if let Some(sender) = retry_events {
+                    let _ = sender.send(RetryEvent {
+                        host: host_name.to_string(),
+                        attempt,
+                        error: error.to_string(),
+                    });
+                }
+            })
+            .await?
+        }
+    };
+    client.set_bandwidth_limiter(bandwidth_limiter);
+    debug!("Async SSH client created");
+    Ok(client)
+}
+
+/// 非零退出码视为失败
+fn nonzero_exit_as_error(result: CommandResult) -> Result<CommandResult, AnsibleError> {
+    if result.exit_code != 0 {
+        return Err(AnsibleError::CommandError(format!(
+            "Command exited with code {}: {}",
+            result.exit_code,
+            result.stderr.trim()
+        )));
+    }
+    Ok(result)
+}
+
+/// 把未成功的传输结果转换为错误
+fn successful_transfer(transfer: FileTransferResult) -> Result<FileTransferResult, AnsibleError> {
+    if transfer.success {
+        Ok(transfer)
+    } else {
+        Err(AnsibleError::FileOperationError(transfer.message))
+    }
+}
+
+fn script_copy_error(e: AnsibleError) -> AnsibleError {
+    AnsibleError::FileOperationError(format!("Failed to copy script to remote host: {}", e))
+}
+
+/// 下载到主机目录中与远程文件同名的文件
+fn fetch_destination(remote_path: &str, host_dir: &std::path::Path) -> Result<std::path::PathBuf, AnsibleError> {
+    let file_name = std::path::Path::new(remote_path).file_name().ok_or_else(|| {
+        AnsibleError::ValidationError(format!("Remote path {} has no file name", remote_path))
+    })?;
+    Ok(host_dir.join(file_name))
+}
+
+/// 没有异步版本的批量操作传给 [`AnsibleManager::execute_transport_operation`] 的占位类型
+type SyncOnly<T> = fn(String, AsyncSshClient) -> std::future::Ready<Result<T, AnsibleError>>;
+
+/// 在阻塞线程中执行同步操作，保留当前的主机 span；任务异常结束时转换为错误
+async fn run_blocking<T, W>(host_name: &str, work: W) -> Result<T, AnsibleError>
+where
+    T: Send + 'static,
+    W: FnOnce() -> Result<T, AnsibleError> + Send + 'static,
+{
+    let span = Span::current();
+    task::spawn_blocking(move || span.in_scope(work))
+        .await
+        .unwrap_or_else(|e| {
+            Err(AnsibleError::CommandExecutionError(format!(
+                "Blocking task for host {} failed: {}",
+                host_name, e
+            )))
+        })
+}
+
+/// 批量传输前预先计算本地文件 Hash（SHA256），避免每个并发任务都重复计算
+///
+/// 计算失败（例如文件不存在）时保持原样，留给底层的 SshClient 再次尝试并汇报具体的错误。
+fn with_precomputed_hash(local_path: &str, options: &FileCopyOptions) -> FileCopyOptions {
+    let mut options = options.clone();
+    if options.precomputed_hash.is_none()
+        && let Ok(hash) = crate::utils::calculate_file_hash(local_path, "sha256")
+    {
+        info!("Pre-calculated local file hash for batch transfer: {}", hash);
+        options.precomputed_hash = Some(hash);
+    }
+    options
+}
+
+/// 为单台主机准备模板参数：有该主机的 facts 且未显式指定时注入 `ansible_facts` 变量
+fn template_options_for_host(
+    options: &crate::types::TemplateOptions,
+    host_name: &str,
+    facts: Option<&HashMap<String, SystemInfo>>,
+) -> crate::types::TemplateOptions {
+    let mut options = options.clone();
+    if let Some(host_facts) = facts.and_then(|facts| facts.get(host_name))
+        && !options.variables.contains_key("ansible_facts")
+        && let Ok(value) = serde_json::to_value(host_facts)
+    {
+        options.variables.insert("ansible_facts".to_string(), value);
+    }
+    options
+}
+
 #[derive(Debug, Serialize)]
 pub struct BatchOperationStats {
     pub total_hosts: usize,
@@ -356,6 +2994,166 @@ pub struct BatchOperationStats {
     pub estimated_duration_seconds: f32,
 }
 
+/// 主机摘除（drain）配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainConfig {
+    pub pre_drain_command: Option<String>, // drain 前执行的命令（例如 kubectl cordon）
+    pub drain_command: String,             // drain 命令
+    pub verify_command: Option<String>,    // 验证 drain 是否完成的命令
+    #[serde(default)]
+    pub post_remove_tasks: Vec<Task>,      // 主机移除后在剩余主机上执行的任务
+}
+
+/// 主机摘除（drain）结果
+#[derive(Debug, Default)]
+pub struct DrainResult {
+    pub pre_drain_ok: bool,
+    pub drained: bool,
+    pub verified: bool,
+    pub post_tasks_result: Option<PlaybookResult>,
+}
+
+impl DrainResult {
+    /// drain 的各步骤是否全部成功
+    pub fn is_success(&self) -> bool {
+        self.pre_drain_ok && self.drained && self.verified
+    }
+}
+
+/// 复制并执行（deploy_and_run）的批量结果
+#[derive(Debug)]
+pub struct DeployRunBatchResult {
+    pub batch: BatchResult<DeployRunResult>,
+    pub transfer_failed: Vec<String>, // 传输失败、未执行命令的主机（已按主机名排序）
+}
+
+/// 分阶段部署中的一个阶段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentStage {
+    pub name: String,
+    pub hosts: Vec<String>, // 本阶段的目标主机，任务中指定的主机会与之取交集
+    pub playbook: Playbook,
+    #[serde(default)]
+    pub depends_on: Vec<String>, // 必须先成功完成的阶段
+}
+
+impl DeploymentStage {
+    pub fn new(name: &str, hosts: &[&str], playbook: Playbook) -> Self {
+        Self {
+            name: name.to_string(),
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            playbook,
+            depends_on: Vec::new(),
+        }
+    }
+
+    pub fn depends_on(mut self, stages: &[&str]) -> Self {
+        self.depends_on = stages.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// 将 playbook 中各任务的目标主机限制在本阶段的主机内
+    fn playbook_for_hosts(&self) -> Playbook {
+        playbook_for_hosts(&self.playbook, &self.hosts)
+    }
+}
+
+/// 将 playbook 中各任务的目标主机限制在 `hosts` 内，任务中指定的主机与之取交集
+fn playbook_for_hosts(playbook: &Playbook, hosts: &[String]) -> Playbook {
+    let mut playbook = playbook.clone();
+    for task in &mut playbook.tasks {
+        task.hosts = Some(match task.hosts.take() {
+            Some(task_hosts) => task_hosts.into_iter().filter(|h| hosts.contains(h)).collect(),
+            None => hosts.to_vec(),
+        });
+    }
+    playbook
+}
+
+/// 分阶段部署结果
+#[derive(Debug)]
+pub struct DeploymentResult {
+    pub stage_results: HashMap<String, PlaybookResult>, // 已执行阶段的结果
+    pub skipped_stages: Vec<String>,                     // 因依赖失败而未启动的阶段
+    pub total_duration: Duration,
+    pub success: bool,
+}
+
+/// 金丝雀发布配置
+#[derive(Debug, Clone)]
+pub struct CanaryConfig {
+    pub canary_percent: f32,              // 先行发布的主机百分比（0-100），至少 1 台
+    pub monitor_duration: Duration,       // 金丝雀 playbook 完成后的观察时长，为 0 时只探测一次
+    pub success_threshold: f32,           // 继续全量发布所需的健康金丝雀主机比例（0.0-1.0）
+    pub canary_health_check: HealthProbe, // 观察期内在金丝雀主机上执行的健康探针
+    pub full_rollout_playbook: Playbook,  // 全量发布的 playbook，随配置一起保存，通常作为 `full_playbook` 传入
+}
+
+/// 金丝雀发布结果
+#[derive(Debug)]
+pub struct CanaryResult {
+    pub canary_hosts: Vec<String>,
+    pub canary_result: PlaybookResult,               // 金丝雀 playbook 的执行结果
+    pub canary_success_rate: f32,                    // 观察期内始终健康的金丝雀主机比例；金丝雀 playbook 失败时为 0
+    pub full_rollout_executed: bool,
+    pub full_rollout_result: Option<PlaybookResult>,
+}
+
+/// 按依赖深度对阶段分层（同层阶段之间没有依赖），并校验阶段名与依赖关系
+fn deployment_levels(stages: &[DeploymentStage]) -> Result<Vec<Vec<String>>, AnsibleError> {
+    let mut depth: HashMap<&str, Option<usize>> = HashMap::new();
+    for stage in stages {
+        if depth.insert(&stage.name, None).is_some() {
+            return Err(AnsibleError::ValidationError(format!(
+                "Duplicate deployment stage '{}'",
+                stage.name
+            )));
+        }
+    }
+    for stage in stages {
+        if let Some(dep) = stage.depends_on.iter().find(|dep| !depth.contains_key(dep.as_str())) {
+            return Err(AnsibleError::ValidationError(format!(
+                "Stage '{}' depends on unknown stage '{}'",
+                stage.name, dep
+            )));
+        }
+    }
+
+    // 反复为依赖都已定层的阶段定层，直到无法推进
+    let mut remaining: Vec<&DeploymentStage> = stages.iter().collect();
+    while !remaining.is_empty() {
+        let before = remaining.len();
+        remaining.retain(|stage| {
+            let deps: Option<Vec<usize>> = stage.depends_on.iter().map(|dep| depth[dep.as_str()]).collect();
+            match deps {
+                Some(deps) => {
+                    depth.insert(&stage.name, Some(deps.into_iter().max().map_or(0, |d| d + 1)));
+                    false
+                }
+                None => true,
+            }
+        });
+        if remaining.len() == before {
+            let mut cyclic: Vec<&str> = remaining.iter().map(|stage| stage.name.as_str()).collect();
+            cyclic.sort();
+            return Err(AnsibleError::ValidationError(format!(
+                "Circular dependency between deployment stages: {}",
+                cyclic.join(", ")
+            )));
+        }
+    }
+
+    let mut levels: Vec<Vec<String>> = Vec::new();
+    for stage in stages {
+        let level = depth[stage.name.as_str()].unwrap_or_default();
+        if levels.len() <= level {
+            levels.resize(level + 1, Vec::new());
+        }
+        levels[level].push(stage.name.clone());
+    }
+    Ok(levels)
+}
+
 #[derive(Default)]
 pub struct HostConfigBuilder {
     config: HostConfig,
@@ -393,11 +3191,49 @@ impl HostConfigBuilder {
         self
     }
 
+    /// 使用内存中的私钥（PEM 内容）认证，私钥不会写入磁盘
+    pub fn private_key_data(mut self, pem: &str) -> Self {
+        self.config.private_key_data = Some(pem.to_string());
+        self
+    }
+
+    /// 与 private_key_data 配套的公钥（可选）
+    pub fn public_key_data(mut self, public_key: &str) -> Self {
+        self.config.public_key_data = Some(public_key.to_string());
+        self
+    }
+
     pub fn passphrase(mut self, passphrase: &str) -> Self {
         self.config.passphrase = Some(passphrase.to_string());
         self
     }
 
+    pub fn r#become(mut self, enabled: bool) -> Self {
+        self.config.r#become = enabled;
+        self
+    }
+
+    pub fn become_user(mut self, user: &str) -> Self {
+        self.config.become_user = Some(user.to_string());
+        self
+    }
+
+    pub fn become_password(mut self, password: &str) -> Self {
+        self.config.become_password = Some(password.to_string());
+        self
+    }
+
+    pub fn remote_tmp(mut self, dir: &str) -> Self {
+        self.config.remote_tmp = Some(dir.to_string());
+        self
+    }
+
+    /// 设置主机变量（渲染模板时可用）
+    pub fn var(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.config.vars.insert(key.to_string(), value.into());
+        self
+    }
+
     pub fn build(self) -> HostConfig {
         self.config
     }
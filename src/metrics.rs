@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+use std::collections::HashSet;
+#[cfg(feature = "metrics")]
+use std::sync::{Mutex, OnceLock, RwLock};
+
+/// 指标标签取值策略，用于控制主机名/任务名带来的标签基数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LabelMode {
+    /// 原样使用主机名与任务名
+    #[default]
+    Full,
+    /// 仅保留最先出现的 N 个不同取值，其余归入 `"other"`
+    Capped(usize),
+    /// 将取值哈希到固定数量的桶中（`"bucket-<n>"`），跨进程稳定
+    Hashed(u16),
+}
+
+/// 设置全局的标签取值策略（未启用 `metrics` feature 时无效）
+pub fn set_label_mode(mode: LabelMode) {
+    #[cfg(feature = "metrics")]
+    {
+        *labels().mode.write().expect("metrics label mode poisoned") = mode;
+        labels().seen_hosts.lock().expect("metrics labels poisoned").clear();
+        labels().seen_tasks.lock().expect("metrics labels poisoned").clear();
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = mode;
+}
+
+#[cfg(feature = "metrics")]
+struct Labels {
+    mode: RwLock<LabelMode>,
+    seen_hosts: Mutex<HashSet<String>>,
+    seen_tasks: Mutex<HashSet<String>>,
+}
+
+#[cfg(feature = "metrics")]
+fn labels() -> &'static Labels {
+    static LABELS: OnceLock<Labels> = OnceLock::new();
+    LABELS.get_or_init(|| Labels {
+        mode: RwLock::new(LabelMode::default()),
+        seen_hosts: Mutex::new(HashSet::new()),
+        seen_tasks: Mutex::new(HashSet::new()),
+    })
+}
+
+/// 按当前策略计算标签取值
+#[cfg(feature = "metrics")]
+fn label_value(value: &str, seen: &Mutex<HashSet<String>>) -> String {
+    use sha2::{Digest, Sha256};
+
+    match *labels().mode.read().expect("metrics label mode poisoned") {
+        LabelMode::Full => value.to_string(),
+        LabelMode::Capped(max) => {
+            let mut seen = seen.lock().expect("metrics labels poisoned");
+            if seen.contains(value) || seen.len() < max {
+                seen.insert(value.to_string());
+                value.to_string()
+            } else {
+                "other".to_string()
+            }
+        }
+        LabelMode::Hashed(buckets) => {
+            let digest = Sha256::digest(value.as_bytes());
+            let hash = u16::from_be_bytes([digest[0], digest[1]]);
+            format!("bucket-{}", hash % buckets.max(1))
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn host_label(host: &str) -> String {
+    label_value(host, &labels().seen_hosts)
+}
+
+#[cfg(feature = "metrics")]
+fn task_label(task: &str) -> String {
+    label_value(task, &labels().seen_tasks)
+}
+
+/// 记录一次 SSH 连接结果
+#[inline]
+pub(crate) fn record_connection(host: &str, success: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let name = if success {
+            "rs_ansible_connections_opened_total"
+        } else {
+            "rs_ansible_connections_failed_total"
+        };
+        ::metrics::counter!(name, "host" => host_label(host)).increment(1);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (host, success);
+}
+
+/// 记录一次远程命令执行
+#[inline]
+pub(crate) fn record_command(host: &str, exit_code: i32) {
+    #[cfg(feature = "metrics")]
+    {
+        let status = if exit_code == 0 { "success" } else { "failure" };
+        ::metrics::counter!("rs_ansible_commands_executed_total", "host" => host_label(host), "status" => status)
+            .increment(1);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (host, exit_code);
+}
+
+/// 记录传输的字节数（direction 为 `"upload"` 或 `"download"`）
+#[inline]
+pub(crate) fn record_bytes_transferred(host: &str, direction: &'static str, bytes: u64) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!("rs_ansible_bytes_transferred_total", "host" => host_label(host), "direction" => direction)
+        .increment(bytes);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (host, direction, bytes);
+}
+
+/// 记录批量操作中单台主机的耗时与结果
+#[inline]
+pub(crate) fn record_host_operation(host: &str, elapsed: Duration, success: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let host = host_label(host);
+        ::metrics::histogram!("rs_ansible_host_operation_duration_seconds", "host" => host.clone())
+            .record(elapsed.as_secs_f64());
+        if !success {
+            ::metrics::counter!("rs_ansible_host_failures_total", "host" => host).increment(1);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (host, elapsed, success);
+}
+
+/// 记录任务耗时及失败的主机
+#[inline]
+pub(crate) fn record_task(task: &str, elapsed: Duration, failed_hosts: &[String]) {
+    #[cfg(feature = "metrics")]
+    {
+        let task = task_label(task);
+        ::metrics::histogram!("rs_ansible_task_duration_seconds", "task" => task.clone())
+            .record(elapsed.as_secs_f64());
+        for host in failed_hosts {
+            ::metrics::counter!("rs_ansible_task_host_failures_total", "task" => task.clone(), "host" => host_label(host))
+                .increment(1);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (task, elapsed, failed_hosts);
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_modes_bound_cardinality() {
+        set_label_mode(LabelMode::Capped(2));
+        assert_eq!(host_label("web1"), "web1");
+        assert_eq!(host_label("web2"), "web2");
+        assert_eq!(host_label("web3"), "other");
+        assert_eq!(host_label("web1"), "web1");
+
+        set_label_mode(LabelMode::Hashed(4));
+        let bucket = host_label("web1");
+        assert!(bucket.starts_with("bucket-"));
+        assert_eq!(host_label("web1"), bucket);
+        let distinct: HashSet<String> = (0..100).map(|i| host_label(&format!("host{}", i))).collect();
+        assert!(distinct.len() <= 4);
+
+        set_label_mode(LabelMode::Full);
+        assert_eq!(task_label("deploy"), "deploy");
+    }
+}
@@ -0,0 +1,585 @@
+use crate::callback::{host_outcomes, HostStatus, TaskOutcome};
+use crate::executor::PlaybookResult;
+use crate::manager::BatchResult;
+use crate::types::SystemInfo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+/// 默认的磁盘使用率告警阈值（百分比）
+pub const DEFAULT_DISK_USAGE_THRESHOLD: f32 = 90.0;
+
+/// 耗时报告中默认列出的最慢主机/任务组合数量
+pub const DEFAULT_SLOWEST_COUNT: usize = 10;
+
+/// JUnit 报告中每个用例附带的 stderr 最大行数（保留末尾）
+pub const JUNIT_STDERR_EXCERPT_LINES: usize = 20;
+
+/// 磁盘使用率超过阈值的挂载点
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiskUsageAlert {
+    pub host: String,
+    pub mount_point: String,
+    pub usage_percent: f32,
+}
+
+/// 机群概览报告（由批量收集的系统信息汇总而来，可直接序列化给看板使用）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FleetReport {
+    pub total_hosts: usize,                           // 参与收集的主机数
+    pub reporting_hosts: usize,                       // 成功返回信息的主机数
+    pub unreachable_hosts: Vec<String>,               // 收集失败的主机
+    pub os_counts: BTreeMap<String, usize>,           // 按操作系统统计
+    pub distribution_counts: BTreeMap<String, usize>, // 按发行版统计
+    pub architecture_counts: BTreeMap<String, usize>, // 按 CPU 架构统计
+    pub total_memory_bytes: u64,                      // 内存总量之和
+    pub free_memory_bytes: u64,                       // 空闲内存之和
+    pub disk_usage_threshold: f32,                    // 磁盘告警阈值（百分比）
+    pub high_disk_usage: Vec<DiskUsageAlert>,         // 超过阈值的挂载点
+}
+
+impl FleetReport {
+    /// 按指定的磁盘使用率阈值汇总系统信息
+    pub fn from_batch(batch: &BatchResult<SystemInfo>, disk_usage_threshold: f32) -> Self {
+        let mut report = FleetReport {
+            total_hosts: batch.results.len(),
+            disk_usage_threshold,
+            ..Default::default()
+        };
+
+        let mut hosts: Vec<&String> = batch.results.keys().collect();
+        hosts.sort();
+
+        for host in hosts {
+            let info = match batch.results[host].value() {
+                Some(info) => info,
+                None => {
+                    report.unreachable_hosts.push(host.clone());
+                    continue;
+                }
+            };
+
+            report.reporting_hosts += 1;
+            *report.os_counts.entry(label(&info.os)).or_default() += 1;
+            *report.distribution_counts.entry(label(&info.distribution)).or_default() += 1;
+            *report.architecture_counts.entry(label(&info.architecture)).or_default() += 1;
+            report.total_memory_bytes += parse_human_size(&info.memory_total).unwrap_or(0);
+            report.free_memory_bytes += parse_human_size(&info.memory_free).unwrap_or(0);
+
+            let mut mounts: Vec<(&String, &String)> = info.disk_usage.iter().collect();
+            mounts.sort();
+            for (mount_point, usage) in mounts {
+                if let Some(usage_percent) = parse_percent(usage)
+                    && usage_percent >= disk_usage_threshold
+                {
+                    report.high_disk_usage.push(DiskUsageAlert {
+                        host: host.clone(),
+                        mount_point: mount_point.clone(),
+                        usage_percent,
+                    });
+                }
+            }
+        }
+
+        report
+    }
+}
+
+impl From<BatchResult<SystemInfo>> for FleetReport {
+    fn from(batch: BatchResult<SystemInfo>) -> Self {
+        Self::from_batch(&batch, DEFAULT_DISK_USAGE_THRESHOLD)
+    }
+}
+
+/// 单个任务的执行时间记录（Playbook 运行期间采集）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTiming {
+    pub task_name: String,
+    pub started_at: DateTime<Utc>,                // 任务开始时间（墙钟）
+    pub finished_at: DateTime<Utc>,               // 任务结束时间（墙钟）
+    pub host_durations: HashMap<String, Duration>, // 每台主机的执行耗时
+}
+
+/// 单个任务的耗时统计（毫秒）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTimingSummary {
+    pub task_name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub wall_clock_ms: f64, // 任务从开始到结束的墙钟时间
+    pub hosts: usize,       // 有耗时记录的主机数
+    pub total_ms: f64,      // 所有主机耗时之和
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// 某台主机在某个任务上的耗时
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HostTaskTiming {
+    pub task_name: String,
+    pub host: String,
+    pub duration_ms: f64,
+}
+
+/// Playbook 耗时报告：按任务统计耗时，并列出最慢的主机/任务组合
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimingReport {
+    pub tasks: Vec<TaskTimingSummary>, // 按执行顺序
+    pub slowest: Vec<HostTaskTiming>,  // 按耗时降序
+}
+
+impl TimingReport {
+    pub fn from_timings(timings: &[TaskTiming], slowest: usize) -> Self {
+        let mut report = TimingReport::default();
+
+        for timing in timings {
+            let mut durations: Vec<f64> = timing.host_durations.values().map(duration_ms).collect();
+            durations.sort_by(f64::total_cmp);
+            let total_ms: f64 = durations.iter().sum();
+
+            report.tasks.push(TaskTimingSummary {
+                task_name: timing.task_name.clone(),
+                started_at: timing.started_at,
+                finished_at: timing.finished_at,
+                wall_clock_ms: (timing.finished_at - timing.started_at).num_microseconds().unwrap_or(0) as f64 / 1000.0,
+                hosts: durations.len(),
+                total_ms,
+                mean_ms: if durations.is_empty() { 0.0 } else { total_ms / durations.len() as f64 },
+                p95_ms: percentile(&durations, 0.95),
+            });
+
+            report
+                .slowest
+                .extend(timing.host_durations.iter().map(|(host, duration)| HostTaskTiming {
+                    task_name: timing.task_name.clone(),
+                    host: host.clone(),
+                    duration_ms: duration_ms(duration),
+                }));
+        }
+
+        report.slowest.sort_by(|a, b| {
+            b.duration_ms
+                .total_cmp(&a.duration_ms)
+                .then_with(|| a.host.cmp(&b.host))
+        });
+        report.slowest.truncate(slowest);
+        report
+    }
+}
+
+impl fmt::Display for TimingReport {
+    /// 按任务总耗时降序输出
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tasks: Vec<&TaskTimingSummary> = self.tasks.iter().collect();
+        tasks.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+
+        writeln!(f, "Task timing (sorted by total host time):")?;
+        for task in tasks {
+            writeln!(
+                f,
+                "  {:<40} total {:>10.3}s  mean {:>8.3}s  p95 {:>8.3}s  wall {:>8.3}s  ({} hosts, started {})",
+                task.task_name,
+                task.total_ms / 1000.0,
+                task.mean_ms / 1000.0,
+                task.p95_ms / 1000.0,
+                task.wall_clock_ms / 1000.0,
+                task.hosts,
+                task.started_at.format("%H:%M:%S%.3f")
+            )?;
+        }
+
+        if !self.slowest.is_empty() {
+            writeln!(f, "Slowest hosts:")?;
+            for entry in &self.slowest {
+                writeln!(f, "  {:<20} {:<40} {:>8.3}s", entry.host, entry.task_name, entry.duration_ms / 1000.0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// JUnit 报告中测试套件的划分方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JunitGrouping {
+    #[default]
+    ByTask, // 每个任务一个 testsuite，主机为 testcase
+    ByHost, // 每台主机一个 testsuite，任务为 testcase
+}
+
+/// 一个 (任务, 主机) 组合对应的测试用例
+struct JunitCase<'a> {
+    task: &'a str,
+    host: String,
+    outcome: TaskOutcome,
+}
+
+/// 将 Playbook 结果转换为 JUnit XML
+///
+/// 每个 (任务, 主机) 组合是一个 testcase：失败携带错误信息，stderr 末尾片段写入 `<system-err>`，跳过的主机为 `<skipped/>`，
+/// `time` 为该主机上的执行耗时（秒）。testsuite 按执行顺序（任务）或主机名排序，输出是确定的。
+pub(crate) fn junit_xml(result: &PlaybookResult, grouping: JunitGrouping) -> String {
+    let mut cases = Vec::new();
+    for (task, task_result) in &result.task_results {
+        let mut outcomes = host_outcomes(task_result);
+        outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+        cases.extend(outcomes.into_iter().map(|(host, outcome)| JunitCase { task, host, outcome }));
+    }
+
+    // 按分组键聚合，保持首次出现的顺序
+    let mut suites: Vec<(String, Vec<&JunitCase>)> = Vec::new();
+    for case in &cases {
+        let key = match grouping {
+            JunitGrouping::ByTask => case.task,
+            JunitGrouping::ByHost => case.host.as_str(),
+        };
+        match suites.iter_mut().find(|(name, _)| name == key) {
+            Some((_, members)) => members.push(case),
+            None => suites.push((key.to_string(), vec![case])),
+        }
+    }
+    if grouping == JunitGrouping::ByHost {
+        suites.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    let count = |cases: &[&JunitCase], status: HostStatus| cases.iter().filter(|c| c.outcome.status == status).count();
+    // 不可达的主机同样计为失败
+    let failures = |cases: &[&JunitCase]| count(cases, HostStatus::Failed) + count(cases, HostStatus::Unreachable);
+    let seconds = |cases: &[&JunitCase]| -> f64 {
+        cases.iter().filter_map(|c| c.outcome.duration).map(|d| d.as_secs_f64()).fold(0.0, |a, b| a + b)
+    };
+    let all: Vec<&JunitCase> = cases.iter().collect();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        xml_escape(&result.playbook_name),
+        all.len(),
+        failures(&all),
+        count(&all, HostStatus::Skipped),
+        seconds(&all)
+    ));
+
+    for (name, members) in &suites {
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(name),
+            members.len(),
+            failures(members),
+            count(members, HostStatus::Skipped),
+            seconds(members)
+        ));
+
+        for case in members {
+            let (case_name, class_name) = match grouping {
+                JunitGrouping::ByTask => (case.host.as_str(), format!("{}.{}", result.playbook_name, case.task)),
+                JunitGrouping::ByHost => (case.task, format!("{}.{}", result.playbook_name, case.host)),
+            };
+            let open = format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                xml_escape(case_name),
+                xml_escape(&class_name),
+                case.outcome.duration.map_or(0.0, |d| d.as_secs_f64())
+            );
+
+            let mut children = String::new();
+            match case.outcome.status {
+                HostStatus::Ok => {}
+                HostStatus::Skipped => children.push_str("      <skipped/>\n"),
+                HostStatus::Failed | HostStatus::Unreachable => {
+                    let message = case.outcome.error.as_deref().unwrap_or_default();
+                    children.push_str(&format!(
+                        "      <failure message=\"{}\">{}</failure>\n",
+                        xml_escape(message.lines().next().unwrap_or_default()),
+                        xml_escape(message)
+                    ));
+                }
+            }
+            if let Some(ref stderr) = case.outcome.stderr {
+                children.push_str(&format!("      <system-err>{}</system-err>\n", xml_escape(&stderr_excerpt(stderr))));
+            }
+
+            if children.is_empty() {
+                xml.push_str(&format!("{}/>\n", open));
+            } else {
+                xml.push_str(&format!("{}>\n{}    </testcase>\n", open, children));
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// 保留 stderr 的最后若干行
+fn stderr_excerpt(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.trim_end().lines().collect();
+    let start = lines.len().saturating_sub(JUNIT_STDERR_EXCERPT_LINES);
+    lines[start..].join("\n")
+}
+
+/// 转义 XML 特殊字符，并替换 XML 1.0 不允许的控制字符（例如命令输出中的 ANSI 转义序列）
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 || c == '\u{FFFE}' || c == '\u{FFFF}' => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn duration_ms(duration: &Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// 最近秩法计算百分位（输入需已升序排列）
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// 空值统一归为 "Unknown"
+fn label(value: &str) -> String {
+    let value = value.trim();
+    if value.is_empty() { "Unknown".to_string() } else { value.to_string() }
+}
+
+/// 解析 `free -h` / `df -h` 风格的容量（如 `15Gi`、`7.6G`、`512M`、`1024`）为字节数
+pub(crate) fn parse_human_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier: f64 = match unit.trim_end_matches(['i', 'B']).to_ascii_uppercase().as_str() {
+        "" => 1.0,
+        "K" => 1024.0,
+        "M" => 1024.0 * 1024.0,
+        "G" => 1024.0 * 1024.0 * 1024.0,
+        "T" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        "P" => 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
+
+/// 解析 `45%` 形式的使用率
+fn parse_percent(value: &str) -> Option<f32> {
+    value.trim().trim_end_matches('%').parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::error::AnsibleError;
+    use crate::manager::SkipReason;
+    use std::collections::HashMap;
+
+    fn info(os: &str, distribution: &str, arch: &str, memory: &str, root_usage: &str) -> SystemInfo {
+        let mut disk_usage = HashMap::new();
+        disk_usage.insert("/".to_string(), root_usage.to_string());
+        disk_usage.insert("/boot".to_string(), "12%".to_string());
+        SystemInfo {
+            hostname: String::new(),
+            os: os.to_string(),
+            distribution: distribution.to_string(),
+            kernel_version: "6.1.0".to_string(),
+            architecture: arch.to_string(),
+            uptime: String::new(),
+            memory_total: memory.to_string(),
+            memory_free: "1Gi".to_string(),
+            disk_usage,
+            disks: Vec::new(),
+            cpu_info: String::new(),
+            network_interfaces: Vec::new(),
+            packages: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_human_size() {
+        assert_eq!(parse_human_size("15Gi"), Some(15 * 1024 * 1024 * 1024));
+        assert_eq!(parse_human_size("512M"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_human_size("1.5K"), Some(1536));
+        assert_eq!(parse_human_size("1024"), Some(1024));
+        assert_eq!(parse_human_size("Unknown"), None);
+    }
+
+    #[test]
+    fn test_fleet_report_from_batch() {
+        let mut batch = BatchResult::new();
+        batch.add_result("web1".to_string(), Ok(info("Linux", "Ubuntu 22.04.3 LTS", "x86_64", "8Gi", "95%")));
+        batch.add_result("web2".to_string(), Ok(info("Linux", "Ubuntu 22.04.3 LTS", "aarch64", "4Gi", "40%")));
+        batch.add_result("db1".to_string(), Ok(info("Linux", "", "x86_64", "16Gi", "90%")));
+        batch.add_result(
+            "old1".to_string(),
+            Err(AnsibleError::SshConnectionError("timeout".to_string())),
+        );
+
+        let report = FleetReport::from(batch);
+        assert_eq!(report.total_hosts, 4);
+        assert_eq!(report.reporting_hosts, 3);
+        assert_eq!(report.unreachable_hosts, vec!["old1".to_string()]);
+        assert_eq!(report.os_counts["Linux"], 3);
+        assert_eq!(report.distribution_counts["Ubuntu 22.04.3 LTS"], 2);
+        assert_eq!(report.distribution_counts["Unknown"], 1);
+        assert_eq!(report.architecture_counts["x86_64"], 2);
+        assert_eq!(report.total_memory_bytes, 28 * 1024 * 1024 * 1024);
+        assert_eq!(report.free_memory_bytes, 3 * 1024 * 1024 * 1024);
+
+        let alerts: Vec<(&str, f32)> = report
+            .high_disk_usage
+            .iter()
+            .map(|a| (a.host.as_str(), a.usage_percent))
+            .collect();
+        assert_eq!(alerts, vec![("db1", 90.0), ("web1", 95.0)]);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["reporting_hosts"], 3);
+    }
+
+    fn timing(name: &str, start_secs: i64, wall_secs: i64, hosts: &[(&str, u64)]) -> TaskTiming {
+        let started_at = Utc.timestamp_opt(1_700_000_000 + start_secs, 0).unwrap();
+        TaskTiming {
+            task_name: name.to_string(),
+            started_at,
+            finished_at: started_at + chrono::Duration::seconds(wall_secs),
+            host_durations: hosts
+                .iter()
+                .map(|(host, ms)| (host.to_string(), Duration::from_millis(*ms)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_timing_report_statistics_and_ordering() {
+        let mut install_hosts: Vec<(String, u64)> = (1..=20).map(|i| (format!("web{}", i), i * 100)).collect();
+        install_hosts.push(("db1".to_string(), 9000));
+        let install_refs: Vec<(&str, u64)> = install_hosts.iter().map(|(h, ms)| (h.as_str(), *ms)).collect();
+
+        let timings = vec![
+            timing("ping", 0, 1, &[("web1", 50), ("db1", 150)]),
+            timing("install packages", 1, 10, &install_refs),
+            timing("skipped", 11, 0, &[]),
+        ];
+        let report = TimingReport::from_timings(&timings, 3);
+
+        assert_eq!(report.tasks.len(), 3);
+        let ping = &report.tasks[0];
+        assert_eq!(ping.hosts, 2);
+        assert_eq!(ping.total_ms, 200.0);
+        assert_eq!(ping.mean_ms, 100.0);
+        assert_eq!(ping.p95_ms, 150.0);
+        assert_eq!(ping.wall_clock_ms, 1000.0);
+
+        let install = &report.tasks[1];
+        assert_eq!(install.hosts, 21);
+        assert_eq!(install.total_ms, 30000.0);
+        // 21 个样本的 p95 为第 20 小的值
+        assert_eq!(install.p95_ms, 2000.0);
+
+        assert_eq!(report.tasks[2].hosts, 0);
+        assert_eq!(report.tasks[2].p95_ms, 0.0);
+
+        let slowest: Vec<(&str, f64)> = report.slowest.iter().map(|s| (s.host.as_str(), s.duration_ms)).collect();
+        assert_eq!(slowest, vec![("db1", 9000.0), ("web20", 2000.0), ("web19", 1900.0)]);
+
+        // Display 按任务总耗时排序
+        let rendered = report.to_string();
+        let install_pos = rendered.find("install packages").unwrap();
+        let ping_pos = rendered.find("ping").unwrap();
+        assert!(install_pos < ping_pos);
+        assert!(rendered.contains("Slowest hosts:"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["tasks"][0]["started_at"], "2023-11-14T22:13:20Z");
+    }
+
+    fn junit_fixture() -> PlaybookResult {
+        use crate::executor::TaskResult;
+        use crate::types::CommandResult;
+
+        let noisy_stderr: String = (1..=25).map(|i| format!("\x1b[33mwarning {}\x1b[0m: <tag> & \"quoted\"\n", i)).collect();
+        let mut command = BatchResult::new();
+        command.add_result(
+            "web1".to_string(),
+            Ok(CommandResult { exit_code: 0, stdout: "ok".to_string(), stderr: String::new(), raw_stdout: None }),
+        );
+        command.add_result(
+            "web2".to_string(),
+            Ok(CommandResult { exit_code: 0, stdout: String::new(), stderr: noisy_stderr, raw_stdout: None }),
+        );
+        command.add_result(
+            "db1".to_string(),
+            Err(AnsibleError::CommandError("exit 1: permission denied for <root> & 'admin'\nsecond line".to_string())),
+        );
+        command.add_skipped("cache1".to_string(), SkipReason::Condition);
+        command.record_duration("web1", Duration::from_millis(120));
+        command.record_duration("web2", Duration::from_millis(80));
+        command.record_duration("db1", Duration::from_millis(30));
+
+        let mut ping = BatchResult::new();
+        ping.add_result("web1".to_string(), Ok(true));
+        ping.add_skipped("db1".to_string(), SkipReason::PreviousFailure);
+        ping.record_duration("web1", Duration::from_millis(10));
+
+        PlaybookResult {
+            playbook_name: "site & db".to_string(),
+            task_results: vec![
+                ("check disk".to_string(), TaskResult::Command(command)),
+                ("ping".to_string(), TaskResult::Ping(ping)),
+            ],
+            overall_success: false,
+            failed_hosts: ["db1".to_string()].into(),
+            skipped_hosts: Default::default(),
+            unreachable_hosts: Default::default(),
+            task_timings: Vec::new(),
+            limit: None,
+            skips: Default::default(),
+            resume_skipped_tasks: Vec::new(),
+            step_skipped_tasks: Vec::new(),
+            aborted_at: None,
+            diffs: Vec::new(),
+        }
+    }
+
+    /// 与 tests/golden 下的文件比对；设置 UPDATE_GOLDEN=1 时重新生成
+    fn assert_golden(name: &str, actual: &str) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, actual).unwrap();
+        }
+        let expected = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(actual, expected, "golden file {} differs", name);
+    }
+
+    #[test]
+    fn test_junit_xml_golden() {
+        let result = junit_fixture();
+        assert_golden("junit_by_task.xml", &result.to_junit_xml());
+        assert_golden("junit_by_host.xml", &result.to_junit_xml_grouped(JunitGrouping::ByHost));
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+        assert_eq!(xml_escape("\x1b[0m\tok\n"), "\u{FFFD}[0m\tok\n");
+        assert_eq!(stderr_excerpt(&"x\n".repeat(30)).lines().count(), JUNIT_STDERR_EXCERPT_LINES);
+    }
+}
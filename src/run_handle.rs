@@ -0,0 +1,121 @@
+use crate::callback::HostStatus;
+use crate::concurrency::CancellationToken;
+use crate::error::AnsibleError;
+use crate::executor::PlaybookResult;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use tokio::task::JoinHandle;
+
+/// 后台运行的整体状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// 单台主机在后台运行中的状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum HostRunStatus {
+    /// 尚未开始执行任何任务
+    Idle,
+    /// 正在执行的任务
+    Running { task: String },
+    /// 最近完成的任务及其结果
+    Finished { task: String, status: HostStatus },
+}
+
+/// 后台运行的状态快照
+#[derive(Debug, Clone, Serialize)]
+pub struct RunStatus {
+    pub playbook: String,
+    pub state: RunState,
+    pub current_task: Option<String>,          // 正在执行的任务
+    pub tasks_completed: usize,
+    pub tasks_total: usize,
+    pub hosts: BTreeMap<String, HostRunStatus>, // 主机名 -> 当前状态
+}
+
+impl RunStatus {
+    pub(crate) fn new<'a>(playbook: &str, tasks_total: usize, hosts: impl IntoIterator<Item = &'a String>) -> Self {
+        Self {
+            playbook: playbook.to_string(),
+            state: RunState::Running,
+            current_task: None,
+            tasks_completed: 0,
+            tasks_total,
+            hosts: hosts.into_iter().map(|h| (h.clone(), HostRunStatus::Idle)).collect(),
+        }
+    }
+}
+
+/// 运行状态的共享写入端，由执行器在任务边界更新
+pub(crate) type SharedRunStatus = Arc<RwLock<RunStatus>>;
+
+/// 在锁内修改状态；锁中毒时继续使用内部数据（状态仅用于展示）
+pub(crate) fn update_status(status: &SharedRunStatus, update: impl FnOnce(&mut RunStatus)) {
+    let mut guard = status.write().unwrap_or_else(|e| e.into_inner());
+    update(&mut guard);
+}
+
+/// 后台运行的 playbook 句柄（由 [`AnsibleManager::spawn_playbook`](crate::AnsibleManager::spawn_playbook) 返回）
+///
+/// # 状态与锁
+///
+/// 运行状态保存在 `Arc<RwLock<RunStatus>>` 中，执行器是唯一的写入方：
+/// 每个任务开始时把目标主机标记为 `Running`，任务结束时按结果标记为 `Finished`。
+/// 写锁只在同步的更新代码中短暂持有，从不跨越 `.await`，因此读取方不会被
+/// 正在执行的 SSH 操作阻塞。[`status`](Self::status) 在读锁内克隆一份快照后立即释放，
+/// 快照反映的是某个任务边界上的一致状态，而不是主机上命令的实时进度。
+///
+/// # 取消
+///
+/// [`cancel`](Self::cancel) 只是设置取消令牌：尚未开始的主机与后续任务不再执行，
+/// `wait` 尽快返回 `AnsibleError::Cancelled`。已经在执行的 SSH 调用是阻塞调用，
+/// 会在后台继续运行直到完成或超时，其结果被丢弃。
+pub struct RunHandle {
+    status: SharedRunStatus,
+    cancellation: CancellationToken,
+    join: JoinHandle<Result<PlaybookResult, AnsibleError>>,
+}
+
+impl RunHandle {
+    pub(crate) fn new(
+        status: SharedRunStatus,
+        cancellation: CancellationToken,
+        join: JoinHandle<Result<PlaybookResult, AnsibleError>>,
+    ) -> Self {
+        Self { status, cancellation, join }
+    }
+
+    /// 当前状态快照
+    pub fn status(&self) -> RunStatus {
+        self.status.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 请求取消运行（可重复调用）
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// 是否已请求取消
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// 后台任务是否已结束
+    pub fn is_finished(&self) -> bool {
+        self.join.is_finished()
+    }
+
+    /// 等待运行结束并返回结果
+    pub async fn wait(self) -> Result<PlaybookResult, AnsibleError> {
+        self.join.await.unwrap_or_else(|e| {
+            Err(AnsibleError::CommandExecutionError(format!("Playbook run task failed: {}", e)))
+        })
+    }
+}
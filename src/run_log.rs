@@ -0,0 +1,276 @@
+use crate::callback::{ExecutionCallback, TaskOutcome};
+use crate::error::AnsibleError;
+use crate::executor::{Playbook, PlaybookResult, Task};
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// 以 JSON Lines 格式记录一次执行过程的日志（每个事件一行，写完立即 flush）
+///
+/// 每行都是一个 JSON 对象，公共字段：
+///
+/// | 字段 | 说明 |
+/// |------|------|
+/// | `event` | 事件类型，见下表 |
+/// | `timestamp` | RFC 3339 格式的 UTC 时间 |
+/// | `playbook` | 当前 playbook 名称 |
+/// | `task` | 当前任务名称，与任务无关的事件为 `null` |
+/// | `host` | 主机名，与单台主机无关的事件为 `null` |
+///
+/// 事件类型及附加字段：
+///
+/// | `event` | 附加字段 |
+/// |---------|----------|
+/// | `playbook_start` | `tasks`：任务数 |
+/// | `task_start` | 无 |
+/// | `host_result` | `status`（ok/failed/unreachable/skipped）、`duration_ms`、`exit_code`、`changed`、`error`、`skip_reason` |
+/// | `host_retry` | `attempt`：即将进行的第几次连接尝试、`error`：上一次失败原因 |
+/// | `task_error` | `error` |
+/// | `playbook_end` | `overall_success`、`tasks_run`、`failed_hosts`、`skipped_hosts`、`unreachable_hosts`、`skips`（主机 -> 跳过的任务与原因）、`duration_ms` |
+///
+/// 不适用的附加字段写为 `null`，因此中途中断的执行也能保留已完成部分的完整记录。
+pub struct RunLogger {
+    writer: Mutex<Box<dyn Write + Send>>,
+    playbook: Mutex<Option<String>>,
+}
+
+impl RunLogger {
+    /// 写入调用方提供的 writer
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            playbook: Mutex::new(None),
+        }
+    }
+
+    /// 以追加方式写入文件（不存在时创建）
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, AnsibleError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())
+            .map_err(|e| {
+                AnsibleError::FileOperationError(format!(
+                    "Failed to open run log {}: {}",
+                    path.as_ref().display(),
+                    e
+                ))
+            })?;
+        Ok(Self::new(Box::new(file)))
+    }
+
+    fn write_event(
+        &self,
+        event: &str,
+        task: Option<&str>,
+        host: Option<&str>,
+        fields: Value,
+    ) -> Result<(), AnsibleError> {
+        let playbook = self.playbook.lock().expect("run log poisoned").clone();
+        let mut record = Map::new();
+        record.insert("event".to_string(), json!(event));
+        record.insert("timestamp".to_string(), json!(Utc::now().to_rfc3339()));
+        record.insert("playbook".to_string(), json!(playbook));
+        record.insert("task".to_string(), json!(task));
+        record.insert("host".to_string(), json!(host));
+        if let Value::Object(fields) = fields {
+            record.extend(fields);
+        }
+
+        let line = Value::Object(record).to_string();
+        let mut writer = self.writer.lock().expect("run log poisoned");
+        writeln!(writer, "{}", line)
+            .and_then(|_| writer.flush())
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to write run log: {}", e)))
+    }
+}
+
+impl ExecutionCallback for RunLogger {
+    fn on_playbook_start(&self, playbook: &Playbook) -> Result<(), AnsibleError> {
+        *self.playbook.lock().expect("run log poisoned") = Some(playbook.name.clone());
+        self.write_event("playbook_start", None, None, json!({ "tasks": playbook.tasks.len() }))
+    }
+
+    fn on_task_start(&self, task: &Task) -> Result<(), AnsibleError> {
+        self.write_event("task_start", Some(&task.name), None, json!({}))
+    }
+
+    fn on_host_result(&self, host: &str, task: &Task, outcome: &TaskOutcome) -> Result<(), AnsibleError> {
+        self.write_event(
+            "host_result",
+            Some(&task.name),
+            Some(host),
+            json!({
+                "status": outcome.status,
+                "duration_ms": outcome.duration.map(|d| d.as_secs_f64() * 1000.0),
+                "exit_code": outcome.exit_code,
+                "changed": outcome.changed,
+                "error": outcome.error,
+                "skip_reason": outcome.skip_reason,
+            }),
+        )
+    }
+
+    fn on_host_retry(&self, host: &str, task: &Task, attempt: u32, error: &str) -> Result<(), AnsibleError> {
+        self.write_event(
+            "host_retry",
+            Some(&task.name),
+            Some(host),
+            json!({ "attempt": attempt, "error": error }),
+        )
+    }
+
+    fn on_task_error(&self, task: &Task, error: &AnsibleError) -> Result<(), AnsibleError> {
+        self.write_event("task_error", Some(&task.name), None, json!({ "error": error.to_string() }))
+    }
+
+    fn on_playbook_end(&self, result: &PlaybookResult) -> Result<(), AnsibleError> {
+        let mut failed_hosts: Vec<&String> = result.failed_hosts.iter().collect();
+        let mut skipped_hosts: Vec<&String> = result.skipped_hosts.iter().collect();
+        let mut unreachable_hosts: Vec<&String> = result.unreachable_hosts.iter().collect();
+        failed_hosts.sort();
+        skipped_hosts.sort();
+        unreachable_hosts.sort();
+        let skips: BTreeMap<&String, Vec<Value>> = result
+            .skips()
+            .iter()
+            .map(|(host, skips)| {
+                (host, skips.iter().map(|(task, reason)| json!({ "task": task, "reason": reason })).collect())
+            })
+            .collect();
+        let duration_ms = match (result.task_timings.first(), result.task_timings.last()) {
+            (Some(first), Some(last)) => Some((last.finished_at - first.started_at).num_milliseconds()),
+            _ => None,
+        };
+
+        self.write_event(
+            "playbook_end",
+            None,
+            None,
+            json!({
+                "overall_success": result.overall_success,
+                "tasks_run": result.task_results.len(),
+                "failed_hosts": failed_hosts,
+                "skipped_hosts": skipped_hosts,
+                "unreachable_hosts": unreachable_hosts,
+                "skips": skips,
+                "duration_ms": duration_ms,
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::callback::{dispatch, host_outcomes};
+    use crate::executor::TaskResult;
+    use crate::manager::BatchResult;
+    use crate::types::CommandResult;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_log_replay() {
+        let path = std::env::temp_dir().join(format!("rs_ansible_run_{}.jsonl", rand::random::<u32>()));
+        let logger: Arc<dyn ExecutionCallback> = Arc::new(RunLogger::create(&path).unwrap());
+        let callbacks = vec![logger];
+
+        let playbook = Playbook::new("deploy")
+            .add_task(Task::command("uptime", "uptime"))
+            .add_task(Task::command("restart", "systemctl restart app"));
+
+        // 模拟一次执行：web1 成功，web2 重试后失败
+        let mut batch = BatchResult::new();
+        batch.add_result(
+            "web1".to_string(),
+            Ok(CommandResult {
+                stdout: "up".to_string(),
+                stderr: String::new(),
+                exit_code: 0,
+                raw_stdout: None,
+            }),
+        );
+        batch.add_result("web2".to_string(), Err(AnsibleError::CommandError("exit 1".to_string())));
+        batch.record_duration("web1", Duration::from_millis(120));
+        let task_result = TaskResult::Command(batch);
+
+        dispatch(&callbacks, "playbook_start", |cb| cb.on_playbook_start(&playbook));
+        let task = &playbook.tasks[0];
+        dispatch(&callbacks, "task_start", |cb| cb.on_task_start(task));
+
+        // 每个事件写完即落盘
+        let partial = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(partial.lines().count(), 2);
+
+        let error = AnsibleError::SshConnectionError("reset".to_string());
+        dispatch(&callbacks, "host_retry", |cb| cb.on_host_retry("web2", task, 2, "connection reset"));
+        for (host, outcome) in host_outcomes(&task_result) {
+            dispatch(&callbacks, "host_result", |cb| cb.on_host_result(&host, task, &outcome));
+        }
+        dispatch(&callbacks, "task_error", |cb| cb.on_task_error(&playbook.tasks[1], &error));
+        let result = PlaybookResult {
+            playbook_name: playbook.name.clone(),
+            task_results: vec![(task.name.clone(), task_result)],
+            overall_success: false,
+            failed_hosts: HashSet::from(["web2".to_string()]),
+            skipped_hosts: HashSet::new(),
+            unreachable_hosts: HashSet::new(),
+            task_timings: Vec::new(),
+            limit: None,
+            skips: Default::default(),
+            resume_skipped_tasks: Vec::new(),
+            step_skipped_tasks: Vec::new(),
+            aborted_at: None,
+            diffs: Vec::new(),
+        };
+        dispatch(&callbacks, "playbook_end", |cb| cb.on_playbook_end(&result));
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("every line is valid JSON"))
+            .collect();
+        for line in &lines {
+            for key in ["event", "timestamp", "playbook", "task", "host"] {
+                assert!(line.get(key).is_some(), "missing {} in {}", key, line);
+            }
+            assert_eq!(line["playbook"], "deploy");
+            assert!(chrono::DateTime::parse_from_rfc3339(line["timestamp"].as_str().unwrap()).is_ok());
+        }
+
+        let events: Vec<&str> = lines.iter().map(|l| l["event"].as_str().unwrap()).collect();
+        assert_eq!(
+            events,
+            vec![
+                "playbook_start",
+                "task_start",
+                "host_retry",
+                "host_result",
+                "host_result",
+                "task_error",
+                "playbook_end",
+            ]
+        );
+
+        let web1 = &lines[3];
+        assert_eq!(web1["host"], "web1");
+        assert_eq!(web1["task"], "uptime");
+        assert_eq!(web1["status"], "ok");
+        assert_eq!(web1["exit_code"], 0);
+        assert_eq!(web1["duration_ms"], 120.0);
+        let web2 = &lines[4];
+        assert_eq!(web2["status"], "failed");
+        assert!(web2["error"].as_str().unwrap().contains("exit 1"));
+        assert_eq!(lines[2]["attempt"], 2);
+        assert_eq!(lines[2]["error"], "connection reset");
+        assert_eq!(lines[6]["overall_success"], false);
+        assert_eq!(lines[6]["failed_hosts"], json!(["web2"]));
+    }
+}
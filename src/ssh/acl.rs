@@ -0,0 +1,201 @@
+use crate::error::AnsibleError;
+use crate::types::{AclEntry, AclEntryType, FileAcl};
+use crate::utils::shell_quote;
+use super::SshClient;
+use tracing::info;
+
+const DEFAULT_PREFIX: &str = "default:";
+
+impl SshClient {
+    /// 读取文件或目录的 POSIX ACL（`getfacl --omit-header`）
+    ///
+    /// 目录的默认 ACL 放在 `default_entries` 中；没有扩展 ACL 的文件只包含 user/group/other 三条基本条目。
+    pub fn get_file_acl(&self, path: &str) -> Result<FileAcl, AnsibleError> {
+        let result = self.execute_command(&format!("getfacl --omit-header {}", shell_quote(path)))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to read ACL of {}: {}",
+                path,
+                result.stderr.trim()
+            )));
+        }
+        Ok(parse_getfacl(path, &result.stdout))
+    }
+
+    /// 添加或修改一条 ACL 条目（`setfacl -m`），返回 ACL 是否发生变化
+    ///
+    /// `recursive` 为 true 时同时应用到目录下的所有文件与子目录。是否变化通过比较执行前后的
+    /// `getfacl` 输出判断，因此权限已符合时返回 false。
+    pub fn set_file_acl(&self, path: &str, acl: &AclEntry, recursive: bool) -> Result<bool, AnsibleError> {
+        validate_acl_entry(acl)?;
+        let result = self.execute_command(&setfacl_command(path, acl, recursive))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to set ACL '{}' on {}: {}",
+                acl_spec(acl),
+                path,
+                result.stderr.trim()
+            )));
+        }
+        let changed = result.stdout.contains("changed");
+        if changed {
+            info!("Set ACL '{}' on {}:{}", acl_spec(acl), self.config.hostname, path);
+        }
+        Ok(changed)
+    }
+}
+
+/// 解析 `getfacl --omit-header` 的输出，忽略注释（包括 `#effective:`）与无法识别的行
+fn parse_getfacl(path: &str, output: &str) -> FileAcl {
+    let mut acl = FileAcl {
+        path: path.to_string(),
+        entries: Vec::new(),
+        default_entries: Vec::new(),
+    };
+    for line in output.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let (entries, spec) = match line.strip_prefix(DEFAULT_PREFIX) {
+            Some(spec) => (&mut acl.default_entries, spec),
+            None => (&mut acl.entries, line),
+        };
+        if let Some(entry) = parse_acl_entry(spec) {
+            entries.push(entry);
+        }
+    }
+    acl
+}
+
+/// 解析 `类型:限定符:权限`，限定符为空时为 None
+fn parse_acl_entry(spec: &str) -> Option<AclEntry> {
+    let mut parts = spec.splitn(3, ':');
+    let type_ = match parts.next()? {
+        "user" => AclEntryType::User,
+        "group" => AclEntryType::Group,
+        "other" => AclEntryType::Other,
+        "mask" => AclEntryType::Mask,
+        _ => return None,
+    };
+    let qualifier = parts.next()?;
+    let permissions = parts.next()?.trim();
+    Some(AclEntry {
+        type_,
+        qualifier: (!qualifier.is_empty()).then(|| qualifier.to_string()),
+        permissions: permissions.to_string(),
+    })
+}
+
+/// setfacl 使用的条目表示，例如 `user:alice:r-x`、`other::r--`
+fn acl_spec(acl: &AclEntry) -> String {
+    format!(
+        "{}:{}:{}",
+        acl.type_.keyword(),
+        acl.qualifier.as_deref().unwrap_or_default(),
+        acl.permissions
+    )
+}
+
+/// 限定符不能包含分隔符或空白，other/mask 条目不能有限定符；权限只允许 `rwxX-`
+fn validate_acl_entry(acl: &AclEntry) -> Result<(), AnsibleError> {
+    if let Some(ref qualifier) = acl.qualifier {
+        if matches!(acl.type_, AclEntryType::Other | AclEntryType::Mask) {
+            return Err(AnsibleError::ValidationError(format!(
+                "ACL entry of type '{}' cannot have a qualifier",
+                acl.type_.keyword()
+            )));
+        }
+        if qualifier.is_empty() || qualifier.chars().any(|c| matches!(c, ':' | ',') || c.is_whitespace()) {
+            return Err(AnsibleError::ValidationError(format!("Invalid ACL qualifier: '{}'", qualifier)));
+        }
+    }
+    if acl.permissions.is_empty() || !acl.permissions.chars().all(|c| "rwxX-".contains(c)) {
+        return Err(AnsibleError::ValidationError(format!(
+            "Invalid ACL permissions: '{}'",
+            acl.permissions
+        )));
+    }
+    Ok(())
+}
+
+/// 修改前后各取一次 ACL 的摘要，不同时输出 `changed`
+fn setfacl_command(path: &str, acl: &AclEntry, recursive: bool) -> String {
+    let flags = if recursive { " -R" } else { "" };
+    let path = shell_quote(path);
+    let snapshot = format!("getfacl -p{} {} 2>/dev/null | sha256sum", flags, path);
+    format!(
+        "before=$({snapshot}); setfacl{flags} -m {} {path} || exit $?; \
+         [ \"$before\" = \"$({snapshot})\" ] || echo changed",
+        shell_quote(&acl_spec(acl))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(type_: AclEntryType, qualifier: Option<&str>, permissions: &str) -> AclEntry {
+        AclEntry {
+            type_,
+            qualifier: qualifier.map(str::to_string),
+            permissions: permissions.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_getfacl() {
+        let output = "\
+user::rwx
+user:alice:rwx\t\t\t#effective:r-x
+group::r-x
+group:auditors:r--
+mask::r-x
+other::---
+default:user::rwx
+default:group::r-x
+default:other::---
+
+";
+        let acl = parse_getfacl("/srv/secrets", output);
+        assert_eq!(acl.path, "/srv/secrets");
+        assert_eq!(
+            acl.entries,
+            vec![
+                entry(AclEntryType::User, None, "rwx"),
+                entry(AclEntryType::User, Some("alice"), "rwx"),
+                entry(AclEntryType::Group, None, "r-x"),
+                entry(AclEntryType::Group, Some("auditors"), "r--"),
+                entry(AclEntryType::Mask, None, "r-x"),
+                entry(AclEntryType::Other, None, "---"),
+            ]
+        );
+        assert_eq!(
+            acl.default_entries,
+            vec![
+                entry(AclEntryType::User, None, "rwx"),
+                entry(AclEntryType::Group, None, "r-x"),
+                entry(AclEntryType::Other, None, "---"),
+            ]
+        );
+
+        let plain = parse_getfacl("/etc/hosts", "user::rw-\ngroup::r--\nother::r--\n");
+        assert_eq!(plain.entries.len(), 3);
+        assert!(plain.default_entries.is_empty());
+    }
+
+    #[test]
+    fn test_setfacl_command() {
+        let alice = entry(AclEntryType::User, Some("alice"), "r-x");
+        assert_eq!(acl_spec(&alice), "user:alice:r-x");
+        assert_eq!(acl_spec(&entry(AclEntryType::Other, None, "---")), "other::---");
+        assert_eq!(
+            setfacl_command("/srv/data", &alice, true),
+            "before=$(getfacl -p -R '/srv/data' 2>/dev/null | sha256sum); setfacl -R -m 'user:alice:r-x' '/srv/data' || exit $?; \
+             [ \"$before\" = \"$(getfacl -p -R '/srv/data' 2>/dev/null | sha256sum)\" ] || echo changed"
+        );
+
+        assert!(validate_acl_entry(&alice).is_ok());
+        assert!(validate_acl_entry(&entry(AclEntryType::Group, Some("dev"), "rX")).is_ok());
+        assert!(validate_acl_entry(&entry(AclEntryType::Mask, Some("x"), "r--")).is_err());
+        assert!(validate_acl_entry(&entry(AclEntryType::User, Some("a b"), "r--")).is_err());
+        assert!(validate_acl_entry(&entry(AclEntryType::User, Some("alice"), "rw;x")).is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use crate::bandwidth::{self, BandwidthLimiter};
+use crate::credentials::{CredentialProvider, SecretString};
+use crate::diff::{unified_diff, MAX_DIFF_SOURCE_BYTES};
+use crate::error::AnsibleError;
+use crate::metrics;
+use crate::types::{CommandOptions, CommandResult, FileCopyOptions, FileHashInfo, FileTransferResult, HostConfig, HostKeyType, PermissionsOptions};
+use crate::utils::{calculate_file_hash_async, generate_remote_temp_path, retry_with_backoff, shell_quote, FileMode};
+use super::async_transport::{self, AsyncTransport};
+use super::client::{become_command, become_password_command, env_command, sudo_requires_password, validate_key_source, SshClient};
+use super::file_transfer::{backup_command, parent_dir_to_create, transfer_message, upload_limiters, verify_upload};
+use super::hash::{exists_command, hash_command, parse_hash, parse_size, remote_file_exists, size_command};
+use super::host_metrics::MetricsCounters;
+use super::permissions::{attribute_commands, attribute_output, parse_stat_result, stat_command, validate_ownership};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn, Span};
+
+/// 基于 [`AsyncTransport`] 的异步 SSH 客户端（`transport: russh`）
+///
+/// 提供 `SshClient` 中命令执行与文件传输部分的异步版本，行为与同步客户端一致：
+/// 环境变量与 become 的包装方式相同，上传同样经过 hash 幂等检查、原子写入、传输后校验与属性设置。
+/// 所有操作都直接 await，`AnsibleManager` 不会为这类主机占用阻塞线程。
+pub struct AsyncSshClient {
+    transport: Box<dyn AsyncTransport>,
+    config: HostConfig,
+    bandwidth_limiter: Option<Arc<BandwidthLimiter>>, // 多个客户端共享的上传带宽限制
+    pub(super) counters: Arc<MetricsCounters>,       // 连接耗时、通道数等轻量统计
+}
+
+impl AsyncSshClient {
+    /// 创建新的异步 SSH 连接（带重试机制）
+    pub async fn new(config: HostConfig) -> Result<Self, AnsibleError> {
+        Self::new_with_provider(config, None).await
+    }
+
+    /// 创建新的异步 SSH 连接，配置中缺失的密码/口令由凭据提供者补全
+    pub async fn new_with_provider(
+        config: HostConfig,
+        provider: Option<Arc<dyn CredentialProvider>>,
+    ) -> Result<Self, AnsibleError> {
+        Self::connect_with_retry_hook(config, provider, &|_, _| {}).await
+    }
+
+    /// 建立连接，每次失败后即将重试时以（即将进行的尝试序号, 失败原因）调用 `on_retry`
+    pub(crate) async fn connect_with_retry_hook(
+        config: HostConfig,
+        provider: Option<Arc<dyn CredentialProvider>>,
+        on_retry: &(dyn Fn(u32, &AnsibleError) + Sync),
+    ) -> Result<Self, AnsibleError> {
+        validate_key_source(&config)?;
+
+        // 在重试循环之前解析一次凭据，避免重复访问外部存储
+        let secret = SshClient::resolve_secret(&config, provider.as_deref())?;
+
+        let max_retries = 3;
+        let (config, secret) = (&config, secret.as_ref());
+        retry_with_backoff(
+            max_retries,
+            Duration::from_millis(1000),
+            Duration::from_secs(10),
+            0.2,
+            |attempt| async move {
+                // 在当前主机 span 上记录连接尝试次数（span 未声明该字段时忽略）
+                Span::current().record("attempt", attempt);
+                if attempt > 1 {
+                    info!(
+                        "Retrying SSH connection to {}:{} (Attempt {}/{})",
+                        config.hostname, config.port, attempt, max_retries
+                    );
+                }
+
+                let result = Self::connect_once(config, secret).await;
+                metrics::record_connection(&config.hostname, result.is_ok());
+                if let Err(ref e) = result {
+                    warn!(
+                        "SSH connection failed for {}:{}: {}. ",
+                        config.hostname, config.port, e
+                    );
+                    if attempt < max_retries {
+                        on_retry(attempt + 1, e);
+                    }
+                }
+                result
+            },
+        )
+        .await
+    }
+
+    /// 执行单次连接尝试
+    async fn connect_once(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        let started = Instant::now();
+        let transport = async_transport::connect(config, secret).await?;
+        let client = Self::with_transport(config.clone(), transport);
+        client.counters.record_connect_time(started.elapsed());
+        Ok(client)
+    }
+
+    /// 使用已建立的异步传输层创建客户端（例如测试用的 mock）
+    pub fn with_transport(config: HostConfig, transport: Box<dyn AsyncTransport>) -> Self {
+        Self {
+            transport,
+            config,
+            bandwidth_limiter: None,
+            counters: Arc::default(),
+        }
+    }
+
+    /// 关闭连接
+    pub async fn disconnect(self) {
+        self.transport.disconnect().await;
+        info!("Disconnected from {}", self.config.hostname);
+    }
+
+    /// 设置共享的上传带宽限制器（例如管理器级别的总带宽上限）
+    pub fn set_bandwidth_limiter(&mut self, limiter: Option<Arc<BandwidthLimiter>>) {
+        self.bandwidth_limiter = limiter;
+    }
+
+    /// 获取当前主机的配置信息
+    pub fn get_host_config(&self) -> &HostConfig {
+        &self.config
+    }
+
+    /// 读取服务器主机密钥的类型与 SHA256 指纹（小写十六进制），不做任何校验
+    pub fn server_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        self.transport.host_key_fingerprint()
+    }
+
+    /// 测试连接是否正常
+    pub async fn ping(&self) -> Result<bool, AnsibleError> {
+        let result = self.execute_command("echo 'pong'").await?;
+        Ok(result.exit_code == 0 && result.stdout.trim() == "pong")
+    }
+
+    /// 执行远程命令（若配置了 become，则通过 sudo 提权执行）
+    pub async fn execute_command(&self, command: &str) -> Result<CommandResult, AnsibleError> {
+        self.execute_command_full(command, CommandOptions::default()).await
+    }
+
+    /// 按选项执行远程命令，与 [`SshClient::execute_command_full`] 相同
+    pub async fn execute_command_full(
+        &self,
+        command: &str,
+        options: CommandOptions,
+    ) -> Result<CommandResult, AnsibleError> {
+        let command = match options.env {
+            Some(ref env) if !env.is_empty() => env_command(env, command)?,
+            _ => command.to_string(),
+        };
+        let result = self.exec_become(&command, options).await?;
+
+        info!(command, exit_code = result.exit_code, "Command executed");
+        metrics::record_command(&self.config.hostname, result.exit_code);
+
+        Ok(result)
+    }
+
+    /// 按 become 设置包装并执行命令，sudo 凭据未被缓存时的回退与 `SshClient` 相同
+    async fn exec_become(&self, command: &str, mut options: CommandOptions) -> Result<CommandResult, AnsibleError> {
+        let config = &self.config;
+        let password = config.become_password.as_ref().filter(|_| config.r#become);
+        if let Some(password) = password {
+            let mut stdin = format!("{}\n", password).into_bytes();
+            stdin.extend(options.stdin.take().unwrap_or_default());
+            options.stdin = Some(stdin);
+        }
+
+        self.counters.record_command_channel();
+        let result = self.transport.exec(&become_command(config, command), &options).await?;
+        if password.is_none() || result.exit_code == 0 || !sudo_requires_password(&result.stderr) {
+            return Ok(result);
+        }
+
+        debug!("Cached sudo credentials not reused on {}, passing the password to sudo directly", config.hostname);
+        self.counters.record_command_channel();
+        self.transport.exec(&become_password_command(config, command), &options).await
+    }
+
+    /// 复制文件到远程主机（使用默认选项）
+    pub async fn copy_file_to_remote(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+    ) -> Result<FileTransferResult, AnsibleError> {
+        self.copy_file_to_remote_with_options(local_path, remote_path, &FileCopyOptions::default())
+            .await
+    }
+
+    /// 复制文件到远程主机（带选项），步骤与 [`SshClient::copy_file_to_remote_with_options`] 相同
+    ///
+    /// 原子模式下的远程临时文件在失败时删除，除非设置了 `keep_temp_on_failure`。
+    pub async fn copy_file_to_remote_with_options(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+        options: &FileCopyOptions,
+    ) -> Result<FileTransferResult, AnsibleError> {
+        // 在任何远程操作之前校验权限格式
+        let mode = options.mode.as_deref().map(FileMode::parse).transpose()?;
+        let hash_algorithm = "sha256";
+
+        let size = tokio::fs::metadata(local_path)
+            .await
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to get file metadata: {}", e)))?
+            .len();
+        let hash = match options.precomputed_hash {
+            Some(ref hash) => hash.clone(),
+            None => calculate_file_hash_async(local_path, hash_algorithm).await?,
+        };
+        let local_hash_info = FileHashInfo {
+            algorithm: hash_algorithm.to_string(),
+            hash,
+            size,
+        };
+        info!("Local file hash: {} (size: {} bytes)", local_hash_info.hash, local_hash_info.size);
+
+        // 幂等性检查：远程文件一致时只更新属性
+        let remote_size = match self.get_remote_file_hash(remote_path, hash_algorithm).await? {
+            Some(remote) if remote.hash == local_hash_info.hash && remote.size == local_hash_info.size => {
+                info!("Remote file unchanged (hash: {}), skipping transfer", remote.hash);
+                self.apply_file_attributes(remote_path, options).await?;
+                return Ok(FileTransferResult {
+                    success: true,
+                    bytes_transferred: 0,
+                    message: format!("File unchanged (hash: {}), attributes updated", remote.hash),
+                    diff: None,
+                });
+            }
+            remote => remote.map(|remote| remote.size),
+        };
+
+        // 差异必须在覆盖远程文件之前计算
+        let diff = if options.diff {
+            Some(self.upload_diff(local_path, remote_path, local_hash_info.size, remote_size).await?)
+        } else {
+            None
+        };
+
+        if options.create_dirs && let Some(parent) = parent_dir_to_create(remote_path) {
+            let mkdir_result = self.execute_command(&format!("mkdir -p '{}'", parent)).await?;
+            if mkdir_result.exit_code != 0 {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "Failed to create directory {}: {}",
+                    parent, mkdir_result.stderr
+                )));
+            }
+        }
+
+        if options.backup {
+            let backup_result = self.execute_command(&backup_command(remote_path)).await?;
+            if backup_result.exit_code != 0 {
+                info!("Backup command failed (file may not exist): {}", backup_result.stderr);
+            }
+        }
+
+        let initial_mode = mode.as_ref().map_or(0o644, FileMode::initial_mode);
+        let bytes_transferred = if options.atomic {
+            let temp_path = generate_remote_temp_path(remote_path);
+            let placed = self
+                .place_via_temp(local_path, &temp_path, remote_path, &local_hash_info, initial_mode, options)
+                .await;
+            if placed.is_err() {
+                self.discard_temp(&temp_path, options.keep_temp_on_failure).await;
+            }
+            placed?
+        } else {
+            let bytes = self.send_file(local_path, size, remote_path, initial_mode, options).await?;
+            let remote_hash_info = self.get_remote_file_hash(remote_path, hash_algorithm).await?;
+            verify_upload(&local_hash_info, local_path, remote_path, remote_hash_info)?;
+            bytes
+        };
+
+        self.apply_file_attributes(remote_path, options).await?;
+
+        info!("File successfully copied and verified: {} -> {}", local_path, remote_path);
+        Ok(FileTransferResult {
+            success: true,
+            bytes_transferred,
+            message: transfer_message(bytes_transferred, &local_hash_info.hash, options),
+            diff,
+        })
+    }
+
+    /// 上传到临时文件，校验后移动到目标位置
+    async fn place_via_temp(
+        &self,
+        local_path: &str,
+        temp_path: &str,
+        remote_path: &str,
+        local_hash_info: &FileHashInfo,
+        mode: i32,
+        options: &FileCopyOptions,
+    ) -> Result<u64, AnsibleError> {
+        let bytes = self.send_file(local_path, local_hash_info.size, temp_path, mode, options).await?;
+        let remote_hash_info = self.get_remote_file_hash(temp_path, &local_hash_info.algorithm).await?;
+        verify_upload(local_hash_info, local_path, temp_path, remote_hash_info)?;
+
+        let mv_result = self.execute_command(&format!("mv '{}' '{}'", temp_path, remote_path)).await?;
+        if mv_result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to move temp file to destination: {}",
+                mv_result.stderr
+            )));
+        }
+        Ok(bytes)
+    }
+
+    /// 删除远程临时文件（`keep` 时保留用于排查），删除失败只记录警告
+    pub(crate) async fn discard_temp(&self, temp_path: &str, keep: bool) {
+        if keep {
+            warn!("Keeping remote temp file for debugging: {}", temp_path);
+            return;
+        }
+        debug!("Removing remote temp file: {}", temp_path);
+        if let Err(e) = self.execute_command(&format!("rm -f {}", shell_quote(temp_path))).await {
+            warn!("Failed to remove remote temp file {}: {}", temp_path, e);
+        }
+    }
+
+    /// 把本地文件流式上传到远程路径，按单次限速与共享的总带宽限制节流
+    async fn send_file(
+        &self,
+        local_path: &str,
+        size: u64,
+        remote_path: &str,
+        mode: i32,
+        options: &FileCopyOptions,
+    ) -> Result<u64, AnsibleError> {
+        let mut file = tokio::fs::File::open(local_path).await.map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to open local file {}: {}", local_path, e))
+        })?;
+        info!("Transferring file to {}", remote_path);
+
+        let started = Instant::now();
+        let limiters = upload_limiters(options, self.bandwidth_limiter.as_ref());
+        let bytes_transferred = if limiters.is_empty() {
+            self.transport.upload(&mut file, size, remote_path, mode).await?
+        } else {
+            // 节流的读取端通过内存管道交给传输层；上传提前失败时丢弃读取端，写入端随之结束
+            let chunk_size = bandwidth::chunk_size(&limiters);
+            let (mut pipe_writer, mut pipe_reader) = tokio::io::duplex(chunk_size);
+            let produce = async move {
+                let mut buffer = vec![0u8; chunk_size];
+                loop {
+                    let n = file.read(&mut buffer).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    for limiter in &limiters {
+                        limiter.consume_async(n).await;
+                    }
+                    pipe_writer.write_all(&buffer[..n]).await?;
+                }
+                pipe_writer.shutdown().await
+            };
+            let upload = async move {
+                let result = self.transport.upload(&mut pipe_reader, size, remote_path, mode).await;
+                drop(pipe_reader);
+                result
+            };
+            let (produced, uploaded) = tokio::join!(produce, upload);
+            let bytes = uploaded?;
+            produced.map_err(|e| AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e)))?;
+            bytes
+        };
+        self.counters.record_transfer_channel(bytes_transferred);
+        metrics::record_bytes_transferred(&self.config.hostname, "upload", bytes_transferred);
+
+        let elapsed = started.elapsed();
+        info!(
+            "File transferred: {} bytes in {:.2}s ({:.0} B/s)",
+            bytes_transferred,
+            elapsed.as_secs_f64(),
+            bytes_transferred as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+        );
+        Ok(bytes_transferred)
+    }
+
+    /// 远程现有内容到本地文件的统一 diff，规则与同步客户端相同
+    async fn upload_diff(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+        local_size: u64,
+        remote_size: Option<u64>,
+    ) -> Result<String, AnsibleError> {
+        let size = local_size.max(remote_size.unwrap_or(0));
+        if size > MAX_DIFF_SOURCE_BYTES {
+            return Ok(format!("File {} too large to diff ({} bytes)\n", remote_path, size));
+        }
+        let mut before = Vec::new();
+        if remote_size.is_some() && let Err(e) = self.transport.download(remote_path, &mut before).await {
+            warn!("Cannot read {} on {} for diff: {}", remote_path, self.config.hostname, e);
+            return Ok(format!("Diff unavailable for {}: cannot read remote file: {}\n", remote_path, e));
+        }
+        let after = tokio::fs::read(local_path).await.map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to read local file {}: {}", local_path, e))
+        })?;
+        Ok(unified_diff(remote_path, &before, &after))
+    }
+
+    /// 从远程主机复制文件到本地
+    pub async fn copy_file_from_remote(
+        &self,
+        remote_path: &str,
+        local_path: &str,
+    ) -> Result<FileTransferResult, AnsibleError> {
+        let mut local_file = tokio::fs::File::create(local_path).await.map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to create local file {}: {}", local_path, e))
+        })?;
+
+        let bytes_transferred = self.transport.download(remote_path, &mut local_file).await?;
+        // tokio 的文件写入在后台完成，flush 后才能读取完整内容
+        local_file.flush().await?;
+        self.counters.record_transfer_channel(bytes_transferred);
+        metrics::record_bytes_transferred(&self.config.hostname, "download", bytes_transferred);
+
+        info!("File {} copied from remote {} ({} bytes)", remote_path, local_path, bytes_transferred);
+        Ok(FileTransferResult {
+            success: true,
+            bytes_transferred,
+            message: format!("Successfully transferred {} bytes", bytes_transferred),
+            diff: None,
+        })
+    }
+
+    /// 从远程主机复制文件到本地并校验完整性，与 [`SshClient::copy_file_from_remote_verified`] 相同
+    pub async fn copy_file_from_remote_verified(
+        &self,
+        remote_path: &str,
+        local_path: &str,
+        algorithm: &str,
+    ) -> Result<FileTransferResult, AnsibleError> {
+        let remote_hash = self.get_remote_file_hash(remote_path, algorithm).await?.ok_or_else(|| {
+            AnsibleError::FileOperationError(format!("Remote file {} does not exist", remote_path))
+        })?;
+        debug!("Remote {} hash of {}: {}", algorithm, remote_path, remote_hash.hash);
+
+        let verified = async {
+            let transfer = self.copy_file_from_remote(remote_path, local_path).await?;
+            let local_hash = calculate_file_hash_async(local_path, algorithm).await?;
+            if local_hash != remote_hash.hash {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "hash mismatch for {}: remote {} != local {}",
+                    remote_path, remote_hash.hash, local_hash
+                )));
+            }
+            Ok((transfer, local_hash))
+        };
+        let (transfer, local_hash) = match verified.await {
+            Ok(verified) => verified,
+            Err(e) => {
+                if let Err(e) = tokio::fs::remove_file(local_path).await {
+                    warn!("Failed to remove local file {}: {}", local_path, e);
+                }
+                return Err(e);
+            }
+        };
+
+        info!("Downloaded {} verified ({}: {})", remote_path, algorithm, local_hash);
+        Ok(FileTransferResult {
+            message: format!("{} (hash: {})", transfer.message, local_hash),
+            ..transfer
+        })
+    }
+
+    /// 获取远程文件的 hash 值
+    async fn get_remote_file_hash(
+        &self,
+        remote_path: &str,
+        algorithm: &str,
+    ) -> Result<Option<FileHashInfo>, AnsibleError> {
+        if !remote_file_exists(&self.execute_command(&exists_command(remote_path)).await?) {
+            return Ok(None);
+        }
+        let size = parse_size(&self.execute_command(&size_command(remote_path)).await?)?;
+        let hash = parse_hash(&self.execute_command(&hash_command(remote_path, algorithm)?).await?)?;
+        Ok(Some(FileHashInfo {
+            algorithm: algorithm.to_string(),
+            hash,
+            size,
+        }))
+    }
+
+    /// 应用复制选项中的权限与所有者（只在与当前属性不一致时修改）
+    async fn apply_file_attributes(&self, remote_path: &str, options: &FileCopyOptions) -> Result<(), AnsibleError> {
+        if options.mode.is_none() && options.owner.is_none() && options.group.is_none() {
+            return Ok(());
+        }
+        let attributes = PermissionsOptions {
+            path: remote_path.to_string(),
+            mode: options.mode.clone(),
+            owner: options.owner.clone(),
+            group: options.group.clone(),
+            recursive: false,
+        };
+        let mode = attributes.mode.as_deref().map(FileMode::parse).transpose()?;
+        validate_ownership(attributes.owner.as_deref(), attributes.group.as_deref())?;
+
+        let current = parse_stat_result(remote_path, &self.execute_command(&stat_command(remote_path)).await?)?;
+        debug!("Current attributes of {}: {:?}", remote_path, current);
+        for command in attribute_commands(&attributes, mode.as_ref(), &current) {
+            attribute_output(self.execute_command(&command.command).await?, command.what, &command.value)?;
+        }
+        Ok(())
+    }
+}
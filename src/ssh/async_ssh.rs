@@ -0,0 +1,718 @@
+use crate::credentials::SecretString;
+use crate::error::AnsibleError;
+use crate::types::{CommandOptions, CommandResult, HostConfig, HostKeyType};
+use crate::utils::shell_quote;
+use super::async_transport::AsyncTransport;
+use super::transport::hex_fingerprint;
+use async_trait::async_trait;
+use russh::client::{self, Handle};
+use russh::keys::{decode_secret_key, load_secret_key, Algorithm, EcdsaCurve, HashAlg, PrivateKey, PrivateKeyWithHashAlg, PublicKey};
+use russh::{ChannelMsg, ChannelReadHalf, ChannelWriteHalf, Disconnect};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::{info, warn};
+
+/// 上传时每次发送的块大小
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// SSH 扩展数据中 stderr 的类型编号
+const EXTENDED_DATA_STDERR: u32 = 1;
+
+/// 基于 russh 的纯异步 SSH 传输实现（`transport: russh`，需启用 `async-ssh` feature）
+///
+/// 连接、认证与通道读写全部在当前 tokio 运行时中 await，不创建线程或子进程。
+/// 认证方式与 `Ssh2Transport` 相同：私钥文件或内存中的私钥（口令来自 `passphrase` 或凭据提供者），
+/// 否则使用密码；主机密钥同样不做校验，可通过 `host_key_fingerprint` 核对。
+/// 上传与下载通过远程的 `cat` 完成，`options.pty` 会为命令分配 xterm 伪终端。
+pub struct RusshTransport {
+    handle: Handle<HostKeyRecorder>,
+    host_key: Option<PublicKey>,
+}
+
+/// 握手时记录服务器主机密钥
+struct HostKeyRecorder {
+    host_key: Arc<Mutex<Option<PublicKey>>>,
+}
+
+impl client::Handler for HostKeyRecorder {
+    type Error = russh::Error;
+
+    async fn check_server_key(&mut self, server_public_key: &PublicKey) -> Result<bool, Self::Error> {
+        *self.host_key.lock().unwrap_or_else(|e| e.into_inner()) = Some(server_public_key.clone());
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl AsyncTransport for RusshTransport {
+    async fn connect(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        let host_key = Arc::new(Mutex::new(None));
+        let recorder = HostKeyRecorder { host_key: host_key.clone() };
+        let mut handle = client::connect(
+            Arc::new(client::Config::default()),
+            (config.hostname.as_str(), config.port),
+            recorder,
+        )
+        .await
+        .map_err(|e| {
+            AnsibleError::SshConnectionError(format!(
+                "Failed to connect to {}:{}: {}",
+                config.hostname, config.port, e
+            ))
+        })?;
+
+        authenticate(&mut handle, config, secret).await?;
+
+        info!("Successfully connected to {} via russh", config.hostname);
+        let host_key = host_key.lock().unwrap_or_else(|e| e.into_inner()).take();
+        Ok(Self { handle, host_key })
+    }
+
+    /// 超时后关闭通道并返回 `CommandExecutionError`，与 `Ssh2Transport` 相同
+    async fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        let channel = self.handle.channel_open_session().await.map_err(command_error)?;
+        let (mut output, input) = channel.split();
+
+        let run = async {
+            if options.pty {
+                input.request_pty(false, "xterm", 80, 24, 0, 0, &[]).await.map_err(command_error)?;
+            }
+            input.exec(true, command).await.map_err(command_error)?;
+
+            // stdin 与输出并发读写，命令回显大量输入时不会因通道窗口写满而互相等待
+            let stdin = options.stdin.as_deref().unwrap_or_default();
+            let write = async {
+                // 命令可能不读取 stdin 就退出，写入失败可以忽略
+                if !stdin.is_empty() {
+                    let _ = input.data(stdin).await;
+                }
+                let _ = input.eof().await;
+            };
+            let (_, collected) = tokio::join!(write, collect_output(&mut output));
+            collected
+        };
+
+        let (exit_code, stdout, stderr) = match options.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, run).await {
+                Ok(collected) => collected?,
+                Err(_) => {
+                    let _ = input.close().await;
+                    return Err(AnsibleError::CommandExecutionError(format!(
+                        "Command timed out after {:?}: {}",
+                        timeout, command
+                    )));
+                }
+            },
+            None => run.await?,
+        };
+
+        CommandResult::from_bytes(exit_code, stdout, stderr, options.output_encoding)
+    }
+
+    async fn upload(
+        &self,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        _size: u64,
+        remote_path: &str,
+        mode: i32,
+    ) -> Result<u64, AnsibleError> {
+        let path = shell_quote(remote_path);
+        let command = format!("cat > {} && chmod {:o} {}", path, mode & 0o7777, path);
+        let channel = self.handle.channel_open_session().await.map_err(transfer_error)?;
+        let (mut output, input) = channel.split();
+        input.exec(true, command).await.map_err(transfer_error)?;
+
+        let send = async {
+            let sent = send_all(&input, reader).await;
+            if sent.is_err() {
+                // 远程的 cat 仍在等待数据，关闭通道使其结束
+                let _ = input.close().await;
+            }
+            sent
+        };
+        let (sent, collected) = tokio::join!(send, collect_output(&mut output));
+
+        let (exit_code, _, stderr) = collected?;
+        if exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to write {}: {}",
+                remote_path,
+                String::from_utf8_lossy(&stderr).trim()
+            )));
+        }
+        sent
+    }
+
+    async fn download(
+        &self,
+        remote_path: &str,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<u64, AnsibleError> {
+        let channel = self.handle.channel_open_session().await.map_err(transfer_error)?;
+        let (mut output, input) = channel.split();
+        input
+            .exec(true, format!("cat {}", shell_quote(remote_path)))
+            .await
+            .map_err(transfer_error)?;
+        let _ = input.eof().await;
+
+        let received = receive_all(&mut output, writer).await;
+        if received.is_err() {
+            let _ = input.close().await;
+        }
+        let (bytes_received, exit_code, stderr) = received?;
+        if exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to read {}: {}",
+                remote_path,
+                String::from_utf8_lossy(&stderr).trim()
+            )));
+        }
+        Ok(bytes_received)
+    }
+
+    async fn disconnect(&self) {
+        if let Err(e) = self.handle.disconnect(Disconnect::ByApplication, "closed by client", "").await {
+            warn!("Failed to disconnect SSH session cleanly: {}", e);
+        }
+    }
+
+    fn host_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        let key = self
+            .host_key
+            .as_ref()
+            .ok_or_else(|| AnsibleError::SshConnectionError("Server host key is not available".to_string()))?;
+        let fingerprint = key.fingerprint(HashAlg::Sha256);
+        Ok((host_key_type(key.algorithm()), hex_fingerprint(fingerprint.as_bytes())))
+    }
+}
+
+/// 按私钥文件、内存私钥、密码的顺序认证（与 `Ssh2Transport` 相同）
+async fn authenticate(
+    handle: &mut Handle<HostKeyRecorder>,
+    config: &HostConfig,
+    secret: Option<&SecretString>,
+) -> Result<(), AnsibleError> {
+    let passphrase = config
+        .passphrase
+        .as_deref()
+        .or_else(|| secret.map(|s| s.expose_secret()));
+    let key = if let Some(ref private_key_path) = config.private_key_path {
+        Some(load_secret_key(private_key_path, passphrase).map_err(key_error)?)
+    } else if let Some(ref private_key_data) = config.private_key_data {
+        // 私钥只在内存中使用，不落盘
+        Some(decode_secret_key(private_key_data, passphrase).map_err(key_error)?)
+    } else {
+        None
+    };
+
+    let result = if let Some(key) = key {
+        publickey_auth(handle, &config.username, key).await?
+    } else if let Some(password) = config
+        .password
+        .as_deref()
+        .or_else(|| secret.map(|s| s.expose_secret()))
+    {
+        handle
+            .authenticate_password(&config.username, password)
+            .await
+            .map_err(auth_error)?
+            .success()
+    } else {
+        return Err(AnsibleError::AuthenticationError(
+            "No authentication method provided".to_string(),
+        ));
+    };
+
+    if !result {
+        return Err(AnsibleError::AuthenticationError(
+            "Authentication failed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn publickey_auth(
+    handle: &mut Handle<HostKeyRecorder>,
+    username: &str,
+    key: PrivateKey,
+) -> Result<bool, AnsibleError> {
+    // RSA 私钥按服务器支持的签名算法（rsa-sha2-256/512）签名
+    let hash_alg = if matches!(key.algorithm(), Algorithm::Rsa { .. }) {
+        handle.best_supported_rsa_hash().await.map_err(auth_error)?.flatten()
+    } else {
+        None
+    };
+    Ok(handle
+        .authenticate_publickey(username, PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg))
+        .await
+        .map_err(auth_error)?
+        .success())
+}
+
+/// 读取通道直到远程关闭，返回（退出码, stdout, stderr）；没有收到退出码时为 -1
+async fn collect_output(output: &mut ChannelReadHalf) -> Result<(i32, Vec<u8>, Vec<u8>), AnsibleError> {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut exit_code = -1;
+    while let Some(message) = output.wait().await {
+        match message {
+            ChannelMsg::Data { data } => stdout.extend_from_slice(&data),
+            ChannelMsg::ExtendedData { data, ext: EXTENDED_DATA_STDERR } => stderr.extend_from_slice(&data),
+            ChannelMsg::ExitStatus { exit_status } => exit_code = exit_status as i32,
+            ChannelMsg::Failure => {
+                return Err(AnsibleError::CommandExecutionError(
+                    "Remote host refused to execute the command".to_string(),
+                ));
+            }
+            ChannelMsg::Close => break,
+            _ => {}
+        }
+    }
+    Ok((exit_code, stdout, stderr))
+}
+
+/// 把 reader 的内容按块写入通道，写完后发送 EOF
+async fn send_all(
+    input: &ChannelWriteHalf<client::Msg>,
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+) -> Result<u64, AnsibleError> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut bytes_sent = 0u64;
+    loop {
+        let n = reader.read(&mut buffer).await.map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e))
+        })?;
+        if n == 0 {
+            break;
+        }
+        input.data(&buffer[..n]).await.map_err(transfer_error)?;
+        bytes_sent += n as u64;
+    }
+    input.eof().await.map_err(transfer_error)?;
+    Ok(bytes_sent)
+}
+
+/// 把通道的 stdout 写入 writer，返回（写入字节数, 退出码, stderr）
+async fn receive_all(
+    output: &mut ChannelReadHalf,
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+) -> Result<(u64, i32, Vec<u8>), AnsibleError> {
+    let mut bytes_received = 0u64;
+    let mut stderr = Vec::new();
+    let mut exit_code = -1;
+    while let Some(message) = output.wait().await {
+        match message {
+            ChannelMsg::Data { data } => {
+                writer.write_all(&data).await.map_err(|e| {
+                    AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e))
+                })?;
+                bytes_received += data.len() as u64;
+            }
+            ChannelMsg::ExtendedData { data, ext: EXTENDED_DATA_STDERR } => stderr.extend_from_slice(&data),
+            ChannelMsg::ExitStatus { exit_status } => exit_code = exit_status as i32,
+            ChannelMsg::Close => break,
+            _ => {}
+        }
+    }
+    Ok((bytes_received, exit_code, stderr))
+}
+
+fn host_key_type(algorithm: Algorithm) -> HostKeyType {
+    match algorithm {
+        Algorithm::Rsa { .. } => HostKeyType::Rsa,
+        Algorithm::Dsa => HostKeyType::Dss,
+        Algorithm::Ecdsa { curve: EcdsaCurve::NistP256 } => HostKeyType::Ecdsa256,
+        Algorithm::Ecdsa { curve: EcdsaCurve::NistP384 } => HostKeyType::Ecdsa384,
+        Algorithm::Ecdsa { curve: EcdsaCurve::NistP521 } => HostKeyType::Ecdsa521,
+        Algorithm::Ed25519 => HostKeyType::Ed25519,
+        _ => HostKeyType::Unknown,
+    }
+}
+
+fn key_error(e: russh::keys::Error) -> AnsibleError {
+    AnsibleError::AuthenticationError(format!("Failed to load private key: {}", e))
+}
+
+fn auth_error(e: russh::Error) -> AnsibleError {
+    AnsibleError::AuthenticationError(format!("Authentication failed: {}", e))
+}
+
+fn command_error(e: russh::Error) -> AnsibleError {
+    AnsibleError::CommandExecutionError(format!("Failed to execute command via russh: {}", e))
+}
+
+fn transfer_error(e: russh::Error) -> AnsibleError {
+    AnsibleError::FileOperationError(format!("Failed to transfer file via russh: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::{Ssh2Transport, Transport};
+    use russh::keys::load_public_key;
+    use russh::server::{self, Auth, Msg, Session};
+    use russh::{Channel, ChannelId};
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    const PASSWORD: &str = "s3cret";
+    const KEY_PASSPHRASE: &str = "unlock-me";
+
+    /// 进程内的 SSH 服务器：命令通过本机的 `sh -c` 执行，分配 pty 时把 stderr 合并到 stdout
+    #[derive(Clone)]
+    struct TestServer {
+        authorized_keys: Arc<Vec<PublicKey>>,
+    }
+
+    struct ServerSession {
+        server: TestServer,
+        channels: HashMap<ChannelId, Channel<Msg>>,
+        pty: HashSet<ChannelId>,
+    }
+
+    impl server::Handler for ServerSession {
+        type Error = russh::Error;
+
+        async fn auth_password(&mut self, _user: &str, password: &str) -> Result<Auth, Self::Error> {
+            Ok(if password == PASSWORD { Auth::Accept } else { Auth::reject() })
+        }
+
+        async fn auth_publickey(&mut self, _user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+            let authorized = self.server.authorized_keys.iter().any(|k| k.key_data() == key.key_data());
+            Ok(if authorized { Auth::Accept } else { Auth::reject() })
+        }
+
+        async fn channel_open_session(&mut self, channel: Channel<Msg>, _session: &mut Session) -> Result<bool, Self::Error> {
+            self.channels.insert(channel.id(), channel);
+            Ok(true)
+        }
+
+        async fn pty_request(
+            &mut self,
+            channel: ChannelId,
+            _term: &str,
+            _col_width: u32,
+            _row_height: u32,
+            _pix_width: u32,
+            _pix_height: u32,
+            _modes: &[(russh::Pty, u32)],
+            session: &mut Session,
+        ) -> Result<(), Self::Error> {
+            self.pty.insert(channel);
+            session.channel_success(channel)
+        }
+
+        async fn exec_request(&mut self, channel: ChannelId, data: &[u8], session: &mut Session) -> Result<(), Self::Error> {
+            let Some(opened) = self.channels.remove(&channel) else {
+                return session.channel_failure(channel);
+            };
+            let command = String::from_utf8_lossy(data).into_owned();
+            let command = if self.pty.contains(&channel) { format!("exec 2>&1; {}", command) } else { command };
+            session.channel_success(channel)?;
+            tokio::spawn(run_command(opened, command));
+            Ok(())
+        }
+    }
+
+    async fn run_command(channel: Channel<Msg>, command: String) {
+        let (mut input, output) = channel.split();
+        let mut child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("failed to spawn sh");
+
+        // 通道消息必须及时取走：阻塞在写 stdin 上会卡住会话循环，使其无法处理客户端的窗口调整
+        let (sender, mut received) = tokio::sync::mpsc::unbounded_channel::<Vec<u8>>();
+        let drain = tokio::spawn(async move {
+            while let Some(message) = input.wait().await {
+                match message {
+                    ChannelMsg::Data { data } => {
+                        let _ = sender.send(data.to_vec());
+                    }
+                    ChannelMsg::Eof => break,
+                    _ => {}
+                }
+            }
+        });
+        let mut stdin = child.stdin.take().unwrap();
+        tokio::spawn(async move {
+            while let Some(data) = received.recv().await {
+                if stdin.write_all(&data).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (stdout, stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+        let _ = tokio::join!(output.data(stdout), output.extended_data(EXTENDED_DATA_STDERR, stderr));
+        let status = child.wait().await.map(|s| s.code().unwrap_or(255)).unwrap_or(255);
+        let _ = output.exit_status(status as u32).await;
+        let _ = output.eof().await;
+        let _ = output.close().await;
+        drain.abort();
+    }
+
+    /// 测试用的密钥：服务器主机密钥与三种客户端私钥（无口令、带口令、RSA）
+    struct TestKeys {
+        dir: PathBuf,
+        host: PathBuf,
+        plain: PathBuf,
+        encrypted: PathBuf,
+        rsa: PathBuf,
+    }
+
+    impl TestKeys {
+        fn generate() -> Self {
+            let dir = PathBuf::from(crate::utils::generate_local_temp_path("rs_ansible_russh_keys"));
+            std::fs::create_dir_all(&dir).unwrap();
+            let keygen = |name: &str, kind: &str, passphrase: &str| {
+                let path = dir.join(name);
+                let status = std::process::Command::new("ssh-keygen")
+                    .args(["-q", "-t", kind, "-N", passphrase, "-C", "", "-f"])
+                    .arg(&path)
+                    .status()
+                    .expect("ssh-keygen is required for russh tests");
+                assert!(status.success());
+                path
+            };
+            Self {
+                host: keygen("host", "ed25519", ""),
+                plain: keygen("id_plain", "ed25519", ""),
+                encrypted: keygen("id_encrypted", "ed25519", KEY_PASSPHRASE),
+                rsa: keygen("id_rsa", "rsa", ""),
+                dir,
+            }
+        }
+
+        fn public(path: &Path) -> PublicKey {
+            load_public_key(path.with_extension("pub")).unwrap()
+        }
+    }
+
+    impl Drop for TestKeys {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// 在随机端口上启动测试服务器，返回端口
+    async fn start_server(keys: &TestKeys) -> u16 {
+        let config = Arc::new(server::Config {
+            keys: vec![load_secret_key(&keys.host, None).unwrap()],
+            auth_rejection_time: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let server = TestServer {
+            authorized_keys: Arc::new(vec![
+                TestKeys::public(&keys.plain),
+                TestKeys::public(&keys.encrypted),
+                TestKeys::public(&keys.rsa),
+            ]),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let session = ServerSession {
+                    server: server.clone(),
+                    channels: HashMap::new(),
+                    pty: HashSet::new(),
+                };
+                let _ = server::run_stream(config.clone(), socket, session).await;
+            }
+        });
+        port
+    }
+
+    fn host_config(port: u16) -> HostConfig {
+        HostConfig {
+            hostname: "127.0.0.1".to_string(),
+            port,
+            username: "deploy".to_string(),
+            password: Some(PASSWORD.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_host_key_type_mapping() {
+        assert_eq!(host_key_type(Algorithm::Ed25519), HostKeyType::Ed25519);
+        assert_eq!(
+            host_key_type(Algorithm::Ecdsa { curve: EcdsaCurve::NistP384 }).algorithm(),
+            "ecdsa-sha2-nistp384"
+        );
+        assert_eq!(host_key_type(Algorithm::Rsa { hash: None }), HostKeyType::Rsa);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_russh_exec_and_transfer() {
+        let keys = TestKeys::generate();
+        let port = start_server(&keys).await;
+        let transport = RusshTransport::connect(&host_config(port), None).await.unwrap();
+
+        let result = transport
+            .exec("printf out; printf err >&2; exit 3", &CommandOptions::default())
+            .await
+            .unwrap();
+        assert_eq!((result.exit_code, result.stdout.as_str(), result.stderr.as_str()), (3, "out", "err"));
+
+        // 输入大于 2 MiB 的通道窗口，覆盖 stdin 与输出同时写满的情况
+        let large: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let options = CommandOptions { stdin: Some(large.clone()), ..Default::default() };
+        let piped = transport.exec("cat", &options).await.unwrap();
+        assert!(piped.stdout_bytes() == large.as_slice(), "stdin round trip differs");
+
+        // 分配 pty 时输出合并到同一个流
+        let options = CommandOptions { pty: true, ..Default::default() };
+        let merged = transport.exec("printf out; printf err >&2", &options).await.unwrap();
+        assert_eq!((merged.stdout.as_str(), merged.stderr.as_str()), ("outerr", ""));
+
+        let started = Instant::now();
+        let options = CommandOptions { timeout: Some(Duration::from_millis(300)), ..Default::default() };
+        let err = transport.exec("sleep 3", &options).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let payload = &large[..512 * 1024];
+        let remote_path = crate::utils::generate_local_temp_path("rs_ansible_russh_upload");
+        let uploaded = transport.upload(&mut &payload[..], payload.len() as u64, &remote_path, 0o640).await.unwrap();
+        assert_eq!(uploaded, payload.len() as u64);
+        let mode = transport.exec(&format!("stat -c %a {}", remote_path), &CommandOptions::default()).await.unwrap();
+        assert_eq!(mode.stdout.trim(), "640");
+        let mut downloaded = Vec::new();
+        assert_eq!(transport.download(&remote_path, &mut downloaded).await.unwrap(), payload.len() as u64);
+        assert!(downloaded == payload, "downloaded content differs");
+        let _ = std::fs::remove_file(&remote_path);
+
+        let err = transport.download(&remote_path, &mut Vec::new()).await.unwrap_err();
+        assert!(matches!(err, AnsibleError::FileOperationError(_)), "{}", err);
+
+        let host_key = TestKeys::public(&keys.host);
+        let expected = hex_fingerprint(host_key.fingerprint(HashAlg::Sha256).as_bytes());
+        assert_eq!(transport.host_key_fingerprint().unwrap(), (HostKeyType::Ed25519, expected));
+        transport.disconnect().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_russh_authentication_methods() {
+        let keys = TestKeys::generate();
+        let port = start_server(&keys).await;
+        let base = HostConfig { password: None, ..host_config(port) };
+        let path = |p: &Path| Some(p.to_string_lossy().into_owned());
+        let secret = SecretString::new(KEY_PASSPHRASE.to_string());
+
+        let configs = [
+            HostConfig { private_key_path: path(&keys.plain), ..base.clone() },
+            HostConfig { private_key_path: path(&keys.rsa), ..base.clone() },
+            HostConfig {
+                private_key_path: path(&keys.encrypted),
+                passphrase: Some(KEY_PASSPHRASE.to_string()),
+                ..base.clone()
+            },
+            HostConfig {
+                private_key_data: Some(std::fs::read_to_string(&keys.encrypted).unwrap()),
+                passphrase: Some(KEY_PASSPHRASE.to_string()),
+                ..base.clone()
+            },
+            HostConfig { private_key_data: Some(std::fs::read_to_string(&keys.plain).unwrap()), ..base.clone() },
+        ];
+        for config in &configs {
+            let transport = RusshTransport::connect(config, None).await.unwrap();
+            let result = transport.exec("echo 'pong'", &CommandOptions::default()).await.unwrap();
+            assert_eq!(result.stdout, "pong\n");
+        }
+
+        // 口令与密码都可以来自凭据提供者
+        let config = HostConfig { private_key_path: path(&keys.encrypted), ..base.clone() };
+        assert!(RusshTransport::connect(&config, Some(&secret)).await.is_ok());
+        let password = SecretString::new(PASSWORD.to_string());
+        assert!(RusshTransport::connect(&base, Some(&password)).await.is_ok());
+
+        let failures = [
+            (HostConfig { password: Some("wrong".to_string()), ..base.clone() }, "Authentication failed"),
+            (base.clone(), "No authentication method provided"),
+            (HostConfig { private_key_path: path(&keys.encrypted), ..base.clone() }, "Failed to load private key"),
+        ];
+        for (config, message) in failures {
+            let err = RusshTransport::connect(&config, None).await.err().unwrap();
+            assert!(matches!(err, AnsibleError::AuthenticationError(_)), "{}", err);
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+
+    /// 同一组操作分别经 ssh2 与 russh 执行，结果应一致
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_russh_transport_parity_with_ssh2() {
+        let keys = TestKeys::generate();
+        let port = start_server(&keys).await;
+        let config = host_config(port);
+        let payload: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let script = "printf 'out'; printf 'err' >&2; exit 3";
+
+        let russh = RusshTransport::connect(&config, None).await.unwrap();
+        let russh_path = crate::utils::generate_local_temp_path("rs_ansible_parity_russh");
+        let echo = russh.exec(script, &CommandOptions::default()).await.unwrap();
+        let uploaded = russh.upload(&mut payload.as_slice(), payload.len() as u64, &russh_path, 0o600).await.unwrap();
+        let mut downloaded = Vec::new();
+        russh.download(&russh_path, &mut downloaded).await.unwrap();
+        let russh_outcome = (echo.exit_code, echo.stdout, echo.stderr, uploaded, downloaded);
+
+        // ssh2 是同步接口，与 AnsibleManager 一样在阻塞线程中使用
+        let (ssh2_path, ssh2_payload) = (crate::utils::generate_local_temp_path("rs_ansible_parity_ssh2"), payload.clone());
+        let remote = ssh2_path.clone();
+        let ssh2_outcome = tokio::task::spawn_blocking(move || {
+            let ssh2 = Ssh2Transport::connect(&config, None).unwrap();
+            let echo = ssh2.exec(script, &CommandOptions::default()).unwrap();
+            let uploaded = ssh2.upload(&mut ssh2_payload.as_slice(), ssh2_payload.len() as u64, &remote, 0o600).unwrap();
+            let mut downloaded = Vec::new();
+            ssh2.download(&remote, &mut downloaded).unwrap();
+            (echo.exit_code, echo.stdout, echo.stderr, uploaded, downloaded)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&russh_path).unwrap(), std::fs::read(&ssh2_path).unwrap());
+        let _ = std::fs::remove_file(&russh_path);
+        let _ = std::fs::remove_file(&ssh2_path);
+        assert!(russh_outcome == ssh2_outcome, "russh and ssh2 results differ");
+        assert_eq!(russh_outcome.0, 3);
+        assert!(russh_outcome.4 == payload);
+    }
+
+    /// 管理器经真实的 russh 连接执行批量命令与文件复制
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_manager_batch_over_russh_transport() {
+        use crate::manager::AnsibleManager;
+        use crate::types::{FileCopyOptions, TransportKind};
+
+        let keys = TestKeys::generate();
+        let port = start_server(&keys).await;
+        let manager = AnsibleManager::new();
+        manager.add_host("web1".to_string(), HostConfig { transport: TransportKind::Russh, ..host_config(port) });
+        let hosts = vec!["web1".to_string()];
+
+        let result = manager.execute_command_on_hosts("echo hello; echo oops >&2; exit 2", &hosts).await;
+        let output = result.results["web1"].value().unwrap();
+        assert_eq!((output.exit_code, output.stdout.as_str(), output.stderr.as_str()), (2, "hello\n", "oops\n"));
+
+        let local = crate::utils::generate_local_temp_path("rs_ansible_russh_local");
+        let remote = crate::utils::generate_local_temp_path("rs_ansible_russh_remote");
+        std::fs::write(&local, b"port = 80\n").unwrap();
+        let options = FileCopyOptions { mode: Some("600".to_string()), ..Default::default() };
+        let copied = manager.copy_file_to_hosts_with_options(&local, &remote, &hosts, &options).await;
+        let transfer = copied.results["web1"].value().cloned();
+        let content = std::fs::read(&remote);
+        let _ = std::fs::remove_file(&local);
+        let _ = std::fs::remove_file(&remote);
+        assert!(transfer.unwrap().success);
+        assert_eq!(content.unwrap(), b"port = 80\n");
+    }
+}
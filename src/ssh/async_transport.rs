@@ -0,0 +1,71 @@
+use crate::credentials::SecretString;
+use crate::error::AnsibleError;
+use crate::types::{CommandOptions, CommandResult, HostConfig, HostKeyType, TransportKind};
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// 异步远程连接传输层
+///
+/// 与 [`super::Transport`] 对应，`AsyncSshClient` 的所有远程操作都通过该 trait 完成。
+/// 实现不得阻塞线程：`AnsibleManager` 在 tokio 任务中直接 await 这些方法，
+/// 数千台主机共用运行时的工作线程，不为每个连接占用阻塞线程。
+#[async_trait]
+pub trait AsyncTransport: Send + Sync {
+    /// 建立连接并完成认证
+    async fn connect(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError>
+    where
+        Self: Sized;
+
+    /// 执行命令并收集输出
+    ///
+    /// 与 [`super::Transport::exec`] 相同：实现需处理 `options` 中的 stdin、timeout 与 pty，
+    /// `timeout` 是整条命令的总时长上限。
+    async fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError>;
+
+    /// 将 reader 中的 `size` 字节上传到远程路径，返回实际传输字节数
+    async fn upload(
+        &self,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        size: u64,
+        remote_path: &str,
+        mode: i32,
+    ) -> Result<u64, AnsibleError>;
+
+    /// 下载远程文件并写入 writer，返回实际传输字节数
+    async fn download(
+        &self,
+        remote_path: &str,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<u64, AnsibleError>;
+
+    /// 主动关闭连接（默认依赖 Drop 关闭）
+    async fn disconnect(&self) {}
+
+    /// 握手时服务器提供的主机密钥类型与 SHA256 指纹（小写十六进制）
+    fn host_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        Err(AnsibleError::ValidationError("Connection has no SSH host key".to_string()))
+    }
+}
+
+/// 按主机配置选择的异步传输实现建立连接
+pub(crate) async fn connect(
+    config: &HostConfig,
+    secret: Option<&SecretString>,
+) -> Result<Box<dyn AsyncTransport>, AnsibleError> {
+    match config.transport {
+        #[cfg(feature = "async-ssh")]
+        TransportKind::Russh => Ok(Box::new(super::async_ssh::RusshTransport::connect(config, secret).await?)),
+        #[cfg(not(feature = "async-ssh"))]
+        TransportKind::Russh => {
+            let _ = secret;
+            Err(AnsibleError::ValidationError(format!(
+                "Host {} uses the russh transport, which requires the async-ssh feature",
+                config.hostname
+            )))
+        }
+        kind => Err(AnsibleError::ValidationError(format!(
+            "Host {} uses the {:?} transport, which has no async implementation",
+            config.hostname, kind
+        ))),
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::AnsibleError;
+use crate::diff::unified_diff;
+use crate::types::{BlockInFileOptions, BlockInFileResult, CommandOptions, UserState};
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use super::SshClient;
+use tracing::{debug, info};
+
+impl SshClient {
+    /// 确保文件中由 BEGIN/END 标记包围的文本块存在（或被删除）
+    ///
+    /// 已有同名标记的块会被原地替换，否则追加到文件末尾。新内容先写入同目录下的临时文件，
+    /// 复制原文件的权限与所有者后再通过 mv 原子替换；内容不变时不做任何修改。
+    pub fn block_in_file(&self, options: &BlockInFileOptions) -> Result<BlockInFileResult, AnsibleError> {
+        if !options.marker.contains("{mark}") {
+            return Err(AnsibleError::ValidationError(format!(
+                "Block marker must contain '{{mark}}': {}",
+                options.marker
+            )));
+        }
+        let begin = options.marker.replace("{mark}", "BEGIN");
+        let end = options.marker.replace("{mark}", "END");
+        let present = options.state == UserState::Present && !options.block.trim().is_empty();
+
+        let path = shell_quote(&options.path);
+        let exists = self.execute_command(&format!("test -f {}", path))?.exit_code == 0;
+        let current = if exists {
+            Some(self.read_remote_file(&options.path)?)
+        } else if present && !options.create {
+            return Err(AnsibleError::FileOperationError(format!(
+                "{} does not exist (set create to add it)",
+                options.path
+            )));
+        } else {
+            None
+        };
+
+        let desired = apply_block(current.as_deref(), &begin, &end, &options.block, present);
+        if current.as_deref().unwrap_or("") == desired {
+            debug!("Block in {} already up to date", options.path);
+            return Ok(BlockInFileResult {
+                changed: false,
+                message: format!("Block in {} already up to date", options.path),
+                diff: None,
+            });
+        }
+
+        let temp_path = shell_quote(&generate_remote_temp_path(&options.path));
+        let cmd = format!(
+            "cat > {temp} && {{ [ ! -e {path} ] || {{ chmod --reference={path} {temp} && {{ chown --reference={path} {temp} 2>/dev/null || true; }}; }}; }} && mv -f {temp} {path}",
+            temp = temp_path,
+            path = path
+        );
+        let options_with_content = CommandOptions {
+            stdin: Some(desired.clone().into_bytes()),
+            ..Default::default()
+        };
+        let result = self.execute_command_full(&cmd, options_with_content)?;
+        if result.exit_code != 0 {
+            let _ = self.execute_command(&format!("rm -f {}", temp_path));
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to write {}: {}",
+                options.path,
+                result.stderr.trim()
+            )));
+        }
+
+        let action = if present { "updated" } else { "removed" };
+        info!("Block in {} {}", options.path, action);
+        Ok(BlockInFileResult {
+            changed: true,
+            message: format!("Block in {} {}", options.path, action),
+            diff: Some(unified_diff(&options.path, current.as_deref().unwrap_or("").as_bytes(), desired.as_bytes())),
+        })
+    }
+}
+
+/// 计算写入（或删除）块之后的文件内容
+///
+/// 标记行按去除首尾空白后完全匹配；只有 BEGIN 没有 END 时视为不存在，新块追加到末尾。
+fn apply_block(content: Option<&str>, begin: &str, end: &str, block: &str, present: bool) -> String {
+    let mut lines: Vec<&str> = content.map(|c| c.lines().collect()).unwrap_or_default();
+
+    let start = lines.iter().position(|l| l.trim() == begin);
+    let range = start.and_then(|start| {
+        lines[start + 1..]
+            .iter()
+            .position(|l| l.trim() == end)
+            .map(|offset| start..start + offset + 2)
+    });
+
+    // 没有可删除的块时保持原样（包括末尾是否有换行）
+    if range.is_none() && !present {
+        return content.unwrap_or_default().to_string();
+    }
+
+    let mut replacement = Vec::new();
+    if present {
+        replacement.push(begin);
+        replacement.extend(block.trim_end_matches('\n').lines());
+        replacement.push(end);
+    }
+    match range {
+        Some(range) => {
+            lines.splice(range, replacement);
+        }
+        None => lines.extend(replacement),
+    }
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEGIN: &str = "# BEGIN RS-ANSIBLE MANAGED BLOCK";
+    const END: &str = "# END RS-ANSIBLE MANAGED BLOCK";
+
+    #[test]
+    fn test_apply_block_insert_update_remove() {
+        let original = "server {\n    listen 80;\n}\n";
+        let block = "location /api {\n    proxy_pass http://backend;\n}\n";
+
+        let inserted = apply_block(Some(original), BEGIN, END, block, true);
+        assert_eq!(
+            inserted,
+            format!("server {{\n    listen 80;\n}}\n{}\nlocation /api {{\n    proxy_pass http://backend;\n}}\n{}\n", BEGIN, END)
+        );
+        // 重复执行不产生变化
+        assert_eq!(apply_block(Some(&inserted), BEGIN, END, block, true), inserted);
+
+        // 原地更新，块之后的内容保持不变
+        let with_trailer = format!("{}# trailer\n", inserted);
+        let updated = apply_block(Some(&with_trailer), BEGIN, END, "location / {}", true);
+        assert_eq!(
+            updated,
+            format!("server {{\n    listen 80;\n}}\n{}\nlocation / {{}}\n{}\n# trailer\n", BEGIN, END)
+        );
+
+        let removed = apply_block(Some(&updated), BEGIN, END, "", false);
+        assert_eq!(removed, "server {\n    listen 80;\n}\n# trailer\n");
+        assert_eq!(apply_block(Some(&removed), BEGIN, END, "", false), removed);
+    }
+
+    #[test]
+    fn test_apply_block_new_file_and_unterminated_marker() {
+        assert_eq!(
+            apply_block(None, BEGIN, END, "a\nb", true),
+            format!("{}\na\nb\n{}\n", BEGIN, END)
+        );
+        assert_eq!(apply_block(None, BEGIN, END, "", false), "");
+        assert_eq!(apply_block(Some("no newline"), BEGIN, END, "", false), "no newline");
+
+        // 缺少 END 标记时不删除任何内容，新块追加到末尾
+        let broken = format!("{}\nkeep me\n", BEGIN);
+        assert_eq!(
+            apply_block(Some(&broken), BEGIN, END, "x", true),
+            format!("{}\nkeep me\n{}\nx\n{}\n", BEGIN, BEGIN, END)
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::error::AnsibleError;
+use crate::types::{CgroupConfig, CgroupResource};
+use crate::utils::shell_quote;
+use super::SshClient;
+use tracing::{debug, info};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const V1_CONTROLLERS: &str = "cpu,memory,pids";
+const V2_CONTROLLERS: &str = "+cpu +memory +pids";
+
+/// 主机使用的 cgroup 层级：v1（每个控制器单独挂载）或 v2（统一层级）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CgroupVersion {
+    V1,
+    V2,
+}
+
+impl SshClient {
+    /// 创建 cgroup（已存在时不重复创建）并应用配置中的资源限制，返回是否有修改
+    ///
+    /// cgroup v1 通过 `cgcreate`（libcgroup）在 cpu、memory、pids 层级下创建；
+    /// v2 直接在 `/sys/fs/cgroup` 下创建目录，并在各级父 cgroup 的 `cgroup.subtree_control`
+    /// 中启用这些控制器。需要 root 权限（become）。
+    pub fn create_cgroup(&self, config: &CgroupConfig) -> Result<bool, AnsibleError> {
+        let path = config.path();
+        validate_cgroup_path(&path)?;
+        let version = self.cgroup_version()?;
+
+        let dir = cgroup_dir(version, &path, CgroupResource::MemoryLimit);
+        let exists = self.execute_command(&format!("test -d {}", shell_quote(&dir)))?.exit_code == 0;
+        let mut changed = false;
+        if !exists {
+            let command = match version {
+                CgroupVersion::V1 => cgcreate_command(&path),
+                CgroupVersion::V2 => v2_create_command(&path),
+            };
+            let result = self.execute_command(&command)?;
+            if result.exit_code != 0 {
+                return Err(AnsibleError::CommandError(format!(
+                    "Failed to create cgroup {}: {}",
+                    path,
+                    result.stderr.trim()
+                )));
+            }
+            info!("Created cgroup {} on {}", path, self.config.hostname);
+            changed = true;
+        }
+
+        // v1 中 memsw 上限不能低于内存上限，因此先设置内存
+        let limits = [
+            (CgroupResource::CpuShares, config.cpu_shares.map(u64::from)),
+            (CgroupResource::MemoryLimit, config.memory_limit_bytes),
+            (CgroupResource::MemorySwapLimit, config.memory_swap_limit_bytes),
+            (CgroupResource::PidsMax, config.pids_max),
+        ];
+        for (resource, value) in limits {
+            if let Some(value) = value {
+                changed |= self.set_cgroup_limit(&path, resource, value)?;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// 设置 cgroup（`name` 为相对于挂载点的路径，例如 `tenants/app1`）的单项资源限制，返回是否有修改
+    ///
+    /// `MemorySwapLimit` 只限制交换分区用量：v1 的 `memory.memsw.limit_in_bytes` 包含内存，
+    /// 写入的值为当前内存上限加上该值（需要内核开启 swap accounting）。
+    pub fn set_cgroup_limit(&self, name: &str, resource: CgroupResource, value: u64) -> Result<bool, AnsibleError> {
+        validate_cgroup_path(name)?;
+        let version = self.cgroup_version()?;
+        let file = cgroup_file_path(version, name, resource);
+
+        let value = match (version, resource) {
+            (CgroupVersion::V1, CgroupResource::MemorySwapLimit) => {
+                let memory_file = cgroup_file_path(version, name, CgroupResource::MemoryLimit);
+                let memory: u64 = self.read_cgroup_file(&memory_file)?.parse().map_err(|_| {
+                    AnsibleError::CommandError(format!("Unexpected value in {}", memory_file))
+                })?;
+                memory.saturating_add(value)
+            }
+            _ => value,
+        };
+        let desired = cgroup_file_value(version, resource, value);
+
+        if self.read_cgroup_file(&file)? == desired {
+            debug!("{} already set to {}", file, desired);
+            return Ok(false);
+        }
+        let result = self.execute_command(&format!("echo {} > {}", desired, shell_quote(&file)))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to write {} to {}: {}",
+                desired,
+                file,
+                result.stderr.trim()
+            )));
+        }
+        info!("Set {} to {} on {}", file, desired, self.config.hostname);
+        Ok(true)
+    }
+
+    /// `/sys/fs/cgroup` 挂载为 cgroup2 文件系统时为 v2，否则为 v1（包括 hybrid 模式）
+    fn cgroup_version(&self) -> Result<CgroupVersion, AnsibleError> {
+        let result = self.execute_command(&format!("stat -fc %T {}", CGROUP_ROOT))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to detect cgroup version: {}",
+                result.stderr.trim()
+            )));
+        }
+        Ok(if result.stdout.trim() == "cgroup2fs" { CgroupVersion::V2 } else { CgroupVersion::V1 })
+    }
+
+    fn read_cgroup_file(&self, file: &str) -> Result<String, AnsibleError> {
+        let result = self.execute_command(&format!("cat {}", shell_quote(file)))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read {}: {}",
+                file,
+                result.stderr.trim()
+            )));
+        }
+        Ok(result.stdout.trim().to_string())
+    }
+}
+
+/// cgroup 路径只允许由字母、数字、`.`、`_`、`-` 组成的非空段，不能包含 `..`
+fn validate_cgroup_path(path: &str) -> Result<(), AnsibleError> {
+    let valid = !path.is_empty()
+        && path.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        });
+    if !valid {
+        return Err(AnsibleError::ValidationError(format!("Invalid cgroup path: '{}'", path)));
+    }
+    Ok(())
+}
+
+fn cgcreate_command(path: &str) -> String {
+    format!("cgcreate -g {}", shell_quote(&format!("{}:{}", V1_CONTROLLERS, path)))
+}
+
+/// 创建 v2 cgroup 目录，并在根到父级的每一级启用子树控制器
+fn v2_create_command(path: &str) -> String {
+    let mut commands = vec![format!("mkdir -p {}", shell_quote(&format!("{}/{}", CGROUP_ROOT, path)))];
+    let segments: Vec<&str> = path.split('/').collect();
+    for depth in 0..segments.len() {
+        let dir = [CGROUP_ROOT]
+            .into_iter()
+            .chain(segments[..depth].iter().copied())
+            .collect::<Vec<_>>()
+            .join("/");
+        commands.push(format!(
+            "echo {} > {}",
+            shell_quote(V2_CONTROLLERS),
+            shell_quote(&format!("{}/cgroup.subtree_control", dir))
+        ));
+    }
+    commands.join(" && ")
+}
+
+/// cgroup 在指定资源所属层级下的目录
+fn cgroup_dir(version: CgroupVersion, path: &str, resource: CgroupResource) -> String {
+    match version {
+        CgroupVersion::V2 => format!("{}/{}", CGROUP_ROOT, path),
+        CgroupVersion::V1 => {
+            let controller = match resource {
+                CgroupResource::CpuShares => "cpu",
+                CgroupResource::MemoryLimit | CgroupResource::MemorySwapLimit => "memory",
+                CgroupResource::PidsMax => "pids",
+            };
+            format!("{}/{}/{}", CGROUP_ROOT, controller, path)
+        }
+    }
+}
+
+/// 资源限制对应的控制文件
+fn cgroup_file_path(version: CgroupVersion, path: &str, resource: CgroupResource) -> String {
+    let file = match (version, resource) {
+        (CgroupVersion::V1, CgroupResource::CpuShares) => "cpu.shares",
+        (CgroupVersion::V1, CgroupResource::MemoryLimit) => "memory.limit_in_bytes",
+        (CgroupVersion::V1, CgroupResource::MemorySwapLimit) => "memory.memsw.limit_in_bytes",
+        (CgroupVersion::V2, CgroupResource::CpuShares) => "cpu.weight",
+        (CgroupVersion::V2, CgroupResource::MemoryLimit) => "memory.max",
+        (CgroupVersion::V2, CgroupResource::MemorySwapLimit) => "memory.swap.max",
+        (_, CgroupResource::PidsMax) => "pids.max",
+    };
+    format!("{}/{}", cgroup_dir(version, path, resource), file)
+}
+
+/// 写入控制文件的值；v2 的 cpu.weight 按 systemd 的方式由 cpu.shares 等比换算（1024 对应 100）
+fn cgroup_file_value(version: CgroupVersion, resource: CgroupResource, value: u64) -> String {
+    match (version, resource) {
+        (CgroupVersion::V2, CgroupResource::CpuShares) => {
+            (value.clamp(2, 262_144) * 100 / 1024).clamp(1, 10_000).to_string()
+        }
+        (CgroupVersion::V1, CgroupResource::CpuShares) => value.clamp(2, 262_144).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgcreate_and_v2_create_commands() {
+        let config = CgroupConfig {
+            name: "app1".to_string(),
+            parent: "/tenants/".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(config.path(), "tenants/app1");
+        assert_eq!(cgcreate_command(&config.path()), "cgcreate -g 'cpu,memory,pids:tenants/app1'");
+        assert_eq!(
+            v2_create_command(&config.path()),
+            "mkdir -p '/sys/fs/cgroup/tenants/app1' \
+             && echo '+cpu +memory +pids' > '/sys/fs/cgroup/cgroup.subtree_control' \
+             && echo '+cpu +memory +pids' > '/sys/fs/cgroup/tenants/cgroup.subtree_control'"
+        );
+
+        assert!(validate_cgroup_path("tenants/app1").is_ok());
+        for invalid in ["", "../etc", "tenants//app1", "app 1", "app;rm"] {
+            assert!(validate_cgroup_path(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_cgroup_file_paths_and_values() {
+        use CgroupResource::*;
+        let v1: Vec<String> = [CpuShares, MemoryLimit, MemorySwapLimit, PidsMax]
+            .into_iter()
+            .map(|r| cgroup_file_path(CgroupVersion::V1, "tenants/app1", r))
+            .collect();
+        assert_eq!(
+            v1,
+            [
+                "/sys/fs/cgroup/cpu/tenants/app1/cpu.shares",
+                "/sys/fs/cgroup/memory/tenants/app1/memory.limit_in_bytes",
+                "/sys/fs/cgroup/memory/tenants/app1/memory.memsw.limit_in_bytes",
+                "/sys/fs/cgroup/pids/tenants/app1/pids.max",
+            ]
+        );
+        assert_eq!(cgroup_file_path(CgroupVersion::V2, "app1", CpuShares), "/sys/fs/cgroup/app1/cpu.weight");
+        assert_eq!(cgroup_file_path(CgroupVersion::V2, "app1", MemorySwapLimit), "/sys/fs/cgroup/app1/memory.swap.max");
+        assert_eq!(cgroup_file_path(CgroupVersion::V2, "app1", PidsMax), "/sys/fs/cgroup/app1/pids.max");
+
+        // 默认的 1024 shares 对应 v2 的默认权重 100
+        assert_eq!(cgroup_file_value(CgroupVersion::V2, CpuShares, 1024), "100");
+        assert_eq!(cgroup_file_value(CgroupVersion::V2, CpuShares, 512), "50");
+        assert_eq!(cgroup_file_value(CgroupVersion::V2, CpuShares, 262_144), "10000");
+        assert_eq!(cgroup_file_value(CgroupVersion::V2, CpuShares, 0), "1");
+        assert_eq!(cgroup_file_value(CgroupVersion::V1, CpuShares, 512), "512");
+        assert_eq!(cgroup_file_value(CgroupVersion::V2, MemoryLimit, 1 << 30), "1073741824");
+    }
+}
@@ -1,108 +1,143 @@
+use crate::bandwidth::BandwidthLimiter;
+use crate::credentials::{key_data_requires_passphrase, key_requires_passphrase, CredentialKind, CredentialProvider, SecretString};
 use crate::error::AnsibleError;
-use crate::types::{CommandResult, HostConfig};
-use ssh2::Session;
-use std::io::prelude::*;
-use std::net::TcpStream;
+use crate::metrics;
+use crate::types::{CommandOptions, CommandResult, HostConfig, HostKeyType};
+use crate::utils::{retry_with_backoff_blocking, shell_quote};
+use super::host_metrics::MetricsCounters;
+use super::transport::{self, Transport};
 use std::path::Path;
-use std::thread;
-use std::time::Duration;
-use tracing::{info, warn};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn, Span};
 
 /// SSH 客户端
 pub struct SshClient {
-    pub(super) session: Session,
-    #[allow(dead_code)]
+    pub(super) transport: Box<dyn Transport>,
     pub(super) config: HostConfig,
+    pub(super) bandwidth_limiter: Option<Arc<BandwidthLimiter>>, // 多个客户端共享的上传带宽限制
+    pub(super) counters: Arc<MetricsCounters>,                   // 连接耗时、通道数等轻量统计
 }
 
 impl SshClient {
     /// 创建新的 SSH 连接（带重试机制）
     pub fn new(config: HostConfig) -> Result<Self, AnsibleError> {
+        Self::new_with_provider(config, None)
+    }
+
+    /// 创建新的 SSH 连接，配置中缺失的密码/口令由凭据提供者补全
+    pub fn new_with_provider(
+        config: HostConfig,
+        provider: Option<Arc<dyn CredentialProvider>>,
+    ) -> Result<Self, AnsibleError> {
+        Self::connect_with_retry_hook(config, provider, &|_, _| {})
+    }
+
+    /// 建立连接，每次失败后即将重试时以（即将进行的尝试序号, 失败原因）调用 `on_retry`
+    pub(crate) fn connect_with_retry_hook(
+        config: HostConfig,
+        provider: Option<Arc<dyn CredentialProvider>>,
+        on_retry: &dyn Fn(u32, &AnsibleError),
+    ) -> Result<Self, AnsibleError> {
+        validate_key_source(&config)?;
+        // 异步传输的主机没有同步连接，重试也不会成功
+        if config.transport.is_async() {
+            return Err(unsupported_async_transport(&config));
+        }
+
+        // 在重试循环之前解析一次凭据，避免重复访问外部存储
+        let secret = Self::resolve_secret(&config, provider.as_deref())?;
+
         let max_retries = 3;
-        let retry_delay = Duration::from_millis(1000);
-        let mut last_error = None;
-
-        for attempt in 1..=max_retries {
-            if attempt > 1 {
-                info!(
-                    "Retrying SSH connection to {}:{} (Attempt {}/{})",
-                    config.hostname, config.port, attempt, max_retries
-                );
-                thread::sleep(retry_delay * (attempt as u32 - 1));
-            }
+        retry_with_backoff_blocking(
+            max_retries,
+            Duration::from_millis(1000),
+            Duration::from_secs(10),
+            0.2,
+            |attempt| {
+                // 在当前主机 span 上记录连接尝试次数（span 未声明该字段时忽略）
+                Span::current().record("attempt", attempt);
+                if attempt > 1 {
+                    info!(
+                        "Retrying SSH connection to {}:{} (Attempt {}/{})",
+                        config.hostname, config.port, attempt, max_retries
+                    );
+                }
 
-            match Self::connect_once(&config) {
-                Ok(client) => return Ok(client),
-                Err(e) => {
+                let result = Self::connect_once(&config, secret.as_ref());
+                metrics::record_connection(&config.hostname, result.is_ok());
+                if let Err(ref e) = result {
                     warn!(
                         "SSH connection failed for {}:{}: {}. ",
                         config.hostname, config.port, e
                     );
-                    last_error = Some(e);
+                    if attempt < max_retries {
+                        on_retry(attempt + 1, e);
+                    }
                 }
+                result
+            },
+        )
+    }
+
+    /// 从凭据提供者解析认证所需的秘密（配置中已存在时不查询）
+    ///
+    /// 使用私钥认证时，仅当私钥已加密才会向提供者请求口令。
+    pub(super) fn resolve_secret(
+        config: &HostConfig,
+        provider: Option<&dyn CredentialProvider>,
+    ) -> Result<Option<SecretString>, AnsibleError> {
+        let Some(provider) = provider else {
+            return Ok(None);
+        };
+        // 本地与容器连接无需认证
+        if !config.uses_ssh() {
+            return Ok(None);
+        }
+
+        if let Some(ref key_path) = config.private_key_path {
+            if config.passphrase.is_none() && key_requires_passphrase(Path::new(key_path)) {
+                return provider.resolve(&config.hostname, CredentialKind::Passphrase);
             }
+        } else if let Some(ref key_data) = config.private_key_data {
+            if config.passphrase.is_none() && key_data_requires_passphrase(key_data) {
+                return provider.resolve(&config.hostname, CredentialKind::Passphrase);
+            }
+        } else if config.password.is_none() {
+            return provider.resolve(&config.hostname, CredentialKind::Password);
         }
 
-        Err(last_error.unwrap_or_else(|| {
-            AnsibleError::SshConnectionError("Failed to connect after retries".to_string())
-        }))
+        Ok(None)
     }
 
     /// 执行单次连接尝试
-    fn connect_once(config: &HostConfig) -> Result<Self, AnsibleError> {
-        let tcp = TcpStream::connect(format!("{}:{}", config.hostname, config.port)).map_err(
-            |e| {
-                AnsibleError::SshConnectionError(format!(
-                    "Failed to connect to {}:{}: {}",
-                    config.hostname, config.port, e
-                ))
-            },
-        )?;
-
-        // 优化：禁用 Nagle 算法，减少小包延迟，有助于握手稳定性
-        if let Err(e) = tcp.set_nodelay(true) {
-            warn!("Failed to set TCP_NODELAY: {}", e);
-        }
-
-        let mut session = Session::new()?;
-        session.set_tcp_stream(tcp);
-        
-        // 优化：设置超时时间（10秒），避免握手长时间卡死
-        // session.set_timeout(10000);
-        
-        session.handshake().map_err(|e| {
-            AnsibleError::SshConnectionError(format!("SSH Handshake failed: {}", e))
-        })?;
-
-        // 认证
-        if let Some(ref private_key_path) = config.private_key_path {
-            let passphrase = config.passphrase.as_deref();
-            session.userauth_pubkey_file(
-                &config.username,
-                None,
-                Path::new(private_key_path),
-                passphrase,
-            )?;
-        } else if let Some(ref password) = config.password {
-            session.userauth_password(&config.username, password)?;
-        } else {
-            return Err(AnsibleError::AuthenticationError(
-                "No authentication method provided".to_string(),
-            ));
-        }
+    fn connect_once(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        let started = Instant::now();
+        let transport = transport::connect(config, secret)?;
+        let client = Self::with_transport(config.clone(), transport);
+        client.record_connect_time(started.elapsed());
+        Ok(client)
+    }
 
-        if !session.authenticated() {
-            return Err(AnsibleError::AuthenticationError(
-                "Authentication failed".to_string(),
-            ));
+    /// 使用已建立的传输层创建客户端（例如自定义连接方式或测试用的 mock）
+    pub fn with_transport(config: HostConfig, transport: Box<dyn Transport>) -> Self {
+        Self {
+            transport,
+            config,
+            bandwidth_limiter: None,
+            counters: Arc::default(),
         }
+    }
 
-        info!("Successfully connected to {}", config.hostname);
+    /// 关闭连接
+    pub fn disconnect(self) {
+        self.transport.disconnect();
+        info!("Disconnected from {}", self.config.hostname);
+    }
 
-        Ok(Self {
-            session,
-            config: config.clone(),
-        })
+    /// 设置共享的上传带宽限制器（例如管理器级别的总带宽上限）
+    pub fn set_bandwidth_limiter(&mut self, limiter: Option<Arc<BandwidthLimiter>>) {
+        self.bandwidth_limiter = limiter;
     }
 
     /// 获取当前主机的配置信息
@@ -110,35 +145,197 @@ impl SshClient {
         &self.config
     }
 
+    /// 读取服务器主机密钥的类型与 SHA256 指纹（小写十六进制），不做任何校验
+    ///
+    /// 只读取握手后的会话状态，不执行远程命令；可用于在写入 known_hosts 前核对指纹。
+    /// 非 SSH 连接（local、docker）没有主机密钥，返回错误。
+    pub fn server_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        self.transport.host_key_fingerprint()
+    }
+
     /// 测试连接是否正常
     pub fn ping(&self) -> Result<bool, AnsibleError> {
         let result = self.execute_command("echo 'pong'")?;
         Ok(result.exit_code == 0 && result.stdout.trim() == "pong")
     }
 
-    /// 执行远程命令
+    /// 检查提权是否可用：按主机的 become 设置执行 `sudo -n true`（配置了密码时先以 `sudo -S -v` 验证密码）
+    ///
+    /// 无论主机是否开启 become 都会检查；sudo 需要交互输入密码或被拒绝时返回 false。
+    pub fn check_privilege_escalation(&self) -> Result<bool, AnsibleError> {
+        let mut config = self.config.clone();
+        config.r#become = true;
+
+        let result = self.exec_become(&config, "true", CommandOptions::default())?;
+        if result.exit_code != 0 {
+            warn!(
+                "Privilege escalation check failed on {}: {}",
+                self.config.hostname,
+                result.stderr.trim()
+            );
+        }
+        Ok(result.exit_code == 0)
+    }
+
+    /// 执行远程命令（若配置了 become，则通过 sudo 提权执行）
     pub fn execute_command(&self, command: &str) -> Result<CommandResult, AnsibleError> {
-        let mut channel = self.session.channel_session()?;
-        channel.exec(command)?;
+        self.execute_command_full(command, CommandOptions::default())
+    }
+
+    /// 按选项执行远程命令，stdin、超时、环境变量与 pty 可任意组合
+    ///
+    /// 环境变量在提权之前以 `export` 的形式注入，因此在 sudo 下同样生效。
+    /// 配置了 become 密码时，密码作为标准输入的第一行，只由 sudo 读取，命令读不到它。
+    pub fn execute_command_full(
+        &self,
+        command: &str,
+        options: CommandOptions,
+    ) -> Result<CommandResult, AnsibleError> {
+        let command = match options.env {
+            Some(ref env) if !env.is_empty() => env_command(env, command)?,
+            _ => command.to_string(),
+        };
+        let result = self.exec_become(&self.config, &command, options)?;
+
+        info!(command, exit_code = result.exit_code, "Command executed");
+        metrics::record_command(&self.config.hostname, result.exit_code);
 
-        let mut stdout = String::new();
-        let mut stderr = String::new();
+        Ok(result)
+    }
 
-        channel.read_to_string(&mut stdout)?;
-        channel.stderr().read_to_string(&mut stderr)?;
+    /// 按 become 设置包装并执行命令；配置了密码时把密码作为标准输入的第一行
+    ///
+    /// 密码先由 `sudo -S -v` 读取并缓存凭据，命令再以 `sudo -n` 执行。sudoers 设置了
+    /// `timestamp_timeout=0` 时凭据不会保留到下一次 sudo 调用，`sudo -n` 报告需要密码且命令没有运行；
+    /// 此时改为由执行命令的 `sudo -S` 直接读取密码（每次调用都需要密码，密码同样不会留给命令）。
+    fn exec_become(&self, config: &HostConfig, command: &str, mut options: CommandOptions) -> Result<CommandResult, AnsibleError> {
+        let password = config.become_password.as_ref().filter(|_| config.r#become);
+        if let Some(password) = password {
+            let mut stdin = format!("{}\n", password).into_bytes();
+            stdin.extend(options.stdin.take().unwrap_or_default());
+            options.stdin = Some(stdin);
+        }
 
-        channel.wait_close()?;
-        let exit_code = channel.exit_status()?;
+        self.record_command_channel();
+        let result = self.transport.exec(&become_command(config, command), &options)?;
+        if password.is_none() || result.exit_code == 0 || !sudo_requires_password(&result.stderr) {
+            return Ok(result);
+        }
+
+        debug!("Cached sudo credentials not reused on {}, passing the password to sudo directly", config.hostname);
+        self.record_command_channel();
+        self.transport.exec(&become_password_command(config, command), &options)
+    }
+}
+
+/// 私钥只能来自文件或内存中的一种
+pub(super) fn validate_key_source(config: &HostConfig) -> Result<(), AnsibleError> {
+    if config.private_key_path.is_some() && config.private_key_data.is_some() {
+        return Err(AnsibleError::AuthenticationError(format!(
+            "Host {} sets both private_key_path and private_key_data, use only one",
+            config.hostname
+        )));
+    }
+    Ok(())
+}
+
+/// 同步客户端无法连接异步传输的主机（见 `AsyncSshClient`）
+pub(super) fn unsupported_async_transport(config: &HostConfig) -> AnsibleError {
+    AnsibleError::ValidationError(format!(
+        "Host {} uses the russh transport, which only supports ping, command execution and file transfer operations",
+        config.hostname
+    ))
+}
+
+/// `sudo -n` 因需要密码而拒绝执行时的错误输出
+pub(super) fn sudo_requires_password(stderr: &str) -> bool {
+    stderr.lines().any(|line| line.trim() == "sudo: a password is required")
+}
+
+/// 由执行命令的 sudo 从标准输入读取密码
+pub(super) fn become_password_command(config: &HostConfig, command: &str) -> String {
+    let user = shell_quote(config.become_user.as_deref().unwrap_or("root"));
+    format!("sudo -S -p '' -H -u {} -- sh -c {}", user, shell_quote(command))
+}
+
+/// 根据主机的 become 设置包装命令
+pub(crate) fn become_command(config: &HostConfig, command: &str) -> String {
+    if !config.r#become {
+        return command.to_string();
+    }
+    let user = shell_quote(config.become_user.as_deref().unwrap_or("root"));
+    let run = format!("sudo -n -H -u {} -- sh -c {}", user, shell_quote(command));
+    if config.become_password.is_some() {
+        // 管道左侧总是读走标准输入的第一行（密码）交给 `sudo -v` 缓存凭据：即使 NOPASSWD
+        // 或凭据已缓存时 sudo 不读取密码，密码也不会留给命令；命令本身以 `sudo -n` 执行
+        return format!("{{ IFS= read -r pw; printf '%s\\n' \"$pw\"; }} | sudo -S -p '' -v && {}", run);
+    }
+    run
+}
+
+/// 在命令前注入环境变量（变量名按 key 排序，保证输出稳定）
+pub(super) fn env_command(env: &std::collections::HashMap<String, String>, command: &str) -> Result<String, AnsibleError> {
+    let mut keys: Vec<&String> = env.keys().collect();
+    keys.sort();
+
+    let mut script = String::new();
+    for key in keys {
+        let valid = !key.is_empty()
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(AnsibleError::ValidationError(format!(
+                "Invalid environment variable name: {}",
+                key
+            )));
+        }
+        script.push_str(&format!("export {}={}; ", key, shell_quote(&env[key])));
+    }
+    script.push_str(command);
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_become_command() {
+        let mut config = HostConfig::default();
+        assert_eq!(become_command(&config, "whoami"), "whoami");
+
+        config.r#become = true;
+        assert_eq!(
+            become_command(&config, "echo 'hi'"),
+            "sudo -n -H -u 'root' -- sh -c 'echo '\\''hi'\\'''"
+        );
+
+        config.become_user = Some("app; reboot".to_string());
+        assert_eq!(become_command(&config, "id"), "sudo -n -H -u 'app; reboot' -- sh -c 'id'");
+
+        config.become_user = Some("postgres".to_string());
+        assert_eq!(become_command(&config, "vacuumdb -a"), "sudo -n -H -u 'postgres' -- sh -c 'vacuumdb -a'");
+
+        // 密码只交给 `sudo -v`，命令本身始终以 `sudo -n` 执行
+        config.become_password = Some("secret".to_string());
+        assert_eq!(
+            become_command(&config, "id"),
+            "{ IFS= read -r pw; printf '%s\\n' \"$pw\"; } | sudo -S -p '' -v && sudo -n -H -u 'postgres' -- sh -c 'id'"
+        );
+        assert!(!serde_json::to_string(&config).unwrap().contains("secret"));
+    }
 
-        info!(
-            "Command '{}' on '{}' executed with exit code: {}",
-            command, self.config.hostname, exit_code
+    #[test]
+    fn test_env_command() {
+        let mut env = std::collections::HashMap::new();
+        env.insert("PGPASSFILE".to_string(), "/tmp/pg pass".to_string());
+        env.insert("LANG".to_string(), "C".to_string());
+        assert_eq!(
+            env_command(&env, "psql -f -").unwrap(),
+            "export LANG='C'; export PGPASSFILE='/tmp/pg pass'; psql -f -"
         );
 
-        Ok(CommandResult {
-            exit_code,
-            stdout,
-            stderr,
-        })
+        env.insert("BAD-NAME".to_string(), "x".to_string());
+        assert!(env_command(&env, "true").is_err());
     }
 }
@@ -0,0 +1,72 @@
+use crate::error::AnsibleError;
+use super::SshClient;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// 远程时间，毫秒级 Unix 时间戳（GNU date）
+const REMOTE_TIME_COMMAND: &str = "date +%s%3N";
+
+impl SshClient {
+    /// 估算远程主机相对控制端的时钟偏差（毫秒，正值表示远程时钟超前）
+    ///
+    /// 在执行远程 `date +%s%3N` 前后各取一次本地时间，以两者的中点作为远程取时的时刻，
+    /// 误差不超过往返耗时的一半。不依赖 NTP，适合快速检查；远程 date 不支持 `%3N`（如 busybox）时返回错误。
+    pub fn get_time_offset_ms(&self) -> Result<f64, AnsibleError> {
+        let local_before = local_time_ms();
+        let result = self.execute_command(REMOTE_TIME_COMMAND)?;
+        let local_after = local_time_ms();
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read remote time: {}",
+                result.stderr.trim()
+            )));
+        }
+        let remote = parse_epoch_ms(&result.stdout)?;
+        let offset = clock_offset_ms(local_before, remote, local_after);
+        debug!(
+            "Clock offset of {}: {:.1} ms (round trip {:.1} ms)",
+            self.config.hostname,
+            offset,
+            local_after - local_before
+        );
+        Ok(offset)
+    }
+}
+
+fn local_time_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64() * 1000.0)
+        .unwrap_or_default()
+}
+
+fn parse_epoch_ms(output: &str) -> Result<f64, AnsibleError> {
+    let output = output.trim();
+    output
+        .parse::<u64>()
+        .map(|ms| ms as f64)
+        .map_err(|_| AnsibleError::CommandError(format!("Unexpected output from '{}': '{}'", REMOTE_TIME_COMMAND, output)))
+}
+
+/// 远程时间减去本地取时前后两个时刻的中点
+fn clock_offset_ms(local_before: f64, remote: f64, local_after: f64) -> f64 {
+    remote - (local_before + local_after) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_offset_arithmetic() {
+        // 往返 40ms，远程在中点时刻报告的时间比本地快 250ms
+        assert_eq!(clock_offset_ms(1_700_000_000_000.0, 1_700_000_000_270.0, 1_700_000_000_040.0), 250.0);
+        assert_eq!(clock_offset_ms(1_000.0, 900.0, 1_100.0), -150.0);
+        assert_eq!(clock_offset_ms(1_000.0, 1_005.0, 1_010.0), 0.0);
+
+        assert_eq!(parse_epoch_ms("1700000000123\n").unwrap(), 1_700_000_000_123.0);
+        // busybox date 不支持 %N，原样输出
+        assert!(parse_epoch_ms("1700000000%3N\n").is_err());
+        assert!(parse_epoch_ms("").is_err());
+    }
+}
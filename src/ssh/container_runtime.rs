@@ -0,0 +1,268 @@
+use crate::error::AnsibleError;
+use crate::types::ContainerRuntimeInfo;
+use super::SshClient;
+use tracing::{debug, info};
+
+impl SshClient {
+    /// 获取容器运行时信息
+    ///
+    /// 依次探测 `crictl info`、`containerd --version`、`docker info --format json`、`crio --version`，
+    /// 返回第一个可用的运行时；都不可用时返回 `None`。
+    pub fn get_container_runtime_info(&self) -> Result<Option<ContainerRuntimeInfo>, AnsibleError> {
+        if let Some(info) = self.probe_crictl()? {
+            info!("Detected container runtime via crictl: {} {}", info.runtime, info.version);
+            return Ok(Some(info));
+        }
+        if let Some(info) = self.probe_containerd()? {
+            info!("Detected container runtime: containerd {}", info.version);
+            return Ok(Some(info));
+        }
+        if let Some(info) = self.probe_docker()? {
+            info!("Detected container runtime: docker {}", info.version);
+            return Ok(Some(info));
+        }
+        if let Some(info) = self.probe_crio()? {
+            info!("Detected container runtime: cri-o {}", info.version);
+            return Ok(Some(info));
+        }
+
+        info!("No container runtime detected on {}", self.config.hostname);
+        Ok(None)
+    }
+
+    /// 通过 crictl 探测 CRI 运行时
+    fn probe_crictl(&self) -> Result<Option<ContainerRuntimeInfo>, AnsibleError> {
+        debug!("Probing container runtime with crictl");
+        let info_result = self.execute_command("crictl info 2>/dev/null")?;
+        if info_result.exit_code != 0 {
+            return Ok(None);
+        }
+
+        let version_result = self.execute_command("crictl version 2>/dev/null")?;
+        if version_result.exit_code != 0 {
+            return Ok(None);
+        }
+
+        let mut info = parse_crictl_output(&version_result.stdout, &info_result.stdout);
+        if info.runtime == "cri-o" {
+            // crictl info 不包含 CRI-O 的 cgroup 驱动，从 CRI-O 自身的配置读取
+            info.cgroup_driver = self.crio_cgroup_manager()?;
+        }
+        Ok(Some(info))
+    }
+
+    /// 探测 containerd
+    fn probe_containerd(&self) -> Result<Option<ContainerRuntimeInfo>, AnsibleError> {
+        debug!("Probing container runtime with containerd --version");
+        let result = self.execute_command("containerd --version 2>/dev/null")?;
+        if result.exit_code != 0 {
+            return Ok(None);
+        }
+
+        let config = self.execute_command("cat /etc/containerd/config.toml 2>/dev/null")?;
+        Ok(parse_containerd_version(&result.stdout).map(|version| ContainerRuntimeInfo {
+            runtime: "containerd".to_string(),
+            version,
+            api_version: "unknown".to_string(),
+            storage_driver: None,
+            cgroup_driver: cgroup_driver_from_systemd_flag(&config.stdout),
+        }))
+    }
+
+    /// 探测 Docker
+    fn probe_docker(&self) -> Result<Option<ContainerRuntimeInfo>, AnsibleError> {
+        debug!("Probing container runtime with docker info");
+        let result = self.execute_command("docker info --format json 2>/dev/null")?;
+        if result.exit_code != 0 {
+            return Ok(None);
+        }
+
+        let Some(mut info) = parse_docker_info(&result.stdout) else {
+            return Ok(None);
+        };
+
+        let api = self.execute_command("docker version --format '{{.Server.APIVersion}}' 2>/dev/null")?;
+        if api.exit_code == 0 && !api.stdout.trim().is_empty() {
+            info.api_version = api.stdout.trim().to_string();
+        }
+
+        Ok(Some(info))
+    }
+
+    /// 探测 CRI-O
+    fn probe_crio(&self) -> Result<Option<ContainerRuntimeInfo>, AnsibleError> {
+        debug!("Probing container runtime with crio --version");
+        let result = self.execute_command("crio --version 2>/dev/null")?;
+        if result.exit_code != 0 {
+            return Ok(None);
+        }
+
+        let cgroup_driver = self.crio_cgroup_manager()?;
+        Ok(parse_crio_version(&result.stdout).map(|version| ContainerRuntimeInfo {
+            runtime: "cri-o".to_string(),
+            version,
+            api_version: "unknown".to_string(),
+            storage_driver: None,
+            cgroup_driver,
+        }))
+    }
+
+    /// 从 `crio config` 输出的生效配置中读取 `cgroup_manager`，读取不到时返回 `unknown`
+    fn crio_cgroup_manager(&self) -> Result<String, AnsibleError> {
+        let config = self.execute_command("crio config 2>/dev/null")?;
+        Ok(parse_crio_cgroup_manager(&config.stdout).unwrap_or_else(|| "unknown".to_string()))
+    }
+}
+
+/// 解析 `crictl version` 与 `crictl info` 的输出
+///
+/// cgroup 驱动根据 containerd 的 `SystemdCgroup` 开关推断；CRI-O 不在 `crictl info` 中报告该设置，
+/// 此时为 `unknown`，由调用方从 CRI-O 配置中读取。
+fn parse_crictl_output(version_output: &str, info_output: &str) -> ContainerRuntimeInfo {
+    let field = |name: &str| {
+        version_output
+            .lines()
+            .find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key.trim() == name).then(|| value.trim().to_string())
+            })
+            .unwrap_or_else(|| "unknown".to_string())
+    };
+
+    let info: serde_json::Value = serde_json::from_str(info_output).unwrap_or_default();
+    let storage_driver = info
+        .pointer("/config/containerd/snapshotter")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let runtime = field("RuntimeName");
+    let cgroup_driver = if runtime == "cri-o" {
+        "unknown".to_string()
+    } else {
+        cgroup_driver_from_systemd_flag(info_output)
+    };
+    ContainerRuntimeInfo {
+        runtime,
+        version: field("RuntimeVersion").trim_start_matches('v').to_string(),
+        api_version: field("RuntimeApiVersion"),
+        storage_driver,
+        cgroup_driver,
+    }
+}
+
+/// 解析 `containerd --version` 输出，例如
+/// `containerd github.com/containerd/containerd v1.7.2 0cae528dd6cb`
+fn parse_containerd_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .nth(2)
+        .map(|v| v.trim_start_matches('v').to_string())
+}
+
+/// 解析 `docker info --format json` 输出
+fn parse_docker_info(output: &str) -> Option<ContainerRuntimeInfo> {
+    let info: serde_json::Value = serde_json::from_str(output.trim()).ok()?;
+    let version = info.get("ServerVersion")?.as_str()?.to_string();
+
+    Some(ContainerRuntimeInfo {
+        runtime: "docker".to_string(),
+        version,
+        api_version: "unknown".to_string(),
+        storage_driver: info
+            .get("Driver")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        cgroup_driver: info
+            .get("CgroupDriver")
+            .and_then(|v| v.as_str())
+            .unwrap_or("cgroupfs")
+            .to_string(),
+    })
+}
+
+/// 解析 `crio --version` 输出（新版本为 `Version:` 行，旧版本为 `crio version x.y.z`）
+fn parse_crio_version(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("Version:") {
+            Some(rest.trim().to_string())
+        } else {
+            line.strip_prefix("crio version ").map(|rest| rest.trim().to_string())
+        }
+    })
+}
+
+/// 解析 `crio config` 输出中 `[crio.runtime]` 段的 `cgroup_manager = "systemd"`
+fn parse_crio_cgroup_manager(config: &str) -> Option<String> {
+    config.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "cgroup_manager")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|v| !v.is_empty())
+    })
+}
+
+/// 根据配置中的 `SystemdCgroup` 开关推断 cgroup 驱动
+fn cgroup_driver_from_systemd_flag(config: &str) -> String {
+    let compact: String = config.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.contains("SystemdCgroup=true") || compact.contains("\"SystemdCgroup\":true") {
+        "systemd".to_string()
+    } else {
+        "cgroupfs".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_docker_info() {
+        let output = r#"{"ID":"abc","Containers":3,"Driver":"overlay2","CgroupDriver":"systemd","CgroupVersion":"2","KernelVersion":"6.1.0","OperatingSystem":"Debian GNU/Linux 12","ServerVersion":"24.0.7"}"#;
+        let info = parse_docker_info(output).unwrap();
+        assert_eq!(info.runtime, "docker");
+        assert_eq!(info.version, "24.0.7");
+        assert_eq!(info.storage_driver.as_deref(), Some("overlay2"));
+        assert_eq!(info.cgroup_driver, "systemd");
+    }
+
+    #[test]
+    fn test_parse_docker_info_invalid() {
+        assert!(parse_docker_info("Cannot connect to the Docker daemon").is_none());
+    }
+
+    #[test]
+    fn test_parse_crictl_output() {
+        let version = "Version:  0.1.0\nRuntimeName:  containerd\nRuntimeVersion:  v1.7.2\nRuntimeApiVersion:  v1\n";
+        let info = r#"{"config":{"containerd":{"snapshotter":"overlayfs","runtimes":{"runc":{"options":{"SystemdCgroup":true}}}}}}"#;
+        let parsed = parse_crictl_output(version, info);
+        assert_eq!(parsed.runtime, "containerd");
+        assert_eq!(parsed.version, "1.7.2");
+        assert_eq!(parsed.api_version, "v1");
+        assert_eq!(parsed.storage_driver.as_deref(), Some("overlayfs"));
+        assert_eq!(parsed.cgroup_driver, "systemd");
+    }
+
+    #[test]
+    fn test_parse_crictl_output_for_crio() {
+        let version = "Version:  0.1.0\nRuntimeName:  cri-o\nRuntimeVersion:  1.29.1\nRuntimeApiVersion:  v1\n";
+        let info = r#"{"status":{"conditions":[]},"config":{"sandboxImage":"registry.k8s.io/pause:3.9"}}"#;
+        let parsed = parse_crictl_output(version, info);
+        assert_eq!(parsed.runtime, "cri-o");
+        assert_eq!(parsed.cgroup_driver, "unknown");
+
+        let config = "[crio.runtime]\n# cgroup_manager = \"cgroupfs\"\ncgroup_manager = \"systemd\"\nconmon_cgroup = \"pod\"\n";
+        assert_eq!(parse_crio_cgroup_manager(config).as_deref(), Some("systemd"));
+        assert_eq!(parse_crio_cgroup_manager(""), None);
+    }
+
+    #[test]
+    fn test_parse_containerd_and_crio_version() {
+        assert_eq!(
+            parse_containerd_version("containerd github.com/containerd/containerd v1.6.21 3dce8eb").as_deref(),
+            Some("1.6.21")
+        );
+        assert_eq!(parse_crio_version("crio version 1.28.1\n").as_deref(), Some("1.28.1"));
+        assert_eq!(parse_crio_version("Version:  1.29.0\nGitCommit: abc\n").as_deref(), Some("1.29.0"));
+    }
+}
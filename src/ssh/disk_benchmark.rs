@@ -0,0 +1,190 @@
+use crate::error::AnsibleError;
+use crate::types::DiskBenchmark;
+use crate::utils::shell_quote;
+use super::{RemoteTempFile, SshClient};
+use tracing::info;
+
+/// IOPS 测试使用的 4K 块数量
+const IOPS_BLOCKS: u64 = 1000;
+
+/// `dd` 一次拷贝的统计（来自 stderr 的 "copied" 行）
+#[derive(Debug, Clone, PartialEq)]
+struct DdStats {
+    bytes: u64,
+    seconds: f64,
+    mbps: f64,
+}
+
+impl SshClient {
+    /// 磁盘 I/O 基准测试
+    ///
+    /// 在 `test_path` 下写入 `size_mb` MB 的测试文件测量顺序读写吞吐，
+    /// 再以 4K 块同步写 / 直接读测量 IOPS 与写延迟。测试文件在结束后删除。
+    pub fn benchmark_disk_io(&self, test_path: &str, size_mb: u64) -> Result<DiskBenchmark, AnsibleError> {
+        if size_mb == 0 {
+            return Err(AnsibleError::ValidationError("Benchmark size must be greater than 0 MB".to_string()));
+        }
+
+        let test_file = format!("{}/rs_ansible_disk_benchmark_{}", test_path.trim_end_matches('/'), rand::random::<u32>());
+        let file = RemoteTempFile::new(self, test_file.clone());
+        let iops_file = RemoteTempFile::new(self, format!("{}.iops", test_file));
+        let quoted = shell_quote(&test_file);
+        info!("Benchmarking disk I/O on {}:{} ({} MB)", self.config.hostname, test_path, size_mb);
+
+        // 顺序写
+        let write = self.run_dd(&format!(
+            "dd if=/dev/zero of={} bs=1M count={} conv=fdatasync",
+            quoted, size_mb
+        ))?;
+
+        // 顺序读（优先绕过页缓存）
+        let read = self.run_dd(&format!(
+            "dd if={0} of=/dev/null bs=1M iflag=direct || dd if={0} of=/dev/null bs=1M",
+            quoted
+        ))?;
+
+        // 4K 同步写：每块一次落盘，用于计算 IOPS 与延迟
+        let write_iops = self.run_dd(&format!(
+            "dd if=/dev/zero of={} bs=4k count={} oflag=dsync",
+            shell_quote(iops_file.path()),
+            IOPS_BLOCKS
+        ))?;
+
+        // 4K 直接读（块数不超过测试文件大小）
+        let blocks = IOPS_BLOCKS.min(size_mb * 256);
+        let read_iops = self.run_dd(&format!(
+            "dd if={0} of=/dev/null bs=4k count={1} iflag=direct || dd if={0} of=/dev/null bs=4k count={1}",
+            quoted, blocks
+        ))?;
+
+        iops_file.discard();
+        file.discard();
+
+        let benchmark = DiskBenchmark {
+            write_mbps: write.mbps,
+            read_mbps: read.mbps,
+            iops_write: per_second(IOPS_BLOCKS, write_iops.seconds),
+            iops_read: per_second(blocks, read_iops.seconds),
+            latency_ms: write_iops.seconds * 1000.0 / IOPS_BLOCKS as f64,
+            test_path: test_path.to_string(),
+        };
+        info!(
+            "Disk benchmark on {}: write {:.1} MB/s, read {:.1} MB/s, {} write IOPS, {} read IOPS",
+            self.config.hostname, benchmark.write_mbps, benchmark.read_mbps, benchmark.iops_write, benchmark.iops_read
+        );
+        Ok(benchmark)
+    }
+
+    /// 执行 dd 命令并解析其 stderr 中的统计行
+    fn run_dd(&self, command: &str) -> Result<DdStats, AnsibleError> {
+        let result = self.execute_command(&format!("LC_ALL=C sh -c {}", shell_quote(command)))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "dd failed (exit {}): {}",
+                result.exit_code,
+                result.stderr.trim()
+            )));
+        }
+
+        parse_dd_output(&result.stderr).ok_or_else(|| {
+            AnsibleError::CommandError(format!("Unable to parse dd output: {}", result.stderr.trim()))
+        })
+    }
+}
+
+fn per_second(count: u64, seconds: f64) -> u64 {
+    if seconds > 0.0 {
+        (count as f64 / seconds).round() as u64
+    } else {
+        0
+    }
+}
+
+/// 解析 dd 的统计输出，兼容 GNU coreutils 与 BusyBox 格式，例如：
+///
+/// `104857600 bytes (105 MB, 100 MiB) copied, 0.5 s, 210 MB/s`
+/// `104857600 bytes (100.0MB) copied, 0.512 seconds, 195.3MB/s`
+///
+/// 存在多行时（例如回退重试）取最后一行。
+fn parse_dd_output(stderr: &str) -> Option<DdStats> {
+    let line = stderr.lines().rev().find(|l| l.contains(" copied"))?;
+    let bytes: u64 = line.split_whitespace().next()?.parse().ok()?;
+
+    let (_, after) = line.split_once(" copied,")?;
+    let mut parts = after.split(',').map(str::trim);
+    let seconds = leading_number(parts.next()?)?;
+    let reported = parts.next().and_then(parse_rate);
+
+    let mbps = if seconds > 0.0 {
+        bytes as f64 / seconds / 1_000_000.0
+    } else {
+        reported?
+    };
+    Some(DdStats { bytes, seconds, mbps })
+}
+
+/// 将 "210 MB/s"、"1.2 GB/s"、"512kB/s" 等速率转换为 MB/s
+fn parse_rate(rate: &str) -> Option<f64> {
+    let value = leading_number(rate)?;
+    let unit = rate.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.').trim();
+    let factor = match unit {
+        "B/s" => 1e-6,
+        "kB/s" | "KB/s" => 1e-3,
+        "KiB/s" => 1024.0 / 1e6,
+        "MB/s" => 1.0,
+        "MiB/s" => 1_048_576.0 / 1e6,
+        "GB/s" => 1e3,
+        "GiB/s" => 1_073_741_824.0 / 1e6,
+        "TB/s" => 1e6,
+        _ => return None,
+    };
+    Some(value * factor)
+}
+
+fn leading_number(s: &str) -> Option<f64> {
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    s[..end].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gnu_dd_output() {
+        let stderr = "100+0 records in\n100+0 records out\n104857600 bytes (105 MB, 100 MiB) copied, 0.5 s, 210 MB/s\n";
+        let stats = parse_dd_output(stderr).unwrap();
+        assert_eq!(stats.bytes, 104_857_600);
+        assert_eq!(stats.seconds, 0.5);
+        assert!((stats.mbps - 209.7152).abs() < 1e-6);
+
+        let old = "1048576000 bytes (1.0 GB) copied, 2.1 s, 499 MB/s";
+        assert_eq!(parse_dd_output(old).unwrap().bytes, 1_048_576_000);
+    }
+
+    #[test]
+    fn test_parse_busybox_and_fallback_output() {
+        let busybox = "104857600 bytes (100.0MB) copied, 0.512 seconds, 195.3MB/s";
+        let stats = parse_dd_output(busybox).unwrap();
+        assert_eq!(stats.seconds, 0.512);
+        assert!((stats.mbps - 204.8).abs() < 1e-6);
+
+        // iflag=direct 失败后回退，取最后一行
+        let fallback = "dd: failed to open 'f': Invalid argument\n4096000 bytes (4.1 MB, 3.9 MiB) copied, 0 s, 1.2 GB/s";
+        let stats = parse_dd_output(fallback).unwrap();
+        assert_eq!(stats.bytes, 4_096_000);
+        assert!((stats.mbps - 1200.0).abs() < 1e-6);
+
+        assert!(parse_dd_output("dd: error writing 'f': No space left on device").is_none());
+    }
+
+    #[test]
+    fn test_parse_rate_units() {
+        assert_eq!(parse_rate("512 kB/s"), Some(0.512));
+        assert_eq!(parse_rate("3 MB/s"), Some(3.0));
+        assert_eq!(parse_rate("1.5 GB/s"), Some(1500.0));
+        assert_eq!(parse_rate("fast"), None);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::error::AnsibleError;
+use crate::types::DmesgEntry;
+use super::SshClient;
+use regex::Regex;
+use serde::Deserialize;
+use tracing::info;
+
+/// syslog 严重级别名称（下标即级别，0 最严重）
+const SEVERITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warn", "notice", "info", "debug"];
+
+/// syslog facility 名称（下标即 facility 编号）
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp",
+    "ntp", "security", "console", "solaris-cron", "local0", "local1", "local2", "local3", "local4", "local5",
+    "local6", "local7",
+];
+
+/// 优先使用 JSON 输出，旧版 util-linux 回退到带优先级前缀的原始文本
+const DMESG_CMD: &str = "dmesg -J 2>/dev/null || dmesg -r";
+
+#[derive(Deserialize)]
+struct DmesgJson {
+    dmesg: Vec<DmesgJsonEntry>,
+}
+
+#[derive(Deserialize)]
+struct DmesgJsonEntry {
+    #[serde(default)]
+    pri: Option<u32>,
+    #[serde(default)]
+    time: f64,
+    #[serde(default)]
+    msg: String,
+}
+
+impl SshClient {
+    /// 读取内核日志，`since_boot_secs` 指定时只返回开机该秒数之后的记录
+    ///
+    /// 启用了 `kernel.dmesg_restrict` 的主机需要 become 权限。
+    pub fn get_dmesg(&self, since_boot_secs: Option<u64>) -> Result<Vec<DmesgEntry>, AnsibleError> {
+        let result = self.execute_command(DMESG_CMD)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!("dmesg failed: {}", result.stderr.trim())));
+        }
+
+        let mut entries = parse_dmesg_output(&result.stdout)?;
+        if let Some(since) = since_boot_secs {
+            entries.retain(|e| e.timestamp_secs >= since as f64);
+        }
+        info!("Read {} kernel log entries from {}", entries.len(), self.config.hostname);
+        Ok(entries)
+    }
+
+    /// 按正则表达式与严重级别搜索内核日志
+    ///
+    /// `severity_level` 为 syslog 级别（0=emerg … 7=debug），只返回不低于该严重程度的记录。
+    pub fn search_dmesg(&self, pattern: &str, severity_level: Option<u8>) -> Result<Vec<DmesgEntry>, AnsibleError> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| AnsibleError::ValidationError(format!("Invalid dmesg pattern '{}': {}", pattern, e)))?;
+        let entries = self.get_dmesg(None)?;
+        Ok(filter_entries(entries, &regex, severity_level))
+    }
+}
+
+fn filter_entries(entries: Vec<DmesgEntry>, regex: &Regex, severity_level: Option<u8>) -> Vec<DmesgEntry> {
+    entries
+        .into_iter()
+        .filter(|e| regex.is_match(&e.message))
+        .filter(|e| match severity_level {
+            Some(max) => severity_rank(&e.severity).is_some_and(|level| level <= max),
+            None => true,
+        })
+        .collect()
+}
+
+/// 严重级别名称对应的数值
+fn severity_rank(severity: &str) -> Option<u8> {
+    SEVERITIES.iter().position(|s| *s == severity).map(|p| p as u8)
+}
+
+/// 将 syslog 优先级拆分为 (facility, severity)
+fn decode_priority(pri: u32) -> (String, String) {
+    let facility = FACILITIES
+        .get((pri >> 3) as usize)
+        .map(|f| f.to_string())
+        .unwrap_or_else(|| format!("facility{}", pri >> 3));
+    (facility, SEVERITIES[(pri & 7) as usize].to_string())
+}
+
+/// 解析 `dmesg -J` 的 JSON 输出或 `dmesg -r` 的文本输出
+fn parse_dmesg_output(output: &str) -> Result<Vec<DmesgEntry>, AnsibleError> {
+    let trimmed = output.trim_start();
+    if trimmed.starts_with('{') {
+        let parsed: DmesgJson = serde_json::from_str(trimmed)
+            .map_err(|e| AnsibleError::CommandError(format!("Failed to parse dmesg JSON: {}", e)))?;
+        return Ok(parsed
+            .dmesg
+            .into_iter()
+            .map(|e| {
+                // 没有优先级信息时按 kern.info 处理
+                let (facility, severity) = decode_priority(e.pri.unwrap_or(6));
+                DmesgEntry {
+                    timestamp_secs: e.time,
+                    facility,
+                    severity,
+                    message: e.msg,
+                }
+            })
+            .collect());
+    }
+
+    Ok(output.lines().filter_map(parse_dmesg_line).collect())
+}
+
+/// 解析 `<6>[    1.234567] message` 形式的一行（优先级前缀可选）
+fn parse_dmesg_line(line: &str) -> Option<DmesgEntry> {
+    let (pri, rest) = match line.strip_prefix('<').and_then(|l| l.split_once('>')) {
+        Some((pri, rest)) => (pri.parse().ok()?, rest),
+        None => (6, line),
+    };
+    let rest = rest.trim_start().strip_prefix('[')?;
+    let (time, message) = rest.split_once(']')?;
+    let (facility, severity) = decode_priority(pri);
+    Some(DmesgEntry {
+        timestamp_secs: time.trim().parse().ok()?,
+        facility,
+        severity,
+        message: message.trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DMESG_JSON: &str = r#"{
+   "dmesg": [
+      {"pri": 5, "time":     0.000000, "msg": "Linux version 6.1.0-18-amd64"},
+      {"pri": 6, "time":     1.204512, "msg": "EXT4-fs (sda1): mounted filesystem"},
+      {"pri": 3, "time":    42.500100, "msg": "EXT4-fs error (device sdb1): ext4_find_entry:1455: inode #2: comm ls: reading directory lblock 0"},
+      {"pri": 12, "time":   50.000000, "msg": "systemd[1]: Started Journal Service."},
+      {"pri": 2, "time":   61.000001, "msg": "mce: [Hardware Error]: CPU 0: Machine Check Exception"}
+   ]
+}"#;
+
+    #[test]
+    fn test_parse_dmesg_json() {
+        let entries = parse_dmesg_output(DMESG_JSON).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(
+            entries[2],
+            DmesgEntry {
+                timestamp_secs: 42.5001,
+                facility: "kern".to_string(),
+                severity: "err".to_string(),
+                message: "EXT4-fs error (device sdb1): ext4_find_entry:1455: inode #2: comm ls: reading directory lblock 0"
+                    .to_string(),
+            }
+        );
+        assert_eq!(entries[3].facility, "user");
+        assert_eq!(entries[3].severity, "warn");
+    }
+
+    #[test]
+    fn test_filter_by_severity_level() {
+        let entries = parse_dmesg_output(DMESG_JSON).unwrap();
+        let any = Regex::new(".").unwrap();
+
+        // err(3) 及更严重
+        let errors = filter_entries(entries.clone(), &any, Some(3));
+        let severities: Vec<&str> = errors.iter().map(|e| e.severity.as_str()).collect();
+        assert_eq!(severities, vec!["err", "crit"]);
+
+        let ext4 = filter_entries(entries.clone(), &Regex::new("EXT4-fs").unwrap(), None);
+        assert_eq!(ext4.len(), 2);
+        let ext4_errors = filter_entries(entries, &Regex::new("EXT4-fs").unwrap(), Some(4));
+        assert_eq!(ext4_errors.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_dmesg_raw_text() {
+        let output = "<6>[    0.000000] Linux version 6.1.0\n<3>[   12.345678] usb 1-1: device descriptor read/64, error -71\n[   13.000000] no prefix\ngarbage\n";
+        let entries = parse_dmesg_output(output).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].severity, "err");
+        assert_eq!(entries[1].timestamp_secs, 12.345678);
+        assert_eq!(entries[1].message, "usb 1-1: device descriptor read/64, error -71");
+        assert_eq!(entries[2].severity, "info");
+    }
+}
@@ -0,0 +1,156 @@
+use crate::error::AnsibleError;
+use crate::types::DnsConfig;
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use super::SshClient;
+use tracing::{debug, error, info};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+impl SshClient {
+    /// 读取并解析远程主机的 /etc/resolv.conf
+    pub fn get_dns_config(&self) -> Result<DnsConfig, AnsibleError> {
+        let result = self.execute_command(&format!("cat {}", RESOLV_CONF))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read {}: {}",
+                RESOLV_CONF, result.stderr
+            )));
+        }
+        Ok(parse_resolv_conf(&result.stdout))
+    }
+
+    /// 设置 nameserver 与 search 域（保留现有 options），返回是否发生了变更
+    pub fn set_nameservers(&self, nameservers: &[&str], search_domains: &[&str]) -> Result<bool, AnsibleError> {
+        let current = self.get_dns_config()?;
+        let desired = DnsConfig {
+            nameservers: nameservers.iter().map(|s| s.to_string()).collect(),
+            search_domains: search_domains.iter().map(|s| s.to_string()).collect(),
+            options: current.options.clone(),
+        };
+        self.apply_dns_config(&current, &desired)
+    }
+
+    /// 将 /etc/resolv.conf 设置为指定配置，返回是否发生了变更
+    pub fn set_dns_config(&self, config: &DnsConfig) -> Result<bool, AnsibleError> {
+        let current = self.get_dns_config()?;
+        self.apply_dns_config(&current, config)
+    }
+
+    fn apply_dns_config(&self, current: &DnsConfig, desired: &DnsConfig) -> Result<bool, AnsibleError> {
+        if current == desired {
+            debug!("DNS configuration already up to date");
+            return Ok(false);
+        }
+
+        if desired.nameservers.is_empty() {
+            return Err(AnsibleError::ValidationError(
+                "At least one nameserver is required".to_string(),
+            ));
+        }
+
+        // 先写入同目录下的临时文件，再通过 mv 原子替换
+        let content = render_resolv_conf(desired);
+        let temp_path = generate_remote_temp_path(RESOLV_CONF);
+        let cmd = format!(
+            "printf '%s' {} > '{}' && chmod 644 '{}' && mv -f '{}' {}",
+            shell_quote(&content),
+            temp_path,
+            temp_path,
+            temp_path,
+            RESOLV_CONF
+        );
+        let result = self.execute_command(&cmd)?;
+        if result.exit_code != 0 {
+            let _ = self.execute_command(&format!("rm -f '{}'", temp_path));
+            error!("Failed to write {}: {}", RESOLV_CONF, result.stderr);
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to write {}: {}",
+                RESOLV_CONF, result.stderr
+            )));
+        }
+
+        // 回读验证
+        let written = self.get_dns_config()?;
+        if &written != desired {
+            return Err(AnsibleError::ValidationError(format!(
+                "DNS configuration verification failed, expected {:?}, found {:?}",
+                desired, written
+            )));
+        }
+
+        info!("Updated {} with nameservers: {}", RESOLV_CONF, desired.nameservers.join(", "));
+        Ok(true)
+    }
+}
+
+/// 解析 resolv.conf 内容
+fn parse_resolv_conf(content: &str) -> DnsConfig {
+    let mut config = DnsConfig::default();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("nameserver") => config.nameservers.extend(parts.next().map(|s| s.to_string())),
+            // search 与 domain 互斥，以最后出现的为准
+            Some("search") | Some("domain") => {
+                config.search_domains = parts.map(|s| s.to_string()).collect();
+            }
+            Some("options") => config.options.extend(parts.map(|s| s.to_string())),
+            _ => {}
+        }
+    }
+
+    config
+}
+
+/// 将配置渲染为 resolv.conf 内容
+fn render_resolv_conf(config: &DnsConfig) -> String {
+    let mut content = String::from("# Managed by rs-ansible\n");
+    if !config.search_domains.is_empty() {
+        content.push_str(&format!("search {}\n", config.search_domains.join(" ")));
+    }
+    for nameserver in &config.nameservers {
+        content.push_str(&format!("nameserver {}\n", nameserver));
+    }
+    if !config.options.is_empty() {
+        content.push_str(&format!("options {}\n", config.options.join(" ")));
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESOLV_CONF_SAMPLE: &str = "\
+# This is /run/systemd/resolve/stub-resolv.conf managed by man:systemd-resolved(8).
+# Do not edit.
+nameserver 10.0.0.2
+nameserver 8.8.8.8
+; legacy comment
+search corp.example.com example.com
+options edns0 trust-ad ndots:2
+";
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let config = parse_resolv_conf(RESOLV_CONF_SAMPLE);
+        assert_eq!(config.nameservers, vec!["10.0.0.2", "8.8.8.8"]);
+        assert_eq!(config.search_domains, vec!["corp.example.com", "example.com"]);
+        assert_eq!(config.options, vec!["edns0", "trust-ad", "ndots:2"]);
+    }
+
+    #[test]
+    fn test_resolv_conf_round_trip() {
+        let config = parse_resolv_conf(RESOLV_CONF_SAMPLE);
+        let rendered = render_resolv_conf(&config);
+        assert!(rendered.contains("nameserver 10.0.0.2\nnameserver 8.8.8.8\n"));
+        assert!(rendered.contains("search corp.example.com example.com\n"));
+        assert_eq!(parse_resolv_conf(&rendered), config);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::credentials::SecretString;
+use crate::error::AnsibleError;
+use crate::types::{CommandOptions, CommandResult, DockerConnection, HostConfig};
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use super::local::LocalTransport;
+use super::transport::{Ssh2Transport, Transport};
+use std::io::prelude::*;
+use tracing::{info, warn};
+
+/// 在容器中执行的传输实现（`transport: docker`）
+///
+/// 命令通过 `docker exec -i <容器> sh -c` 执行；文件先暂存到容器宿主机的临时目录，
+/// 再用 `docker cp` 复制进出容器。容器命令在控制端本机调用，
+/// 或在 `via_ssh` 时通过 SSH 连接 `hostname` 所指的宿主机调用（宿主机用户需有权限使用 docker）。
+///
+/// become 仍通过容器内的 sudo 实现，精简镜像中通常没有 sudo，容器默认以 root 运行时无需开启。
+pub struct DockerTransport {
+    host: Box<dyn Transport>,
+    engine: &'static str,
+    container: String,
+    staging_dir: String,
+}
+
+impl Transport for DockerTransport {
+    fn connect(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        let docker = config.docker.as_ref().ok_or_else(|| {
+            AnsibleError::ValidationError(format!(
+                "Host {} uses the docker transport but has no docker settings",
+                config.hostname
+            ))
+        })?;
+        let (host, staging_dir): (Box<dyn Transport>, String) = if docker.via_ssh {
+            (Box::new(Ssh2Transport::connect(config, secret)?), "/tmp".to_string())
+        } else {
+            let dir = std::env::temp_dir().to_string_lossy().trim_end_matches('/').to_string();
+            (Box::new(LocalTransport::connect(config, secret)?), dir)
+        };
+
+        let transport = Self::with_host_transport(host, docker, &staging_dir);
+        transport.check_running()?;
+        info!("Connected to container {} via {}", docker.container, transport.engine);
+        Ok(transport)
+    }
+
+    fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        let wrapped = format!(
+            "{} exec -i {} sh -c {}",
+            self.engine,
+            shell_quote(&self.container),
+            shell_quote(command)
+        );
+        self.host.exec(&wrapped, options)
+    }
+
+    fn upload(
+        &self,
+        reader: &mut dyn Read,
+        size: u64,
+        remote_path: &str,
+        mode: i32,
+    ) -> Result<u64, AnsibleError> {
+        let staging = self.staging_path();
+        let result = self.host.upload(reader, size, &staging, 0o600).and_then(|bytes| {
+            self.host_command(&format!(
+                "{engine} cp {} {}:{} && {engine} exec {} chmod {:o} {}",
+                shell_quote(&staging),
+                shell_quote(&self.container),
+                shell_quote(remote_path),
+                shell_quote(&self.container),
+                mode & 0o7777,
+                shell_quote(remote_path),
+                engine = self.engine
+            ))?;
+            Ok(bytes)
+        });
+        self.remove_staging(&staging);
+        result
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write) -> Result<u64, AnsibleError> {
+        let staging = self.staging_path();
+        let result = self
+            .host_command(&format!(
+                "{} cp {}:{} {}",
+                self.engine,
+                shell_quote(&self.container),
+                shell_quote(remote_path),
+                shell_quote(&staging)
+            ))
+            .and_then(|_| self.host.download(&staging, writer));
+        self.remove_staging(&staging);
+        result
+    }
+
+    fn disconnect(&self) {
+        self.host.disconnect();
+    }
+}
+
+impl DockerTransport {
+    /// 通过已建立的宿主机传输层访问容器（`staging_dir` 为宿主机上暂存文件的目录）
+    pub fn with_host_transport(host: Box<dyn Transport>, docker: &DockerConnection, staging_dir: &str) -> Self {
+        Self {
+            host,
+            engine: docker.engine.command(),
+            container: docker.container.clone(),
+            staging_dir: staging_dir.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// 确认容器存在且正在运行
+    fn check_running(&self) -> Result<(), AnsibleError> {
+        let result = self.host.exec(
+            &format!(
+                "{} inspect -f '{{{{.State.Running}}}}' {}",
+                self.engine,
+                shell_quote(&self.container)
+            ),
+            &CommandOptions::default(),
+        )?;
+        if result.exit_code != 0 || result.stdout.trim() != "true" {
+            let reason = if result.exit_code != 0 { result.stderr.trim() } else { "container is not running" };
+            return Err(AnsibleError::SshConnectionError(format!(
+                "Container {} is not available: {}",
+                self.container, reason
+            )));
+        }
+        Ok(())
+    }
+
+    fn staging_path(&self) -> String {
+        generate_remote_temp_path(&format!("{}/rs_ansible_docker", self.staging_dir))
+    }
+
+    /// 在宿主机上执行命令，非零退出码视为失败
+    fn host_command(&self, command: &str) -> Result<CommandResult, AnsibleError> {
+        let result = self.host.exec(command, &CommandOptions::default())?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Container file transfer failed: {}",
+                result.stderr.trim()
+            )));
+        }
+        Ok(result)
+    }
+
+    fn remove_staging(&self, staging: &str) {
+        let removed = self.host.exec(&format!("rm -f {}", shell_quote(staging)), &CommandOptions::default());
+        if !matches!(removed, Ok(ref r) if r.exit_code == 0) {
+            warn!("Failed to remove staging file {} on container host", staging);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTransport;
+    use crate::types::ContainerEngine;
+
+    #[test]
+    fn test_docker_transport_wraps_host_commands() {
+        let mock = MockTransport::new();
+        let host = mock.factory()("dockerhost", &HostConfig::default()).unwrap();
+        let docker = DockerConnection {
+            container: "web 1".to_string(),
+            engine: ContainerEngine::Podman,
+            via_ssh: true,
+        };
+        let transport = DockerTransport::with_host_transport(host, &docker, "/tmp/");
+
+        transport.exec("echo 'hi'", &CommandOptions::default()).unwrap();
+        let size = transport.upload(&mut &b"data"[..], 4, "/etc/app.conf", 0o100644).unwrap();
+        assert_eq!(size, 4);
+
+        let commands = mock.commands("dockerhost");
+        assert_eq!(commands[0], "podman exec -i 'web 1' sh -c 'echo '\\''hi'\\'''");
+        let staging = commands[1].split('\'').nth(1).unwrap().to_string();
+        assert!(staging.starts_with("/tmp/rs_ansible_docker.tmp."));
+        assert_eq!(
+            commands[1],
+            format!(
+                "podman cp '{}' 'web 1':'/etc/app.conf' && podman exec 'web 1' chmod 644 '/etc/app.conf'",
+                staging
+            )
+        );
+        // 暂存文件在复制进容器后删除
+        assert_eq!(commands[2], format!("rm -f '{}'", staging));
+        assert!(mock.file("dockerhost", &staging).is_none());
+    }
+}
@@ -1,9 +1,76 @@
+use crate::bandwidth::{BandwidthLimiter, ThrottledReader};
+use crate::diff::{unified_diff, MAX_DIFF_SOURCE_BYTES};
 use crate::error::AnsibleError;
+use crate::metrics;
 use crate::ssh::client::SshClient;
-use crate::types::{FileCopyOptions, FileTransferResult};
-use crate::utils::generate_remote_temp_path;
-use std::path::Path;
-use tracing::info;
+use crate::ssh::temp_file::RemoteTempFile;
+use crate::types::{
+    DirectoryManifest, FileCopyOptions, FileHashInfo, FileTransferResult, ManifestAction, ManifestEntry, PermissionsOptions,
+    TransferProgress,
+};
+use crate::utils::{calculate_file_hash, generate_remote_temp_path, shell_quote, FileMode};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+/// 上传进度回调的触发间隔（字节）
+pub const REPORT_INTERVAL_BYTES: u64 = 64 * 1024;
+
+/// 统计已读取字节数并按间隔回调进度的 Reader
+struct ProgressReader<R, F> {
+    inner: R,
+    on_progress: F,
+    total_bytes: u64,
+    bytes_sent: u64,
+    next_report: u64,
+    started: Instant,
+}
+
+impl<R: Read, F: Fn(TransferProgress)> ProgressReader<R, F> {
+    fn new(inner: R, total_bytes: u64, on_progress: F) -> Self {
+        Self {
+            inner,
+            on_progress,
+            total_bytes,
+            bytes_sent: 0,
+            next_report: REPORT_INTERVAL_BYTES,
+            started: Instant::now(),
+        }
+    }
+
+    fn report(&self) {
+        let elapsed_secs = self.started.elapsed().as_secs_f64();
+        let estimated_remaining_secs = (self.bytes_sent > 0 && elapsed_secs > 0.0).then(|| {
+            let rate = self.bytes_sent as f64 / elapsed_secs;
+            self.total_bytes.saturating_sub(self.bytes_sent) as f64 / rate
+        });
+        (self.on_progress)(TransferProgress {
+            bytes_sent: self.bytes_sent,
+            total_bytes: self.total_bytes,
+            elapsed_secs,
+            estimated_remaining_secs,
+        });
+    }
+}
+
+impl<R: Read, F: Fn(TransferProgress)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_sent += n as u64;
+        // 每跨过一个间隔回调一次，读取结束时补发最终进度
+        if self.bytes_sent >= self.next_report || (n == 0 && self.next_report != u64::MAX) {
+            self.report();
+            self.next_report = if n == 0 {
+                u64::MAX
+            } else {
+                (self.bytes_sent / REPORT_INTERVAL_BYTES + 1) * REPORT_INTERVAL_BYTES
+            };
+        }
+        Ok(n)
+    }
+}
 
 impl SshClient {
     /// 复制文件到远程主机（使用默认选项）
@@ -22,6 +89,139 @@ impl SshClient {
         remote_path: &str,
         options: &FileCopyOptions,
     ) -> Result<FileTransferResult, AnsibleError> {
+        self.copy_file_to_remote_with_progress(local_path, remote_path, options, |_| {})
+    }
+
+    /// 复制文件到远程主机，传输过程中每 64 KiB 回调一次进度（远程文件未变化时不回调）
+    pub fn copy_file_to_remote_with_progress<F>(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+        options: &FileCopyOptions,
+        on_progress: F,
+    ) -> Result<FileTransferResult, AnsibleError>
+    where
+        F: Fn(TransferProgress) + Send,
+    {
+        self.upload_file(local_path, remote_path, options, on_progress)
+            .map(|(result, _)| result)
+    }
+
+    /// 复制目录树到远程主机，逐个文件调用单文件传输，并返回每个文件的处理结果
+    ///
+    /// 远程文件路径为 `remote_dir` 加上相对路径。符号链接默认在远程按原目标重建（`ln -s`，
+    /// 不应用 mode/owner）；`follow_symlinks` 为 true 时改为复制链接目标的内容，
+    /// 指向祖先目录的链接（循环）会被跳过。
+    /// 任一文件失败时立即返回错误，此前已复制的文件保留在远程主机上。
+    pub fn copy_dir_with_manifest(
+        &self,
+        local_dir: &str,
+        remote_dir: &str,
+        options: &FileCopyOptions,
+    ) -> Result<DirectoryManifest, AnsibleError> {
+        let mut entries = Vec::new();
+        collect_entries(Path::new(local_dir), Path::new(""), options.follow_symlinks, &mut Vec::new(), &mut entries)?;
+        entries.sort_by(|a, b| a.path().cmp(b.path()));
+
+        let remote_root = remote_dir.trim_end_matches('/');
+        let mut manifest = DirectoryManifest::default();
+        for entry in entries {
+            let remote_path = format!("{}/{}", remote_root, entry.path());
+            match entry {
+                LocalEntry::Symlink { path, target } => {
+                    let action = self.ensure_symlink(&remote_path, &target, options.create_dirs)?;
+                    debug!("{} -> {} (symlink): {:?}", target, remote_path, action);
+                    manifest.files.push(ManifestEntry {
+                        path,
+                        size: 0,
+                        hash: String::new(),
+                        action,
+                        link_target: Some(target),
+                    });
+                }
+                LocalEntry::File(relative) => {
+                    let local_path = Path::new(local_dir).join(&relative);
+                    let local_path = local_path.to_string_lossy();
+
+                    let local_hash = self.calculate_local_file_hash(&local_path, "sha256")?;
+                    let file_options = FileCopyOptions {
+                        precomputed_hash: Some(local_hash.hash.clone()),
+                        ..options.clone()
+                    };
+                    let (_, action) = self.upload_file(&local_path, &remote_path, &file_options, |_| {})?;
+                    debug!("{} -> {}: {:?}", local_path, remote_path, action);
+                    manifest.files.push(ManifestEntry {
+                        path: relative,
+                        size: local_hash.size,
+                        hash: local_hash.hash,
+                        action,
+                        link_target: None,
+                    });
+                }
+            }
+        }
+
+        info!(
+            "Directory {} copied to {}: {} files, {} symlinks, {} changed",
+            local_dir,
+            remote_dir,
+            manifest.files.len() - manifest.symlinks().len(),
+            manifest.symlinks().len(),
+            manifest.changed_files().len()
+        );
+        Ok(manifest)
+    }
+
+    /// 确保远程路径是指向 `target` 的符号链接，返回对远程路径做了什么
+    ///
+    /// 远程已有同名普通文件时替换为链接；已有同名目录时报错，避免误删目录内容。
+    fn ensure_symlink(&self, remote_path: &str, target: &str, create_dirs: bool) -> Result<ManifestAction, AnsibleError> {
+        let state = self.execute_command(&symlink_state_command(remote_path))?;
+        let action = match state.exit_code {
+            0 if state.stdout.trim_end_matches('\n') == target => return Ok(ManifestAction::Skipped),
+            0 | 3 => ManifestAction::Updated,
+            4 => ManifestAction::Created,
+            2 => {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "Cannot create symlink {}: a directory already exists at that path",
+                    remote_path
+                )));
+            }
+            _ => {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "Failed to inspect {}: {}",
+                    remote_path,
+                    state.stderr.trim()
+                )));
+            }
+        };
+
+        let result = self.execute_command(&symlink_command(remote_path, target, create_dirs))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to create symlink {} -> {}: {}",
+                remote_path,
+                target,
+                result.stderr.trim()
+            )));
+        }
+        Ok(action)
+    }
+
+    /// 单文件传输的实际实现，同时返回对远程文件做了什么
+    fn upload_file<F>(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+        options: &FileCopyOptions,
+        on_progress: F,
+    ) -> Result<(FileTransferResult, ManifestAction), AnsibleError>
+    where
+        F: Fn(TransferProgress) + Send,
+    {
+        // 在任何远程操作之前校验权限格式
+        let mode = options.mode.as_deref().map(FileMode::parse).transpose()?;
+
         // 固定使用 SHA256 算法进行完整性验证
         let hash_algorithm = "sha256";
 
@@ -31,7 +231,7 @@ impl SshClient {
             let metadata = std::fs::metadata(local_path).map_err(|e| {
                 AnsibleError::FileOperationError(format!("Failed to get file metadata: {}", e))
             })?;
-            crate::types::FileHashInfo {
+            FileHashInfo {
                 algorithm: hash_algorithm.to_string(),
                 hash: hash.clone(),
                 size: metadata.len(),
@@ -48,7 +248,7 @@ impl SshClient {
 
         // ========== 第二次 Hash：检查远程文件（幂等性检查，总是执行） ==========
         info!("[2/3] Checking remote file for idempotency...");
-        match self.get_remote_file_hash(remote_path, hash_algorithm)? {
+        let (action, remote_size) = match self.get_remote_file_hash(remote_path, hash_algorithm)? {
             Some(remote_hash_info) => {
                 // 比较 hash 和大小
                 if remote_hash_info.hash == local_hash_info.hash
@@ -62,25 +262,38 @@ impl SshClient {
                     // 仍然需要更新权限和所有者（如果指定）
                     self.apply_file_attributes(remote_path, options)?;
 
-                    return Ok(FileTransferResult {
-                        success: true,
-                        bytes_transferred: 0,
-                        message: format!(
-                            "File unchanged (hash: {}), attributes updated",
-                            remote_hash_info.hash
-                        ),
-                    });
+                    return Ok((
+                        FileTransferResult {
+                            success: true,
+                            bytes_transferred: 0,
+                            message: format!(
+                                "File unchanged (hash: {}), attributes updated",
+                                remote_hash_info.hash
+                            ),
+                            diff: None,
+                        },
+                        ManifestAction::Skipped,
+                    ));
                 } else {
                     info!(
                         "File changed - Local: {}, Remote: {}, will transfer",
                         local_hash_info.hash, remote_hash_info.hash
                     );
+                    (ManifestAction::Updated, Some(remote_hash_info.size))
                 }
             }
             None => {
                 info!("Remote file {} does not exist, will transfer", remote_path);
+                (ManifestAction::Created, None)
             }
-        }
+        };
+
+        // 差异必须在覆盖远程文件之前计算
+        let diff = if options.diff {
+            Some(self.upload_diff(local_path, remote_path, local_hash_info.size, remote_size)?)
+        } else {
+            None
+        };
 
         // ========== 执行实际的文件传输（带原子性保证） ==========
         let local_file = std::fs::File::open(local_path).map_err(|e| {
@@ -97,30 +310,20 @@ impl SshClient {
         let file_size = metadata.len();
 
         // 创建目录（如果需要）
-        if options.create_dirs
-            && let Some(parent_dir) = Path::new(remote_path).parent() {
-                let parent_str = parent_dir.to_string_lossy();
-                if !parent_str.is_empty() && parent_str != "/" {
-                    let mkdir_cmd = format!("mkdir -p '{}'", parent_str);
-                    let mkdir_result = self.execute_command(&mkdir_cmd)?;
-                    if mkdir_result.exit_code != 0 {
-                        return Err(AnsibleError::FileOperationError(format!(
-                            "Failed to create directory {}: {}",
-                            parent_str, mkdir_result.stderr
-                        )));
-                    }
-                }
+        if options.create_dirs && let Some(parent_str) = parent_dir_to_create(remote_path) {
+            let mkdir_cmd = format!("mkdir -p '{}'", parent_str);
+            let mkdir_result = self.execute_command(&mkdir_cmd)?;
+            if mkdir_result.exit_code != 0 {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "Failed to create directory {}: {}",
+                    parent_str, mkdir_result.stderr
+                )));
             }
+        }
 
         // 备份现有文件（如果需要）
         if options.backup {
-            // 在 Rust 端生成时间戳，避免 shell 命令中的 $() 被当作字面字符串
-            let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-            let backup_cmd = format!(
-                "[ -f '{}' ] && cp '{}' '{}.bak.{}' || true",
-                remote_path, remote_path, remote_path, timestamp
-            );
-            let backup_result = self.execute_command(&backup_cmd)?;
+            let backup_result = self.execute_command(&backup_command(remote_path))?;
             if backup_result.exit_code != 0 {
                 info!(
                     "Backup command failed (file may not exist): {}",
@@ -129,131 +332,112 @@ impl SshClient {
             }
         }
 
-        // 使用临时文件进行原子性传输（使用统一的工具函数生成唯一后缀）
-        let temp_remote_path = generate_remote_temp_path(remote_path);
+        // 原子模式下先写入目标目录中的临时文件（使用统一的工具函数生成唯一后缀），校验后 mv 到位，
+        // mv 不跨文件系统；守卫保证任何失败路径上都会清理远程临时文件。
+        // 非原子模式直接写入目标文件，用于不支持 rename 的文件系统（例如部分 FUSE 挂载）
+        let temp_remote = options.atomic.then(|| {
+            RemoteTempFile::new(self, generate_remote_temp_path(remote_path))
+                .keep_on_failure(options.keep_temp_on_failure)
+        });
+        let upload_path = temp_remote.as_ref().map_or(remote_path, |t| t.path()).to_string();
 
-        let initial_mode = if let Some(ref mode) = options.mode {
-            u32::from_str_radix(mode, 8).unwrap_or(0o644)
-        } else {
-            0o644
-        };
+        let initial_mode = mode.as_ref().map_or(0o644, FileMode::initial_mode);
 
         info!(
-            "Transferring file to temporary location: {}",
-            temp_remote_path
+            "Transferring file to {}: {}",
+            if temp_remote.is_some() { "temporary location" } else { "destination" },
+            upload_path
         );
-        let mut remote_file = self.session.scp_send(
-            Path::new(&temp_remote_path),
-            initial_mode as i32,
+        // 按主机限速与管理器的总带宽限制节流
+        let limiters = upload_limiters(options, self.bandwidth_limiter.as_ref());
+
+        let started = Instant::now();
+        let mut local_reader = ProgressReader::new(
+            ThrottledReader::new(std::io::BufReader::new(local_file), limiters),
             file_size,
-            None,
+            on_progress,
+        );
+        let bytes_transferred = self.transport.upload(
+            &mut local_reader,
+            file_size,
+            &upload_path,
+            initial_mode,
         )?;
+        self.record_transfer_channel(bytes_transferred);
+        let elapsed = started.elapsed();
+        metrics::record_bytes_transferred(&self.config.hostname, "upload", bytes_transferred);
 
-        let mut local_reader = std::io::BufReader::new(local_file);
-        let bytes_transferred =
-            std::io::copy(&mut local_reader, &mut remote_file).map_err(|e| {
-                AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e))
-            })?;
-
-        remote_file.send_eof()?;
-        remote_file.wait_eof()?;
-        remote_file.close()?;
-        remote_file.wait_close()?;
-
-        info!("File transferred: {} bytes", bytes_transferred);
+        info!(
+            "File transferred: {} bytes in {:.2}s ({:.0} B/s)",
+            bytes_transferred,
+            elapsed.as_secs_f64(),
+            bytes_transferred as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+        );
 
         // ========== 第三次 Hash：验证传输后的文件（总是执行，确保传输完整性） ==========
         info!("[3/3] Verifying file integrity after transfer (SHA256, forced)...");
-        match self.get_remote_file_hash(&temp_remote_path, hash_algorithm)? {
-            Some(remote_hash_info) => {
-                // 验证 hash
-                if remote_hash_info.hash != local_hash_info.hash {
-                    // Hash 不匹配，删除临时文件并报错
-                    let _ = self.execute_command(&format!("rm -f '{}'", temp_remote_path));
-                    return Err(AnsibleError::FileOperationError(format!(
-                        "File transfer verification FAILED! SHA256 hash mismatch detected.\n\
-                         Local hash:  {}\n\
-                         Local path: {} \n\
-                         Remote hash: {}\n\
-                         Remote path: {} \n\
-                         File may be corrupted during transfer: {}",
-                        local_hash_info.hash,
-                        local_path,
-                        remote_hash_info.hash,
-                        temp_remote_path,
-                        local_path
-                    )));
-                }
-
-                // 验证文件大小
-                if remote_hash_info.size != local_hash_info.size {
-                    let _ = self.execute_command(&format!("rm -f '{}'", temp_remote_path));
-                    return Err(AnsibleError::FileOperationError(format!(
-                        "File transfer verification FAILED! Size mismatch detected.\n\
-                         Local size:  {} bytes\n\
-                         Remote size: {} bytes\n\
-                         File may be corrupted during transfer: {}",
-                        local_hash_info.size,
-                        remote_hash_info.size,
-                        local_path
-                    )));
-                }
+        let remote_hash_info = self.get_remote_file_hash(&upload_path, hash_algorithm)?;
+        verify_upload(&local_hash_info, local_path, &upload_path, remote_hash_info)?;
 
-                info!(
-                    "✓ Transfer verification passed! Hash: {} (size: {} bytes)",
-                    remote_hash_info.hash, remote_hash_info.size
-                );
-            }
-            None => {
-                let _ = self.execute_command(&format!("rm -f '{}'", temp_remote_path));
+        // 原子性地移动临时文件到目标位置
+        if let Some(temp_remote) = temp_remote {
+            info!("Moving verified file to final destination: {}", remote_path);
+            let mv_cmd = format!("mv '{}' '{}'", upload_path, remote_path);
+            let mv_result = self.execute_command(&mv_cmd)?;
+            if mv_result.exit_code != 0 {
+                // 移动失败，临时文件由守卫清理
                 return Err(AnsibleError::FileOperationError(format!(
-                    "Failed to calculate remote file hash after transfer: {}",
-                    temp_remote_path
+                    "Failed to move temp file to destination: {}",
+                    mv_result.stderr
                 )));
             }
-        }
-
-        // 原子性地移动临时文件到目标位置
-        info!("Moving verified file to final destination: {}", remote_path);
-        let mv_cmd = format!("mv '{}' '{}'", temp_remote_path, remote_path);
-        let mv_result = self.execute_command(&mv_cmd)?;
-        if mv_result.exit_code != 0 {
-            // 移动失败，清理临时文件
-            let _ = self.execute_command(&format!("rm -f '{}'", temp_remote_path));
-            return Err(AnsibleError::FileOperationError(format!(
-                "Failed to move temp file to destination: {}",
-                mv_result.stderr
-            )));
+            temp_remote.commit();
         }
 
         // 应用文件属性（权限、所有者、组）
         self.apply_file_attributes(remote_path, options)?;
 
-        // 构建成功消息
-        let mut message = format!(
-            "Successfully transferred {} bytes (hash: {})",
-            bytes_transferred, local_hash_info.hash
-        );
-        if let Some(ref owner) = options.owner {
-            message.push_str(&format!(", owner: {}", owner));
-        }
-        if let Some(ref group) = options.group {
-            message.push_str(&format!(", group: {}", group));
-        }
-        if let Some(ref mode) = options.mode {
-            message.push_str(&format!(", mode: {}", mode));
-        }
+        let message = transfer_message(bytes_transferred, &local_hash_info.hash, options);
 
         info!(
             "File successfully copied and verified: {} -> {}",
             local_path, remote_path
         );
 
-        Ok(FileTransferResult {
-            success: true,
-            bytes_transferred,
-            message,
-        })
+        Ok((
+            FileTransferResult {
+                success: true,
+                bytes_transferred,
+                message,
+                diff,
+            },
+            action,
+        ))
+    }
+
+    /// 远程现有内容（不存在时为空）到本地文件的统一 diff；任一侧超过大小上限时只报告文件过大，
+    /// 远程文件无法读取时只报告差异不可用
+    fn upload_diff(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+        local_size: u64,
+        remote_size: Option<u64>,
+    ) -> Result<String, AnsibleError> {
+        let size = local_size.max(remote_size.unwrap_or(0));
+        if size > MAX_DIFF_SOURCE_BYTES {
+            return Ok(format!("File {} too large to diff ({} bytes)\n", remote_path, size));
+        }
+        let mut before = Vec::new();
+        if remote_size.is_some() && let Err(e) = self.transport.download(remote_path, &mut before) {
+            // 差异只是附带信息：远程文件不可读（例如只有 root 可读）时不影响复制本身
+            warn!("Cannot read {} on {} for diff: {}", remote_path, self.config.hostname, e);
+            return Ok(format!("Diff unavailable for {}: cannot read remote file: {}\n", remote_path, e));
+        }
+        let after = std::fs::read(local_path).map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to read local file {}: {}", local_path, e))
+        })?;
+        Ok(unified_diff(remote_path, &before, &after))
     }
 
     /// 从远程主机复制文件到本地
@@ -262,8 +446,6 @@ impl SshClient {
         remote_path: &str,
         local_path: &str,
     ) -> Result<FileTransferResult, AnsibleError> {
-        let (mut remote_file, _stat) = self.session.scp_recv(Path::new(remote_path))?;
-
         let mut local_file = std::fs::File::create(local_path).map_err(|e| {
             AnsibleError::FileOperationError(format!(
                 "Failed to create local file {}: {}",
@@ -271,14 +453,9 @@ impl SshClient {
             ))
         })?;
 
-        let bytes_transferred = std::io::copy(&mut remote_file, &mut local_file).map_err(|e| {
-            AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e))
-        })?;
-
-        remote_file.send_eof()?;
-        remote_file.wait_eof()?;
-        remote_file.close()?;
-        remote_file.wait_close()?;
+        let bytes_transferred = self.transport.download(remote_path, &mut local_file)?;
+        self.record_transfer_channel(bytes_transferred);
+        metrics::record_bytes_transferred(&self.config.hostname, "download", bytes_transferred);
 
         info!(
             "File {} copied from remote {} ({} bytes)",
@@ -289,6 +466,45 @@ impl SshClient {
             success: true,
             bytes_transferred,
             message: format!("Successfully transferred {} bytes", bytes_transferred),
+            diff: None,
+        })
+    }
+
+    /// 从远程主机复制文件到本地，并在下载后校验完整性
+    ///
+    /// 下载前计算远程文件 hash，下载后计算本地文件 hash；两者不一致（或下载失败）时删除本地文件。
+    pub fn copy_file_from_remote_verified(
+        &self,
+        remote_path: &str,
+        local_path: &str,
+        algorithm: &str,
+    ) -> Result<FileTransferResult, AnsibleError> {
+        let remote_hash = self.get_remote_file_hash(remote_path, algorithm)?.ok_or_else(|| {
+            AnsibleError::FileOperationError(format!("Remote file {} does not exist", remote_path))
+        })?;
+        debug!("Remote {} hash of {}: {}", algorithm, remote_path, remote_hash.hash);
+
+        let remove_partial = || {
+            if let Err(e) = std::fs::remove_file(local_path) {
+                warn!("Failed to remove local file {}: {}", local_path, e);
+            }
+        };
+
+        let transfer = self.copy_file_from_remote(remote_path, local_path).inspect_err(|_| remove_partial())?;
+        let local_hash = calculate_file_hash(local_path, algorithm).inspect_err(|_| remove_partial())?;
+
+        if local_hash != remote_hash.hash {
+            remove_partial();
+            return Err(AnsibleError::FileOperationError(format!(
+                "hash mismatch for {}: remote {} != local {}",
+                remote_path, remote_hash.hash, local_hash
+            )));
+        }
+
+        info!("Downloaded {} verified ({}: {})", remote_path, algorithm, local_hash);
+        Ok(FileTransferResult {
+            message: format!("{} (hash: {})", transfer.message, local_hash),
+            ..transfer
         })
     }
 
@@ -298,45 +514,217 @@ impl SshClient {
         remote_path: &str,
         options: &FileCopyOptions,
     ) -> Result<(), AnsibleError> {
-        // 设置文件权限（如果指定）
-        if let Some(ref mode) = options.mode {
-            let chmod_cmd = format!("chmod {} '{}'", mode, remote_path);
-            let chmod_result = self.execute_command(&chmod_cmd)?;
-            if chmod_result.exit_code != 0 {
-                return Err(AnsibleError::FileOperationError(format!(
-                    "Failed to set file permissions {}: {}",
-                    mode, chmod_result.stderr
-                )));
-            }
+        if options.mode.is_none() && options.owner.is_none() && options.group.is_none() {
+            return Ok(());
         }
 
-        // 设置文件所有者（如果指定）
-        if let Some(ref owner) = options.owner {
-            let chown_user = if let Some(ref group) = options.group {
-                format!("{}:{}", owner, group)
-            } else {
-                owner.clone()
-            };
-            let chown_cmd = format!("chown {} '{}'", chown_user, remote_path);
-            let chown_result = self.execute_command(&chown_cmd)?;
-            if chown_result.exit_code != 0 {
-                return Err(AnsibleError::FileOperationError(format!(
-                    "Failed to set file owner {}: {}",
-                    chown_user, chown_result.stderr
-                )));
-            }
-        } else if let Some(ref group) = options.group {
-            // 只设置组
-            let chgrp_cmd = format!("chgrp {} '{}'", group, remote_path);
-            let chgrp_result = self.execute_command(&chgrp_cmd)?;
-            if chgrp_result.exit_code != 0 {
-                return Err(AnsibleError::FileOperationError(format!(
-                    "Failed to set file group {}: {}",
-                    group, chgrp_result.stderr
-                )));
-            }
-        }
+        self.set_file_attributes(&PermissionsOptions {
+            path: remote_path.to_string(),
+            mode: options.mode.clone(),
+            owner: options.owner.clone(),
+            group: options.group.clone(),
+            recursive: false,
+        })?;
 
         Ok(())
     }
 }
+
+// 以下步骤由同步与异步客户端的上传共用
+
+/// `create_dirs` 时需要创建的父目录（根目录与相对文件名无需创建）
+pub(super) fn parent_dir_to_create(remote_path: &str) -> Option<String> {
+    let parent = Path::new(remote_path).parent()?.to_string_lossy().to_string();
+    (!parent.is_empty() && parent != "/").then_some(parent)
+}
+
+/// 覆盖前把现有文件备份为 `<path>.bak.<时间戳>`
+pub(super) fn backup_command(remote_path: &str) -> String {
+    // 在 Rust 端生成时间戳，避免 shell 命令中的 $() 被当作字面字符串
+    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
+    format!(
+        "[ -f '{}' ] && cp '{}' '{}.bak.{}' || true",
+        remote_path, remote_path, remote_path, timestamp
+    )
+}
+
+/// 上传使用的限速器：单次传输的限速与客户端共享的总带宽限制
+pub(super) fn upload_limiters(
+    options: &FileCopyOptions,
+    shared: Option<&Arc<BandwidthLimiter>>,
+) -> Vec<Arc<BandwidthLimiter>> {
+    let mut limiters = Vec::new();
+    if let Some(bytes_per_sec) = options.max_bandwidth_bytes_per_sec {
+        limiters.push(Arc::new(BandwidthLimiter::new(bytes_per_sec)));
+    }
+    limiters.extend(shared.cloned());
+    limiters
+}
+
+/// 比较上传前的本地 hash 与上传后的远程 hash 和大小
+pub(super) fn verify_upload(
+    local_hash_info: &FileHashInfo,
+    local_path: &str,
+    upload_path: &str,
+    remote_hash_info: Option<FileHashInfo>,
+) -> Result<(), AnsibleError> {
+    let Some(remote_hash_info) = remote_hash_info else {
+        return Err(AnsibleError::FileOperationError(format!(
+            "Failed to calculate remote file hash after transfer: {}",
+            upload_path
+        )));
+    };
+
+    // 验证 hash
+    if remote_hash_info.hash != local_hash_info.hash {
+        // Hash 不匹配，报错（临时文件由调用方清理）
+        return Err(AnsibleError::FileOperationError(format!(
+            "File transfer verification FAILED! SHA256 hash mismatch detected.\n\
+             Local hash:  {}\n\
+             Local path: {} \n\
+             Remote hash: {}\n\
+             Remote path: {} \n\
+             File may be corrupted during transfer: {}",
+            local_hash_info.hash,
+            local_path,
+            remote_hash_info.hash,
+            upload_path,
+            local_path
+        )));
+    }
+
+    // 验证文件大小
+    if remote_hash_info.size != local_hash_info.size {
+        return Err(AnsibleError::FileOperationError(format!(
+            "File transfer verification FAILED! Size mismatch detected.\n\
+             Local size:  {} bytes\n\
+             Remote size: {} bytes\n\
+             File may be corrupted during transfer: {}",
+            local_hash_info.size,
+            remote_hash_info.size,
+            local_path
+        )));
+    }
+
+    info!(
+        "✓ Transfer verification passed! Hash: {} (size: {} bytes)",
+        remote_hash_info.hash, remote_hash_info.size
+    );
+    Ok(())
+}
+
+/// 上传成功的消息，附带设置的所有者、组与权限
+pub(super) fn transfer_message(bytes_transferred: u64, hash: &str, options: &FileCopyOptions) -> String {
+    let mut message = format!(
+        "Successfully transferred {} bytes (hash: {})",
+        bytes_transferred, hash
+    );
+    if let Some(ref owner) = options.owner {
+        message.push_str(&format!(", owner: {}", owner));
+    }
+    if let Some(ref group) = options.group {
+        message.push_str(&format!(", group: {}", group));
+    }
+    if let Some(ref mode) = options.mode {
+        message.push_str(&format!(", mode: {}", mode));
+    }
+    message
+}
+
+/// 目录中需要复制的一项，路径相对于目录根并以 `/` 分隔
+#[derive(Debug, PartialEq)]
+enum LocalEntry {
+    File(String),
+    Symlink { path: String, target: String },
+}
+
+impl LocalEntry {
+    fn path(&self) -> &str {
+        match self {
+            LocalEntry::File(path) | LocalEntry::Symlink { path, .. } => path,
+        }
+    }
+}
+
+/// 递归收集目录下的普通文件和符号链接
+///
+/// 跟随符号链接时，`ancestors` 记录当前递归路径上各目录的规范路径，
+/// 遇到指回其中任一目录的链接即判定为循环并跳过。
+fn collect_entries(
+    root: &Path,
+    relative: &Path,
+    follow_symlinks: bool,
+    ancestors: &mut Vec<PathBuf>,
+    entries: &mut Vec<LocalEntry>,
+) -> Result<(), AnsibleError> {
+    let dir = root.join(relative);
+    if follow_symlinks {
+        let canonical = dir.canonicalize().map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to resolve {}: {}", dir.display(), e))
+        })?;
+        if ancestors.contains(&canonical) {
+            warn!("Skipping {}: symlink loop back to {}", dir.display(), canonical.display());
+            return Ok(());
+        }
+        ancestors.push(canonical);
+    }
+
+    let read_dir = std::fs::read_dir(&dir).map_err(|e| {
+        AnsibleError::FileOperationError(format!("Failed to read directory {}: {}", dir.display(), e))
+    })?;
+    for entry in read_dir {
+        let entry = entry.map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to read directory {}: {}", dir.display(), e))
+        })?;
+        let path = relative.join(entry.file_name());
+        let is_symlink = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+        if is_symlink && !follow_symlinks {
+            let target = std::fs::read_link(entry.path()).map_err(|e| {
+                AnsibleError::FileOperationError(format!("Failed to read symlink {}: {}", entry.path().display(), e))
+            })?;
+            entries.push(LocalEntry::Symlink {
+                path: slash_path(&path),
+                target: target.to_string_lossy().to_string(),
+            });
+            continue;
+        }
+
+        let metadata = std::fs::metadata(entry.path()).map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to get file metadata: {}", e))
+        })?;
+        if metadata.is_dir() {
+            collect_entries(root, &path, follow_symlinks, ancestors, entries)?;
+        } else if metadata.is_file() {
+            entries.push(LocalEntry::File(slash_path(&path)));
+        }
+    }
+
+    if follow_symlinks {
+        ancestors.pop();
+    }
+    Ok(())
+}
+
+fn slash_path(path: &Path) -> String {
+    let parts: Vec<String> = path.iter().map(|part| part.to_string_lossy().to_string()).collect();
+    parts.join("/")
+}
+
+/// 检查远程路径的类型：符号链接时输出目标（退出码 0），目录为 2，其他已存在的文件为 3，不存在为 4
+fn symlink_state_command(remote_path: &str) -> String {
+    let path = shell_quote(remote_path);
+    format!(
+        "if [ -L {p} ]; then readlink {p}; elif [ -d {p} ]; then exit 2; elif [ -e {p} ]; then exit 3; else exit 4; fi",
+        p = path
+    )
+}
+
+fn symlink_command(remote_path: &str, target: &str, create_dirs: bool) -> String {
+    let link = format!("ln -sfn {} {}", shell_quote(target), shell_quote(remote_path));
+    match Path::new(remote_path).parent().map(|p| p.to_string_lossy()) {
+        Some(parent) if create_dirs && !parent.is_empty() && parent != "/" => {
+            format!("mkdir -p {} && {}", shell_quote(&parent), link)
+        }
+        _ => link,
+    }
+}
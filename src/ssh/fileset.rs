@@ -0,0 +1,214 @@
+use crate::error::AnsibleError;
+use crate::types::{FileCopyOptions, FileSetAction, FileSetFileResult, FileSetResult, PlannedFile};
+use crate::utils::{generate_remote_temp_path, shell_quote, FileMode};
+use super::SshClient;
+use super::temp_file::RemoteTempFile;
+use super::template::render_validate_command;
+use std::collections::HashSet;
+use tracing::{error, info, warn};
+
+/// 已替换到目标位置的文件及其备份（目标此前不存在时没有备份）
+struct AppliedFile<'a> {
+    index: usize,
+    backup: Option<RemoteTempFile<'a>>,
+}
+
+impl SshClient {
+    /// 以事务方式部署一组相互关联的文件（例如证书、私钥与引用它们的 nginx 配置）
+    ///
+    /// 先把所有文件上传到目标旁的暂存文件，全部通过 SHA256 校验与 `validate` 命令后，
+    /// 再按顺序备份原文件并替换到位。任一替换失败时，本事务中已替换的文件从备份恢复，
+    /// 主机上不会留下新旧混杂的文件组合。暂存阶段失败时不会修改任何目标文件。
+    ///
+    /// 返回 `Err` 仅表示参数无效；事务失败通过 `FileSetResult::success` 与每个文件的状态报告。
+    /// 与本地一致的文件不会被替换，只在暂存阶段更新其权限与所有者（不参与回滚）。
+    pub fn deploy_fileset(&self, files: &[PlannedFile]) -> Result<FileSetResult, AnsibleError> {
+        validate_fileset(files)?;
+        info!("Deploying file set of {} file(s) to {}", files.len(), self.config.hostname);
+
+        let mut result = FileSetResult {
+            success: false,
+            changed: false,
+            files: files
+                .iter()
+                .map(|file| FileSetFileResult { dest: file.dest.clone(), action: FileSetAction::Untouched })
+                .collect(),
+            error: None,
+        };
+
+        // ========== 暂存：上传、校验并验证所有文件，不修改任何目标文件 ==========
+        let mut staged = Vec::new();
+        for (index, file) in files.iter().enumerate() {
+            match self.stage_planned_file(file) {
+                Ok(Some(temp)) => staged.push((index, temp)),
+                Ok(None) => result.files[index].action = FileSetAction::Unchanged,
+                Err(e) => {
+                    error!("Staging {} failed, no file was replaced: {}", file.dest, e);
+                    // 暂存文件由守卫清理
+                    result.error = Some(format!("Failed to stage {}: {}", file.dest, e));
+                    return Ok(result);
+                }
+            }
+        }
+
+        // ========== 替换：按顺序备份原文件并移动到位，失败时回滚 ==========
+        let mut applied: Vec<AppliedFile> = Vec::new();
+        for (index, temp) in staged {
+            let dest = &files[index].dest;
+            match self.swap_staged_file(temp, dest) {
+                Ok(backup) => {
+                    result.files[index].action = FileSetAction::Applied;
+                    applied.push(AppliedFile { index, backup });
+                }
+                Err(e) => {
+                    error!("Replacing {} failed, rolling back {} file(s): {}", dest, applied.len(), e);
+                    for file in applied.into_iter().rev() {
+                        result.files[file.index].action = self.restore_applied_file(&files[file.index].dest, file.backup);
+                    }
+                    result.error = Some(format!("Failed to replace {}: {}", dest, e));
+                    return Ok(result);
+                }
+            }
+        }
+
+        // 事务完成，删除备份
+        for file in applied {
+            if let Some(backup) = file.backup {
+                backup.discard();
+            }
+        }
+
+        result.success = true;
+        result.changed = result.files.iter().any(|f| f.action == FileSetAction::Applied);
+        info!("File set deployed to {}: {}", self.config.hostname, result.summary());
+        Ok(result)
+    }
+
+    /// 上传到目标旁的暂存文件并执行验证；与远程文件一致时返回 None
+    fn stage_planned_file(&self, file: &PlannedFile) -> Result<Option<RemoteTempFile<'_>>, AnsibleError> {
+        let local = self.calculate_local_file_hash(&file.src, "sha256")?;
+        if let Some(remote) = self.get_remote_file_hash(&file.dest, "sha256")?
+            && remote.hash == local.hash
+            && remote.size == local.size
+        {
+            info!("{} unchanged (hash: {})", file.dest, remote.hash);
+            self.apply_file_attributes(&file.dest, &copy_options(file))?;
+            return Ok(None);
+        }
+
+        let temp = RemoteTempFile::new(self, generate_remote_temp_path(&file.dest));
+        let options = FileCopyOptions {
+            precomputed_hash: Some(local.hash),
+            // 暂存文件本身就是临时文件，直接写入即可
+            atomic: false,
+            ..copy_options(file)
+        };
+        self.copy_file_to_remote_with_options(&file.src, temp.path(), &options)?;
+
+        if let Some(ref validate_cmd) = file.validate {
+            let command = render_validate_command(validate_cmd, temp.path(), &file.dest)?;
+            let validation = self.execute_command(&command)?;
+            if validation.exit_code != 0 {
+                return Err(AnsibleError::ValidationError(format!(
+                    "Validation of {} failed: {}",
+                    file.dest,
+                    validation.stderr.trim()
+                )));
+            }
+        }
+        Ok(Some(temp))
+    }
+
+    /// 备份目标文件（存在时）并把暂存文件移动到位，返回备份
+    fn swap_staged_file<'a>(
+        &'a self,
+        temp: RemoteTempFile<'a>,
+        dest: &str,
+    ) -> Result<Option<RemoteTempFile<'a>>, AnsibleError> {
+        let backup = RemoteTempFile::new(self, generate_remote_temp_path(dest));
+        let result = self.execute_command(&format!(
+            "if [ -e {dest} ]; then cp -p {dest} {} && echo backed_up; fi",
+            shell_quote(backup.path()),
+            dest = shell_quote(dest)
+        ))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to back up {}: {}",
+                dest,
+                result.stderr.trim()
+            )));
+        }
+        let backup = if result.stdout.contains("backed_up") {
+            Some(backup)
+        } else {
+            backup.commit();
+            None
+        };
+
+        let result = self.execute_command(&format!("mv -f {} {}", shell_quote(temp.path()), shell_quote(dest)))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to move staged file to {}: {}",
+                dest,
+                result.stderr.trim()
+            )));
+        }
+        temp.commit();
+        Ok(backup)
+    }
+
+    /// 从备份恢复已替换的文件；目标此前不存在时删除
+    fn restore_applied_file(&self, dest: &str, backup: Option<RemoteTempFile<'_>>) -> FileSetAction {
+        let command = match backup {
+            Some(ref backup) => format!("mv -f {} {}", shell_quote(backup.path()), shell_quote(dest)),
+            None => format!("rm -f {}", shell_quote(dest)),
+        };
+        match self.execute_command(&command) {
+            Ok(result) if result.exit_code == 0 => {
+                if let Some(backup) = backup {
+                    backup.commit();
+                }
+                info!("Rolled back {}", dest);
+                FileSetAction::RolledBack
+            }
+            Ok(result) => {
+                warn!("Failed to roll back {}: {}", dest, result.stderr.trim());
+                FileSetAction::RollbackFailed
+            }
+            Err(e) => {
+                warn!("Failed to roll back {}: {}", dest, e);
+                FileSetAction::RollbackFailed
+            }
+        }
+    }
+}
+
+fn copy_options(file: &PlannedFile) -> FileCopyOptions {
+    FileCopyOptions {
+        owner: file.owner.clone(),
+        group: file.group.clone(),
+        mode: file.mode.clone(),
+        create_dirs: true,
+        ..Default::default()
+    }
+}
+
+/// 文件集不能为空，目标路径不能重复，权限格式必须有效
+fn validate_fileset(files: &[PlannedFile]) -> Result<(), AnsibleError> {
+    if files.is_empty() {
+        return Err(AnsibleError::ValidationError("File set is empty".to_string()));
+    }
+    let mut seen = HashSet::new();
+    for file in files {
+        if !seen.insert(file.dest.as_str()) {
+            return Err(AnsibleError::ValidationError(format!(
+                "Destination {} appears more than once in the file set",
+                file.dest
+            )));
+        }
+        if let Some(ref mode) = file.mode {
+            FileMode::parse(mode)?;
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::error::AnsibleError;
 use crate::ssh::client::SshClient;
-use crate::types::FileHashInfo;
+use crate::types::{CommandResult, FileHashInfo};
 
 impl SshClient {
     /// 计算本地文件的 hash 值
@@ -28,62 +28,18 @@ impl SshClient {
         algorithm: &str,
     ) -> Result<Option<FileHashInfo>, AnsibleError> {
         // 首先检查文件是否存在
-        let check_cmd = format!(
-            "test -f '{}' && echo 'exists' || echo 'not_exists'",
-            remote_path
-        );
-        let check_result = self.execute_command(&check_cmd)?;
-
-        if check_result.stdout.trim() == "not_exists" {
+        let check_result = self.execute_command(&exists_command(remote_path))?;
+        if !remote_file_exists(&check_result) {
             return Ok(None);
         }
 
         // 获取文件大小
-        let size_cmd = format!(
-            "stat -c %s '{}' 2>/dev/null || stat -f %z '{}'",
-            remote_path, remote_path
-        );
-        let size_result = self.execute_command(&size_cmd)?;
-        let size: u64 = size_result.stdout.trim().parse().map_err(|e| {
-            AnsibleError::FileOperationError(format!("Failed to parse file size: {}", e))
-        })?;
+        let size_result = self.execute_command(&size_command(remote_path))?;
+        let size = parse_size(&size_result)?;
 
         // 计算远程文件 hash
-        let hash_cmd = match algorithm.to_lowercase().as_str() {
-            "sha256" => format!(
-                "sha256sum '{}' 2>/dev/null || shasum -a 256 '{}'",
-                remote_path, remote_path
-            ),
-            "md5" => format!(
-                "md5sum '{}' 2>/dev/null || md5 -r '{}'",
-                remote_path, remote_path
-            ),
-            _ => {
-                return Err(AnsibleError::FileOperationError(format!(
-                    "Unsupported hash algorithm: {}",
-                    algorithm
-                )));
-            }
-        };
-
-        let hash_result = self.execute_command(&hash_cmd)?;
-
-        if hash_result.exit_code != 0 {
-            return Err(AnsibleError::FileOperationError(format!(
-                "Failed to calculate remote file hash: {}",
-                hash_result.stderr
-            )));
-        }
-
-        // 解析 hash 输出（不同系统格式可能不同）
-        let hash = hash_result
-            .stdout
-            .split_whitespace()
-            .next()
-            .ok_or_else(|| {
-                AnsibleError::FileOperationError("Failed to parse hash output".to_string())
-            })?
-            .to_string();
+        let hash_result = self.execute_command(&hash_command(remote_path, algorithm)?)?;
+        let hash = parse_hash(&hash_result)?;
 
         Ok(Some(FileHashInfo {
             algorithm: algorithm.to_string(),
@@ -92,3 +48,63 @@ impl SshClient {
         }))
     }
 }
+
+// 以下命令与解析由同步与异步客户端共用
+
+pub(super) fn exists_command(remote_path: &str) -> String {
+    format!(
+        "test -f '{}' && echo 'exists' || echo 'not_exists'",
+        remote_path
+    )
+}
+
+pub(super) fn remote_file_exists(check_result: &CommandResult) -> bool {
+    check_result.stdout.trim() != "not_exists"
+}
+
+pub(super) fn size_command(remote_path: &str) -> String {
+    format!(
+        "stat -c %s '{}' 2>/dev/null || stat -f %z '{}'",
+        remote_path, remote_path
+    )
+}
+
+pub(super) fn parse_size(size_result: &CommandResult) -> Result<u64, AnsibleError> {
+    size_result.stdout.trim().parse().map_err(|e| {
+        AnsibleError::FileOperationError(format!("Failed to parse file size: {}", e))
+    })
+}
+
+pub(super) fn hash_command(remote_path: &str, algorithm: &str) -> Result<String, AnsibleError> {
+    match algorithm.to_lowercase().as_str() {
+        "sha256" => Ok(format!(
+            "sha256sum '{}' 2>/dev/null || shasum -a 256 '{}'",
+            remote_path, remote_path
+        )),
+        "md5" => Ok(format!(
+            "md5sum '{}' 2>/dev/null || md5 -r '{}'",
+            remote_path, remote_path
+        )),
+        _ => Err(AnsibleError::FileOperationError(format!(
+            "Unsupported hash algorithm: {}",
+            algorithm
+        ))),
+    }
+}
+
+pub(super) fn parse_hash(hash_result: &CommandResult) -> Result<String, AnsibleError> {
+    if hash_result.exit_code != 0 {
+        return Err(AnsibleError::FileOperationError(format!(
+            "Failed to calculate remote file hash: {}",
+            hash_result.stderr
+        )));
+    }
+
+    // 解析 hash 输出（不同系统格式可能不同）
+    hash_result
+        .stdout
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+        .ok_or_else(|| AnsibleError::FileOperationError("Failed to parse hash output".to_string()))
+}
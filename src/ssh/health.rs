@@ -0,0 +1,83 @@
+use crate::error::AnsibleError;
+use crate::types::{CommandOptions, HealthProbe, HealthProbeResult, HostRequirement};
+use super::SshClient;
+use std::time::Instant;
+use tracing::{info, warn};
+
+impl SshClient {
+    /// 执行一个健康探针，退出码与期望一致且输出包含期望文本时视为通过
+    ///
+    /// 探针不通过仍返回 `Ok`（`passed: false`）；命令超时或连接错误返回 `Err`。
+    pub fn run_health_probe(&self, probe: &HealthProbe) -> Result<HealthProbeResult, AnsibleError> {
+        let options = CommandOptions::new().timeout(probe.timeout);
+        let started = Instant::now();
+        let result = self.execute_command_full(&probe.command, options)?;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let passed = probe_passed(probe, result.exit_code, &result.stdout);
+        if passed {
+            info!("Health probe '{}' passed on {} ({:.1} ms)", probe.name, self.config.hostname, latency_ms);
+        } else {
+            warn!(
+                "Health probe '{}' failed on {}: exit code {}",
+                probe.name, self.config.hostname, result.exit_code
+            );
+        }
+        Ok(HealthProbeResult {
+            name: probe.name.clone(),
+            passed,
+            exit_code: result.exit_code,
+            stdout: result.stdout,
+            latency_ms,
+        })
+    }
+
+    /// 依次执行多个健康探针（某个探针不通过不影响后续探针）
+    pub fn run_health_probes(&self, probes: &[HealthProbe]) -> Result<Vec<HealthProbeResult>, AnsibleError> {
+        probes.iter().map(|probe| self.run_health_probe(probe)).collect()
+    }
+
+    /// 依次执行健康探针与主机条件检查，条件检查的结果排在探针之后
+    pub fn run_health_checks(
+        &self,
+        probes: &[HealthProbe],
+        requirements: &[HostRequirement],
+    ) -> Result<Vec<HealthProbeResult>, AnsibleError> {
+        let mut results = self.run_health_probes(probes)?;
+        for requirement in requirements {
+            results.push(self.check_host_requirement(requirement)?);
+        }
+        Ok(results)
+    }
+}
+
+fn probe_passed(probe: &HealthProbe, exit_code: i32, stdout: &str) -> bool {
+    exit_code == probe.expected_exit_code
+        && probe
+            .expected_stdout_contains
+            .as_deref()
+            .is_none_or(|expected| stdout.contains(expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_passed_and_yaml_timeout() {
+        let probe: HealthProbe = serde_yaml::from_str(
+            "name: nginx\ncommand: curl -s localhost/health\nexpected_stdout_contains: ok\ntimeout: 2.5\n",
+        )
+        .unwrap();
+        assert_eq!(probe.expected_exit_code, 0);
+        assert_eq!(probe.timeout, std::time::Duration::from_millis(2500));
+
+        assert!(probe_passed(&probe, 0, "status: ok\n"));
+        assert!(!probe_passed(&probe, 0, "status: degraded\n"));
+        assert!(!probe_passed(&probe, 7, "ok"));
+
+        let probe: HealthProbe = serde_yaml::from_str("name: no-app\ncommand: pgrep app\nexpected_exit_code: 1\n").unwrap();
+        assert_eq!(probe.timeout, std::time::Duration::from_secs(10));
+        assert!(probe_passed(&probe, 1, ""));
+    }
+}
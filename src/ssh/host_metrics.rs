@@ -0,0 +1,79 @@
+use crate::types::HostMetrics;
+use super::{AsyncSshClient, SshClient};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 客户端在连接期间累计的计数（原子操作，开销可忽略）
+///
+/// 通过 `Arc` 共享：客户端被操作消费后，调用方仍可从保留的句柄读取统计。
+#[derive(Debug, Default)]
+pub(crate) struct MetricsCounters {
+    connect_time_us: AtomicU64,
+    channels_opened: AtomicU64,
+    commands: AtomicU64,
+    bytes_transferred: AtomicU64,
+}
+
+impl MetricsCounters {
+    pub(crate) fn snapshot(&self) -> HostMetrics {
+        HostMetrics {
+            connect_time: Duration::from_micros(self.connect_time_us.load(Ordering::Relaxed)),
+            channels_opened: self.channels_opened.load(Ordering::Relaxed),
+            commands: self.commands.load(Ordering::Relaxed),
+            bytes_transferred: self.bytes_transferred.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn record_connect_time(&self, elapsed: Duration) {
+        self.connect_time_us.store(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// 每次执行命令都会打开一个通道
+    pub(super) fn record_command_channel(&self) {
+        self.channels_opened.fetch_add(1, Ordering::Relaxed);
+        self.commands.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 上传或下载打开一个通道并传输 `bytes` 字节
+    pub(super) fn record_transfer_channel(&self, bytes: u64) {
+        self.channels_opened.fetch_add(1, Ordering::Relaxed);
+        self.bytes_transferred.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl SshClient {
+    /// 当前连接的统计：连接耗时、打开的通道数、执行的命令数与传输字节数
+    pub fn host_metrics(&self) -> HostMetrics {
+        self.counters.snapshot()
+    }
+
+    /// 统计计数的共享句柄
+    pub(crate) fn metrics_counters(&self) -> Arc<MetricsCounters> {
+        self.counters.clone()
+    }
+
+    pub(crate) fn record_connect_time(&self, elapsed: Duration) {
+        self.counters.record_connect_time(elapsed);
+    }
+
+    pub(super) fn record_command_channel(&self) {
+        self.counters.record_command_channel();
+    }
+
+    pub(super) fn record_transfer_channel(&self, bytes: u64) {
+        self.counters.record_transfer_channel(bytes);
+    }
+}
+
+impl AsyncSshClient {
+    /// 当前连接的统计，与 [`SshClient::host_metrics`] 相同
+    pub fn host_metrics(&self) -> HostMetrics {
+        self.counters.snapshot()
+    }
+
+    /// 统计计数的共享句柄
+    pub(crate) fn metrics_counters(&self) -> Arc<MetricsCounters> {
+        self.counters.clone()
+    }
+}
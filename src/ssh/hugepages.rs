@@ -0,0 +1,201 @@
+use crate::error::AnsibleError;
+use crate::types::HugepagesInfo;
+use super::SshClient;
+use tracing::{info, warn};
+
+/// 持久化 hugepages 数量的 sysctl 配置文件
+const SYSCTL_CONF: &str = "/etc/sysctl.d/90-rs-ansible-hugepages.conf";
+
+/// 读取 /proc/meminfo，并列出 sysfs 中每种页面大小的计数（`@<目录> total free reserved surplus`）
+const HUGEPAGES_CMD: &str = "cat /proc/meminfo; \
+     for d in /sys/kernel/mm/hugepages/hugepages-*kB; do \
+     [ -d \"$d\" ] && echo \"@$d $(cat $d/nr_hugepages) $(cat $d/free_hugepages) $(cat $d/resv_hugepages) $(cat $d/surplus_hugepages)\"; \
+     done";
+
+impl SshClient {
+    /// 获取各页面大小的 hugepages 使用情况
+    ///
+    /// 优先使用 sysfs 中按页面大小划分的计数；不可用时回退到 /proc/meminfo 中默认页面大小的统计。
+    pub fn get_hugepages_info(&self) -> Result<Vec<HugepagesInfo>, AnsibleError> {
+        let result = self.execute_command(HUGEPAGES_CMD)?;
+        if result.exit_code != 0 && result.stdout.is_empty() {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read hugepages info: {}",
+                result.stderr.trim()
+            )));
+        }
+        Ok(parse_hugepages(&result.stdout))
+    }
+
+    /// 设置指定页面大小的 hugepages 数量，返回是否有变更
+    ///
+    /// 默认页面大小写入 `/proc/sys/vm/nr_hugepages`，其他大小写入对应的 sysfs 文件。
+    /// `persist` 为 true 时同时写入 `/etc/sysctl.d/`（仅支持默认页面大小，其他大小需通过内核启动参数配置）。
+    /// 需要 root 权限（become）。
+    pub fn set_hugepages_count(&self, count: u64, size_kb: u32, persist: bool) -> Result<bool, AnsibleError> {
+        let current = self.get_hugepages_info()?;
+        let default_size = current.first().map(|info| info.size_kb);
+        let existing = current.iter().find(|info| info.size_kb == size_kb).ok_or_else(|| {
+            AnsibleError::ValidationError(format!(
+                "Hugepage size {} kB is not supported on {}",
+                size_kb, self.config.hostname
+            ))
+        })?;
+        let is_default = default_size == Some(size_kb);
+        if persist && !is_default {
+            return Err(AnsibleError::ValidationError(format!(
+                "Only the default hugepage size can be persisted via sysctl, {} kB requires kernel boot parameters",
+                size_kb
+            )));
+        }
+
+        let mut changed = false;
+        if existing.total != count {
+            let target = if is_default {
+                "/proc/sys/vm/nr_hugepages".to_string()
+            } else {
+                format!("/sys/kernel/mm/hugepages/hugepages-{}kB/nr_hugepages", size_kb)
+            };
+            let result = self.execute_command(&format!("echo {} > {}", count, target))?;
+            if result.exit_code != 0 {
+                return Err(AnsibleError::CommandError(format!(
+                    "Failed to set hugepages count: {}",
+                    result.stderr.trim()
+                )));
+            }
+            changed = true;
+
+            // 内存碎片较多时内核可能只分配到部分页面
+            let allocated = self
+                .get_hugepages_info()?
+                .into_iter()
+                .find(|info| info.size_kb == size_kb)
+                .map_or(0, |info| info.total);
+            if allocated != count {
+                warn!(
+                    "Requested {} hugepages of {} kB on {}, kernel allocated {}",
+                    count, size_kb, self.config.hostname, allocated
+                );
+            }
+        }
+
+        if persist {
+            let line = format!("vm.nr_hugepages = {}", count);
+            let existing_conf = self.execute_command(&format!("cat {} 2>/dev/null", SYSCTL_CONF))?;
+            if existing_conf.stdout.trim() != line {
+                let result = self.execute_command(&format!("echo '{}' > {}", line, SYSCTL_CONF))?;
+                if result.exit_code != 0 {
+                    return Err(AnsibleError::CommandError(format!(
+                        "Failed to write {}: {}",
+                        SYSCTL_CONF,
+                        result.stderr.trim()
+                    )));
+                }
+                changed = true;
+            }
+        }
+
+        info!(
+            "Hugepages ({} kB) on {} set to {} (changed: {})",
+            size_kb, self.config.hostname, count, changed
+        );
+        Ok(changed)
+    }
+}
+
+/// 解析 hugepages 信息，默认页面大小排在首位
+fn parse_hugepages(output: &str) -> Vec<HugepagesInfo> {
+    let mut meminfo = HugepagesInfo::default();
+    let mut per_size = Vec::new();
+
+    for line in output.lines() {
+        if let Some(sysfs) = line.strip_prefix('@') {
+            let fields: Vec<&str> = sysfs.split_whitespace().collect();
+            let size_kb = fields
+                .first()
+                .and_then(|dir| dir.rsplit("hugepages-").next())
+                .and_then(|size| size.strip_suffix("kB"))
+                .and_then(|size| size.parse().ok());
+            let counts: Vec<u64> = fields.iter().skip(1).filter_map(|v| v.parse().ok()).collect();
+            if let (Some(size_kb), [total, free, reserved, surplus]) = (size_kb, counts.as_slice()) {
+                per_size.push(HugepagesInfo {
+                    size_kb,
+                    total: *total,
+                    free: *free,
+                    reserved: *reserved,
+                    surplus: *surplus,
+                });
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.split_whitespace().next().and_then(|v| v.parse::<u64>().ok());
+        let Some(value) = value else {
+            continue;
+        };
+        match key.trim() {
+            "HugePages_Total" => meminfo.total = value,
+            "HugePages_Free" => meminfo.free = value,
+            "HugePages_Rsvd" => meminfo.reserved = value,
+            "HugePages_Surp" => meminfo.surplus = value,
+            "Hugepagesize" => meminfo.size_kb = value as u32,
+            _ => {}
+        }
+    }
+
+    if per_size.is_empty() {
+        return if meminfo.size_kb > 0 { vec![meminfo] } else { Vec::new() };
+    }
+
+    // sysfs 不区分默认页面大小，按 meminfo 中的大小排到首位
+    per_size.sort_by_key(|info| (info.size_kb != meminfo.size_kb, info.size_kb));
+    per_size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &str = "MemTotal:       65843212 kB
+MemFree:        12345678 kB
+HugePages_Total:    1024
+HugePages_Free:      1000
+HugePages_Rsvd:        16
+HugePages_Surp:         2
+Hugepagesize:       2048 kB
+Hugetlb:         2097152 kB
+";
+
+    #[test]
+    fn test_parse_meminfo() {
+        let info = parse_hugepages(MEMINFO);
+        assert_eq!(
+            info,
+            vec![HugepagesInfo {
+                size_kb: 2048,
+                total: 1024,
+                free: 1000,
+                reserved: 16,
+                surplus: 2,
+            }]
+        );
+        assert!(parse_hugepages("MemTotal: 1024 kB\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_sysfs_sizes() {
+        let output = format!(
+            "{}@/sys/kernel/mm/hugepages/hugepages-1048576kB 4 4 0 0\n@/sys/kernel/mm/hugepages/hugepages-2048kB 1024 1000 16 2\n",
+            MEMINFO
+        );
+        let info = parse_hugepages(&output);
+        assert_eq!(info.len(), 2);
+        assert_eq!(info[0].size_kb, 2048);
+        assert_eq!(info[0].free, 1000);
+        assert_eq!(info[1].size_kb, 1048576);
+        assert_eq!(info[1].total, 4);
+    }
+}
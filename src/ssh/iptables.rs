@@ -0,0 +1,148 @@
+use crate::error::AnsibleError;
+use crate::types::{IpVersion, IptablesChain};
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use super::SshClient;
+use tracing::info;
+
+impl SshClient {
+    /// 读取指定表的防火墙规则（只读，不修改任何状态）
+    pub fn get_iptables_rules(&self, table: &str, ip_version: IpVersion) -> Result<Vec<IptablesChain>, AnsibleError> {
+        if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(AnsibleError::ValidationError(format!("Invalid iptables table name: '{}'", table)));
+        }
+
+        let result = self.execute_command(&format!("{} -t {}", ip_version.save_command(), table))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "{} failed: {}",
+                ip_version.save_command(),
+                result.stderr.trim()
+            )));
+        }
+
+        let chains = parse_iptables_save(&result.stdout);
+        info!(
+            "Read {} chain(s) from {} table '{}' on {}",
+            chains.len(),
+            ip_version.save_command(),
+            table,
+            self.config.hostname
+        );
+        Ok(chains)
+    }
+
+    /// 将当前全部防火墙规则保存到远程文件（先写临时文件再原子替换）
+    pub fn save_iptables_rules(&self, path: &str, ip_version: IpVersion) -> Result<(), AnsibleError> {
+        let temp_path = generate_remote_temp_path(path);
+        let cmd = format!(
+            "{0} > {1} && mv -f {1} {2} || {{ rm -f {1}; exit 1; }}",
+            ip_version.save_command(),
+            shell_quote(&temp_path),
+            shell_quote(path)
+        );
+        let result = self.execute_command(&cmd)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to save {} rules to {}: {}",
+                ip_version.save_command(),
+                path,
+                result.stderr.trim()
+            )));
+        }
+
+        info!("Saved {} rules to {} on {}", ip_version.save_command(), path, self.config.hostname);
+        Ok(())
+    }
+}
+
+/// 解析 `iptables-save` 输出
+///
+/// `*<table>` 开始一个表，`:<chain> <policy> [pkts:bytes]` 声明链（自定义链的策略为 `-`），
+/// `-A <chain> <spec>` 为规则，`COMMIT` 结束一个表。
+fn parse_iptables_save(output: &str) -> Vec<IptablesChain> {
+    let mut chains: Vec<IptablesChain> = Vec::new();
+    let mut table = String::new();
+
+    for line in output.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line == "COMMIT" {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('*') {
+            table = name.to_string();
+        } else if let Some(declaration) = line.strip_prefix(':') {
+            let mut parts = declaration.split_whitespace();
+            let Some(chain) = parts.next() else { continue };
+            let policy = parts.next().filter(|p| *p != "-").map(str::to_string);
+            chains.push(IptablesChain {
+                table: table.clone(),
+                chain: chain.to_string(),
+                policy,
+                rules: Vec::new(),
+            });
+        } else if let Some(rule) = line.strip_prefix("-A ") {
+            let (chain, spec) = rule.split_once(' ').unwrap_or((rule, ""));
+            let index = match chains.iter().position(|c| c.table == table && c.chain == chain) {
+                Some(index) => index,
+                None => {
+                    chains.push(IptablesChain {
+                        table: table.clone(),
+                        chain: chain.to_string(),
+                        policy: None,
+                        rules: Vec::new(),
+                    });
+                    chains.len() - 1
+                }
+            };
+            chains[index].rules.push(spec.trim().to_string());
+        }
+    }
+
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iptables_save() {
+        let output = "\
+# Generated by iptables-save v1.8.7 on Mon Jan  1 00:00:00 2024
+*filter
+:INPUT DROP [120:9000]
+:FORWARD ACCEPT [0:0]
+:OUTPUT ACCEPT [300:42000]
+:DOCKER-USER - [0:0]
+-A INPUT -i lo -j ACCEPT
+-A INPUT -p tcp -m tcp --dport 22 -j ACCEPT
+-A INPUT -m state --state RELATED,ESTABLISHED -j ACCEPT
+-A FORWARD -j DOCKER-USER
+-A DOCKER-USER -j RETURN
+COMMIT
+# Completed on Mon Jan  1 00:00:00 2024
+";
+        let chains = parse_iptables_save(output);
+        assert_eq!(chains.len(), 4);
+
+        assert_eq!(
+            chains[0],
+            IptablesChain {
+                table: "filter".to_string(),
+                chain: "INPUT".to_string(),
+                policy: Some("DROP".to_string()),
+                rules: vec![
+                    "-i lo -j ACCEPT".to_string(),
+                    "-p tcp -m tcp --dport 22 -j ACCEPT".to_string(),
+                    "-m state --state RELATED,ESTABLISHED -j ACCEPT".to_string(),
+                ],
+            }
+        );
+        assert_eq!(chains[1].rules, vec!["-j DOCKER-USER".to_string()]);
+        assert!(chains[2].rules.is_empty());
+        assert_eq!(chains[3].chain, "DOCKER-USER");
+        assert_eq!(chains[3].policy, None);
+        assert_eq!(chains[3].rules, vec!["-j RETURN".to_string()]);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::error::AnsibleError;
+use crate::types::{KernelModuleConfig, KernelModuleResult, UserState};
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use super::SshClient;
+use std::collections::BTreeMap;
+use tracing::{debug, info, warn};
+
+const MODULES_LOAD_DIR: &str = "/etc/modules-load.d";
+const MODPROBE_DIR: &str = "/etc/modprobe.d";
+/// 本模块写入的文件的首行，只有带此标记的文件才会被覆盖或删除
+const MANAGED_HEADER: &str = "# Managed by rs-ansible";
+
+impl SshClient {
+    /// 加载（present）或卸载（absent）内核模块，需要 root 权限（become）
+    ///
+    /// 模块已加载时不会因参数不同而重新加载。`persist` 为 true 时，present 写入
+    /// `/etc/modules-load.d/<name>.conf`（有参数时同时写入 `/etc/modprobe.d/<name>.conf`，
+    /// 无参数时删除遗留的 modprobe.d 文件）使模块在重启后自动加载；absent 删除这两个文件。
+    /// 只覆盖或删除首行为 `# Managed by rs-ansible` 的文件，软件包或管理员编写的同名文件保持不变并记录警告。
+    pub fn manage_kernel_module(
+        &self,
+        config: &KernelModuleConfig,
+        state: UserState,
+    ) -> Result<KernelModuleResult, AnsibleError> {
+        validate_kernel_module(config)?;
+        let loaded = self.execute_command(&loaded_check_command(&config.name))?.exit_code == 0;
+        let mut changes = Vec::new();
+
+        match state {
+            UserState::Present => {
+                if !loaded {
+                    self.run_module_command(&modprobe_command(config))?;
+                    changes.push("loaded");
+                }
+                if config.persist {
+                    let mut written = false;
+                    for (path, content) in persist_files(config) {
+                        written |= self.ensure_module_file(&path, &content)?;
+                    }
+                    // 参数已清空时，旧的 options 行不能继续生效
+                    if config.params.is_empty() {
+                        written |= self.remove_module_files(&config.name, &[MODPROBE_DIR])?;
+                    }
+                    if written {
+                        changes.push("persisted");
+                    }
+                }
+            }
+            UserState::Absent => {
+                if loaded {
+                    self.run_module_command(&rmmod_command(&config.name))?;
+                    changes.push("unloaded");
+                }
+                if config.persist && self.remove_module_files(&config.name, &[MODULES_LOAD_DIR, MODPROBE_DIR])? {
+                    changes.push("unpersisted");
+                }
+            }
+        }
+
+        let changed = !changes.is_empty();
+        let message = if changed {
+            info!("Kernel module '{}' on {}: {}", config.name, self.config.hostname, changes.join(", "));
+            format!("Kernel module '{}' {}", config.name, changes.join(", "))
+        } else {
+            format!("Kernel module '{}' already {}", config.name, if loaded { "loaded" } else { "absent" })
+        };
+        Ok(KernelModuleResult { changed, message })
+    }
+
+    fn run_module_command(&self, command: &str) -> Result<(), AnsibleError> {
+        let result = self.execute_command(command)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "'{}' failed: {}",
+                command,
+                result.stderr.trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// 删除各目录下由本模块写入的 `<name>.conf`，返回是否删除了文件
+    fn remove_module_files(&self, name: &str, dirs: &[&str]) -> Result<bool, AnsibleError> {
+        let mut removed = false;
+        for dir in dirs {
+            let path = format!("{}/{}.conf", dir, name);
+            let current = self.execute_command(&format!("cat {} 2>/dev/null", shell_quote(&path)))?;
+            if current.exit_code != 0 {
+                continue;
+            }
+            if !is_managed(&current.stdout) {
+                warn!("Keeping {} on {}: not managed by rs-ansible", path, self.config.hostname);
+                continue;
+            }
+            let result = self.execute_command(&format!("rm -f {}", shell_quote(&path)))?;
+            if result.exit_code != 0 {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "Failed to remove {} for module '{}': {}",
+                    path,
+                    name,
+                    result.stderr.trim()
+                )));
+            }
+            removed = true;
+        }
+        Ok(removed)
+    }
+
+    /// 文件内容与期望不一致时原子地写入，返回是否有修改；已存在且不由本模块管理的文件不覆盖
+    fn ensure_module_file(&self, path: &str, content: &str) -> Result<bool, AnsibleError> {
+        let current = self.execute_command(&format!("cat {} 2>/dev/null", shell_quote(path)))?;
+        if current.exit_code == 0 && current.stdout == content {
+            debug!("{} already up to date", path);
+            return Ok(false);
+        }
+        if current.exit_code == 0 && !is_managed(&current.stdout) {
+            warn!("Not overwriting {} on {}: not managed by rs-ansible", path, self.config.hostname);
+            return Ok(false);
+        }
+
+        let temp_path = generate_remote_temp_path(path);
+        let result = self.execute_command(&format!(
+            "printf '%s' {} > {temp} && chmod 644 {temp} && mv -f {temp} {}",
+            shell_quote(content),
+            shell_quote(path),
+            temp = shell_quote(&temp_path)
+        ))?;
+        if result.exit_code != 0 {
+            let _ = self.execute_command(&format!("rm -f {}", shell_quote(&temp_path)));
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to write {}: {}",
+                path,
+                result.stderr.trim()
+            )));
+        }
+        Ok(true)
+    }
+}
+
+/// 模块名与参数名只允许字母、数字、`_` 与 `-`；参数值不能为空，也不能包含空白或控制字符
+///
+/// 参数值会写入 modprobe.d 的 `options` 行，空白或换行会拆出额外的参数或指令。
+fn validate_kernel_module(config: &KernelModuleConfig) -> Result<(), AnsibleError> {
+    let valid_name = |name: &str| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+    };
+    if !valid_name(&config.name) {
+        return Err(AnsibleError::ValidationError(format!(
+            "Invalid kernel module name: '{}'",
+            config.name
+        )));
+    }
+    if let Some(key) = config.params.keys().find(|key| !valid_name(key)) {
+        return Err(AnsibleError::ValidationError(format!(
+            "Invalid parameter '{}' for kernel module '{}'",
+            key, config.name
+        )));
+    }
+    if let Some((key, _)) = config
+        .params
+        .iter()
+        .find(|(_, value)| value.is_empty() || value.chars().any(|c| c.is_whitespace() || c.is_control()))
+    {
+        return Err(AnsibleError::ValidationError(format!(
+            "Invalid value for parameter '{}' of kernel module '{}': must be non-empty without whitespace",
+            key, config.name
+        )));
+    }
+    Ok(())
+}
+
+/// 参数按名称排序，保证生成的命令与文件内容稳定
+fn sorted_params(config: &KernelModuleConfig) -> BTreeMap<&String, &String> {
+    config.params.iter().collect()
+}
+
+/// /proc/modules 中的模块名使用 `_`，modprobe 对 `-` 与 `_` 不作区分
+fn loaded_check_command(name: &str) -> String {
+    format!("grep -q {} /proc/modules", shell_quote(&format!("^{} ", name.replace('-', "_"))))
+}
+
+fn modprobe_command(config: &KernelModuleConfig) -> String {
+    let mut command = format!("modprobe {}", config.name);
+    for (key, value) in sorted_params(config) {
+        command.push(' ');
+        command.push_str(&shell_quote(&format!("{}={}", key, value)));
+    }
+    command
+}
+
+fn rmmod_command(name: &str) -> String {
+    format!("rmmod {}", name)
+}
+
+/// present 且 persist 时写入的文件（路径，内容）
+fn persist_files(config: &KernelModuleConfig) -> Vec<(String, String)> {
+    let mut files = vec![(
+        format!("{}/{}.conf", MODULES_LOAD_DIR, config.name),
+        format!("{}\n{}\n", MANAGED_HEADER, config.name),
+    )];
+    if !config.params.is_empty() {
+        let params: Vec<String> = sorted_params(config)
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        files.push((
+            format!("{}/{}.conf", MODPROBE_DIR, config.name),
+            format!("{}\noptions {} {}\n", MANAGED_HEADER, config.name, params.join(" ")),
+        ));
+    }
+    files
+}
+
+fn is_managed(content: &str) -> bool {
+    content.lines().next().map(str::trim_end) == Some(MANAGED_HEADER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTransport;
+    use crate::types::{CommandResult, HostConfig};
+    use std::collections::HashMap;
+
+    fn module(name: &str, params: &[(&str, &str)], persist: bool) -> KernelModuleConfig {
+        KernelModuleConfig {
+            name: name.to_string(),
+            params: params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+            persist,
+        }
+    }
+
+    #[test]
+    fn test_load_and_unload_commands() {
+        assert_eq!(modprobe_command(&module("overlay", &[], false)), "modprobe overlay");
+        assert_eq!(
+            modprobe_command(&module("dm_thin_pool", &[("zero_new", "N"), ("block_size", "128")], false)),
+            "modprobe dm_thin_pool 'block_size=128' 'zero_new=N'"
+        );
+        assert_eq!(rmmod_command("br_netfilter"), "rmmod br_netfilter");
+        assert_eq!(loaded_check_command("br-netfilter"), "grep -q '^br_netfilter ' /proc/modules");
+
+        assert!(validate_kernel_module(&module("br_netfilter", &[("a-b", "1")], false)).is_ok());
+        assert!(validate_kernel_module(&module("overlay; reboot", &[], false)).is_err());
+        assert!(validate_kernel_module(&module("overlay", &[("x y", "1")], false)).is_err());
+        assert!(validate_kernel_module(&module("overlay", &[("a", "1 b=2")], false)).is_err());
+        assert!(validate_kernel_module(&module("overlay", &[("a", "1\ninstall overlay /bin/sh")], false)).is_err());
+        assert!(validate_kernel_module(&module("overlay", &[("a", "")], false)).is_err());
+    }
+
+    #[test]
+    fn test_persist_files() {
+        assert_eq!(
+            persist_files(&module("overlay", &[], true)),
+            vec![(
+                "/etc/modules-load.d/overlay.conf".to_string(),
+                "# Managed by rs-ansible\noverlay\n".to_string()
+            )]
+        );
+
+        let files = persist_files(&module("dm_thin_pool", &[("zero_new", "N"), ("block_size", "128")], true));
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].0, "/etc/modprobe.d/dm_thin_pool.conf");
+        assert_eq!(files[1].1, "# Managed by rs-ansible\noptions dm_thin_pool block_size=128 zero_new=N\n");
+        assert!(files.iter().all(|(_, content)| is_managed(content)));
+        assert!(!is_managed("###\n### This configuration file was provided by the qemu package.\n"));
+        assert!(!is_managed(""));
+    }
+
+    #[test]
+    fn test_present_without_params_removes_stale_modprobe_file() {
+        let mock = MockTransport::new();
+        let ok = |stdout: &str| CommandResult { exit_code: 0, stdout: stdout.to_string(), stderr: String::new(), raw_stdout: None };
+        mock.on_command("node1", "/proc/modules", ok(""));
+        mock.put_file("node1", "/etc/modules-load.d/overlay.conf", b"# Managed by rs-ansible\noverlay\n");
+        mock.put_file("node1", "/etc/modprobe.d/overlay.conf", b"# Managed by rs-ansible\noptions overlay metacopy=on\n");
+        let transport = mock.factory()("node1", &HostConfig::default()).unwrap();
+        let client = SshClient::with_transport(HostConfig::default(), transport);
+
+        let result = client.manage_kernel_module(&module("overlay", &[], true), UserState::Present).unwrap();
+        assert!(result.changed);
+        assert_eq!(result.message, "Kernel module 'overlay' persisted");
+        let commands = mock.commands("node1");
+        assert!(!commands.iter().any(|c| c.starts_with("modprobe")));
+        assert_eq!(commands.last().unwrap(), "rm -f '/etc/modprobe.d/overlay.conf'");
+        assert_eq!(mock.file("node1", "/etc/modprobe.d/overlay.conf"), None);
+    }
+
+    #[test]
+    fn test_unmanaged_files_are_kept() {
+        let mock = MockTransport::new();
+        let loaded = CommandResult { exit_code: 0, stdout: String::new(), stderr: String::new(), raw_stdout: None };
+        mock.on_command("node1", "/proc/modules", loaded);
+        let packaged: &[u8] = b"###\n### This configuration file was provided by the qemu package.\n###\n#options kvm_intel nested=1\n";
+        mock.put_file("node1", "/etc/modprobe.d/kvm.conf", packaged);
+        mock.put_file("node1", "/etc/modules-load.d/kvm.conf", b"# Managed by rs-ansible\nkvm\n");
+        let transport = mock.factory()("node1", &HostConfig::default()).unwrap();
+        let client = SshClient::with_transport(HostConfig::default(), transport);
+
+        // 无参数时不删除软件包提供的 modprobe.d 文件
+        let result = client.manage_kernel_module(&module("kvm", &[], true), UserState::Present).unwrap();
+        assert!(!result.changed);
+        assert_eq!(mock.file("node1", "/etc/modprobe.d/kvm.conf").as_deref(), Some(packaged));
+
+        // 有参数时也不覆盖
+        client.manage_kernel_module(&module("kvm", &[("ignore_msrs", "1")], true), UserState::Present).unwrap();
+        assert_eq!(mock.file("node1", "/etc/modprobe.d/kvm.conf").as_deref(), Some(packaged));
+
+        // absent 只删除本模块写入的文件
+        let result = client.manage_kernel_module(&module("kvm", &[], true), UserState::Absent).unwrap();
+        assert!(result.message.contains("unpersisted"), "{}", result.message);
+        assert_eq!(mock.file("node1", "/etc/modules-load.d/kvm.conf"), None);
+        assert_eq!(mock.file("node1", "/etc/modprobe.d/kvm.conf").as_deref(), Some(packaged));
+        assert!(!mock.commands("node1").iter().any(|c| c.contains("rm -f '/etc/modprobe.d/kvm.conf'")));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::credentials::SecretString;
+use crate::error::AnsibleError;
+use crate::types::{CommandOptions, CommandResult, HostConfig};
+use super::transport::Transport;
+use std::io::prelude::*;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// 在控制端本机执行的传输实现（`transport: local`）
+///
+/// 命令通过 `sh -c` 执行，上传与下载直接读写本地文件，
+/// 因此复制、模板部署等操作与 SSH 主机共用同一套 hash 校验与幂等逻辑。
+/// 本地执行不分配 pty，`pty` 选项会被忽略。
+pub struct LocalTransport;
+
+impl Transport for LocalTransport {
+    fn connect(config: &HostConfig, _secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        info!("Using local connection for {}", config.hostname);
+        Ok(Self)
+    }
+
+    fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| AnsibleError::CommandExecutionError(format!("Failed to spawn local command: {}", e)))?;
+
+        // stdin 与输出在独立线程中读写，避免管道缓冲区写满导致死锁
+        let mut stdin = child.stdin.take();
+        let input = options.stdin.clone().unwrap_or_default();
+        let writer = thread::spawn(move || {
+            if let Some(ref mut stdin) = stdin {
+                // 命令可能不读取 stdin 就退出，写入失败可以忽略
+                let _ = stdin.write_all(&input);
+            }
+        });
+        let stdout = child.stdout.take().map(read_pipe);
+        let stderr = child.stderr.take().map(read_pipe);
+
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if let (Some(deadline), Some(timeout)) = (deadline, options.timeout)
+                && Instant::now() >= deadline
+            {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(AnsibleError::CommandExecutionError(format!(
+                    "Command timed out after {:?}: {}",
+                    timeout, command
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let _ = writer.join();
+        let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+            reader.map(|r| r.join().unwrap_or_default()).unwrap_or_default()
+        };
+
+        CommandResult::from_bytes(exit_code(status), collect(stdout), collect(stderr), options.output_encoding)
+    }
+
+    fn upload(
+        &self,
+        reader: &mut dyn Read,
+        _size: u64,
+        remote_path: &str,
+        mode: i32,
+    ) -> Result<u64, AnsibleError> {
+        let mut file = std::fs::File::create(remote_path).map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to create {}: {}", remote_path, e))
+        })?;
+        let bytes_transferred = std::io::copy(reader, &mut file).map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e))
+        })?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(remote_path, std::fs::Permissions::from_mode(mode as u32 & 0o7777))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        Ok(bytes_transferred)
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write) -> Result<u64, AnsibleError> {
+        let mut file = std::fs::File::open(remote_path).map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to open {}: {}", remote_path, e))
+        })?;
+        std::io::copy(&mut file, writer)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e)))
+    }
+}
+
+/// 在后台线程中读完管道内容
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+/// 按 shell 的惯例，被信号终止的进程退出码为 128 + 信号值
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(-1)
+}
@@ -0,0 +1,116 @@
+use crate::error::AnsibleError;
+use crate::types::{LogRotateResult, LogRotateStatus};
+use crate::utils::shell_quote;
+use super::SshClient;
+use tracing::{debug, error, info};
+
+const DEFAULT_LOGROTATE_CONFIG: &str = "/etc/logrotate.conf";
+
+impl SshClient {
+    /// 强制触发日志轮转（默认使用 /etc/logrotate.conf）
+    pub fn rotate_logs(&self, config_file: Option<&str>) -> Result<LogRotateResult, AnsibleError> {
+        self.run_logrotate(config_file, true)
+    }
+
+    /// 执行 logrotate，`force` 为 true 时即使未到轮转条件也会轮转
+    pub fn run_logrotate(&self, config_file: Option<&str>, force: bool) -> Result<LogRotateResult, AnsibleError> {
+        let config_file = config_file.unwrap_or(DEFAULT_LOGROTATE_CONFIG);
+        let cmd = format!(
+            "logrotate -v {}{} 2>&1",
+            if force { "-f " } else { "" },
+            shell_quote(config_file)
+        );
+
+        debug!("Running logrotate: {}", cmd);
+        let result = self.execute_command(&cmd)?;
+
+        if result.exit_code != 0 {
+            error!("logrotate failed on {}: {}", self.config.hostname, result.stdout);
+            return Err(AnsibleError::CommandError(format!(
+                "logrotate failed with exit code {}: {}",
+                result.exit_code, result.stdout
+            )));
+        }
+
+        let files_rotated = count_rotated_logs(&result.stdout);
+        info!("logrotate rotated {} log(s) using {}", files_rotated, config_file);
+
+        Ok(LogRotateResult {
+            files_rotated,
+            output: result.stdout,
+        })
+    }
+
+    /// 读取 logrotate 状态文件，获取每个日志的最近轮转时间
+    pub fn get_logrotate_status(&self) -> Result<Vec<LogRotateStatus>, AnsibleError> {
+        // 不同发行版的状态文件位置不同
+        let cmd = "cat /var/lib/logrotate/status 2>/dev/null || cat /var/lib/logrotate.status";
+        let result = self.execute_command(cmd)?;
+
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read logrotate status: {}",
+                result.stderr
+            )));
+        }
+
+        Ok(parse_logrotate_status(&result.stdout))
+    }
+}
+
+/// 统计 `logrotate -v` 输出中实际需要轮转的日志数量
+fn count_rotated_logs(output: &str) -> u32 {
+    output
+        .lines()
+        .filter(|line| line.trim() == "log needs rotating")
+        .count() as u32
+}
+
+/// 解析 logrotate 状态文件
+///
+/// 格式:
+/// ```text
+/// logrotate state -- version 2
+/// "/var/log/syslog" 2024-1-15-6:25:1
+/// ```
+fn parse_logrotate_status(content: &str) -> Vec<LogRotateStatus> {
+    content
+        .lines()
+        .filter(|line| line.starts_with('"'))
+        .filter_map(|line| {
+            let rest = &line[1..];
+            let end = rest.find('"')?;
+            let path = rest[..end].to_string();
+            let last_rotated = rest[end + 1..].trim().to_string();
+            Some(LogRotateStatus { path, last_rotated })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_logrotate_status() {
+        let content = r#"logrotate state -- version 2
+"/var/log/syslog" 2024-1-15-6:25:1
+"/var/log/apt/history.log" 2024-1-1-0:0:0
+"/var/log/nginx/access.log" 2024-1-15-6:25:1
+"#;
+        let status = parse_logrotate_status(content);
+        assert_eq!(status.len(), 3);
+        assert_eq!(status[0].path, "/var/log/syslog");
+        assert_eq!(status[0].last_rotated, "2024-1-15-6:25:1");
+        assert_eq!(status[1].path, "/var/log/apt/history.log");
+    }
+
+    #[test]
+    fn test_count_rotated_logs() {
+        let output = "rotating pattern: /var/log/syslog  forced from command line (7 rotations)\n\
+                      considering log /var/log/syslog\n  log needs rotating\n\
+                      considering log /var/log/mail.log\n  log does not need rotating\n\
+                      considering log /var/log/kern.log\n  log needs rotating\n";
+        assert_eq!(count_rotated_logs(output), 2);
+    }
+}
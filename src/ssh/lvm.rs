@@ -0,0 +1,185 @@
+use crate::error::AnsibleError;
+use crate::types::{LogicalVolume, LvmInfo, VolumeGroup};
+use super::SshClient;
+use serde::Deserialize;
+use tracing::info;
+
+const VGS_COMMAND: &str =
+    "vgs --reportformat json --units b --nosuffix -o vg_name,vg_size,vg_free,pv_count,lv_count";
+const LVS_COMMAND: &str =
+    "lvs --reportformat json --units b --nosuffix -o lv_name,vg_name,lv_size,lv_path,lv_attr";
+
+/// `--reportformat json` 的外层结构：`{"report": [{"vg": [...]}]}`
+#[derive(Deserialize)]
+struct Report<T> {
+    report: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct VgSection {
+    #[serde(default)]
+    vg: Vec<VgRow>,
+}
+
+#[derive(Deserialize)]
+struct LvSection {
+    #[serde(default)]
+    lv: Vec<LvRow>,
+}
+
+/// lvm 在 JSON 报告中以字符串输出所有字段
+#[derive(Deserialize)]
+struct VgRow {
+    vg_name: String,
+    vg_size: String,
+    vg_free: String,
+    pv_count: String,
+    lv_count: String,
+}
+
+#[derive(Deserialize)]
+struct LvRow {
+    lv_name: String,
+    vg_name: String,
+    lv_size: String,
+    #[serde(default)]
+    lv_path: String,
+    #[serde(default)]
+    lv_attr: String,
+}
+
+impl SshClient {
+    /// 获取 LVM 卷组及其逻辑卷（`vgs` / `lvs` 的 JSON 报告），需要 root 权限（become）
+    pub fn get_lvm_info(&self) -> Result<LvmInfo, AnsibleError> {
+        let vgs = self.run_lvm_report(VGS_COMMAND)?;
+        let lvs = self.run_lvm_report(LVS_COMMAND)?;
+        let info = parse_lvm_reports(&vgs, &lvs)?;
+        info!(
+            "Found {} volume group(s) on {}",
+            info.volume_groups.len(),
+            self.config.hostname
+        );
+        Ok(info)
+    }
+
+    fn run_lvm_report(&self, command: &str) -> Result<String, AnsibleError> {
+        let result = self.execute_command(command)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to run '{}': {}",
+                command.split_whitespace().next().unwrap_or(command),
+                result.stderr.trim()
+            )));
+        }
+        Ok(result.stdout)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(field: &str, value: &str) -> Result<T, AnsibleError> {
+    value.trim().parse().map_err(|_| {
+        AnsibleError::CommandError(format!("Unexpected value for {} in LVM report: '{}'", field, value))
+    })
+}
+
+fn parse_report<T: for<'de> Deserialize<'de>>(output: &str) -> Result<Vec<T>, AnsibleError> {
+    let report: Report<T> = serde_json::from_str(output)
+        .map_err(|e| AnsibleError::CommandError(format!("Failed to parse LVM JSON report: {}", e)))?;
+    Ok(report.report)
+}
+
+/// 合并 vgs 与 lvs 的报告，逻辑卷按卷组归类
+fn parse_lvm_reports(vgs_output: &str, lvs_output: &str) -> Result<LvmInfo, AnsibleError> {
+    let mut volume_groups = Vec::new();
+    for row in parse_report::<VgSection>(vgs_output)?.into_iter().flat_map(|s| s.vg) {
+        volume_groups.push(VolumeGroup {
+            size_bytes: parse_number("vg_size", &row.vg_size)?,
+            free_bytes: parse_number("vg_free", &row.vg_free)?,
+            pv_count: parse_number("pv_count", &row.pv_count)?,
+            lv_count: parse_number("lv_count", &row.lv_count)?,
+            name: row.vg_name,
+            logical_volumes: Vec::new(),
+        });
+    }
+
+    for row in parse_report::<LvSection>(lvs_output)?.into_iter().flat_map(|s| s.lv) {
+        let volume = LogicalVolume {
+            size_bytes: parse_number("lv_size", &row.lv_size)?,
+            // lv_attr 第 5 位为激活状态，例如 "-wi-ao----"
+            active: row.lv_attr.chars().nth(4) == Some('a'),
+            path: row.lv_path,
+            name: row.lv_name,
+            vg: row.vg_name,
+        };
+        if let Some(group) = volume_groups.iter_mut().find(|g| g.name == volume.vg) {
+            group.logical_volumes.push(volume);
+        }
+    }
+
+    Ok(LvmInfo { volume_groups })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VGS: &str = r#"  {
+      "report": [
+          {
+              "vg": [
+                  {"vg_name":"data", "vg_size":"2199019061248", "vg_free":"1099507433472", "pv_count":"2", "lv_count":"2"},
+                  {"vg_name":"ubuntu-vg", "vg_size":"52072284160", "vg_free":"0", "pv_count":"1", "lv_count":"1"}
+              ]
+          }
+      ]
+  }
+"#;
+
+    const LVS: &str = r#"  {
+      "report": [
+          {
+              "lv": [
+                  {"lv_name":"pg", "vg_name":"data", "lv_size":"1073741824000", "lv_path":"/dev/data/pg", "lv_attr":"-wi-ao----"},
+                  {"lv_name":"backup", "vg_name":"data", "lv_size":"25769803776", "lv_path":"/dev/data/backup", "lv_attr":"-wi-------"},
+                  {"lv_name":"ubuntu-lv", "vg_name":"ubuntu-vg", "lv_size":"52072284160", "lv_path":"/dev/ubuntu-vg/ubuntu-lv", "lv_attr":"-wi-ao----"}
+              ]
+          }
+      ]
+  }
+"#;
+
+    #[test]
+    fn test_parse_lvm_reports() {
+        let info = parse_lvm_reports(VGS, LVS).unwrap();
+        assert_eq!(info.volume_groups.len(), 2);
+
+        let data = &info.volume_groups[0];
+        assert_eq!(data.name, "data");
+        assert_eq!(data.size_bytes, 2_199_019_061_248);
+        assert_eq!(data.free_bytes, 1_099_507_433_472);
+        assert_eq!((data.pv_count, data.lv_count), (2, 2));
+        assert_eq!(
+            data.logical_volumes[0],
+            LogicalVolume {
+                name: "pg".to_string(),
+                vg: "data".to_string(),
+                size_bytes: 1_073_741_824_000,
+                path: "/dev/data/pg".to_string(),
+                active: true,
+            }
+        );
+        assert!(!data.logical_volumes[1].active);
+        assert_eq!(info.volume_groups[1].logical_volumes[0].name, "ubuntu-lv");
+    }
+
+    #[test]
+    fn test_parse_lvm_reports_empty_and_invalid() {
+        let empty = r#"{"report": [{"vg": []}]}"#;
+        let info = parse_lvm_reports(empty, r#"{"report": [{"lv": []}]}"#).unwrap();
+        assert!(info.volume_groups.is_empty());
+
+        // 未使用 --units b 时大小带单位，无法按字节解析
+        let with_units = r#"{"report": [{"vg": [{"vg_name":"vg0", "vg_size":"<100.00g", "vg_free":"0", "pv_count":"1", "lv_count":"0"}]}]}"#;
+        assert!(parse_lvm_reports(with_units, empty).is_err());
+        assert!(parse_lvm_reports("not json", empty).is_err());
+    }
+}
@@ -1,10 +1,58 @@
 // SSH 客户端核心模块
 mod client;
+mod transport;
+mod async_transport;
+mod async_client;
+mod local;
+mod docker;
+#[cfg(feature = "async-ssh")]
+mod async_ssh;
 mod file_transfer;
 mod hash;
 mod system_info;
 mod user;
 mod template;
+mod container_runtime;
+mod logrotate;
+mod dns;
+mod pipeline;
+mod permissions;
+mod temp_file;
+mod scheduled_tasks;
+mod ssh_key;
+mod disk_benchmark;
+mod iptables;
+mod dmesg;
+mod hugepages;
+mod raid;
+mod ssh_audit;
+mod repo;
+mod lvm;
+mod blockinfile;
+mod health;
+mod updates;
+mod cgroup;
+mod tcp;
+mod kernel_module;
+mod trust_store;
+mod host_metrics;
+mod fileset;
+mod version;
+mod acl;
+mod pam;
+mod sysctl;
+mod clock;
 
 // 重新导出 SshClient，使外部可以直接使用
 pub use client::SshClient;
+pub use transport::{Transport, Ssh2Transport};
+pub use local::LocalTransport;
+pub use docker::DockerTransport;
+pub use async_transport::AsyncTransport;
+pub use async_client::AsyncSshClient;
+#[cfg(feature = "async-ssh")]
+pub use async_ssh::RusshTransport;
+pub use template::TemplateCache;
+pub use version::parse_version;
+pub use temp_file::RemoteTempFile;
+pub use file_transfer::REPORT_INTERVAL_BYTES;
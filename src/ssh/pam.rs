@@ -0,0 +1,319 @@
+use crate::error::AnsibleError;
+use crate::types::{PamConfig, PamLine};
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use super::SshClient;
+use std::collections::HashSet;
+use tracing::{debug, info};
+
+const PAM_DIR: &str = "/etc/pam.d";
+const PWQUALITY_CONF: &str = "/etc/security/pwquality.conf";
+/// pam_pwquality 不接受小于 6 的 minlen
+const MIN_PASSWORD_LENGTH: u8 = 6;
+/// minclass 的上限：数字、大写、小写、其他字符四类
+const MAX_CHARACTER_CLASSES: u8 = 4;
+
+impl SshClient {
+    /// 读取并解析 `/etc/pam.d/<service>`，注释与空行被忽略，`\` 结尾的行与下一行合并
+    pub fn get_pam_config(&self, service: &str) -> Result<PamConfig, AnsibleError> {
+        validate_service(service)?;
+        let path = format!("{}/{}", PAM_DIR, service);
+        let result = self.execute_command(&format!("cat {}", shell_quote(&path)))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to read {}: {}",
+                path,
+                result.stderr.trim()
+            )));
+        }
+        Ok(PamConfig {
+            service: service.to_string(),
+            lines: parse_pam_config(&result.stdout),
+        })
+    }
+
+    /// 服务的 PAM 配置是否使用指定模块（可写为 `pam_faillock`、`pam_faillock.so` 或完整路径）
+    ///
+    /// 同时检查通过 `@include`、`include`、`substack` 引入的文件；被引入的文件不存在时忽略。
+    pub fn check_pam_module(&self, service: &str, module: &str) -> Result<bool, AnsibleError> {
+        let mut pending = vec![service.to_string()];
+        let mut visited = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            let config = match self.get_pam_config(&name) {
+                Ok(config) => config,
+                Err(e) if name == service => return Err(e),
+                Err(e) => {
+                    debug!("Skipping included PAM file '{}': {}", name, e);
+                    continue;
+                }
+            };
+            if config.lines.iter().any(|line| !line.type_.starts_with('@') && module_matches(&line.module, module)) {
+                return Ok(true);
+            }
+            pending.extend(included_services(&config));
+        }
+        Ok(false)
+    }
+
+    /// 通过 `/etc/security/pwquality.conf` 设置密码最小长度与至少包含的字符类别数（minlen、minclass）
+    ///
+    /// 已有的同名设置（包括被注释的默认值）被原地替换，否则追加到文件末尾；返回文件是否被修改。
+    /// 只有在密码栈中启用了 pam_pwquality 时生效，可用 `check_pam_module` 确认。
+    pub fn set_pam_password_policy(&self, min_length: u8, complexity: u8) -> Result<bool, AnsibleError> {
+        if min_length < MIN_PASSWORD_LENGTH {
+            return Err(AnsibleError::ValidationError(format!(
+                "Minimum password length must be at least {}, got {}",
+                MIN_PASSWORD_LENGTH, min_length
+            )));
+        }
+        if complexity > MAX_CHARACTER_CLASSES {
+            return Err(AnsibleError::ValidationError(format!(
+                "Password complexity must be between 0 and {} character classes, got {}",
+                MAX_CHARACTER_CLASSES, complexity
+            )));
+        }
+
+        let current = self.execute_command(&format!("cat {} 2>/dev/null", PWQUALITY_CONF))?;
+        let current = if current.exit_code == 0 { current.stdout } else { String::new() };
+        let desired = apply_password_policy(
+            &current,
+            &[("minlen", min_length.to_string()), ("minclass", complexity.to_string())],
+        );
+        if desired == current {
+            debug!("{} already up to date", PWQUALITY_CONF);
+            return Ok(false);
+        }
+
+        let temp_path = generate_remote_temp_path(PWQUALITY_CONF);
+        let result = self.execute_command(&format!(
+            "printf '%s' {} > {temp} && chmod 644 {temp} && mv -f {temp} {}",
+            shell_quote(&desired),
+            PWQUALITY_CONF,
+            temp = shell_quote(&temp_path)
+        ))?;
+        if result.exit_code != 0 {
+            let _ = self.execute_command(&format!("rm -f {}", shell_quote(&temp_path)));
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to write {}: {}",
+                PWQUALITY_CONF,
+                result.stderr.trim()
+            )));
+        }
+        info!(
+            "Set password policy on {}: minlen={}, minclass={}",
+            self.config.hostname, min_length, complexity
+        );
+        Ok(true)
+    }
+}
+
+/// 服务名对应 `/etc/pam.d` 下的文件名，不允许包含路径
+fn validate_service(service: &str) -> Result<(), AnsibleError> {
+    if service.is_empty() || service.contains('/') || service == "." || service == ".." {
+        return Err(AnsibleError::ValidationError(format!("Invalid PAM service name: '{}'", service)));
+    }
+    Ok(())
+}
+
+fn parse_pam_config(content: &str) -> Vec<PamLine> {
+    let mut lines = Vec::new();
+    let mut logical = String::new();
+    for raw in content.lines() {
+        if let Some(continued) = raw.strip_suffix('\\') {
+            logical.push_str(continued);
+            logical.push(' ');
+            continue;
+        }
+        logical.push_str(raw);
+        let line = std::mem::take(&mut logical);
+        let line = line.split('#').next().unwrap_or_default();
+        if let Some(parsed) = parse_pam_line(&tokenize(line)) {
+            lines.push(parsed);
+        }
+    }
+    lines
+}
+
+/// 按空白分词，`[...]` 内的空白不分割
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_brackets = false;
+    for c in line.chars() {
+        match c {
+            '[' if current.is_empty() => {
+                in_brackets = true;
+                current.push(c);
+            }
+            ']' if in_brackets => {
+                in_brackets = false;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_brackets => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_pam_line(tokens: &[String]) -> Option<PamLine> {
+    let first = tokens.first()?;
+    if first.starts_with('@') {
+        return Some(PamLine {
+            type_: first.clone(),
+            control: String::new(),
+            module: tokens.get(1)?.clone(),
+            arguments: tokens[2..].to_vec(),
+        });
+    }
+    let [type_, control, module, arguments @ ..] = tokens else {
+        return None;
+    };
+    Some(PamLine {
+        type_: type_.clone(),
+        control: control.clone(),
+        module: module.clone(),
+        arguments: arguments.to_vec(),
+    })
+}
+
+/// 通过 `@include`、`include`、`substack` 引入的服务
+fn included_services(config: &PamConfig) -> Vec<String> {
+    config
+        .lines
+        .iter()
+        .filter(|line| line.type_ == "@include" || matches!(line.control.as_str(), "include" | "substack"))
+        .map(|line| line.module.clone())
+        .filter(|name| validate_service(name).is_ok())
+        .collect()
+}
+
+/// 按模块文件名比较，忽略目录与 `.so` 后缀
+fn module_matches(configured: &str, wanted: &str) -> bool {
+    let normalize = |module: &str| {
+        let name = module.rsplit('/').next().unwrap_or(module);
+        name.strip_suffix(".so").unwrap_or(name).to_string()
+    };
+    normalize(configured) == normalize(wanted)
+}
+
+/// `key = value` 形式的设置名；注释行与其他行返回 None
+fn config_key(line: &str) -> Option<&str> {
+    let (key, _) = line.split_once('=')?;
+    let key = key.trim();
+    (!key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')).then_some(key)
+}
+
+/// 替换已有设置；没有生效的设置时替换第一条被注释的同名设置，否则追加
+fn apply_password_policy(content: &str, settings: &[(&str, String)]) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    for (key, value) in settings {
+        let setting = format!("{} = {}", key, value);
+        let is_active = |line: &str| !line.trim_start().starts_with('#') && config_key(line) == Some(key);
+        let is_commented =
+            |line: &str| line.trim_start().strip_prefix('#').and_then(config_key) == Some(key);
+        if lines.iter().any(|line| is_active(line)) {
+            for line in lines.iter_mut().filter(|line| is_active(line)) {
+                *line = setting.clone();
+            }
+        } else if let Some(line) = lines.iter_mut().find(|line| is_commented(line)) {
+            *line = setting;
+        } else {
+            lines.push(setting);
+        }
+    }
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pam_sshd() {
+        let content = "\
+# PAM configuration for the Secure Shell service
+
+# Standard Un*x authentication.
+@include common-auth
+
+# Disallow non-root logins when /etc/nologin exists.
+account    required     pam_nologin.so
+
+auth [success=1 default=ignore] pam_unix.so nullok
+auth    requisite     pam_deny.so
+account  include  password-auth
+session [success=ok ignore=ignore module_unknown=ignore default=bad]        pam_selinux.so close
+session    required     pam_loginuid.so
+-session   optional     pam_systemd.so
+session    optional     pam_motd.so  motd=/run/motd.dynamic
+session    optional     pam_env.so user_readenv=1 \\
+                        envfile=/etc/default/locale
+session    optional     pam_exec.so [/usr/local/bin/notify --level info]
+";
+        let lines = parse_pam_config(content);
+        assert_eq!(lines.len(), 11);
+        assert_eq!(
+            lines[0],
+            PamLine {
+                type_: "@include".to_string(),
+                control: String::new(),
+                module: "common-auth".to_string(),
+                arguments: Vec::new(),
+            }
+        );
+        assert_eq!(lines[2].control, "[success=1 default=ignore]");
+        assert_eq!((lines[2].module.as_str(), lines[2].arguments.clone()), ("pam_unix.so", vec!["nullok".to_string()]));
+        assert_eq!(lines[5].module, "pam_selinux.so");
+        assert_eq!(lines[7].type_, "-session");
+        assert_eq!(lines[9].arguments, vec!["user_readenv=1", "envfile=/etc/default/locale"]);
+        assert_eq!(lines[10].arguments, vec!["[/usr/local/bin/notify --level info]"]);
+
+        let config = PamConfig { service: "sshd".to_string(), lines };
+        assert_eq!(included_services(&config), vec!["common-auth", "password-auth"]);
+        assert!(module_matches("/lib/x86_64-linux-gnu/security/pam_unix.so", "pam_unix"));
+        assert!(module_matches("pam_systemd.so", "pam_systemd.so"));
+        assert!(!module_matches("pam_unix.so", "pam_unix2"));
+
+        assert!(validate_service("sshd").is_ok());
+        assert!(validate_service("../shadow").is_err());
+    }
+
+    #[test]
+    fn test_apply_password_policy() {
+        let settings = [("minlen", "12".to_string()), ("minclass", "3".to_string())];
+        let content = "\
+# Minimum acceptable size for the new password (plus one if
+# credits are not disabled which is the default). (See pam_cracklib manual.)
+# Cannot be set to lower value than 6.
+# minlen = 8
+#
+# The minimum number of required classes of characters for the new
+# password (digits, uppercase, lowercase, others).
+# minclass = 0
+";
+        let updated = apply_password_policy(content, &settings);
+        assert!(updated.contains("\nminlen = 12\n"));
+        assert!(updated.contains("\nminclass = 3\n"));
+        assert!(!updated.contains("# minlen = 8"));
+        assert_eq!(updated.lines().count(), content.lines().count());
+        assert_eq!(apply_password_policy(&updated, &settings), updated);
+
+        assert_eq!(
+            apply_password_policy("minlen=9\ndcredit = -1\nminlen = 10\n", &settings),
+            "minlen = 12\ndcredit = -1\nminlen = 12\nminclass = 3\n"
+        );
+        assert_eq!(apply_password_policy("", &settings), "minlen = 12\nminclass = 3\n");
+    }
+}
@@ -0,0 +1,292 @@
+use crate::error::AnsibleError;
+use crate::types::{CommandResult, DirSpec, EnsureDirsResult, PermissionsOptions};
+use crate::utils::{shell_quote, FileMode};
+use super::SshClient;
+use tracing::{debug, info};
+
+/// 通过 stat 获取的文件属性
+#[derive(Debug, PartialEq)]
+pub(super) struct FileStat {
+    mode: String,
+    owner: String,
+    group: String,
+}
+
+impl SshClient {
+    /// 幂等地设置远程路径的权限与所有者，返回是否发生了变更
+    ///
+    /// 非递归时先通过 `stat` 比较当前属性，一致则不执行任何修改；符号形式的权限（如 `u+x`）无法与
+    /// `stat` 结果直接比较，使用 `chmod -c` 并根据输出判断是否修改。
+    /// 递归时使用 `chmod -R -c`/`chown -R -c`，根据输出判断是否有文件被修改。
+    /// 所有者与用户组只接受用户名/组名或数字 ID，否则返回 `ValidationError`。
+    pub fn set_file_attributes(&self, options: &PermissionsOptions) -> Result<bool, AnsibleError> {
+        let path = shell_quote(&options.path);
+        let mode = options.mode.as_deref().map(FileMode::parse).transpose()?;
+        validate_ownership(options.owner.as_deref(), options.group.as_deref())?;
+
+        if options.recursive {
+            return self.set_file_attributes_recursive(options, mode.as_ref(), &path);
+        }
+
+        let current = self.stat_file(&options.path)?;
+        debug!("Current attributes of {}: {:?}", options.path, current);
+
+        let mut changed = false;
+        for command in attribute_commands(options, mode.as_ref(), &current) {
+            let output = self.run_attribute_command(&command.command, command.what, &command.value)?;
+            changed |= command.changed(&output);
+        }
+
+        if changed {
+            info!("Updated attributes of {}", options.path);
+        }
+        Ok(changed)
+    }
+
+    fn set_file_attributes_recursive(
+        &self,
+        options: &PermissionsOptions,
+        mode: Option<&FileMode>,
+        path: &str,
+    ) -> Result<bool, AnsibleError> {
+        let mut changed = false;
+
+        if let Some(mode) = mode {
+            let mode = mode.to_chmod_arg();
+            let output = self.run_attribute_command(&format!("chmod -R -c {} {}", mode, path), "permissions", &mode)?;
+            changed |= !output.trim().is_empty();
+        }
+
+        if options.owner.is_some() || options.group.is_some() {
+            let chown_user = match (&options.owner, &options.group) {
+                (Some(owner), Some(group)) => format!("{}:{}", owner, group),
+                (Some(owner), None) => owner.clone(),
+                (None, Some(group)) => format!(":{}", group),
+                (None, None) => unreachable!(),
+            };
+            let output = self.run_attribute_command(&format!("chown -R -c {} {}", chown_user, path), "owner", &chown_user)?;
+            changed |= !output.trim().is_empty();
+        }
+
+        if changed {
+            info!("Recursively updated attributes under {}", options.path);
+        }
+        Ok(changed)
+    }
+
+    /// 按顺序确保每个目录存在（`mkdir -p`），并只在属性不一致时设置权限与所有者
+    ///
+    /// 只设置列出的目录本身的属性，`mkdir -p` 创建的中间目录使用默认属性，需要时应一并列出（父目录在前）。
+    /// 路径已存在但不是目录时返回错误。
+    pub fn ensure_dirs(&self, dirs: &[DirSpec]) -> Result<EnsureDirsResult, AnsibleError> {
+        for dir in dirs {
+            if let Some(ref mode) = dir.mode {
+                FileMode::parse(mode)?;
+            }
+            validate_ownership(dir.owner.as_deref(), dir.group.as_deref())?;
+        }
+
+        let mut result = EnsureDirsResult::default();
+        for dir in dirs {
+            let path = shell_quote(&dir.path);
+            if self.execute_command(&format!("test -d {}", path))?.exit_code == 0 {
+                result.present.push(dir.path.clone());
+            } else {
+                let mkdir = self.execute_command(&format!("mkdir -p {}", path))?;
+                if mkdir.exit_code != 0 {
+                    return Err(AnsibleError::FileOperationError(format!(
+                        "Failed to create directory {}: {}",
+                        dir.path,
+                        mkdir.stderr.trim()
+                    )));
+                }
+                info!("Created directory {} on {}", dir.path, self.config.hostname);
+                result.created.push(dir.path.clone());
+            }
+
+            let attributes = PermissionsOptions {
+                path: dir.path.clone(),
+                mode: dir.mode.clone(),
+                owner: dir.owner.clone(),
+                group: dir.group.clone(),
+                recursive: false,
+            };
+            let has_attributes = dir.mode.is_some() || dir.owner.is_some() || dir.group.is_some();
+            if has_attributes && self.set_file_attributes(&attributes)? {
+                result.updated.push(dir.path.clone());
+            }
+        }
+        result.changed = !result.created.is_empty() || !result.updated.is_empty();
+        Ok(result)
+    }
+
+    /// 执行 chmod/chown/chgrp 命令，返回其标准输出
+    fn run_attribute_command(&self, cmd: &str, what: &str, value: &str) -> Result<String, AnsibleError> {
+        attribute_output(self.execute_command(cmd)?, what, value)
+    }
+
+    fn stat_file(&self, path: &str) -> Result<FileStat, AnsibleError> {
+        parse_stat_result(path, &self.execute_command(&stat_command(path))?)
+    }
+}
+
+/// 非递归设置属性时需要执行的一条 chmod/chown/chgrp 命令
+pub(super) struct AttributeCommand {
+    pub(super) command: String,
+    pub(super) what: &'static str,
+    pub(super) value: String,
+    reports_changes: bool, // `chmod -c` 只在确实修改时输出
+}
+
+impl AttributeCommand {
+    /// 根据命令输出判断是否发生了变更
+    pub(super) fn changed(&self, output: &str) -> bool {
+        !self.reports_changes || !output.trim().is_empty()
+    }
+}
+
+/// 与当前属性比较，列出需要执行的修改命令（同步与异步客户端共用）
+pub(super) fn attribute_commands(
+    options: &PermissionsOptions,
+    mode: Option<&FileMode>,
+    current: &FileStat,
+) -> Vec<AttributeCommand> {
+    let path = shell_quote(&options.path);
+    let mut commands = Vec::new();
+
+    match mode {
+        Some(FileMode::Octal(value)) if !mode_matches(&current.mode, *value) => {
+            let mode = format!("{:04o}", value);
+            commands.push(AttributeCommand {
+                command: format!("chmod {} {}", mode, path),
+                what: "permissions",
+                value: mode,
+                reports_changes: false,
+            });
+        }
+        Some(FileMode::Symbolic(mode)) => commands.push(AttributeCommand {
+            command: format!("chmod -c {} {}", mode, path),
+            what: "permissions",
+            value: mode.clone(),
+            reports_changes: true,
+        }),
+        _ => {}
+    }
+
+    let owner_changed = options.owner.as_ref().is_some_and(|o| o != &current.owner);
+    let group_changed = options.group.as_ref().is_some_and(|g| g != &current.group);
+
+    if owner_changed || (group_changed && options.owner.is_some()) {
+        let owner = options.owner.as_deref().unwrap_or_default();
+        let chown_user = match options.group {
+            Some(ref group) => format!("{}:{}", owner, group),
+            None => owner.to_string(),
+        };
+        commands.push(AttributeCommand {
+            command: format!("chown {} {}", chown_user, path),
+            what: "owner",
+            value: chown_user,
+            reports_changes: false,
+        });
+    } else if group_changed && let Some(ref group) = options.group {
+        commands.push(AttributeCommand {
+            command: format!("chgrp {} {}", group, path),
+            what: "group",
+            value: group.clone(),
+            reports_changes: false,
+        });
+    }
+    commands
+}
+
+/// chmod/chown/chgrp 的结果，失败时返回错误
+pub(super) fn attribute_output(result: CommandResult, what: &str, value: &str) -> Result<String, AnsibleError> {
+    if result.exit_code != 0 {
+        return Err(AnsibleError::FileOperationError(format!(
+            "Failed to set file {} {}: {}",
+            what, value, result.stderr
+        )));
+    }
+    Ok(result.stdout)
+}
+
+pub(super) fn stat_command(path: &str) -> String {
+    format!("stat -c '%a %U %G' {}", shell_quote(path))
+}
+
+pub(super) fn parse_stat_result(path: &str, result: &CommandResult) -> Result<FileStat, AnsibleError> {
+    if result.exit_code != 0 {
+        return Err(AnsibleError::FileOperationError(format!(
+            "Failed to stat {}: {}",
+            path, result.stderr
+        )));
+    }
+    parse_stat_output(&result.stdout).ok_or_else(|| {
+        AnsibleError::FileOperationError(format!("Unexpected stat output: {}", result.stdout))
+    })
+}
+
+fn parse_stat_output(output: &str) -> Option<FileStat> {
+    let mut parts = output.split_whitespace();
+    Some(FileStat {
+        mode: parts.next()?.to_string(),
+        owner: parts.next()?.to_string(),
+        group: parts.next()?.to_string(),
+    })
+}
+
+/// 比较 `stat` 报告的八进制权限
+fn mode_matches(current: &str, desired: u32) -> bool {
+    u32::from_str_radix(current, 8).ok() == Some(desired)
+}
+
+/// 所有者与用户组会直接拼入 chown/chgrp 命令，只允许用户名/组名字符或数字 ID
+pub(super) fn validate_ownership(owner: Option<&str>, group: Option<&str>) -> Result<(), AnsibleError> {
+    for (kind, name) in [("owner", owner), ("group", group)] {
+        let Some(name) = name else { continue };
+        let body = name.strip_suffix('$').unwrap_or(name);
+        let valid = body.chars().next().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            && body.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid {
+            return Err(AnsibleError::ValidationError(format!("Invalid {} name: '{}'", kind, name)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat_output() {
+        let stat = parse_stat_output("644 root www-data\n").unwrap();
+        assert_eq!(
+            stat,
+            FileStat {
+                mode: "644".to_string(),
+                owner: "root".to_string(),
+                group: "www-data".to_string()
+            }
+        );
+        assert!(parse_stat_output("644").is_none());
+    }
+
+    #[test]
+    fn test_mode_matches() {
+        assert!(mode_matches("644", 0o644));
+        assert!(mode_matches("4755", 0o4755));
+        assert!(!mode_matches("644", 0o755));
+    }
+
+    #[test]
+    fn test_validate_ownership() {
+        assert!(validate_ownership(Some("www-data"), Some("app_users")).is_ok());
+        assert!(validate_ownership(Some("1000"), Some("host01$")).is_ok());
+        assert!(validate_ownership(None, None).is_ok());
+        assert!(validate_ownership(Some("root; reboot"), None).is_err());
+        assert!(validate_ownership(None, Some("$(id)")).is_err());
+        assert!(validate_ownership(Some(""), None).is_err());
+        assert!(validate_ownership(Some("-R"), None).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::error::AnsibleError;
+use crate::types::{CommandBatchOptions, CommandResult};
+use crate::utils::generate_temp_suffix;
+use super::SshClient;
+use tracing::{debug, info};
+
+impl SshClient {
+    /// 在同一会话上依次执行多条命令（每条命令使用独立的 channel）
+    pub fn execute_commands(&self, commands: &[&str]) -> Result<Vec<CommandResult>, AnsibleError> {
+        self.execute_commands_with_options(commands, &CommandBatchOptions::default())
+    }
+
+    /// 将多条命令合并为一次执行（fast 模式），省去每条命令建立 channel 的往返延迟
+    ///
+    /// 每条命令在独立的子 shell 中运行，通过唯一的分隔标记拆分输出并捕获各自的退出码。
+    pub fn execute_commands_batched(&self, commands: &[&str]) -> Result<Vec<CommandResult>, AnsibleError> {
+        self.execute_commands_with_options(commands, &CommandBatchOptions { fast: true, ..Default::default() })
+    }
+
+    /// 依次执行多条命令，保留每条命令各自的退出码和输出
+    ///
+    /// `stop_on_failure` 为 true 时，某条命令以非零状态退出后不再执行后续命令，
+    /// 返回的结果只包含已执行的命令（最后一条即失败的命令）。
+    pub fn execute_commands_with_options(
+        &self,
+        commands: &[&str],
+        options: &CommandBatchOptions,
+    ) -> Result<Vec<CommandResult>, AnsibleError> {
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        if !options.fast {
+            let mut results = Vec::with_capacity(commands.len());
+            for cmd in commands {
+                let result = self.execute_command(cmd)?;
+                let failed = result.exit_code != 0;
+                results.push(result);
+                if failed && options.stop_on_failure {
+                    debug!("Command '{}' failed, skipping {} remaining command(s)", cmd, commands.len() - results.len());
+                    break;
+                }
+            }
+            return Ok(results);
+        }
+
+        let sentinel = format!("__RS_ANSIBLE_{}", generate_temp_suffix().replace('.', "_"));
+        let script = build_batched_script(commands, &sentinel, options.stop_on_failure);
+        debug!("Executing {} commands in one batch", commands.len());
+
+        let combined = self.execute_command(&script)?;
+        let results = split_batched_output(&combined, commands.len(), &sentinel, options.stop_on_failure)?;
+
+        info!(
+            "Batched execution of {}/{} commands on '{}' completed",
+            results.len(),
+            commands.len(),
+            self.config.hostname
+        );
+        Ok(results)
+    }
+}
+
+/// 构建批量执行脚本
+fn build_batched_script(commands: &[&str], sentinel: &str, stop_on_failure: bool) -> String {
+    let mut script = String::new();
+    for (i, cmd) in commands.iter().enumerate() {
+        script.push_str(&format!("printf '%s\\n' '{s}:BEGIN:{i}'; printf '%s\\n' '{s}:BEGIN:{i}' >&2\n", s = sentinel, i = i));
+        // 子 shell 隔离 exit/cd 等副作用，与独立执行的语义保持一致
+        script.push_str(&format!("(\n{}\n)\n", cmd));
+        script.push_str(&format!(
+            "__rc=$?; printf '\\n%s:%s\\n' '{s}:END:{i}' \"$__rc\"; printf '\\n%s\\n' '{s}:END:{i}' >&2\n",
+            s = sentinel,
+            i = i
+        ));
+        if stop_on_failure {
+            script.push_str("[ \"$__rc\" -eq 0 ] || exit 0\n");
+        }
+    }
+    script
+}
+
+/// 从合并输出中截取第 i 条命令的内容
+fn extract_section<'a>(output: &'a str, sentinel: &str, index: usize) -> Option<(&'a str, &'a str)> {
+    let begin = format!("{}:BEGIN:{}\n", sentinel, index);
+    let end = format!("\n{}:END:{}", sentinel, index);
+    let start = output.find(&begin)? + begin.len();
+    let len = output[start..].find(&end)?;
+    let rest = &output[start + len + end.len()..];
+    Some((&output[start..start + len], rest))
+}
+
+/// 拆分批量执行的输出
+fn split_batched_output(
+    combined: &CommandResult,
+    count: usize,
+    sentinel: &str,
+    stop_on_failure: bool,
+) -> Result<Vec<CommandResult>, AnsibleError> {
+    let mut results: Vec<CommandResult> = Vec::with_capacity(count);
+
+    for i in 0..count {
+        // 前一条命令失败后脚本已主动退出
+        if stop_on_failure && results.last().is_some_and(|r| r.exit_code != 0) {
+            break;
+        }
+        let Some((stdout, rest)) = extract_section(&combined.stdout, sentinel, i) else {
+            // 之后的命令没有执行（例如整个脚本被中断）
+            return Err(AnsibleError::CommandExecutionError(format!(
+                "Batched command {} did not complete (exit code {}): {}",
+                i, combined.exit_code, combined.stderr
+            )));
+        };
+
+        let exit_code = rest
+            .strip_prefix(':')
+            .and_then(|r| r.lines().next())
+            .and_then(|code| code.trim().parse().ok())
+            .ok_or_else(|| {
+                AnsibleError::CommandExecutionError(format!("Failed to parse exit code of batched command {}", i))
+            })?;
+
+        let stderr = extract_section(&combined.stderr, sentinel, i)
+            .map(|(stderr, _)| stderr)
+            .unwrap_or_default();
+
+        results.push(CommandResult {
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            raw_stdout: None,
+        });
+    }
+
+    Ok(results)
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+    use crate::utils::run_local_command;
+
+    #[test]
+    fn test_batched_script_preserves_individual_results() {
+        let commands = ["echo one", "printf 'no-newline'", "echo err >&2; exit 3", "echo after"];
+        let sentinel = "__RS_ANSIBLE_TEST";
+        let script = build_batched_script(&commands, sentinel, false);
+
+        let combined = run_local_command(&script).unwrap();
+        let results = split_batched_output(&combined, commands.len(), sentinel, false).unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].stdout, "one\n");
+        assert_eq!(results[0].exit_code, 0);
+        assert_eq!(results[1].stdout, "no-newline");
+        assert_eq!(results[2].exit_code, 3);
+        assert_eq!(results[2].stderr, "err\n");
+        assert_eq!(results[2].stdout, "");
+        assert_eq!(results[3].stdout, "after\n");
+
+        // 失败后停止：后续命令不执行，结果截止到失败的命令
+        let script = build_batched_script(&commands, sentinel, true);
+        let combined = run_local_command(&script).unwrap();
+        let results = split_batched_output(&combined, commands.len(), sentinel, true).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2].exit_code, 3);
+        assert!(!combined.stdout.contains("after"));
+    }
+}
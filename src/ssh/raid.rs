@@ -0,0 +1,215 @@
+use crate::error::AnsibleError;
+use crate::types::RaidArray;
+use super::SshClient;
+use tracing::{info, warn};
+
+impl SshClient {
+    /// 获取所有 mdadm 软件 RAID 阵列的状态
+    ///
+    /// 先通过 `mdadm --detail --scan --verbose` 列出阵列，再逐个读取详细状态。
+    /// 主机上没有阵列时返回空列表。需要 root 权限（become）。
+    pub fn get_raid_arrays(&self) -> Result<Vec<RaidArray>, AnsibleError> {
+        let result = self.execute_command("mdadm --detail --scan --verbose")?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to scan RAID arrays: {}",
+                result.stderr.trim()
+            )));
+        }
+
+        let mut arrays = Vec::new();
+        for scanned in parse_scan(&result.stdout) {
+            match self.get_raid_array_detail(&scanned.device) {
+                Ok(detail) => arrays.push(detail),
+                Err(e) => {
+                    // 单个阵列读取失败时保留扫描结果，状态未知
+                    warn!("Failed to read detail of {} on {}: {}", scanned.device, self.config.hostname, e);
+                    arrays.push(scanned);
+                }
+            }
+        }
+        info!("Found {} RAID arrays on {}", arrays.len(), self.config.hostname);
+        Ok(arrays)
+    }
+
+    /// 获取指定阵列（例如 `/dev/md0`）的详细状态
+    pub fn get_raid_array_detail(&self, device: &str) -> Result<RaidArray, AnsibleError> {
+        let result = self.execute_command(&format!("mdadm --detail '{}'", device))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read RAID array {}: {}",
+                device,
+                result.stderr.trim()
+            )));
+        }
+        let mut array = parse_detail(&result.stdout);
+        if array.device.is_empty() {
+            array.device = device.to_string();
+        }
+        Ok(array)
+    }
+
+    /// 列出处于降级状态的阵列
+    pub fn find_degraded_raid_arrays(&self) -> Result<Vec<RaidArray>, AnsibleError> {
+        let degraded: Vec<RaidArray> = self.get_raid_arrays()?.into_iter().filter(RaidArray::is_degraded).collect();
+        for array in &degraded {
+            warn!(
+                "RAID array {} on {} is degraded ({}, {}/{} active)",
+                array.device, self.config.hostname, array.state, array.active_devices, array.total_devices
+            );
+        }
+        Ok(degraded)
+    }
+}
+
+/// 解析 `mdadm --detail --scan --verbose` 的输出
+///
+/// ```text
+/// ARRAY /dev/md0 level=raid1 num-devices=2 metadata=1.2 spares=1 name=host:0 UUID=...
+///    devices=/dev/sda1,/dev/sdb1,/dev/sdc1
+/// ```
+fn parse_scan(output: &str) -> Vec<RaidArray> {
+    let mut arrays: Vec<RaidArray> = Vec::new();
+    for line in output.lines() {
+        let mut fields = line.split_whitespace().peekable();
+        if fields.peek() == Some(&"ARRAY") {
+            fields.next();
+            let Some(device) = fields.next() else {
+                continue;
+            };
+            arrays.push(RaidArray {
+                device: device.to_string(),
+                ..Default::default()
+            });
+        }
+        let Some(array) = arrays.last_mut() else {
+            continue;
+        };
+        for (key, value) in fields.filter_map(|field| field.split_once('=')) {
+            match key {
+                "level" => array.type_ = value.to_string(),
+                "num-devices" => array.total_devices = value.parse().unwrap_or(0),
+                "spares" => array.spare_devices = value.parse().unwrap_or(0),
+                "devices" => array.members = value.split(',').map(str::to_string).collect(),
+                _ => {}
+            }
+        }
+    }
+    arrays
+}
+
+/// 解析 `mdadm --detail <device>` 的输出
+fn parse_detail(output: &str) -> RaidArray {
+    let mut array = RaidArray::default();
+    let mut in_device_table = false;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if array.device.is_empty()
+            && let Some(device) = trimmed.strip_suffix(':')
+            && device.starts_with("/dev/")
+        {
+            array.device = device.to_string();
+            continue;
+        }
+        if trimmed.starts_with("Number") && trimmed.contains("RaidDevice") {
+            in_device_table = true;
+            continue;
+        }
+        if in_device_table {
+            // `0 8 1 0 active sync /dev/sda1`；已移除的槽位没有设备名
+            if let Some(member) = trimmed.split_whitespace().last().filter(|m| m.starts_with("/dev/")) {
+                array.members.push(member.to_string());
+            }
+            continue;
+        }
+
+        let Some((key, value)) = trimmed.split_once(" : ") else {
+            continue;
+        };
+        let value = value.trim();
+        let count = || value.parse().unwrap_or(0);
+        match key.trim() {
+            "Raid Level" => array.type_ = value.to_string(),
+            "State" => array.state = value.to_string(),
+            "Raid Devices" => array.total_devices = count(),
+            "Active Devices" => array.active_devices = count(),
+            "Failed Devices" => array.failed_devices = count(),
+            "Spare Devices" => array.spare_devices = count(),
+            _ => {}
+        }
+    }
+    array
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEGRADED_DETAIL: &str = "/dev/md0:
+           Version : 1.2
+     Creation Time : Mon Mar  4 10:12:01 2024
+        Raid Level : raid1
+        Array Size : 1047552 (1023.00 MiB 1072.69 MB)
+     Used Dev Size : 1047552 (1023.00 MiB 1072.69 MB)
+      Raid Devices : 2
+     Total Devices : 2
+       Persistence : Superblock is persistent
+
+       Update Time : Tue Mar  5 08:30:44 2024
+             State : clean, degraded
+    Active Devices : 1
+   Working Devices : 1
+    Failed Devices : 1
+     Spare Devices : 0
+
+Consistency Policy : resync
+
+              Name : db1:0  (local to host db1)
+              UUID : 3d1c7a4e:1b2c3d4e:5f6a7b8c:9d0e1f2a
+            Events : 42
+
+    Number   Major   Minor   RaidDevice State
+       0       8        1        0      active sync   /dev/sda1
+       -       0        0        1      removed
+
+       1       8       17        -      faulty   /dev/sdb1
+";
+
+    #[test]
+    fn test_parse_degraded_detail() {
+        let array = parse_detail(DEGRADED_DETAIL);
+        assert_eq!(
+            array,
+            RaidArray {
+                device: "/dev/md0".to_string(),
+                type_: "raid1".to_string(),
+                state: "clean, degraded".to_string(),
+                active_devices: 1,
+                total_devices: 2,
+                failed_devices: 1,
+                spare_devices: 0,
+                members: vec!["/dev/sda1".to_string(), "/dev/sdb1".to_string()],
+            }
+        );
+        assert!(array.is_degraded());
+    }
+
+    #[test]
+    fn test_parse_scan() {
+        let output = "ARRAY /dev/md0 level=raid1 num-devices=2 metadata=1.2 name=db1:0 UUID=3d1c7a4e:1b2c3d4e:5f6a7b8c:9d0e1f2a
+   devices=/dev/sda1,/dev/sdb1
+ARRAY /dev/md1 level=raid5 num-devices=3 metadata=1.2 spares=1 name=db1:1 UUID=aa:bb:cc:dd
+   devices=/dev/sdc1,/dev/sdd1,/dev/sde1,/dev/sdf1
+";
+        let arrays = parse_scan(output);
+        assert_eq!(arrays.len(), 2);
+        assert_eq!(arrays[0].device, "/dev/md0");
+        assert_eq!(arrays[0].members, vec!["/dev/sda1", "/dev/sdb1"]);
+        assert_eq!(arrays[1].type_, "raid5");
+        assert_eq!(arrays[1].total_devices, 3);
+        assert_eq!(arrays[1].spare_devices, 1);
+        assert_eq!(arrays[1].members.len(), 4);
+        assert!(parse_scan("").is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::AnsibleError;
+use crate::types::{RepoConfig, RepoResult, RepoType, UserState};
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use super::SshClient;
+use tracing::{debug, info};
+
+impl SshClient {
+    /// 配置软件包仓库（present 时写入源文件，absent 时删除）
+    ///
+    /// 源文件内容与期望一致时不做任何修改；需要写入时先导入 GPG 公钥
+    /// （apt 使用 `apt-key add`，yum/dnf 使用 `rpm --import`），导入失败则不写入源文件。
+    /// 不会自动执行 `apt-get update` / `yum makecache`。
+    pub fn manage_repo(&self, config: &RepoConfig, state: UserState) -> Result<RepoResult, AnsibleError> {
+        let path = repo_file_path(config)?;
+
+        if state == UserState::Absent {
+            let result = self.execute_command(&format!(
+                "if [ -e {0} ]; then rm -f {0} && echo removed; fi",
+                shell_quote(&path)
+            ))?;
+            if result.exit_code != 0 {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "Failed to remove {}: {}",
+                    path,
+                    result.stderr.trim()
+                )));
+            }
+            let changed = result.stdout.trim() == "removed";
+            return Ok(RepoResult {
+                changed,
+                message: if changed {
+                    format!("Repository '{}' removed", config.name)
+                } else {
+                    format!("Repository '{}' not present", config.name)
+                },
+            });
+        }
+
+        let content = render_repo_file(config);
+        let current = self.execute_command(&format!("cat {} 2>/dev/null", shell_quote(&path)))?;
+        if current.exit_code == 0 && current.stdout == content {
+            debug!("Repository file {} already up to date", path);
+            return Ok(RepoResult {
+                changed: false,
+                message: format!("Repository '{}' already configured", config.name),
+            });
+        }
+
+        if let Some(ref key_url) = config.gpg_key_url {
+            self.import_repo_key(config.type_, key_url)?;
+        }
+
+        let temp_path = generate_remote_temp_path(&path);
+        let result = self.execute_command(&format!(
+            "printf '%s' {} > {temp} && chmod 644 {temp} && mv -f {temp} {}",
+            shell_quote(&content),
+            shell_quote(&path),
+            temp = shell_quote(&temp_path)
+        ))?;
+        if result.exit_code != 0 {
+            let _ = self.execute_command(&format!("rm -f {}", shell_quote(&temp_path)));
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to write {}: {}",
+                path,
+                result.stderr.trim()
+            )));
+        }
+
+        info!("Repository '{}' written to {}", config.name, path);
+        Ok(RepoResult {
+            changed: true,
+            message: format!("Repository '{}' configured in {}", config.name, path),
+        })
+    }
+
+    fn import_repo_key(&self, repo_type: RepoType, key_url: &str) -> Result<(), AnsibleError> {
+        let cmd = match repo_type {
+            RepoType::Apt => format!("curl -fsSL {} | apt-key add -", shell_quote(key_url)),
+            RepoType::Yum | RepoType::Dnf => format!("rpm --import {}", shell_quote(key_url)),
+        };
+        let result = self.execute_command(&cmd)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to import GPG key {}: {}",
+                key_url,
+                result.stderr.trim()
+            )));
+        }
+        info!("Imported GPG key {}", key_url);
+        Ok(())
+    }
+}
+
+/// 源文件路径；名称只允许字母、数字、`.`、`_` 与 `-`
+fn repo_file_path(config: &RepoConfig) -> Result<String, AnsibleError> {
+    let valid = !config.name.is_empty()
+        && !config.name.starts_with('.')
+        && config.name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(AnsibleError::ValidationError(format!(
+            "Invalid repository name: '{}'",
+            config.name
+        )));
+    }
+    Ok(match config.type_ {
+        RepoType::Apt => format!("/etc/apt/sources.list.d/{}.list", config.name),
+        RepoType::Yum | RepoType::Dnf => format!("/etc/yum.repos.d/{}.repo", config.name),
+    })
+}
+
+/// 生成源文件内容
+fn render_repo_file(config: &RepoConfig) -> String {
+    let mut content = String::from("# Managed by rs-ansible\n");
+    match config.type_ {
+        RepoType::Apt => {
+            let prefix = if config.enabled { "" } else { "# " };
+            content.push_str(&format!("{}deb {}\n", prefix, config.base_url));
+        }
+        RepoType::Yum | RepoType::Dnf => {
+            content.push_str(&format!("[{}]\n", config.name));
+            content.push_str(&format!("name={}\n", config.name));
+            content.push_str(&format!("baseurl={}\n", config.base_url));
+            content.push_str(&format!("enabled={}\n", config.enabled as u8));
+            match config.gpg_key_url {
+                Some(ref key_url) => content.push_str(&format!("gpgcheck=1\ngpgkey={}\n", key_url)),
+                None => content.push_str("gpgcheck=0\n"),
+            }
+            if let Some(priority) = config.priority {
+                content.push_str(&format!("priority={}\n", priority));
+            }
+        }
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(type_: RepoType) -> RepoConfig {
+        RepoConfig {
+            name: "internal".to_string(),
+            base_url: "https://mirror.example.com/el9/$basearch".to_string(),
+            enabled: true,
+            gpg_key_url: Some("https://mirror.example.com/RPM-GPG-KEY".to_string()),
+            priority: Some(10),
+            type_,
+        }
+    }
+
+    #[test]
+    fn test_render_yum_and_dnf_repo() {
+        let expected = "# Managed by rs-ansible\n[internal]\nname=internal\nbaseurl=https://mirror.example.com/el9/$basearch\n\
+                        enabled=1\ngpgcheck=1\ngpgkey=https://mirror.example.com/RPM-GPG-KEY\npriority=10\n";
+        assert_eq!(render_repo_file(&repo(RepoType::Yum)), expected);
+        assert_eq!(render_repo_file(&repo(RepoType::Dnf)), expected);
+        assert_eq!(repo_file_path(&repo(RepoType::Dnf)).unwrap(), "/etc/yum.repos.d/internal.repo");
+
+        let mut config = repo(RepoType::Yum);
+        config.enabled = false;
+        config.gpg_key_url = None;
+        config.priority = None;
+        assert_eq!(
+            render_repo_file(&config),
+            "# Managed by rs-ansible\n[internal]\nname=internal\nbaseurl=https://mirror.example.com/el9/$basearch\nenabled=0\ngpgcheck=0\n"
+        );
+    }
+
+    #[test]
+    fn test_render_apt_repo() {
+        let mut config = repo(RepoType::Apt);
+        config.base_url = "http://deb.example.com/ubuntu jammy main".to_string();
+        assert_eq!(
+            render_repo_file(&config),
+            "# Managed by rs-ansible\ndeb http://deb.example.com/ubuntu jammy main\n"
+        );
+        assert_eq!(repo_file_path(&config).unwrap(), "/etc/apt/sources.list.d/internal.list");
+
+        config.enabled = false;
+        assert_eq!(
+            render_repo_file(&config),
+            "# Managed by rs-ansible\n# deb http://deb.example.com/ubuntu jammy main\n"
+        );
+
+        config.name = "../sources".to_string();
+        assert!(repo_file_path(&config).is_err());
+    }
+}
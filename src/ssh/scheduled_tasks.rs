@@ -0,0 +1,210 @@
+use crate::error::AnsibleError;
+use crate::types::{CronEntry, ScheduledTasks, SystemdTimer};
+use super::SshClient;
+use tracing::{debug, info};
+
+/// 输出系统 crontab、/etc/cron.d 以及各用户 crontab，每段以 `### <来源>` 开头
+const CRON_LISTING_CMD: &str = "for f in /etc/crontab /etc/cron.d/*; do [ -f \"$f\" ] && { echo \"### $f\"; cat \"$f\"; }; done; \
+for u in $(cut -d: -f1 /etc/passwd); do c=$(crontab -l -u \"$u\" 2>/dev/null) && { echo \"### user:$u\"; echo \"$c\"; }; done";
+
+impl SshClient {
+    /// 获取所有 cron 定时任务（系统 crontab、/etc/cron.d 与各用户 crontab）
+    ///
+    /// 读取其他用户的 crontab 需要 root 权限，权限不足时只返回可读取的部分。
+    pub fn get_all_cron_jobs(&self) -> Result<Vec<CronEntry>, AnsibleError> {
+        let result = self.execute_command(CRON_LISTING_CMD)?;
+        let entries = parse_cron_listing(&result.stdout);
+        info!("Found {} cron job(s) on {}", entries.len(), self.config.hostname);
+        Ok(entries)
+    }
+
+    /// 获取 systemd 定时器列表（未使用 systemd 的主机返回空列表）
+    pub fn get_systemd_timers(&self) -> Result<Vec<SystemdTimer>, AnsibleError> {
+        let result = self.execute_command("LC_ALL=C systemctl list-timers --all --no-pager 2>/dev/null")?;
+        if result.exit_code != 0 {
+            debug!("systemctl list-timers unavailable on {}", self.config.hostname);
+            return Ok(Vec::new());
+        }
+
+        let timers = parse_list_timers(&result.stdout);
+        info!("Found {} systemd timer(s) on {}", timers.len(), self.config.hostname);
+        Ok(timers)
+    }
+
+    /// 汇总 cron 与 systemd 定时器
+    pub fn get_all_scheduled_tasks(&self) -> Result<ScheduledTasks, AnsibleError> {
+        Ok(ScheduledTasks {
+            cron_entries: self.get_all_cron_jobs()?,
+            systemd_timers: self.get_systemd_timers()?,
+        })
+    }
+}
+
+/// 解析 `### <来源>` 分段的 crontab 输出
+fn parse_cron_listing(output: &str) -> Vec<CronEntry> {
+    let mut entries = Vec::new();
+    let mut source = String::new();
+
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix("### ") {
+            source = header.to_string();
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') || is_env_assignment(line) {
+            continue;
+        }
+
+        // 用户 crontab 没有用户字段
+        let user_crontab = source.strip_prefix("user:");
+        if let Some(entry) = parse_cron_line(line, &source, user_crontab) {
+            entries.push(entry);
+        }
+    }
+
+    entries
+}
+
+/// 解析单行 crontab，`owner` 为 None 时表示系统 crontab（调度后带有用户字段）
+fn parse_cron_line(line: &str, source: &str, owner: Option<&str>) -> Option<CronEntry> {
+    let mut fields = line.split_whitespace();
+    let schedule_fields = if line.starts_with('@') { 1 } else { 5 };
+    let schedule: Vec<&str> = fields.by_ref().take(schedule_fields).collect();
+    if schedule.len() != schedule_fields {
+        return None;
+    }
+
+    let user = match owner {
+        Some(owner) => owner.to_string(),
+        None => fields.next()?.to_string(),
+    };
+    let command = fields.collect::<Vec<_>>().join(" ");
+    if command.is_empty() {
+        return None;
+    }
+
+    Some(CronEntry {
+        source: source.to_string(),
+        user: Some(user),
+        schedule: schedule.join(" "),
+        command,
+    })
+}
+
+/// crontab 中的环境变量设置行，例如 `SHELL=/bin/sh`
+fn is_env_assignment(line: &str) -> bool {
+    match line.split_once('=') {
+        Some((name, _)) => {
+            let name = name.trim();
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// 解析 `systemctl list-timers` 的表格输出（按表头列位置切分）
+fn parse_list_timers(output: &str) -> Vec<SystemdTimer> {
+    let mut lines = output.lines();
+    let Some(header) = lines.find(|l| l.trim_start().starts_with("NEXT")) else {
+        return Vec::new();
+    };
+
+    let columns = ["NEXT", "LEFT", "LAST", "PASSED", "UNIT", "ACTIVATES"];
+    let Some(offsets) = columns
+        .iter()
+        .map(|name| header.find(name))
+        .collect::<Option<Vec<usize>>>()
+    else {
+        return Vec::new();
+    };
+
+    let mut timers = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            // 空行之后是 "N timers listed." 汇总
+            break;
+        }
+
+        let column = |i: usize| -> String {
+            let start = offsets[i].min(line.len());
+            let end = offsets.get(i + 1).copied().unwrap_or(line.len()).min(line.len());
+            line.get(start..end).unwrap_or("").trim().to_string()
+        };
+        let optional = |i: usize| {
+            let value = column(i);
+            (!value.is_empty() && value != "n/a" && value != "-").then_some(value)
+        };
+
+        let unit = column(4);
+        if unit.is_empty() {
+            continue;
+        }
+        timers.push(SystemdTimer {
+            unit,
+            next_elapse: optional(0),
+            last_trigger: optional(2),
+            passed: optional(3),
+            activates: column(5),
+        });
+    }
+
+    timers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_timers() {
+        let output = "\
+NEXT                        LEFT          LAST                        PASSED       UNIT                         ACTIVATES
+Thu 2024-01-11 00:00:00 UTC 5h 2min left  Wed 2024-01-10 00:00:01 UTC 18h ago      logrotate.timer              logrotate.service
+Thu 2024-01-11 06:12:44 UTC 11h left      n/a                         n/a          apt-daily.timer              apt-daily.service
+n/a                         n/a           n/a                         n/a          snapd.snap-repair.timer      snapd.snap-repair.service
+
+3 timers listed.
+Pass --all to see loaded but inactive timers, too.
+";
+        let timers = parse_list_timers(output);
+        assert_eq!(timers.len(), 3);
+        assert_eq!(timers[0].unit, "logrotate.timer");
+        assert_eq!(timers[0].next_elapse.as_deref(), Some("Thu 2024-01-11 00:00:00 UTC"));
+        assert_eq!(timers[0].last_trigger.as_deref(), Some("Wed 2024-01-10 00:00:01 UTC"));
+        assert_eq!(timers[0].passed.as_deref(), Some("18h ago"));
+        assert_eq!(timers[0].activates, "logrotate.service");
+        assert_eq!(timers[1].last_trigger, None);
+        assert_eq!(timers[2].next_elapse, None);
+        assert_eq!(timers[2].activates, "snapd.snap-repair.service");
+    }
+
+    #[test]
+    fn test_parse_list_timers_empty() {
+        assert!(parse_list_timers("0 timers listed.\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_cron_listing() {
+        let output = "\
+### /etc/crontab
+SHELL=/bin/sh
+# m h dom mon dow user  command
+17 *    * * *   root    cd / && run-parts --report /etc/cron.hourly
+### /etc/cron.d/backup
+@reboot backup /usr/local/bin/backup --init
+### user:deploy
+*/5 * * * * /home/deploy/bin/poll.sh >/dev/null 2>&1
+";
+        let entries = parse_cron_listing(output);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].source, "/etc/crontab");
+        assert_eq!(entries[0].user.as_deref(), Some("root"));
+        assert_eq!(entries[0].schedule, "17 * * * *");
+        assert_eq!(entries[0].command, "cd / && run-parts --report /etc/cron.hourly");
+        assert_eq!(entries[1].schedule, "@reboot");
+        assert_eq!(entries[1].user.as_deref(), Some("backup"));
+        assert_eq!(entries[2].source, "user:deploy");
+        assert_eq!(entries[2].user.as_deref(), Some("deploy"));
+        assert_eq!(entries[2].command, "/home/deploy/bin/poll.sh >/dev/null 2>&1");
+    }
+}
@@ -0,0 +1,189 @@
+use crate::error::AnsibleError;
+use crate::types::{SshConfigAudit, SshFinding};
+use super::SshClient;
+use std::collections::HashMap;
+use tracing::info;
+
+const SSHD_CONFIG: &str = "/etc/ssh/sshd_config";
+
+/// 基线项的判定方式
+enum Check {
+    OneOf(&'static [&'static str]), // 取值（不区分大小写）必须是其中之一
+    AtMost(u64),                    // 数值（时间类按秒）不得超过上限
+}
+
+/// 基线项：(设置名, 判定方式, 建议值, 严重程度, OpenSSH 默认值, 说明)
+const BASELINE: &[(&str, Check, &str, &str, &str, &str)] = &[
+    ("PermitRootLogin", Check::OneOf(&["no"]), "no", "high", "prohibit-password", "Root should not log in over SSH directly"),
+    ("PasswordAuthentication", Check::OneOf(&["no"]), "no", "high", "yes", "Password logins are exposed to brute-force attacks, use keys"),
+    ("PermitEmptyPasswords", Check::OneOf(&["no"]), "no", "critical", "no", "Accounts with empty passwords must not be able to log in"),
+    ("Protocol", Check::OneOf(&["2"]), "2", "high", "2", "SSH protocol 1 is insecure"),
+    ("X11Forwarding", Check::OneOf(&["no"]), "no", "medium", "no", "X11 forwarding exposes the client display to the server"),
+    ("HostbasedAuthentication", Check::OneOf(&["no"]), "no", "medium", "no", "Host-based trust should not replace user authentication"),
+    ("IgnoreRhosts", Check::OneOf(&["yes"]), "yes", "medium", "yes", ".rhosts files must not grant access"),
+    ("PermitUserEnvironment", Check::OneOf(&["no"]), "no", "medium", "no", "Users must not be able to override the environment of sshd"),
+    ("MaxAuthTries", Check::AtMost(4), "4", "medium", "6", "Limit authentication attempts per connection"),
+    ("LoginGraceTime", Check::AtMost(60), "60", "low", "120", "Unauthenticated connections should be dropped quickly"),
+    ("LogLevel", Check::OneOf(&["info", "verbose"]), "INFO", "low", "INFO", "Logging must be detailed enough to audit logins"),
+];
+
+/// 严重程度对应的评分权重
+fn severity_weight(severity: &str) -> f32 {
+    match severity {
+        "critical" => 4.0,
+        "high" => 3.0,
+        "medium" => 2.0,
+        _ => 1.0,
+    }
+}
+
+impl SshClient {
+    /// 按内置的 CIS 风格基线检查 `/etc/ssh/sshd_config`
+    ///
+    /// 只检查全局设置（`Match` 块之前），与 sshd 一样以首次出现的值为准；
+    /// 未配置的项按 OpenSSH 默认值判断。`Include` 引入的文件不会被读取。
+    pub fn audit_ssh_config(&self) -> Result<SshConfigAudit, AnsibleError> {
+        let result = self.execute_command(&format!("cat {}", SSHD_CONFIG))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read {}: {}",
+                SSHD_CONFIG,
+                result.stderr.trim()
+            )));
+        }
+
+        let audit = audit_sshd_config(&result.stdout);
+        info!(
+            "SSH config audit on {}: {} finding(s), score {:.1}",
+            self.config.hostname,
+            audit.findings.len(),
+            audit.score
+        );
+        Ok(audit)
+    }
+}
+
+/// 解析全局设置，键统一为小写
+fn parse_sshd_config(content: &str) -> HashMap<String, String> {
+    let mut settings = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // 关键字与取值之间可以是空白或 `=`
+        let (key, value) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+            Some(index) => (&line[..index], line[index + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == '=')),
+            None => (line, ""),
+        };
+        let key = key.to_lowercase();
+        if key == "match" {
+            break;
+        }
+        settings.entry(key).or_insert_with(|| value.trim().to_string());
+    }
+    settings
+}
+
+/// 解析 sshd 的时间格式（例如 `90`、`1m30s`），返回秒数
+fn parse_sshd_time(value: &str) -> Option<u64> {
+    let mut total = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let multiplier = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return None,
+        };
+        total += number.parse::<u64>().ok()? * multiplier;
+        number.clear();
+    }
+    if !number.is_empty() {
+        total += number.parse::<u64>().ok()?;
+    }
+    Some(total)
+}
+
+fn audit_sshd_config(content: &str) -> SshConfigAudit {
+    let settings = parse_sshd_config(content);
+    let mut findings = Vec::new();
+    let mut total_weight = 0.0;
+    let mut failed_weight = 0.0;
+
+    for (setting, check, recommended, severity, default, description) in BASELINE {
+        let configured = settings.get(&setting.to_lowercase());
+        let value = configured.map_or(*default, String::as_str);
+        let passed = match check {
+            Check::OneOf(accepted) => accepted.iter().any(|a| a.eq_ignore_ascii_case(value)),
+            // 0 表示不限制
+            Check::AtMost(max) => parse_sshd_time(value).is_some_and(|v| v > 0 && v <= *max),
+        };
+
+        let weight = severity_weight(severity);
+        total_weight += weight;
+        if !passed {
+            failed_weight += weight;
+            findings.push(SshFinding {
+                setting: setting.to_string(),
+                current_value: match configured {
+                    Some(value) => value.clone(),
+                    None => format!("(default) {}", default),
+                },
+                recommended_value: recommended.to_string(),
+                severity: severity.to_string(),
+                description: description.to_string(),
+            });
+        }
+    }
+
+    SshConfigAudit {
+        findings,
+        score: (total_weight - failed_weight) / total_weight * 100.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_default_config() {
+        let config = "# Debian default\nInclude /etc/ssh/sshd_config.d/*.conf\nKbdInteractiveAuthentication no\nUsePAM yes\nX11Forwarding yes\nSubsystem sftp /usr/lib/openssh/sftp-server\n";
+        let audit = audit_sshd_config(config);
+        let failed: Vec<&str> = audit.findings.iter().map(|f| f.setting.as_str()).collect();
+        assert_eq!(
+            failed,
+            vec!["PermitRootLogin", "PasswordAuthentication", "X11Forwarding", "MaxAuthTries", "LoginGraceTime"]
+        );
+        assert_eq!(audit.findings[0].current_value, "(default) prohibit-password");
+        assert_eq!(audit.findings[2].current_value, "yes");
+        assert_eq!(audit.findings[2].severity, "medium");
+        assert!(!audit.is_compliant());
+        assert!(audit.score > 0.0 && audit.score < 100.0);
+    }
+
+    #[test]
+    fn test_audit_hardened_config() {
+        let config = "PermitRootLogin no\npasswordauthentication=no\nMaxAuthTries 3\nLoginGraceTime 1m\nLogLevel VERBOSE\n\
+                      PermitRootLogin yes\n\nMatch User backup\n    PasswordAuthentication yes\n";
+        let audit = audit_sshd_config(config);
+        assert!(audit.is_compliant(), "unexpected findings: {:?}", audit.findings);
+        assert_eq!(audit.score, 100.0);
+
+        // 首次出现的值生效，Match 块中的设置不影响全局结果
+        let audit = audit_sshd_config("PermitEmptyPasswords yes\nPermitEmptyPasswords no\nMaxAuthTries 0\n");
+        let empty = audit.findings.iter().find(|f| f.setting == "PermitEmptyPasswords").unwrap();
+        assert_eq!(empty.severity, "critical");
+        assert!(audit.findings.iter().any(|f| f.setting == "MaxAuthTries" && f.current_value == "0"));
+
+        assert_eq!(parse_sshd_time("1m30s"), Some(90));
+        assert_eq!(parse_sshd_time("2x"), None);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::error::AnsibleError;
+use crate::types::{CommandOptions, SshKeyEntry, SshKeyType, SshKeypairResult};
+use crate::utils::shell_quote;
+use super::SshClient;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{debug, info};
+
+/// 轮换审计中 RSA 密钥的最小长度
+const MIN_RSA_BITS: u32 = 4096;
+
+/// authorized_keys 中每行内容之前的标记，其后一行为 `ssh-keygen -l` 的输出
+const KEY_MARKER: &str = "==> ";
+
+impl SshClient {
+    /// 在远程主机上生成 SSH 密钥对并返回公钥与指纹
+    ///
+    /// 私钥已存在时不会重新生成（幂等），直接读取现有公钥。
+    /// 口令通过环境变量传递，不会出现在日志中。
+    pub fn generate_ssh_keypair(
+        &self,
+        key_path: &str,
+        key_type: SshKeyType,
+        bits: u32,
+        comment: &str,
+        passphrase: Option<&str>,
+    ) -> Result<SshKeypairResult, AnsibleError> {
+        let check = self.execute_command(&format!("test -f {} && echo exists", shell_quote(key_path)))?;
+        let exists = check.stdout.trim() == "exists";
+
+        if exists {
+            info!("SSH key {} already exists on {}, skipping generation", key_path, self.config.hostname);
+        } else {
+            if let Some(parent) = Path::new(key_path).parent()
+                && !parent.as_os_str().is_empty()
+            {
+                let parent = parent.to_string_lossy();
+                let result = self.execute_command(&format!("mkdir -p -m 700 {}", shell_quote(&parent)))?;
+                if result.exit_code != 0 {
+                    return Err(AnsibleError::FileOperationError(format!(
+                        "Failed to create key directory {}: {}",
+                        parent, result.stderr
+                    )));
+                }
+            }
+
+            let cmd = keygen_command(key_path, key_type, bits, comment);
+            let options = CommandOptions::new().env("RS_ANSIBLE_KEY_PASSPHRASE", passphrase.unwrap_or(""));
+            let result = self.execute_command_full(&cmd, options)?;
+            if result.exit_code != 0 {
+                return Err(AnsibleError::CommandError(format!(
+                    "ssh-keygen failed with exit code {}: {}",
+                    result.exit_code, result.stderr
+                )));
+            }
+            info!("Generated {} SSH key {} on {}", key_type.as_str(), key_path, self.config.hostname);
+        }
+
+        let public_key_path = format!("{}.pub", key_path);
+        let public_key = self.read_remote_file(&public_key_path)?.trim().to_string();
+
+        let fingerprint = self.execute_command(&format!("ssh-keygen -l -f {}", shell_quote(&public_key_path)))?;
+        if fingerprint.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read key fingerprint: {}",
+                fingerprint.stderr
+            )));
+        }
+
+        Ok(SshKeypairResult {
+            private_key_path: key_path.to_string(),
+            public_key,
+            fingerprint: parse_fingerprint(&fingerprint.stdout).unwrap_or_default(),
+            changed: !exists,
+        })
+    }
+}
+
+impl SshClient {
+    /// 读取并解析用户的 `~/.ssh/authorized_keys`，注释与空行被忽略；文件不存在时返回空列表
+    ///
+    /// 指纹由主机上的 `ssh-keygen -l` 计算。读取其他用户的文件通常需要 root 权限（become）。
+    pub fn get_authorized_keys(&self, user: &str) -> Result<Vec<SshKeyEntry>, AnsibleError> {
+        Ok(self.read_authorized_keys(user)?.into_iter().map(|(entry, _)| entry).collect())
+    }
+
+    /// 列出用户 `~/.ssh/` 中的私钥文件（按 `file` 命令的识别结果判断），返回排序后的完整路径
+    pub fn get_user_private_keys(&self, user: &str) -> Result<Vec<String>, AnsibleError> {
+        validate_key_user(user)?;
+        let result = self.execute_command(&format!(
+            "{}; for f in ~{}/.ssh/*; do [ -f \"$f\" ] && file \"$f\"; done; true",
+            user_exists_check(user),
+            user
+        ))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to list SSH keys of {}: {}",
+                user,
+                result.stderr.trim()
+            )));
+        }
+        Ok(parse_private_keys(&result.stdout))
+    }
+
+    /// 找出需要轮换密钥的用户：authorized_keys 中有小于 4096 位的 RSA 公钥，
+    /// 或 `~/.ssh/` 中有超过 `max_age_days` 天未修改的私钥
+    ///
+    /// 返回的用户保持 `users` 中的顺序；主机上不存在的用户被忽略。
+    pub fn find_users_with_old_keys(&self, users: &[String], max_age_days: u32) -> Result<Vec<String>, AnsibleError> {
+        let mut flagged = Vec::new();
+        for user in users {
+            validate_key_user(user)?;
+            if self.execute_command(&format!("getent passwd {} >/dev/null", user))?.exit_code != 0 {
+                debug!("User {} does not exist on {}, skipping", user, self.config.hostname);
+                continue;
+            }
+
+            let weak = self
+                .read_authorized_keys(user)?
+                .iter()
+                .any(|(entry, bits)| is_weak_key(entry, *bits));
+            let old = !weak && {
+                let private_keys: HashSet<String> = self.get_user_private_keys(user)?.into_iter().collect();
+                !private_keys.is_empty() && {
+                    let result = self.execute_command(&format!(
+                        "find ~{}/.ssh -maxdepth 1 -type f -mtime +{}",
+                        user, max_age_days
+                    ))?;
+                    result.stdout.lines().any(|path| private_keys.contains(path.trim()))
+                }
+            };
+            if weak || old {
+                info!(
+                    "User {} on {} has {} SSH keys",
+                    user,
+                    self.config.hostname,
+                    if weak { "weak" } else { "old" }
+                );
+                flagged.push(user.clone());
+            }
+        }
+        Ok(flagged)
+    }
+
+    /// authorized_keys 中的公钥及其位数
+    fn read_authorized_keys(&self, user: &str) -> Result<Vec<(SshKeyEntry, Option<u32>)>, AnsibleError> {
+        validate_key_user(user)?;
+        let result = self.execute_command(&authorized_keys_command(user))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read authorized_keys of {}: {}",
+                user,
+                result.stderr.trim()
+            )));
+        }
+        Ok(parse_authorized_keys(&result.stdout))
+    }
+}
+
+/// 用户名会以 `~user` 形式出现在命令中，不能加引号，因此只允许常见的用户名字符
+fn validate_key_user(user: &str) -> Result<(), AnsibleError> {
+    let valid = user.chars().next().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && user.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return Err(AnsibleError::ValidationError(format!("Invalid user name: '{}'", user)));
+    }
+    Ok(())
+}
+
+fn user_exists_check(user: &str) -> String {
+    format!("getent passwd {0} >/dev/null || {{ echo 'user {0} not found' >&2; exit 1; }}", user)
+}
+
+/// 逐行输出 authorized_keys，每行后跟该行的 `ssh-keygen -l` 结果（无法解析时没有输出）
+fn authorized_keys_command(user: &str) -> String {
+    format!(
+        "{}; f=~{}/.ssh/authorized_keys; [ -f \"$f\" ] || exit 0; \
+         while IFS= read -r line || [ -n \"$line\" ]; do echo \"{}$line\"; \
+         printf '%s\\n' \"$line\" | ssh-keygen -l -f /dev/stdin 2>/dev/null; done < \"$f\"",
+        user_exists_check(user),
+        user,
+        KEY_MARKER
+    )
+}
+
+fn parse_authorized_keys(output: &str) -> Vec<(SshKeyEntry, Option<u32>)> {
+    let mut keys: Vec<(SshKeyEntry, Option<u32>)> = Vec::new();
+    let mut awaiting_fingerprint = false;
+    for line in output.lines() {
+        if let Some(key_line) = line.strip_prefix(KEY_MARKER) {
+            awaiting_fingerprint = match parse_authorized_key_line(key_line) {
+                Some(entry) => {
+                    keys.push((entry, None));
+                    true
+                }
+                None => false,
+            };
+        } else if awaiting_fingerprint && let Some((entry, bits)) = keys.last_mut() {
+            entry.fingerprint = parse_fingerprint(line);
+            *bits = line.split_whitespace().next().and_then(|b| b.parse().ok());
+            awaiting_fingerprint = false;
+        }
+    }
+    keys
+}
+
+/// 解析 `[选项] 类型 公钥 [注释]`，选项中可以有带引号的空白（如 `command="..."`）
+fn parse_authorized_key_line(line: &str) -> Option<SshKeyEntry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    let is_key_type = |token: &str| {
+        ["ssh-", "ecdsa-sha2-", "sk-ssh-", "sk-ecdsa-sha2-"]
+            .iter()
+            .any(|prefix| token.starts_with(prefix))
+    };
+    let index = tokens.iter().position(|token| is_key_type(token))?;
+    Some(SshKeyEntry {
+        type_: tokens[index].clone(),
+        public_key: tokens.get(index + 1)?.clone(),
+        comment: tokens[index + 2..].join(" "),
+        fingerprint: None,
+    })
+}
+
+/// `file` 的输出为 `路径: 描述`，例如 `/root/.ssh/id_rsa: OpenSSH private key`
+fn parse_private_keys(output: &str) -> Vec<String> {
+    let mut keys: Vec<String> = output
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .filter(|(_, description)| description.to_ascii_lowercase().contains("private key"))
+        .map(|(path, _)| path.to_string())
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// 位数未知的 RSA 公钥不视为弱密钥
+fn is_weak_key(entry: &SshKeyEntry, bits: Option<u32>) -> bool {
+    entry.type_ == "ssh-rsa" && bits.is_some_and(|bits| bits < MIN_RSA_BITS)
+}
+
+/// 构建 ssh-keygen 命令（口令从环境变量读取）
+fn keygen_command(key_path: &str, key_type: SshKeyType, bits: u32, comment: &str) -> String {
+    let bits_arg = match key_type {
+        // ed25519 密钥长度固定
+        SshKeyType::Ed25519 => String::new(),
+        _ => format!(" -b {}", bits),
+    };
+    format!(
+        "ssh-keygen -q -t {}{} -C {} -f {} -N \"$RS_ANSIBLE_KEY_PASSPHRASE\"",
+        key_type.as_str(),
+        bits_arg,
+        shell_quote(comment),
+        shell_quote(key_path)
+    )
+}
+
+/// 解析 `ssh-keygen -l` 输出，例如 `256 SHA256:abc... deploy@host (ED25519)`
+fn parse_fingerprint(output: &str) -> Option<String> {
+    output.split_whitespace().nth(1).map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keygen_command() {
+        assert_eq!(
+            keygen_command("/home/deploy/.ssh/id_ed25519", SshKeyType::Ed25519, 256, "deploy@web1"),
+            "ssh-keygen -q -t ed25519 -C 'deploy@web1' -f '/home/deploy/.ssh/id_ed25519' -N \"$RS_ANSIBLE_KEY_PASSPHRASE\""
+        );
+        assert!(keygen_command("/root/.ssh/id_rsa", SshKeyType::Rsa, 4096, "").contains("-t rsa -b 4096"));
+    }
+
+    #[test]
+    fn test_parse_authorized_keys() {
+        let output = "\
+==> # deploy keys
+==> ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl alice@laptop
+256 SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s alice@laptop (ED25519)
+==> 
+==> command=\"/usr/bin/backup --dest /srv/backup\",no-pty ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQC7 backup key
+2048 SHA256:Xy5YzUz0B0RPaFtvbMKbjHh1s8BaVYH5XoSAzuqNKsY backup key (RSA)
+==> ssh-rsa AAAAinvalid
+";
+        let keys = parse_authorized_keys(output);
+        assert_eq!(keys.len(), 3);
+        assert_eq!(
+            keys[0].0,
+            SshKeyEntry {
+                type_: "ssh-ed25519".to_string(),
+                public_key: "AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl".to_string(),
+                comment: "alice@laptop".to_string(),
+                fingerprint: Some("SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s".to_string()),
+            }
+        );
+        assert_eq!((keys[1].0.type_.as_str(), keys[1].0.comment.as_str(), keys[1].1), ("ssh-rsa", "backup key", Some(2048)));
+        assert_eq!((keys[2].0.fingerprint.as_deref(), keys[2].1), (None, None));
+
+        assert!(!is_weak_key(&keys[0].0, keys[0].1));
+        assert!(is_weak_key(&keys[1].0, keys[1].1));
+        assert!(!is_weak_key(&keys[2].0, keys[2].1));
+    }
+
+    #[test]
+    fn test_parse_private_keys() {
+        let output = "\
+/home/alice/.ssh/known_hosts: ASCII text
+/home/alice/.ssh/id_rsa: PEM RSA private key
+/home/alice/.ssh/id_ed25519.pub: OpenSSH ED25519 public key
+/home/alice/.ssh/id_ed25519: OpenSSH private key
+";
+        assert_eq!(parse_private_keys(output), vec!["/home/alice/.ssh/id_ed25519", "/home/alice/.ssh/id_rsa"]);
+        assert!(validate_key_user("alice").is_ok());
+        assert!(validate_key_user("svc-backup.1").is_ok());
+        assert!(validate_key_user("-alice").is_err());
+        assert!(validate_key_user("alice;id").is_err());
+    }
+
+    #[test]
+    fn test_parse_fingerprint() {
+        assert_eq!(
+            parse_fingerprint("256 SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s deploy@web1 (ED25519)\n").as_deref(),
+            Some("SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s")
+        );
+        assert_eq!(parse_fingerprint(""), None);
+    }
+}
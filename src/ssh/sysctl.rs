@@ -0,0 +1,141 @@
+use crate::error::AnsibleError;
+use crate::types::SysctlDiff;
+use crate::utils::shell_quote;
+use super::SshClient;
+use std::collections::HashMap;
+use tracing::info;
+
+impl SshClient {
+    /// 读取全部内核参数（`sysctl -a`），无权限读取的参数被忽略
+    pub fn get_sysctl_values_all(&self) -> Result<HashMap<String, String>, AnsibleError> {
+        let result = self.execute_command("sysctl -a 2>/dev/null")?;
+        if result.exit_code != 0 && result.stdout.is_empty() {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to read kernel parameters: {}",
+                result.stderr.trim()
+            )));
+        }
+        Ok(parse_sysctl(&result.stdout))
+    }
+
+    /// 将内核参数与基线比较；多个值之间的空白（如 `tcp_rmem` 中的制表符）不影响比较
+    pub fn compare_to_sysctl_baseline(&self, baseline: &HashMap<String, String>) -> Result<SysctlDiff, AnsibleError> {
+        Ok(diff_sysctl(baseline, &self.get_sysctl_values_all()?))
+    }
+
+    /// 用 `sysctl -w` 将与基线不一致的参数改为基线值，返回修改前的比较结果，需要 root 权限（become）
+    ///
+    /// 只修改运行时的值，重启后失效；主机上不存在的参数无法设置，保留在 `missing` 中。
+    pub fn apply_sysctl_baseline(&self, baseline: &HashMap<String, String>) -> Result<SysctlDiff, AnsibleError> {
+        if let Some(key) = baseline.keys().find(|key| !valid_sysctl_key(key)) {
+            return Err(AnsibleError::ValidationError(format!("Invalid kernel parameter name: '{}'", key)));
+        }
+        let diff = self.compare_to_sysctl_baseline(baseline)?;
+        if diff.wrong_value.is_empty() {
+            return Ok(diff);
+        }
+        let result = self.execute_command(&sysctl_write_command(&diff.wrong_value))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to set kernel parameters: {}",
+                result.stderr.trim()
+            )));
+        }
+        info!(
+            "Set {} kernel parameter(s) on {} to baseline",
+            diff.wrong_value.len(),
+            self.config.hostname
+        );
+        Ok(diff)
+    }
+}
+
+/// 解析 `key = value` 行，值中的连续空白归一为单个空格
+fn parse_sysctl(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(" = ").or_else(|| line.split_once('='))?;
+            let key = key.trim();
+            (!key.is_empty()).then(|| (key.to_string(), normalize_value(value)))
+        })
+        .collect()
+}
+
+fn normalize_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn diff_sysctl(baseline: &HashMap<String, String>, actual: &HashMap<String, String>) -> SysctlDiff {
+    let mut keys: Vec<&String> = baseline.keys().collect();
+    keys.sort();
+    let mut diff = SysctlDiff::default();
+    for key in keys {
+        let expected = normalize_value(&baseline[key]);
+        match actual.get(key) {
+            None => diff.missing.push(key.clone()),
+            Some(value) if *value == expected => diff.compliant.push(key.clone()),
+            Some(value) => diff.wrong_value.push((key.clone(), expected, value.clone())),
+        }
+    }
+    diff
+}
+
+/// 参数名只允许字母、数字与 `._-/`（`/` 为 `.` 的等价写法）
+fn valid_sysctl_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+}
+
+/// 一次执行设置所有参数，任一失败则整体失败
+fn sysctl_write_command(values: &[(String, String, String)]) -> String {
+    values
+        .iter()
+        .map(|(key, expected, _)| format!("sysctl -q -w {}", shell_quote(&format!("{}={}", key, expected))))
+        .collect::<Vec<_>>()
+        .join(" && ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_sysctl_against_baseline() {
+        let output = "\
+kernel.randomize_va_space = 2
+net.ipv4.conf.all.accept_redirects = 1
+net.ipv4.ip_forward = 0
+net.ipv4.tcp_rmem = 4096\t131072\t6291456
+kernel.domainname = (none)
+fs.binfmt_misc.status = enabled
+";
+        let actual = parse_sysctl(output);
+        assert_eq!(actual.len(), 6);
+        assert_eq!(actual["net.ipv4.tcp_rmem"], "4096 131072 6291456");
+
+        let baseline: HashMap<String, String> = [
+            ("kernel.randomize_va_space", "2"),
+            ("net.ipv4.conf.all.accept_redirects", "0"),
+            ("net.ipv4.ip_forward", "0"),
+            ("net.ipv4.tcp_rmem", "4096 131072  6291456"),
+            ("kernel.yama.ptrace_scope", "1"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let diff = diff_sysctl(&baseline, &actual);
+        assert_eq!(diff.missing, vec!["kernel.yama.ptrace_scope"]);
+        assert_eq!(
+            diff.wrong_value,
+            vec![("net.ipv4.conf.all.accept_redirects".to_string(), "0".to_string(), "1".to_string())]
+        );
+        assert_eq!(diff.compliant, vec!["kernel.randomize_va_space", "net.ipv4.ip_forward", "net.ipv4.tcp_rmem"]);
+
+        assert_eq!(
+            sysctl_write_command(&diff.wrong_value),
+            "sysctl -q -w 'net.ipv4.conf.all.accept_redirects=0'"
+        );
+        assert!(valid_sysctl_key("net/ipv4/ip_forward"));
+        assert!(!valid_sysctl_key("kernel.x; reboot"));
+    }
+}
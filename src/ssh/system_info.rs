@@ -1,44 +1,90 @@
 use crate::error::AnsibleError;
 use crate::ssh::client::SshClient;
-use crate::types::{NetworkInterface, SystemInfo};
+use crate::types::{DiskUsage, FactSubset, NetworkInterface, SystemInfo};
 use std::collections::HashMap;
 use tracing::info;
 
 impl SshClient {
-    /// 获取远程主机的系统信息
+    /// 获取远程主机的系统信息（默认类别，与 `FactSubset::default()` 一致）
     pub fn get_system_info(&self) -> Result<SystemInfo, AnsibleError> {
-        let hostname = self.execute_command("hostname")?.stdout.trim().to_string();
-        let os = self.execute_command("uname -s")?.stdout.trim().to_string();
-        let kernel_version = self.execute_command("uname -r")?.stdout.trim().to_string();
-        let architecture = self.execute_command("uname -m")?.stdout.trim().to_string();
-        let uptime = self.execute_command("uptime")?.stdout.trim().to_string();
+        self.get_system_info_subset(FactSubset::default())
+    }
+
+    /// 只收集指定类别的系统信息，未收集的字段保持为空值，减少 SSH 往返次数
+    pub fn get_system_info_subset(&self, subset: FactSubset) -> Result<SystemInfo, AnsibleError> {
+        let mut info = SystemInfo::default();
+
+        if subset.contains(FactSubset::OS) {
+            self.collect_os_facts(&mut info)?;
+        }
+        if subset.contains(FactSubset::HARDWARE) {
+            self.collect_hardware_facts(&mut info)?;
+        }
+        if subset.contains(FactSubset::MOUNTS) {
+            self.collect_mount_facts(&mut info)?;
+        }
+        if subset.contains(FactSubset::NETWORK) {
+            self.collect_network_facts(&mut info)?;
+        }
+        if subset.contains(FactSubset::PACKAGES) {
+            self.collect_package_facts(&mut info)?;
+        }
+
+        info!("System info collected for {} ({:?})", self.config.hostname, subset);
+        Ok(info)
+    }
 
+    fn collect_os_facts(&self, info: &mut SystemInfo) -> Result<(), AnsibleError> {
+        // 精简的容器镜像中可能没有 hostname 命令
+        info.hostname = self
+            .execute_command("hostname 2>/dev/null || cat /etc/hostname")?
+            .stdout
+            .trim()
+            .to_string();
+        info.os = self.execute_command("uname -s")?.stdout.trim().to_string();
+        let distribution = self
+            .execute_command("grep -E '^PRETTY_NAME=' /etc/os-release 2>/dev/null | cut -d= -f2- | tr -d '\"'")?
+            .stdout
+            .trim()
+            .to_string();
+        info.distribution = if distribution.is_empty() { "Unknown".to_string() } else { distribution };
+        info.kernel_version = self.execute_command("uname -r")?.stdout.trim().to_string();
+        info.architecture = self.execute_command("uname -m")?.stdout.trim().to_string();
+        info.uptime = self.execute_command("uptime")?.stdout.trim().to_string();
+        Ok(())
+    }
+
+    fn collect_hardware_facts(&self, info: &mut SystemInfo) -> Result<(), AnsibleError> {
         // 获取内存信息
         let memory_info = self.execute_command("free -h | grep Mem")?;
         let memory_parts: Vec<&str> = memory_info.stdout.split_whitespace().collect();
-        let memory_total = memory_parts.get(1).unwrap_or(&"Unknown").to_string();
-        let memory_free = memory_parts.get(3).unwrap_or(&"Unknown").to_string();
-
-        // 获取磁盘使用情况
-        let disk_info = self.execute_command("df -h")?;
-        let mut disk_usage = HashMap::new();
-        for line in disk_info.stdout.lines().skip(1) {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 6 {
-                disk_usage.insert(parts[5].to_string(), parts[4].to_string());
-            }
-        }
+        info.memory_total = memory_parts.get(1).unwrap_or(&"Unknown").to_string();
+        info.memory_free = memory_parts.get(3).unwrap_or(&"Unknown").to_string();
 
         // 获取CPU信息
-        let cpu_info = self
+        info.cpu_info = self
             .execute_command("lscpu | grep 'Model name' | cut -d':' -f2 | xargs")?
             .stdout
             .trim()
             .to_string();
+        Ok(())
+    }
+
+    fn collect_mount_facts(&self, info: &mut SystemInfo) -> Result<(), AnsibleError> {
+        // POSIX 格式保证每个文件系统只占一行，容量单位为 1024 字节
+        let disk_info = self.execute_command("df -P -k")?;
+        info.disks = parse_df(&disk_info.stdout);
+        info.disk_usage = info
+            .disks
+            .iter()
+            .map(|disk| (disk.mount.clone(), format!("{}%", disk.percent)))
+            .collect();
+        Ok(())
+    }
 
+    fn collect_network_facts(&self, info: &mut SystemInfo) -> Result<(), AnsibleError> {
         // 获取网络接口信息
         let network_info = self.execute_command("ip addr show")?;
-        let mut network_interfaces = Vec::new();
 
         let mut current_interface = String::new();
         for line in network_info.stdout.lines() {
@@ -52,7 +98,7 @@ impl SshClient {
                 if let Some(ip_part) = parts.get(1) {
                     let ip = ip_part.split('/').next().unwrap_or("").to_string();
                     if !ip.is_empty() && ip != "127.0.0.1" {
-                        network_interfaces.push(NetworkInterface {
+                        info.network_interfaces.push(NetworkInterface {
                             name: current_interface.clone(),
                             ip_address: ip,
                             mac_address: "Unknown".to_string(), // 简化处理
@@ -61,20 +107,106 @@ impl SshClient {
                 }
             }
         }
+        Ok(())
+    }
+
+    fn collect_package_facts(&self, info: &mut SystemInfo) -> Result<(), AnsibleError> {
+        // dpkg（Debian 系）或 rpm（RHEL 系），每行 "名称 版本"
+        let result = self.execute_command(
+            "dpkg-query -W -f='${Package} ${Version}\\n' 2>/dev/null || rpm -qa --qf '%{NAME} %{VERSION}-%{RELEASE}\\n' 2>/dev/null",
+        )?;
+        info.packages = parse_package_list(&result.stdout);
+        Ok(())
+    }
+}
 
-        info!("System info collected for {}", hostname);
-
-        Ok(SystemInfo {
-            hostname,
-            os,
-            kernel_version,
-            architecture,
-            uptime,
-            memory_total,
-            memory_free,
-            disk_usage,
-            cpu_info,
-            network_interfaces,
+/// 解析 "名称 版本" 格式的软件包列表
+fn parse_package_list(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, version) = line.trim().split_once(' ')?;
+            Some((name.to_string(), version.trim().to_string()))
         })
+        .collect()
+}
+
+/// 解析 `df -P -k` 的输出
+///
+/// 以 `<3 个数字> <百分比>` 定位各列，因此设备名与挂载点中包含空格也能正确解析。
+fn parse_df(output: &str) -> Vec<DiskUsage> {
+    let mut disks = Vec::new();
+    for line in output.lines().skip(1) {
+        // 记录每个字段的起始位置，以便挂载点保留原始空格
+        let mut fields: Vec<(usize, &str)> = Vec::new();
+        let mut start = None;
+        for (index, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+            match (c.is_whitespace(), start) {
+                (true, Some(s)) => {
+                    fields.push((s, &line[s..index]));
+                    start = None;
+                }
+                (false, None) => start = Some(index),
+                _ => {}
+            }
+        }
+
+        let columns = (1..fields.len().saturating_sub(4)).find(|&i| {
+            fields[i..i + 3].iter().all(|(_, f)| f.parse::<u64>().is_ok())
+                && (fields[i + 3].1.ends_with('%') || fields[i + 3].1 == "-")
+        });
+        let Some(i) = columns else {
+            continue;
+        };
+        let kb = |index: usize| fields[index].1.parse::<u64>().unwrap_or(0) * 1024;
+        disks.push(DiskUsage {
+            device: line[..fields[i].0].trim_end().to_string(),
+            total: kb(i),
+            used: kb(i + 1),
+            avail: kb(i + 2),
+            percent: fields[i + 3].1.trim_end_matches('%').parse().unwrap_or(0),
+            mount: line[fields[i + 4].0..].trim_end().to_string(),
+        });
+    }
+    disks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_package_list() {
+        let packages = parse_package_list("openssl 3.0.11-1~deb12u2\nbash 5.2.15-2+b2\n\nbroken\n");
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages["openssl"], "3.0.11-1~deb12u2");
+    }
+
+    #[test]
+    fn test_parse_df() {
+        let output = "\
+Filesystem                                       1024-blocks     Used Available Capacity Mounted on
+udev                                                 4029428        0   4029428       0% /dev
+/dev/mapper/ubuntu--vg-ubuntu--lv--with--a--long--name  102626232 45678912  51687420      47% /
+tmpfs                                                 812340     1580    810760       1% /run
+//nas.example.com/share                            976762584 12345678 964416906       2% /mnt/team share
+none                                                       0        0         0        - /sys/fs/bpf
+";
+        let disks = parse_df(output);
+        assert_eq!(disks.len(), 5);
+        assert_eq!(
+            disks[1],
+            DiskUsage {
+                device: "/dev/mapper/ubuntu--vg-ubuntu--lv--with--a--long--name".to_string(),
+                total: 102626232 * 1024,
+                used: 45678912 * 1024,
+                avail: 51687420 * 1024,
+                percent: 47,
+                mount: "/".to_string(),
+            }
+        );
+        assert_eq!(disks[3].mount, "/mnt/team share");
+        assert_eq!(disks[4].percent, 0);
+        assert!(parse_df("Filesystem 1024-blocks Used Available Capacity Mounted on\n").is_empty());
     }
 }
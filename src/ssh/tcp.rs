@@ -0,0 +1,188 @@
+use crate::error::AnsibleError;
+use crate::types::TcpConnection;
+use super::SshClient;
+use tracing::info;
+
+const CONNECTIONS_COMMAND: &str =
+    "if command -v ss >/dev/null 2>&1; then ss -tanp; else netstat -tanp 2>/dev/null; fi";
+
+impl SshClient {
+    /// 获取主机上的 TCP 连接，`state_filter` 按状态过滤（例如 `established`、`listening`、`time-wait`）
+    ///
+    /// 优先使用 `ss -tanp`，没有 ss 时使用 `netstat -tanp`。状态名统一为 ss 的写法，
+    /// 过滤时不区分大小写，并接受 ss/netstat 的缩写（`ESTAB`、`LISTEN`、`TIME_WAIT`）。
+    /// 进程信息需要 root 权限（become），否则 `pid` / `program` 为 None。
+    pub fn get_tcp_connections(&self, state_filter: Option<&str>) -> Result<Vec<TcpConnection>, AnsibleError> {
+        let result = self.execute_command(CONNECTIONS_COMMAND)?;
+        if result.exit_code != 0 || result.stdout.trim().is_empty() {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to list TCP connections (ss/netstat not available?): {}",
+                result.stderr.trim()
+            )));
+        }
+        let mut connections = parse_tcp_connections(&result.stdout);
+        if let Some(filter) = state_filter {
+            let filter = normalize_state(filter);
+            connections.retain(|c| c.state == filter);
+        }
+        info!("Found {} TCP connection(s) on {}", connections.len(), self.config.hostname);
+        Ok(connections)
+    }
+
+    /// 本机端口上已建立的连接数（用于快速估计负载）
+    pub fn count_connections_to_port(&self, port: u16) -> Result<u32, AnsibleError> {
+        let connections = self.get_tcp_connections(Some("established"))?;
+        Ok(count_to_port(&connections, port))
+    }
+}
+
+fn count_to_port(connections: &[TcpConnection], port: u16) -> u32 {
+    connections.iter().filter(|c| c.local_port == port).count() as u32
+}
+
+/// 统一 ss 与 netstat 的状态名，例如 `ESTAB` / `ESTABLISHED` -> `established`，`TIME_WAIT` -> `time-wait`
+fn normalize_state(state: &str) -> String {
+    let state = state.trim().to_ascii_lowercase().replace('_', "-");
+    match state.as_str() {
+        "estab" => "established".to_string(),
+        "listen" => "listening".to_string(),
+        "syn-recv" => "syn-received".to_string(),
+        _ => state,
+    }
+}
+
+/// 解析 `ss -tanp` 或 `netstat -tanp` 的输出（按表头识别格式）
+fn parse_tcp_connections(output: &str) -> Vec<TcpConnection> {
+    let mut lines = output.lines().filter(|l| !l.trim().is_empty()).peekable();
+    let netstat = lines.peek().is_some_and(|l| l.starts_with("Active") || l.starts_with("Proto"));
+
+    lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // ss:      State Recv-Q Send-Q Local:Port Peer:Port [Process]
+            // netstat: Proto Recv-Q Send-Q Local:Port Foreign:Port State [PID/Program]
+            let (state, local, remote, process) = if netstat {
+                if !fields.first()?.starts_with("tcp") || fields.len() < 6 {
+                    return None;
+                }
+                (fields[5], fields[3], fields[4], fields.get(6).copied())
+            } else {
+                if fields.len() < 5 || fields[0] == "State" {
+                    return None;
+                }
+                (fields[0], fields[3], fields[4], fields.get(5).copied())
+            };
+
+            let (local_address, local_port) = split_endpoint(local)?;
+            let (remote_address, remote_port) = split_endpoint(remote)?;
+            let (pid, program) = match process {
+                Some(p) if netstat => parse_netstat_process(p),
+                Some(p) => parse_ss_process(p),
+                None => (None, None),
+            };
+            Some(TcpConnection {
+                local_address,
+                local_port,
+                remote_address,
+                remote_port,
+                state: normalize_state(state),
+                pid,
+                program,
+            })
+        })
+        .collect()
+}
+
+/// 拆分 `地址:端口`，支持 `[::1]:22`、`:::80`、`0.0.0.0:*` 与 `127.0.0.53%lo:53`
+fn split_endpoint(endpoint: &str) -> Option<(String, u16)> {
+    let (address, port) = endpoint.rsplit_once(':')?;
+    let port = if port == "*" { 0 } else { port.parse().ok()? };
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let address = address.split('%').next().unwrap_or(address);
+    Some((address.to_string(), port))
+}
+
+/// ss 的进程列：`users:(("nginx",pid=900,fd=6),("nginx",pid=901,fd=6))`，取第一个进程
+fn parse_ss_process(field: &str) -> (Option<u32>, Option<String>) {
+    let program = field.split('"').nth(1).map(str::to_string);
+    let pid = field
+        .split("pid=")
+        .nth(1)
+        .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+        .and_then(|pid| pid.parse().ok());
+    (pid, program)
+}
+
+/// netstat 的进程列：`812/sshd`，无权限时为 `-`
+fn parse_netstat_process(field: &str) -> (Option<u32>, Option<String>) {
+    match field.split_once('/') {
+        Some((pid, program)) => (pid.parse().ok(), Some(program.trim_end_matches(':').to_string())),
+        None => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SS_OUTPUT: &str = "\
+State     Recv-Q Send-Q        Local Address:Port         Peer Address:Port  Process
+LISTEN    0      511                 0.0.0.0:80                0.0.0.0:*      users:((\"nginx\",pid=901,fd=6),(\"nginx\",pid=900,fd=6))
+LISTEN    0      4096          127.0.0.53%lo:53                0.0.0.0:*
+ESTAB     0      0                 10.0.0.5:22                10.0.0.1:53422  users:((\"sshd\",pid=1234,fd=4))
+ESTAB     0      0                 10.0.0.5:80              10.0.0.20:41000  users:((\"nginx\",pid=901,fd=12))
+ESTAB     0      0        [::ffff:10.0.0.5]:80     [::ffff:10.0.0.21]:41002
+TIME-WAIT 0      0                 10.0.0.5:80              10.0.0.22:40998
+LISTEN    0      128                    [::]:22                   [::]:*      users:((\"sshd\",pid=812,fd=4))
+";
+
+    #[test]
+    fn test_parse_ss_output_and_filter_states() {
+        let connections = parse_tcp_connections(SS_OUTPUT);
+        assert_eq!(connections.len(), 7);
+        assert_eq!(
+            connections[2],
+            TcpConnection {
+                local_address: "10.0.0.5".to_string(),
+                local_port: 22,
+                remote_address: "10.0.0.1".to_string(),
+                remote_port: 53422,
+                state: "established".to_string(),
+                pid: Some(1234),
+                program: Some("sshd".to_string()),
+            }
+        );
+        assert_eq!((connections[0].pid, connections[0].program.as_deref()), (Some(901), Some("nginx")));
+        assert_eq!((connections[1].local_address.as_str(), connections[1].pid), ("127.0.0.53", None));
+        assert_eq!(connections[4].remote_address, "::ffff:10.0.0.21");
+        assert_eq!((connections[6].local_address.as_str(), connections[6].remote_port), ("::", 0));
+
+        let established: Vec<TcpConnection> = connections
+            .iter()
+            .filter(|c| c.state == normalize_state("ESTAB"))
+            .cloned()
+            .collect();
+        assert_eq!(established.len(), 3);
+        assert_eq!(count_to_port(&established, 80), 2);
+        assert_eq!(connections.iter().filter(|c| c.state == normalize_state("listening")).count(), 3);
+        assert_eq!(connections.iter().filter(|c| c.state == normalize_state("TIME_WAIT")).count(), 1);
+    }
+
+    #[test]
+    fn test_parse_netstat_output() {
+        let output = "\
+Active Internet connections (servers and established)
+Proto Recv-Q Send-Q Local Address           Foreign Address         State       PID/Program name
+tcp        0      0 0.0.0.0:22              0.0.0.0:*               LISTEN      812/sshd
+tcp        0     36 10.0.0.5:22             10.0.0.1:53422          ESTABLISHED 1234/sshd: deploy
+tcp6       0      0 :::80                   :::*                    LISTEN      -
+";
+        let connections = parse_tcp_connections(output);
+        assert_eq!(connections.len(), 3);
+        assert_eq!(connections[0].state, "listening");
+        assert_eq!((connections[1].state.as_str(), connections[1].pid), ("established", Some(1234)));
+        assert_eq!(connections[1].program.as_deref(), Some("sshd"));
+        assert_eq!((connections[2].local_address.as_str(), connections[2].local_port), ("::", 80));
+        assert_eq!(connections[2].program, None);
+    }
+}
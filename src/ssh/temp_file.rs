@@ -0,0 +1,77 @@
+use super::SshClient;
+use crate::utils::{generate_remote_temp_path, shell_quote};
+use tracing::{debug, warn};
+
+/// 远程临时文件守卫
+///
+/// 在 Drop 时删除远程临时文件，除非已调用 `commit`（例如已被 `mv` 到目标位置）。
+/// 设置 `keep_on_failure` 后，未提交的临时文件会被保留以便排查问题。
+pub struct RemoteTempFile<'a> {
+    client: &'a SshClient,
+    path: String,
+    committed: bool,
+    keep_on_failure: bool,
+}
+
+impl<'a> RemoteTempFile<'a> {
+    pub fn new(client: &'a SshClient, path: String) -> Self {
+        Self {
+            client,
+            path,
+            committed: false,
+            keep_on_failure: false,
+        }
+    }
+
+    /// 失败（未提交）时保留临时文件，用于调试
+    pub fn keep_on_failure(mut self, keep: bool) -> Self {
+        self.keep_on_failure = keep;
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 标记临时文件已被正常使用（例如已移动到目标位置），Drop 时不再删除
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+
+    /// 立即删除临时文件（忽略 `keep_on_failure`）
+    pub fn discard(mut self) {
+        self.remove();
+        self.committed = true;
+    }
+
+    fn remove(&self) {
+        debug!("Removing remote temp file: {}", self.path);
+        if let Err(e) = self.client.execute_command(&format!("rm -f {}", shell_quote(&self.path))) {
+            warn!("Failed to remove remote temp file {}: {}", self.path, e);
+        }
+    }
+}
+
+impl Drop for RemoteTempFile<'_> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        if self.keep_on_failure {
+            warn!("Keeping remote temp file for debugging: {}", self.path);
+            return;
+        }
+        self.remove();
+    }
+}
+
+/// 未配置 `remote_tmp` 时使用的远程临时目录
+const DEFAULT_REMOTE_TMP: &str = "/tmp";
+
+impl SshClient {
+    /// 在主机配置的远程临时目录下生成唯一的临时文件路径
+    pub(super) fn remote_temp_path(&self, prefix: &str) -> String {
+        let dir = self.config.remote_tmp.as_deref().unwrap_or(DEFAULT_REMOTE_TMP);
+        generate_remote_temp_path(&format!("{}/{}", dir.trim_end_matches('/'), prefix))
+    }
+}
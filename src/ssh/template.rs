@@ -1,33 +1,254 @@
 use crate::error::AnsibleError;
-use crate::types::{TemplateOptions, TemplateResult, FileCopyOptions};
-use crate::utils::{generate_local_temp_path, generate_remote_temp_path};
+use crate::diff::unified_diff;
+use crate::types::{HostConfig, TemplateOptions, TemplateResult, FileCopyOptions, TemplateCacheStats};
+use crate::utils::{generate_remote_temp_path, run_local_command, sha256_hex, shell_quote, FileMode, LocalTempFile};
 use super::SshClient;
+use super::temp_file::RemoteTempFile;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tera::{Tera, Context};
-use tracing::{info, debug, error};
+use tracing::{info, debug, error, warn};
+
+/// 模板渲染缓存
+///
+/// 在一次批量部署中共享：同一模板只读取一次，解析结果只按模板内容缓存，因此无论主机变量是否不同，
+/// N 台主机只解析一次；模板内容与最终变量（含主机相关变量）完全相同的渲染结果只渲染一次，
+/// 并复用同一个本地临时文件上传。缓存被释放时临时文件随之删除。
+#[derive(Default)]
+pub struct TemplateCache {
+    inner: Mutex<TemplateCacheInner>,
+}
+
+#[derive(Default)]
+struct TemplateCacheInner {
+    sources: HashMap<String, Arc<String>>,             // 模板路径 -> 模板内容
+    parsed: HashMap<String, Arc<Tera>>,                // 内容 hash -> 已解析的模板（未注册查找函数）
+    instances: HashMap<String, Arc<Tera>>,             // 内容 hash + 查找设置 hash -> 注册了查找函数的模板
+    rendered: HashMap<String, Arc<RenderedTemplate>>,  // 内容 hash + 查找设置 hash + 变量 hash -> 渲染结果
+    parses: usize,
+    parse_hits: usize,
+    renders: usize,
+    render_hits: usize,
+    parse_time: Duration,
+    render_time: Duration,
+}
+
+/// 渲染结果及其（按需创建的）本地临时文件
+struct RenderedTemplate {
+    content: String,
+    artifact: Mutex<Option<Arc<LocalTempFile>>>,
+}
+
+impl RenderedTemplate {
+    /// 获取承载渲染内容的本地文件，首次调用时创建，之后在所有主机间复用
+    fn local_file(&self) -> Result<Arc<LocalTempFile>, AnsibleError> {
+        let mut artifact = self.artifact.lock().expect("template artifact poisoned");
+        if let Some(ref file) = *artifact {
+            return Ok(file.clone());
+        }
+        let file = Arc::new(LocalTempFile::create("rs_ansible_template", self.content.as_bytes())?);
+        *artifact = Some(file.clone());
+        Ok(file)
+    }
+}
+
+/// reload 失败时用于恢复的原文件副本
+enum RollbackBackup<'a> {
+    Kept(String),                  // `backup` 选项创建的持久备份
+    Temporary(RemoteTempFile<'a>), // 仅用于回滚的临时备份
+}
+
+impl RollbackBackup<'_> {
+    fn path(&self) -> &str {
+        match self {
+            RollbackBackup::Kept(path) => path,
+            RollbackBackup::Temporary(file) => file.path(),
+        }
+    }
+}
+
+impl TemplateCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前的缓存统计
+    pub fn stats(&self) -> TemplateCacheStats {
+        let inner = self.inner.lock().expect("template cache poisoned");
+        let avg = |total: Duration, count: usize| {
+            if count == 0 { Duration::ZERO } else { total / count as u32 }
+        };
+        let saved = avg(inner.parse_time, inner.parses) * inner.parse_hits as u32
+            + avg(inner.render_time, inner.renders) * inner.render_hits as u32;
+
+        TemplateCacheStats {
+            parses: inner.parses,
+            parse_cache_hits: inner.parse_hits,
+            renders: inner.renders,
+            render_cache_hits: inner.render_hits,
+            parse_time_ms: inner.parse_time.as_secs_f64() * 1000.0,
+            render_time_ms: inner.render_time.as_secs_f64() * 1000.0,
+            estimated_saved_ms: saved.as_secs_f64() * 1000.0,
+        }
+    }
+
+    /// 已创建的本地临时文件路径（用于测试清理行为）
+    #[cfg(test)]
+    pub(crate) fn artifact_paths(&self) -> Vec<String> {
+        let inner = self.inner.lock().expect("template cache poisoned");
+        inner
+            .rendered
+            .values()
+            .filter_map(|r| r.artifact.lock().expect("template artifact poisoned").as_ref().map(|f| f.path().to_string()))
+            .collect()
+    }
+
+    /// 仅在本地渲染模板，不连接主机
+    ///
+    /// 渲染上下文与部署时一致（含按 `host` 注入的主机变量），结果同样进入缓存，
+    /// 之后使用同一缓存部署时不会重复渲染。
+    pub fn render_for_host(&self, options: &TemplateOptions, host: &HostConfig) -> Result<String, AnsibleError> {
+        let template = self.source(&options.src)?;
+        let rendered = self.render(&template, options, template_variables(host, &options.variables))?;
+        Ok(rendered.content.clone())
+    }
+
+    /// 读取模板文件（同一路径只读取一次）
+    fn source(&self, path: &str) -> Result<Arc<String>, AnsibleError> {
+        if let Some(source) = self.inner.lock().expect("template cache poisoned").sources.get(path) {
+            return Ok(source.clone());
+        }
+
+        debug!("Reading template file: {}", path);
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            error!("Failed to read template file '{}': {}", path, e);
+            AnsibleError::FileOperationError(format!("Failed to read template file: {}", e))
+        })?;
+        let content = Arc::new(content);
+        self.inner
+            .lock()
+            .expect("template cache poisoned")
+            .sources
+            .insert(path.to_string(), content.clone());
+        Ok(content)
+    }
+
+    /// 渲染模板：解析结果的缓存键只有模板内容 hash；渲染结果的缓存键另含查找函数设置与完整变量
+    /// （含主机相关变量）的 hash
+    fn render(
+        &self,
+        template: &str,
+        options: &TemplateOptions,
+        variables: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Arc<RenderedTemplate>, AnsibleError> {
+        let lookup = LookupSettings::new(options);
+        let content_hash = sha256_hex(template.as_bytes());
+        let instance_key = format!("{}:{}", content_hash, lookup.cache_key());
+        // serde_json::Map 按键排序，序列化结果稳定
+        let vars_json = serde_json::Value::Object(variables);
+        let key = format!("{}:{}", instance_key, sha256_hex(vars_json.to_string().as_bytes()));
+
+        let tera = {
+            let mut inner = self.inner.lock().expect("template cache poisoned");
+            if let Some(rendered) = inner.rendered.get(&key).cloned() {
+                inner.render_hits += 1;
+                debug!("Template render cache hit");
+                return Ok(rendered);
+            }
+
+            match inner.parsed.get(&content_hash).cloned() {
+                Some(parsed) => {
+                    inner.parse_hits += 1;
+                    match inner.instances.get(&instance_key).cloned() {
+                        Some(tera) => tera,
+                        None => {
+                            // 同一模板使用不同的查找设置：复用解析结果，只注册查找函数
+                            let mut tera = (*parsed).clone();
+                            lookup.register(&mut tera);
+                            let tera = Arc::new(tera);
+                            inner.instances.insert(instance_key, tera.clone());
+                            tera
+                        }
+                    }
+                }
+                None => {
+                    // 在持有锁时解析，保证同一模板只解析一次
+                    debug!("Parsing template, size: {} bytes", template.len());
+                    let started = Instant::now();
+                    let mut parsed = Tera::default();
+                    parsed.add_raw_template("template", template).map_err(|e| {
+                        error!("Failed to parse template: {}", e);
+                        AnsibleError::TemplateError(format!("Failed to parse template: {}", e))
+                    })?;
+                    let mut tera = parsed.clone();
+                    lookup.register(&mut tera);
+                    let tera = Arc::new(tera);
+                    inner.parses += 1;
+                    inner.parse_time += started.elapsed();
+                    inner.parsed.insert(content_hash, Arc::new(parsed));
+                    inner.instances.insert(instance_key, tera.clone());
+                    tera
+                }
+            }
+        };
+
+        debug!("Rendering template with Tera engine");
+        let started = Instant::now();
+        let context = Context::from_value(vars_json).map_err(|e| {
+            AnsibleError::TemplateError(format!("Invalid template variables: {}", e))
+        })?;
+        let mut content = tera.render("template", &context).map_err(|e| {
+            let message = error_chain(&e);
+            error!("Failed to render template: {}", message);
+            AnsibleError::TemplateError(format!("Failed to render template: {}", message))
+        })?;
+
+        // 确保渲染后的内容使用 Unix 换行符 (\n)，避免在 Windows 上生成 \r\n 导致执行失败
+        if content.contains('\r') {
+            debug!("Removing CR characters from rendered template content");
+            content = content.replace('\r', "");
+        }
+
+        let rendered = Arc::new(RenderedTemplate {
+            content,
+            artifact: Mutex::new(None),
+        });
+        let mut inner = self.inner.lock().expect("template cache poisoned");
+        inner.renders += 1;
+        inner.render_time += started.elapsed();
+        inner.rendered.insert(key, rendered.clone());
+        Ok(rendered)
+    }
+}
 
 impl SshClient {
     /// 部署模板到远程主机
     pub fn deploy_template(&self, options: &TemplateOptions) -> Result<TemplateResult, AnsibleError> {
+        self.deploy_template_with_cache(options, &TemplateCache::new())
+    }
+
+    /// 部署模板到远程主机，模板解析、渲染结果与本地临时文件通过 `cache` 在多台主机间复用
+    pub fn deploy_template_with_cache(
+        &self,
+        options: &TemplateOptions,
+        cache: &TemplateCache,
+    ) -> Result<TemplateResult, AnsibleError> {
         info!("Deploying template from '{}' to '{}'", options.src, options.dest);
+
+        // 渲染前先校验权限格式
+        if let Some(ref mode) = options.mode {
+            FileMode::parse(mode)?;
+        }
         
         // 读取本地模板文件
-        debug!("Reading template file: {}", options.src);
-        let template_content = std::fs::read_to_string(&options.src)
-            .map_err(|e| {
-                error!("Failed to read template file '{}': {}", options.src, e);
-                AnsibleError::FileOperationError(format!("Failed to read template file: {}", e))
-            })?;
+        let template_content = cache.source(&options.src)?;
         
         // 渲染模板
         debug!("Rendering template with {} variables", options.variables.len());
-        let mut rendered_content = self.render_template(&template_content, &options.variables)?;
-        
-        // 确保渲染后的内容使用 Unix 换行符 (\n)，避免在 Windows 上生成 \r\n 导致执行失败
-        if rendered_content.contains('\r') {
-            debug!("Removing CR characters from rendered template content");
-            rendered_content = rendered_content.replace('\r', "");
-        }
+        let rendered = cache.render(&template_content, options, template_variables(&self.config, &options.variables))?;
+        let rendered_content = &rendered.content;
         
         info!("Template rendered successfully, size: {} bytes", rendered_content.len());
         
@@ -36,6 +257,7 @@ impl SshClient {
         let remote_exists = self.check_file_exists(&options.dest)?;
         let mut changed = false;
         let mut diff = None;
+        let mut backup_path = None;
         
         if remote_exists {
             debug!("Remote file exists, comparing content");
@@ -43,15 +265,15 @@ impl SshClient {
             let remote_content = self.read_remote_file(&options.dest)?;
             
             // 比较内容
-            if remote_content != rendered_content {
+            if remote_content != *rendered_content {
                 info!("Content differs, file will be updated");
                 changed = true;
-                diff = Some(self.generate_diff(&remote_content, &rendered_content));
+                diff = Some(unified_diff(&options.dest, remote_content.as_bytes(), rendered_content.as_bytes()));
                 
                 // 如果需要备份
                 if options.backup {
                     info!("Creating backup of existing file");
-                    self.backup_remote_file(&options.dest)?;
+                    backup_path = Some(self.backup_remote_file(&options.dest)?);
                 }
             } else {
                 debug!("Content is identical, no changes needed");
@@ -59,26 +281,21 @@ impl SshClient {
         } else {
             info!("Remote file does not exist, will be created");
             changed = true;
+            diff = Some(unified_diff(&options.dest, b"", rendered_content.as_bytes()));
         }
         
         // 如果有变更，写入新内容
         if changed {
             info!("Deploying changed content to remote host");
-            // 创建本地临时文件（使用统一的工具函数生成唯一路径）
-            let local_temp = generate_local_temp_path("rs_ansible_template");
-            
-            // 写入渲染后的内容到本地临时文件
-            debug!("Writing rendered content to local temp file: {}", local_temp);
-            std::fs::write(&local_temp, &rendered_content)
-                .map_err(|e| {
-                    error!("Failed to write temp file: {}", e);
-                    AnsibleError::FileOperationError(format!("Failed to write temp file: {}", e))
-                })?;
+            // 复用缓存中的本地临时文件（由缓存负责清理）
+            let local_file = rendered.local_file()?;
+            let local_temp = local_file.path();
             
             // 如果提供了验证命令，需要先上传到临时位置验证
             if let Some(ref validate_cmd) = options.validate {
                 info!("Validating template before deployment");
-                let temp_remote = generate_remote_temp_path("/tmp/rs_ansible_validate");
+                let temp_remote = RemoteTempFile::new(self, self.remote_temp_path("rs_ansible_validate"))
+                    .keep_on_failure(options.keep_temp_on_failure);
                 
                 // ✅ 使用 file_transfer 的方法上传到临时位置（带 SHA256 验证）
                 let temp_options = FileCopyOptions {
@@ -87,24 +304,29 @@ impl SshClient {
                     group: None,
                     backup: false,
                     create_dirs: true,
-                    precomputed_hash: None,
+                    precomputed_hash: Some(local_file.hash().to_string()),
+                    keep_temp_on_failure: options.keep_temp_on_failure,
+                    max_bandwidth_bytes_per_sec: None,
+                    follow_symlinks: false,
+                    atomic: true,
+                    diff: false,
                 };
-                self.copy_file_to_remote_with_options(&local_temp, &temp_remote, &temp_options)?;
+                self.copy_file_to_remote_with_options(local_temp, temp_remote.path(), &temp_options)?;
                 
                 // 执行验证命令
-                let validation_cmd = validate_cmd.replace("%s", &temp_remote);
+                let validation_cmd = render_validate_command(validate_cmd, temp_remote.path(), &options.dest)?;
                 let result = self.execute_command(&validation_cmd)?;
                 
-                // 清理远程临时文件
-                let _ = self.execute_command(&format!("rm -f '{}'", temp_remote));
-                
                 if result.exit_code != 0 {
+                    // 验证失败，临时文件由守卫清理（或按 keep_temp_on_failure 保留）
                     error!("Template validation failed: {}", result.stderr);
-                    let _ = std::fs::remove_file(&local_temp);
                     return Err(AnsibleError::ValidationError(format!(
                         "Template validation failed: {}", result.stderr
                     )));
                 }
+
+                // 清理远程临时文件
+                temp_remote.discard();
                 info!("Template validation passed");
             }
             
@@ -116,15 +338,33 @@ impl SshClient {
                 group: options.group.clone(),
                 backup: false, // 已经在前面处理过备份
                 create_dirs: true, // 自动创建目标目录
-                precomputed_hash: None,
+                precomputed_hash: Some(local_file.hash().to_string()),
+                keep_temp_on_failure: options.keep_temp_on_failure,
+                max_bandwidth_bytes_per_sec: None,
+                follow_symlinks: false,
+                atomic: true,
+                diff: false,
             };
             
-            let transfer_result = self.copy_file_to_remote_with_options(&local_temp, &options.dest, &file_options)?;
+            // reload 失败时需要原文件才能回滚：未开启 backup 时临时备份，成功后删除
+            let rollback_backup = if options.rollback_on_failure && options.reload_command.is_some() && remote_exists {
+                match backup_path {
+                    Some(ref path) => Some(RollbackBackup::Kept(path.clone())),
+                    None => Some(RollbackBackup::Temporary(self.temporary_backup(&options.dest)?)),
+                }
+            } else {
+                None
+            };
+
+            let transfer_result = self.copy_file_to_remote_with_options(local_temp, &options.dest, &file_options)?;
             info!("Template uploaded: {}", transfer_result.message);
-            
-            // 清理本地临时文件
-            let _ = std::fs::remove_file(&local_temp);
             info!("Template deployed successfully to {}", options.dest);
+
+            if let Some(ref reload_command) = options.reload_command
+                && let Err(reload_error) = self.run_reload_command(reload_command)
+            {
+                return Ok(self.handle_reload_failure(options, reload_error, rollback_backup.as_ref(), diff));
+            }
         } else {
             info!("Template at {} is already up to date", options.dest);
         }
@@ -138,44 +378,98 @@ impl SshClient {
                 format!("Template at {} is already up to date", options.dest)
             },
             diff,
+            rolled_back: false,
+            critical: false,
         })
     }
 
-    /// 渲染模板（使用 Tera 模板引擎）
-    fn render_template(&self, template: &str, variables: &HashMap<String, serde_json::Value>) -> Result<String, AnsibleError> {
-        debug!("Creating Tera template engine instance");
-        // 创建 Tera 实例
-        let mut tera = Tera::default();
-        
-        // 添加模板字符串
-        debug!("Parsing template, size: {} bytes", template.len());
-        tera.add_raw_template("template", template)
-            .map_err(|e| {
-                error!("Failed to parse template: {}", e);
-                AnsibleError::TemplateError(format!("Failed to parse template: {}", e))
-            })?;
-        
-        // 创建上下文并添加变量
-        debug!("Adding {} variables to template context", variables.len());
-        let mut context = Context::new();
-        for (key, value) in variables {
-            // ✅ 直接插入 serde_json::Value，Tera 的 Context 支持任意可序列化的值
-            context.insert(key, value);
+    /// 执行 reload 命令，非零退出码视为失败
+    fn run_reload_command(&self, command: &str) -> Result<(), AnsibleError> {
+        info!("Running reload command: {}", command);
+        let result = self.execute_command(command)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "'{}' exited with code {}: {}",
+                command,
+                result.exit_code,
+                result.stderr.trim()
+            )));
         }
+        Ok(())
+    }
 
-        // 自动注入 Host 信息
-        context.insert("ansible_host", &self.config.hostname); // HostConfig 中的 hostname 通常是 IP 或者可解析的主机名
-        context.insert("inventory_hostname", &self.config.hostname); 
-        context.insert("ansible_port", &self.config.port);
-        context.insert("ansible_user", &self.config.username);
-        
-        // 渲染模板
-        debug!("Rendering template with Tera engine");
-        tera.render("template", &context)
-            .map_err(|e| {
-                error!("Failed to render template: {}", e);
-                AnsibleError::TemplateError(format!("Failed to render template: {}", e))
-            })
+    /// reload 失败：按 `rollback_on_failure` 恢复原文件，恢复也失败时标记为 critical
+    fn handle_reload_failure(
+        &self,
+        options: &TemplateOptions,
+        reload_error: AnsibleError,
+        backup: Option<&RollbackBackup<'_>>,
+        diff: Option<String>,
+    ) -> TemplateResult {
+        error!("Reload after deploying {} failed: {}", options.dest, reload_error);
+        let failed = |message: String, rolled_back: bool, critical: bool| TemplateResult {
+            success: false,
+            changed: !rolled_back,
+            message,
+            diff: diff.clone(),
+            rolled_back,
+            critical,
+        };
+        if !options.rollback_on_failure {
+            return failed(
+                format!("Reload failed after deploying {}, new content left in place: {}", options.dest, reload_error),
+                false,
+                false,
+            );
+        }
+
+        // 原文件不存在时删除新文件
+        let restore = match backup {
+            Some(backup) => format!("cp -p {} {}", shell_quote(backup.path()), shell_quote(&options.dest)),
+            None => format!("rm -f {}", shell_quote(&options.dest)),
+        };
+        match self.execute_command(&restore) {
+            Ok(result) if result.exit_code == 0 => {
+                warn!("Rolled back {} after reload failure", options.dest);
+                failed(
+                    format!("Reload failed after deploying {}, rolled back: {}", options.dest, reload_error),
+                    true,
+                    false,
+                )
+            }
+            restore_result => {
+                let restore_error = match restore_result {
+                    Ok(result) => result.stderr.trim().to_string(),
+                    Err(e) => e.to_string(),
+                };
+                error!(
+                    "CRITICAL: rollback of {} on {} failed, host is in an inconsistent state: {}",
+                    options.dest, self.config.hostname, restore_error
+                );
+                failed(
+                    format!(
+                        "CRITICAL: reload failed after deploying {} ({}) and rollback failed ({}), host is in an inconsistent state",
+                        options.dest, reload_error, restore_error
+                    ),
+                    false,
+                    true,
+                )
+            }
+        }
+    }
+
+    /// 为回滚复制一份原文件（守卫在部署结束后删除）
+    fn temporary_backup(&self, path: &str) -> Result<RemoteTempFile<'_>, AnsibleError> {
+        let backup = RemoteTempFile::new(self, generate_remote_temp_path(path));
+        let result = self.execute_command(&format!("cp -p {} {}", shell_quote(path), shell_quote(backup.path())))?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::FileOperationError(format!(
+                "Failed to back up {} before deployment: {}",
+                path,
+                result.stderr.trim()
+            )));
+        }
+        Ok(backup)
     }
 
     /// 检查远程文件是否存在
@@ -186,7 +480,7 @@ impl SshClient {
     }
 
     /// 读取远程文件内容
-    fn read_remote_file(&self, path: &str) -> Result<String, AnsibleError> {
+    pub(super) fn read_remote_file(&self, path: &str) -> Result<String, AnsibleError> {
         let cmd = format!("cat '{}'", path);
         let result = self.execute_command(&cmd)?;
         
@@ -199,36 +493,8 @@ impl SshClient {
         Ok(result.stdout)
     }
 
-    /// 生成文件差异
-    fn generate_diff(&self, old_content: &str, new_content: &str) -> String {
-        // 简单的行差异显示
-        let old_lines: Vec<&str> = old_content.lines().collect();
-        let new_lines: Vec<&str> = new_content.lines().collect();
-        
-        let mut diff = String::new();
-        diff.push_str("--- old\n");
-        diff.push_str("+++ new\n");
-        
-        let max_lines = old_lines.len().max(new_lines.len());
-        for i in 0..max_lines {
-            let old_line = old_lines.get(i).unwrap_or(&"");
-            let new_line = new_lines.get(i).unwrap_or(&"");
-            
-            if old_line != new_line {
-                if !old_line.is_empty() {
-                    diff.push_str(&format!("- {}\n", old_line));
-                }
-                if !new_line.is_empty() {
-                    diff.push_str(&format!("+ {}\n", new_line));
-                }
-            }
-        }
-        
-        diff
-    }
-
-    /// 备份远程文件
-    fn backup_remote_file(&self, path: &str) -> Result<(), AnsibleError> {
+    /// 备份远程文件，返回备份路径
+    fn backup_remote_file(&self, path: &str) -> Result<String, AnsibleError> {
         let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
         let backup_path = format!("{}.{}.backup", path, timestamp);
         
@@ -244,6 +510,296 @@ impl SshClient {
         }
         
         info!("Backup created successfully: {}", backup_path);
-        Ok(())
+        Ok(backup_path)
+    }
+}
+
+/// 合并主机变量、用户变量与自动注入的主机信息，作为渲染上下文
+///
+/// 优先级从低到高：主机配置中的 `vars`、`variables`（任务变量与已注册变量）、自动注入的连接信息。
+fn template_variables(
+    config: &HostConfig,
+    variables: &HashMap<String, serde_json::Value>,
+) -> serde_json::Map<String, serde_json::Value> {
+    debug!("Adding {} variables to template context", variables.len());
+    let mut context: serde_json::Map<String, serde_json::Value> =
+        config.vars.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    context.extend(variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    // 自动注入 Host 信息
+    context.insert("ansible_host".to_string(), config.hostname.clone().into()); // HostConfig 中的 hostname 通常是 IP 或者可解析的主机名
+    context.insert("inventory_hostname".to_string(), config.hostname.clone().into());
+    context.insert("ansible_port".to_string(), config.port.into());
+    context.insert("ansible_user".to_string(), config.username.clone().into());
+    context
+}
+
+/// 模板查找函数的设置，来自 `TemplateOptions`
+///
+/// - `file(path)`：读取控制端文件，相对路径以模板所在目录为基准；解析符号链接与 `..` 后
+///   必须位于模板所在目录或 `lookup_dirs` 之内
+/// - `env(name, default)`：读取控制端环境变量，未设置且没有 `default` 时渲染失败
+/// - `pipe(cmd)`：在控制端执行命令并返回去掉末尾换行的标准输出，需开启 `allow_pipe_lookup`
+#[derive(Debug, Clone)]
+struct LookupSettings {
+    template_dir: PathBuf,      // file() 相对路径的基准
+    allowed_dirs: Vec<PathBuf>, // file() 可读取的目录（已规范化），不存在的目录被忽略
+    allow_pipe: bool,
+}
+
+impl LookupSettings {
+    fn new(options: &TemplateOptions) -> Self {
+        let template_dir = Path::new(&options.src)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+        let allowed_dirs = std::iter::once(template_dir.clone())
+            .chain(options.lookup_dirs.iter().map(PathBuf::from))
+            .filter_map(|dir| dir.canonicalize().ok())
+            .collect();
+        Self {
+            template_dir,
+            allowed_dirs,
+            allow_pipe: options.allow_pipe_lookup,
+        }
+    }
+
+    /// 同一模板在不同查找设置下分别解析与缓存
+    fn cache_key(&self) -> String {
+        sha256_hex(format!("{:?}", self).as_bytes())
+    }
+
+    fn register(&self, tera: &mut Tera) {
+        let settings = self.clone();
+        tera.register_function("file", move |args: &HashMap<String, tera::Value>| {
+            let path = string_arg("file", args, "path")?;
+            settings.read_file(&path).map(tera::Value::String).map_err(tera::Error::msg)
+        });
+        tera.register_function("env", |args: &HashMap<String, tera::Value>| {
+            let name = string_arg("env", args, "name")?;
+            match (std::env::var(&name), args.get("default")) {
+                (Ok(value), _) => Ok(tera::Value::String(value)),
+                (Err(_), Some(default)) => Ok(default.clone()),
+                (Err(_), None) => Err(tera::Error::msg(format!(
+                    "env(): variable '{}' is not set and no default was given",
+                    name
+                ))),
+            }
+        });
+        let allow_pipe = self.allow_pipe;
+        tera.register_function("pipe", move |args: &HashMap<String, tera::Value>| {
+            if !allow_pipe {
+                return Err(tera::Error::msg("pipe() is disabled, set allow_pipe_lookup to enable it"));
+            }
+            let command = string_arg("pipe", args, "cmd")?;
+            let result = run_local_command(&command).map_err(|e| tera::Error::msg(e.to_string()))?;
+            if result.exit_code != 0 {
+                return Err(tera::Error::msg(format!(
+                    "pipe(): '{}' exited with code {}: {}",
+                    command,
+                    result.exit_code,
+                    result.stderr.trim()
+                )));
+            }
+            Ok(tera::Value::String(result.stdout.trim_end_matches('\n').to_string()))
+        });
+    }
+
+    /// 读取 file() 指定的文件，拒绝允许目录之外的路径
+    fn read_file(&self, path: &str) -> Result<String, String> {
+        let resolved = self
+            .template_dir
+            .join(path)
+            .canonicalize()
+            .map_err(|e| format!("file(): cannot read '{}': {}", path, e))?;
+        if !self.allowed_dirs.iter().any(|dir| resolved.starts_with(dir)) {
+            return Err(format!("file(): '{}' is outside the allowed lookup directories", path));
+        }
+        std::fs::read_to_string(&resolved).map_err(|e| format!("file(): cannot read '{}': {}", path, e))
+    }
+}
+
+/// Tera 的渲染错误把具体原因（例如查找函数返回的错误）放在 source 链中
+fn error_chain(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+fn string_arg(function: &str, args: &HashMap<String, tera::Value>, name: &str) -> tera::Result<String> {
+    match args.get(name) {
+        Some(tera::Value::String(value)) => Ok(value.clone()),
+        Some(other) => Err(tera::Error::msg(format!(
+            "{}(): argument '{}' must be a string, got {}",
+            function, name, other
+        ))),
+        None => Err(tera::Error::msg(format!("{}(): missing argument '{}'", function, name))),
+    }
+}
+
+/// 渲染验证命令
+///
+/// 支持 `{{ path }}`（待验证的临时文件）与 `{{ dest }}`（最终目标路径），
+/// 替换值已按 shell 单引号转义，命令中无需再加引号。
+/// 旧的 `%s` 占位符仍然可用，但已弃用。
+pub(super) fn render_validate_command(command: &str, path: &str, dest: &str) -> Result<String, AnsibleError> {
+    if command.contains("{{") {
+        let mut context = Context::new();
+        context.insert("path", &shell_quote(path));
+        context.insert("dest", &shell_quote(dest));
+        return Tera::one_off(command, &context, false)
+            .map_err(|e| AnsibleError::TemplateError(format!("Invalid validate command '{}': {}", command, e)));
+    }
+
+    if command.contains("%s") {
+        warn!(
+            "The '%s' placeholder in validate commands is deprecated and will be removed in the next release, use '{{{{ path }}}}' instead: {}",
+            command
+        );
+        return Ok(command.replace("%s", path));
+    }
+
+    Ok(command.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_validate_command() {
+        let tmp = "/tmp/rs_ansible_validate.tmp.1";
+        assert_eq!(
+            render_validate_command("nginx -t -c {{ path }}", tmp, "/etc/nginx/nginx.conf").unwrap(),
+            "nginx -t -c '/tmp/rs_ansible_validate.tmp.1'"
+        );
+        // 目标路径中的百分号与引号不再被误替换
+        assert_eq!(
+            render_validate_command("check {{path}} --for {{ dest }}", tmp, "/srv/100%s/it's.conf").unwrap(),
+            "check '/tmp/rs_ansible_validate.tmp.1' --for '/srv/100%s/it'\\''s.conf'"
+        );
+        // 兼容旧的 %s 写法
+        assert_eq!(
+            render_validate_command("visudo -cf %s", tmp, "/etc/sudoers").unwrap(),
+            "visudo -cf /tmp/rs_ansible_validate.tmp.1"
+        );
+        assert!(render_validate_command("check {{ path", tmp, "/etc/x").is_err());
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> serde_json::Map<String, serde_json::Value> {
+        pairs.iter().map(|(k, v)| (k.to_string(), serde_json::Value::from(*v))).collect()
+    }
+
+    #[test]
+    fn test_template_cache_reuses_identical_renders() {
+        let cache = TemplateCache::new();
+        let template = "server {{ name }}\r\n";
+
+        let first = cache.render(template, &TemplateOptions::default(), vars(&[("name", "web")])).unwrap();
+        let second = cache.render(template, &TemplateOptions::default(), vars(&[("name", "web")])).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.content, "server web\n");
+
+        // 按主机不同的变量必须得到各自的渲染结果，但模板只解析一次
+        let other = cache.render(template, &TemplateOptions::default(), vars(&[("name", "db")])).unwrap();
+        assert_eq!(other.content, "server db\n");
+
+        let stats = cache.stats();
+        assert_eq!(stats.parses, 1);
+        assert_eq!(stats.parse_cache_hits, 1);
+        assert_eq!(stats.renders, 2);
+        assert_eq!(stats.render_cache_hits, 1);
+    }
+
+    #[test]
+    fn test_template_cache_parses_once_for_many_hosts() {
+        let dir = crate::utils::generate_local_temp_path("rs_ansible_cache_hosts");
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = format!("{}/motd.j2", dir);
+        std::fs::write(&src, "welcome to {{ inventory_hostname }} ({{ env }})\n").unwrap();
+        let mut options = TemplateOptions { src, ..Default::default() };
+        options.variables.insert("env".to_string(), serde_json::json!("prod"));
+
+        let cache = TemplateCache::new();
+        for index in 0..5 {
+            let host = HostConfig { hostname: format!("web{}", index), ..Default::default() };
+            let content = cache.render_for_host(&options, &host).unwrap();
+            assert_eq!(content, format!("welcome to web{} (prod)\n", index));
+        }
+        // 查找设置不同（额外的 lookup 目录）时同样不重新解析
+        let with_lookups = TemplateOptions { lookup_dirs: vec![dir.clone()], ..options.clone() };
+        cache.render_for_host(&with_lookups, &HostConfig::default()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let stats = cache.stats();
+        assert_eq!(stats.parses, 1);
+        assert_eq!(stats.parse_cache_hits, 5);
+        assert_eq!(stats.renders, 6);
+        assert_eq!(stats.render_cache_hits, 0);
+    }
+
+    #[test]
+    fn test_template_cache_cleans_up_artifacts() {
+        let cache = TemplateCache::new();
+        let rendered = cache.render("{{ a }}", &TemplateOptions::default(), vars(&[("a", "1")])).unwrap();
+        let path = rendered.local_file().unwrap().path().to_string();
+        assert_eq!(rendered.local_file().unwrap().path(), path);
+        assert!(std::path::Path::new(&path).exists());
+
+        drop(rendered);
+        drop(cache);
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_template_lookup_functions() {
+        let root = crate::utils::generate_local_temp_path("rs_ansible_lookup");
+        let templates = format!("{}/templates", root);
+        let certs = format!("{}/certs", root);
+        std::fs::create_dir_all(format!("{}/files", templates)).unwrap();
+        std::fs::create_dir_all(&certs).unwrap();
+        std::fs::write(format!("{}/files/motd", templates), "welcome").unwrap();
+        std::fs::write(format!("{}/ca.pem", certs), "-----BEGIN CERTIFICATE-----").unwrap();
+        std::fs::write(format!("{}/secret", root), "hunter2").unwrap();
+
+        let render = |template: &str, options: &TemplateOptions| {
+            let src = format!("{}/main.conf.tera", templates);
+            std::fs::write(&src, template).unwrap();
+            let options = TemplateOptions { src, ..options.clone() };
+            TemplateCache::new().render_for_host(&options, &HostConfig::default())
+        };
+        let options = TemplateOptions::default();
+
+        assert_eq!(render("{{ file(path=\"files/motd\") }}", &options).unwrap(), "welcome");
+        assert_eq!(
+            render("{{ env(name=\"RS_ANSIBLE_UNSET_LOOKUP\", default=\"fallback\") }}", &options).unwrap(),
+            "fallback"
+        );
+        assert!(render("{{ env(name=\"RS_ANSIBLE_UNSET_LOOKUP\") }}", &options).is_err());
+
+        // 模板目录之外的文件只有在 lookup_dirs 中时才可读取，`..` 与绝对路径不能绕过
+        let ca = format!("{{{{ file(path=\"{}/ca.pem\") }}}}", certs);
+        for escape in ["{{ file(path=\"../secret\") }}", "{{ file(path=\"files/../../secret\") }}", ca.as_str()] {
+            let err = render(escape, &options).unwrap_err().to_string();
+            assert!(err.contains("outside the allowed lookup directories"), "{}", err);
+        }
+        let with_certs = TemplateOptions { lookup_dirs: vec![certs.clone()], ..TemplateOptions::default() };
+        assert_eq!(render(&ca, &with_certs).unwrap(), "-----BEGIN CERTIFICATE-----");
+        assert!(render("{{ file(path=\"../secret\") }}", &with_certs).is_err());
+
+        // pipe() 需要显式开启
+        let pipe = "{{ pipe(cmd=\"printf 'v1.2\\n'\") }}";
+        assert!(render(pipe, &options).unwrap_err().to_string().contains("allow_pipe_lookup"));
+        let with_pipe = TemplateOptions { allow_pipe_lookup: true, ..TemplateOptions::default() };
+        assert_eq!(render(pipe, &with_pipe).unwrap(), "v1.2");
+        assert!(render("{{ pipe(cmd=\"exit 3\") }}", &with_pipe).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
     }
 }
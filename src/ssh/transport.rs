@@ -0,0 +1,354 @@
+use crate::credentials::SecretString;
+use crate::error::AnsibleError;
+use crate::types::{CommandOptions, CommandResult, HostConfig, HostKeyType, TransportKind};
+use ssh2::{HashType, Session};
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// 远程连接传输层
+///
+/// `SshClient` 的所有远程操作（执行命令、上传、下载）都通过该 trait 完成，
+/// 以便替换底层实现（ssh2、其他连接方式或测试用的 mock）；异步实现见 `AsyncTransport`。
+pub trait Transport: Send {
+    /// 建立连接并完成认证
+    fn connect(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError>
+    where
+        Self: Sized;
+
+    /// 执行命令并收集输出
+    ///
+    /// 实现需处理 `options` 中的 stdin、timeout 与 pty；`env` 已由 `SshClient` 合并进命令，无需再处理。
+    /// `timeout` 是整条命令（写入 stdin 到读完输出）的总时长上限，不是单次读写的空闲超时。
+    fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError>;
+
+    /// 将 reader 中的 `size` 字节上传到远程路径，返回实际传输字节数
+    fn upload(
+        &self,
+        reader: &mut dyn Read,
+        size: u64,
+        remote_path: &str,
+        mode: i32,
+    ) -> Result<u64, AnsibleError>;
+
+    /// 下载远程文件并写入 writer，返回实际传输字节数
+    fn download(&self, remote_path: &str, writer: &mut dyn Write) -> Result<u64, AnsibleError>;
+
+    /// 主动关闭连接（默认依赖 Drop 关闭）
+    fn disconnect(&self) {}
+
+    /// 握手时服务器提供的主机密钥类型与 SHA256 指纹（小写十六进制）
+    ///
+    /// 只有 SSH 连接有主机密钥，默认返回错误。
+    fn host_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        Err(AnsibleError::ValidationError("Connection has no SSH host key".to_string()))
+    }
+}
+
+/// 按主机配置选择的传输实现建立连接
+pub(crate) fn connect(
+    config: &HostConfig,
+    secret: Option<&SecretString>,
+) -> Result<Box<dyn Transport>, AnsibleError> {
+    match config.transport {
+        TransportKind::Ssh2 => Ok(Box::new(Ssh2Transport::connect(config, secret)?)),
+        TransportKind::Local => Ok(Box::new(super::local::LocalTransport::connect(config, secret)?)),
+        TransportKind::Docker => Ok(Box::new(super::docker::DockerTransport::connect(config, secret)?)),
+        // 异步传输由 `AsyncSshClient` 直接 await，见 `super::async_transport::connect`
+        TransportKind::Russh => Err(super::client::unsupported_async_transport(config)),
+    }
+}
+
+/// 基于 libssh2 的同步传输实现（默认）
+pub struct Ssh2Transport {
+    session: Session,
+}
+
+impl Transport for Ssh2Transport {
+    fn connect(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        let tcp = TcpStream::connect(format!("{}:{}", config.hostname, config.port)).map_err(
+            |e| {
+                AnsibleError::SshConnectionError(format!(
+                    "Failed to connect to {}:{}: {}",
+                    config.hostname, config.port, e
+                ))
+            },
+        )?;
+
+        // 优化：禁用 Nagle 算法，减少小包延迟，有助于握手稳定性
+        if let Err(e) = tcp.set_nodelay(true) {
+            warn!("Failed to set TCP_NODELAY: {}", e);
+        }
+
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+
+        // 优化：设置超时时间（10秒），避免握手长时间卡死
+        // session.set_timeout(10000);
+
+        session.handshake().map_err(|e| {
+            AnsibleError::SshConnectionError(format!("SSH Handshake failed: {}", e))
+        })?;
+
+        // 认证
+        let passphrase = config
+            .passphrase
+            .as_deref()
+            .or_else(|| secret.map(|s| s.expose_secret()));
+        if let Some(ref private_key_path) = config.private_key_path {
+            session.userauth_pubkey_file(
+                &config.username,
+                None,
+                Path::new(private_key_path),
+                passphrase,
+            )?;
+        } else if let Some(ref private_key_data) = config.private_key_data {
+            // 私钥只在内存中使用，不落盘
+            session.userauth_pubkey_memory(
+                &config.username,
+                config.public_key_data.as_deref(),
+                private_key_data,
+                passphrase,
+            )?;
+        } else if let Some(password) = config
+            .password
+            .as_deref()
+            .or_else(|| secret.map(|s| s.expose_secret()))
+        {
+            session.userauth_password(&config.username, password)?;
+        } else {
+            return Err(AnsibleError::AuthenticationError(
+                "No authentication method provided".to_string(),
+            ));
+        }
+
+        if !session.authenticated() {
+            return Err(AnsibleError::AuthenticationError(
+                "Authentication failed".to_string(),
+            ));
+        }
+
+        info!("Successfully connected to {}", config.hostname);
+
+        Ok(Self { session })
+    }
+
+    /// 超时后关闭通道并返回 `CommandExecutionError`。关闭通道时分配了 pty 的远程进程会收到 SIGHUP；
+    /// 没有 pty 的进程不会被主动终止，直到它再写输出时因管道关闭而退出。
+    fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        // 总时长由 exec_channel 的读写循环控制；会话超时只约束打开、关闭通道等阻塞调用，结束后恢复为不限时
+        if let Some(timeout) = options.timeout {
+            self.session.set_timeout(timeout.as_millis().min(u32::MAX as u128) as u32);
+        }
+        let result = self.exec_channel(command, options);
+        if options.timeout.is_some() {
+            self.session.set_timeout(0);
+        }
+
+        result.map_err(|e| match options.timeout {
+            // libssh2 超时错误的信息为 "Timed out waiting on socket"（io 错误同样携带该信息）
+            Some(timeout) if e.to_string().to_lowercase().contains("timed out") => {
+                AnsibleError::CommandExecutionError(format!(
+                    "Command timed out after {:?}: {}",
+                    timeout, command
+                ))
+            }
+            _ => e,
+        })
+    }
+
+    fn upload(
+        &self,
+        reader: &mut dyn Read,
+        size: u64,
+        remote_path: &str,
+        mode: i32,
+    ) -> Result<u64, AnsibleError> {
+        let mut remote_file = self.session.scp_send(Path::new(remote_path), mode, size, None)?;
+
+        let bytes_transferred = std::io::copy(reader, &mut remote_file).map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e))
+        })?;
+
+        remote_file.send_eof()?;
+        remote_file.wait_eof()?;
+        remote_file.close()?;
+        remote_file.wait_close()?;
+
+        Ok(bytes_transferred)
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write) -> Result<u64, AnsibleError> {
+        let (mut remote_file, _stat) = self.session.scp_recv(Path::new(remote_path))?;
+
+        let bytes_transferred = std::io::copy(&mut remote_file, writer).map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to transfer file: {}", e))
+        })?;
+
+        remote_file.send_eof()?;
+        remote_file.wait_eof()?;
+        remote_file.close()?;
+        remote_file.wait_close()?;
+
+        Ok(bytes_transferred)
+    }
+
+    fn disconnect(&self) {
+        if let Err(e) = self.session.disconnect(None, "closed by client", None) {
+            warn!("Failed to disconnect SSH session cleanly: {}", e);
+        }
+    }
+
+    fn host_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        let unavailable = || AnsibleError::SshConnectionError("Server host key is not available".to_string());
+        let (_, kind) = self.session.host_key().ok_or_else(unavailable)?;
+        let hash = self.session.host_key_hash(HashType::Sha256).ok_or_else(unavailable)?;
+        Ok((host_key_type(kind), hex_fingerprint(hash)))
+    }
+}
+
+fn host_key_type(kind: ssh2::HostKeyType) -> HostKeyType {
+    match kind {
+        ssh2::HostKeyType::Rsa => HostKeyType::Rsa,
+        ssh2::HostKeyType::Dss => HostKeyType::Dss,
+        ssh2::HostKeyType::Ecdsa256 => HostKeyType::Ecdsa256,
+        ssh2::HostKeyType::Ecdsa384 => HostKeyType::Ecdsa384,
+        ssh2::HostKeyType::Ecdsa521 => HostKeyType::Ecdsa521,
+        ssh2::HostKeyType::Ed25519 => HostKeyType::Ed25519,
+        ssh2::HostKeyType::Unknown => HostKeyType::Unknown,
+    }
+}
+
+pub(super) fn hex_fingerprint(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl Ssh2Transport {
+    /// 在新通道上执行命令：按需分配 pty，交替写入 stdin 与读取输出
+    ///
+    /// 读写在非阻塞模式下交替进行，命令回显大量输入时不会因通道窗口写满而互相等待；
+    /// 超过 `options.timeout` 时关闭通道并返回超时错误。
+    fn exec_channel(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        let mut channel = self.session.channel_session()?;
+        if options.pty {
+            channel.request_pty("xterm", None, None)?;
+        }
+        channel.exec(command)?;
+
+        self.session.set_blocking(false);
+        let output = pump_channel(&mut channel, options.stdin.as_deref().unwrap_or_default(), deadline);
+        self.session.set_blocking(true);
+
+        let (stdout, stderr) = match output {
+            Ok(ChannelOutput::Closed { stdout, stderr }) => (stdout, stderr),
+            Ok(ChannelOutput::TimedOut) => {
+                let _ = channel.close();
+                return Err(AnsibleError::CommandExecutionError(format!(
+                    "Command timed out after {:?}: {}",
+                    options.timeout.unwrap_or_default(),
+                    command
+                )));
+            }
+            Err(e) => {
+                let _ = channel.close();
+                return Err(e);
+            }
+        };
+
+        channel.wait_close()?;
+        let exit_code = channel.exit_status()?;
+
+        CommandResult::from_bytes(exit_code, stdout, stderr, options.output_encoding)
+    }
+}
+
+/// 没有进展时两次轮询之间的最长等待
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 通道读写循环的结果
+enum ChannelOutput {
+    Closed { stdout: Vec<u8>, stderr: Vec<u8> }, // 远程已关闭输出
+    TimedOut,                                    // 到达截止时间
+}
+
+/// 在非阻塞通道上交替写入 `stdin`（写完后发送 EOF）与读取 stdout/stderr，直到远程关闭输出或到达 `deadline`
+fn pump_channel(channel: &mut ssh2::Channel, stdin: &[u8], deadline: Option<Instant>) -> Result<ChannelOutput, AnsibleError> {
+    let would_block = |e: &std::io::Error| e.kind() == std::io::ErrorKind::WouldBlock;
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut buf = vec![0u8; 32 * 1024];
+    let mut written = 0;
+    let mut eof_sent = false;
+    let mut interval = Duration::from_millis(1);
+
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Ok(ChannelOutput::TimedOut);
+        }
+        let mut progressed = false;
+
+        if written < stdin.len() {
+            match channel.write(&stdin[written..]) {
+                Ok(n) => {
+                    written += n;
+                    progressed |= n > 0;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if written == stdin.len() && !eof_sent {
+            match channel.send_eof().map_err(std::io::Error::from) {
+                Ok(()) => {
+                    eof_sent = true;
+                    progressed = true;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut drained = true;
+        for (stream_id, output) in [(0, &mut stdout), (ssh2::EXTENDED_DATA_STDERR, &mut stderr)] {
+            match channel.stream(stream_id).read(&mut buf) {
+                Ok(0) => {}
+                Ok(n) => {
+                    output.extend_from_slice(&buf[..n]);
+                    progressed = true;
+                    drained = false;
+                }
+                Err(e) if would_block(&e) => drained = false,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if drained && channel.eof() {
+            return Ok(ChannelOutput::Closed { stdout, stderr });
+        }
+
+        if progressed {
+            interval = Duration::from_millis(1);
+        } else {
+            let remaining = deadline.map_or(interval, |deadline| deadline.saturating_duration_since(Instant::now()));
+            std::thread::sleep(interval.min(remaining));
+            interval = (interval * 2).min(MAX_POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_key_fingerprint_format() {
+        let hash: Vec<u8> = (0u8..32).map(|i| i.wrapping_mul(37)).collect();
+        let fingerprint = hex_fingerprint(&hash);
+        assert_eq!(fingerprint.len(), 64);
+        assert!(fingerprint.starts_with("00254a6f94b9de03"));
+        assert_eq!(host_key_type(ssh2::HostKeyType::Ecdsa384).algorithm(), "ecdsa-sha2-nistp384");
+        assert_eq!(host_key_type(ssh2::HostKeyType::Ed25519), HostKeyType::Ed25519);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::error::AnsibleError;
+use crate::types::TrustStoreCert;
+use super::SshClient;
+use std::collections::HashSet;
+use tracing::info;
+
+/// 证书之间的分隔行前缀，后接文件路径
+const FILE_MARKER: &str = "==> ";
+
+/// 在一次执行中列出信任库中的证书文件并逐个解析，避免每个文件一次往返
+const TRUST_STORE_COMMAND: &str = "command -v openssl >/dev/null 2>&1 || { echo 'openssl not found' >&2; exit 127; }; \
+     find /etc/ssl/certs /etc/ca-certificates /etc/pki/tls/certs \\( -name '*.pem' -o -name '*.crt' \\) 2>/dev/null \
+     | while IFS= read -r f; do echo \"==> $f\"; \
+     openssl x509 -in \"$f\" -noout -subject -issuer -enddate -fingerprint -sha256 2>/dev/null; done";
+
+impl SshClient {
+    /// 获取系统信任库（`/etc/ssl/certs`、`/etc/ca-certificates`、`/etc/pki/tls/certs`）中的 CA 证书
+    ///
+    /// 指纹为 SHA256。同一证书通过多个路径（例如 `/etc/ssl/certs` 下的符号链接）出现时只保留第一个；
+    /// 捆绑文件（如 `ca-certificates.crt`）只解析其中的第一张证书，无法解析的文件被忽略。
+    pub fn get_trusted_certificates(&self) -> Result<Vec<TrustStoreCert>, AnsibleError> {
+        let result = self.execute_command(TRUST_STORE_COMMAND)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "Failed to list trusted certificates: {}",
+                result.stderr.trim()
+            )));
+        }
+        let certs = parse_trust_store(&result.stdout);
+        info!("Found {} trusted certificate(s) on {}", certs.len(), self.config.hostname);
+        Ok(certs)
+    }
+
+    /// 指定 SHA256 指纹的证书是否在信任库中（指纹不区分大小写，冒号可省略）
+    pub fn is_certificate_trusted(&self, fingerprint: &str) -> Result<bool, AnsibleError> {
+        let wanted = normalize_fingerprint(fingerprint);
+        Ok(self
+            .get_trusted_certificates()?
+            .iter()
+            .any(|cert| normalize_fingerprint(&cert.fingerprint) == wanted))
+    }
+}
+
+/// 统一指纹格式：去掉冒号并转为大写
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.trim().replace(':', "").to_ascii_uppercase()
+}
+
+/// 解析 `==> 路径` 与 openssl 输出交替的文本，按指纹去重
+fn parse_trust_store(output: &str) -> Vec<TrustStoreCert> {
+    let mut certs = Vec::new();
+    let mut seen = HashSet::new();
+    let mut current: Option<TrustStoreCert> = None;
+
+    let mut finish = |cert: Option<TrustStoreCert>, certs: &mut Vec<TrustStoreCert>| {
+        if let Some(cert) = cert.filter(|c| !c.fingerprint.is_empty())
+            && seen.insert(normalize_fingerprint(&cert.fingerprint))
+        {
+            certs.push(cert);
+        }
+    };
+
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix(FILE_MARKER) {
+            finish(current.take(), &mut certs);
+            current = Some(TrustStoreCert {
+                subject: String::new(),
+                issuer: String::new(),
+                not_after: String::new(),
+                fingerprint: String::new(),
+                file_path: path.to_string(),
+            });
+            continue;
+        }
+        let (Some(cert), Some((key, value))) = (current.as_mut(), line.split_once('=')) else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "subject" => cert.subject = value,
+            "issuer" => cert.issuer = value,
+            "notAfter" => cert.not_after = value,
+            // OpenSSL 1.x 输出 "SHA256 Fingerprint"，3.x 输出 "sha256 Fingerprint"
+            key if key.to_ascii_lowercase().ends_with("fingerprint") => cert.fingerprint = value,
+            _ => {}
+        }
+    }
+    finish(current, &mut certs);
+    certs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trust_store() {
+        let output = "\
+==> /etc/ssl/certs/ISRG_Root_X1.pem
+subject=C = US, O = Internet Security Research Group, CN = ISRG Root X1
+issuer=C = US, O = Internet Security Research Group, CN = ISRG Root X1
+notAfter=Jun  4 11:04:38 2035 GMT
+sha256 Fingerprint=96:BC:EC:06:26:49:76:F3:74:60:77:9A:CF:28:C5:A7:CF:E8:A3:C0:AA:E1:1A:8F:FC:EE:05:C0:BD:DF:08:C6
+==> /etc/ssl/certs/4042bcee.0
+subject=C = US, O = Internet Security Research Group, CN = ISRG Root X1
+issuer=C = US, O = Internet Security Research Group, CN = ISRG Root X1
+notAfter=Jun  4 11:04:38 2035 GMT
+sha256 Fingerprint=96:BC:EC:06:26:49:76:F3:74:60:77:9A:CF:28:C5:A7:CF:E8:A3:C0:AA:E1:1A:8F:FC:EE:05:C0:BD:DF:08:C6
+==> /etc/ssl/certs/broken.pem
+==> /etc/pki/tls/certs/internal-ca.crt
+subject= /C=CN/O=Example/CN=Example Internal CA
+issuer= /C=CN/O=Example/CN=Example Internal CA
+notAfter=Jan  1 00:00:00 2030 GMT
+SHA256 Fingerprint=AA:BB:CC
+";
+        let certs = parse_trust_store(output);
+        assert_eq!(certs.len(), 2);
+        assert_eq!(certs[0].file_path, "/etc/ssl/certs/ISRG_Root_X1.pem");
+        assert_eq!(certs[0].subject, "C = US, O = Internet Security Research Group, CN = ISRG Root X1");
+        assert_eq!(certs[0].not_after, "Jun  4 11:04:38 2035 GMT");
+        assert_eq!(certs[1].file_path, "/etc/pki/tls/certs/internal-ca.crt");
+        assert_eq!(certs[1].issuer, "/C=CN/O=Example/CN=Example Internal CA");
+        assert_eq!(certs[1].fingerprint, "AA:BB:CC");
+
+        assert_eq!(normalize_fingerprint("aa:bb:cc"), normalize_fingerprint(&certs[1].fingerprint));
+        assert_eq!(normalize_fingerprint(" aabbcc "), "AABBCC");
+    }
+}
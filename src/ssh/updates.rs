@@ -0,0 +1,222 @@
+use crate::error::AnsibleError;
+use crate::types::PendingUpdate;
+use crate::utils::shell_quote;
+use super::SshClient;
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+
+const DETECT_COMMAND: &str = "if command -v apt-get >/dev/null 2>&1; then echo apt; \
+     elif command -v dnf >/dev/null 2>&1; then echo dnf; \
+     elif command -v yum >/dev/null 2>&1; then echo yum; fi";
+
+/// rpm 包常见的架构后缀，用于识别 check-update 输出中的包名列
+const RPM_ARCHES: &[&str] = &["x86_64", "noarch", "i686", "i386", "aarch64", "ppc64le", "s390x", "armv7hl"];
+
+impl SshClient {
+    /// 列出待更新的软件包（只模拟，不做任何修改）
+    ///
+    /// Debian 系使用 `apt-get -s upgrade`，来源包含 `-security` 的视为安全更新；
+    /// RHEL 系使用 `dnf`/`yum check-update`，并用 `check-update --security` 标记安全更新，
+    /// 当前版本通过 `rpm -q` 查询。结果取决于主机上软件源缓存的新旧，本方法不会刷新缓存。
+    pub fn get_pending_updates(&self) -> Result<Vec<PendingUpdate>, AnsibleError> {
+        let manager = self.execute_command(DETECT_COMMAND)?.stdout.trim().to_string();
+        let updates = match manager.as_str() {
+            "apt" => self.apt_pending_updates()?,
+            "dnf" | "yum" => self.rpm_pending_updates(&manager)?,
+            _ => {
+                return Err(AnsibleError::CommandError(format!(
+                    "No supported package manager (apt-get, dnf, yum) found on {}",
+                    self.config.hostname
+                )));
+            }
+        };
+        info!(
+            "{} pending update(s) on {} ({} security)",
+            updates.len(),
+            self.config.hostname,
+            updates.iter().filter(|u| u.security).count()
+        );
+        Ok(updates)
+    }
+
+    fn apt_pending_updates(&self) -> Result<Vec<PendingUpdate>, AnsibleError> {
+        let result = self.execute_command("apt-get -s upgrade 2>/dev/null")?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "apt-get -s upgrade failed with exit code {}",
+                result.exit_code
+            )));
+        }
+        Ok(parse_apt_simulation(&result.stdout))
+    }
+
+    fn rpm_pending_updates(&self, manager: &str) -> Result<Vec<PendingUpdate>, AnsibleError> {
+        let available = self.run_check_update(&format!("{} -q check-update 2>/dev/null", manager))?;
+        if available.is_empty() {
+            return Ok(Vec::new());
+        }
+        let security: HashSet<String> = self
+            .run_check_update(&format!("{} -q check-update --security 2>/dev/null", manager))?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+
+        let names: Vec<String> = available.iter().map(|(name, _)| shell_quote(name)).collect();
+        let installed = self.execute_command(&format!(
+            "rpm -q --qf '%{{NAME}} %|EPOCH?{{%{{EPOCH}}:}}:{{}}|%{{VERSION}}-%{{RELEASE}}\\n' {} 2>/dev/null",
+            names.join(" ")
+        ))?;
+        let current = parse_rpm_versions(&installed.stdout);
+
+        Ok(available
+            .into_iter()
+            .map(|(name, new_version)| PendingUpdate {
+                current_version: current.get(&name).cloned().unwrap_or_default(),
+                security: security.contains(&name),
+                name,
+                new_version,
+            })
+            .collect())
+    }
+
+    /// check-update 退出码 100 表示有可用更新，0 表示没有
+    fn run_check_update(&self, command: &str) -> Result<Vec<(String, String)>, AnsibleError> {
+        let result = self.execute_command(command)?;
+        match result.exit_code {
+            0 => Ok(Vec::new()),
+            100 => Ok(parse_check_update(&result.stdout)),
+            code => Err(AnsibleError::CommandError(format!(
+                "'{}' failed with exit code {}",
+                command.trim_end_matches(" 2>/dev/null"),
+                code
+            ))),
+        }
+    }
+}
+
+/// 解析 `apt-get -s upgrade` 输出中的 `Inst` 行，例如：
+/// `Inst libssl3 [3.0.2-0ubuntu1.10] (3.0.2-0ubuntu1.12 Ubuntu:22.04/jammy-updates, Ubuntu:22.04/jammy-security [amd64])`
+fn parse_apt_simulation(output: &str) -> Vec<PendingUpdate> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("Inst ")?;
+            let (name, rest) = rest.split_once(' ')?;
+            let (current_version, rest) = match rest.strip_prefix('[') {
+                Some(bracketed) => {
+                    let (version, rest) = bracketed.split_once(']')?;
+                    (version.to_string(), rest.trim_start())
+                }
+                None => (String::new(), rest),
+            };
+            let details = rest.strip_prefix('(')?;
+            let (new_version, origins) = details.split_once(' ').unwrap_or((details.trim_end_matches(')'), ""));
+            Some(PendingUpdate {
+                name: name.to_string(),
+                current_version,
+                new_version: new_version.to_string(),
+                security: origins.contains("-security"),
+            })
+        })
+        .collect()
+}
+
+/// 解析 `yum/dnf check-update` 输出，返回（不含架构的包名，新版本）
+///
+/// 包名过长时 yum 会把版本与仓库折到下一行；`Obsoleting Packages` 之后的内容忽略。
+fn parse_check_update(output: &str) -> Vec<(String, String)> {
+    let package_name = |token: &str| {
+        token
+            .rsplit_once('.')
+            .filter(|(_, arch)| RPM_ARCHES.contains(arch))
+            .map(|(name, _)| name.to_string())
+    };
+
+    let mut updates = Vec::new();
+    let mut wrapped: Option<String> = None;
+    for line in output.lines() {
+        if line.starts_with("Obsoleting") {
+            break;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match (wrapped.take(), tokens.as_slice()) {
+            (Some(name), [version, _repo]) => updates.push((name, version.to_string())),
+            (_, [package, version, _repo]) => {
+                if let Some(name) = package_name(package) {
+                    updates.push((name, version.to_string()));
+                }
+            }
+            (_, [package]) => wrapped = package_name(package),
+            _ => {}
+        }
+    }
+    updates
+}
+
+/// 解析 `rpm -q --qf '%{NAME} <版本>\n'` 输出；同名包安装了多个版本（如 kernel）时取最后一个
+fn parse_rpm_versions(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, version)| (name.to_string(), version.trim().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_apt_simulation() {
+        let output = "\
+Reading package lists...
+Building dependency tree...
+The following packages will be upgraded:
+  libssl3 tzdata
+Inst libssl3 [3.0.2-0ubuntu1.10] (3.0.2-0ubuntu1.12 Ubuntu:22.04/jammy-updates, Ubuntu:22.04/jammy-security [amd64])
+Inst tzdata [2023c-0ubuntu0.22.04.2] (2024a-0ubuntu0.22.04 Ubuntu:22.04/jammy-updates [all])
+Inst linux-modules-extra (5.15.0-92.102 Ubuntu:22.04/jammy-updates [amd64])
+Conf libssl3 (3.0.2-0ubuntu1.12 Ubuntu:22.04/jammy-updates, Ubuntu:22.04/jammy-security [amd64])
+";
+        let updates = parse_apt_simulation(output);
+        assert_eq!(updates.len(), 3);
+        assert_eq!(
+            updates[0],
+            PendingUpdate {
+                name: "libssl3".to_string(),
+                current_version: "3.0.2-0ubuntu1.10".to_string(),
+                new_version: "3.0.2-0ubuntu1.12".to_string(),
+                security: true,
+            }
+        );
+        assert!(!updates[1].security);
+        assert_eq!(updates[1].new_version, "2024a-0ubuntu0.22.04");
+        assert_eq!(updates[2].current_version, "");
+    }
+
+    #[test]
+    fn test_parse_check_update_and_rpm_versions() {
+        let output = "\
+Last metadata expiration check: 0:12:03 ago on Mon 04 Mar 2024 10:00:00 AM UTC.
+
+kernel.x86_64                         5.14.0-362.18.1.el9_3          baseos
+openssl-libs.x86_64                   1:3.0.7-25.el9_3               baseos
+python3-very-long-package-name-for-wrapping.noarch
+                                      1.2.3-4.el9                    appstream
+Obsoleting Packages
+grub2-tools.x86_64                    1:2.06-70.el9_3.2              baseos
+";
+        assert_eq!(
+            parse_check_update(output),
+            vec![
+                ("kernel".to_string(), "5.14.0-362.18.1.el9_3".to_string()),
+                ("openssl-libs".to_string(), "1:3.0.7-25.el9_3".to_string()),
+                ("python3-very-long-package-name-for-wrapping".to_string(), "1.2.3-4.el9".to_string()),
+            ]
+        );
+
+        let versions = parse_rpm_versions("kernel 5.14.0-284.11.1.el9_2\nkernel 5.14.0-362.8.1.el9_3\nopenssl-libs 1:3.0.7-24.el9\n");
+        assert_eq!(versions["kernel"], "5.14.0-362.8.1.el9_3");
+        assert_eq!(versions["openssl-libs"], "1:3.0.7-24.el9");
+    }
+}
@@ -0,0 +1,167 @@
+use crate::error::AnsibleError;
+use crate::types::{HealthProbeResult, HostRequirement};
+use crate::utils::shell_quote;
+use super::SshClient;
+use regex::Regex;
+use semver::Version;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// 默认的版本参数
+const DEFAULT_VERSION_FLAG: &str = "--version";
+
+/// 默认从输出中提取第一个 `主.次[.修订]` 形式的版本号
+const DEFAULT_VERSION_REGEX: &str = r"(\d+\.\d+(?:\.\d+)?)";
+
+impl SshClient {
+    /// 执行 `<command> <version_flag>` 并用 `parse_regex` 从输出中提取版本号
+    ///
+    /// 同时在 stdout 与 stderr 中查找（部分工具把版本打印到 stderr）。正则有捕获组时使用第一个捕获组，
+    /// 否则使用整个匹配，提取的版本按 [`parse_version`] 的规则解析。
+    /// `version_flag` 作为单个参数传递，为空时不传。命令不存在或执行失败、输出中没有版本号时返回错误。
+    pub fn get_command_version(
+        &self,
+        command: &str,
+        version_flag: &str,
+        parse_regex: &str,
+    ) -> Result<Version, AnsibleError> {
+        let regex = Regex::new(parse_regex).map_err(|e| {
+            AnsibleError::ValidationError(format!("Invalid version regex '{}': {}", parse_regex, e))
+        })?;
+        let command_line = if version_flag.is_empty() {
+            shell_quote(command)
+        } else {
+            format!("{} {}", shell_quote(command), shell_quote(version_flag))
+        };
+        let result = self.execute_command(&command_line)?;
+        if result.exit_code != 0 {
+            return Err(AnsibleError::CommandError(format!(
+                "'{} {}' failed with exit code {}: {}",
+                command,
+                version_flag,
+                result.exit_code,
+                result.stderr.trim()
+            )));
+        }
+        let output = format!("{}\n{}", result.stdout, result.stderr);
+        extract_version(&regex, &output).ok_or_else(|| {
+            AnsibleError::CommandError(format!(
+                "No version found in output of '{} {}': {}",
+                command,
+                version_flag,
+                output.trim()
+            ))
+        })
+    }
+
+    /// 要求 `command --version` 报告的版本不低于 `min_version`，否则返回 `ValidationError`
+    pub fn require_min_version(&self, command: &str, min_version: &Version) -> Result<(), AnsibleError> {
+        let version = self.get_command_version(command, DEFAULT_VERSION_FLAG, DEFAULT_VERSION_REGEX)?;
+        if version < *min_version {
+            return Err(AnsibleError::ValidationError(format!(
+                "{} {} on {} is older than required {}",
+                command, version, self.config.hostname, min_version
+            )));
+        }
+        info!("{} {} on {} satisfies >= {}", command, version, self.config.hostname, min_version);
+        Ok(())
+    }
+
+    /// 检查一个主机条件，结果以健康探针的形式返回
+    ///
+    /// 条件不满足（包括命令不存在）时返回 `passed: false`，`stdout` 中是检测到的版本或失败原因；
+    /// 条件本身无效（例如版本号格式错误）时返回 `Err`。
+    pub fn check_host_requirement(&self, requirement: &HostRequirement) -> Result<HealthProbeResult, AnsibleError> {
+        match requirement {
+            HostRequirement::CommandVersion { command, min_version } => {
+                let min_version = parse_version(min_version)?;
+                let started = Instant::now();
+                let (passed, stdout) = match self.require_min_version(command, &min_version) {
+                    Ok(()) => (true, String::new()),
+                    Err(e) => {
+                        warn!("Requirement {} >= {} not met on {}: {}", command, min_version, self.config.hostname, e);
+                        (false, e.to_string())
+                    }
+                };
+                Ok(HealthProbeResult {
+                    name: format!("{} >= {}", command, min_version),
+                    passed,
+                    exit_code: if passed { 0 } else { 1 },
+                    stdout,
+                    latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+                })
+            }
+        }
+    }
+}
+
+/// 宽松地解析命令行工具的版本号：接受 `20.10.21`、`2.30`、`v1.2.3`、`24.0.7-rc1`、`4.09.00` 等形式
+///
+/// 缺少的次版本号与修订号补 0，各数字段去掉前导零（`2024.01.1` 按 `2024.1.1` 比较），
+/// 预发布与构建后缀按 semver 规则保留（`24.0.7-rc1` 低于 `24.0.7`）。
+pub fn parse_version(s: &str) -> Result<Version, AnsibleError> {
+    let invalid = |reason: String| AnsibleError::ValidationError(format!("Invalid version '{}': {}", s, reason));
+    let trimmed = s.trim().trim_start_matches('v');
+    let split = trimmed.find(['-', '+']).unwrap_or(trimmed.len());
+    let (core, suffix) = trimmed.split_at(split);
+    let mut parts = core
+        .split('.')
+        .map(|part| part.parse::<u64>().map_err(|e| invalid(format!("'{}': {}", part, e))))
+        .collect::<Result<Vec<_>, _>>()?;
+    if parts.len() > 3 {
+        return Err(invalid("expected at most three numeric components".to_string()));
+    }
+    parts.resize(3, 0);
+    Version::parse(&format!("{}.{}.{}{}", parts[0], parts[1], parts[2], suffix))
+        .map_err(|e| invalid(e.to_string()))
+}
+
+fn extract_version(regex: &Regex, output: &str) -> Option<Version> {
+    let captures = regex.captures(output)?;
+    let matched = captures.get(1).or_else(|| captures.get(0))?;
+    parse_version(matched.as_str()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_versions_from_common_tools() {
+        let regex = Regex::new(DEFAULT_VERSION_REGEX).unwrap();
+        let cases = [
+            ("Docker version 20.10.21, build baeda1f\n", Version::new(20, 10, 21)),
+            ("git version 2.30.2\n", Version::new(2, 30, 2)),
+            ("Python 3.11.4\n", Version::new(3, 11, 4)),
+            ("tmux 3.2\n", Version::new(3, 2, 0)),
+            ("Screen version 4.09.00 (GNU) 30-Jan-22\n", Version::new(4, 9, 0)),
+            ("youtube-dl 2024.01.1\n", Version::new(2024, 1, 1)),
+        ];
+        for (output, expected) in cases {
+            assert_eq!(extract_version(&regex, output), Some(expected), "{}", output);
+        }
+        assert_eq!(extract_version(&regex, "command not found"), None);
+
+        // 自定义正则：取 OpenSSH 的版本而不是 OpenSSL 的
+        let regex = Regex::new(r"OpenSSH_(\d+\.\d+)").unwrap();
+        assert_eq!(
+            extract_version(&regex, "OpenSSH_9.6p1 Ubuntu-3ubuntu13, OpenSSL 3.0.13 30 Jan 2024"),
+            Some(Version::new(9, 6, 0))
+        );
+    }
+
+    #[test]
+    fn test_parse_and_compare_versions() {
+        assert_eq!(parse_version("v1.2.3").unwrap(), Version::new(1, 2, 3));
+        assert_eq!(parse_version("24.0.7-rc1").unwrap(), Version::parse("24.0.7-rc1").unwrap());
+        assert_eq!(parse_version("20").unwrap(), Version::new(20, 0, 0));
+        assert_eq!(parse_version("2.30+build5").unwrap(), Version::parse("2.30.0+build5").unwrap());
+        assert!(parse_version("1.2.3.4").is_err());
+        assert!(parse_version("latest").is_err());
+
+        assert!(Version::new(20, 10, 0) > Version::new(20, 9, 30));
+        assert!(Version::new(2, 30, 0) <= parse_version("2.30").unwrap());
+        assert!(parse_version("24.0.7-rc1").unwrap() < Version::new(24, 0, 7));
+        assert_eq!(Version::new(3, 11, 4).to_string(), "3.11.4");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::callback::{HostStatus, TaskOutcome};
+use crate::error::AnsibleError;
+use crate::executor::{Playbook, Task, TaskType};
+use crate::utils::sha256_hex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// 状态文件的格式版本，格式不兼容时递增；版本不同的文件被忽略
+pub const STATE_FILE_VERSION: u32 = 2;
+
+/// 记录 playbook 每个任务在各主机上结果的运行状态文件（JSON）
+///
+/// 重新运行时，之前在某台主机上成功且报告未修改主机（ok 且 changed 为 false）的任务在该主机上跳过，
+/// 并以 `SkipReason::Cached` 报告；不报告 changed 的任务（例如命令）每次都执行。
+/// 任务 i 的键为 playbook 设置与第 0..=i 个任务内容（包括模板、复制等任务的本地源文件内容）的哈希，
+/// 因此修改 playbook 中的某个任务或其源文件会使该任务及之后所有任务的记录失效，之前的任务仍可复用。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunStateFile {
+    pub version: u32,
+    pub playbook: String,      // playbook 名称
+    pub playbook_hash: String, // 整个 playbook 内容（含本地源文件）的 SHA256
+    pub tasks: Vec<TaskState>, // 按任务下标
+}
+
+/// 一个任务在各主机上的最近一次结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskState {
+    pub name: String,
+    pub hash: String,                           // 该任务的键
+    pub hosts: BTreeMap<String, HostTaskState>, // 主机 -> 结果
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HostTaskState {
+    pub status: HostStatus,
+    #[serde(default)]
+    pub changed: Option<bool>, // 任务结果不报告 changed 时为 None，此类记录不会被复用
+}
+
+impl RunStateFile {
+    /// 为 playbook 创建状态；`previous` 中键仍然一致的任务保留其主机结果
+    ///
+    /// 记录写入之后 playbook 或其源文件被修改时记录警告，只复用键仍一致的前置任务。
+    pub fn for_playbook(playbook: &Playbook, previous: Option<RunStateFile>) -> Self {
+        let sources: Vec<String> = playbook.tasks.iter().map(source_hash).collect();
+        let playbook_hash = sha256_hex(format!("{}\n{}", canonical_json(playbook), sources.join("\n")).as_bytes());
+        if let Some(ref previous) = previous
+            && previous.playbook_hash != playbook_hash
+        {
+            warn!(
+                "Playbook '{}' or its source files changed since the state file was written, \
+                 recorded results are reused only up to the first changed task",
+                playbook.name
+            );
+        }
+        let mut previous_tasks = previous.map(|state| state.tasks).unwrap_or_default().into_iter();
+        let tasks: Vec<TaskState> = playbook
+            .tasks
+            .iter()
+            .zip(task_hashes(playbook, &sources))
+            .map(|(task, hash)| {
+                let hosts = previous_tasks
+                    .next()
+                    .filter(|previous| previous.hash == hash)
+                    .map(|previous| previous.hosts)
+                    .unwrap_or_default();
+                TaskState {
+                    name: task.name.clone(),
+                    hash,
+                    hosts,
+                }
+            })
+            .collect();
+        let kept = tasks.iter().take_while(|task| !task.hosts.is_empty()).count();
+        if kept > 0 {
+            info!("Reusing recorded results of {} task(s) of playbook '{}'", kept, playbook.name);
+        }
+        Self {
+            version: STATE_FILE_VERSION,
+            playbook: playbook.name.clone(),
+            playbook_hash,
+            tasks,
+        }
+    }
+
+    /// 读取状态文件；文件不存在返回 None，无法解析或版本不同时记录警告并返回 None
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>, AnsibleError> {
+        let path = path.as_ref();
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(AnsibleError::FileOperationError(format!(
+                    "Failed to read state file {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+        match serde_json::from_str::<RunStateFile>(&content) {
+            Ok(state) if state.version == STATE_FILE_VERSION => Ok(Some(state)),
+            Ok(state) => {
+                warn!(
+                    "Ignoring state file {}: version {} is not supported (expected {})",
+                    path.display(),
+                    state.version,
+                    STATE_FILE_VERSION
+                );
+                Ok(None)
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable state file {}: {}", path.display(), e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// 写入文件（先写临时文件再重命名，中断时不会留下不完整的状态）
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AnsibleError> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to serialize state: {}", e)))?;
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, content)
+            .and_then(|_| std::fs::rename(&temp_path, path))
+            .map_err(|e| {
+                AnsibleError::FileOperationError(format!("Failed to write state file {}: {}", path.display(), e))
+            })
+    }
+
+    /// 下标为 `index` 的任务之前成功且报告未修改主机（changed 为 false）的主机
+    pub fn cached_hosts(&self, index: usize) -> Vec<String> {
+        self.tasks
+            .get(index)
+            .map(|task| {
+                task.hosts
+                    .iter()
+                    .filter(|(_, state)| state.status == HostStatus::Ok && state.changed == Some(false))
+                    .map(|(host, _)| host.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 记录任务在各主机上的结果；本次被跳过的主机保留原有记录
+    pub(crate) fn record(&mut self, index: usize, outcomes: &[(String, TaskOutcome)]) {
+        let Some(task) = self.tasks.get_mut(index) else {
+            return;
+        };
+        for (host, outcome) in outcomes {
+            if outcome.status == HostStatus::Skipped {
+                continue;
+            }
+            task.hosts.insert(
+                host.clone(),
+                HostTaskState {
+                    status: outcome.status,
+                    changed: outcome.changed,
+                },
+            );
+        }
+    }
+}
+
+/// 键按字母顺序排列的 JSON（serde_json 的 Map 有序），保证同一内容的哈希在不同进程中一致
+fn canonical_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value).map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// 任务读取的本地源文件（模板、复制源文件）内容的哈希；文件无法读取时记为 `missing`
+fn source_hash(task: &Task) -> String {
+    let sources: Vec<&str> = match task.task_type {
+        TaskType::Template { ref options } => vec![&options.src],
+        TaskType::CopyFile { ref src, .. } => vec![src],
+        TaskType::FileSet { ref files } => files.iter().map(|file| file.src.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    sources
+        .into_iter()
+        .map(|path| std::fs::read(path).map_or_else(|_| "missing".to_string(), |data| sha256_hex(&data)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 每个任务的键：以 playbook 设置（不含任务）为起点，依次与每个任务的内容及源文件哈希链式哈希
+fn task_hashes(playbook: &Playbook, sources: &[String]) -> Vec<String> {
+    let mut settings = serde_json::to_value(playbook).unwrap_or_default();
+    if let Some(settings) = settings.as_object_mut() {
+        settings.remove("tasks");
+    }
+    let mut hash = sha256_hex(settings.to_string().as_bytes());
+    playbook
+        .tasks
+        .iter()
+        .zip(sources)
+        .map(|(task, source)| {
+            hash = sha256_hex(format!("{}\n{}\n{}", hash, canonical_json(task), source).as_bytes());
+            hash.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::SkipReason;
+    use crate::types::TemplateOptions;
+
+    fn playbook(restart: &str) -> Playbook {
+        Playbook::new("deploy")
+            .add_task(Task::command("migrate", "app migrate"))
+            .add_task(Task::command("restart", restart))
+            .add_task(Task::command("verify", "curl -f localhost"))
+    }
+
+    #[test]
+    fn test_state_invalidated_from_changed_task() {
+        let mut state = RunStateFile::for_playbook(&playbook("systemctl restart app"), None);
+        let with_changed = |changed| TaskOutcome {
+            changed: Some(changed),
+            ..TaskOutcome::ok()
+        };
+        for index in 0..3 {
+            state.record(
+                index,
+                &[
+                    ("web1".to_string(), with_changed(false)),
+                    ("web2".to_string(), with_changed(true)),
+                    ("web3".to_string(), TaskOutcome::skipped(SkipReason::PreviousFailure)),
+                    // 不报告 changed 的结果不会被复用
+                    ("web4".to_string(), TaskOutcome::ok()),
+                ],
+            );
+        }
+        assert_eq!(state.cached_hosts(0), vec!["web1"]);
+        assert!(!state.tasks[0].hosts.contains_key("web3"));
+
+        let json = serde_json::to_string(&state).unwrap();
+        let reloaded: RunStateFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded, state);
+        assert_eq!(RunStateFile::for_playbook(&playbook("systemctl restart app"), Some(reloaded)), state);
+
+        // 修改第 2 个任务：第 1 个任务的记录保留，第 2、3 个任务失效
+        let edited = RunStateFile::for_playbook(&playbook("systemctl reload app"), Some(state.clone()));
+        assert_ne!(edited.playbook_hash, state.playbook_hash);
+        assert_eq!(edited.cached_hosts(0), vec!["web1"]);
+        assert!(edited.cached_hosts(1).is_empty() && edited.cached_hosts(2).is_empty());
+        assert_eq!(edited.tasks[2].name, "verify");
+    }
+
+    #[test]
+    fn test_source_file_change_invalidates_task() {
+        let src = crate::utils::generate_local_temp_path("rs_ansible_state_src");
+        std::fs::write(&src, "listen 80\n").unwrap();
+        let options = TemplateOptions {
+            src: src.clone(),
+            dest: "/etc/app.conf".to_string(),
+            ..Default::default()
+        };
+        let playbook = Playbook::new("deploy")
+            .add_task(Task::command("install", "install-app"))
+            .add_task(Task::template("config", options));
+
+        let mut state = RunStateFile::for_playbook(&playbook, None);
+        let unchanged = TaskOutcome {
+            changed: Some(false),
+            ..TaskOutcome::ok()
+        };
+        state.record(1, &[("web1".to_string(), unchanged)]);
+        assert_eq!(RunStateFile::for_playbook(&playbook, Some(state.clone())).cached_hosts(1), vec!["web1"]);
+
+        std::fs::write(&src, "listen 8080\n").unwrap();
+        let edited = RunStateFile::for_playbook(&playbook, Some(state.clone()));
+        std::fs::remove_file(&src).unwrap();
+        assert_ne!(edited.playbook_hash, state.playbook_hash);
+        assert_eq!(edited.tasks[0].hash, state.tasks[0].hash);
+        assert!(edited.cached_hosts(1).is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+use crate::credentials::SecretString;
+use crate::error::AnsibleError;
+use crate::manager::{AsyncTransportFactory, TransportFactory};
+use crate::ssh::{AsyncTransport, Transport};
+use crate::types::{CommandOptions, CommandResult, HostConfig, HostKeyType};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 匹配所有主机的通配名称
+pub const ANY_HOST: &str = "*";
+
+/// 预设的命令响应：命令包含 `pattern` 时返回
+struct Rule {
+    host: String,
+    pattern: String,
+    response: Result<CommandResult, String>,
+}
+
+#[derive(Default)]
+struct MockState {
+    rules: Vec<Rule>,
+    connect_failures: HashMap<String, String>,
+    latency: HashMap<String, Duration>,
+    host_keys: HashMap<String, (HostKeyType, String)>,
+    download_failures: HashMap<(String, String), String>,
+    commands: HashMap<String, Vec<String>>,
+    command_options: HashMap<String, Vec<CommandOptions>>,
+    files: HashMap<String, HashMap<String, Vec<u8>>>,
+}
+
+/// 按主机脚本化响应的内存传输层
+///
+/// 所有克隆共享同一份状态：测试中保留一个实例用于设置响应与检查记录，
+/// 通过 [`MockTransport::factory`] 交给 `AnsibleManager` 为每台主机创建连接；
+/// `transport: russh` 的主机使用 [`MockTransport::async_factory`]，延迟以 `tokio::time::sleep` 模拟，不阻塞线程。
+///
+/// - 命令按后设置优先的顺序匹配预设响应（`*` 匹配所有主机）；
+/// - 未预设的命令模拟一个内存文件系统：支持 `test -f`、`cat`、`stat`、`sha256sum`、`mv`、`rm -f`，
+///   因此文件复制、模板部署与 Shell 任务可以完整执行；其余命令返回退出码 0 与空输出，
+///   `echo 'pong'` 返回 `pong`；
+/// - 可为主机注入连接失败、命令失败、下载失败与延迟。
+///
+/// # 示例
+/// ```
+/// use rs_ansible::testing::MockTransport;
+/// use rs_ansible::{AnsibleManager, CommandResult, HostConfig};
+///
+/// let mock = MockTransport::new();
+/// mock.on_command("web1", "uptime", CommandResult { exit_code: 0, stdout: "up 3 days\n".into(), stderr: String::new(), raw_stdout: None });
+///
+/// let manager = AnsibleManager::new().with_transport_factory(mock.factory());
+/// manager.add_host("web1".to_string(), HostConfig::default());
+///
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// let result = runtime.block_on(manager.execute_command_all("uptime"));
+/// assert_eq!(result.results["web1"].value().unwrap().stdout, "up 3 days\n");
+/// assert_eq!(mock.commands("web1"), vec!["uptime"]);
+/// ```
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    host: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 命令包含 `pattern` 时返回指定结果
+    pub fn on_command(&self, host: &str, pattern: &str, result: CommandResult) -> &Self {
+        self.add_rule(host, pattern, Ok(result))
+    }
+
+    /// 命令包含 `pattern` 时返回执行错误（模拟连接中断等异常）
+    pub fn fail_command(&self, host: &str, pattern: &str, message: &str) -> &Self {
+        self.add_rule(host, pattern, Err(message.to_string()))
+    }
+
+    /// 连接该主机时失败
+    pub fn fail_connect(&self, host: &str, message: &str) -> &Self {
+        self.lock().connect_failures.insert(host.to_string(), message.to_string());
+        self
+    }
+
+    /// 该主机上的每次命令执行与传输都延迟指定时间
+    pub fn with_latency(&self, host: &str, latency: Duration) -> &Self {
+        self.lock().latency.insert(host.to_string(), latency);
+        self
+    }
+
+    /// 下载该主机上的指定文件时失败（模拟权限不足等读取错误）
+    pub fn fail_download(&self, host: &str, path: &str, message: &str) -> &Self {
+        self.lock()
+            .download_failures
+            .insert((host.to_string(), path.to_string()), message.to_string());
+        self
+    }
+
+    /// 主机的 SSH 主机密钥类型与指纹（未设置时与非 SSH 连接一样返回错误）
+    pub fn with_host_key(&self, host: &str, key_type: HostKeyType, fingerprint: &str) -> &Self {
+        self.lock().host_keys.insert(host.to_string(), (key_type, fingerprint.to_string()));
+        self
+    }
+
+    /// 在主机的内存文件系统中放入文件
+    pub fn put_file(&self, host: &str, path: &str, data: &[u8]) -> &Self {
+        self.lock()
+            .files
+            .entry(host.to_string())
+            .or_default()
+            .insert(path.to_string(), data.to_vec());
+        self
+    }
+
+    /// 读取主机内存文件系统中的文件
+    pub fn file(&self, host: &str, path: &str) -> Option<Vec<u8>> {
+        self.lock().files.get(host).and_then(|files| files.get(path)).cloned()
+    }
+
+    /// 主机上按顺序执行过的命令（包含 become 与环境变量包装）
+    pub fn commands(&self, host: &str) -> Vec<String> {
+        self.lock().commands.get(host).cloned().unwrap_or_default()
+    }
+
+    /// 每次执行命令时传入的选项（stdin、timeout、pty 等），与 [`Self::commands`] 一一对应
+    pub fn command_options(&self, host: &str) -> Vec<CommandOptions> {
+        self.lock().command_options.get(host).cloned().unwrap_or_default()
+    }
+
+    /// 到指定主机的连接，与当前实例共享状态（用于直接构造 `SshClient`/`AsyncSshClient`）
+    pub fn connection(&self, host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            state: self.state.clone(),
+        }
+    }
+
+    /// 供 `AnsibleManager::with_transport_factory` 使用的工厂，连接按 inventory 中的主机名区分
+    pub fn factory(&self) -> TransportFactory {
+        let mock = self.clone();
+        Arc::new(move |host: &str, _config: &HostConfig| Ok(Box::new(mock.try_connect(host)?) as Box<dyn Transport>))
+    }
+
+    /// 供 `AnsibleManager::with_async_transport_factory` 使用的工厂
+    pub fn async_factory(&self) -> AsyncTransportFactory {
+        let mock = self.clone();
+        Arc::new(move |host: &str, _config: &HostConfig| {
+            Ok(Box::new(mock.try_connect(host)?) as Box<dyn AsyncTransport>)
+        })
+    }
+
+    fn try_connect(&self, host: &str) -> Result<Self, AnsibleError> {
+        if let Some(message) = self.lock().connect_failures.get(host) {
+            return Err(AnsibleError::SshConnectionError(format!(
+                "Failed to connect to {}: {}",
+                host, message
+            )));
+        }
+        Ok(self.connection(host))
+    }
+
+    fn add_rule(&self, host: &str, pattern: &str, response: Result<CommandResult, String>) -> &Self {
+        self.lock().rules.push(Rule {
+            host: host.to_string(),
+            pattern: pattern.to_string(),
+            response,
+        });
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        // 测试断言失败导致的锁中毒不应掩盖原始错误
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn latency(&self) -> Option<Duration> {
+        self.lock().latency.get(&self.host).copied()
+    }
+
+    /// 记录命令并返回预设响应或内存文件系统的模拟结果
+    fn run(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        let scripted = {
+            let mut state = self.lock();
+            state.commands.entry(self.host.clone()).or_default().push(command.to_string());
+            state.command_options.entry(self.host.clone()).or_default().push(options.clone());
+            state
+                .rules
+                .iter()
+                .rev()
+                .find(|rule| (rule.host == self.host || rule.host == ANY_HOST) && command.contains(&rule.pattern))
+                .map(|rule| rule.response.clone())
+        };
+
+        match scripted {
+            Some(Ok(result)) => Ok(result),
+            Some(Err(message)) => Err(AnsibleError::CommandExecutionError(message)),
+            None => Ok(self.simulate(command)),
+        }
+    }
+
+    fn read_file(&self, remote_path: &str) -> Result<Vec<u8>, AnsibleError> {
+        let failure = self.lock().download_failures.get(&(self.host.clone(), remote_path.to_string())).cloned();
+        if let Some(message) = failure {
+            return Err(AnsibleError::FileOperationError(format!("{}: {}", remote_path, message)));
+        }
+        self.file(&self.host, remote_path).ok_or_else(|| {
+            AnsibleError::FileOperationError(format!("{}: no such file on mock host {}", remote_path, self.host))
+        })
+    }
+
+    fn recorded_host_key(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        self.lock()
+            .host_keys
+            .get(&self.host)
+            .cloned()
+            .ok_or_else(|| AnsibleError::ValidationError(format!("No host key set for mock host {}", self.host)))
+    }
+
+    /// 在内存文件系统上模拟文件传输用到的命令
+    fn simulate(&self, command: &str) -> CommandResult {
+        let command = unwrap_become(command);
+        let paths: Vec<String> = command.split('\'').skip(1).step_by(2).map(str::to_string).collect();
+        let mut state = self.lock();
+        let files = state.files.entry(self.host.clone()).or_default();
+        let reply = |exit_code: i32, stdout: String| CommandResult {
+            exit_code,
+            stdout,
+            stderr: String::new(),
+            raw_stdout: None,
+        };
+        let path = paths.first();
+
+        if command.starts_with("test -f") && let Some(path) = path {
+            let exists = files.contains_key(path);
+            reply(0, if exists { "exists\n" } else { "not_exists\n" }.to_string())
+        } else if command.starts_with("stat -c '%a %U %G'") && paths.len() >= 2 {
+            if files.contains_key(&paths[1]) {
+                reply(0, "644 root root\n".to_string())
+            } else {
+                reply(1, String::new())
+            }
+        } else if command.starts_with("cat ") && let Some(path) = path {
+            match files.get(path) {
+                Some(data) => reply(0, String::from_utf8_lossy(data).into_owned()),
+                None => reply(1, String::new()),
+            }
+        } else if command.starts_with("stat -c %s") && let Some(data) = path.and_then(|p| files.get(p)) {
+            reply(0, format!("{}\n", data.len()))
+        } else if command.starts_with("sha256sum") && let Some(data) = path.and_then(|p| files.get(p)) {
+            reply(0, format!("{}  {}\n", crate::utils::sha256_hex(data), paths[0]))
+        } else if command.starts_with("mv ") && paths.len() >= 2 {
+            match files.remove(&paths[0]) {
+                Some(data) => {
+                    files.insert(paths[1].clone(), data);
+                    reply(0, String::new())
+                }
+                None => reply(1, String::new()),
+            }
+        } else if command.starts_with("rm -f") {
+            let target = path.cloned().or_else(|| command.split_whitespace().nth(2).map(str::to_string));
+            if let Some(target) = target {
+                files.remove(&target);
+            }
+            reply(0, String::new())
+        } else if command == "echo 'pong'" {
+            reply(0, "pong\n".to_string())
+        } else {
+            reply(0, String::new())
+        }
+    }
+}
+
+/// 去掉 `sudo ... -- sh -c '<command>'` 包装，还原原始命令
+fn unwrap_become(command: &str) -> String {
+    match command.strip_prefix("sudo ").and_then(|rest| rest.split_once(" -- sh -c ")) {
+        Some((_, quoted)) if quoted.len() >= 2 && quoted.starts_with('\'') && quoted.ends_with('\'') => {
+            quoted[1..quoted.len() - 1].replace("'\\''", "'")
+        }
+        _ => command.to_string(),
+    }
+}
+
+impl Transport for MockTransport {
+    fn connect(config: &HostConfig, _secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        Ok(Self {
+            host: config.hostname.clone(),
+            state: Arc::default(),
+        })
+    }
+
+    fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        if let Some(latency) = self.latency() {
+            std::thread::sleep(latency);
+        }
+        self.run(command, options)
+    }
+
+    fn upload(&self, reader: &mut dyn Read, _size: u64, remote_path: &str, _mode: i32) -> Result<u64, AnsibleError> {
+        if let Some(latency) = self.latency() {
+            std::thread::sleep(latency);
+        }
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        self.put_file(&self.host, remote_path, &data);
+        Ok(data.len() as u64)
+    }
+
+    fn download(&self, remote_path: &str, writer: &mut dyn Write) -> Result<u64, AnsibleError> {
+        if let Some(latency) = self.latency() {
+            std::thread::sleep(latency);
+        }
+        let data = self.read_file(remote_path)?;
+        writer.write_all(&data)?;
+        Ok(data.len() as u64)
+    }
+
+    fn host_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        self.recorded_host_key()
+    }
+}
+
+#[async_trait]
+impl AsyncTransport for MockTransport {
+    async fn connect(config: &HostConfig, secret: Option<&SecretString>) -> Result<Self, AnsibleError> {
+        <Self as Transport>::connect(config, secret)
+    }
+
+    async fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, AnsibleError> {
+        if let Some(latency) = self.latency() {
+            tokio::time::sleep(latency).await;
+        }
+        self.run(command, options)
+    }
+
+    async fn upload(
+        &self,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        _size: u64,
+        remote_path: &str,
+        _mode: i32,
+    ) -> Result<u64, AnsibleError> {
+        if let Some(latency) = self.latency() {
+            tokio::time::sleep(latency).await;
+        }
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        self.put_file(&self.host, remote_path, &data);
+        Ok(data.len() as u64)
+    }
+
+    async fn download(
+        &self,
+        remote_path: &str,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<u64, AnsibleError> {
+        if let Some(latency) = self.latency() {
+            tokio::time::sleep(latency).await;
+        }
+        let data = self.read_file(remote_path)?;
+        writer.write_all(&data).await?;
+        Ok(data.len() as u64)
+    }
+
+    fn host_key_fingerprint(&self) -> Result<(HostKeyType, String), AnsibleError> {
+        self.recorded_host_key()
+    }
+}
@@ -27,7 +27,7 @@ fn test_host_config_default() {
 
 #[test]
 fn test_ansible_manager_operations() {
-    let mut manager = AnsibleManager::new();
+    let manager = AnsibleManager::new();
 
     let config = AnsibleManager::host_builder()
         .hostname("192.168.1.100")
@@ -56,6 +56,7 @@ fn test_command_result() {
         exit_code: 0,
         stdout: "Hello World".to_string(),
         stderr: "".to_string(),
+        raw_stdout: None,
     };
 
     assert_eq!(result.exit_code, 0);
@@ -80,6 +81,42 @@ fn test_batch_result() {
     assert_eq!(batch_result.success_rate(), 0.5);
 }
 
+#[test]
+fn test_host_outcomes_in_batch_result() {
+    let mut batch_result: BatchResult<bool> = BatchResult::new();
+    batch_result.add_result("host1".to_string(), Ok(true));
+    batch_result.add_result("host2".to_string(), Ok(false));
+    batch_result.add_result(
+        "host3".to_string(),
+        Err(crate::error::AnsibleError::SshConnectionError("connection refused".to_string())),
+    );
+    batch_result.add_result(
+        "host4".to_string(),
+        Err(crate::error::AnsibleError::CommandError("exit 1".to_string())),
+    );
+    batch_result.add_skipped("host5".to_string(), SkipReason::Condition);
+    let batch_result = batch_result.with_changed(|changed| *changed);
+
+    assert!(batch_result.results["host3"].is_unreachable());
+    assert!(matches!(batch_result.results["host4"], HostOutcome::Failed(_)));
+    assert_eq!(batch_result.failed.len(), 2);
+    assert_eq!((batch_result.unreachable.clone(), batch_result.skipped.clone()), (vec!["host3".to_string()], vec!["host5".to_string()]));
+    assert_eq!(batch_result.changed_hosts(), vec!["host1"]);
+    // 跳过的主机不计入成功率
+    assert_eq!(batch_result.success_rate(), 0.5);
+    assert_eq!(batch_result.value("host2"), Some(&false));
+    assert_eq!(batch_result.error("host4").unwrap().to_string(), "Command failed: exit 1");
+
+    let json = serde_json::to_value(&batch_result.results).unwrap();
+    assert_eq!(json["host1"], serde_json::json!({ "ok": { "value": true, "changed": true } }));
+    assert_eq!(json["host3"], serde_json::json!({ "unreachable": { "SshConnectionError": "connection refused" } }));
+    assert_eq!(json["host5"], serde_json::json!({ "skipped": "condition" }));
+    let restored: std::collections::HashMap<String, HostOutcome<bool>> = serde_json::from_value(json).unwrap();
+    assert!(restored["host1"].changed());
+    assert!(matches!(restored["host5"], HostOutcome::Skipped(SkipReason::Condition)));
+    assert!(matches!(restored["host4"].error(), Some(crate::error::AnsibleError::CommandError(_))));
+}
+
 #[test]
 fn test_system_info_serialization() {
     use std::collections::HashMap;
@@ -96,14 +133,17 @@ fn test_system_info_serialization() {
     let sys_info = SystemInfo {
         hostname: "test-host".to_string(),
         os: "Linux".to_string(),
+        distribution: "Ubuntu 20.04.6 LTS".to_string(),
         kernel_version: "5.4.0".to_string(),
         architecture: "x86_64".to_string(),
         uptime: "up 1 day".to_string(),
         memory_total: "8G".to_string(),
         memory_free: "4G".to_string(),
         disk_usage,
+        disks: Vec::new(),
         cpu_info: "Intel Core i7".to_string(),
         network_interfaces,
+        packages: HashMap::new(),
     };
 
     // 测试序列化
@@ -116,3 +156,2789 @@ fn test_system_info_serialization() {
     assert_eq!(deserialized.hostname, "test-host");
     assert_eq!(deserialized.network_interfaces.len(), 1);
 }
+
+#[test]
+fn test_drain_result_removes_host() {
+    let manager = AnsibleManager::new();
+    let config = AnsibleManager::host_builder()
+        .hostname("192.168.1.100")
+        .username("test")
+        .password("test")
+        .build();
+    manager.add_host("node1".to_string(), config.clone());
+    manager.add_host("node2".to_string(), config);
+
+    // drain 未完成时主机保留
+    let failed = DrainResult {
+        pre_drain_ok: true,
+        drained: false,
+        verified: false,
+        post_tasks_result: None,
+    };
+    assert!(!manager.apply_drain_result("node1", &failed));
+    assert_eq!(manager.list_hosts().len(), 2);
+
+    // drain 成功后主机被移除
+    let drained = DrainResult {
+        pre_drain_ok: true,
+        drained: true,
+        verified: true,
+        post_tasks_result: None,
+    };
+    assert!(manager.apply_drain_result("node1", &drained));
+    let hosts = manager.list_hosts();
+    assert_eq!(hosts.len(), 1);
+    assert!(!hosts.contains(&"node1".to_string()));
+}
+
+#[tokio::test]
+async fn test_drain_unknown_host() {
+    let manager = AnsibleManager::new();
+    let config = DrainConfig {
+        pre_drain_command: None,
+        drain_command: "true".to_string(),
+        verify_command: None,
+        post_remove_tasks: Vec::new(),
+    };
+    assert!(manager.drain_host("missing", &config).await.is_err());
+}
+
+#[test]
+fn test_playbook_gather_facts_flag() {
+    use crate::executor::Playbook;
+
+    let yaml = "name: demo\ntasks:\n  - name: ping\n    task_type: ping\n";
+    let playbook: Playbook = serde_yaml::from_str(yaml).unwrap();
+    assert!(!playbook.gather_facts);
+
+    let yaml = "name: demo\ngather_facts: true\ntasks: []\n";
+    let playbook: Playbook = serde_yaml::from_str(yaml).unwrap();
+    assert!(playbook.gather_facts);
+
+    assert!(Playbook::new("demo").gather_facts(true).gather_facts);
+
+    // facts 类别：默认与以往一致，可在 YAML 中按名称选择
+    assert_eq!(playbook.fact_subset, crate::types::FactSubset::default());
+    let yaml = "name: demo\ngather_facts: true\nfact_subset: [network, os]\ntasks: []\n";
+    let playbook: Playbook = serde_yaml::from_str(yaml).unwrap();
+    let subset = playbook.fact_subset;
+    assert!(subset.contains(crate::types::FactSubset::NETWORK | crate::types::FactSubset::OS));
+    assert!(!subset.contains(crate::types::FactSubset::MOUNTS));
+    assert_eq!(serde_yaml::to_string(&subset).unwrap(), "- network\n- os\n");
+    assert!(serde_yaml::from_str::<Playbook>("name: x\nfact_subset: [gpu]\ntasks: []\n").is_err());
+}
+
+#[tokio::test]
+async fn test_blocking_operations_do_not_starve_runtime() {
+    use std::time::{Duration, Instant};
+
+    let manager = AnsibleManager::new().with_max_concurrent_connections(4);
+    let host_names: Vec<String> = (0..8).map(|i| format!("host{}", i)).collect();
+    for name in &host_names {
+        manager.add_host(name.clone(), HostConfig::default());
+    }
+
+    let start = Instant::now();
+    // 模拟缓慢的阻塞 SSH 操作
+    let batch = manager.execute_blocking_operation(&host_names, |_host, _config| {
+        std::thread::sleep(Duration::from_millis(300));
+        Ok(())
+    });
+    let timer = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        start.elapsed()
+    };
+
+    // 单线程运行时下，如果阻塞操作占用了工作线程，定时器将无法按时触发
+    let (result, timer_elapsed) = tokio::join!(batch, timer);
+    assert!(timer_elapsed < Duration::from_millis(250), "timer fired late: {:?}", timer_elapsed);
+    assert_eq!(result.successful.len(), host_names.len());
+}
+
+#[tokio::test]
+async fn test_operation_options_override_concurrency() {
+    use crate::concurrency::OperationOptions;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let manager = AnsibleManager::new().with_max_concurrent_connections(8);
+    let host_names: Vec<String> = (0..6).map(|i| format!("host{}", i)).collect();
+    for name in &host_names {
+        manager.add_host(name.clone(), HostConfig::default());
+    }
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let (running_ref, peak_ref) = (running.clone(), peak.clone());
+
+    let scoped = manager.override_operation_options(OperationOptions::new().max_concurrency(2));
+    let result = scoped
+        .execute_blocking_operation(&host_names, move |_host, _config| {
+            let now = running_ref.fetch_add(1, Ordering::SeqCst) + 1;
+            peak_ref.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            running_ref.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .await;
+
+    assert_eq!(result.successful.len(), host_names.len());
+    assert!(peak.load(Ordering::SeqCst) <= 2, "peak concurrency: {}", peak.load(Ordering::SeqCst));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_fail_limit_stops_scheduling_new_hosts() {
+    use crate::concurrency::OperationOptions;
+    use std::time::Duration;
+
+    let manager = AnsibleManager::new();
+    let host_names: Vec<String> = (0..6).map(|i| format!("host{}", i)).collect();
+    for name in &host_names {
+        manager.add_host(name.clone(), HostConfig::default());
+    }
+
+    let scoped = manager.override_operation_options(OperationOptions::new().max_concurrency(2).max_failures(2));
+    let result: BatchResult<()> = scoped
+        .execute_blocking_operation(&host_names, |host, _config| {
+            std::thread::sleep(Duration::from_millis(20));
+            Err(crate::error::AnsibleError::CommandError(format!("{} failed", host)))
+        })
+        .await;
+
+    // 已经拿到许可的主机会继续执行，因此失败数最多为 上限 + 并发数 - 1
+    assert!((2..=3).contains(&result.failed.len()), "failed: {:?}", result.failed);
+    assert!(result.successful.is_empty());
+    assert_eq!(result.failed.len() + result.skipped.len(), host_names.len());
+    assert!(result
+        .skipped
+        .iter()
+        .all(|h| matches!(result.results[h], HostOutcome::Skipped(SkipReason::FailureLimit))));
+
+    // 未设置上限时所有主机都会执行
+    let result: BatchResult<()> = manager
+        .execute_blocking_operation(&host_names, |_host, _config| {
+            Err(crate::error::AnsibleError::CommandError("failed".to_string()))
+        })
+        .await;
+    assert_eq!(result.failed.len(), host_names.len());
+    assert!(result.skipped.is_empty());
+}
+
+#[test]
+fn test_task_become_override_does_not_leak() {
+    use crate::executor::Task;
+
+    let manager = AnsibleManager::new();
+    let config = AnsibleManager::host_builder()
+        .hostname("db1")
+        .username("deploy")
+        .password("test")
+        .build();
+    manager.add_host("db1".to_string(), config);
+
+    let task = Task::command("vacuum", "vacuumdb -a").become_user(Some("postgres".to_string()));
+    assert!(task.has_become_override());
+
+    let scoped = manager.with_become_override(task.r#become, task.become_user.clone());
+    let scoped_config = scoped.get_host("db1").unwrap();
+    assert!(scoped_config.r#become);
+    assert_eq!(scoped_config.become_user.as_deref(), Some("postgres"));
+    assert_eq!(scoped_config.username, "deploy");
+
+    // 原管理器中的配置不受影响
+    let original = manager.get_host("db1").unwrap();
+    assert!(!original.r#become);
+    assert!(original.become_user.is_none());
+
+    // 显式关闭 become 的任务优先
+    let scoped = manager.with_become_override(Some(false), Some("postgres".to_string()));
+    assert!(!scoped.get_host("db1").unwrap().r#become);
+}
+
+#[test]
+fn test_task_connection_override_does_not_leak() {
+    use crate::executor::Task;
+
+    let manager = AnsibleManager::new();
+    let config = AnsibleManager::host_builder()
+        .hostname("db1")
+        .port(22)
+        .username("deploy")
+        .password("test")
+        .build();
+    manager.add_host("db1".to_string(), config);
+
+    let task = Task::command("fsck", "fsck -n /dev/sda1").as_user("root").on_port(2222);
+    assert!(task.has_connection_override());
+    assert!(!Task::command("uptime", "uptime").has_connection_override());
+
+    let scoped = manager.with_connection_override(task.remote_user.clone(), task.port);
+    let scoped_config = scoped.get_host("db1").unwrap();
+    assert_eq!(scoped_config.username, "root");
+    assert_eq!(scoped_config.port, 2222);
+    assert_eq!(scoped_config.password.as_deref(), Some("test"));
+
+    let original = manager.get_host("db1").unwrap();
+    assert_eq!(original.username, "deploy");
+    assert_eq!(original.port, 22);
+
+    // 只覆盖端口时保留原用户
+    let scoped = manager.with_connection_override(None, Some(2222));
+    assert_eq!(scoped.get_host("db1").unwrap().username, "deploy");
+
+    let yaml = serde_yaml::to_string(&task).unwrap();
+    let parsed: Task = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(parsed.remote_user.as_deref(), Some("root"));
+    assert_eq!(parsed.port, Some(2222));
+}
+
+#[test]
+fn test_client_over_custom_transport() {
+    use crate::ssh::SshClient;
+    use crate::testing::MockTransport;
+
+    let config = HostConfig {
+        hostname: "mock".to_string(),
+        r#become: true,
+        ..Default::default()
+    };
+    let mock = MockTransport::new();
+    mock.put_file("mock", "/etc/motd", b"hello");
+
+    let client = SshClient::with_transport(config, Box::new(mock.connection("mock")));
+    assert!(client.ping().unwrap());
+    assert_eq!(
+        mock.commands("mock")[0],
+        "sudo -n -H -u 'root' -- sh -c 'echo '\\''pong'\\'''"
+    );
+
+    let local = crate::utils::generate_local_temp_path("rs_ansible_mock_download");
+    let result = client.copy_file_from_remote("/etc/motd", &local).unwrap();
+    assert_eq!(result.bytes_transferred, 5);
+    assert_eq!(std::fs::read(&local).unwrap(), b"hello");
+    let _ = std::fs::remove_file(&local);
+}
+
+#[test]
+fn test_check_privilege_escalation_uses_become_method() {
+    use crate::ssh::SshClient;
+    use crate::testing::MockTransport;
+
+    // 未开启 become 的主机同样按 sudo 检查
+    let config = HostConfig::default();
+    let mock = MockTransport::new();
+    let client = SshClient::with_transport(config, Box::new(mock.connection("web1")));
+    assert!(client.check_privilege_escalation().unwrap());
+    assert_eq!(mock.commands("web1")[0], "sudo -n -H -u 'root' -- sh -c 'true'");
+
+    let config = HostConfig {
+        become_user: Some("postgres".to_string()),
+        become_password: Some("s3cret".to_string()),
+        ..Default::default()
+    };
+    let mock = MockTransport::new();
+    let client = SshClient::with_transport(config, Box::new(mock.connection("db1")));
+    assert!(client.check_privilege_escalation().unwrap());
+    assert_eq!(
+        mock.commands("db1")[0],
+        "{ IFS= read -r pw; printf '%s\\n' \"$pw\"; } | sudo -S -p '' -v && sudo -n -H -u 'postgres' -- sh -c 'true'"
+    );
+}
+
+#[test]
+fn test_become_password_without_cached_credentials() {
+    use crate::ssh::{SshClient, Transport};
+    use std::sync::{Arc, Mutex};
+
+    /// 模拟 `Defaults timestamp_timeout=0`：`sudo -v` 成功但不缓存凭据，每次 sudo 都要读取密码
+    struct NoTimestampSudo {
+        commands: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Transport for NoTimestampSudo {
+        fn connect(_config: &HostConfig, _secret: Option<&crate::credentials::SecretString>) -> Result<Self, crate::error::AnsibleError> {
+            Ok(Self { commands: Arc::default() })
+        }
+
+        fn exec(&self, command: &str, options: &CommandOptions) -> Result<CommandResult, crate::error::AnsibleError> {
+            self.commands.lock().unwrap().push(command.to_string());
+            let stdin = String::from_utf8_lossy(options.stdin.as_deref().unwrap_or_default()).into_owned();
+            let reply = |exit_code: i32, stdout: &str, stderr: &str| CommandResult {
+                exit_code,
+                stdout: stdout.to_string(),
+                stderr: stderr.to_string(),
+                raw_stdout: None,
+            };
+            if command.contains("sudo -n") {
+                return Ok(reply(1, "", "sudo: a password is required\n"));
+            }
+            // `sudo -S` 读走密码行，剩余的标准输入交给命令（这里的命令回显标准输入）
+            match stdin.split_once('\n') {
+                Some(("s3cret", rest)) => Ok(reply(0, rest, "")),
+                _ => Ok(reply(1, "", "sudo: 1 incorrect password attempt\n")),
+            }
+        }
+
+        fn upload(&self, _reader: &mut dyn std::io::Read, _size: u64, _remote_path: &str, _mode: i32) -> Result<u64, crate::error::AnsibleError> {
+            Ok(0)
+        }
+
+        fn download(&self, _remote_path: &str, _writer: &mut dyn std::io::Write) -> Result<u64, crate::error::AnsibleError> {
+            Ok(0)
+        }
+    }
+
+    let config = HostConfig {
+        r#become: true,
+        become_password: Some("s3cret".to_string()),
+        ..Default::default()
+    };
+    let transport = NoTimestampSudo::connect(&config, None).unwrap();
+    let commands = transport.commands.clone();
+    let client = SshClient::with_transport(config, Box::new(transport));
+
+    let options = CommandOptions { stdin: Some(b"payload\n".to_vec()), ..Default::default() };
+    let result = client.execute_command_full("cat", options).unwrap();
+    assert_eq!(result.exit_code, 0);
+    assert_eq!(result.stdout, "payload\n");
+    assert_eq!(
+        *commands.lock().unwrap(),
+        vec![
+            "{ IFS= read -r pw; printf '%s\\n' \"$pw\"; } | sudo -S -p '' -v && sudo -n -H -u 'root' -- sh -c 'cat'".to_string(),
+            "sudo -S -p '' -H -u 'root' -- sh -c 'cat'".to_string(),
+        ]
+    );
+
+    commands.lock().unwrap().clear();
+    assert!(client.check_privilege_escalation().unwrap());
+    assert_eq!(commands.lock().unwrap()[1], "sudo -S -p '' -H -u 'root' -- sh -c 'true'");
+}
+
+#[tokio::test]
+async fn test_local_command_register() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+
+    // 没有任何远程主机时，本地任务仍会执行一次
+    let manager = AnsibleManager::new();
+    let executor = TaskExecutor::new(&manager);
+    let playbook = Playbook::new("local")
+        .add_task(Task::local_command("generate token", "echo abc123").register("token"));
+
+    let result = executor.execute_playbook(&playbook).await.unwrap();
+    assert!(result.overall_success);
+    let (_, task_result) = &result.task_results[0];
+    assert_eq!(task_result.successful_hosts(), &vec!["localhost".to_string()]);
+    if let crate::executor::TaskResult::Command(batch) = task_result {
+        let output = batch.results["localhost"].value().unwrap();
+        assert_eq!(output.stdout.trim(), "abc123");
+    } else {
+        panic!("unexpected task result type");
+    }
+}
+
+#[test]
+fn test_task_list_round_trip() {
+    use crate::executor::{Playbook, Task, TaskType};
+
+    let playbook = Playbook::new("base")
+        .add_task(Task::command("uptime", "uptime"))
+        .add_task(Task::local_command("notify", "echo done").register("notified"));
+
+    let path = std::env::temp_dir().join(format!("rs_ansible_tasks_{}.yml", std::process::id()));
+    playbook.save_tasks_to_file(&path).unwrap();
+
+    // 任务文件的根节点是序列，而不是完整 Playbook
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(matches!(serde_yaml::from_str::<serde_yaml::Value>(&content).unwrap(), serde_yaml::Value::Sequence(_)));
+
+    let imported = Playbook::new("composed")
+        .add_task(Task::command("first", "hostname"))
+        .import_tasks_from_file(&path)
+        .unwrap();
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(imported.tasks.len(), 3);
+    assert_eq!(imported.tasks[1].name, "uptime");
+    assert_eq!(imported.tasks[2].register.as_deref(), Some("notified"));
+    assert!(matches!(imported.tasks[2].task_type, TaskType::LocalCommand { .. }));
+
+    // 执行入口可以识别两种格式
+    let from_tasks = Playbook::from_yaml_str(&content, "tasks_only").unwrap();
+    assert_eq!(from_tasks.name, "tasks_only");
+    assert_eq!(from_tasks.tasks.len(), 2);
+
+    let full = serde_yaml::to_string(&playbook).unwrap();
+    let from_full = Playbook::from_yaml_str(&full, "ignored").unwrap();
+    assert_eq!(from_full.name, "base");
+    assert!(Playbook::from_yaml_str("cmd: uptime", "x").is_err());
+}
+
+#[test]
+fn test_execute_command_full_combines_stdin_and_timeout() {
+    use crate::ssh::SshClient;
+    use crate::testing::MockTransport;
+    use crate::types::CommandOptions;
+    use std::time::Duration;
+
+    let config = AnsibleManager::host_builder()
+        .hostname("db1")
+        .username("postgres")
+        .password("test")
+        .build();
+    let mock = MockTransport::new();
+    let client = SshClient::with_transport(config, Box::new(mock.connection("db1")));
+
+    let options = CommandOptions::new()
+        .stdin("SELECT 1;\n")
+        .timeout(Duration::from_secs(30))
+        .env("PGDATABASE", "app");
+    client.execute_command_full("psql -f -", options).unwrap();
+
+    assert_eq!(mock.commands("db1")[0], "export PGDATABASE='app'; psql -f -");
+    let recorded = mock.command_options("db1");
+    assert_eq!(recorded[0].stdin.as_deref(), Some(&b"SELECT 1;\n"[..]));
+    assert_eq!(recorded[0].timeout, Some(Duration::from_secs(30)));
+
+    // 便捷方法不携带任何选项
+    client.execute_command("true").unwrap();
+    let recorded = mock.command_options("db1");
+    assert_eq!(mock.commands("db1")[1], "true");
+    assert_eq!((recorded[1].stdin.as_deref(), recorded[1].timeout), (None, None));
+}
+
+#[test]
+fn test_shell_task_guards() {
+    use crate::executor::{shell_guard_command, Task, TaskType};
+
+    assert_eq!(shell_guard_command(None, None), None);
+    assert_eq!(
+        shell_guard_command(Some("/opt/app/.installed"), None).unwrap(),
+        "if [ -e '/opt/app/.installed' ]; then echo skip; fi"
+    );
+    assert_eq!(
+        shell_guard_command(Some("/a"), Some("/b")).unwrap(),
+        "if [ -e '/a' ] || [ ! -e '/b' ]; then echo skip; fi"
+    );
+
+    let task = Task::shell_script("provision", "./install.sh\r\n")
+        .creates("/opt/app/.installed")
+        .chdir("/opt/app");
+    let yaml = serde_yaml::to_string(&task).unwrap();
+    let parsed: Task = serde_yaml::from_str(&yaml).unwrap();
+    match parsed.task_type {
+        TaskType::Shell { creates, removes, chdir, .. } => {
+            assert_eq!(creates.as_deref(), Some("/opt/app/.installed"));
+            assert_eq!(removes, None);
+            assert_eq!(chdir.as_deref(), Some("/opt/app"));
+        }
+        other => panic!("unexpected task type: {:?}", other),
+    }
+
+    // 旧格式（只有 script 字段）仍可解析
+    let legacy: Task = serde_yaml::from_str("name: old\ntask_type: shell\nscript: echo hi\n").unwrap();
+    assert!(matches!(legacy.task_type, TaskType::Shell { creates: None, .. }));
+}
+
+/// 模拟远程文件系统的传输层，可在指定阶段注入失败
+#[derive(Default)]
+struct FakeFsTransport {
+    files: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, Vec<u8>>>>,
+    links: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, String>>>, // 符号链接 -> 目标
+    fail_upload: bool,
+    fail_mv: bool,
+    fail_mv_to: Option<String>, // 仅移动到该路径时失败
+    fail_copy_to: Option<String>, // 仅复制到该路径时失败
+    fail_validate: bool,
+    fail_reload: bool,
+    corrupt_download: bool,
+}
+
+impl FakeFsTransport {
+    fn quoted_paths(command: &str) -> Vec<String> {
+        command.split('\'').skip(1).step_by(2).map(|s| s.to_string()).collect()
+    }
+
+    fn reply(exit_code: i32, stdout: String) -> Result<CommandResult, crate::error::AnsibleError> {
+        Ok(CommandResult { exit_code, stdout, stderr: String::new(), raw_stdout: None })
+    }
+}
+
+impl crate::ssh::Transport for FakeFsTransport {
+    fn connect(
+        _config: &HostConfig,
+        _secret: Option<&crate::credentials::SecretString>,
+    ) -> Result<Self, crate::error::AnsibleError> {
+        Ok(Self::default())
+    }
+
+    fn exec(
+        &self,
+        command: &str,
+        _options: &crate::types::CommandOptions,
+    ) -> Result<CommandResult, crate::error::AnsibleError> {
+        let mut files = self.files.lock().unwrap();
+        let paths = Self::quoted_paths(command);
+        if command.starts_with("test -f") {
+            let exists = files.contains_key(&paths[0]);
+            Self::reply(0, if exists { "exists\n" } else { "not_exists\n" }.to_string())
+        } else if command.starts_with("stat -c '%a %U %G'") {
+            Self::reply(0, "644 root root\n".to_string())
+        } else if command.starts_with("stat -c %s") {
+            Self::reply(0, format!("{}\n", files[&paths[0]].len()))
+        } else if command.starts_with("sha256sum") {
+            Self::reply(0, format!("{}  {}\n", crate::utils::sha256_hex(&files[&paths[0]]), paths[0]))
+        } else if command.starts_with("mv ") {
+            if self.fail_mv || self.fail_mv_to.as_ref().is_some_and(|dest| paths[1] == *dest) {
+                return Self::reply(1, String::new());
+            }
+            let data = files.remove(&paths[0]).unwrap();
+            files.insert(paths[1].clone(), data);
+            Self::reply(0, String::new())
+        } else if command.starts_with("rm -f") {
+            files.remove(&paths[0]);
+            Self::reply(0, String::new())
+        } else if command.starts_with("if [ -e") && command.contains("cp -p") {
+            match files.get(&paths[0]).cloned() {
+                Some(data) => {
+                    files.insert(paths[2].clone(), data);
+                    Self::reply(0, "backed_up\n".to_string())
+                }
+                None => Self::reply(0, String::new()),
+            }
+        } else if command.starts_with("if [ -L") {
+            match self.links.lock().unwrap().get(&paths[0]) {
+                Some(target) => Self::reply(0, format!("{}\n", target)),
+                None if files.contains_key(&paths[0]) => Self::reply(3, String::new()),
+                None => Self::reply(4, String::new()),
+            }
+        } else if command.contains("ln -sfn") {
+            let [.., target, link] = paths.as_slice() else { unreachable!() };
+            files.remove(link);
+            self.links.lock().unwrap().insert(link.clone(), target.clone());
+            Self::reply(0, String::new())
+        } else if command.starts_with("validate") {
+            Self::reply(if self.fail_validate { 1 } else { 0 }, String::new())
+        } else if command.starts_with("reload") {
+            Self::reply(if self.fail_reload { 1 } else { 0 }, String::new())
+        } else if command.starts_with("cat '") {
+            match files.get(&paths[0]) {
+                Some(data) => Self::reply(0, String::from_utf8_lossy(data).into_owned()),
+                None => Self::reply(1, String::new()),
+            }
+        } else if command.starts_with("cp ") {
+            if self.fail_copy_to.as_ref().is_some_and(|dest| paths[1] == *dest) {
+                return Self::reply(1, String::new());
+            }
+            let data = files[&paths[0]].clone();
+            files.insert(paths[1].clone(), data);
+            Self::reply(0, String::new())
+        } else {
+            Self::reply(0, String::new())
+        }
+    }
+
+    fn upload(
+        &self,
+        reader: &mut dyn std::io::Read,
+        _size: u64,
+        remote_path: &str,
+        _mode: i32,
+    ) -> Result<u64, crate::error::AnsibleError> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        let len = buf.len() as u64;
+        // 模拟传输中断：写入了部分数据后失败
+        self.files.lock().unwrap().insert(remote_path.to_string(), buf);
+        if self.fail_upload {
+            return Err(crate::error::AnsibleError::Ssh2Error("connection reset".to_string()));
+        }
+        Ok(len)
+    }
+
+    fn download(
+        &self,
+        remote_path: &str,
+        writer: &mut dyn std::io::Write,
+    ) -> Result<u64, crate::error::AnsibleError> {
+        let mut data = self.files.lock().unwrap().get(remote_path).cloned().unwrap_or_default();
+        // 模拟传输过程中的数据损坏
+        if self.corrupt_download && let Some(byte) = data.first_mut() {
+            *byte ^= 0xff;
+        }
+        writer.write_all(&data)?;
+        Ok(data.len() as u64)
+    }
+}
+
+#[test]
+fn test_verified_download_detects_hash_mismatch() {
+    use crate::ssh::SshClient;
+
+    let remote_file = ("/var/log/app.log".to_string(), b"line 1\nline 2\n".to_vec());
+    let local_path = crate::utils::generate_local_temp_path("rs_ansible_fetch");
+
+    let transport = FakeFsTransport::default();
+    transport.files.lock().unwrap().insert(remote_file.0.clone(), remote_file.1.clone());
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    let result = client.copy_file_from_remote_verified(&remote_file.0, &local_path, "sha256").unwrap();
+    assert_eq!(result.bytes_transferred, remote_file.1.len() as u64);
+    assert_eq!(std::fs::read(&local_path).unwrap(), remote_file.1);
+    std::fs::remove_file(&local_path).unwrap();
+
+    let transport = FakeFsTransport { corrupt_download: true, ..Default::default() };
+    transport.files.lock().unwrap().insert(remote_file.0.clone(), remote_file.1.clone());
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    match client.copy_file_from_remote_verified(&remote_file.0, &local_path, "sha256") {
+        Err(crate::error::AnsibleError::FileOperationError(msg)) => assert!(msg.contains("hash mismatch")),
+        other => panic!("expected hash mismatch, got {:?}", other),
+    }
+    assert!(!std::path::Path::new(&local_path).exists(), "corrupted download was not removed");
+
+    // 远程文件不存在时不创建本地文件
+    assert!(client.copy_file_from_remote_verified("/missing", &local_path, "sha256").is_err());
+    assert!(!std::path::Path::new(&local_path).exists());
+}
+
+#[test]
+fn test_upload_reports_progress() {
+    use crate::ssh::{SshClient, REPORT_INTERVAL_BYTES};
+
+    let local_path = crate::utils::generate_local_temp_path("rs_ansible_progress");
+    let data = vec![42u8; 200 * 1024];
+    std::fs::write(&local_path, &data).unwrap();
+
+    let transport = FakeFsTransport::default();
+    let files = transport.files.clone();
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    let reports = std::sync::Mutex::new(Vec::new());
+    let result = client
+        .copy_file_to_remote_with_progress(&local_path, "/srv/data.bin", &crate::types::FileCopyOptions::default(), |p| {
+            reports.lock().unwrap().push(p)
+        })
+        .unwrap();
+    assert_eq!(result.bytes_transferred, data.len() as u64);
+    assert_eq!(files.lock().unwrap()["/srv/data.bin"], data);
+
+    let reports = reports.into_inner().unwrap();
+    assert!(reports.len() as u64 >= data.len() as u64 / REPORT_INTERVAL_BYTES);
+    assert!(reports.windows(2).all(|w| w[0].bytes_sent < w[1].bytes_sent));
+    assert!(reports.iter().all(|p| p.total_bytes == data.len() as u64));
+    let last = reports.last().unwrap();
+    assert_eq!(last.bytes_sent, data.len() as u64);
+    assert_eq!(last.estimated_remaining_secs, Some(0.0));
+
+    // 远程文件未变化时跳过传输，不回调进度
+    let called = std::sync::atomic::AtomicBool::new(false);
+    client
+        .copy_file_to_remote_with_progress(&local_path, "/srv/data.bin", &crate::types::FileCopyOptions::default(), |_| {
+            called.store(true, std::sync::atomic::Ordering::SeqCst)
+        })
+        .unwrap();
+    assert!(!called.load(std::sync::atomic::Ordering::SeqCst));
+    std::fs::remove_file(&local_path).unwrap();
+}
+
+#[test]
+fn test_temp_files_cleaned_up_on_failures() {
+    use crate::ssh::{SshClient, TemplateCache};
+    use crate::types::TemplateOptions;
+
+    let template_path = crate::utils::generate_local_temp_path("rs_ansible_cleanup_template");
+    std::fs::write(&template_path, "listen {{ port }}\n").unwrap();
+
+    let mut variables = std::collections::HashMap::new();
+    variables.insert("port".to_string(), serde_json::json!(8080));
+    let options = TemplateOptions {
+        src: template_path.clone(),
+        dest: "/etc/app.conf".to_string(),
+        variables,
+        validate: Some("validate {{ path }}".to_string()),
+        ..Default::default()
+    };
+
+    let stages = [
+        ("upload", FakeFsTransport { fail_upload: true, ..Default::default() }),
+        ("validate", FakeFsTransport { fail_validate: true, ..Default::default() }),
+        ("mv", FakeFsTransport { fail_mv: true, ..Default::default() }),
+    ];
+    for (stage, transport) in stages {
+        let files = transport.files.clone();
+        let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+        let cache = TemplateCache::new();
+
+        let result = client.deploy_template_with_cache(&options, &cache);
+        assert!(result.is_err(), "stage {} should fail", stage);
+        assert!(
+            files.lock().unwrap().is_empty(),
+            "stage {} left remote files: {:?}",
+            stage,
+            files.lock().unwrap().keys().collect::<Vec<_>>()
+        );
+
+        let local_artifacts = cache.artifact_paths();
+        assert_eq!(local_artifacts.len(), 1);
+        drop(cache);
+        assert!(local_artifacts.iter().all(|p| !std::path::Path::new(p).exists()), "stage {} left local files", stage);
+    }
+
+    // 开启 keep_temp_on_failure 时保留远程临时文件
+    let transport = FakeFsTransport { fail_mv: true, ..Default::default() };
+    let files = transport.files.clone();
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    let keep_options = TemplateOptions { validate: None, keep_temp_on_failure: true, ..options.clone() };
+    assert!(client.deploy_template(&keep_options).is_err());
+    let kept: Vec<String> = files.lock().unwrap().keys().cloned().collect();
+    assert_eq!(kept.len(), 1);
+    assert!(kept[0].starts_with("/etc/app.conf.tmp."));
+
+    // 验证用的临时文件位于主机配置的 remote_tmp 目录
+    let transport = FakeFsTransport { fail_validate: true, ..Default::default() };
+    let files = transport.files.clone();
+    let config = HostConfig { remote_tmp: Some("/var/tmp/rs_ansible/".to_string()), ..Default::default() };
+    let client = SshClient::with_transport(config, Box::new(transport));
+    let keep_options = TemplateOptions { keep_temp_on_failure: true, ..options.clone() };
+    assert!(client.deploy_template(&keep_options).is_err());
+    let kept: Vec<String> = files.lock().unwrap().keys().cloned().collect();
+    assert_eq!(kept.len(), 1);
+    assert!(kept[0].starts_with("/var/tmp/rs_ansible/rs_ansible_validate.tmp."), "unexpected temp path {}", kept[0]);
+
+    std::fs::remove_file(&template_path).unwrap();
+}
+
+#[test]
+fn test_non_atomic_copy_writes_destination_directly() {
+    use crate::ssh::SshClient;
+    use crate::types::FileCopyOptions;
+
+    let local_path = crate::utils::generate_local_temp_path("rs_ansible_non_atomic");
+    std::fs::write(&local_path, b"payload\n").unwrap();
+
+    // 目标文件系统不支持 rename：原子模式在 mv 时失败，并清理临时文件
+    let transport = FakeFsTransport { fail_mv: true, ..Default::default() };
+    let files = transport.files.clone();
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    assert!(client.copy_file_to_remote(&local_path, "/mnt/fuse/app.bin").is_err());
+    assert!(files.lock().unwrap().is_empty());
+
+    let options = FileCopyOptions { atomic: false, ..Default::default() };
+    let result = client.copy_file_to_remote_with_options(&local_path, "/mnt/fuse/app.bin", &options).unwrap();
+    assert_eq!(result.bytes_transferred, 8);
+    let files = files.lock().unwrap();
+    assert_eq!(files.keys().collect::<Vec<_>>(), vec!["/mnt/fuse/app.bin"]);
+    assert_eq!(files["/mnt/fuse/app.bin"], b"payload\n");
+
+    std::fs::remove_file(&local_path).unwrap();
+}
+
+#[test]
+fn test_copy_dir_manifest_reports_actions() {
+    use crate::ssh::SshClient;
+    use crate::types::{FileCopyOptions, ManifestAction};
+
+    let local_dir = crate::utils::generate_local_temp_path("rs_ansible_manifest");
+    std::fs::create_dir_all(format!("{}/conf", local_dir)).unwrap();
+    std::fs::write(format!("{}/app.bin", local_dir), b"v2").unwrap();
+    std::fs::write(format!("{}/conf/app.conf", local_dir), b"port = 80\n").unwrap();
+    std::fs::write(format!("{}/README", local_dir), b"docs\n").unwrap();
+
+    let transport = FakeFsTransport::default();
+    let files = transport.files.clone();
+    {
+        let mut files = files.lock().unwrap();
+        files.insert("/opt/app/app.bin".to_string(), b"v1".to_vec());
+        files.insert("/opt/app/README".to_string(), b"docs\n".to_vec());
+    }
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    let manifest = client
+        .copy_dir_with_manifest(&local_dir, "/opt/app/", &FileCopyOptions::default())
+        .unwrap();
+
+    let actions: Vec<(&str, ManifestAction)> =
+        manifest.files.iter().map(|e| (e.path.as_str(), e.action)).collect();
+    assert_eq!(
+        actions,
+        vec![
+            ("README", ManifestAction::Skipped),
+            ("app.bin", ManifestAction::Updated),
+            ("conf/app.conf", ManifestAction::Created),
+        ]
+    );
+    assert_eq!(manifest.files[1].hash, crate::utils::sha256_hex(b"v2"));
+    assert_eq!(manifest.files[2].size, 10);
+    assert_eq!(files.lock().unwrap()["/opt/app/conf/app.conf"], b"port = 80\n");
+
+    let changed: Vec<&str> = manifest.changed_files().iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(changed, vec!["app.bin", "conf/app.conf"]);
+
+    let json_path = format!("{}.json", local_dir);
+    manifest.save_to_json(&json_path).unwrap();
+    let saved: crate::types::DirectoryManifest =
+        serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+    assert_eq!(saved, manifest);
+    std::fs::remove_file(&json_path).unwrap();
+    std::fs::remove_dir_all(&local_dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_copy_dir_recreates_or_follows_symlinks() {
+    use crate::ssh::SshClient;
+    use crate::types::{FileCopyOptions, ManifestAction};
+    use std::os::unix::fs::symlink;
+
+    let local_dir = crate::utils::generate_local_temp_path("rs_ansible_symlinks");
+    std::fs::create_dir_all(&local_dir).unwrap();
+    std::fs::write(format!("{}/app.conf", local_dir), b"port = 80\n").unwrap();
+    symlink("app.conf", format!("{}/conf.link", local_dir)).unwrap();
+    symlink(".", format!("{}/loop", local_dir)).unwrap();
+
+    let transport = FakeFsTransport::default();
+    let (files, links) = (transport.files.clone(), transport.links.clone());
+    links.lock().unwrap().insert("/opt/app/loop".to_string(), "..".to_string());
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+
+    // 默认在远程重建符号链接，不跟随指向自身的目录链接
+    let manifest = client.copy_dir_with_manifest(&local_dir, "/opt/app", &FileCopyOptions::default()).unwrap();
+    let entries: Vec<(&str, ManifestAction, Option<&str>)> =
+        manifest.files.iter().map(|e| (e.path.as_str(), e.action, e.link_target.as_deref())).collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("app.conf", ManifestAction::Created, None),
+            ("conf.link", ManifestAction::Created, Some("app.conf")),
+            ("loop", ManifestAction::Updated, Some(".")),
+        ]
+    );
+    assert_eq!(manifest.symlinks().len(), 2);
+    assert_eq!(links.lock().unwrap()["/opt/app/conf.link"], "app.conf");
+    assert!(!files.lock().unwrap().contains_key("/opt/app/conf.link"));
+
+    let again = client.copy_dir_with_manifest(&local_dir, "/opt/app", &FileCopyOptions::default()).unwrap();
+    assert!(again.changed_files().is_empty());
+
+    // 跟随符号链接时复制目标内容，循环链接被跳过
+    let options = FileCopyOptions { follow_symlinks: true, ..Default::default() };
+    let manifest = client.copy_dir_with_manifest(&local_dir, "/srv/app", &options).unwrap();
+    let paths: Vec<&str> = manifest.files.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, vec!["app.conf", "conf.link"]);
+    assert!(manifest.symlinks().is_empty());
+    assert_eq!(files.lock().unwrap()["/srv/app/conf.link"], b"port = 80\n");
+
+    std::fs::remove_dir_all(&local_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_parallel_deploy_runs_stages_in_dependency_order() {
+    use crate::executor::{Playbook, Task};
+    use crate::manager::DeploymentStage;
+
+    let log = crate::utils::generate_local_temp_path("rs_ansible_deploy");
+    let record = |stage: &str| {
+        Playbook::new(stage).add_task(Task::local_command(stage, &format!("echo {} >> {}", stage, log)))
+    };
+
+    let stages = vec![
+        DeploymentStage::new("frontend", &[], record("frontend")).depends_on(&["backend"]),
+        DeploymentStage::new("backend", &[], record("backend")).depends_on(&["database"]),
+        DeploymentStage::new("database", &[], record("database")),
+        DeploymentStage::new("cache", &[], record("cache")),
+        // 目标主机不存在，阶段失败
+        DeploymentStage::new("migrate", &["ghost"], Playbook::new("migrate").add_task(Task::command("migrate", "true")))
+            .depends_on(&["database"]),
+        DeploymentStage::new("reindex", &[], record("reindex")).depends_on(&["migrate"]),
+        DeploymentStage::new("notify", &[], record("notify")).depends_on(&["reindex", "frontend"]),
+    ];
+
+    let manager = AnsibleManager::new();
+    let result = manager.parallel_deploy(stages).await.unwrap();
+    let order: Vec<String> = std::fs::read_to_string(&log).unwrap().lines().map(str::to_string).collect();
+    std::fs::remove_file(&log).unwrap();
+
+    let position = |stage: &str| order.iter().position(|s| s == stage).unwrap();
+    assert_eq!(order.len(), 4);
+    assert!(position("database") < position("backend"));
+    assert!(position("backend") < position("frontend"));
+    assert!(position("cache") < position("backend"));
+
+    assert!(!result.success);
+    assert!(!result.stage_results["migrate"].overall_success);
+    assert!(result.stage_results["frontend"].overall_success);
+    assert_eq!(result.skipped_stages, vec!["notify", "reindex"]);
+}
+
+#[tokio::test]
+async fn test_parallel_deploy_rejects_invalid_graphs() {
+    use crate::executor::{Playbook, Task};
+    use crate::manager::DeploymentStage;
+
+    let stage = |name: &str, deps: &[&str]| {
+        DeploymentStage::new(name, &[], Playbook::new(name).add_task(Task::local_command(name, "true"))).depends_on(deps)
+    };
+    let manager = AnsibleManager::new();
+
+    let cyclic = vec![stage("a", &["c"]), stage("b", &["a"]), stage("c", &["b"]), stage("d", &[])];
+    match manager.parallel_deploy(cyclic).await {
+        Err(crate::error::AnsibleError::ValidationError(msg)) => assert!(msg.contains("a, b, c"), "{}", msg),
+        other => panic!("expected cycle error, got {:?}", other.map(|r| r.success)),
+    }
+    assert!(manager.parallel_deploy(vec![stage("a", &["missing"])]).await.is_err());
+    assert!(manager.parallel_deploy(vec![stage("a", &[]), stage("a", &[])]).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_playbook_over_local_connection() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::types::{TemplateOptions, TransportKind};
+
+    let dir = crate::utils::generate_local_temp_path("rs_ansible_local_conn");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(format!("{}/app.conf", dir), b"port = 80\n").unwrap();
+    std::fs::write(format!("{}/motd.j2", dir), b"welcome to {{ site }}\n").unwrap();
+
+    // inventory 中的 `connection: local` 映射为本地传输
+    let config: HostConfig = serde_yaml::from_str("hostname: controller\nport: 22\nusername: deploy\nconnection: local\n").unwrap();
+    assert_eq!(config.transport, TransportKind::Local);
+    let manager = AnsibleManager::new();
+    manager.add_host("controller".to_string(), config);
+
+    let dest = format!("{}/deployed.conf", dir);
+    let mut template = TemplateOptions {
+        src: format!("{}/motd.j2", dir),
+        dest: format!("{}/motd", dir),
+        ..Default::default()
+    };
+    template.variables.insert("site".to_string(), serde_json::json!("prod"));
+    let playbook = Playbook::new("local")
+        .add_task(Task::command("echo", "printf '%s' \"$0\"; echo oops >&2; exit 3").ignore_errors())
+        .add_task(Task::copy_file("copy", &format!("{}/app.conf", dir), &dest))
+        .add_task(Task::copy_file("copy again", &format!("{}/app.conf", dir), &dest))
+        .add_task(Task::template("motd", template));
+
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(result.overall_success);
+
+    let TaskResult::Command(ref batch) = result.task_results[0].1 else { panic!("unexpected task result type") };
+    let output = batch.results["controller"].value().unwrap();
+    assert_eq!((output.exit_code, output.stdout.as_str(), output.stderr.as_str()), (3, "sh", "oops\n"));
+
+    let copied = |index: usize| match result.task_results[index].1 {
+        TaskResult::CopyFile(ref batch) => batch.results["controller"].value().unwrap().bytes_transferred,
+        _ => panic!("unexpected task result type"),
+    };
+    assert_eq!(copied(1), 10);
+    // 第二次复制命中 hash 幂等检查
+    assert_eq!(copied(2), 0);
+    assert_eq!(std::fs::read_to_string(&dest).unwrap(), "port = 80\n");
+    assert_eq!(std::fs::read_to_string(format!("{}/motd", dir)).unwrap(), "welcome to prod\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// 使用 mock 传输层的管理器，主机配置均为默认值
+fn mock_manager(mock: &crate::testing::MockTransport, hosts: &[&str]) -> AnsibleManager {
+    let manager = AnsibleManager::new().with_transport_factory(mock.factory());
+    for host in hosts {
+        manager.add_host(host.to_string(), HostConfig::default());
+    }
+    manager
+}
+
+#[tokio::test]
+async fn test_executor_tracks_failed_hosts_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_connect("db1", "connection refused")
+        .fail_command("web2", "migrate", "connection reset by peer");
+    let manager = mock_manager(&mock, &["web1", "web2", "db1"]);
+
+    let playbook = Playbook::new("deploy")
+        .add_task(Task::command("migrate", "app migrate"))
+        .add_task(Task::command("restart", "systemctl restart app"));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+
+    let mut failed: Vec<&String> = result.failed_hosts.iter().collect();
+    failed.sort();
+    assert_eq!(failed, vec!["db1", "web2"]);
+    // 部分主机失败不影响其余主机继续执行，但默认要求所有主机成功，整体视为失败
+    assert!(!result.overall_success);
+    assert_eq!(mock.commands("web1"), vec!["app migrate", "systemctl restart app"]);
+    assert_eq!(mock.commands("web2"), vec!["app migrate"]);
+    assert!(mock.commands("db1").is_empty());
+
+    let (_, restart) = &result.task_results[1];
+    // 之前失败的主机不再出现在后续任务的结果中
+    assert_eq!(restart.successful_hosts(), &vec!["web1".to_string()]);
+    assert!(restart.failed_hosts().is_empty());
+
+    // 全部主机失败时停止执行后续任务
+    let mock = MockTransport::new();
+    mock.fail_command(crate::testing::ANY_HOST, "migrate", "timeout");
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(!result.overall_success);
+    assert_eq!(result.task_results.len(), 1);
+    assert_eq!(mock.commands("web1"), vec!["app migrate"]);
+}
+
+#[tokio::test]
+async fn test_commands_task_stops_on_failure_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::{MockTransport, ANY_HOST};
+
+    let mock = MockTransport::new();
+    mock.on_command(ANY_HOST, "uptime", CommandResult { exit_code: 0, stdout: "up 3 days\n".to_string(), stderr: String::new(), raw_stdout: None })
+        .on_command("web2", "df", CommandResult { exit_code: 1, stdout: String::new(), stderr: "df: /data: No such file\n".to_string(), raw_stdout: None });
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let playbook = Playbook::new("diagnose")
+        .add_task(Task::commands("diagnose", &["uptime", "df -h /data", "free -m"]).stop_on_failure());
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    // 非零退出码不使主机失败，每条命令的结果都被保留
+    assert!(result.overall_success);
+    let TaskResult::Commands(ref batch) = result.task_results[0].1 else { panic!("unexpected task result type") };
+
+    let web1 = batch.value("web1").unwrap();
+    assert_eq!(web1.iter().map(|r| r.exit_code).collect::<Vec<_>>(), vec![0, 0, 0]);
+    assert_eq!(web1[0].stdout, "up 3 days\n");
+    let web2 = batch.value("web2").unwrap();
+    assert_eq!(web2.len(), 2);
+    assert_eq!(web2[1].exit_code, 1);
+    assert_eq!(mock.commands("web2"), vec!["uptime", "df -h /data"]);
+    assert_eq!(mock.commands("web1"), vec!["uptime", "df -h /data", "free -m"]);
+}
+
+#[tokio::test]
+async fn test_success_threshold_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_command("web3", "migrate", "connection reset by peer");
+    let manager = mock_manager(&mock, &["web1", "web2", "web3", "web4"]);
+
+    // 3/4 主机成功，达到 playbook 的阈值
+    let playbook = Playbook::new("deploy")
+        .success_threshold(0.75)
+        .add_task(Task::command("migrate", "app migrate"))
+        .add_task(Task::command("restart", "systemctl restart app"));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(result.overall_success);
+    assert_eq!(result.task_results.len(), 2);
+
+    // 任务级设置覆盖 playbook 的阈值
+    let playbook = Playbook::new("deploy")
+        .success_threshold(0.5)
+        .add_task(Task::command("migrate", "app migrate").require_all());
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(!result.overall_success);
+
+    // 没有目标主机的任务不使 playbook 失败
+    let playbook = Playbook::new("noop").add_task(Task::command("nothing", "true").on_hosts(vec![]));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(result.overall_success);
+
+    let playbook = Playbook::new("deploy").add_task(Task::command("migrate", "app migrate").success_threshold(1.5));
+    assert!(matches!(
+        TaskExecutor::new(&manager).execute_playbook(&playbook).await,
+        Err(crate::error::AnsibleError::ValidationError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_executor_ignore_errors_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::testing::{MockTransport, ANY_HOST};
+
+    let mock = MockTransport::new();
+    mock.fail_command(ANY_HOST, "optional-hook", "hook crashed");
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let playbook = Playbook::new("deploy")
+        .add_task(Task::command("hook", "optional-hook").ignore_errors())
+        .add_task(Task::command("deploy", "deploy-app"));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+
+    assert!(result.overall_success);
+    assert!(result.failed_hosts.is_empty());
+    assert_eq!(result.task_results[0].1.failed_hosts().len(), 2);
+    for host in ["web1", "web2"] {
+        assert_eq!(mock.commands(host), vec!["optional-hook", "deploy-app"]);
+    }
+}
+
+#[tokio::test]
+async fn test_health_check_task_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::{MockTransport, ANY_HOST};
+    use crate::types::HealthProbe;
+
+    let mock = MockTransport::new();
+    mock.on_command(
+        ANY_HOST,
+        "curl -s localhost:8080/health",
+        CommandResult { exit_code: 0, stdout: "{\"status\":\"ok\"}".to_string(), stderr: String::new(), raw_stdout: None },
+    );
+    mock.on_command(
+        "web2",
+        "curl -s localhost:8080/health",
+        CommandResult { exit_code: 0, stdout: "{\"status\":\"degraded\"}".to_string(), stderr: String::new(), raw_stdout: None },
+    );
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let probes = vec![
+        HealthProbe {
+            name: "api".to_string(),
+            command: "curl -s localhost:8080/health".to_string(),
+            expected_exit_code: 0,
+            expected_stdout_contains: Some("\"ok\"".to_string()),
+            timeout: std::time::Duration::from_secs(5),
+        },
+        HealthProbe {
+            name: "disk".to_string(),
+            command: "test -w /var/lib/app".to_string(),
+            expected_exit_code: 0,
+            expected_stdout_contains: None,
+            timeout: std::time::Duration::from_secs(5),
+        },
+    ];
+
+    let direct = manager.run_health_probes_on_hosts(&probes, &["web2".to_string()]).await;
+    let web2 = direct.results["web2"].value().unwrap();
+    assert!(!web2[0].passed);
+    assert!(web2[1].passed);
+
+    let playbook = Playbook::new("verify").add_task(Task::health_check("probe", probes));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert_eq!(result.failed_hosts.iter().collect::<Vec<_>>(), vec!["web2"]);
+    match &result.task_results[0].1 {
+        TaskResult::HealthCheck(batch) => {
+            assert_eq!(batch.results["web1"].value().unwrap().len(), 2);
+            let error = batch.results["web2"].error().unwrap().to_string();
+            assert!(error.contains("api"), "{}", error);
+        }
+        other => panic!("unexpected result: {:?}", other.failed_hosts()),
+    }
+}
+
+#[tokio::test]
+async fn test_playbook_start_at_task_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    let manager = mock_manager(&mock, &["web1"]);
+    let playbook = Playbook::new("deploy")
+        .add_task(Task::command("build", "make build"))
+        .add_task(Task::command("migrate", "migrate-db"))
+        .add_task(Task::command("deploy", "deploy-app"));
+    let executor = TaskExecutor::new(&manager);
+
+    let result = executor.execute_playbook_from_task(&playbook, "migrate").await.unwrap();
+    assert!(result.overall_success);
+    assert_eq!(mock.commands("web1"), vec!["migrate-db", "deploy-app"]);
+    let executed: Vec<&str> = result.task_results.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(executed, vec!["migrate", "deploy"]);
+    // 因恢复而跳过的任务与按主机跳过的任务分开记录
+    assert_eq!(result.resume_skipped_tasks, vec!["build"]);
+    assert!(result.skips().is_empty());
+
+    let result = executor.execute_playbook_from_index(&playbook, 2).await.unwrap();
+    assert_eq!(result.resume_skipped_tasks, vec!["build", "migrate"]);
+    assert_eq!(result.task_results.len(), 1);
+
+    assert!(executor.execute_playbook(&playbook).await.unwrap().resume_skipped_tasks.is_empty());
+    assert!(executor.execute_playbook_from_task(&playbook, "rollback").await.is_err());
+    assert!(executor.execute_playbook_from_index(&playbook, 3).await.is_err());
+}
+
+#[tokio::test]
+async fn test_playbook_step_mode_with_mock_transport() {
+    use crate::executor::{Playbook, StepAction, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    let manager = mock_manager(&mock, &["web1"]);
+    let playbook = Playbook::new("deploy")
+        .add_task(Task::command("build", "make build"))
+        .add_task(Task::command("migrate", "migrate-db"))
+        .add_task(Task::command("deploy", "deploy-app"));
+    let executor = TaskExecutor::new(&manager);
+
+    let mut asked = Vec::new();
+    let result = executor
+        .execute_playbook_step(&playbook, |task| {
+            asked.push(task.name.clone());
+            if task.name == "migrate" { StepAction::Skip } else { StepAction::Run }
+        })
+        .await
+        .unwrap();
+    assert_eq!(asked, vec!["build", "migrate", "deploy"]);
+    assert_eq!(mock.commands("web1"), vec!["make build", "deploy-app"]);
+    assert_eq!(result.step_skipped_tasks, vec!["migrate"]);
+    assert!(result.overall_success && result.aborted_at.is_none());
+
+    // 中止时保留已执行任务的结果，之后的任务不再询问
+    let mut asked = 0;
+    let result = executor
+        .execute_playbook_step(&playbook, |task| {
+            asked += 1;
+            if task.name == "migrate" { StepAction::Abort } else { StepAction::Run }
+        })
+        .await
+        .unwrap();
+    assert_eq!(asked, 2);
+    assert_eq!(result.task_results.len(), 1);
+    assert_eq!(result.aborted_at.as_deref(), Some("migrate"));
+    assert!(!result.overall_success);
+    assert_eq!(mock.commands("web1"), vec!["make build", "deploy-app", "make build"]);
+}
+
+#[tokio::test]
+async fn test_playbook_limit_and_retry_with_mock_transport() {
+    use crate::executor::{Playbook, RunOptions, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_command("web3", "deploy-app", "connection reset by peer")
+        .fail_command("web1", "deploy-app", "disk full");
+    let manager = mock_manager(&mock, &["web1", "web2", "web3", "db1"]);
+
+    let playbook = Playbook::new("deploy")
+        .add_task(Task::command("migrate", "migrate-db").on_hosts(vec!["db1".to_string()]))
+        .add_task(Task::command("deploy", "deploy-app"));
+    let executor = TaskExecutor::new(&manager)
+        .with_groups(std::collections::HashMap::from([(
+            "web".to_string(),
+            vec!["web1".to_string(), "web2".to_string(), "web3".to_string()],
+        )]))
+        .with_run_options(RunOptions {
+            limit: Some("web".to_string()),
+            exclude: Some(vec!["web2".to_string()]),
+            diff: false,
+        });
+    let result = executor.execute_playbook(&playbook).await.unwrap();
+
+    // db1 不在 limit 内，只针对 db1 的任务整体跳过；web2 被排除
+    assert_eq!(result.task_results.len(), 1);
+    assert!(mock.commands("db1").is_empty());
+    assert!(mock.commands("web2").is_empty());
+    assert_eq!(result.limit.as_deref(), Some("web,!web2"));
+    assert_eq!(result.failed_hosts_pattern().as_deref(), Some("web1,web3"));
+    // 被 limit 排除的主机记录为跳过，与失败的主机区分
+    let skips = result.skips();
+    assert_eq!(
+        skips["db1"],
+        vec![("migrate".to_string(), SkipReason::Limit), ("deploy".to_string(), SkipReason::Limit)]
+    );
+    assert_eq!(skips["web2"], vec![("deploy".to_string(), SkipReason::Limit)]);
+    assert_eq!(result.skipped_hosts, ["db1".to_string(), "web2".to_string()].into());
+
+    let retry_path = std::env::temp_dir().join(format!("rs_ansible_{}.retry", rand::random::<u32>()));
+    result.write_retry_file(&retry_path).unwrap();
+    assert_eq!(std::fs::read_to_string(&retry_path).unwrap(), "web1\nweb3\n");
+    let _ = std::fs::remove_file(&retry_path);
+
+    // 用失败主机模式重跑
+    let retry = TaskExecutor::new(&manager).with_run_options(RunOptions {
+        limit: result.failed_hosts_pattern(),
+        exclude: None,
+        diff: false,
+    });
+    let mut targets = retry.target_hosts(&playbook.tasks[1]).unwrap();
+    targets.sort();
+    assert_eq!(targets, vec!["web1", "web3"]);
+
+    let bad_limit = TaskExecutor::new(&manager).with_run_options(RunOptions {
+        limit: Some("cache*".to_string()),
+        exclude: None,
+        diff: false,
+    });
+    assert!(bad_limit.execute_playbook(&playbook).await.is_err());
+}
+
+#[tokio::test]
+async fn test_deploy_and_run_with_mock_transport() {
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_command("web2", "sha256sum", "connection reset by peer");
+    mock.on_command(
+        "web1",
+        "/opt/bin/tool --version",
+        CommandResult { exit_code: 0, stdout: "tool 1.2.0\n".to_string(), stderr: String::new(), raw_stdout: None },
+    );
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let local = std::env::temp_dir().join(format!("rs_ansible_tool_{}", rand::random::<u32>()));
+    std::fs::write(&local, b"#!/bin/sh\necho tool 1.2.0\n").unwrap();
+    let hosts = vec!["web1".to_string(), "web2".to_string()];
+    let result = manager
+        .deploy_and_run(
+            local.to_str().unwrap(),
+            "/opt/bin/tool",
+            &FileCopyOptions::default(),
+            "/opt/bin/tool --version",
+            &hosts,
+        )
+        .await;
+    let _ = std::fs::remove_file(&local);
+
+    let web1 = result.batch.results["web1"].value().unwrap();
+    assert!(web1.transfer.success);
+    assert_eq!(web1.command.stdout, "tool 1.2.0\n");
+    assert_eq!(mock.file("web1", "/opt/bin/tool").unwrap(), b"#!/bin/sh\necho tool 1.2.0\n");
+
+    // web2 传输校验失败，不执行命令
+    assert_eq!(result.transfer_failed, vec!["web2"]);
+    assert_eq!(result.batch.failed, vec!["web2"]);
+    assert!(!mock.commands("web2").iter().any(|c| c.contains("--version")));
+}
+
+#[tokio::test]
+async fn test_unreachable_hosts_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::manager::TransportFactory;
+    use crate::testing::MockTransport;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let mock = MockTransport::new();
+    mock.fail_connect("db1", "connection refused");
+    mock.fail_command("web1", "migrate", "exit status 1");
+
+    // web2 第一次连接超时，之后恢复
+    let inner = mock.factory();
+    let web2_attempts = Arc::new(AtomicUsize::new(0));
+    let attempts = web2_attempts.clone();
+    let factory: TransportFactory = Arc::new(move |host: &str, config: &HostConfig| {
+        if host == "web2" && attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Err(crate::error::AnsibleError::SshConnectionError("connection timed out".to_string()));
+        }
+        inner(host, config)
+    });
+    let manager = AnsibleManager::new().with_transport_factory(factory);
+    for host in ["web1", "web2", "db1"] {
+        manager.add_host(host.to_string(), HostConfig::default());
+    }
+
+    let playbook = Playbook::new("deploy")
+        .add_task(Task::command("check", "uptime").ignore_unreachable())
+        .add_task(Task::command("migrate", "migrate").ignore_errors())
+        .add_task(Task::command("restart", "systemctl restart app"));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+
+    let check = &result.task_results[0].1;
+    let mut unreachable = check.unreachable_hosts().clone();
+    unreachable.sort();
+    assert_eq!(unreachable, vec!["db1", "web2"]);
+
+    // ignore_unreachable 让 web2 与 db1 继续参与后续任务；web2 恢复后移出不可达集合，
+    // db1 在不忽略不可达的任务中被移出，ignore_errors 不覆盖不可达
+    assert_eq!(result.unreachable_hosts.iter().collect::<Vec<_>>(), vec!["db1"]);
+    assert_eq!(result.failed_hosts.iter().collect::<Vec<_>>(), vec!["db1"]);
+    assert_eq!(result.task_results[1].1.failed_hosts().len(), 2);
+    assert!(result.task_results[1].1.unreachable_hosts().contains(&"db1".to_string()));
+    assert_eq!(mock.commands("web2"), vec!["migrate", "systemctl restart app"]);
+    assert!(mock.commands("db1").is_empty());
+}
+
+#[tokio::test]
+async fn test_shell_task_flow_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    // web2 上 creates 标记文件已存在，跳过执行
+    mock.on_command(
+        "web2",
+        "/opt/app/.installed",
+        CommandResult { exit_code: 0, stdout: "skip\n".to_string(), stderr: String::new(), raw_stdout: None },
+    );
+    mock.on_command(
+        "web1",
+        "chmod +x",
+        CommandResult { exit_code: 0, stdout: "installed\n".to_string(), stderr: String::new(), raw_stdout: None },
+    );
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let task = Task::shell_script("install", "#!/bin/sh\r\necho installed\r\n")
+        .creates("/opt/app/.installed")
+        .chdir("/opt/app");
+    let result = TaskExecutor::new(&manager)
+        .execute_playbook(&Playbook::new("install").add_task(task))
+        .await
+        .unwrap();
+    assert!(result.overall_success);
+
+    let TaskResult::Command(ref batch) = result.task_results[0].1 else { panic!("unexpected task result type") };
+    assert_eq!(batch.skipped, vec!["web2"]);
+    assert_eq!(batch.results["web1"].value().unwrap().stdout, "installed\n");
+
+    // 脚本以 Unix 换行上传，在 chdir 目录中执行，结束后删除
+    let commands = mock.commands("web1");
+    let exec = commands.iter().find(|c| c.starts_with("chmod +x")).unwrap();
+    let quoted_script = exec.split_whitespace().nth(2).unwrap();
+    assert!(exec.contains("cd '/opt/app'"));
+    assert_eq!(commands.last().unwrap(), &format!("rm -f {}", quoted_script));
+    assert!(mock.file("web1", quoted_script.trim_matches('\'')).is_none());
+    assert!(!mock.commands("web2").iter().any(|c| c.starts_with("chmod +x")));
+}
+
+#[tokio::test]
+async fn test_shell_task_removes_remote_script_when_exec_fails() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_command("*", "chmod +x", "connection reset");
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let executor = TaskExecutor::new(&manager);
+    let install = Task::shell_script("install", "echo ok\n").on_hosts(vec!["web1".to_string()]);
+    let result = executor.execute_playbook(&Playbook::new("install").add_task(install)).await.unwrap();
+    assert_eq!(result.task_results[0].1.failed_hosts(), &vec!["web1".to_string()]);
+    let debug = Task::shell_script("debug", "echo ok\n")
+        .keep_temp_on_failure(true)
+        .on_hosts(vec!["web2".to_string()]);
+    let result = executor.execute_playbook(&Playbook::new("debug").add_task(debug)).await.unwrap();
+    assert_eq!(result.task_results[0].1.failed_hosts(), &vec!["web2".to_string()]);
+
+    // 执行失败后远程脚本被删除
+    let commands = mock.commands("web1");
+    let exec = commands.iter().find(|c| c.starts_with("chmod +x")).unwrap();
+    let quoted_script = exec.split_whitespace().nth(2).unwrap();
+    assert_eq!(commands.last().unwrap(), &format!("rm -f {}", quoted_script));
+    assert!(mock.file("web1", quoted_script.trim_matches('\'')).is_none());
+
+    // 开启 keep_temp_on_failure 时保留远程脚本
+    let commands = mock.commands("web2");
+    let exec = commands.iter().find(|c| c.starts_with("chmod +x")).unwrap();
+    let quoted_script = exec.split_whitespace().nth(2).unwrap();
+    assert!(!commands.iter().any(|c| c.starts_with("rm -f")));
+    assert_eq!(mock.file("web2", quoted_script.trim_matches('\'')).unwrap(), b"echo ok\n");
+}
+
+#[tokio::test]
+async fn test_shell_task_script_copy_failure_is_per_host() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_command("web2", "mv '", "read-only file system");
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let result = TaskExecutor::new(&manager)
+        .execute_playbook(&Playbook::new("install").add_task(Task::shell_script("install", "echo ok\n")))
+        .await
+        .unwrap();
+
+    // 复制失败的主机记为失败，其余主机照常执行
+    let TaskResult::Command(ref batch) = result.task_results[0].1 else { panic!("unexpected task result type") };
+    assert_eq!(batch.successful, vec!["web1"]);
+    assert_eq!(batch.failed, vec!["web2"]);
+    assert!(batch.error("web2").unwrap().to_string().contains("Failed to copy script"));
+    assert!(mock.commands("web1").iter().any(|c| c.starts_with("chmod +x '")));
+    assert!(!mock.commands("web2").iter().any(|c| c.starts_with("chmod +x")));
+}
+
+/// 需要本机 docker 守护进程：`cargo test --features docker-tests`
+///
+/// 镜像可通过 `RS_ANSIBLE_TEST_IMAGE` 覆盖，默认使用 debian:bookworm-slim（自带 useradd）。
+#[cfg(feature = "docker-tests")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_playbook_in_docker_container() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::types::{DockerConnection, TemplateOptions, TransportKind, UserOptions};
+    use std::process::Command;
+
+    let image = std::env::var("RS_ANSIBLE_TEST_IMAGE").unwrap_or_else(|_| "debian:bookworm-slim".to_string());
+    let name = format!("rs_ansible_test_{}", std::process::id());
+    let started = Command::new("docker")
+        .args(["run", "-d", "--rm", "--name", &name, &image, "sleep", "300"])
+        .status()
+        .unwrap();
+    assert!(started.success(), "failed to start test container");
+
+    let dir = crate::utils::generate_local_temp_path("rs_ansible_docker_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(format!("{}/app.conf", dir), b"port = 80\n").unwrap();
+    std::fs::write(format!("{}/motd.j2", dir), b"welcome to {{ site }}\n").unwrap();
+
+    let manager = AnsibleManager::new();
+    manager.add_host(
+        "container".to_string(),
+        HostConfig {
+            hostname: name.clone(),
+            transport: TransportKind::Docker,
+            docker: Some(DockerConnection { container: name.clone(), ..Default::default() }),
+            ..Default::default()
+        },
+    );
+    let mut template = TemplateOptions {
+        src: format!("{}/motd.j2", dir),
+        dest: "/etc/motd".to_string(),
+        ..Default::default()
+    };
+    template.variables.insert("site".to_string(), serde_json::json!("prod"));
+    let user = UserOptions { name: "deploy".to_string(), ..Default::default() };
+    let playbook = Playbook::new("container")
+        .add_task(Task::command("read", "cat /etc/os-release"))
+        .add_task(Task::copy_file("copy", &format!("{}/app.conf", dir), "/opt/app/app.conf"))
+        .add_task(Task::template("motd", template))
+        .add_task(Task::user("user", user))
+        .add_task(Task::system_info("facts"));
+
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await;
+    let cat = |path: &str| {
+        Command::new("docker").args(["exec", &name, "cat", path]).output().map(|o| o.stdout).unwrap_or_default()
+    };
+    let (app_conf, motd) = (cat("/opt/app/app.conf"), cat("/etc/motd"));
+    let passwd = String::from_utf8(cat("/etc/passwd")).unwrap();
+    let _ = Command::new("docker").args(["rm", "-f", &name]).status();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let result = result.unwrap();
+    assert!(result.overall_success, "failed hosts: {:?}", result.failed_hosts);
+    assert!(result.failed_hosts.is_empty());
+    assert_eq!(app_conf, b"port = 80\n");
+    assert_eq!(motd, b"welcome to prod\n");
+    assert!(passwd.lines().any(|l| l.starts_with("deploy:")));
+    let TaskResult::SystemInfo(ref facts) = result.task_results[4].1 else { panic!("unexpected task result type") };
+    assert!(!facts.results["container"].value().unwrap().hostname.is_empty());
+}
+
+#[tokio::test]
+async fn test_russh_transport_selection() {
+    use crate::ssh::SshClient;
+    use crate::types::TransportKind;
+
+    let config: HostConfig = serde_yaml::from_str("hostname: web1\nport: 22\nusername: deploy\nconnection: russh\n").unwrap();
+    assert_eq!(config.transport, TransportKind::Russh);
+    assert!(config.uses_ssh() && config.transport.is_async());
+
+    // 同步客户端在连接前拒绝 russh 主机，不会进入重试
+    let err = SshClient::new(config.clone()).err().unwrap();
+    assert!(matches!(err, crate::error::AnsibleError::ValidationError(_)), "{}", err);
+
+    // 没有异步版本的批量操作对 russh 主机同样快速失败
+    let manager = AnsibleManager::new();
+    manager.add_host("web1".to_string(), config);
+    let started = std::time::Instant::now();
+    let result = manager.get_system_info_all().await;
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    let err = result.results["web1"].error().unwrap();
+    assert!(err.to_string().contains("russh"), "{}", err);
+}
+
+/// russh 主机的批量操作在主机任务中直接 await，不占用阻塞线程
+#[test]
+fn test_async_transport_hosts_do_not_use_blocking_threads() {
+    use crate::testing::MockTransport;
+    use crate::types::TransportKind;
+    use std::time::{Duration, Instant};
+
+    // 只有一个阻塞线程：若操作经 spawn_blocking 执行，200 台主机需要串行 20 秒以上
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .max_blocking_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+    let mock = MockTransport::new();
+    let manager = AnsibleManager::new()
+        .with_max_concurrent_connections(200)
+        .with_async_transport_factory(mock.async_factory());
+    let hosts: Vec<String> = (0..200).map(|i| format!("web{}", i)).collect();
+    for host in &hosts {
+        mock.with_latency(host, Duration::from_millis(100));
+        let config = HostConfig { hostname: host.clone(), transport: TransportKind::Russh, ..Default::default() };
+        manager.add_host(host.clone(), config);
+    }
+
+    let started = Instant::now();
+    let result = runtime.block_on(manager.ping_hosts(&hosts));
+    assert_eq!(result.successful.len(), 200, "{:?}", result.failed);
+    assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+    assert_eq!(mock.commands("web0"), vec!["echo 'pong'"]);
+}
+
+/// 文件复制、脚本执行与拉取在 ssh2 与 russh 主机上的远程操作一致
+#[tokio::test(flavor = "multi_thread")]
+async fn test_async_transport_operations_match_sync_client() {
+    use crate::testing::MockTransport;
+    use crate::types::TransportKind;
+
+    let mock = MockTransport::new();
+    let manager = AnsibleManager::new()
+        .with_transport_factory(mock.factory())
+        .with_async_transport_factory(mock.async_factory());
+    for (name, transport) in [("sync", TransportKind::Ssh2), ("async", TransportKind::Russh)] {
+        let config = HostConfig { hostname: name.to_string(), transport, ..Default::default() };
+        manager.add_host(name.to_string(), config);
+    }
+    let hosts = vec!["sync".to_string(), "async".to_string()];
+
+    let local = crate::utils::generate_local_temp_path("rs_ansible_async_parity");
+    std::fs::write(&local, b"#!/bin/sh\necho deployed\n").unwrap();
+    let copied = manager.copy_file_to_hosts(&local, "/opt/app/run.sh", &hosts).await;
+    assert_eq!(copied.successful.len(), 2, "{:?}", copied.failed);
+    assert_eq!(mock.file("async", "/opt/app/run.sh"), mock.file("sync", "/opt/app/run.sh"));
+    // 再次复制时内容未变，两种客户端都只更新属性
+    let again = manager.copy_file_to_hosts(&local, "/opt/app/run.sh", &hosts).await;
+    assert_eq!(again.results["async"].value().unwrap().message, again.results["sync"].value().unwrap().message);
+
+    let scripted = manager
+        .run_script_on_hosts(&local, "/tmp/rs_ansible_deploy.sh", &FileCopyOptions::default(), None, &hosts)
+        .await;
+    assert_eq!(scripted.successful.len(), 2, "{:?}", scripted.failed);
+
+    let fetch_dir = crate::utils::generate_local_temp_path("rs_ansible_async_fetch");
+    let fetched = manager.fetch_file_from_hosts_verified("/opt/app/run.sh", &fetch_dir, &hosts).await;
+    assert_eq!(fetched.successful.len(), 2, "{:?}", fetched.failed);
+    let fetch_dir = std::path::Path::new(&fetch_dir);
+    assert_eq!(
+        std::fs::read(fetch_dir.join("async/run.sh")).unwrap(),
+        std::fs::read(fetch_dir.join("sync/run.sh")).unwrap()
+    );
+    let _ = std::fs::remove_dir_all(fetch_dir);
+    let _ = std::fs::remove_file(&local);
+
+    // 原子写入使用随机的临时文件名，比较前统一替换
+    let normalize = |host: &str| -> Vec<String> {
+        let temp = regex::Regex::new(r"\.tmp\.[0-9.]+").unwrap();
+        mock.commands(host).iter().map(|c| temp.replace_all(c, ".tmp.<random>").into_owned()).collect()
+    };
+    assert_eq!(normalize("async"), normalize("sync"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_playbook_reports_connection_retries_to_callbacks() {
+    use crate::callback::ExecutionCallback;
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Retries(Mutex<Vec<(String, String, u32)>>);
+
+    impl ExecutionCallback for Retries {
+        fn on_host_retry(&self, host: &str, task: &Task, attempt: u32, error: &str) -> Result<(), crate::error::AnsibleError> {
+            assert!(!error.is_empty());
+            self.0.lock().unwrap().push((host.to_string(), task.name.clone(), attempt));
+            Ok(())
+        }
+    }
+
+    // 本机未监听的端口：每次连接立即被拒绝，按退避重试后失败
+    let manager = AnsibleManager::new();
+    manager.add_host(
+        "down".to_string(),
+        HostConfig {
+            hostname: "127.0.0.1".to_string(),
+            port: 1,
+            username: "deploy".to_string(),
+            password: Some("secret".to_string()),
+            ..Default::default()
+        },
+    );
+    let retries = Arc::new(Retries::default());
+    let playbook = Playbook::new("retry").add_task(Task::command("uptime", "uptime"));
+    let result = TaskExecutor::new(&manager)
+        .with_callback(retries.clone())
+        .execute_playbook(&playbook)
+        .await
+        .unwrap();
+
+    assert!(result.unreachable_hosts.contains("down"));
+    let expected: Vec<(String, String, u32)> =
+        (2..=3).map(|attempt| ("down".to_string(), "uptime".to_string(), attempt)).collect();
+    assert_eq!(*retries.0.lock().unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_spawn_playbook_status_and_cancel_with_mock_transport() {
+    use crate::executor::{Playbook, Task};
+    use crate::run_handle::{HostRunStatus, RunState};
+    use crate::testing::MockTransport;
+    use std::time::Duration;
+
+    let mock = MockTransport::new();
+    mock.with_latency("web2", Duration::from_millis(500));
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let playbook = Playbook::new("release")
+        .add_task(Task::command("prepare", "echo prepare"))
+        .add_task(Task::command("migrate", "run-migrate"));
+
+    let handle = manager.spawn_playbook(playbook);
+    let status = handle.status();
+    assert_eq!((status.state, status.tasks_total), (RunState::Running, 2));
+
+    // 状态按任务边界更新：任务执行期间其目标主机都处于 Running
+    let mut status = handle.status();
+    for _ in 0..20 {
+        if status.current_task.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        status = handle.status();
+    }
+    assert_eq!(status.current_task.as_deref(), Some("prepare"));
+    for host in ["web1", "web2"] {
+        assert_eq!(status.hosts[host], HostRunStatus::Running { task: "prepare".to_string() });
+    }
+
+    // 取消后不等待 web2 上正在执行的命令，后续任务不再执行
+    handle.cancel();
+    for _ in 0..20 {
+        if handle.is_finished() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(handle.is_finished());
+    let status = handle.status();
+    assert_eq!((status.state, status.tasks_completed, status.current_task), (RunState::Cancelled, 0, None));
+    let result = handle.wait().await;
+    assert!(matches!(result, Err(crate::error::AnsibleError::Cancelled(_))));
+    assert!(!mock.commands("web1").iter().any(|c| c.contains("run-migrate")));
+}
+
+#[tokio::test]
+async fn test_previously_failed_hosts_are_skipped_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_command("web2", "deploy", "exit status 1");
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let playbook = Playbook::new("site")
+        .add_task(Task::command("deploy", "deploy"))
+        .add_task(Task::command("restart", "systemctl restart app").on_hosts(vec!["web2".to_string()]))
+        .add_task(Task::copy_file("config", "/etc/hosts", "/tmp/hosts").on_hosts(vec!["web2".to_string()]));
+
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    let restart = &result.task_results[1].1;
+    assert!(restart.failed_hosts().is_empty());
+    assert_eq!(restart.skipped_hosts(), &vec!["web2".to_string()]);
+    // 全部主机被跳过时仍返回任务自身的结果类型
+    let TaskResult::Command(ref batch) = *restart else { panic!("unexpected task result type") };
+    assert!(matches!(batch.results["web2"], HostOutcome::Skipped(SkipReason::PreviousFailure)));
+    let TaskResult::CopyFile(ref copy) = result.task_results[2].1 else { panic!("unexpected task result type") };
+    assert!(matches!(copy.results["web2"], HostOutcome::Skipped(SkipReason::PreviousFailure)));
+    assert!(!mock.commands("web2").iter().any(|c| c.contains("restart")));
+
+    // 因之前失败而跳过的主机只算作失败
+    assert_eq!(
+        result.skips()["web2"],
+        vec![
+            ("restart".to_string(), SkipReason::PreviousFailure),
+            ("config".to_string(), SkipReason::PreviousFailure),
+        ]
+    );
+    assert!(result.skipped_hosts.is_empty());
+    assert!(result.failed_hosts.contains("web2"));
+}
+
+#[tokio::test]
+async fn test_template_render_is_validated_on_all_hosts_before_deploying() {
+    use crate::testing::MockTransport;
+
+    let template_path = crate::utils::generate_local_temp_path("rs_ansible_preflight_template");
+    std::fs::write(&template_path, "server_name {{ ansible_facts.hostname }};\n").unwrap();
+    let options = TemplateOptions {
+        src: template_path.clone(),
+        dest: "/etc/nginx/conf.d/site.conf".to_string(),
+        ..Default::default()
+    };
+    // 只有 web1 有 facts，web2 渲染时缺少 ansible_facts 变量
+    let facts = std::collections::HashMap::from([(
+        "web1".to_string(),
+        SystemInfo { hostname: "web1.example.com".to_string(), ..Default::default() },
+    )]);
+    let hosts = vec!["web1".to_string(), "web2".to_string()];
+
+    let mock = MockTransport::new();
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let failures = manager.check_template_render(&options, &hosts, Some(&facts));
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, "web2");
+
+    let result = manager.deploy_template_to_hosts_with_facts(&options, &hosts, &facts).await;
+    assert!(result.successful.is_empty());
+    assert_eq!(result.failed.len(), 2);
+    assert!(result.error("web2").unwrap().to_string().contains("Failed to render template"));
+    assert!(result.error("web1").unwrap().to_string().contains("rendering failed on web2"));
+    assert!(mock.commands("web1").is_empty());
+    assert!(mock.commands("web2").is_empty());
+
+    // 关闭预渲染后，可以渲染的主机照常部署
+    let options = TemplateOptions { validate_render_first: false, ..options };
+    let result = manager.deploy_template_to_hosts_with_facts(&options, &hosts, &facts).await;
+    assert_eq!(result.failed, vec!["web2".to_string()]);
+    assert!(!mock.commands("web1").is_empty());
+
+    std::fs::remove_file(&template_path).unwrap();
+}
+
+#[tokio::test]
+async fn test_host_metrics_are_collected_when_enabled() {
+    use crate::concurrency::OperationOptions;
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_connect("db1", "connection refused");
+    let manager = mock_manager(&mock, &["web1", "db1"]);
+    let hosts = vec!["web1".to_string(), "db1".to_string()];
+
+    // 默认不收集
+    let result = manager.execute_command_on_hosts("uptime", &hosts).await;
+    assert!(result.metrics.is_empty());
+
+    let manager = manager.override_operation_options(OperationOptions::new().host_metrics(true));
+    let before = mock.commands("web1").len();
+    let local = std::env::temp_dir().join(format!("rs_ansible_metrics_{}", rand::random::<u32>()));
+    std::fs::write(&local, b"0123456789").unwrap();
+    let result = manager.copy_file_to_hosts(local.to_str().unwrap(), "/opt/app/data", &hosts).await;
+    let _ = std::fs::remove_file(&local);
+
+    assert_eq!(result.successful, vec!["web1"]);
+    let web1 = &result.metrics["web1"];
+    assert_eq!(web1.bytes_transferred, 10);
+    assert_eq!(web1.commands, (mock.commands("web1").len() - before) as u64);
+    assert_eq!(web1.channels_opened, web1.commands + 1);
+    // 连接失败的主机没有统计
+    assert!(!result.metrics.contains_key("db1"));
+}
+
+#[test]
+fn test_fileset_rolls_back_on_partial_failure() {
+    use crate::ssh::SshClient;
+
+    let dir = crate::utils::generate_local_temp_path("rs_ansible_fileset");
+    std::fs::create_dir_all(&dir).unwrap();
+    let planned = |name: &str, content: &str, dest: &str| {
+        let src = format!("{}/{}", dir, name);
+        std::fs::write(&src, content).unwrap();
+        PlannedFile { src, dest: dest.to_string(), mode: Some("600".to_string()), ..Default::default() }
+    };
+    let files = vec![
+        planned("app.crt", "new cert", "/etc/ssl/app.crt"),
+        planned("app.key", "new key", "/etc/ssl/private/app.key"),
+        PlannedFile { validate: Some("validate {{ path }}".to_string()), ..planned("app.conf", "new conf", "/etc/nginx/app.conf") },
+    ];
+    let remote = |fs: &std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, Vec<u8>>>>| {
+        let files = fs.lock().unwrap().clone();
+        let mut entries: Vec<(String, String)> =
+            files.into_iter().map(|(k, v)| (k, String::from_utf8(v).unwrap())).collect();
+        entries.sort();
+        entries
+    };
+    let old_state = || {
+        let transport = FakeFsTransport::default();
+        let mut files = transport.files.lock().unwrap();
+        files.insert("/etc/ssl/app.crt".to_string(), b"old cert".to_vec());
+        files.insert("/etc/nginx/app.conf".to_string(), b"old conf".to_vec());
+        drop(files);
+        transport
+    };
+    let old = vec![
+        ("/etc/nginx/app.conf".to_string(), "old conf".to_string()),
+        ("/etc/ssl/app.crt".to_string(), "old cert".to_string()),
+    ];
+    let actions = |result: &FileSetResult| result.files.iter().map(|f| f.action).collect::<Vec<_>>();
+
+    // 第三个文件替换失败：已替换的证书恢复原内容，此前不存在的私钥被删除
+    let transport = FakeFsTransport { fail_mv_to: Some("/etc/nginx/app.conf".to_string()), ..old_state() };
+    let fs = transport.files.clone();
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    let result = client.deploy_fileset(&files).unwrap();
+    assert!(!result.success);
+    assert_eq!(actions(&result), vec![FileSetAction::RolledBack, FileSetAction::RolledBack, FileSetAction::Untouched]);
+    assert!(result.summary().contains("/etc/ssl/app.crt: rolled_back"));
+    assert_eq!(remote(&fs), old);
+
+    // 验证失败发生在替换之前，不修改任何文件
+    let transport = FakeFsTransport { fail_validate: true, ..old_state() };
+    let fs = transport.files.clone();
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    let result = client.deploy_fileset(&files).unwrap();
+    assert!(!result.success);
+    assert!(result.error.as_deref().unwrap().contains("/etc/nginx/app.conf"));
+    assert_eq!(actions(&result), vec![FileSetAction::Untouched; 3]);
+    assert_eq!(remote(&fs), old);
+
+    // 全部成功，备份与暂存文件都被清理；与本地一致的文件不被替换
+    let transport = old_state();
+    transport.files.lock().unwrap().insert("/etc/ssl/app.crt".to_string(), b"new cert".to_vec());
+    let fs = transport.files.clone();
+    let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+    let result = client.deploy_fileset(&files).unwrap();
+    assert!(result.success && result.changed);
+    assert_eq!(actions(&result), vec![FileSetAction::Unchanged, FileSetAction::Applied, FileSetAction::Applied]);
+    assert_eq!(
+        remote(&fs),
+        vec![
+            ("/etc/nginx/app.conf".to_string(), "new conf".to_string()),
+            ("/etc/ssl/app.crt".to_string(), "new cert".to_string()),
+            ("/etc/ssl/private/app.key".to_string(), "new key".to_string()),
+        ]
+    );
+
+    assert!(client.deploy_fileset(&[files[0].clone(), files[0].clone()]).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_clone_host_config_keeps_unoverridden_fields() {
+    let manager = AnsibleManager::new();
+    manager.add_host(
+        "web-template".to_string(),
+        HostConfig {
+            hostname: "10.0.0.10".to_string(),
+            port: 2222,
+            username: "deploy".to_string(),
+            private_key_data: Some("PEM".to_string()),
+            r#become: true,
+            remote_tmp: Some("/var/tmp".to_string()),
+            ..Default::default()
+        },
+    );
+
+    manager
+        .clone_host_config(
+            "web-template",
+            "web1",
+            HostConfigOverrides { hostname: Some("10.0.0.11".to_string()), ..Default::default() },
+        )
+        .unwrap();
+    let web1 = manager.get_host("web1").unwrap();
+    assert_eq!(web1.hostname, "10.0.0.11");
+    assert_eq!((web1.port, web1.username.as_str()), (2222, "deploy"));
+    assert_eq!(web1.private_key_data.as_deref(), Some("PEM"));
+    assert!(web1.r#become);
+    assert_eq!(web1.remote_tmp.as_deref(), Some("/var/tmp"));
+
+    let added = manager
+        .add_hosts_from_template(
+            "web-template",
+            vec![
+                ("web2".to_string(), HostConfigOverrides { port: Some(22), ..Default::default() }),
+                (
+                    "web3".to_string(),
+                    HostConfigOverrides { private_key_path: Some("/keys/web3".to_string()), ..Default::default() },
+                ),
+            ],
+        )
+        .unwrap();
+    assert_eq!(added, 2);
+    let web2 = manager.get_host("web2").unwrap();
+    assert_eq!((web2.hostname.as_str(), web2.port), ("10.0.0.10", 22));
+    // 覆盖私钥路径时不保留模板中的内存私钥
+    let web3 = manager.get_host("web3").unwrap();
+    assert_eq!(web3.private_key_path.as_deref(), Some("/keys/web3"));
+    assert!(web3.private_key_data.is_none());
+    assert_eq!(web3.username, "deploy");
+
+    // 模板不存在或名称冲突时不添加任何主机
+    assert!(manager.clone_host_config("missing", "web4", HostConfigOverrides::default()).is_err());
+    let conflict = vec![
+        ("web4".to_string(), HostConfigOverrides::default()),
+        ("web1".to_string(), HostConfigOverrides::default()),
+    ];
+    assert!(manager.add_hosts_from_template("web-template", conflict).is_err());
+    assert!(manager.get_host("web4").is_none());
+    assert_eq!(manager.get_host("web1").unwrap().hostname, "10.0.0.11");
+}
+
+#[tokio::test]
+async fn test_health_check_command_version_requirement_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    let output = |exit_code: i32, stdout: &str| CommandResult { exit_code, stdout: stdout.to_string(), stderr: String::new(), raw_stdout: None };
+    mock.on_command("web1", "'docker' '--version'", output(0, "Docker version 24.0.7, build afdd53b\n"))
+        .on_command("web2", "'docker' '--version'", output(0, "Docker version 19.03.15, build 99e3ed8\n"))
+        .on_command("db1", "'docker' '--version'", output(127, ""));
+    let manager = mock_manager(&mock, &["web1", "web2", "db1"]);
+
+    let playbook = Playbook::new("preflight")
+        .add_task(Task::health_check("docker", Vec::new()).require_command_version("docker", "20.10"));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    let TaskResult::HealthCheck(ref batch) = result.task_results[0].1 else { panic!("unexpected task result type") };
+    assert_eq!(batch.successful, vec!["web1"]);
+    assert_eq!(batch.results["web1"].value().unwrap()[0].name, "docker >= 20.10.0");
+    for host in ["web2", "db1"] {
+        let error = batch.error(host).unwrap().to_string();
+        assert!(error.contains("docker >= 20.10.0"), "{}", error);
+    }
+
+    // 无效的版本要求是配置错误
+    let bad = manager
+        .run_health_checks_on_hosts(
+            &[],
+            &[HostRequirement::CommandVersion { command: "git".to_string(), min_version: "latest".to_string() }],
+            &["web1".to_string()],
+        )
+        .await;
+    assert!(bad.error("web1").unwrap().to_string().contains("Invalid version"));
+}
+
+#[tokio::test]
+async fn test_gathered_facts_and_host_vars_are_available_to_templates() {
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    let output = |stdout: &str| CommandResult { exit_code: 0, stdout: stdout.to_string(), stderr: String::new(), raw_stdout: None };
+    mock.on_command("web1", "hostname", output("web1.internal\n"))
+        .on_command("web2", "hostname", output("web2.internal\n"))
+        .fail_command("db1", "hostname", "connection reset by peer");
+    let manager = AnsibleManager::new().with_transport_factory(mock.factory());
+    for (name, address, role) in [("web1", "10.0.0.11", "frontend"), ("web2", "10.0.0.12", "frontend"), ("db1", "10.0.0.21", "database")] {
+        manager.add_host(name.to_string(), HostConfigBuilder::new().hostname(address).var("role", role).build());
+    }
+
+    let template_path = crate::utils::generate_local_temp_path("rs_ansible_hosts_template");
+    std::fs::write(
+        &template_path,
+        "{% for name, host in hostvars %}{{ host.ansible_host }} {{ name }} {{ host.role }}{% if host.ansible_facts %} {{ host.ansible_facts.hostname }}{% endif %}\n{% endfor %}\
+         # {{ ansible_facts.hostname }} ({{ role }}, {{ site }})\n",
+    )
+    .unwrap();
+    let mut options = TemplateOptions {
+        src: template_path.clone(),
+        dest: "/etc/hosts".to_string(),
+        ..Default::default()
+    };
+    options.variables.insert("site".to_string(), serde_json::json!("eu-1"));
+    let hosts = vec!["web1".to_string(), "web2".to_string(), "db1".to_string()];
+    let result = manager.gather_facts_and_deploy_template(&options, FactSubset::OS, &hosts).await;
+
+    assert_eq!(result.successful.len(), 2);
+    assert!(result.error("db1").unwrap().to_string().contains("connection reset"));
+    assert!(mock.file("db1", "/etc/hosts").is_none());
+    let rendered = String::from_utf8(mock.file("web1", "/etc/hosts").unwrap()).unwrap();
+    assert_eq!(
+        rendered,
+        "10.0.0.21 db1 database\n10.0.0.11 web1 frontend web1.internal\n10.0.0.12 web2 frontend web2.internal\n\
+         # web1.internal (frontend, eu-1)\n"
+    );
+
+    // 任务变量优先于主机变量
+    options.variables.insert("role".to_string(), serde_json::json!("edge"));
+    manager.gather_facts_and_deploy_template(&options, FactSubset::OS, &hosts[..1]).await;
+    let rendered = String::from_utf8(mock.file("web1", "/etc/hosts").unwrap()).unwrap();
+    assert!(rendered.ends_with("# web1.internal (edge, eu-1)\n"), "{}", rendered);
+
+    std::fs::remove_file(&template_path).unwrap();
+}
+
+#[test]
+fn test_template_reload_failure_rolls_back() {
+    use crate::ssh::SshClient;
+
+    let template_path = crate::utils::generate_local_temp_path("rs_ansible_reload_template");
+    std::fs::write(&template_path, "worker_processes {{ workers }};\n").unwrap();
+    let mut options = TemplateOptions {
+        src: template_path.clone(),
+        dest: "/etc/nginx/nginx.conf".to_string(),
+        reload_command: Some("reload nginx".to_string()),
+        rollback_on_failure: true,
+        ..Default::default()
+    };
+    options.variables.insert("workers".to_string(), serde_json::json!(4));
+    let deploy = |transport: FakeFsTransport, options: &TemplateOptions| {
+        let fs = transport.files.clone();
+        fs.lock().unwrap().insert("/etc/nginx/nginx.conf".to_string(), b"worker_processes 2;\n".to_vec());
+        let client = SshClient::with_transport(HostConfig::default(), Box::new(transport));
+        let result = client.deploy_template(options).unwrap();
+        let files = fs.lock().unwrap().clone();
+        (result, files)
+    };
+
+    // reload 成功：保留新内容，临时备份被删除
+    let (result, files) = deploy(FakeFsTransport::default(), &options);
+    assert!(result.success && result.changed && !result.rolled_back);
+    assert_eq!(files["/etc/nginx/nginx.conf"], b"worker_processes 4;\n");
+    assert_eq!(files.len(), 1);
+
+    // reload 失败：恢复原内容
+    let (result, files) = deploy(FakeFsTransport { fail_reload: true, ..Default::default() }, &options);
+    assert!(!result.success && result.rolled_back && !result.critical && !result.changed);
+    assert_eq!(files["/etc/nginx/nginx.conf"], b"worker_processes 2;\n");
+    assert_eq!(files.len(), 1);
+
+    // 恢复也失败：标记为 critical
+    let transport = FakeFsTransport {
+        fail_reload: true,
+        fail_copy_to: Some("/etc/nginx/nginx.conf".to_string()),
+        ..Default::default()
+    };
+    let (result, files) = deploy(transport, &options);
+    assert!(!result.success && result.critical && !result.rolled_back);
+    assert!(result.message.starts_with("CRITICAL"));
+    assert_eq!(files["/etc/nginx/nginx.conf"], b"worker_processes 4;\n");
+
+    // 未开启回滚时保留新内容
+    options.rollback_on_failure = false;
+    let (result, files) = deploy(FakeFsTransport { fail_reload: true, ..Default::default() }, &options);
+    assert!(!result.success && !result.rolled_back && result.changed);
+    assert_eq!(files["/etc/nginx/nginx.conf"], b"worker_processes 4;\n");
+
+    std::fs::remove_file(&template_path).unwrap();
+}
+
+#[tokio::test]
+async fn test_template_batch_reports_rollback_and_critical_hosts() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let template_path = crate::utils::generate_local_temp_path("rs_ansible_reload_batch");
+    std::fs::write(&template_path, "worker_processes 4;\n").unwrap();
+    let options = TemplateOptions {
+        src: template_path.clone(),
+        dest: "/etc/nginx/nginx.conf".to_string(),
+        reload_command: Some("reload nginx".to_string()),
+        rollback_on_failure: true,
+        ..Default::default()
+    };
+    let failed_reload = CommandResult { exit_code: 1, stdout: String::new(), stderr: "bad config".to_string(), raw_stdout: None };
+    let mock = MockTransport::new();
+    for host in ["web1", "web2", "web3"] {
+        mock.put_file(host, "/etc/nginx/nginx.conf", b"worker_processes 2;\n");
+    }
+    mock.on_command("web2", "reload nginx", failed_reload.clone())
+        .on_command("web3", "reload nginx", failed_reload)
+        // 只有恢复命令从临时备份复制
+        .fail_command("web3", "cp -p '/etc/nginx/nginx.conf.tmp", "disk full");
+    let manager = mock_manager(&mock, &["web1", "web2", "web3"]);
+    let hosts: Vec<String> = ["web1", "web2", "web3"].iter().map(|h| h.to_string()).collect();
+
+    // reload 失败的主机返回结构化结果而不是错误
+    let result = manager.deploy_template_to_hosts(&options, &hosts).await;
+    assert_eq!(result.successful.len(), 3);
+    let web1 = result.value("web1").unwrap();
+    assert!(web1.success && web1.changed);
+    let web2 = result.value("web2").unwrap();
+    assert!(!web2.success && web2.rolled_back && !web2.critical && !web2.changed);
+    let web3 = result.value("web3").unwrap();
+    assert!(!web3.success && web3.critical && !web3.rolled_back);
+
+    // playbook 中 reload 失败的主机记为失败（mock 的 cp 不复制内容，先还原原文件）
+    for host in ["web1", "web2", "web3"] {
+        mock.put_file(host, "/etc/nginx/nginx.conf", b"worker_processes 2;\n");
+    }
+    let playbook = Playbook::new("nginx").add_task(Task::template("config", options));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    let mut failed: Vec<&String> = result.failed_hosts.iter().collect();
+    failed.sort();
+    assert_eq!(failed, vec!["web2", "web3"]);
+    let failures: std::collections::HashMap<String, String> = result.task_results[0].1.get_failures().into_iter().collect();
+    assert!(failures["web3"].contains("CRITICAL"), "{}", failures["web3"]);
+
+    std::fs::remove_file(&template_path).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_hosts_can_be_added_while_batch_runs() {
+    use crate::testing::MockTransport;
+    use std::time::Duration;
+
+    let mock = MockTransport::new();
+    let initial = ["web1", "web2", "web3", "web4"];
+    for host in initial {
+        mock.with_latency(host, Duration::from_millis(100));
+    }
+    let manager = mock_manager(&mock, &initial);
+
+    let batch = manager.execute_command_all("uptime");
+    let mutate = async {
+        for i in 0..20 {
+            manager.add_host(format!("new{}", i), HostConfig::default());
+            if i == 5 {
+                assert!(manager.remove_host("web4").is_some());
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    };
+    let (result, ()) = tokio::time::timeout(Duration::from_secs(10), async { tokio::join!(batch, mutate) })
+        .await
+        .expect("batch and host mutation deadlocked");
+
+    // 已开始的操作使用开始时的快照：新主机不参与，被移除的主机仍完成
+    let mut hosts: Vec<&String> = result.results.keys().collect();
+    hosts.sort();
+    assert_eq!(hosts, initial.iter().collect::<Vec<_>>());
+    assert_eq!(result.successful.len(), initial.len());
+    assert!(mock.commands("new0").is_empty());
+
+    assert_eq!(manager.list_hosts().len(), 23);
+    assert!(manager.get_host("web4").is_none());
+    let later = manager.execute_command_all("uptime").await;
+    assert_eq!(later.successful.len(), 23);
+}
+
+#[tokio::test]
+async fn test_disabled_hosts_are_skipped_with_mock_transport() {
+    use crate::executor::{Playbook, RunOptions, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    let manager = mock_manager(&mock, &["web1", "web2", "web3"]);
+    manager.disable_host("web2").unwrap();
+    manager.disable_host("web3").unwrap();
+    manager.enable_host("web3").unwrap();
+    assert!(manager.disable_host("web9").is_err());
+    assert_eq!(manager.list_disabled(), vec!["web2"]);
+    assert_eq!(manager.list_hosts().len(), 3);
+
+    // 批量操作把禁用的主机报告为跳过，而不是静默忽略
+    let result = manager.execute_command_all("uptime").await;
+    assert_eq!(result.successful.len(), 2);
+    assert!(matches!(result.results["web2"], HostOutcome::Skipped(SkipReason::Disabled)));
+    assert!(mock.commands("web2").is_empty());
+    assert!(manager.connect_host("web2").is_err());
+
+    let playbook = Playbook::new("deploy").add_task(Task::command("deploy", "deploy-app"));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(result.overall_success);
+    assert_eq!(result.skips()["web2"], vec![("deploy".to_string(), SkipReason::Disabled)]);
+    assert!(result.skipped_hosts.contains("web2"));
+    assert!(mock.commands("web2").is_empty());
+
+    // limit 中可以用 `!&disabled` 排除禁用的主机
+    let limited = TaskExecutor::new(&manager).with_run_options(RunOptions {
+        limit: Some("all,!&disabled".to_string()),
+        exclude: None,
+        diff: false,
+    });
+    let mut targets = limited.target_hosts(&playbook.tasks[0]).unwrap();
+    targets.sort();
+    assert_eq!(targets, vec!["web1", "web3"]);
+
+    manager.enable_host("web2").unwrap();
+    assert!(manager.list_disabled().is_empty());
+    assert_eq!(manager.execute_command_all("uptime").await.successful.len(), 3);
+}
+
+#[tokio::test]
+async fn test_fetch_file_from_all_hosts_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.put_file("web1", "/var/log/app.log", b"web1 log\n")
+        .put_file("web2", "/var/log/app.log", b"web2 log\n");
+    let manager = mock_manager(&mock, &["web1", "web2", "web3"]);
+    let local_dir = crate::utils::generate_local_temp_path("rs_ansible_fetch");
+
+    let result = manager.fetch_file_all("/var/log/app.log", &local_dir).await;
+    assert_eq!(result.successful.len(), 2);
+    let local = std::path::Path::new(&local_dir);
+    assert_eq!(std::fs::read(local.join("web1/app.log")).unwrap(), b"web1 log\n");
+    assert_eq!(std::fs::read(local.join("web2/app.log")).unwrap(), b"web2 log\n");
+    // 文件不存在的主机记为失败并给出原因，不留下空目录
+    let error = result.results["web3"].error().unwrap().to_string();
+    assert!(error.contains("/var/log/app.log") && error.contains("does not exist"), "{}", error);
+    assert!(!local.join("web3").exists());
+
+    let playbook = Playbook::new("collect logs").add_task(Task::fetch("fetch app log", "/var/log/app.log", &local_dir));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(matches!(result.task_results[0].1, TaskResult::Fetch(ref batch) if batch.successful.len() == 2));
+    assert!(result.failed_hosts.contains("web3"));
+
+    std::fs::remove_dir_all(&local_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_duplicate_hosts_with_mock_transport() {
+    use crate::testing::MockTransport;
+    use crate::types::{DuplicateHostKey, DuplicateHostPolicy};
+
+    let host = |hostname: &str, port: u16| HostConfig {
+        hostname: hostname.to_string(),
+        port,
+        ..Default::default()
+    };
+    let mock = MockTransport::new();
+
+    let strict = AnsibleManager::new().with_duplicate_policy(DuplicateHostPolicy::Error, DuplicateHostKey::Address);
+    strict.try_add_host("web1".to_string(), host("10.0.0.5", 22)).unwrap();
+    assert!(strict.try_add_host("web1-ip".to_string(), host("10.0.0.5", 22)).is_err());
+    strict.try_add_host("web1-alt".to_string(), host("10.0.0.5", 2222)).unwrap();
+    // 替换同名主机不算重复
+    strict.try_add_host("web1".to_string(), host("10.0.0.5", 22)).unwrap();
+    assert_eq!(strict.list_hosts().len(), 2);
+
+    let manager = AnsibleManager::new()
+        .with_transport_factory(mock.factory())
+        .with_duplicate_policy(DuplicateHostPolicy::Alias, DuplicateHostKey::Address);
+    manager.add_host("web1".to_string(), host("10.0.0.5", 22));
+    manager.add_host("web2".to_string(), host("10.0.0.6", 22));
+    manager.add_host("legacy-web".to_string(), host("10.0.0.5", 22));
+    assert_eq!(manager.list_hosts().len(), 2);
+    assert_eq!(manager.resolve_alias("legacy-web"), "web1");
+    assert_eq!(manager.resolve_alias("web2"), "web2");
+    let duplicates = manager.duplicate_hosts();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!((duplicates[0].name.as_str(), duplicates[0].key.as_str()), ("legacy-web", "10.0.0.5:22"));
+
+    // 通过别名与规范名同时指定时只在该机器上执行一次
+    let result = manager
+        .execute_command_on_hosts("uptime", &["legacy-web".to_string(), "web1".to_string()])
+        .await;
+    assert_eq!(result.successful, vec!["web1"]);
+    assert_eq!(mock.commands("web1").len(), 1);
+
+    manager.remove_host("web1");
+    assert_eq!(manager.resolve_alias("legacy-web"), "legacy-web");
+
+    // 有多个同键主机时，规范主机为其中名称排序在前者（与添加顺序无关）
+    let resolved = AnsibleManager::new().with_duplicate_policy(DuplicateHostPolicy::Warn, DuplicateHostKey::ResolvedAddress);
+    resolved.add_host("zz-db".to_string(), host("127.0.0.1", 5432));
+    resolved.add_host("mm-db".to_string(), host("127.0.0.1", 5432));
+    resolved.add_host("aa-db".to_string(), host("127.0.0.1", 5432));
+    let duplicates = resolved.duplicate_hosts();
+    assert_eq!(duplicates.len(), 2);
+    assert_eq!((duplicates[0].name.as_str(), duplicates[0].canonical.as_str()), ("mm-db", "zz-db"));
+    assert_eq!((duplicates[1].name.as_str(), duplicates[1].canonical.as_str()), ("aa-db", "mm-db"));
+    assert_eq!(duplicates[1].key, "127.0.0.1:5432");
+}
+
+#[tokio::test]
+async fn test_apply_sysctl_baseline_with_mock_transport() {
+    use crate::testing::MockTransport;
+
+    let sysctl = |stdout: &str| CommandResult {
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+        exit_code: 0,
+        raw_stdout: None,
+    };
+    let mock = MockTransport::new();
+    mock.on_command("web1", "sysctl -a", sysctl("net.ipv4.ip_forward = 1\nkernel.randomize_va_space = 2\n"))
+        .on_command("web2", "sysctl -a", sysctl("net.ipv4.ip_forward = 0\nkernel.randomize_va_space = 2\n"));
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let baseline: std::collections::HashMap<String, String> = [("net.ipv4.ip_forward", "0"), ("kernel.randomize_va_space", "2")]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    let result = manager
+        .apply_sysctl_baseline_to_hosts(&baseline, &["web1".to_string(), "web2".to_string()])
+        .await;
+    assert_eq!(result.successful.len(), 2);
+    assert_eq!(result.changed_hosts(), vec!["web1"]);
+    let web1 = result.results["web1"].value().unwrap();
+    assert_eq!(web1.wrong_value, vec![("net.ipv4.ip_forward".to_string(), "0".to_string(), "1".to_string())]);
+    assert!(mock.commands("web1").iter().any(|c| c == "sysctl -q -w 'net.ipv4.ip_forward=0'"));
+    assert_eq!(mock.commands("web2").len(), 1);
+}
+
+#[tokio::test]
+async fn test_find_users_with_old_keys_with_mock_transport() {
+    use crate::testing::MockTransport;
+
+    let output = |exit_code: i32, stdout: &str| CommandResult {
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+        exit_code,
+        raw_stdout: None,
+    };
+    let mock = MockTransport::new();
+    mock.on_command("web1", "~alice/.ssh/authorized_keys", output(0, "\
+==> ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQC7 alice@old-laptop
+2048 SHA256:Xy5YzUz0B0RPaFtvbMKbjHh1s8BaVYH5XoSAzuqNKsY alice@old-laptop (RSA)
+"))
+    .on_command("web1", "~carol/.ssh/*", output(0, "/home/carol/.ssh/id_ed25519: OpenSSH private key\n"))
+    .on_command("web1", "find ~carol/.ssh", output(0, "/home/carol/.ssh/id_ed25519\n/home/carol/.ssh/known_hosts\n"))
+    .on_command("web1", "getent passwd bob >/dev/null", output(2, ""));
+    let manager = mock_manager(&mock, &["web1"]);
+
+    let users: Vec<String> = ["alice", "bob", "carol", "dave"].iter().map(|u| u.to_string()).collect();
+    let result = manager.find_users_with_old_keys_all(&users, 365).await;
+    assert_eq!(result.results["web1"].value().unwrap(), &vec!["alice", "carol"]);
+    assert!(mock.commands("web1").iter().any(|c| c == "find ~carol/.ssh -maxdepth 1 -type f -mtime +365"));
+
+    let keys = manager.get_authorized_keys_all("alice").await;
+    let keys = keys.results["web1"].value().unwrap();
+    assert_eq!((keys[0].type_.as_str(), keys[0].comment.as_str()), ("ssh-rsa", "alice@old-laptop"));
+    assert!(manager.get_authorized_keys_all("alice;reboot").await.results["web1"].error().is_some());
+}
+
+#[tokio::test]
+async fn test_set_permissions_with_mock_transport() {
+    use crate::testing::MockTransport;
+
+    let output = |stdout: &str| CommandResult {
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+        exit_code: 0,
+        raw_stdout: None,
+    };
+    let mock = MockTransport::new();
+    mock.on_command(crate::testing::ANY_HOST, "stat -c '%a %U %G'", output("755 root root\n"))
+        .on_command("web2", "chmod -c", output("mode of '/opt/run.sh' changed from 0644 (rw-r--r--) to 0744 (rwxr--r--)\n"));
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let hosts = vec!["web1".to_string(), "web2".to_string()];
+
+    // 符号形式的权限由 chmod -c 的输出判断是否变更
+    let symbolic = PermissionsOptions { path: "/opt/run.sh".to_string(), mode: Some("u+x".to_string()), ..Default::default() };
+    let result = manager.set_permissions_on_hosts(&symbolic, &hosts).await;
+    assert_eq!(result.successful.len(), 2);
+    assert_eq!(result.changed_hosts(), vec!["web2"]);
+    assert!(mock.commands("web1").contains(&"chmod -c u+x '/opt/run.sh'".to_string()));
+
+    // 所有者与用户组不能夹带其他命令
+    let injected = PermissionsOptions {
+        path: "/opt/run.sh".to_string(),
+        owner: Some("root; reboot".to_string()),
+        ..Default::default()
+    };
+    let result = manager.set_permissions_on_hosts(&injected, &hosts).await;
+    assert!(matches!(result.error("web1"), Some(crate::error::AnsibleError::ValidationError(_))));
+    assert!(!mock.commands("web1").iter().any(|c| c.contains("reboot")));
+}
+
+#[tokio::test]
+async fn test_ensure_dirs_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor, TaskResult};
+    use crate::testing::MockTransport;
+    use crate::types::DirSpec;
+
+    let output = |exit_code: i32, stdout: &str| CommandResult {
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+        exit_code,
+        raw_stdout: None,
+    };
+    let mock = MockTransport::new();
+    mock.on_command(crate::testing::ANY_HOST, "test -d", output(1, ""))
+        .on_command("web1", "test -d '/srv/app'", output(0, ""))
+        .on_command("web1", "stat -c '%a %U %G' '/srv/app'", output(0, "750 app app\n"))
+        .on_command("web1", "stat -c '%a %U %G' '/srv/app/releases'", output(0, "755 root root\n"))
+        .on_command("web2", "stat -c '%a %U %G'", output(0, "755 root root\n"));
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let dirs = vec![
+        DirSpec {
+            path: "/srv/app".to_string(),
+            mode: Some("750".to_string()),
+            owner: Some("app".to_string()),
+            group: Some("app".to_string()),
+        },
+        DirSpec {
+            path: "/srv/app/releases".to_string(),
+            mode: Some("0750".to_string()),
+            owner: Some("app".to_string()),
+            group: None,
+        },
+    ];
+
+    let result = manager.ensure_dirs_all(&dirs).await;
+    assert_eq!(result.successful.len(), 2);
+    let web1 = result.results["web1"].value().unwrap();
+    assert_eq!(web1.present, vec!["/srv/app"]);
+    assert_eq!(web1.created, vec!["/srv/app/releases"]);
+    assert_eq!(web1.updated, vec!["/srv/app/releases"]);
+    let web2 = result.results["web2"].value().unwrap();
+    assert_eq!(web2.created.len(), 2);
+    assert_eq!(web2.updated.len(), 2);
+
+    // 属性已符合的目录不执行 chmod/chown
+    let web1_commands = mock.commands("web1");
+    let on_app: Vec<&String> = web1_commands.iter().filter(|c| c.ends_with(" '/srv/app'")).collect();
+    assert_eq!(on_app, vec!["test -d '/srv/app'", "stat -c '%a %U %G' '/srv/app'"]);
+    assert!(web1_commands.contains(&"chmod 0750 '/srv/app/releases'".to_string()));
+    assert!(web1_commands.contains(&"chown app '/srv/app/releases'".to_string()));
+
+    let invalid = vec![DirSpec { path: "/srv/x".to_string(), mode: Some("99z".to_string()), ..Default::default() }];
+    assert!(manager.ensure_dirs_all(&invalid).await.results["web1"].error().is_some());
+
+    let playbook = Playbook::new("layout").add_task(Task::ensure_dirs("app dirs", dirs));
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    assert!(matches!(result.task_results[0].1, TaskResult::EnsureDirs(ref batch) if batch.changed_hosts().len() == 2));
+}
+
+#[tokio::test]
+async fn test_playbook_state_file_replay_with_mock_transport() {
+    use crate::executor::{Playbook, Task, TaskExecutor};
+    use crate::state_file::{RunStateFile, STATE_FILE_VERSION};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.fail_command("web2", "restart-app", "connection reset by peer");
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let state_path = crate::utils::generate_local_temp_path("rs_ansible_state");
+    let src = crate::utils::generate_local_temp_path("rs_ansible_state_tpl");
+    std::fs::write(&src, "listen 80\n").unwrap();
+    let playbook = |verify: &str| {
+        let options = TemplateOptions {
+            src: src.clone(),
+            dest: "/etc/app.conf".to_string(),
+            ..Default::default()
+        };
+        Playbook::new("deploy")
+            .add_task(Task::template("config", options))
+            .add_task(Task::command("restart", "restart-app"))
+            .add_task(Task::command("verify", verify))
+    };
+    let run = |verify: &'static str, resume: bool| {
+        let executor = TaskExecutor::new(&manager).with_state_file(&state_path, resume);
+        let playbook = playbook(verify);
+        async move { executor.execute_playbook(&playbook).await.unwrap() }
+    };
+
+    // 首次部署修改了主机，记录不会被复用
+    let result = run("curl -f localhost", true).await;
+    assert!(result.failed_hosts.contains("web2"));
+    let state = RunStateFile::load(&state_path).unwrap().unwrap();
+    assert_eq!(state.version, STATE_FILE_VERSION);
+    assert!(state.cached_hosts(0).is_empty());
+
+    // 修复 web2 后重跑：模板未变化，记录为未修改
+    let mock_fixed = MockTransport::new();
+    for host in ["web1", "web2"] {
+        mock_fixed.put_file(host, "/etc/app.conf", b"listen 80\n");
+    }
+    let manager = mock_manager(&mock_fixed, &["web1", "web2"]);
+    let run = |verify: &'static str, resume: bool| {
+        let executor = TaskExecutor::new(&manager).with_state_file(&state_path, resume);
+        let playbook = playbook(verify);
+        async move { executor.execute_playbook(&playbook).await.unwrap() }
+    };
+    assert!(run("curl -f localhost", true).await.overall_success);
+    assert_eq!(RunStateFile::load(&state_path).unwrap().unwrap().cached_hosts(0), vec!["web1", "web2"]);
+
+    // 再次运行：未修改的模板任务被跳过并报告为 cached，命令任务不报告 changed，总是执行
+    let before = mock_fixed.commands("web1").len();
+    let result = run("curl -f localhost", true).await;
+    assert_eq!(mock_fixed.commands("web1")[before..], ["restart-app", "curl -f localhost"]);
+    assert_eq!(result.skips()["web1"], vec![("config".to_string(), SkipReason::Cached)]);
+
+    // 修改模板源文件后，模板任务的记录失效，重新部署
+    std::fs::write(&src, "listen 8080\n").unwrap();
+    let result = run("curl -f localhost", true).await;
+    assert!(!result.skips().contains_key("web1"));
+    assert_eq!(mock_fixed.file("web1", "/etc/app.conf").unwrap(), b"listen 8080\n");
+
+    // resume 为 false 时忽略已有记录，全部重跑
+    assert_eq!(run("curl -f localhost", true).await.skips().len(), 0);
+    assert_eq!(run("curl -f localhost", true).await.skips()["web1"].len(), 1);
+    assert_eq!(run("curl -f localhost", false).await.skips().len(), 0);
+
+    std::fs::remove_file(&state_path).unwrap();
+    std::fs::remove_file(&src).unwrap();
+}
+
+#[tokio::test]
+async fn test_canary_deployment_aborts_on_failed_health_check() {
+    use crate::executor::{Playbook, Task};
+    use crate::manager::CanaryConfig;
+    use crate::testing::MockTransport;
+    use crate::types::HealthProbe;
+    use std::time::Duration;
+
+    let mock = MockTransport::new();
+    let manager = mock_manager(&mock, &["web1", "web2", "web3", "web4"]);
+    let canary = Playbook::new("canary").add_task(Task::command("deploy", "deploy v2"));
+    let full = Playbook::new("full").add_task(Task::command("deploy", "deploy v2 --all"));
+    let config = CanaryConfig {
+        canary_percent: 25.0,
+        monitor_duration: Duration::ZERO,
+        success_threshold: 1.0,
+        canary_health_check: HealthProbe {
+            name: "api".to_string(),
+            command: "curl -fs localhost/health".to_string(),
+            expected_exit_code: 0,
+            expected_stdout_contains: None,
+            timeout: Duration::from_secs(5),
+        },
+        full_rollout_playbook: full.clone(),
+    };
+    mock.on_command(
+        "web1",
+        "curl -fs localhost/health",
+        CommandResult { exit_code: 22, stdout: String::new(), stderr: "503\n".to_string(), raw_stdout: None },
+    );
+
+    let result = manager.execute_canary_deployment(&canary, &full, &config).await.unwrap();
+    assert_eq!(result.canary_hosts, vec!["web1"]);
+    assert!(result.canary_result.overall_success);
+    assert_eq!(result.canary_success_rate, 0.0);
+    assert!(!result.full_rollout_executed && result.full_rollout_result.is_none());
+    assert_eq!(mock.commands("web1"), vec!["deploy v2", "curl -fs localhost/health"]);
+    assert!(mock.commands("web2").is_empty());
+
+    // 金丝雀健康后在其余主机上全量发布
+    mock.on_command("web1", "curl -fs localhost/health", CommandResult {
+        exit_code: 0,
+        stdout: "ok\n".to_string(),
+        stderr: String::new(),
+        raw_stdout: None,
+    });
+    let result = manager.execute_canary_deployment(&canary, &full, &config).await.unwrap();
+    assert_eq!(result.canary_success_rate, 1.0);
+    assert!(result.full_rollout_executed);
+    assert!(result.full_rollout_result.unwrap().overall_success);
+    assert!(!mock.commands("web1").iter().any(|c| c.contains("--all")));
+    for host in ["web2", "web3", "web4"] {
+        assert_eq!(mock.commands(host), vec!["deploy v2 --all"]);
+    }
+
+    let invalid = CanaryConfig { canary_percent: 0.0, ..config };
+    assert!(manager.execute_canary_deployment(&canary, &full, &invalid).await.is_err());
+}
+
+#[tokio::test]
+async fn test_find_hosts_with_clock_drift_with_mock_transport() {
+    use crate::testing::MockTransport;
+
+    let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
+    let mock = MockTransport::new();
+    let manager = mock_manager(&mock, &["web1", "web2", "web3"]);
+    let remote_time = |ms: u128| CommandResult {
+        exit_code: 0,
+        stdout: format!("{}\n", ms),
+        stderr: String::new(),
+        raw_stdout: None,
+    };
+    mock.on_command("web1", "date +%s%3N", remote_time(now_ms + 60_000));
+    mock.on_command("web2", "date +%s%3N", remote_time(now_ms));
+    // web3 返回空输出，查询失败不计入结果
+
+    let hosts: Vec<String> = ["web1", "web2", "web3"].map(String::from).to_vec();
+    let drifted = manager.find_hosts_with_clock_drift_ms(&hosts, 5_000.0).await;
+    assert_eq!(drifted.len(), 1);
+    assert_eq!(drifted[0].0, "web1");
+    assert!((drifted[0].1 - 60_000.0).abs() < 5_000.0, "{}", drifted[0].1);
+    assert_eq!(mock.commands("web2"), vec!["date +%s%3N"]);
+}
+
+#[tokio::test]
+async fn test_server_key_fingerprints_with_mock_transport() {
+    use crate::testing::MockTransport;
+
+    let fingerprint = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    let mock = MockTransport::new();
+    mock.with_host_key("web1", HostKeyType::Ed25519, fingerprint);
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+
+    let result = manager.get_server_key_fingerprints_all().await;
+    assert_eq!(
+        result.results["web1"].value(),
+        Some(&(HostKeyType::Ed25519, fingerprint.to_string()))
+    );
+    // 没有主机密钥的连接报告失败，不执行任何远程命令
+    assert!(result.results["web2"].is_failed());
+    assert!(mock.commands("web1").is_empty());
+}
+
+#[tokio::test]
+async fn test_diff_mode_collects_copy_diffs_with_mock_transport() {
+    use crate::callback::ConsoleReporter;
+    use crate::executor::{Playbook, RunOptions, Task, TaskExecutor, TaskResult};
+    use crate::testing::MockTransport;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mock = MockTransport::new();
+    mock.put_file("web1", "/etc/app.conf", b"port = 80\nworkers = 4\n");
+    let manager = mock_manager(&mock, &["web1", "web2"]);
+    let local = std::env::temp_dir().join(format!("rs_ansible_diff_{}", rand::random::<u32>()));
+    std::fs::write(&local, b"port = 8080\nworkers = 4\n").unwrap();
+    let playbook = Playbook::new("diff")
+        .add_task(Task::copy_file("config", local.to_str().unwrap(), "/etc/app.conf"))
+        .add_task(Task::copy_file("config again", local.to_str().unwrap(), "/etc/app.conf"));
+
+    let output = Output::default();
+    let result = TaskExecutor::new(&manager)
+        .with_run_options(RunOptions { diff: true, ..Default::default() })
+        .with_callback(Arc::new(ConsoleReporter::to_writer(Box::new(output.clone()), false)))
+        .execute_playbook(&playbook)
+        .await
+        .unwrap();
+
+    assert!(result.overall_success);
+    let diffs = result.collect_diffs();
+    // 第二次复制内容未变化，没有 diff
+    assert_eq!(diffs.len(), 2);
+    assert_eq!((diffs[0].host.as_str(), diffs[0].task.as_str(), diffs[0].path.as_str()), ("web1", "config", "/etc/app.conf"));
+    assert!(diffs[0].diff.contains("@@ -1,2 +1,2 @@\n-port = 80\n+port = 8080\n workers = 4\n"), "{}", diffs[0].diff);
+    assert!(diffs[1].diff.contains("@@ -0,0 +1,2 @@\n+port = 8080\n+workers = 4\n"), "{}", diffs[1].diff);
+    let console = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(console.contains("[web1]\n--- before: /etc/app.conf\n+++ after: /etc/app.conf\n"), "{}", console);
+
+    // 未开启差异模式时不计算也不报告 diff
+    mock.put_file("web1", "/etc/app.conf", b"port = 80\n");
+    let result = TaskExecutor::new(&manager).execute_playbook(&playbook).await.unwrap();
+    let _ = std::fs::remove_file(&local);
+    assert!(result.collect_diffs().is_empty());
+    let TaskResult::CopyFile(ref batch) = result.task_results[0].1 else { panic!("unexpected task result type") };
+    assert_eq!(batch.results["web1"].value().unwrap().diff, None);
+}
+
+#[tokio::test]
+async fn test_diff_mode_copies_when_remote_file_is_unreadable() {
+    use crate::executor::{Playbook, RunOptions, Task, TaskExecutor};
+    use crate::testing::MockTransport;
+
+    let mock = MockTransport::new();
+    mock.put_file("web1", "/etc/secret.conf", b"token = old\n");
+    mock.fail_download("web1", "/etc/secret.conf", "Permission denied");
+    let manager = mock_manager(&mock, &["web1"]);
+    let local = std::env::temp_dir().join(format!("rs_ansible_diff_{}", rand::random::<u32>()));
+    std::fs::write(&local, b"token = new\n").unwrap();
+    let playbook = Playbook::new("diff").add_task(Task::copy_file("secret", local.to_str().unwrap(), "/etc/secret.conf"));
+
+    let result = TaskExecutor::new(&manager)
+        .with_run_options(RunOptions { diff: true, ..Default::default() })
+        .execute_playbook(&playbook)
+        .await
+        .unwrap();
+    let _ = std::fs::remove_file(&local);
+
+    // 差异不可用不影响复制本身
+    assert!(result.overall_success);
+    assert_eq!(mock.file("web1", "/etc/secret.conf").unwrap(), b"token = new\n");
+    let diffs = result.collect_diffs();
+    assert_eq!(diffs.len(), 1);
+    assert!(diffs[0].diff.starts_with("Diff unavailable for /etc/secret.conf: "), "{}", diffs[0].diff);
+    assert!(diffs[0].diff.contains("Permission denied"), "{}", diffs[0].diff);
+}
+
+#[test]
+fn test_passphrase_provider_chains_with_credential_provider() {
+    use crate::credentials::{CredentialKind, StaticCredentialProvider};
+    use std::sync::Arc;
+
+    let manager = AnsibleManager::new()
+        .with_credential_provider(Arc::new(
+            StaticCredentialProvider::new()
+                .with_password("web1", "pw")
+                .with_passphrase("web2", "stored"),
+        ))
+        .with_passphrase_provider(|host| (host == "web1").then(|| "prompted".to_string()));
+    let provider = manager.get_credential_provider().unwrap();
+
+    let password = provider.resolve("web1", CredentialKind::Password).unwrap();
+    assert_eq!(password.unwrap().expose_secret(), "pw");
+    let prompted = provider.resolve("web1", CredentialKind::Passphrase).unwrap();
+    assert_eq!(prompted.unwrap().expose_secret(), "prompted");
+    let stored = provider.resolve("web2", CredentialKind::Passphrase).unwrap();
+    assert_eq!(stored.unwrap().expose_secret(), "stored");
+}
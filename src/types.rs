@@ -8,7 +8,133 @@ pub struct HostConfig {
     pub username: String,
     pub password: Option<String>,
     pub private_key_path: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub private_key_data: Option<String>, // 内存中的私钥（PEM 内容），与 private_key_path 二选一（不会被序列化）
+    #[serde(default, skip_serializing)]
+    pub public_key_data: Option<String>,  // 与 private_key_data 配套的公钥，未设置时由私钥推导
     pub passphrase: Option<String>,
+    #[serde(default)]
+    pub r#become: bool,                  // 是否通过 sudo 提权执行命令
+    #[serde(default)]
+    pub become_user: Option<String>,     // 提权目标用户，默认 root
+    #[serde(default, skip_serializing)]
+    pub become_password: Option<String>, // sudo 密码（不会被序列化，未设置时使用 sudo -n）
+    #[serde(default, alias = "connection")]
+    pub transport: TransportKind,        // 连接使用的传输实现（inventory 中也可写作 connection）
+    #[serde(default)]
+    pub remote_tmp: Option<String>,      // 远程临时文件目录，默认 /tmp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docker: Option<DockerConnection>, // transport 为 docker 时的容器设置
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vars: HashMap<String, serde_json::Value>, // 主机变量，渲染模板时可用（优先级最低）
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,                   // 为 false 时（维护中）所有操作跳过该主机，配置保留
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+/// 传输层实现选择
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    #[default]
+    #[serde(alias = "ssh")]
+    Ssh2,  // 基于 libssh2 的同步实现
+    Local,  // 在控制端本机执行，不建立 SSH 连接
+    Docker, // 通过 docker/podman exec 在容器中执行，见 `HostConfig::docker`
+    Russh,  // 基于 russh 的纯异步 SSH 实现，需启用 `async-ssh` feature，见 `TransportKind::is_async`
+}
+
+impl TransportKind {
+    /// 是否为异步传输：这类主机由 `AsyncSshClient` 在 tokio 任务中直接 await，不占用阻塞线程
+    ///
+    /// 异步传输只支持命令执行与文件传输类的批量操作，其他操作对这类主机返回错误。
+    pub fn is_async(&self) -> bool {
+        matches!(self, TransportKind::Russh)
+    }
+}
+
+/// 容器连接设置（`transport: docker`）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct DockerConnection {
+    pub container: String,       // 容器名或 ID
+    #[serde(default)]
+    pub engine: ContainerEngine,
+    #[serde(default)]
+    pub via_ssh: bool,           // 通过 SSH 在 hostname 所指的宿主机上调用容器命令，默认在控制端本机调用
+}
+
+/// 容器命令行工具
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerEngine {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerEngine {
+    /// 命令行程序名
+    pub fn command(&self) -> &'static str {
+        match self {
+            ContainerEngine::Docker => "docker",
+            ContainerEngine::Podman => "podman",
+        }
+    }
+}
+
+impl HostConfig {
+    /// 是否需要建立 SSH 连接（以及对应的用户名与认证信息）
+    pub fn uses_ssh(&self) -> bool {
+        match self.transport {
+            TransportKind::Ssh2 | TransportKind::Russh => true,
+            TransportKind::Local => false,
+            TransportKind::Docker => self.docker.as_ref().is_some_and(|d| d.via_ssh),
+        }
+    }
+
+    /// 复制当前配置并应用 `overrides` 中非 None 的字段
+    ///
+    /// 覆盖 `private_key_path` 时会清除内存中的私钥（`private_key_data` 与 `public_key_data`），
+    /// 避免两种私钥来源同时存在。
+    pub fn with_overrides(&self, overrides: &HostConfigOverrides) -> HostConfig {
+        let mut config = self.clone();
+        if let Some(ref hostname) = overrides.hostname {
+            config.hostname = hostname.clone();
+        }
+        if let Some(port) = overrides.port {
+            config.port = port;
+        }
+        if let Some(ref username) = overrides.username {
+            config.username = username.clone();
+        }
+        if let Some(ref password) = overrides.password {
+            config.password = Some(password.clone());
+        }
+        if let Some(ref key_path) = overrides.private_key_path {
+            config.private_key_path = Some(key_path.clone());
+            config.private_key_data = None;
+            config.public_key_data = None;
+        }
+        config
+    }
+}
+
+/// 基于已有主机复制配置时要覆盖的字段（None 表示沿用源主机的值）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostConfigOverrides {
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>, // 不会被序列化
+    #[serde(default)]
+    pub private_key_path: Option<String>,
 }
 
 impl Default for HostConfig {
@@ -19,23 +145,146 @@ impl Default for HostConfig {
             username: String::new(),
             password: None,
             private_key_path: None,
+            private_key_data: None,
+            public_key_data: None,
             passphrase: None,
+            r#become: false,
+            become_user: None,
+            become_password: None,
+            transport: TransportKind::default(),
+            remote_tmp: None,
+            docker: None,
+            vars: HashMap::new(),
+            enabled: true,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 单台主机在一次批量操作中的轻量统计（`OperationOptions::host_metrics` 开启时收集）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HostMetrics {
+    pub connect_time: std::time::Duration, // 建立连接（最后一次成功尝试）的耗时
+    pub channels_opened: u64,              // 打开的通道数（每次命令执行、上传、下载各一个）
+    pub commands: u64,                     // 执行的命令数
+    pub bytes_transferred: u64,            // 上传与下载的总字节数
+}
+
+impl HostMetrics {
+    /// 累加另一次操作的统计（一个任务包含多个批量操作时使用）
+    pub fn merge(&mut self, other: &HostMetrics) {
+        self.connect_time += other.connect_time;
+        self.channels_opened += other.channels_opened;
+        self.commands += other.commands;
+        self.bytes_transferred += other.bytes_transferred;
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SystemInfo {
     pub hostname: String,
     pub os: String,
+    #[serde(default)]
+    pub distribution: String, // 发行版（/etc/os-release 中的 PRETTY_NAME）
     pub kernel_version: String,
     pub architecture: String,
     pub uptime: String,
     pub memory_total: String,
     pub memory_free: String,
-    pub disk_usage: HashMap<String, String>,
+    pub disk_usage: HashMap<String, String>, // 挂载点 -> 使用率（例如 "45%"），与 disks 一致，保留用于兼容
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<DiskUsage>, // 各文件系统的容量与使用情况
     pub cpu_info: String,
     pub network_interfaces: Vec<NetworkInterface>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub packages: HashMap<String, String>, // 已安装软件包 -> 版本（仅在收集 Packages 时填充）
+}
+
+/// 单个文件系统的容量与使用情况（来自 `df -P`）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub device: String, // 文件系统（设备）
+    pub total: u64,     // 总容量（字节）
+    pub used: u64,      // 已用（字节）
+    pub avail: u64,     // 可用（字节）
+    pub percent: u8,    // 使用率（df 输出 `-` 时为 0）
+    pub mount: String,  // 挂载点
+}
+
+/// 需要收集的 facts 类别（可按位组合，例如 `FactSubset::NETWORK | FactSubset::OS`）
+///
+/// 未收集的类别在 `SystemInfo` 中保持为空值。序列化为类别名称列表，例如 `[network, os]`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FactSubset(u8);
+
+impl FactSubset {
+    pub const NETWORK: FactSubset = FactSubset(1);       // 网络接口
+    pub const HARDWARE: FactSubset = FactSubset(1 << 1); // 内存与 CPU
+    pub const OS: FactSubset = FactSubset(1 << 2);       // 主机名、系统、发行版、内核、架构、运行时间
+    pub const MOUNTS: FactSubset = FactSubset(1 << 3);   // 磁盘挂载点使用率
+    pub const PACKAGES: FactSubset = FactSubset(1 << 4); // 已安装软件包（开销较大）
+    pub const ALL: FactSubset = FactSubset(0b1_1111);
+
+    const NAMES: [(&'static str, FactSubset); 5] = [
+        ("network", FactSubset::NETWORK),
+        ("hardware", FactSubset::HARDWARE),
+        ("os", FactSubset::OS),
+        ("mounts", FactSubset::MOUNTS),
+        ("packages", FactSubset::PACKAGES),
+    ];
+
+    pub const fn empty() -> Self {
+        FactSubset(0)
+    }
+
+    pub const fn contains(self, other: FactSubset) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: FactSubset) -> Self {
+        FactSubset(self.0 | other.0)
+    }
+}
+
+impl Default for FactSubset {
+    /// 与以往 `get_system_info` 收集的内容一致（不包含软件包列表）
+    fn default() -> Self {
+        FactSubset::NETWORK | FactSubset::HARDWARE | FactSubset::OS | FactSubset::MOUNTS
+    }
+}
+
+impl std::ops::BitOr for FactSubset {
+    type Output = FactSubset;
+
+    fn bitor(self, rhs: FactSubset) -> FactSubset {
+        self.union(rhs)
+    }
+}
+
+impl Serialize for FactSubset {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names: Vec<&str> = Self::NAMES
+            .iter()
+            .filter(|(_, flag)| self.contains(*flag))
+            .map(|(name, _)| *name)
+            .collect();
+        names.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FactSubset {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names.iter().try_fold(FactSubset::empty(), |subset, name| {
+            if name == "all" {
+                return Ok(FactSubset::ALL);
+            }
+            Self::NAMES
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, flag)| subset | *flag)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown fact subset '{}'", name)))
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +299,104 @@ pub struct CommandResult {
     pub exit_code: i32,
     pub stdout: String,
     pub stderr: String,
+    #[serde(skip)]
+    pub raw_stdout: Option<Vec<u8>>, // 标准输出不是有效 UTF-8 时的原始字节（此时 stdout 为有损转换的结果）
+}
+
+impl CommandResult {
+    /// 由命令的原始输出构建结果，按 `encoding` 处理无效的 UTF-8
+    pub fn from_bytes(
+        exit_code: i32,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+        encoding: OutputEncoding,
+    ) -> Result<Self, crate::error::AnsibleError> {
+        if encoding == OutputEncoding::Strict {
+            for (name, output) in [("stdout", &stdout), ("stderr", &stderr)] {
+                if let Err(e) = std::str::from_utf8(output) {
+                    return Err(crate::error::AnsibleError::CommandExecutionError(format!(
+                        "Command {} is not valid UTF-8: {}",
+                        name, e
+                    )));
+                }
+            }
+        }
+        let (stdout, raw_stdout) = match String::from_utf8(stdout) {
+            Ok(stdout) => (stdout, None),
+            Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), Some(e.into_bytes())),
+        };
+        Ok(Self {
+            exit_code,
+            stdout,
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            raw_stdout,
+        })
+    }
+
+    /// 标准输出的原始字节（包括有损转换前的无效字节）
+    pub fn stdout_bytes(&self) -> &[u8] {
+        self.raw_stdout.as_deref().unwrap_or(self.stdout.as_bytes())
+    }
+}
+
+/// 命令输出不是有效 UTF-8（二进制输出、混合编码的日志等）时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputEncoding {
+    #[default]
+    Lossy,  // 无效字节替换为 U+FFFD，原始标准输出可通过 `CommandResult::stdout_bytes` 获取
+    Strict, // 返回错误
+}
+
+/// 远程命令执行选项（可组合使用）
+#[derive(Debug, Clone, Default)]
+pub struct CommandOptions {
+    pub stdin: Option<Vec<u8>>,                 // 写入命令标准输入的数据
+    pub timeout: Option<std::time::Duration>,   // 命令执行超时
+    pub env: Option<HashMap<String, String>>,   // 额外的环境变量
+    pub pty: bool,                              // 是否分配伪终端
+    pub output_encoding: OutputEncoding,        // 输出不是有效 UTF-8 时的处理方式
+}
+
+impl CommandOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stdin(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(data.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env
+            .get_or_insert_with(HashMap::new)
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn pty(mut self, enabled: bool) -> Self {
+        self.pty = enabled;
+        self
+    }
+
+    pub fn output_encoding(mut self, encoding: OutputEncoding) -> Self {
+        self.output_encoding = encoding;
+        self
+    }
+}
+
+/// 依次执行多条命令的选项
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandBatchOptions {
+    #[serde(default)]
+    pub fast: bool,            // 合并为一次执行，省去每条命令的 channel 往返
+    #[serde(default)]
+    pub stop_on_failure: bool, // 某条命令以非零状态退出后不再执行后续命令
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +404,146 @@ pub struct FileTransferResult {
     pub success: bool,
     pub bytes_transferred: u64,
     pub message: String,
+    /// 开启 `FileCopyOptions::diff` 且文件被创建或更新时，覆盖前内容到新内容的统一 diff
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+/// 复制并执行（deploy_and_run）的单主机结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployRunResult {
+    pub transfer: FileTransferResult,
+    pub command: CommandResult, // 命令以非零状态退出时仍视为成功，由调用方检查退出码
+}
+
+/// 文件传输进度
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TransferProgress {
+    pub bytes_sent: u64,
+    pub total_bytes: u64,
+    pub elapsed_secs: f64,
+    pub estimated_remaining_secs: Option<f64>, // 按当前平均速率估算，尚无数据时为 None
+}
+
+/// 目录中单个文件的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestAction {
+    Transferred, // 已传输，但不清楚目标此前的状态
+    Skipped,     // 远程文件与本地一致，未传输
+    Created,     // 远程文件此前不存在
+    Updated,     // 覆盖了内容不同的远程文件
+}
+
+/// 目录清单中的一个文件或符号链接
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String, // 相对于目录根的路径，以 `/` 分隔
+    pub size: u64,    // 符号链接为 0
+    pub hash: String, // SHA256；符号链接为空
+    pub action: ManifestAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>, // 符号链接指向的路径（原样重建，不做解析）
+}
+
+impl ManifestEntry {
+    pub fn is_symlink(&self) -> bool {
+        self.link_target.is_some()
+    }
+}
+
+/// 目录复制的文件清单（按路径排序，包含符号链接）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryManifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl DirectoryManifest {
+    /// 实际写入了远程主机的文件（不含跳过的文件）
+    pub fn changed_files(&self) -> Vec<&ManifestEntry> {
+        self.files.iter().filter(|entry| entry.action != ManifestAction::Skipped).collect()
+    }
+
+    /// 在远程重建的符号链接
+    pub fn symlinks(&self) -> Vec<&ManifestEntry> {
+        self.files.iter().filter(|entry| entry.is_symlink()).collect()
+    }
+
+    /// 保存清单到JSON文件
+    pub fn save_to_json<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), crate::error::AnsibleError> {
+        let json_content = serde_json::to_string_pretty(self).map_err(|e| {
+            crate::error::AnsibleError::FileOperationError(format!("Failed to serialize to JSON: {}", e))
+        })?;
+
+        std::fs::write(path, json_content)
+            .map_err(|e| crate::error::AnsibleError::FileOperationError(format!("Failed to write file: {}", e)))
+    }
+}
+
+/// 文件集中的一个文件（见 `SshClient::deploy_fileset`）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlannedFile {
+    pub src: String,              // 本地文件路径
+    pub dest: String,             // 远程目标路径（父目录不存在时自动创建）
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub mode: Option<String>,     // 文件权限，例如 "600"
+    #[serde(default)]
+    pub validate: Option<String>, // 验证命令（在替换前对暂存文件执行），支持 {{ path }} 与 {{ dest }} 占位符
+}
+
+/// 文件集中单个文件的最终状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSetAction {
+    Applied,        // 已替换到目标位置
+    Unchanged,      // 远程文件与本地一致，无需替换
+    RolledBack,     // 已替换，但因后续文件失败恢复为原内容
+    RollbackFailed, // 已替换，恢复原内容时失败
+    Untouched,      // 未替换（事务在替换该文件之前失败）
+}
+
+/// 文件集中单个文件的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSetFileResult {
+    pub dest: String,
+    pub action: FileSetAction,
+}
+
+/// 文件集部署结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSetResult {
+    pub success: bool,              // 所有文件均已替换（或无需替换）
+    pub changed: bool,              // 事务成功且至少替换了一个文件
+    pub files: Vec<FileSetFileResult>, // 与传入顺序一致
+    pub error: Option<String>,      // 失败原因
+}
+
+impl std::fmt::Display for FileSetAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FileSetAction::Applied => "applied",
+            FileSetAction::Unchanged => "unchanged",
+            FileSetAction::RolledBack => "rolled_back",
+            FileSetAction::RollbackFailed => "rollback_failed",
+            FileSetAction::Untouched => "untouched",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FileSetResult {
+    /// 每个文件的状态，例如 `/etc/ssl/app.crt: rolled_back, /etc/nginx/app.conf: untouched`
+    pub fn summary(&self) -> String {
+        self.files
+            .iter()
+            .map(|file| format!("{}: {}", file.dest, file.action))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +556,26 @@ pub struct FileCopyOptions {
     /// 预先计算的本地文件 Hash (SHA256)。如果提供，将跳过本地计算步骤。
     #[serde(skip_serializing_if = "Option::is_none")]
     pub precomputed_hash: Option<String>,
+    /// 传输失败时保留远程临时文件（用于调试）
+    #[serde(default)]
+    pub keep_temp_on_failure: bool,
+    /// 单台主机的上传带宽上限（字节/秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bandwidth_bytes_per_sec: Option<u64>,
+    /// 复制目录时跟随符号链接复制目标内容；默认在远程重建符号链接
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// 先写入目标目录中的临时文件，校验后再 mv 到位（默认）。关闭后直接写入目标文件，
+    /// 适用于不支持 rename 的文件系统，但传输或校验失败时目标文件可能已被部分覆盖
+    #[serde(default = "default_atomic")]
+    pub atomic: bool,
+    /// 传输前计算远程现有内容到本地文件的统一 diff，放入结果的 `diff` 字段
+    #[serde(default)]
+    pub diff: bool,
+}
+
+fn default_atomic() -> bool {
+    true
 }
 
 impl Default for FileCopyOptions {
@@ -80,10 +587,50 @@ impl Default for FileCopyOptions {
             backup: false,
             create_dirs: true,
             precomputed_hash: None,
+            keep_temp_on_failure: false,
+            max_bandwidth_bytes_per_sec: None,
+            follow_symlinks: false,
+            atomic: true,
+            diff: false,
         }
     }
 }
 
+/// 文件权限/所有者设置选项
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PermissionsOptions {
+    pub path: String,           // 远程路径
+    #[serde(default)]
+    pub mode: Option<String>,   // 文件权限，例如 "644"
+    #[serde(default)]
+    pub owner: Option<String>,  // 所有者
+    #[serde(default)]
+    pub group: Option<String>,  // 所属组
+    #[serde(default)]
+    pub recursive: bool,        // 是否递归应用（chmod -R / chown -R）
+}
+
+/// 需要存在的目录及其属性（None 的属性不检查）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DirSpec {
+    pub path: String,          // 远程目录路径
+    #[serde(default)]
+    pub mode: Option<String>,  // 目录权限，例如 "750"
+    #[serde(default)]
+    pub owner: Option<String>, // 所有者
+    #[serde(default)]
+    pub group: Option<String>, // 所属组
+}
+
+/// 确保目录存在的结果（各列表与传入顺序一致）
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EnsureDirsResult {
+    pub created: Vec<String>, // 新建的目录
+    pub present: Vec<String>, // 已存在的目录
+    pub updated: Vec<String>, // 修改了权限或所有者的目录（包括新建后调整属性的目录）
+    pub changed: bool,        // 新建了目录或修改了属性
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileHashInfo {
     pub algorithm: String,
@@ -108,9 +655,10 @@ pub struct UserOptions {
     pub expires: Option<String>,         // 账户过期时间
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum UserState {
+    #[default]
     Present,  // 确保用户存在
     Absent,   // 确保用户不存在
 }
@@ -162,7 +710,23 @@ pub struct TemplateOptions {
     pub group: Option<String>,           // 文件组
     pub mode: Option<String>,            // 文件权限
     pub backup: bool,                    // 是否备份现有文件
-    pub validate: Option<String>,        // 验证命令（在替换前验证文件）
+    pub validate: Option<String>,        // 验证命令（在替换前验证文件），支持 {{ path }} 与 {{ dest }} 占位符
+    #[serde(default)]
+    pub keep_temp_on_failure: bool,      // 失败时保留远程临时文件（用于调试）
+    #[serde(default)]
+    pub reload_command: Option<String>,  // 文件被修改后执行的命令（例如 systemctl reload nginx）
+    #[serde(default)]
+    pub rollback_on_failure: bool,       // reload_command 失败时恢复原文件（原本不存在时删除）
+    #[serde(default = "default_validate_render_first")]
+    pub validate_render_first: bool,     // 批量部署前先在本地为所有主机渲染，任一主机失败则不部署到任何主机（默认开启）
+    #[serde(default)]
+    pub lookup_dirs: Vec<String>,        // 模板中 file() 可读取的本地目录（模板所在目录始终允许）
+    #[serde(default)]
+    pub allow_pipe_lookup: bool,         // 允许模板通过 pipe() 在控制端执行命令（默认关闭）
+}
+
+fn default_validate_render_first() -> bool {
+    true
 }
 
 impl Default for TemplateOptions {
@@ -176,6 +740,12 @@ impl Default for TemplateOptions {
             mode: Some("644".to_string()),
             backup: false,
             validate: None,
+            keep_temp_on_failure: false,
+            reload_command: None,
+            rollback_on_failure: false,
+            validate_render_first: true,
+            lookup_dirs: Vec::new(),
+            allow_pipe_lookup: false,
         }
     }
 }
@@ -186,4 +756,589 @@ pub struct TemplateResult {
     pub changed: bool,     // 文件是否被改变
     pub message: String,
     pub diff: Option<String>,  // 文件差异（如果可用）
-}
\ No newline at end of file
+    #[serde(default)]
+    pub rolled_back: bool, // reload_command 失败后已恢复原文件
+    #[serde(default)]
+    pub critical: bool,    // reload_command 失败且恢复原文件也失败，主机处于不一致状态，需要人工处理
+}
+
+/// 模板缓存统计（一次批量部署中节省的解析/渲染次数与估算耗时）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateCacheStats {
+    pub parses: usize,             // 实际解析次数
+    pub parse_cache_hits: usize,   // 复用已解析模板的次数
+    pub renders: usize,            // 实际渲染次数
+    pub render_cache_hits: usize,  // 复用渲染结果的次数
+    pub parse_time_ms: f64,
+    pub render_time_ms: f64,
+    pub estimated_saved_ms: f64,   // 按平均耗时估算的节省时间
+}
+
+/// SSH 密钥类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SshKeyType {
+    Rsa,
+    #[default]
+    Ed25519,
+    Ecdsa,
+}
+
+impl SshKeyType {
+    /// `ssh-keygen -t` 参数值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SshKeyType::Rsa => "rsa",
+            SshKeyType::Ed25519 => "ed25519",
+            SshKeyType::Ecdsa => "ecdsa",
+        }
+    }
+
+    /// 默认密钥长度（ed25519 长度固定，忽略该参数）
+    pub fn default_bits(&self) -> u32 {
+        match self {
+            SshKeyType::Rsa => 4096,
+            SshKeyType::Ed25519 => 256,
+            SshKeyType::Ecdsa => 256,
+        }
+    }
+}
+
+/// SSH 服务器主机密钥的类型（握手时协商得到）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HostKeyType {
+    Rsa,
+    Dss,
+    Ecdsa256,
+    Ecdsa384,
+    Ecdsa521,
+    Ed25519,
+    Unknown,
+}
+
+impl HostKeyType {
+    /// known_hosts 中使用的算法名
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            HostKeyType::Rsa => "ssh-rsa",
+            HostKeyType::Dss => "ssh-dss",
+            HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+            HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+            HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+            HostKeyType::Ed25519 => "ssh-ed25519",
+            HostKeyType::Unknown => "unknown",
+        }
+    }
+}
+
+/// SSH 密钥对生成结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshKeypairResult {
+    pub private_key_path: String,
+    pub public_key: String,
+    pub fingerprint: String,  // 例如 SHA256:...
+    pub changed: bool,        // 是否新生成了密钥（已存在时为 false）
+}
+
+/// authorized_keys 中的一条公钥
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SshKeyEntry {
+    #[serde(rename = "type")]
+    pub type_: String,               // 例如 ssh-ed25519、ssh-rsa
+    pub public_key: String,          // base64 编码的公钥
+    pub comment: String,             // 公钥后的注释，没有时为空
+    pub fingerprint: Option<String>, // SHA256 指纹，ssh-keygen 无法解析该公钥时为 None
+}
+
+/// 文件中由 BEGIN/END 标记包围的受管理文本块（blockinfile）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlockInFileOptions {
+    pub path: String,                    // 远程文件路径
+    #[serde(default = "default_block_marker")]
+    pub marker: String,                  // 标记行模板，{mark} 替换为 BEGIN / END
+    #[serde(default)]
+    pub block: String,                   // 块内容，为空时等同于 absent
+    #[serde(default)]
+    pub state: UserState,                // present: 插入或更新；absent: 删除
+    #[serde(default)]
+    pub create: bool,                    // 文件不存在时是否创建
+}
+
+fn default_block_marker() -> String {
+    "# {mark} RS-ANSIBLE MANAGED BLOCK".to_string()
+}
+
+impl Default for BlockInFileOptions {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            marker: default_block_marker(),
+            block: String::new(),
+            state: UserState::Present,
+            create: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockInFileResult {
+    pub changed: bool,
+    pub message: String,
+    pub diff: Option<String>,  // 变更时的文件差异
+}
+
+/// 软件源类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoType {
+    #[default]
+    Apt, // /etc/apt/sources.list.d/<name>.list
+    Yum, // /etc/yum.repos.d/<name>.repo
+    Dnf, // 与 yum 使用相同的 .repo 格式
+}
+
+/// 软件源配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RepoConfig {
+    pub name: String,                    // 源名称，同时作为文件名
+    pub base_url: String,                // yum/dnf 为 baseurl；apt 为 deb 之后的部分，例如 "http://deb.example.com/ubuntu jammy main"
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,                   // 禁用时 apt 注释掉该行，yum/dnf 写入 enabled=0
+    #[serde(default)]
+    pub gpg_key_url: Option<String>,     // 写入源文件前导入的 GPG 公钥
+    #[serde(default)]
+    pub priority: Option<u32>,           // 仅 yum/dnf 支持
+    #[serde(rename = "type", default)]
+    pub type_: RepoType,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 软件源配置结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoResult {
+    pub changed: bool,
+    pub message: String,
+}
+
+/// 内核模块配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct KernelModuleConfig {
+    pub name: String,                    // 模块名，例如 overlay、br_netfilter
+    #[serde(default)]
+    pub params: HashMap<String, String>, // 加载参数（modprobe name key=value）
+    #[serde(default)]
+    pub persist: bool,                   // 写入 modules-load.d / modprobe.d，重启后自动加载
+}
+
+/// 内核模块管理结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelModuleResult {
+    pub changed: bool,
+    pub message: String,
+}
+
+/// crontab 中的一条定时任务
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CronEntry {
+    pub source: String,          // 来源，例如 /etc/crontab、/etc/cron.d/foo、user:root
+    pub user: Option<String>,    // 执行用户（系统 crontab 中显式指定，用户 crontab 为其所属用户）
+    pub schedule: String,        // 调度表达式，例如 "*/5 * * * *" 或 "@reboot"
+    pub command: String,
+}
+
+/// systemd 定时器（`systemctl list-timers` 的一行）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SystemdTimer {
+    pub unit: String,
+    pub next_elapse: Option<String>,   // 下次触发时间
+    pub last_trigger: Option<String>,  // 上次触发时间
+    pub passed: Option<String>,        // 距上次触发已过去的时间
+    pub activates: String,             // 被触发的 unit
+}
+
+/// 主机上的全部定时任务（cron 与 systemd 定时器）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScheduledTasks {
+    pub cron_entries: Vec<CronEntry>,
+    pub systemd_timers: Vec<SystemdTimer>,
+}
+
+/// IP 协议版本（决定使用 iptables 还是 ip6tables）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    #[default]
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    /// 对应的规则导出命令
+    pub fn save_command(&self) -> &'static str {
+        match self {
+            IpVersion::V4 => "iptables-save",
+            IpVersion::V6 => "ip6tables-save",
+        }
+    }
+}
+
+/// iptables 链及其规则（来自 `iptables-save`）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IptablesChain {
+    pub table: String,          // 所属表，例如 filter、nat
+    pub chain: String,          // 链名，例如 INPUT
+    pub policy: Option<String>, // 默认策略（自定义链为 None）
+    pub rules: Vec<String>,     // 规则（去掉 `-A <chain>` 前缀后的匹配与动作）
+}
+
+/// 内核日志记录（dmesg）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DmesgEntry {
+    pub timestamp_secs: f64, // 开机后的秒数
+    pub facility: String,    // 例如 kern、user
+    pub severity: String,    // emerg/alert/crit/err/warn/notice/info/debug
+    pub message: String,
+}
+
+/// 某一页面大小的 hugepages 使用情况
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HugepagesInfo {
+    pub size_kb: u32,  // 页面大小（kB）
+    pub total: u64,    // 已分配的页面数
+    pub free: u64,     // 空闲页面数
+    pub reserved: u64, // 已预留但尚未使用的页面数
+    pub surplus: u64,  // 超出 nr_hugepages 的临时页面数
+}
+
+/// 磁盘 I/O 基准测试结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiskBenchmark {
+    pub write_mbps: f64,   // 顺序写吞吐（MB/s）
+    pub read_mbps: f64,    // 顺序读吞吐（MB/s）
+    pub iops_write: u64,   // 4K 同步写 IOPS
+    pub iops_read: u64,    // 4K 读 IOPS
+    pub latency_ms: f64,   // 4K 同步写平均延迟（毫秒）
+    pub test_path: String, // 测试所在目录
+}
+
+/// 容器运行时信息（containerd / CRI-O / Docker）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContainerRuntimeInfo {
+    pub runtime: String,                 // 运行时名称，例如 "containerd"、"docker"
+    pub version: String,                 // 运行时版本
+    pub api_version: String,             // CRI / Engine API 版本
+    pub storage_driver: Option<String>,  // 存储驱动（例如 overlay2）
+    pub cgroup_driver: String,           // cgroup 驱动: systemd、cgroupfs 或 unknown
+}
+
+/// logrotate 执行结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRotateResult {
+    pub files_rotated: u32,  // 实际被轮转的日志数量
+    pub output: String,      // logrotate 的详细输出
+}
+
+/// logrotate 状态文件中的单条记录
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogRotateStatus {
+    pub path: String,          // 日志文件路径
+    pub last_rotated: String,  // 最近一次轮转时间（状态文件原始格式，例如 2024-1-15-6:25:1）
+}
+
+/// DNS 解析配置（/etc/resolv.conf）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct DnsConfig {
+    pub nameservers: Vec<String>,     // nameserver 行
+    #[serde(default)]
+    pub search_domains: Vec<String>,  // search 域
+    #[serde(default)]
+    pub options: Vec<String>,         // options 行中的选项，例如 "ndots:2"
+}
+
+/// mdadm 软件 RAID 阵列状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RaidArray {
+    pub device: String, // 例如 /dev/md0
+    #[serde(rename = "type")]
+    pub type_: String, // RAID 级别，例如 raid1
+    pub state: String, // 例如 "clean"、"clean, degraded"
+    pub active_devices: u32,
+    pub total_devices: u32, // 阵列设计的成员数（Raid Devices）
+    pub failed_devices: u32,
+    pub spare_devices: u32,
+    pub members: Vec<String>, // mdadm 列出的成员设备（含故障与备用设备）
+}
+
+impl RaidArray {
+    /// 阵列是否处于降级状态（缺少成员或存在故障成员）
+    pub fn is_degraded(&self) -> bool {
+        self.state.contains("degraded") || self.active_devices < self.total_devices || self.failed_devices > 0
+    }
+}
+
+/// LVM 卷组与逻辑卷信息
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LvmInfo {
+    pub volume_groups: Vec<VolumeGroup>,
+}
+
+/// LVM 卷组
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VolumeGroup {
+    pub name: String,
+    pub size_bytes: u64,
+    pub free_bytes: u64,
+    pub pv_count: u32,                      // 物理卷数量
+    pub lv_count: u32,                      // 逻辑卷数量
+    pub logical_volumes: Vec<LogicalVolume>,
+}
+
+/// LVM 逻辑卷
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogicalVolume {
+    pub name: String,
+    pub vg: String,       // 所属卷组
+    pub size_bytes: u64,
+    pub path: String,     // 例如 /dev/vg0/data
+    pub active: bool,
+}
+
+/// sshd_config 中偏离安全基线的一项设置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SshFinding {
+    pub setting: String,
+    pub current_value: String,     // 未显式配置时为 OpenSSH 默认值，并带 "(default)" 前缀
+    pub recommended_value: String,
+    pub severity: String,          // critical / high / medium / low
+    pub description: String,
+}
+
+/// sshd_config 安全基线检查结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SshConfigAudit {
+    pub findings: Vec<SshFinding>,
+    pub score: f32, // 按严重程度加权的通过率（0–100）
+}
+
+impl SshConfigAudit {
+    /// 是否满足全部基线项
+    pub fn is_compliant(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// 用户自定义的主机健康探针：执行命令并检查退出码与输出
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HealthProbe {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub expected_exit_code: i32,                 // 期望的退出码，默认 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_stdout_contains: Option<String>, // 标准输出中必须包含的文本
+    #[serde(default = "default_probe_timeout", with = "duration_secs")]
+    pub timeout: std::time::Duration,            // 命令超时，配置中以秒表示（可为小数）
+}
+
+fn default_probe_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(10)
+}
+
+/// 以秒（浮点数）序列化 Duration，便于在 YAML 中书写 `timeout: 2.5`
+mod duration_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 主机必须满足的条件（用于 `health_check` 任务）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HostRequirement {
+    /// 命令的版本（`<command> --version` 输出中的第一个版本号）不低于 `min_version`
+    CommandVersion { command: String, min_version: String },
+}
+
+/// 单个健康探针的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthProbeResult {
+    pub name: String,
+    pub passed: bool,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub latency_ms: f64, // 命令往返耗时
+}
+
+/// 待更新的软件包
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingUpdate {
+    pub name: String,
+    pub current_version: String, // 新增依赖包没有已安装版本时为空
+    pub new_version: String,
+    pub security: bool,          // 是否来自安全更新源
+}
+
+/// cgroup 及其资源限制，未设置的限制保持不变
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct CgroupConfig {
+    pub name: String,
+    #[serde(default)]
+    pub parent: String,                          // 父 cgroup 路径，为空时位于根 cgroup 下
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_shares: Option<u32>,                 // cgroup v1 的 cpu.shares（2..=262144），v2 换算为 cpu.weight
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_swap_limit_bytes: Option<u64>,    // 仅交换分区的用量上限（不含内存）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pids_max: Option<u64>,
+}
+
+impl CgroupConfig {
+    /// 相对于 cgroup 挂载点的路径，例如 `tenants/app1`
+    pub fn path(&self) -> String {
+        let parent = self.parent.trim_matches('/');
+        if parent.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}/{}", parent, self.name)
+        }
+    }
+}
+
+/// 可设置的 cgroup 资源限制
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CgroupResource {
+    CpuShares,       // v1: cpu/cpu.shares；v2: cpu.weight
+    MemoryLimit,     // v1: memory/memory.limit_in_bytes；v2: memory.max
+    MemorySwapLimit, // v1: memory/memory.memsw.limit_in_bytes（内存 + 交换）；v2: memory.swap.max
+    PidsMax,         // pids.max
+}
+
+/// 系统信任库中的一张 CA 证书
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrustStoreCert {
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: String,   // openssl 输出的原始格式，例如 "Jun  4 11:04:38 2035 GMT"
+    pub fingerprint: String, // SHA256，冒号分隔的大写十六进制
+    pub file_path: String,
+}
+
+/// TCP 连接（`ss -tanp` / `netstat -tanp` 的一行）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TcpConnection {
+    pub local_address: String,
+    pub local_port: u16,          // 监听所有端口（`*`）时为 0
+    pub remote_address: String,
+    pub remote_port: u16,         // 监听中的连接为 0
+    pub state: String,            // 统一为 ss 的状态名：established、listening、time-wait 等
+    pub pid: Option<u32>,         // 无权限查看进程信息时为 None
+    pub program: Option<String>,
+}
+
+/// POSIX ACL 条目的类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AclEntryType {
+    User,
+    Group,
+    Other,
+    Mask,
+}
+
+impl AclEntryType {
+    /// getfacl/setfacl 中使用的关键字
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            AclEntryType::User => "user",
+            AclEntryType::Group => "group",
+            AclEntryType::Other => "other",
+            AclEntryType::Mask => "mask",
+        }
+    }
+}
+
+/// 一条 POSIX ACL 条目，例如 `user:alice:r-x`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AclEntry {
+    #[serde(rename = "type")]
+    pub type_: AclEntryType,
+    pub qualifier: Option<String>, // 用户名/组名；文件属主、属组以及 other、mask 条目为 None
+    pub permissions: String,       // `rwx` 形式，例如 "r-x"；getfacl 中的 `#effective` 注释不计入
+}
+
+/// 文件或目录的 POSIX ACL
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileAcl {
+    pub path: String,
+    pub entries: Vec<AclEntry>,
+    pub default_entries: Vec<AclEntry>, // 目录的默认 ACL（`default:` 前缀），新建文件继承
+}
+
+/// PAM 配置中的一行，例如 `auth required pam_unix.so nullok`
+///
+/// `@include common-auth` 形式的行解析为 `type_` 为 `@include`、`module` 为被包含文件名、`control` 为空。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PamLine {
+    #[serde(rename = "type")]
+    pub type_: String,          // auth、account、password、session（可带 `-` 前缀，表示模块缺失时忽略）
+    pub control: String,        // required、sufficient 等，或 `[success=1 default=ignore]` 形式
+    pub module: String,         // 模块名或路径，例如 pam_unix.so、/lib/security/pam_faillock.so
+    pub arguments: Vec<String>, // `[...]` 形式的参数保持为一项
+}
+
+/// `/etc/pam.d/<service>` 的解析结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PamConfig {
+    pub service: String,
+    pub lines: Vec<PamLine>,
+}
+
+/// 检测到重复主机（同一台机器以不同名称出现）时的处理方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateHostPolicy {
+    Error, // 拒绝添加/加载
+    #[default]
+    Warn,  // 记录警告，保留所有名称
+    Alias, // 只保留规范名称，其余名称作为别名
+}
+
+/// 判断两个主机是否为同一台机器的依据
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateHostKey {
+    #[default]
+    Address,         // `hostname:port`（主机名不区分大小写）
+    ResolvedAddress, // 先将主机名解析为 IP 再比较，可识别 IP 与域名写法；解析失败时按原主机名比较
+}
+
+/// 检测到的一个重复主机
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DuplicateHost {
+    pub name: String,      // 重复的主机名
+    pub canonical: String, // 与之相同的规范主机名（已有同键主机中名称排序在前者）
+    pub key: String,       // 判断重复的依据，例如 "10.0.0.5:22"
+}
+
+/// 内核参数与基线的比较结果（各列表按参数名排序）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct SysctlDiff {
+    pub missing: Vec<String>,                        // 主机上不存在的参数
+    pub wrong_value: Vec<(String, String, String)>, // (参数名, 期望值, 实际值)
+    pub compliant: Vec<String>,                      // 与基线一致的参数
+}
@@ -1,8 +1,11 @@
 use crate::error::AnsibleError;
+use crate::types::{CommandResult, OutputEncoding};
 use md5::Md5;
 use sha2::{Digest as Sha2Digest, Sha256};
 use std::fs::File;
+use std::future::Future;
 use std::io::{BufReader, Read};
+use std::time::Duration;
 
 /// 计算本地文件的 Hash 值 (SHA256 或 MD5)
 pub fn calculate_file_hash(path: &str, algorithm: &str) -> Result<String, AnsibleError> {
@@ -48,12 +51,45 @@ pub fn calculate_file_hash(path: &str, algorithm: &str) -> Result<String, Ansibl
     Ok(hash)
 }
 
+/// [`calculate_file_hash`] 的异步版本，通过 tokio 分块读取文件
+pub async fn calculate_file_hash_async(path: &str, algorithm: &str) -> Result<String, AnsibleError> {
+    match algorithm.to_lowercase().as_str() {
+        "sha256" => hash_file_async::<Sha256>(path).await,
+        "md5" => hash_file_async::<Md5>(path).await,
+        _ => Err(AnsibleError::FileOperationError(format!(
+            "Unsupported hash algorithm: {}",
+            algorithm
+        ))),
+    }
+}
+
+async fn hash_file_async<D: Sha2Digest>(path: &str) -> Result<String, AnsibleError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(path).await.map_err(|e| {
+        AnsibleError::FileOperationError(format!("Failed to open file for hash: {}", e))
+    })?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let count = file.read(&mut buffer).await.map_err(|e| {
+            AnsibleError::FileOperationError(format!("Failed to read file: {}", e))
+        })?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
 /// 生成唯一的临时文件后缀
 /// 
 /// 使用纳秒级时间戳 + 随机数，确保在高并发场景下不会产生文件名冲突。
 /// 
 /// # 示例
 /// ```
+/// # use rs_ansible::utils::generate_temp_suffix;
 /// let suffix = generate_temp_suffix();
 /// let temp_file = format!("/tmp/my_file_{}.tmp", suffix);
 /// ```
@@ -77,6 +113,7 @@ pub fn generate_temp_suffix() -> String {
 /// 
 /// # 示例
 /// ```
+/// # use rs_ansible::utils::generate_local_temp_path;
 /// let temp_path = generate_local_temp_path("rs_ansible_template");
 /// // Unix: "/tmp/rs_ansible_template_1732492800.123456789.987654321.tmp"
 /// // Windows: "C:\Users\Username\AppData\Local\Temp\rs_ansible_template_1732492800.123456789.987654321.tmp"
@@ -107,6 +144,7 @@ pub fn generate_local_temp_path(prefix: &str) -> String {
 /// 
 /// # 示例
 /// ```
+/// # use rs_ansible::utils::generate_remote_temp_path;
 /// let temp_path = generate_remote_temp_path("/etc/nginx/nginx.conf");
 /// // 返回类似: "/etc/nginx/nginx.conf.tmp.1732492800.123456789.987654321"
 /// ```
@@ -114,11 +152,290 @@ pub fn generate_remote_temp_path(base_path: &str) -> String {
     format!("{}.tmp.{}", base_path, generate_temp_suffix())
 }
 
+/// 将字符串转义为单引号包裹的 shell 参数
+///
+/// # 示例
+/// ```
+/// # use rs_ansible::utils::shell_quote;
+/// assert_eq!(shell_quote("it's"), "'it'\\''s'");
+/// ```
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// 计算内存数据的 SHA256（十六进制）
+pub fn sha256_hex(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// 本地临时文件，Drop 时自动删除（除非已调用 `commit`）
+///
+/// 用于批量任务中只准备一次、在所有主机间复用的本地产物（脚本、渲染后的模板），
+/// 即使任务在中途出错返回也不会遗留临时文件。
+#[derive(Debug)]
+pub struct LocalTempFile {
+    path: String,
+    hash: String,
+    committed: bool,
+}
+
+impl LocalTempFile {
+    /// 将内容写入新的本地临时文件
+    pub fn create(prefix: &str, content: &[u8]) -> Result<Self, AnsibleError> {
+        let path = generate_local_temp_path(prefix);
+        std::fs::write(&path, content)
+            .map_err(|e| AnsibleError::FileOperationError(format!("Failed to write temp file: {}", e)))?;
+        Ok(Self {
+            path,
+            hash: sha256_hex(content),
+            committed: false,
+        })
+    }
+
+    /// 保留该文件（Drop 时不再删除），返回其路径
+    pub fn commit(mut self) -> String {
+        self.committed = true;
+        std::mem::take(&mut self.path)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 文件内容的 SHA256，可作为 `FileCopyOptions::precomputed_hash`
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+impl Drop for LocalTempFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// 校验后的文件权限（八进制或 chmod 符号形式）
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileMode {
+    Octal(u32),       // 例如 "644"、"0644"、"4755"
+    Symbolic(String), // 例如 "u+x"、"go-w,a+r"
+}
+
+impl FileMode {
+    /// 解析权限字符串，无法识别时返回 `ValidationError`（而不是静默使用默认值）
+    ///
+    /// # 示例
+    /// ```
+    /// # use rs_ansible::utils::FileMode;
+    /// assert_eq!(FileMode::parse("0644").unwrap(), FileMode::Octal(0o644));
+    /// assert!(FileMode::parse("0o644").is_err());
+    /// ```
+    pub fn parse(mode: &str) -> Result<Self, AnsibleError> {
+        let mode = mode.trim();
+        let invalid = || AnsibleError::ValidationError(format!("Invalid file mode: '{}'", mode));
+
+        if !mode.is_empty() && mode.chars().all(|c| c.is_digit(8)) {
+            return match u32::from_str_radix(mode, 8) {
+                Ok(value) if mode.len() <= 5 && value <= 0o7777 => Ok(FileMode::Octal(value)),
+                _ => Err(invalid()),
+            };
+        }
+
+        if mode.split(',').all(is_symbolic_clause) {
+            Ok(FileMode::Symbolic(mode.to_string()))
+        } else {
+            Err(invalid())
+        }
+    }
+
+    /// 传给 chmod 的参数（八进制统一为 4 位，如 "0644"）
+    pub fn to_chmod_arg(&self) -> String {
+        match self {
+            FileMode::Octal(value) => format!("{:04o}", value),
+            FileMode::Symbolic(mode) => mode.clone(),
+        }
+    }
+
+    /// scp 上传时的初始权限；符号形式需基于现有权限计算，先使用 0644 再由 chmod 调整
+    pub fn initial_mode(&self) -> i32 {
+        match self {
+            FileMode::Octal(value) => (value & 0o777) as i32,
+            FileMode::Symbolic(_) => 0o644,
+        }
+    }
+}
+
+/// 校验单个 chmod 符号子句：`[ugoa]*([-+=]([rwxXst]*|[ugo]))+`
+fn is_symbolic_clause(clause: &str) -> bool {
+    let rest = clause.trim_start_matches(['u', 'g', 'o', 'a']);
+    if rest.is_empty() {
+        return false;
+    }
+
+    let mut chars = rest.chars().peekable();
+    while let Some(op) = chars.next() {
+        if !matches!(op, '+' | '-' | '=') {
+            return false;
+        }
+        // 复制其他类别的权限，例如 g=u
+        if let Some(&who) = chars.peek()
+            && matches!(who, 'u' | 'g' | 'o')
+        {
+            chars.next();
+            continue;
+        }
+        while chars.next_if(|c| matches!(c, 'r' | 'w' | 'x' | 'X' | 's' | 't')).is_some() {}
+    }
+    true
+}
+
+/// 第 `attempt` 次重试前的等待时间（`attempt` 从 1 开始）
+///
+/// 以 `base` 为起点指数增长、不超过 `max`，再按 `jitter`（0.0–1.0）随机缩短，
+/// 避免大量主机同时重连。`jitter` 为 0 时结果是确定的。
+pub fn backoff_delay(attempt: u32, base: Duration, max: Duration, jitter: f64) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(31);
+    let delay = base.saturating_mul(1u32 << exponent).min(max);
+    let jitter = jitter.clamp(0.0, 1.0);
+    if jitter == 0.0 {
+        return delay;
+    }
+    delay.mul_f64(1.0 - jitter * rand::random::<f64>())
+}
+
+/// 按指数退避重试异步操作，最多执行 `attempts` 次
+///
+/// `op` 的参数为当前尝试次数（从 1 开始）。全部失败时返回最后一次的错误。
+///
+/// # 示例
+/// ```
+/// # use rs_ansible::utils::retry_with_backoff;
+/// # use std::time::Duration;
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let (base, max) = (Duration::from_millis(1), Duration::from_millis(5));
+/// let result: Result<u32, String> = retry_with_backoff(3, base, max, 0.2, |attempt| async move {
+///     if attempt < 2 { Err("not yet".to_string()) } else { Ok(attempt) }
+/// })
+/// .await;
+/// assert_eq!(result, Ok(2));
+/// # });
+/// ```
+pub async fn retry_with_backoff<T, E, F, Fut>(
+    attempts: u32,
+    base: Duration,
+    max: Duration,
+    jitter: f64,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let attempts = attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match op(attempt).await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(_) => {
+                tokio::time::sleep(backoff_delay(attempt, base, max, jitter)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// [`retry_with_backoff`] 的同步版本，在当前线程上休眠（用于阻塞的 SSH 连接等场景）
+pub fn retry_with_backoff_blocking<T, E, F>(
+    attempts: u32,
+    base: Duration,
+    max: Duration,
+    jitter: f64,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut(u32) -> Result<T, E>,
+{
+    let attempts = attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match op(attempt) {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(_) => {
+                std::thread::sleep(backoff_delay(attempt, base, max, jitter));
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// 在控制机本地执行 shell 命令
+pub fn run_local_command(command: &str) -> Result<CommandResult, AnsibleError> {
+    #[cfg(target_os = "windows")]
+    let output = std::process::Command::new("cmd").arg("/C").arg(command).output();
+
+    #[cfg(not(target_os = "windows"))]
+    let output = std::process::Command::new("sh").arg("-c").arg(command).output();
+
+    let output = output.map_err(|e| {
+        AnsibleError::CommandExecutionError(format!("Failed to run local command '{}': {}", command, e))
+    })?;
+
+    // 被信号终止时没有退出码
+    CommandResult::from_bytes(
+        output.status.code().unwrap_or(-1),
+        output.stdout,
+        output.stderr,
+        OutputEncoding::Lossy,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::HashSet;
 
+    #[test]
+    fn test_local_temp_file_removed_on_drop() {
+        let file = LocalTempFile::create("rs_ansible_test_artifact", b"echo hi\n").unwrap();
+        let path = file.path().to_string();
+        assert_eq!(std::fs::read(&path).unwrap(), b"echo hi\n");
+        assert_eq!(file.hash(), sha256_hex(b"echo hi\n"));
+        drop(file);
+        assert!(!std::path::Path::new(&path).exists());
+
+        let kept = LocalTempFile::create("rs_ansible_test_artifact", b"keep").unwrap().commit();
+        assert!(std::path::Path::new(&kept).exists());
+        std::fs::remove_file(&kept).unwrap();
+    }
+
+    #[test]
+    fn test_file_mode_parsing() {
+        assert_eq!(FileMode::parse("644").unwrap().to_chmod_arg(), "0644");
+        assert_eq!(FileMode::parse("0755").unwrap().initial_mode(), 0o755);
+        assert_eq!(FileMode::parse("4755").unwrap(), FileMode::Octal(0o4755));
+        assert_eq!(FileMode::parse("4755").unwrap().initial_mode(), 0o755);
+
+        for symbolic in ["u+x", "go-w", "a=r,u+w", "g=u", "u=rwX", "+t"] {
+            let mode = FileMode::parse(symbolic).unwrap();
+            assert_eq!(mode.to_chmod_arg(), symbolic);
+            assert_eq!(mode.initial_mode(), 0o644);
+        }
+
+        for garbage in ["", "0o644", "rwxr-xr-x", "999", "17777", "u+q", "u", "644; rm -rf /", "u+x,"] {
+            assert!(
+                matches!(FileMode::parse(garbage), Err(AnsibleError::ValidationError(_))),
+                "'{}' should be rejected",
+                garbage
+            );
+        }
+    }
+
     #[test]
     fn test_temp_suffix_uniqueness() {
         // 测试生成的后缀是否唯一
@@ -154,6 +471,15 @@ mod tests {
         }
     }
 
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_run_local_command() {
+        let result = run_local_command("echo hello && echo oops >&2 && exit 3").unwrap();
+        assert_eq!(result.exit_code, 3);
+        assert_eq!(result.stdout.trim(), "hello");
+        assert_eq!(result.stderr.trim(), "oops");
+    }
+
     #[test]
     fn test_remote_temp_path_format() {
         let base = "/etc/config.conf";
@@ -162,4 +488,53 @@ mod tests {
         assert!(path.starts_with("/etc/config.conf.tmp."));
         assert!(!path.contains("\\"));  // 不应该包含 Windows 路径分隔符
     }
+
+    #[test]
+    fn test_backoff_delay_grows_and_caps() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_secs(1);
+        let delays: Vec<Duration> = (1..=6).map(|n| backoff_delay(n, base, max, 0.0)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis).to_vec()
+        );
+        assert_eq!(backoff_delay(u32::MAX, base, max, 0.0), max);
+
+        for _ in 0..100 {
+            let delay = backoff_delay(3, base, max, 0.5);
+            assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(400));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff() {
+        let tiny = Duration::from_millis(1);
+        let mut calls = Vec::new();
+        let result: Result<(), String> = retry_with_backoff(3, tiny, tiny, 0.5, |attempt| {
+            calls.push(attempt);
+            async move { Err(format!("failure {}", attempt)) }
+        })
+        .await;
+        assert_eq!(result, Err("failure 3".to_string()));
+        assert_eq!(calls, vec![1, 2, 3]);
+
+        let result: Result<u32, String> = retry_with_backoff_blocking(0, tiny, tiny, 0.0, Ok);
+        assert_eq!(result, Ok(1));
+    }
+
+    #[test]
+    fn test_non_utf8_command_output() {
+        let result = run_local_command("printf 'caf\\351\\n'; printf '\\377' >&2").unwrap();
+        assert_eq!(result.stdout, "caf\u{FFFD}\n");
+        assert_eq!(result.stdout_bytes(), b"caf\xe9\n");
+        assert_eq!(result.stderr, "\u{FFFD}");
+
+        let ascii = CommandResult::from_bytes(0, b"ok\n".to_vec(), Vec::new(), OutputEncoding::Strict).unwrap();
+        assert!(ascii.raw_stdout.is_none());
+        assert_eq!(ascii.stdout_bytes(), b"ok\n");
+        assert!(matches!(
+            CommandResult::from_bytes(0, vec![0xff], Vec::new(), OutputEncoding::Strict),
+            Err(AnsibleError::CommandExecutionError(_))
+        ));
+    }
 }
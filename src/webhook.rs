@@ -0,0 +1,281 @@
+use crate::callback::ExecutionCallback;
+use crate::error::AnsibleError;
+use crate::executor::PlaybookResult;
+use crate::utils::retry_with_backoff_blocking;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tera::{Context, Tera};
+use tracing::{info, warn};
+
+/// 何时发送 webhook 通知
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotifyOn {
+    /// 每次运行结束都发送
+    #[default]
+    Always,
+    /// 仅在运行失败时发送
+    Failure,
+}
+
+/// playbook 结束时向 webhook 发送执行摘要（需要启用 `http` feature）
+///
+/// 默认发送摘要 JSON，其中的 `text` 字段可直接用于 Slack incoming webhook；
+/// 设置模板后改为发送 Tera 模板的渲染结果，模板可使用的变量与摘要 JSON 的字段相同：
+/// `playbook`、`success`、`status`（success/failure）、`text`、`tasks_run`、
+/// `failed_hosts`、`skipped_hosts`、`unreachable_hosts`、`duration_secs`。
+///
+/// 发送失败会重试一次，仍失败时只记录日志，不影响执行结果。
+pub struct WebhookCallback {
+    url: String,
+    bearer_token: Option<String>,
+    template: Option<String>,
+    notify_on: NotifyOn,
+    timeout: Duration,
+}
+
+impl WebhookCallback {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            bearer_token: None,
+            template: None,
+            notify_on: NotifyOn::default(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// 以 `Authorization: Bearer <token>` 认证
+    pub fn bearer_token(mut self, token: &str) -> Self {
+        self.bearer_token = Some(token.to_string());
+        self
+    }
+
+    /// 使用 Tera 模板生成请求体
+    pub fn payload_template(mut self, template: &str) -> Self {
+        self.template = Some(template.to_string());
+        self
+    }
+
+    pub fn notify_on(mut self, notify_on: NotifyOn) -> Self {
+        self.notify_on = notify_on;
+        self
+    }
+
+    /// 单次请求的超时时间（默认 10 秒）
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 生成请求体：未设置模板时为摘要 JSON
+    fn render_payload(&self, summary: &Value) -> Result<String, AnsibleError> {
+        let Some(ref template) = self.template else {
+            return Ok(summary.to_string());
+        };
+        let context = Context::from_value(summary.clone())
+            .map_err(|e| AnsibleError::TemplateError(format!("Failed to build webhook context: {}", e)))?;
+        Tera::one_off(template, &context, false)
+            .map_err(|e| AnsibleError::TemplateError(format!("Failed to render webhook payload: {}", e)))
+    }
+
+    /// 发送请求，失败时重试一次
+    fn deliver(&self, payload: String) -> Result<(), AnsibleError> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .map_err(|e| AnsibleError::IoError(format!("Failed to create HTTP client: {}", e)))?;
+        let content_type = if serde_json::from_str::<Value>(&payload).is_ok() {
+            "application/json"
+        } else {
+            "text/plain; charset=utf-8"
+        };
+
+        retry_with_backoff_blocking(2, Duration::from_millis(500), Duration::from_millis(500), 0.0, |attempt| {
+            let mut request = client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(payload.clone());
+            if let Some(ref token) = self.bearer_token {
+                request = request.bearer_auth(token);
+            }
+            let outcome = request
+                .send()
+                .map_err(|e| AnsibleError::IoError(format!("Webhook request failed: {}", e)))
+                .and_then(|response| match response.status() {
+                    status if status.is_success() => Ok(()),
+                    status => Err(AnsibleError::IoError(format!("Webhook returned HTTP {}", status))),
+                });
+            if let Err(ref e) = outcome {
+                warn!("Webhook delivery attempt {} to {} failed: {}", attempt, self.url, e);
+            }
+            outcome
+        })
+    }
+}
+
+/// webhook 使用的执行摘要
+fn summary(result: &PlaybookResult) -> Value {
+    let mut failed_hosts: Vec<&String> = result.failed_hosts.iter().collect();
+    let mut skipped_hosts: Vec<&String> = result.skipped_hosts.iter().collect();
+    let mut unreachable_hosts: Vec<&String> = result.unreachable_hosts.iter().collect();
+    failed_hosts.sort();
+    skipped_hosts.sort();
+    unreachable_hosts.sort();
+    let duration_secs = match (result.task_timings.first(), result.task_timings.last()) {
+        (Some(first), Some(last)) => (last.finished_at - first.started_at).num_milliseconds() as f64 / 1000.0,
+        _ => 0.0,
+    };
+
+    let status = if result.overall_success { "success" } else { "failure" };
+    let mut text = format!(
+        "Playbook '{}' {} ({} task(s) run)",
+        result.playbook_name,
+        if result.overall_success { "succeeded" } else { "failed" },
+        result.task_results.len()
+    );
+    if !failed_hosts.is_empty() {
+        let hosts: Vec<&str> = failed_hosts.iter().map(|h| h.as_str()).collect();
+        text.push_str(&format!(", failed hosts: {}", hosts.join(", ")));
+    }
+
+    json!({
+        "playbook": result.playbook_name,
+        "success": result.overall_success,
+        "status": status,
+        "text": text,
+        "tasks_run": result.task_results.len(),
+        "failed_hosts": failed_hosts,
+        "skipped_hosts": skipped_hosts,
+        "unreachable_hosts": unreachable_hosts,
+        "duration_secs": duration_secs,
+    })
+}
+
+impl ExecutionCallback for WebhookCallback {
+    fn on_playbook_end(&self, result: &PlaybookResult) -> Result<(), AnsibleError> {
+        if self.notify_on == NotifyOn::Failure && result.overall_success {
+            return Ok(());
+        }
+
+        let payload = match self.render_payload(&summary(result)) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Webhook notification for '{}' not sent: {}", result.playbook_name, e);
+                return Ok(());
+            }
+        };
+
+        // 阻塞的 HTTP 客户端不能在异步运行时线程上使用，放到独立线程中发送并等待完成
+        let delivered = std::thread::scope(|scope| {
+            scope
+                .spawn(|| self.deliver(payload))
+                .join()
+                .unwrap_or_else(|_| Err(AnsibleError::IoError("Webhook delivery thread panicked".to_string())))
+        });
+        match delivered {
+            Ok(()) => info!("Webhook notification for '{}' sent to {}", result.playbook_name, self.url),
+            Err(e) => warn!("Webhook notification for '{}' failed: {}", result.playbook_name, e),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// 收到的请求：(Authorization 头, 请求体)
+    type Request = (Option<String>, String);
+
+    #[derive(Clone, Default)]
+    struct Received {
+        requests: Arc<Mutex<Vec<Request>>>,
+        flaky_calls: Arc<AtomicUsize>,
+    }
+
+    async fn record(State(received): State<Received>, headers: HeaderMap, body: String) -> StatusCode {
+        let auth = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        received.requests.lock().unwrap().push((auth, body));
+        StatusCode::OK
+    }
+
+    /// 第一次请求返回 500，之后正常
+    async fn flaky(State(received): State<Received>, headers: HeaderMap, body: String) -> StatusCode {
+        if received.flaky_calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+        record(State(received), headers, body).await
+    }
+
+    fn playbook_result(success: bool) -> PlaybookResult {
+        PlaybookResult {
+            playbook_name: "deploy".to_string(),
+            task_results: Vec::new(),
+            overall_success: success,
+            failed_hosts: if success { HashSet::new() } else { HashSet::from(["web2".to_string()]) },
+            skipped_hosts: HashSet::new(),
+            unreachable_hosts: HashSet::new(),
+            task_timings: Vec::new(),
+            limit: None,
+            skips: Default::default(),
+            resume_skipped_tasks: Vec::new(),
+            step_skipped_tasks: Vec::new(),
+            aborted_at: None,
+            diffs: Vec::new(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_webhook_payloads() {
+        let received = Received::default();
+        let app = Router::new()
+            .route("/hook", post(record))
+            .route("/flaky", post(flaky))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let hook = WebhookCallback::new(&format!("{}/hook", base)).bearer_token("s3cret");
+        hook.on_playbook_end(&playbook_result(true)).unwrap();
+        hook.on_playbook_end(&playbook_result(false)).unwrap();
+
+        let templated = WebhookCallback::new(&format!("{}/flaky", base))
+            .notify_on(NotifyOn::Failure)
+            .payload_template("{{ playbook }}: {{ status }} on {{ failed_hosts | join(sep=\",\") }}");
+        templated.on_playbook_end(&playbook_result(true)).unwrap();
+        templated.on_playbook_end(&playbook_result(false)).unwrap();
+
+        // 无法连接时只记录日志
+        let unreachable = WebhookCallback::new("http://127.0.0.1:1/hook").timeout(Duration::from_millis(200));
+        assert!(unreachable.on_playbook_end(&playbook_result(false)).is_ok());
+
+        let requests = received.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3);
+
+        let (auth, body) = &requests[0];
+        assert_eq!(auth.as_deref(), Some("Bearer s3cret"));
+        let success: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(success["status"], "success");
+        assert_eq!(success["text"], "Playbook 'deploy' succeeded (0 task(s) run)");
+
+        let failure: Value = serde_json::from_str(&requests[1].1).unwrap();
+        assert_eq!(failure["success"], false);
+        assert_eq!(failure["failed_hosts"], json!(["web2"]));
+        assert!(failure["text"].as_str().unwrap().ends_with("failed hosts: web2"));
+
+        // 仅失败时通知，第一次 500 后重试成功
+        assert_eq!(requests[2], (None, "deploy: failure on web2".to_string()));
+        assert_eq!(received.flaky_calls.load(Ordering::SeqCst), 2);
+    }
+}